@@ -24,12 +24,103 @@ const INPUT: &str = r#"
 </html>
 "#;
 
+/// Generates a document with `items` list items, with the target element (`#target`) at the end
+fn fixture(items: usize) -> String {
+    let mut html = String::from("<!doctype html><html><head><title>Fixture</title></head><body>");
+
+    html.push_str(r#"<div id="content"><ul class="list">"#);
+    for i in 0..items {
+        html.push_str(&format!(
+            r#"<li class="item item-{i}" data-index="{i}"><a href="/items/{i}">Item <b>{i}</b></a></li>"#
+        ));
+    }
+    html.push_str("</ul>");
+    html.push_str(r#"<p id="target" class="footer">The end</p></div></body></html>"#);
+
+    html
+}
+
 pub fn criterion_benchmark(cr: &mut Criterion) {
     cr.bench_function("tl", |b| {
         b.iter(|| {
             let _ = tl::parse(black_box(INPUT), tl::ParserOptions::default());
         });
     });
+
+    let medium = fixture(100);
+    let large = fixture(10_000);
+
+    let mut parse = cr.benchmark_group("parse");
+    for (name, input) in [("small", INPUT), ("medium", &medium), ("large", &large)] {
+        parse.throughput(Throughput::Bytes(input.len() as u64));
+        parse.bench_function(name, |b| {
+            b.iter(|| tl::parse(black_box(input), tl::ParserOptions::default()).unwrap());
+        });
+    }
+    parse.finish();
+
+    let untracked = tl::parse(&large, tl::ParserOptions::default()).unwrap();
+    let tracked = tl::parse(
+        &large,
+        tl::ParserOptions::default().track_ids().track_classes(),
+    )
+    .unwrap();
+
+    let mut lookup = cr.benchmark_group("get_element_by_id");
+    lookup.bench_function("untracked", |b| {
+        b.iter(|| untracked.get_element_by_id(black_box("target")).unwrap());
+    });
+    lookup.bench_function("tracked", |b| {
+        b.iter(|| tracked.get_element_by_id(black_box("target")).unwrap());
+    });
+    lookup.finish();
+
+    let mut selectors = cr.benchmark_group("query_selector");
+    for (name, selector) in [
+        ("class", ".item-5000"),
+        ("descendant", "ul.list li a"),
+        ("id_rooted", "#content > p"),
+        ("class_subject", "#content li.item-5000"),
+        ("class_rooted", ".item-5000 a"),
+    ] {
+        selectors.bench_function(format!("{name}_untracked"), |b| {
+            b.iter(|| {
+                untracked
+                    .query_selector(black_box(selector))
+                    .unwrap()
+                    .count()
+            });
+        });
+        selectors.bench_function(format!("{name}_tracked"), |b| {
+            b.iter(|| tracked.query_selector(black_box(selector)).unwrap().count());
+        });
+    }
+    selectors.finish();
+
+    let tag_names = tl::parse(&large, tl::ParserOptions::default().track_tag_names()).unwrap();
+
+    let mut by_tag_name = cr.benchmark_group("get_elements_by_tag_name");
+    by_tag_name.bench_function("query_selector", |b| {
+        b.iter(|| untracked.query_selector(black_box("a")).unwrap().count());
+    });
+    by_tag_name.bench_function("untracked", |b| {
+        b.iter(|| untracked.get_elements_by_tag_name(black_box("a")).count());
+    });
+    by_tag_name.bench_function("tracked", |b| {
+        b.iter(|| tag_names.get_elements_by_tag_name(black_box("a")).count());
+    });
+    by_tag_name.finish();
+
+    let body = untracked
+        .query_selector("body")
+        .and_then(|mut iter| iter.next())
+        .unwrap()
+        .get(untracked.parser())
+        .unwrap();
+
+    cr.bench_function("inner_text_body", |b| {
+        b.iter(|| body.inner_text(untracked.parser()).len());
+    });
 }
 
 criterion_group!(benches, criterion_benchmark);
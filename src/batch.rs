@@ -0,0 +1,122 @@
+use crate::parser::Scratch;
+use crate::queryselector::{self, OwnedSelector, Selector};
+use crate::{ParseError, Parser, ParserOptions, VDom};
+use std::collections::HashMap;
+
+/// Parses many documents with the same options, reusing allocations between them
+///
+/// The scratch buffers that the parser only needs while parsing (such as the stack of open elements) are kept
+/// for the next document, and the node table of each document is preallocated based on the number of nodes
+/// per byte seen so far. The returned documents are independent of each other and of the batch:
+/// each one only borrows its own input, so they can be kept or dropped in any order.
+/// The names in the tag name lookup tables (see `ParserOptions::track_tag_names()`) are interned,
+/// so that all documents share one allocation per name, and query selectors can be compiled once for all documents
+/// with `selector()`. The lookups themselves and the query cache (see `VDom::enable_query_cache()`)
+/// refer to the nodes of a single document, so they are not shared.
+///
+/// # Example
+/// ```
+/// let mut batch = tl::Batch::new(tl::ParserOptions::default().track_ids());
+///
+/// let first = batch.parse("<p id=a>Hello</p>").unwrap();
+/// let second = batch.parse("<p id=b>World</p>").unwrap();
+/// assert!(first.get_element_by_id("a").is_some());
+/// assert!(second.get_element_by_id("a").is_none());
+///
+/// let selector = batch.selector("p#b").unwrap();
+/// assert_eq!(first.query_selector_with(selector).count(), 0);
+/// assert_eq!(second.query_selector_with(selector).count(), 1);
+///
+/// assert_eq!(batch.documents(), 2);
+/// assert_eq!(batch.total_nodes(), 4);
+/// ```
+#[derive(Debug)]
+pub struct Batch {
+    options: ParserOptions,
+    scratch: Scratch,
+    selectors: HashMap<Box<str>, OwnedSelector>,
+    documents: usize,
+    total_nodes: usize,
+    total_bytes: usize,
+}
+
+impl Batch {
+    /// Creates a batch that parses documents with the given options
+    pub fn new(options: ParserOptions) -> Self {
+        Self {
+            options,
+            scratch: Scratch::default(),
+            selectors: HashMap::new(),
+            documents: 0,
+            total_nodes: 0,
+            total_bytes: 0,
+        }
+    }
+
+    /// Returns the options that documents are parsed with
+    pub fn options(&self) -> &ParserOptions {
+        &self.options
+    }
+
+    /// Parses the given input string, like `tl::parse()`
+    ///
+    /// Documents that fail to parse are not counted.
+    ///
+    /// # Errors
+    /// See `tl::parse()`
+    pub fn parse<'a>(&mut self, input: &'a str) -> Result<VDom<'a>, ParseError> {
+        let mut parser = Parser::new(input, self.options.clone());
+        parser.use_scratch(std::mem::take(&mut self.scratch));
+
+        // preallocate the node table for the average number of nodes per byte so far
+        if self.total_bytes > 0 {
+            let nodes_per_byte = self.total_nodes as f64 / self.total_bytes as f64;
+            parser
+                .tags
+                .reserve((input.len() as f64 * nodes_per_byte) as usize);
+        }
+
+        let result = parser.parse();
+        self.scratch = parser.take_scratch();
+        result?;
+
+        self.documents += 1;
+        self.total_nodes += parser.tags.len();
+        self.total_bytes += input.len();
+
+        Ok(VDom::from(parser))
+    }
+
+    /// Returns the given query selector compiled with `Selector::parse()`, which is only parsed the first time
+    ///
+    /// The selector can be used with any document by calling `VDom::query_selector_with()`.
+    ///
+    /// # Errors
+    /// Returns an error if the selector is invalid. Invalid selectors are not cached.
+    pub fn selector(
+        &mut self,
+        selector: &str,
+    ) -> Result<&OwnedSelector, queryselector::ParseError> {
+        if !self.selectors.contains_key(selector) {
+            self.selectors
+                .insert(selector.into(), Selector::parse(selector)?);
+        }
+
+        Ok(&self.selectors[selector])
+    }
+
+    /// Returns the number of documents that were parsed successfully
+    pub fn documents(&self) -> usize {
+        self.documents
+    }
+
+    /// Returns the total number of nodes of all documents that were parsed successfully, at the time they were parsed
+    pub fn total_nodes(&self) -> usize {
+        self.total_nodes
+    }
+
+    /// Returns the total length in bytes of the inputs of all documents that were parsed successfully
+    pub fn total_bytes(&self) -> usize {
+        self.total_bytes
+    }
+}
@@ -1,12 +1,12 @@
 use core::{fmt, fmt::Debug};
 use std::{
-    borrow::Cow,
+    borrow::{Borrow, Cow},
     hash::{Hash, Hasher},
     marker::PhantomData,
     mem::ManuallyDrop,
 };
 
-use crate::errors::SetBytesError;
+use crate::{entities, errors::SetBytesError, simd, util};
 
 /// A storage type for raw bytes, used by the parser
 #[derive(Eq, PartialOrd, Ord)]
@@ -25,12 +25,23 @@ pub struct Bytes<'a> {
 /// which is the same as if this was just `struct Bytes<'a>(&'a [u8])`
 #[derive(PartialEq, Eq, PartialOrd, Ord)]
 enum BytesInner {
-    /// Borrowed bytes
-    Borrowed(*const u8, u32),
-    /// Owned bytes
+    /// Borrowed bytes and their [`flags`]
+    Borrowed(*const u8, u32, u8),
+    /// Owned bytes and their [`flags`]
     ///
     /// This pointer is managed and will be freed when dropped
-    Owned(*mut u8, u32),
+    Owned(*mut u8, u32, u8),
+}
+
+/// Flags that the parser sets on the bytes it creates
+mod flags {
+    /// The bytes are known to contain no character references (no `&`)
+    ///
+    /// The parser sets this while scanning text, so that decoding can be skipped for most text nodes.
+    /// This is only tracked for borrowed bytes.
+    pub const PLAIN: u8 = 1 << 0;
+    /// The bytes were cut off at the maximum length configured in the parser options
+    pub const TRUNCATED: u8 = 1 << 1;
 }
 
 impl<'a> PartialEq<str> for Bytes<'a> {
@@ -65,6 +76,14 @@ impl<'a> Hash for Bytes<'a> {
     }
 }
 
+impl<'a> Borrow<[u8]> for Bytes<'a> {
+    #[inline]
+    fn borrow(&self) -> &[u8] {
+        // consistent with the Hash and Eq implementations, which only consider the bytes
+        self.as_bytes()
+    }
+}
+
 impl<'a> Clone for Bytes<'a> {
     fn clone(&self) -> Self {
         // It is important to manually implement Clone for Bytes,
@@ -73,13 +92,14 @@ impl<'a> Clone for Bytes<'a> {
         // which leads to aliasing boxes, and later, when `Bytes` is dropped,
         // the box is freed twice!
         match &self.data {
-            BytesInner::Borrowed(data, len) => {
-                Bytes::from(unsafe { compact_bytes_to_slice(*data, *len) })
-            }
-            BytesInner::Owned(data, len) => {
+            &BytesInner::Borrowed(data, len, flags) => Bytes {
+                data: BytesInner::Borrowed(data, len, flags),
+                _lt: PhantomData,
+            },
+            BytesInner::Owned(data, len, flags) => {
                 let (ptr, len) = unsafe { clone_compact_bytes_parts(*data, *len) };
                 Bytes {
-                    data: BytesInner::Owned(ptr, len),
+                    data: BytesInner::Owned(ptr, len, *flags),
                     _lt: PhantomData,
                 }
             }
@@ -98,7 +118,7 @@ impl<'a> From<&'a [u8]> for Bytes<'a> {
     #[inline]
     fn from(s: &'a [u8]) -> Self {
         Bytes {
-            data: BytesInner::Borrowed(s.as_ptr(), s.len() as u32),
+            data: BytesInner::Borrowed(s.as_ptr(), s.len() as u32, 0),
             _lt: PhantomData,
         }
     }
@@ -115,6 +135,15 @@ impl TryFrom<String> for Bytes<'static> {
     }
 }
 
+/// Sets or clears the given flag
+#[inline]
+fn set_flag(flags: &mut u8, flag: u8, enabled: bool) {
+    match enabled {
+        true => *flags |= flag,
+        false => *flags &= !flag,
+    }
+}
+
 /// Converts `Bytes` raw parts to a slice
 #[inline]
 unsafe fn compact_bytes_to_slice<'a>(ptr: *const u8, l: u32) -> &'a [u8] {
@@ -148,12 +177,19 @@ impl<'a> Debug for Bytes<'a> {
     }
 }
 
+impl<'a> Default for Bytes<'a> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<'a> Bytes<'a> {
     /// Creates an empty `Bytes`
     #[inline]
     pub fn new() -> Self {
         Self {
-            data: BytesInner::Borrowed("".as_bytes().as_ptr(), 0),
+            data: BytesInner::Borrowed("".as_bytes().as_ptr(), 0, flags::PLAIN),
             _lt: PhantomData,
         }
     }
@@ -175,8 +211,8 @@ impl<'a> Bytes<'a> {
     #[inline]
     pub fn as_bytes(&self) -> &[u8] {
         match &self.data {
-            BytesInner::Borrowed(b, l) => unsafe { compact_bytes_to_slice(*b, *l) },
-            BytesInner::Owned(o, l) => unsafe { compact_bytes_to_slice(*o, *l) },
+            BytesInner::Borrowed(b, l, _) => unsafe { compact_bytes_to_slice(*b, *l) },
+            BytesInner::Owned(o, l, _) => unsafe { compact_bytes_to_slice(*o, *l) },
         }
     }
 
@@ -187,17 +223,222 @@ impl<'a> Bytes<'a> {
     #[inline]
     pub fn as_bytes_borrowed(&self) -> Option<&'a [u8]> {
         match &self.data {
-            BytesInner::Borrowed(b, l) => Some(unsafe { compact_bytes_to_slice(*b, *l) }),
+            BytesInner::Borrowed(b, l, _) => Some(unsafe { compact_bytes_to_slice(*b, *l) }),
             _ => None,
         }
     }
 
+    /// Returns the given subrange of these bytes
+    ///
+    /// Borrowed bytes stay borrowed, while owned bytes are copied.
+    ///
+    /// # Panics
+    /// Panics if the range is out of bounds.
+    pub(crate) fn slice(&self, range: std::ops::Range<usize>) -> Bytes<'a> {
+        match self.as_bytes_borrowed() {
+            Some(bytes) => bytes[range].into(),
+            None => {
+                let mut bytes = Bytes::new();
+                // SAFETY: the length of a subrange cannot be greater than the length of these bytes, which is a u32
+                unsafe { bytes.set_unchecked(&self.as_bytes()[range]) };
+                bytes
+            }
+        }
+    }
+
+    /// Copies these bytes so that they do not borrow from the input they were parsed from
+    ///
+    /// Bytes that borrow from `from` keep borrowing, but from the same offset in `to`, which must be a copy of `from`.
+    /// All other bytes are copied into an owned buffer.
+    pub(crate) fn rebase<'b>(&self, from: &[u8], to: &'b [u8]) -> Bytes<'b> {
+        let range = from.as_ptr_range();
+
+        match self.data {
+            BytesInner::Borrowed(ptr, len, flags)
+                if range.contains(&ptr) || (ptr == range.end && len == 0) =>
+            {
+                let offset = ptr as usize - range.start as usize;
+                let slice = &to[offset..offset + len as usize];
+
+                Bytes {
+                    data: BytesInner::Borrowed(slice.as_ptr(), len, flags),
+                    _lt: PhantomData,
+                }
+            }
+            _ => {
+                let mut bytes = Bytes::new();
+                // SAFETY: the length of these bytes already fits in a u32
+                unsafe { bytes.set_unchecked(self.as_bytes()) };
+                bytes.with_truncated(self.is_truncated())
+            }
+        }
+    }
+
+    /// Sets whether these bytes are known to contain no character references (only tracked for borrowed bytes)
+    ///
+    /// `plain` must only be `true` if the bytes contain no `&`.
+    #[inline]
+    pub(crate) fn with_plain_text(mut self, plain: bool) -> Self {
+        if let BytesInner::Borrowed(_, _, flags) = &mut self.data {
+            set_flag(flags, flags::PLAIN, plain);
+        }
+        self
+    }
+
+    /// Sets whether these bytes were cut off at the maximum length configured in the parser options
+    #[inline]
+    pub(crate) fn with_truncated(mut self, truncated: bool) -> Self {
+        match &mut self.data {
+            BytesInner::Borrowed(_, _, flags) | BytesInner::Owned(_, _, flags) => {
+                set_flag(flags, flags::TRUNCATED, truncated)
+            }
+        }
+        self
+    }
+
+    /// Checks whether these bytes were cut off by the parser
+    ///
+    /// This is only ever `true` if a maximum length was configured with `ParserOptions::max_stored_text_len()`
+    /// or `ParserOptions::max_stored_attribute_len()`. Setting new data resets it.
+    #[inline]
+    pub fn is_truncated(&self) -> bool {
+        match &self.data {
+            BytesInner::Borrowed(_, _, flags) | BytesInner::Owned(_, _, flags) => {
+                flags & flags::TRUNCATED != 0
+            }
+        }
+    }
+
+    /// Checks whether these bytes may contain character references that need to be decoded
+    ///
+    /// This is only `false` if the parser has already checked that there are none.
+    #[inline]
+    pub(crate) fn may_contain_char_refs(&self) -> bool {
+        #[cfg(test)]
+        if !char_ref_fast_path::is_enabled() {
+            return true;
+        }
+
+        !matches!(self.data, BytesInner::Borrowed(_, _, flags) if flags & flags::PLAIN != 0)
+    }
+
+    /// Decodes the character references (e.g. `&amp;` or `&#x27;`) in these bytes, and lossily converts them to UTF-8
+    ///
+    /// All numeric references are decoded, but only a subset of the named references of the HTML specification:
+    /// the markup-significant ones, the Latin-1 range (e.g. `&nbsp;` or `&eacute;`) and common punctuation and symbols.
+    /// Unknown references are left as they are. No memory is allocated if there is nothing to decode.
+    ///
+    /// # Example
+    /// ```
+    /// let bytes = tl::Bytes::from("Fish &amp; Chips &#8364;5");
+    /// assert_eq!(bytes.as_decoded_str(), "Fish & Chips €5");
+    /// ```
+    pub fn as_decoded_str(&self) -> Cow<'_, str> {
+        let text = self.as_utf8_str();
+
+        if !self.may_contain_char_refs() {
+            return text;
+        }
+
+        match text {
+            Cow::Borrowed(text) => entities::decode(text),
+            Cow::Owned(text) => Cow::Owned(entities::decode(&text).into_owned()),
+        }
+    }
+
+    /// Returns these bytes with character references decoded if `decode` is `true`, or as they are otherwise
+    ///
+    /// This is used to compare ids and class names. No memory is allocated if there is nothing to decode.
+    pub(crate) fn decoded_if(&self, decode: bool) -> Cow<'_, [u8]> {
+        if decode && self.may_contain_char_refs() && self.as_bytes().contains(&b'&') {
+            Cow::Owned(self.as_decoded_str().into_owned().into_bytes())
+        } else {
+            Cow::Borrowed(self.as_bytes())
+        }
+    }
+
+    /// Checks whether these bytes are equal to `other` after removing leading and trailing ASCII whitespace
+    ///
+    /// Only these bytes are trimmed, `other` is compared as is.
+    ///
+    /// # Example
+    /// ```
+    /// let bytes = tl::Bytes::from("\n\t submit ");
+    /// assert!(bytes.eq_trimmed(b"submit"));
+    /// assert!(!bytes.eq_trimmed(b"Submit"));
+    /// ```
+    pub fn eq_trimmed(&self, other: &[u8]) -> bool {
+        util::trim_ascii_whitespace_bytes(self.as_bytes()) == other
+    }
+
+    /// Checks whether these bytes are equal to `other` after removing leading and trailing ASCII whitespace,
+    /// ignoring the case of ASCII letters
+    ///
+    /// Like the `i` flag of attribute selectors, only ASCII letters are folded, so non-ASCII characters must match exactly.
+    ///
+    /// # Example
+    /// ```
+    /// let bytes = tl::Bytes::from(" Submit\n");
+    /// assert!(bytes.eq_trimmed_ignore_case(b"SUBMIT"));
+    /// ```
+    pub fn eq_trimmed_ignore_case(&self, other: &[u8]) -> bool {
+        simd::eq_case_insensitive(util::trim_ascii_whitespace_bytes(self.as_bytes()), other)
+    }
+
+    /// Checks whether these bytes, as a list of tokens separated by ASCII whitespace (like `class` or `rel`),
+    /// contain `token`, ignoring the case of ASCII letters
+    ///
+    /// An empty token is never contained.
+    ///
+    /// # Example
+    /// ```
+    /// let bytes = tl::Bytes::from("noopener\tNoFollow");
+    /// assert!(bytes.contains_token_ignore_case(b"nofollow"));
+    /// assert!(!bytes.contains_token_ignore_case(b"follow"));
+    /// ```
+    pub fn contains_token_ignore_case(&self, token: &[u8]) -> bool {
+        util::split_ascii_whitespace_bytes(self.as_bytes())
+            .any(|x| simd::eq_case_insensitive(x, token))
+    }
+
+    /// Lossily converts these bytes to UTF-8 and normalizes them as a URL, see `util::normalize_url()`
+    pub(crate) fn as_url_str(&self) -> Cow<'_, str> {
+        match self.as_utf8_str() {
+            Cow::Borrowed(s) => util::normalize_url(s),
+            Cow::Owned(s) => Cow::Owned(util::normalize_url(&s).into_owned()),
+        }
+    }
+
+    /// Parses these bytes as a URL, resolving it against the given base URL if it is relative
+    ///
+    /// Character references are decoded, and the value is stripped like in `Attributes::get_url()`. Parsing lowercases the scheme and the host,
+    /// removes default ports and resolves `.` and `..` path segments, and the hexadecimal digits of percent-encoded bytes
+    /// are uppercased as well, so URLs that only differ in these ways compare equal. Only available with the `url` feature.
+    ///
+    /// Returns `None` if the bytes are not a valid URL, or if they are relative and there is no base URL.
+    ///
+    /// # Example
+    /// ```
+    /// let base = url::Url::parse("https://example.com/docs/").unwrap();
+    ///
+    /// let a = tl::Bytes::from(" ../A%2fB?x=1&amp;y=2 ").as_normalized_url(Some(&base)).unwrap();
+    /// let b = tl::Bytes::from("HTTPS://EXAMPLE.COM:443/A%2FB?x=1&y=2").as_normalized_url(None).unwrap();
+    /// assert_eq!(a, b);
+    /// assert_eq!(a.as_str(), "https://example.com/A%2FB?x=1&y=2");
+    ///
+    /// assert!(tl::Bytes::from("/relative").as_normalized_url(None).is_none());
+    /// ```
+    #[cfg(feature = "url")]
+    pub fn as_normalized_url(&self, base: Option<&url::Url>) -> Option<url::Url> {
+        crate::link_set::parse(&self.as_decoded_str(), base)
+    }
+
     /// Returns a read-only raw pointer to the inner data
     #[inline]
     pub fn as_ptr(&self) -> *const u8 {
         match &self.data {
-            BytesInner::Borrowed(b, _) => *b,
-            BytesInner::Owned(o, _) => *o,
+            BytesInner::Borrowed(b, _, _) => *b,
+            BytesInner::Owned(o, _, _) => *o,
         }
     }
 
@@ -225,15 +466,15 @@ impl<'a> Bytes<'a> {
 
         let (ptr, len) = boxed_slice_into_compact_parts(data);
 
-        let bytes = BytesInner::Owned(ptr, len);
+        let bytes = BytesInner::Owned(ptr, len, 0);
         let old = std::mem::replace(&mut self.data, bytes);
 
         // we cannot let Drop code run because that would deallocate `old`
         let old = ManuallyDrop::new(old);
 
         match &*old {
-            BytesInner::Borrowed(_, _) => None,
-            BytesInner::Owned(ptr, len) => {
+            BytesInner::Borrowed(..) => None,
+            BytesInner::Owned(ptr, len, _) => {
                 let len = *len as usize;
                 Some(Vec::from_raw_parts(*ptr, len, len).into_boxed_slice())
             }
@@ -288,7 +529,7 @@ impl Drop for BytesInner {
     fn drop(&mut self) {
         // we only need to deallocate if we own the data
         // if we don't, just do nothing
-        if let BytesInner::Owned(ptr, len) = self {
+        if let BytesInner::Owned(ptr, len, _) = self {
             let ptr = *ptr;
             let len = *len as usize;
 
@@ -298,3 +539,21 @@ impl Drop for BytesInner {
         }
     }
 }
+
+/// Allows tests to turn off skipping the decoding of text that the parser found to contain no character references
+#[cfg(test)]
+pub(crate) mod char_ref_fast_path {
+    use std::cell::Cell;
+
+    thread_local! {
+        static ENABLED: Cell<bool> = const { Cell::new(true) };
+    }
+
+    pub fn set_enabled(enabled: bool) {
+        ENABLED.with(|x| x.set(enabled));
+    }
+
+    pub fn is_enabled() -> bool {
+        ENABLED.with(Cell::get)
+    }
+}
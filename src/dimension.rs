@@ -0,0 +1,66 @@
+use crate::util;
+
+/// A length given by a presentational attribute, such as `width="50%"` or `cellpadding="4"`
+///
+/// Obtained by calling `Attributes::get_dimension()`.
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+pub enum Dimension {
+    /// A length in CSS pixels: `300`
+    Pixels(f64),
+    /// A percentage of the available space: `50%`
+    Percent(f64),
+    /// A relative share of the remaining space, as used in the `cols` of a `<frameset>`: `2*`
+    Relative(f64),
+}
+
+impl Dimension {
+    /// Parses a dimension value
+    ///
+    /// This follows the rules for parsing dimension values of the HTML specification: leading whitespace is skipped,
+    /// the value must start with a digit and may have a fraction (e.g. `2.5`), and anything after the number is ignored,
+    /// except for a `%` or `*` right after it, which makes it a percentage or a relative value.
+    /// Returns `None` if the value does not start with a number.
+    ///
+    /// # Example
+    /// ```
+    /// use tl::Dimension;
+    ///
+    /// assert_eq!(Dimension::parse("300px"), Some(Dimension::Pixels(300.0)));
+    /// assert_eq!(Dimension::parse(" 50% "), Some(Dimension::Percent(50.0)));
+    /// assert_eq!(Dimension::parse("2.5*"), Some(Dimension::Relative(2.5)));
+    /// assert_eq!(Dimension::parse("-1"), None);
+    /// ```
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim_start_matches(util::is_ascii_whitespace_char);
+
+        let integer = value.len() - value.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        if integer == 0 {
+            return None;
+        }
+
+        // a fraction is only part of the number if there is a digit after the dot
+        let rest = &value[integer..];
+        let fraction = match rest.strip_prefix('.') {
+            Some(after) if after.starts_with(|c: char| c.is_ascii_digit()) => {
+                1 + after.len() - after.trim_start_matches(|c: char| c.is_ascii_digit()).len()
+            }
+            _ => 0,
+        };
+
+        let end = integer + fraction;
+        let number = value[..end].parse().ok()?;
+
+        Some(match value[end..].chars().next() {
+            Some('%') => Self::Percent(number),
+            Some('*') => Self::Relative(number),
+            _ => Self::Pixels(number),
+        })
+    }
+
+    /// Returns the number of this dimension, regardless of its unit
+    pub fn value(&self) -> f64 {
+        match *self {
+            Self::Pixels(value) | Self::Percent(value) | Self::Relative(value) => value,
+        }
+    }
+}
@@ -0,0 +1,797 @@
+use std::borrow::Cow;
+use std::cmp::Ordering;
+
+use crate::{util, Bytes, Dimension, HTMLTag, Node, Parser};
+
+/// The attributes that are checked for the URL of an image by [`HTMLTag::image_source()`], in order
+///
+/// Lazy loading libraries store the actual URL in one of the data attributes, and point `src` at a placeholder
+/// until the image is scrolled into view.
+pub const DEFAULT_IMAGE_SOURCE_ATTRIBUTES: &[&str] =
+    &["data-src", "data-lazy-src", "data-original", "src"];
+
+/// The attributes that are checked for a `srcset` by [`HTMLTag::image_source()`], in order
+const SRCSET_ATTRIBUTES: &[&str] = &["data-srcset", "srcset"];
+
+impl<'a> HTMLTag<'a> {
+    /// Returns a typed view of this element if it is an `<a>` or `<area>` element
+    ///
+    /// # Example
+    /// ```
+    /// let dom = tl::parse(r#"<a href=" /docs" rel="nofollow">Docs</a>"#, Default::default()).unwrap();
+    /// let anchor = dom.nodes()[0].as_tag().unwrap().as_anchor().unwrap();
+    ///
+    /// assert_eq!(anchor.href().as_deref(), Some("/docs"));
+    /// assert!(anchor.has_rel("NoFollow"));
+    /// assert_eq!(anchor.text(dom.parser()), "Docs");
+    /// ```
+    pub fn as_anchor(&self) -> Option<Anchor<'_, 'a>> {
+        (self.has_name(b"a") || self.has_name(b"area")).then_some(Anchor { tag: self })
+    }
+
+    /// Returns a typed view of this element if it is an `<img>` element
+    ///
+    /// # Example
+    /// ```
+    /// let dom = tl::parse(r#"<img src="a.png" width="120px" alt="A">"#, Default::default()).unwrap();
+    /// let image = dom.nodes()[0].as_tag().unwrap().as_image().unwrap();
+    ///
+    /// assert_eq!(image.src().as_deref(), Some("a.png"));
+    /// assert_eq!(image.width(), Some(120));
+    /// assert_eq!(image.height(), None);
+    /// ```
+    pub fn as_image(&self) -> Option<Image<'_, 'a>> {
+        self.has_name(b"img").then_some(Image { tag: self })
+    }
+
+    /// Returns a typed view of this element if it is a `<meta>` element
+    ///
+    /// # Example
+    /// ```
+    /// let dom = tl::parse(r#"<meta property="og:title" content="Hello">"#, Default::default()).unwrap();
+    /// let meta = dom.nodes()[0].as_tag().unwrap().as_meta().unwrap();
+    ///
+    /// assert_eq!(meta.name_or_property().as_deref(), Some("og:title"));
+    /// assert_eq!(meta.content().as_deref(), Some("Hello"));
+    /// ```
+    pub fn as_meta(&self) -> Option<Meta<'_, 'a>> {
+        self.has_name(b"meta").then_some(Meta { tag: self })
+    }
+
+    /// Returns a typed view of this element and its options if it is a `<select>` element
+    ///
+    /// The options are the `<option>` children of the element and of its `<optgroup>` children, in document order.
+    ///
+    /// # Example
+    /// ```
+    /// let html = r#"<select name="size"><option>Small<option selected value="m">Medium</select>"#;
+    /// let dom = tl::parse(html, Default::default()).unwrap();
+    /// let select = dom.nodes()[0].as_tag().unwrap().as_select(dom.parser()).unwrap();
+    ///
+    /// assert_eq!(select.options().len(), 2);
+    /// assert_eq!(select.options()[0].value(), "Small");
+    /// assert_eq!(select.selected_value().as_deref(), Some("m"));
+    /// ```
+    pub fn as_select<'t>(&'t self, parser: &'t Parser<'a>) -> Option<Select<'t, 'a>> {
+        if !self.has_name(b"select") {
+            return None;
+        }
+
+        let mut options = Vec::new();
+        for child in child_tags(self, parser) {
+            if child.has_name(b"option") {
+                options.push(SelectOption::new(child, None, parser));
+            } else if child.has_name(b"optgroup") {
+                let group_options = child_tags(child, parser)
+                    .filter(|tag| tag.has_name(b"option"))
+                    .map(|tag| SelectOption::new(tag, Some(child), parser));
+
+                options.extend(group_options);
+            }
+        }
+
+        Some(Select { tag: self, options })
+    }
+
+    /// Returns the terms and descriptions of this element if it is a `<dl>` element
+    ///
+    /// Each `<dt>` is paired with the text of the `<dd>` elements that follow it, up to the next term.
+    /// Consecutive terms share the descriptions that follow them, and descriptions before the first term
+    /// are paired with an empty term. Groups of terms and descriptions may be wrapped in `<div>` elements.
+    /// The texts have character references decoded and whitespace stripped and collapsed.
+    ///
+    /// # Example
+    /// ```
+    /// let html = "<dl><dt>Color<dd>Red<dd>Blue<dt>Size<dd>Large</dl>";
+    /// let dom = tl::parse(html, Default::default()).unwrap();
+    /// let list = dom.nodes()[0].as_tag().unwrap().as_definition_list(dom.parser()).unwrap();
+    ///
+    /// assert_eq!(
+    ///     list,
+    ///     [
+    ///         (String::from("Color"), vec![String::from("Red"), String::from("Blue")]),
+    ///         (String::from("Size"), vec![String::from("Large")]),
+    ///     ]
+    /// );
+    /// ```
+    pub fn as_definition_list(&self, parser: &Parser<'a>) -> Option<Vec<(String, Vec<String>)>> {
+        if !self.has_name(b"dl") {
+            return None;
+        }
+
+        let mut items = Vec::new();
+        for child in child_tags(self, parser) {
+            if child.has_name(b"div") {
+                items.extend(child_tags(child, parser));
+            } else {
+                items.push(child);
+            }
+        }
+
+        let mut entries: Vec<(String, Vec<String>)> = Vec::new();
+        // the index of the first term that the next description belongs to
+        let mut group_start = 0;
+        let mut in_descriptions = false;
+
+        for item in items {
+            if item.has_name(b"dt") {
+                if in_descriptions {
+                    group_start = entries.len();
+                    in_descriptions = false;
+                }
+
+                entries.push((collapsed_text(item, parser), Vec::new()));
+            } else if item.has_name(b"dd") {
+                if entries.is_empty() {
+                    entries.push((String::new(), Vec::new()));
+                }
+
+                let description = collapsed_text(item, parser);
+                for (_, descriptions) in &mut entries[group_start..] {
+                    descriptions.push(description.clone());
+                }
+
+                in_descriptions = true;
+            }
+        }
+
+        Some(entries)
+    }
+
+    /// Returns the items of this element and of the lists nested in them if it is a `<ul>`, `<ol>` or `<menu>` element
+    ///
+    /// The text of an item does not include the text of the lists nested in it, which are returned as its sublists
+    /// instead. A list that is a direct child of this list (rather than of an `<li>`) is added to the sublists
+    /// of the item before it, as browsers render it that way.
+    ///
+    /// # Example
+    /// ```
+    /// let html = "<ul><li>Fruit<ol><li>Apple<li>Pear</ol><li>Bread</ul>";
+    /// let dom = tl::parse(html, Default::default()).unwrap();
+    /// let list = dom.nodes()[0].as_tag().unwrap().as_list(dom.parser()).unwrap();
+    ///
+    /// assert!(!list.ordered);
+    /// assert_eq!(list.items.len(), 2);
+    /// assert_eq!(list.items[0].text, "Fruit");
+    /// assert_eq!(list.items[0].sublists[0].items[1].text, "Pear");
+    /// assert_eq!(list.items[1].text, "Bread");
+    /// ```
+    pub fn as_list(&self, parser: &Parser<'a>) -> Option<ListNode> {
+        let ordered = self.has_name(b"ol");
+        if !ordered && !self.has_name(b"ul") && !self.has_name(b"menu") {
+            return None;
+        }
+
+        let mut items: Vec<ListItem> = Vec::new();
+        for child in child_tags(self, parser) {
+            if child.has_name(b"li") {
+                let mut text = String::new();
+                let mut sublists = Vec::new();
+                collect_list_item(child, parser, &mut text, &mut sublists);
+
+                items.push(ListItem {
+                    text: collapse_whitespace(&text),
+                    sublists,
+                });
+            } else if let Some(sublist) = child.as_list(parser) {
+                if let Some(item) = items.last_mut() {
+                    item.sublists.push(sublist);
+                }
+            }
+        }
+
+        Some(ListNode { ordered, items })
+    }
+
+    /// Returns the URL of the image shown by this element (usually an `<img>`), following common lazy loading patterns
+    ///
+    /// This is [`HTMLTag::image_source_with()`] using the attributes in [`DEFAULT_IMAGE_SOURCE_ATTRIBUTES`].
+    ///
+    /// # Example
+    /// ```
+    /// let html = r#"<img src="data:image/gif;base64,R0lGODlhAQABAAAAACw=" data-src="/photo.jpg">"#;
+    /// let dom = tl::parse(html, Default::default()).unwrap();
+    /// let image = dom.nodes()[0].as_tag().unwrap();
+    ///
+    /// assert_eq!(image.image_source(dom.parser()).as_deref(), Some("/photo.jpg"));
+    /// ```
+    pub fn image_source<'t>(&'t self, parser: &'t Parser<'a>) -> Option<Cow<'t, str>> {
+        self.image_source_with(parser, DEFAULT_IMAGE_SOURCE_ATTRIBUTES)
+    }
+
+    /// Returns the URL of the image shown by this element, checking the given attributes in order
+    ///
+    /// The URL is the first of these that exists:
+    /// 1. The value of the first of the given attributes that is neither empty nor a placeholder,
+    ///    normalized like [`Attributes::get_url()`](crate::Attributes::get_url). Placeholders are `data:` URIs,
+    ///    and the `src` attribute of an element whose `width` and `height` are at most 1 (a spacer pixel).
+    /// 2. The largest candidate of the `data-srcset` or `srcset` attribute, which is the widest one,
+    ///    or the one with the highest density if no candidate has a width. `data:` URIs are skipped.
+    /// 3. If this element is in a `<picture>`, the largest candidate of the first `<source>` element
+    ///    with a `data-srcset` or `srcset` attribute.
+    ///
+    /// # Example
+    /// ```
+    /// let html = r#"<img src="/spacer.gif" width="1" height="1" data-hi-res="/big.jpg" srcset="/a.jpg 1x, /a@2x.jpg 2x">"#;
+    /// let dom = tl::parse(html, Default::default()).unwrap();
+    /// let image = dom.nodes()[0].as_tag().unwrap();
+    ///
+    /// assert_eq!(image.image_source(dom.parser()).as_deref(), Some("/a@2x.jpg"));
+    /// assert_eq!(image.image_source_with(dom.parser(), &["data-hi-res", "src"]).as_deref(), Some("/big.jpg"));
+    /// ```
+    pub fn image_source_with<'t>(
+        &'t self,
+        parser: &'t Parser<'a>,
+        attributes: &[&str],
+    ) -> Option<Cow<'t, str>> {
+        let is_spacer = [pixels(self, "width"), pixels(self, "height")]
+            .iter()
+            .all(|x| x.is_some_and(|x| x <= 1));
+
+        let source = attributes
+            .iter()
+            .filter(|name| !(is_spacer && name.eq_ignore_ascii_case("src")))
+            .filter_map(|name| self.attributes().get_by_name(name.as_bytes())?)
+            .map(Bytes::as_url_str)
+            .find(|url| !url.is_empty() && !is_data_uri(url));
+
+        if source.is_some() {
+            return source;
+        }
+
+        if let Some(candidate) = largest_srcset_candidate(self) {
+            return Some(Cow::Owned(candidate.url));
+        }
+
+        let parent = self.parent()?.get(parser)?.as_tag()?;
+        if !parent.has_name(b"picture") {
+            return None;
+        }
+
+        child_tags(parent, parser)
+            .filter(|tag| tag.has_name(b"source"))
+            .find_map(largest_srcset_candidate)
+            .map(|candidate| Cow::Owned(candidate.url))
+    }
+
+    fn has_name(&self, name: &[u8]) -> bool {
+        self.name().as_bytes().eq_ignore_ascii_case(name)
+    }
+}
+
+/// Returns an iterator over the child elements of a tag
+fn child_tags<'t, 'a>(
+    tag: &'t HTMLTag<'a>,
+    parser: &'t Parser<'a>,
+) -> impl Iterator<Item = &'t HTMLTag<'a>> + 't {
+    tag._children
+        .as_slice()
+        .iter()
+        .filter_map(move |handle| handle.get(parser)?.as_tag())
+}
+
+/// Returns the decoded text of a tag with ASCII whitespace stripped and collapsed
+fn collapsed_text(tag: &HTMLTag<'_>, parser: &Parser<'_>) -> String {
+    collapse_whitespace(&tag.inner_text_decoded(parser))
+}
+
+/// Strips leading and trailing ASCII whitespace and replaces all other runs of ASCII whitespace with a single space
+fn collapse_whitespace(text: &str) -> String {
+    util::split_ascii_whitespace(text)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Appends the decoded text of the subtree of a list item to `text`, except for the lists in it,
+/// which are appended to `sublists`
+fn collect_list_item(
+    tag: &HTMLTag<'_>,
+    parser: &Parser<'_>,
+    text: &mut String,
+    sublists: &mut Vec<ListNode>,
+) {
+    for node in tag
+        ._children
+        .as_slice()
+        .iter()
+        .filter_map(|handle| handle.get(parser))
+    {
+        match node {
+            Node::Raw(raw) => text.push_str(&raw.as_decoded_str()),
+            Node::Tag(tag) => match tag.as_list(parser) {
+                Some(sublist) => sublists.push(sublist),
+                None => collect_list_item(tag, parser, text, sublists),
+            },
+            Node::Comment(_) => {}
+        }
+    }
+}
+
+/// Returns the value of an attribute with leading and trailing ASCII whitespace removed
+fn trimmed_attribute<'t>(tag: &'t HTMLTag<'_>, name: &'static str) -> Option<Cow<'t, str>> {
+    let value = tag.attributes().get(name).flatten()?;
+
+    Some(match value.as_utf8_str() {
+        Cow::Borrowed(s) => Cow::Borrowed(util::trim_ascii_whitespace(s)),
+        Cow::Owned(s) => Cow::Owned(util::trim_ascii_whitespace(&s).to_owned()),
+    })
+}
+
+/// Returns the raw value of an attribute, or `None` if it does not exist or has no value
+fn raw_attribute<'t>(tag: &'t HTMLTag<'_>, name: &'static str) -> Option<Cow<'t, str>> {
+    tag.attributes().get(name).flatten().map(Bytes::as_utf8_str)
+}
+
+/// Checks whether the given URL is a `data:` URI, which embeds the resource
+fn is_data_uri(url: &str) -> bool {
+    url.get(..5)
+        .is_some_and(|scheme| scheme.eq_ignore_ascii_case("data:"))
+}
+
+/// Returns the largest candidate of the first `srcset` attribute of a tag (see [`SRCSET_ATTRIBUTES`]) that has one,
+/// which is the widest one, or the one with the highest density if no candidate has a width
+fn largest_srcset_candidate(tag: &HTMLTag<'_>) -> Option<SrcsetCandidate> {
+    let size = |candidate: &SrcsetCandidate| match candidate.descriptor {
+        SrcsetDescriptor::Width(width) => (true, f64::from(width)),
+        SrcsetDescriptor::Density(density) => (false, density),
+    };
+
+    SRCSET_ATTRIBUTES.iter().find_map(|name| {
+        let srcset = tag.attributes().get_by_name(name.as_bytes())??;
+
+        SrcsetCandidate::parse_list(&srcset.as_utf8_str())
+            .into_iter()
+            .filter(|candidate| !is_data_uri(&candidate.url))
+            .max_by(|a, b| {
+                let (a, b) = (size(a), size(b));
+                a.0.cmp(&b.0)
+                    .then(a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal))
+            })
+    })
+}
+
+/// Returns the value of a dimension attribute in whole pixels, or `None` if it is not a length in pixels or does not fit into a `u32`
+fn pixels(tag: &HTMLTag<'_>, name: &'static str) -> Option<u32> {
+    match tag.attributes().get_dimension(name)? {
+        Dimension::Pixels(pixels) if pixels <= f64::from(u32::MAX) => Some(pixels as u32),
+        _ => None,
+    }
+}
+
+/// A typed view of an `<a>` or `<area>` element, obtained by calling [`HTMLTag::as_anchor()`]
+#[derive(Debug, Copy, Clone)]
+pub struct Anchor<'t, 'a> {
+    tag: &'t HTMLTag<'a>,
+}
+
+impl<'t, 'a> Anchor<'t, 'a> {
+    /// Returns the underlying element
+    pub fn tag(&self) -> &'t HTMLTag<'a> {
+        self.tag
+    }
+
+    /// Returns the link target, normalized like [`Attributes::get_url()`](crate::Attributes::get_url)
+    pub fn href(&self) -> Option<Cow<'t, str>> {
+        self.tag.attributes().get_url("href")
+    }
+
+    /// Returns an iterator over the link types of the `rel` attribute (e.g. `nofollow`), as they are written
+    ///
+    /// The iterator is empty if there is no `rel` attribute.
+    pub fn rel_tokens(&self) -> impl Iterator<Item = &'t str> + 't {
+        self.tag
+            .attributes()
+            .token_list("rel")
+            .into_iter()
+            .flatten()
+    }
+
+    /// Checks whether the `rel` attribute contains the given link type (ASCII case-insensitive)
+    pub fn has_rel(&self, rel: &str) -> bool {
+        self.tag
+            .attributes()
+            .get("rel")
+            .flatten()
+            .is_some_and(|tokens| tokens.contains_token_ignore_case(rel.as_bytes()))
+    }
+
+    /// Returns the browsing context the link is opened in (e.g. `_blank`), without surrounding whitespace
+    pub fn target(&self) -> Option<Cow<'t, str>> {
+        trimmed_attribute(self.tag, "target")
+    }
+
+    /// Returns the text of the link, see [`HTMLTag::inner_text()`]
+    pub fn text<'p>(&self, parser: &'p Parser<'a>) -> Cow<'p, str> {
+        self.tag.inner_text(parser)
+    }
+}
+
+/// A typed view of an `<img>` element, obtained by calling [`HTMLTag::as_image()`]
+#[derive(Debug, Copy, Clone)]
+pub struct Image<'t, 'a> {
+    tag: &'t HTMLTag<'a>,
+}
+
+impl<'t, 'a> Image<'t, 'a> {
+    /// Returns the underlying element
+    pub fn tag(&self) -> &'t HTMLTag<'a> {
+        self.tag
+    }
+
+    /// Returns the image URL, normalized like [`Attributes::get_url()`](crate::Attributes::get_url)
+    pub fn src(&self) -> Option<Cow<'t, str>> {
+        self.tag.attributes().get_url("src")
+    }
+
+    /// Returns the raw alternative text
+    ///
+    /// An empty `alt` attribute is returned as `Some("")`, since it marks a decorative image.
+    /// An `alt` attribute without a value is treated the same way.
+    pub fn alt(&self) -> Option<Cow<'t, str>> {
+        match self.tag.attributes().get("alt")? {
+            Some(alt) => Some(alt.as_utf8_str()),
+            None => Some(Cow::Borrowed("")),
+        }
+    }
+
+    /// Returns the `width` attribute in whole pixels, parsed as a dimension like browsers do (e.g. `120px` is `120`)
+    ///
+    /// Returns `None` if the attribute is missing, does not start with a number, is a percentage or is too large.
+    /// See [`Attributes::get_dimension()`](crate::Attributes::get_dimension) for the value with its unit.
+    pub fn width(&self) -> Option<u32> {
+        pixels(self.tag, "width")
+    }
+
+    /// Returns the `height` attribute, parsed like [`Image::width()`]
+    pub fn height(&self) -> Option<u32> {
+        pixels(self.tag, "height")
+    }
+
+    /// Checks whether the image is a server-side image map, which is set by the boolean `ismap` attribute
+    ///
+    /// Like all boolean attributes, its presence is what matters, so `ismap="false"` is `true` as well.
+    pub fn is_map(&self) -> bool {
+        self.tag.attributes().contains("ismap")
+    }
+
+    /// Parses the `srcset` attribute into its image candidates
+    ///
+    /// Candidates with invalid descriptors (e.g. `2q`, or both a width and a density) are skipped,
+    /// and candidates without a descriptor have a density of `1x`. Returns an empty list if there is no `srcset` attribute.
+    ///
+    /// # Example
+    /// ```
+    /// use tl::SrcsetDescriptor;
+    ///
+    /// let dom = tl::parse(r#"<img srcset="a.png, b.png 2x, c.png 640w, d.png 3q">"#, Default::default()).unwrap();
+    /// let image = dom.nodes()[0].as_tag().unwrap().as_image().unwrap();
+    ///
+    /// let candidates = image.srcset_candidates();
+    /// let descriptors = candidates.iter().map(|c| (c.url.as_str(), c.descriptor)).collect::<Vec<_>>();
+    /// assert_eq!(
+    ///     descriptors,
+    ///     [
+    ///         ("a.png", SrcsetDescriptor::Density(1.0)),
+    ///         ("b.png", SrcsetDescriptor::Density(2.0)),
+    ///         ("c.png", SrcsetDescriptor::Width(640)),
+    ///     ]
+    /// );
+    /// ```
+    pub fn srcset_candidates(&self) -> Vec<SrcsetCandidate> {
+        raw_attribute(self.tag, "srcset")
+            .map(|srcset| SrcsetCandidate::parse_list(&srcset))
+            .unwrap_or_default()
+    }
+}
+
+/// The descriptor of an image candidate in a `srcset` attribute
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SrcsetDescriptor {
+    /// The width of the image in pixels: `640w`
+    Width(u32),
+    /// The pixel density the image is meant for: `2x`
+    Density(f64),
+}
+
+/// An image candidate in a `srcset` attribute, obtained by calling [`Image::srcset_candidates()`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SrcsetCandidate {
+    /// The URL of the image
+    pub url: String,
+    /// The width or density of the image
+    pub descriptor: SrcsetDescriptor,
+}
+
+impl SrcsetCandidate {
+    /// Parses a `srcset` attribute, following the candidate splitting of the HTML specification
+    ///
+    /// URLs end at whitespace, and a URL that ends with a `,` ends the candidate. Descriptors end at a `,` that is not in parentheses.
+    fn parse_list(srcset: &str) -> Vec<Self> {
+        let mut candidates = Vec::new();
+        let mut rest = srcset;
+
+        loop {
+            rest = rest.trim_start_matches(|c: char| util::is_ascii_whitespace_char(c) || c == ',');
+            if rest.is_empty() {
+                return candidates;
+            }
+
+            let url_len = rest
+                .find(util::is_ascii_whitespace_char)
+                .unwrap_or(rest.len());
+            let (url, after) = rest.split_at(url_len);
+            rest = after;
+
+            let descriptors = match url.strip_suffix(',') {
+                Some(_) => "",
+                None => {
+                    let len = descriptors_len(rest);
+                    let descriptors = &rest[..len];
+                    rest = &rest[len..];
+                    descriptors
+                }
+            };
+
+            let url = url.trim_end_matches(',');
+            if let Some(descriptor) = parse_descriptors(descriptors) {
+                candidates.push(Self {
+                    url: url.to_owned(),
+                    descriptor,
+                });
+            }
+        }
+    }
+}
+
+/// Returns the length of the descriptors at the start of `input`, which end at a `,` outside of parentheses
+fn descriptors_len(input: &str) -> usize {
+    let mut in_parens = false;
+
+    for (index, c) in input.char_indices() {
+        match c {
+            '(' => in_parens = true,
+            ')' => in_parens = false,
+            ',' if !in_parens => return index,
+            _ => {}
+        }
+    }
+
+    input.len()
+}
+
+/// Parses the whitespace-separated descriptors of an image candidate
+///
+/// At most one width or density is allowed, and a height (`100h`) is only allowed together with a width and is ignored.
+fn parse_descriptors(descriptors: &str) -> Option<SrcsetDescriptor> {
+    let mut descriptor = None;
+    let mut height = false;
+
+    for token in util::split_ascii_whitespace(descriptors) {
+        let unit_start = token.len() - token.chars().last().map_or(0, char::len_utf8);
+        let (value, unit) = token.split_at(unit_start);
+        let is_integer = !value.is_empty() && value.bytes().all(|c| c.is_ascii_digit());
+
+        match unit {
+            "w" if is_integer && descriptor.is_none() => {
+                let width = value.parse().ok().filter(|&width| width > 0)?;
+                descriptor = Some(SrcsetDescriptor::Width(width));
+            }
+            "x" if descriptor.is_none() => {
+                let is_float = value.starts_with(|c: char| c.is_ascii_digit() || c == '.')
+                    && value.bytes().all(|c| {
+                        c.is_ascii_digit() || matches!(c, b'.' | b'e' | b'E' | b'+' | b'-')
+                    });
+                let density = value.parse::<f64>().ok().filter(|_| is_float)?;
+                descriptor = Some(SrcsetDescriptor::Density(density));
+            }
+            "h" if is_integer && !height && value.parse::<u32>().is_ok_and(|h| h > 0) => {
+                height = true
+            }
+            _ => return None,
+        }
+    }
+
+    match descriptor {
+        Some(SrcsetDescriptor::Density(_)) if height => None,
+        Some(descriptor) => Some(descriptor),
+        None if height => None,
+        None => Some(SrcsetDescriptor::Density(1.0)),
+    }
+}
+
+/// A typed view of a `<meta>` element, obtained by calling [`HTMLTag::as_meta()`]
+#[derive(Debug, Copy, Clone)]
+pub struct Meta<'t, 'a> {
+    tag: &'t HTMLTag<'a>,
+}
+
+impl<'t, 'a> Meta<'t, 'a> {
+    /// Returns the underlying element
+    pub fn tag(&self) -> &'t HTMLTag<'a> {
+        self.tag
+    }
+
+    /// Returns the metadata name from the `name` attribute (e.g. `description`), or from the `property` attribute
+    /// used by Open Graph (e.g. `og:title`) if there is no `name`, without surrounding whitespace
+    pub fn name_or_property(&self) -> Option<Cow<'t, str>> {
+        trimmed_attribute(self.tag, "name")
+            .filter(|name| !name.is_empty())
+            .or_else(|| trimmed_attribute(self.tag, "property"))
+    }
+
+    /// Returns the raw value of the `content` attribute
+    pub fn content(&self) -> Option<Cow<'t, str>> {
+        raw_attribute(self.tag, "content")
+    }
+}
+
+/// A typed view of a `<select>` element and its options, obtained by calling [`HTMLTag::as_select()`]
+#[derive(Debug, Clone)]
+pub struct Select<'t, 'a> {
+    tag: &'t HTMLTag<'a>,
+    options: Vec<SelectOption<'t, 'a>>,
+}
+
+impl<'t, 'a> Select<'t, 'a> {
+    /// Returns the underlying element
+    pub fn tag(&self) -> &'t HTMLTag<'a> {
+        self.tag
+    }
+
+    /// Checks whether multiple options can be selected (the `multiple` attribute)
+    pub fn is_multiple(&self) -> bool {
+        self.tag.attributes().contains("multiple")
+    }
+
+    /// Returns all options in document order, including the ones in groups
+    pub fn options(&self) -> &[SelectOption<'t, 'a>] {
+        &self.options
+    }
+
+    /// Returns the options grouped by their `<optgroup>`, along with the label of the group
+    ///
+    /// Consecutive options outside of a group are yielded as a group without a label.
+    pub fn groups(&self) -> impl Iterator<Item = (Option<Cow<'t, str>>, &[SelectOption<'t, 'a>])> {
+        self.options
+            .chunk_by(|a, b| a.group.map(std::ptr::from_ref) == b.group.map(std::ptr::from_ref))
+            .map(|options| (options[0].group_label(), options))
+    }
+
+    /// Returns the options that are selected
+    ///
+    /// If multiple options can be selected, these are all options with a `selected` attribute.
+    /// Otherwise, this is the last option with a `selected` attribute, or the first option that is not disabled
+    /// if there is none (as browsers do for drop-down lists).
+    pub fn selected_options(&self) -> Vec<&SelectOption<'t, 'a>> {
+        let mut selected = self.options.iter().filter(|option| option.is_selected());
+
+        if self.is_multiple() {
+            return selected.collect();
+        }
+
+        selected
+            .next_back()
+            .or_else(|| self.options.iter().find(|option| !option.is_disabled()))
+            .into_iter()
+            .collect()
+    }
+
+    /// Returns the value of the selected option, or of the first selected option if multiple options can be selected
+    ///
+    /// See [`Select::selected_options()`].
+    pub fn selected_value(&self) -> Option<Cow<'_, str>> {
+        self.selected_options().first().map(|option| option.value())
+    }
+
+    /// Returns the values of all selected options, see [`Select::selected_options()`]
+    pub fn selected_values(&self) -> Vec<Cow<'_, str>> {
+        self.selected_options()
+            .into_iter()
+            .map(SelectOption::value)
+            .collect()
+    }
+}
+
+/// An `<option>` of a [`Select`]
+#[derive(Debug, Clone)]
+pub struct SelectOption<'t, 'a> {
+    tag: &'t HTMLTag<'a>,
+    /// The `<optgroup>` that contains this option, if any
+    group: Option<&'t HTMLTag<'a>>,
+    /// The decoded text with ASCII whitespace stripped and collapsed
+    text: String,
+}
+
+impl<'t, 'a> SelectOption<'t, 'a> {
+    fn new(tag: &'t HTMLTag<'a>, group: Option<&'t HTMLTag<'a>>, parser: &Parser<'a>) -> Self {
+        let text = collapsed_text(tag, parser);
+
+        Self { tag, group, text }
+    }
+
+    /// Returns the underlying element
+    pub fn tag(&self) -> &'t HTMLTag<'a> {
+        self.tag
+    }
+
+    /// Returns the `<optgroup>` element that contains this option, if any
+    pub fn group(&self) -> Option<&'t HTMLTag<'a>> {
+        self.group
+    }
+
+    /// Returns the raw `label` attribute of the `<optgroup>` that contains this option, if any
+    pub fn group_label(&self) -> Option<Cow<'t, str>> {
+        raw_attribute(self.group?, "label")
+    }
+
+    /// Returns the text of the option, with character references decoded and whitespace stripped and collapsed
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Returns the raw `value` attribute, or the text of the option if there is none
+    pub fn value(&self) -> Cow<'_, str> {
+        raw_attribute(self.tag, "value").unwrap_or(Cow::Borrowed(&self.text))
+    }
+
+    /// Returns the raw `label` attribute, or the text of the option if it is missing or empty
+    pub fn label(&self) -> Cow<'_, str> {
+        raw_attribute(self.tag, "label")
+            .filter(|label| !label.is_empty())
+            .unwrap_or(Cow::Borrowed(&self.text))
+    }
+
+    /// Checks whether the option has a `selected` attribute
+    ///
+    /// Use [`Select::selected_options()`] to get the options that are actually selected.
+    pub fn is_selected(&self) -> bool {
+        self.tag.attributes().contains("selected")
+    }
+
+    /// Checks whether the option or its `<optgroup>` has a `disabled` attribute
+    pub fn is_disabled(&self) -> bool {
+        self.tag.attributes().contains("disabled")
+            || self
+                .group
+                .is_some_and(|group| group.attributes().contains("disabled"))
+    }
+}
+
+/// A `<ul>`, `<ol>` or `<menu>` element with its items, obtained by calling [`HTMLTag::as_list()`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListNode {
+    /// Whether this is an `<ol>` element
+    pub ordered: bool,
+    /// The `<li>` children of the list, in document order
+    pub items: Vec<ListItem>,
+}
+
+/// An `<li>` item of a [`ListNode`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListItem {
+    /// The text of the item without the text of its sublists, with character references decoded
+    /// and whitespace stripped and collapsed
+    pub text: String,
+    /// The lists nested in the item, in document order
+    pub sublists: Vec<ListNode>,
+}
@@ -0,0 +1,288 @@
+use std::borrow::Cow;
+
+/// Named character references that are decoded
+///
+/// This is not the full table of the HTML specification, but covers the references that are commonly found in documents.
+const NAMED: &[(&str, &str)] = &[
+    ("amp", "&"),
+    ("lt", "<"),
+    ("gt", ">"),
+    ("quot", "\""),
+    ("apos", "'"),
+    ("nbsp", "\u{a0}"),
+    ("iexcl", "¡"),
+    ("cent", "¢"),
+    ("pound", "£"),
+    ("curren", "¤"),
+    ("yen", "¥"),
+    ("brvbar", "¦"),
+    ("sect", "§"),
+    ("uml", "¨"),
+    ("copy", "©"),
+    ("ordf", "ª"),
+    ("laquo", "«"),
+    ("not", "¬"),
+    ("shy", "\u{ad}"),
+    ("reg", "®"),
+    ("macr", "¯"),
+    ("deg", "°"),
+    ("plusmn", "±"),
+    ("sup2", "²"),
+    ("sup3", "³"),
+    ("acute", "´"),
+    ("micro", "µ"),
+    ("para", "¶"),
+    ("middot", "·"),
+    ("cedil", "¸"),
+    ("sup1", "¹"),
+    ("ordm", "º"),
+    ("raquo", "»"),
+    ("frac14", "¼"),
+    ("frac12", "½"),
+    ("frac34", "¾"),
+    ("iquest", "¿"),
+    ("times", "×"),
+    ("divide", "÷"),
+    ("Agrave", "À"),
+    ("Aacute", "Á"),
+    ("Acirc", "Â"),
+    ("Atilde", "Ã"),
+    ("Auml", "Ä"),
+    ("Aring", "Å"),
+    ("AElig", "Æ"),
+    ("Ccedil", "Ç"),
+    ("Egrave", "È"),
+    ("Eacute", "É"),
+    ("Ecirc", "Ê"),
+    ("Euml", "Ë"),
+    ("Igrave", "Ì"),
+    ("Iacute", "Í"),
+    ("Icirc", "Î"),
+    ("Iuml", "Ï"),
+    ("ETH", "Ð"),
+    ("Ntilde", "Ñ"),
+    ("Ograve", "Ò"),
+    ("Oacute", "Ó"),
+    ("Ocirc", "Ô"),
+    ("Otilde", "Õ"),
+    ("Ouml", "Ö"),
+    ("Oslash", "Ø"),
+    ("Ugrave", "Ù"),
+    ("Uacute", "Ú"),
+    ("Ucirc", "Û"),
+    ("Uuml", "Ü"),
+    ("Yacute", "Ý"),
+    ("THORN", "Þ"),
+    ("szlig", "ß"),
+    ("agrave", "à"),
+    ("aacute", "á"),
+    ("acirc", "â"),
+    ("atilde", "ã"),
+    ("auml", "ä"),
+    ("aring", "å"),
+    ("aelig", "æ"),
+    ("ccedil", "ç"),
+    ("egrave", "è"),
+    ("eacute", "é"),
+    ("ecirc", "ê"),
+    ("euml", "ë"),
+    ("igrave", "ì"),
+    ("iacute", "í"),
+    ("icirc", "î"),
+    ("iuml", "ï"),
+    ("eth", "ð"),
+    ("ntilde", "ñ"),
+    ("ograve", "ò"),
+    ("oacute", "ó"),
+    ("ocirc", "ô"),
+    ("otilde", "õ"),
+    ("ouml", "ö"),
+    ("oslash", "ø"),
+    ("ugrave", "ù"),
+    ("uacute", "ú"),
+    ("ucirc", "û"),
+    ("uuml", "ü"),
+    ("yacute", "ý"),
+    ("thorn", "þ"),
+    ("yuml", "ÿ"),
+    ("OElig", "Œ"),
+    ("oelig", "œ"),
+    ("Scaron", "Š"),
+    ("scaron", "š"),
+    ("Yuml", "Ÿ"),
+    ("fnof", "ƒ"),
+    ("circ", "ˆ"),
+    ("tilde", "˜"),
+    ("ensp", "\u{2002}"),
+    ("emsp", "\u{2003}"),
+    ("thinsp", "\u{2009}"),
+    ("zwnj", "\u{200c}"),
+    ("zwj", "\u{200d}"),
+    ("lrm", "\u{200e}"),
+    ("rlm", "\u{200f}"),
+    ("ndash", "–"),
+    ("mdash", "—"),
+    ("lsquo", "‘"),
+    ("rsquo", "’"),
+    ("sbquo", "‚"),
+    ("ldquo", "“"),
+    ("rdquo", "”"),
+    ("bdquo", "„"),
+    ("dagger", "†"),
+    ("Dagger", "‡"),
+    ("bull", "•"),
+    ("hellip", "…"),
+    ("permil", "‰"),
+    ("prime", "′"),
+    ("Prime", "″"),
+    ("lsaquo", "‹"),
+    ("rsaquo", "›"),
+    ("oline", "‾"),
+    ("euro", "€"),
+    ("trade", "™"),
+    ("larr", "←"),
+    ("uarr", "↑"),
+    ("rarr", "→"),
+    ("darr", "↓"),
+    ("harr", "↔"),
+    ("minus", "−"),
+    ("infin", "∞"),
+    ("ne", "≠"),
+    ("le", "≤"),
+    ("ge", "≥"),
+    ("asymp", "≈"),
+    ("hearts", "♥"),
+];
+
+/// Named references that are also recognized without a trailing semicolon, for compatibility with old documents
+const LEGACY: &[&str] = &["amp", "lt", "gt", "quot", "nbsp", "copy", "reg"];
+
+/// Replacements for numeric references in the range 0x80 to 0x9F, which are interpreted as Windows-1252
+const WINDOWS_1252: [char; 32] = [
+    '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8d}', 'Ž', '\u{8f}',
+    '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9d}', 'ž', 'Ÿ',
+];
+
+/// Decodes all character references in the given text
+///
+/// Unknown or malformed references are left as they are.
+pub(crate) fn decode(text: &str) -> Cow<'_, str> {
+    let Some(first) = text.find('&') else {
+        return Cow::Borrowed(text);
+    };
+
+    let mut decoded = String::with_capacity(text.len());
+    decoded.push_str(&text[..first]);
+
+    let mut rest = &text[first..];
+    while let Some(index) = rest.find('&') {
+        decoded.push_str(&rest[..index]);
+        rest = &rest[index..];
+
+        match decode_reference(&rest[1..]) {
+            Some((consumed, replacement)) => {
+                replacement.push_to(&mut decoded);
+                rest = &rest[1 + consumed..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    decoded.push_str(rest);
+    Cow::Owned(decoded)
+}
+
+/// Escapes `&`, `<` and `>` in the given text, so that it is parsed as the same text again
+pub(crate) fn escape(text: &str) -> Cow<'_, str> {
+    let Some(first) = text.find(['&', '<', '>']) else {
+        return Cow::Borrowed(text);
+    };
+
+    let mut escaped = String::with_capacity(text.len() + 8);
+    escaped.push_str(&text[..first]);
+
+    for c in text[first..].chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            c => escaped.push(c),
+        }
+    }
+
+    Cow::Owned(escaped)
+}
+
+/// The replacement text of a character reference
+enum Replacement {
+    Char(char),
+    Str(&'static str),
+}
+
+impl Replacement {
+    fn push_to(self, s: &mut String) {
+        match self {
+            Self::Char(c) => s.push(c),
+            Self::Str(x) => s.push_str(x),
+        }
+    }
+}
+
+/// Decodes the character reference at the start of `input` (right after the `&`)
+///
+/// Returns the number of bytes consumed and the replacement, or `None` if this is not a reference.
+fn decode_reference(input: &str) -> Option<(usize, Replacement)> {
+    if let Some(numeric) = input.strip_prefix('#') {
+        let (digits, radix, prefix) = match numeric.strip_prefix(['x', 'X']) {
+            Some(hex) => (hex, 16, 2),
+            None => (numeric, 10, 1),
+        };
+
+        let len = digits
+            .bytes()
+            .take_while(|&c| (c as char).is_digit(radix))
+            .count();
+        if len == 0 {
+            return None;
+        }
+
+        let value = digits[..len]
+            .bytes()
+            .try_fold(0u32, |value, c| {
+                value
+                    .checked_mul(radix)?
+                    .checked_add((c as char).to_digit(radix)?)
+            })
+            .unwrap_or(u32::MAX);
+
+        let semicolon = usize::from(digits[len..].starts_with(';'));
+        return Some((
+            prefix + len + semicolon,
+            Replacement::Char(numeric_char(value)),
+        ));
+    }
+
+    let len = input.bytes().take_while(u8::is_ascii_alphanumeric).count();
+    let name = &input[..len];
+    let (_, replacement) = NAMED.iter().find(|(x, _)| *x == name)?;
+
+    if input[len..].starts_with(';') {
+        Some((len + 1, Replacement::Str(replacement)))
+    } else if LEGACY.contains(&name) {
+        Some((len, Replacement::Str(replacement)))
+    } else {
+        None
+    }
+}
+
+/// Returns the character of a numeric reference, following the replacements of the HTML specification
+fn numeric_char(value: u32) -> char {
+    match value {
+        0 => char::REPLACEMENT_CHARACTER,
+        0x80..=0x9f => WINDOWS_1252[(value - 0x80) as usize],
+        _ => char::from_u32(value).unwrap_or(char::REPLACEMENT_CHARACTER),
+    }
+}
@@ -1,11 +1,22 @@
 use core::fmt;
 use std::error::Error;
 
+use crate::Diagnostic;
+
 /// An error that occurred during parsing
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum ParseError {
     /// The input string length was too large to fit in a `u32`
     InvalidLength,
+    /// Parsing was cancelled by the progress callback (see `ParserOptions::on_progress()`)
+    Cancelled,
+    /// The input contained an error that the parser would have recovered from, but strict mode is enabled
+    /// (see `ParserOptions::strict()`)
+    Strict(Diagnostic),
+    /// The element whose children are replaced by `VDom::set_inner_html()` does not refer to an element of the document
+    InvalidParent,
+    /// The element whose children are replaced by `VDom::set_inner_html()` is a void element (e.g. `<img>`), which cannot have any content
+    VoidElement,
 }
 
 impl fmt::Display for ParseError {
@@ -14,17 +25,30 @@ impl fmt::Display for ParseError {
             ParseError::InvalidLength => {
                 write!(f, "The input string length is too large to fit in a `u32`")
             }
+            ParseError::Cancelled => write!(f, "Parsing was cancelled"),
+            ParseError::Strict(diagnostic) => write!(f, "{diagnostic}"),
+            ParseError::InvalidParent => write!(f, "The parent is not an element of the document"),
+            ParseError::VoidElement => {
+                write!(
+                    f,
+                    "The parent is a void element and cannot have any content"
+                )
+            }
         }
     }
 }
 
 impl Error for ParseError {}
 
-/// An error that occurred during a call to `Bytes::set`
+/// An error that occurred during a call to `Bytes::set` or `HTMLTag::set_name`
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum SetBytesError {
     /// The length of the given data would overflow a `u32`
     LengthOverflow,
+    /// The given tag name is empty or contains characters that are not allowed in tag names
+    InvalidTagName(String),
+    /// An element with children cannot be renamed to a void element (such as `br`), which has no content
+    VoidElementContent(String),
 }
 
 impl fmt::Display for SetBytesError {
@@ -33,8 +57,201 @@ impl fmt::Display for SetBytesError {
             SetBytesError::LengthOverflow => {
                 write!(f, "The string length is too large to fit in a `u32`")
             }
+            SetBytesError::InvalidTagName(name) => write!(f, "`{name}` is not a valid tag name"),
+            SetBytesError::VoidElementContent(name) => {
+                write!(f, "`{name}` is a void element and cannot have children")
+            }
         }
     }
 }
 
 impl Error for SetBytesError {}
+
+/// An error that occurred during a call to `HTMLTag::to_xml`, because the element contains a construct that XML cannot represent
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum XmlExportError {
+    /// The name of an element is not a valid XML name
+    InvalidElementName(String),
+    /// The name of an attribute is not a valid XML name
+    InvalidAttributeName(String),
+    /// A comment contains `--` or ends with `-`
+    InvalidComment(String),
+    /// A character that is not allowed in XML documents, such as most control characters
+    InvalidCharacter(char),
+}
+
+impl fmt::Display for XmlExportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            XmlExportError::InvalidElementName(name) => {
+                write!(f, "`{name}` is not a valid XML element name")
+            }
+            XmlExportError::InvalidAttributeName(name) => {
+                write!(f, "`{name}` is not a valid XML attribute name")
+            }
+            XmlExportError::InvalidComment(_) => {
+                write!(f, "XML comments cannot contain `--` or end with `-`")
+            }
+            XmlExportError::InvalidCharacter(c) => {
+                write!(f, "{c:?} is not allowed in XML documents")
+            }
+        }
+    }
+}
+
+impl Error for XmlExportError {}
+
+/// An error that occurred while building an element with `ElementBuilder`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ElementBuilderError {
+    /// The name of the element is not a valid tag name
+    InvalidElementName(String),
+    /// The name of an attribute is empty or contains whitespace, quotes, `>`, `/` or `=`
+    InvalidAttributeName(String),
+    /// Text was added to a void element (e.g. `<img>`), which cannot have any content
+    VoidElementContent(String),
+    /// The length of a name, value or text would overflow a `u32`
+    LengthOverflow,
+}
+
+impl fmt::Display for ElementBuilderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            ElementBuilderError::InvalidElementName(name) => {
+                write!(f, "`{name}` is not a valid element name")
+            }
+            ElementBuilderError::InvalidAttributeName(name) => {
+                write!(f, "`{name}` is not a valid attribute name")
+            }
+            ElementBuilderError::VoidElementContent(name) => {
+                write!(
+                    f,
+                    "`<{name}>` is a void element and cannot have any content"
+                )
+            }
+            ElementBuilderError::LengthOverflow => {
+                write!(f, "The string length is too large to fit in a `u32`")
+            }
+        }
+    }
+}
+
+impl Error for ElementBuilderError {}
+
+/// An error that occurred while inserting nodes into the children of an element, e.g. with `VDom::insert_before()`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum InsertError {
+    /// The parent does not refer to an element of the document
+    InvalidParent,
+    /// The parent is a void element (e.g. `<img>`), which cannot have any content
+    VoidElement,
+    /// The reference node is not a child of the parent
+    NotAChild,
+    /// The HTML fragment to insert could not be parsed
+    Parse(ParseError),
+    /// The text to insert is too long to fit in a `u32`
+    LengthOverflow,
+    /// The text to insert into a raw text element (e.g. `<script>`) contains its end tag, which would end the element early
+    RawTextEndTag,
+}
+
+impl fmt::Display for InsertError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            InsertError::InvalidParent => write!(f, "The parent is not an element of the document"),
+            InsertError::VoidElement => {
+                write!(
+                    f,
+                    "The parent is a void element and cannot have any content"
+                )
+            }
+            InsertError::NotAChild => write!(f, "The reference node is not a child of the parent"),
+            InsertError::Parse(error) => {
+                write!(f, "The HTML fragment could not be parsed: {error}")
+            }
+            InsertError::LengthOverflow => {
+                write!(f, "The string length is too large to fit in a `u32`")
+            }
+            InsertError::RawTextEndTag => {
+                write!(f, "The text contains the end tag of the raw text element")
+            }
+        }
+    }
+}
+
+impl Error for InsertError {}
+
+/// An error that occurred while moving a node with `VDom::move_node()`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MoveError {
+    /// The node does not refer to a node of the document
+    InvalidNode,
+    /// The new parent does not refer to an element of the document
+    InvalidParent,
+    /// The new parent is a void element (e.g. `<img>`), which cannot have any content
+    VoidElement,
+    /// The new parent is the node itself or one of its descendants
+    Cycle,
+    /// The index is greater than the number of children of the new parent
+    InvalidIndex,
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            MoveError::InvalidNode => write!(f, "The node is not part of the document"),
+            MoveError::InvalidParent => write!(f, "The parent is not an element of the document"),
+            MoveError::VoidElement => {
+                write!(
+                    f,
+                    "The parent is a void element and cannot have any content"
+                )
+            }
+            MoveError::Cycle => write!(f, "A node cannot be moved into its own subtree"),
+            MoveError::InvalidIndex => {
+                write!(
+                    f,
+                    "The index is out of bounds for the children of the parent"
+                )
+            }
+        }
+    }
+}
+
+impl Error for MoveError {}
+
+/// An error that occurred while parsing a file with `parse_file()`
+#[cfg(feature = "mmap")]
+#[derive(Debug)]
+pub enum FileParseError {
+    /// The file could not be opened or memory-mapped
+    Io(std::io::Error),
+    /// The file is not valid UTF-8
+    InvalidUtf8(std::str::Utf8Error),
+    /// The contents of the file could not be parsed
+    Parse(ParseError),
+}
+
+#[cfg(feature = "mmap")]
+impl fmt::Display for FileParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            FileParseError::Io(error) => write!(f, "The file could not be read: {error}"),
+            FileParseError::InvalidUtf8(error) => {
+                write!(f, "The file is not valid UTF-8: {error}")
+            }
+            FileParseError::Parse(error) => write!(f, "The file could not be parsed: {error}"),
+        }
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl Error for FileParseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            FileParseError::Io(error) => Some(error),
+            FileParseError::InvalidUtf8(error) => Some(error),
+            FileParseError::Parse(error) => Some(error),
+        }
+    }
+}
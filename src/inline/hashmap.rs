@@ -1,14 +1,22 @@
+use std::borrow::Borrow;
 use std::fmt::{Debug, Formatter};
 use std::hash::Hash;
 use std::ptr;
 use std::{collections::HashMap, mem::MaybeUninit};
 
+/// The raw parts of an inlined [`InlineHashMap`]: the backing array and the number of initialized elements
+pub type InlineParts<'a, K, V, const N: usize> = (&'a mut [MaybeUninit<(K, V)>; N], usize);
+
+/// The number of elements up to which a map that does not fit inline is stored in a vector instead of a `HashMap`
+const SPILLED_LIMIT: usize = 8;
+
 /// Similar to InlineVec, this structure will use an array
 /// if it is small enough to live on the stack, otherwise
-/// it allocates a HashMap on the heap
+/// it allocates a vector (for up to 8 elements) or a HashMap on the heap
 ///
 /// Hashing can be slower than just iterating through an array
-/// if the array is small, which is where it makes most sense
+/// if the array is small, which is where it makes most sense.
+/// Elements are iterated over in insertion order until the map uses a HashMap or an element is removed.
 #[derive(Debug, Clone)]
 pub struct InlineHashMap<K, V, const N: usize>(InlineHashMapInner<K, V, N>);
 
@@ -21,16 +29,90 @@ where
         Self(InlineHashMapInner::new())
     }
 
+    /// Creates a new InlineHashMap that can hold at least `capacity` elements without reallocating
+    ///
+    /// If `capacity` is larger than `N`, the map is allocated on the heap right away.
+    pub(crate) fn with_capacity(capacity: usize) -> Self {
+        if capacity > N {
+            Self(InlineHashMapInner::heap(capacity))
+        } else {
+            Self::new()
+        }
+    }
+
     /// Returns the number of elements in the map
     #[inline]
     pub fn len(&self) -> usize {
         self.0.len()
     }
 
+    /// Returns the number of elements the map can hold without reallocating
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        match &self.0 {
+            InlineHashMapInner::Inline { .. } => N,
+            InlineHashMapInner::Spilled(vec) => vec.capacity(),
+            InlineHashMapInner::Heap(map) => map.capacity(),
+        }
+    }
+
+    /// Reserves capacity for at least `additional` more elements
+    ///
+    /// If the inline array is too small, the elements are moved to the heap.
+    pub fn reserve(&mut self, additional: usize) {
+        let len = self.len();
+
+        match &mut self.0 {
+            InlineHashMapInner::Inline { .. } if len + additional > N => {
+                self.0.move_to_heap(len + additional)
+            }
+            InlineHashMapInner::Inline { .. } => {}
+            InlineHashMapInner::Spilled(_) if len + additional > SPILLED_LIMIT => {
+                self.0.move_to_heap(len + additional)
+            }
+            InlineHashMapInner::Spilled(vec) => vec.reserve(additional),
+            InlineHashMapInner::Heap(map) => map.reserve(additional),
+        }
+    }
+
+    /// Shrinks the capacity of the map as much as possible
+    ///
+    /// If the elements fit into the inline array, they are moved back from the heap.
+    pub fn shrink_to_fit(&mut self) {
+        let len = self.len();
+
+        match &mut self.0 {
+            InlineHashMapInner::Inline { .. } => {}
+            InlineHashMapInner::Spilled(vec) if len > N => vec.shrink_to_fit(),
+            InlineHashMapInner::Heap(map) if len > N => map.shrink_to_fit(),
+            InlineHashMapInner::Spilled(vec) => {
+                let mut inline = InlineHashMapInner::new();
+                for (key, value) in vec.drain(..) {
+                    inline.insert(key, value);
+                }
+                self.0 = inline;
+            }
+            InlineHashMapInner::Heap(map) => {
+                let mut inline = InlineHashMapInner::new();
+                for (key, value) in map.drain() {
+                    inline.insert(key, value);
+                }
+                self.0 = inline;
+            }
+        }
+    }
+
+    /// Checks whether this map is empty
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
     /// Returns an iterator over the elements of this map
     ///
-    /// This function boxes the returned iterator because it can be either of two:
+    /// This function boxes the returned iterator because it can be either of three:
     /// - The iterator returned by `HashMap::iter()`
+    /// - The iterator over a heap-allocated vector
     /// - The iterator over a stack-allocated array
     #[inline]
     pub fn iter(&self) -> Box<dyn Iterator<Item = (&K, &V)> + '_> {
@@ -41,7 +123,7 @@ where
     ///
     /// Only the first `.1` elements are initialized.
     #[inline]
-    pub fn inline_parts_mut(&mut self) -> Option<(&mut [MaybeUninit<(K, V)>; N], usize)> {
+    pub fn inline_parts_mut(&mut self) -> Option<InlineParts<'_, K, V, N>> {
         self.0.inline_parts_mut()
     }
 
@@ -90,6 +172,36 @@ where
     pub fn contains_key(&self, key: &K) -> bool {
         self.0.contains_key(key)
     }
+
+    /// Like `get()`, but takes any borrowed form of the key, like `HashMap::get()`
+    #[inline]
+    pub(crate) fn get_borrowed<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.0.get(key)
+    }
+
+    /// Like `get_mut()`, but takes any borrowed form of the key, like `HashMap::get_mut()`
+    #[inline]
+    pub(crate) fn get_mut_borrowed<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.0.get_mut(key)
+    }
+
+    /// Like `remove()`, but takes any borrowed form of the key, like `HashMap::remove()`
+    #[inline]
+    pub(crate) fn remove_borrowed<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.0.remove(key)
+    }
 }
 
 enum InlineHashMapInner<K, V, const N: usize> {
@@ -97,6 +209,7 @@ enum InlineHashMapInner<K, V, const N: usize> {
         len: usize,
         data: [MaybeUninit<(K, V)>; N],
     },
+    Spilled(Vec<(K, V)>),
     Heap(HashMap<K, V>),
 }
 
@@ -117,6 +230,7 @@ where
 {
     fn clone(&self) -> Self {
         match self {
+            Self::Spilled(v) => Self::Spilled(v.clone()),
             Self::Heap(m) => Self::Heap(m.clone()),
             Self::Inline { len, data } => {
                 let mut new_data = super::uninit_array();
@@ -157,20 +271,29 @@ impl<K, V, const N: usize> InlineHashMapInner<K, V, N> {
         }
     }
 
+    /// Creates an empty map on the heap that can hold at least `capacity` elements without reallocating
+    fn heap(capacity: usize) -> Self {
+        match capacity > SPILLED_LIMIT {
+            true => Self::Heap(HashMap::with_capacity(capacity)),
+            false => Self::Spilled(Vec::with_capacity(capacity)),
+        }
+    }
+
     #[inline]
     pub fn iter(&self) -> Box<dyn Iterator<Item = (&K, &V)> + '_> {
         match self {
             Self::Inline { len, data } => {
                 Box::new(unsafe { InlineHashMapIterator::new(data, *len) })
             }
+            Self::Spilled(v) => Box::new(v.iter().map(|(k, v)| (k, v))),
             Self::Heap(h) => Box::new(h.iter()),
         }
     }
 
     #[inline]
-    pub fn inline_parts_mut(&mut self) -> Option<(&mut [MaybeUninit<(K, V)>; N], usize)> {
+    pub fn inline_parts_mut(&mut self) -> Option<InlineParts<'_, K, V, N>> {
         match self {
-            Self::Heap(_) => None,
+            Self::Spilled(_) | Self::Heap(_) => None,
             Self::Inline { len, data } => Some((data, *len)),
         }
     }
@@ -182,11 +305,12 @@ impl<K, V, const N: usize> InlineHashMapInner<K, V, N> {
         V: Clone,
     {
         match &self {
+            InlineHashMapInner::Spilled(v) => v.iter().cloned().collect(),
             InlineHashMapInner::Heap(m) => m.clone(),
             InlineHashMapInner::Inline { len, data } => {
                 let mut new_data = HashMap::with_capacity(*len);
 
-                let iter = data.into_iter().take(*len);
+                let iter = data.iter().take(*len);
 
                 for element in iter {
                     let element = unsafe { &*element.as_ptr() };
@@ -203,47 +327,68 @@ impl<K, V, const N: usize> InlineHashMapInner<K, V, N> {
     pub fn len(&self) -> usize {
         match self {
             Self::Inline { len, .. } => *len,
+            Self::Spilled(vec) => vec.len(),
             Self::Heap(map) => map.len(),
         }
     }
 
     #[inline]
     pub fn is_heap_allocated(&self) -> bool {
-        matches!(self, Self::Heap(_))
+        !matches!(self, Self::Inline { .. })
     }
 }
 
 impl<K: Eq + Hash, V, const N: usize> InlineHashMapInner<K, V, N> {
-    pub fn get<'m>(&'m self, k: &K) -> Option<&'m V> {
+    pub fn get<'m, Q>(&'m self, k: &Q) -> Option<&'m V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
         match self {
             Self::Inline { data, len } => unsafe {
                 InlineHashMapIterator::new(data, *len)
-                    .find(|(key, _)| key.eq(&k))
+                    .find(|(key, _)| <K as Borrow<Q>>::borrow(key) == k)
                     .map(|(_, value)| value)
             },
+            Self::Spilled(vec) => vec
+                .iter()
+                .find(|(key, _)| <K as Borrow<Q>>::borrow(key) == k)
+                .map(|(_, value)| value),
             Self::Heap(map) => map.get(k),
         }
     }
 
-    pub fn get_mut<'m>(&'m mut self, k: &K) -> Option<&'m mut V> {
+    pub fn get_mut<'m, Q>(&'m mut self, k: &Q) -> Option<&'m mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
         match self {
             Self::Inline { data, len } => unsafe {
                 InlineHashMapIteratorMut::new(data, *len)
-                    .find(|(key, _)| key.eq(k))
+                    .find(|(key, _)| <K as Borrow<Q>>::borrow(key) == k)
                     .map(|(_, value)| value)
             },
+            Self::Spilled(vec) => vec
+                .iter_mut()
+                .find(|(key, _)| <K as Borrow<Q>>::borrow(key) == k)
+                .map(|(_, value)| value),
             Self::Heap(map) => map.get_mut(k),
         }
     }
 
-    pub fn remove(&mut self, key: &K) -> Option<V> {
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
         match self {
             Self::Inline { data, len } => {
                 let idx = data
                     .iter()
                     .take(*len)
                     .map(|x| unsafe { &*x.as_ptr() })
-                    .position(|x| &x.0 == key)?;
+                    .position(|x| <K as Borrow<Q>>::borrow(&x.0) == key)?;
 
                 let element = unsafe {
                     std::mem::replace(data.get_unchecked_mut(idx), MaybeUninit::uninit())
@@ -256,40 +401,63 @@ impl<K: Eq + Hash, V, const N: usize> InlineHashMapInner<K, V, N> {
 
                 Some(unsafe { element.assume_init().1 })
             }
+            Self::Spilled(vec) => {
+                let idx = vec
+                    .iter()
+                    .position(|(k, _)| <K as Borrow<Q>>::borrow(k) == key)?;
+                Some(vec.remove(idx).1)
+            }
             Self::Heap(h) => h.remove(key),
         }
     }
 
     pub fn insert(&mut self, k: K, v: V) {
-        let (array, len) = match self {
-            Self::Inline { data, len } => (data, len),
+        match *self {
+            Self::Inline { len, .. } if len >= N => self.move_to_heap(len + 1),
+            Self::Spilled(ref vec) if vec.len() >= SPILLED_LIMIT => {
+                self.move_to_heap(vec.len() + 1)
+            }
+            _ => {}
+        }
+
+        match self {
+            Self::Inline { data, len } => {
+                data[*len].write((k, v));
+                *len += 1;
+            }
+            // like the inline array, this does not replace an element with the same key
+            Self::Spilled(vec) => vec.push((k, v)),
             Self::Heap(map) => {
                 map.insert(k, v);
-                return;
             }
-        };
+        }
+    }
+
+    /// Moves the elements to a heap allocated vector or map (depending on `SPILLED_LIMIT`) with the given capacity
+    fn move_to_heap(&mut self, capacity: usize) {
+        let mut heap = Self::heap(capacity);
 
-        if *len >= N {
-            let mut map = HashMap::with_capacity(*len);
+        match self {
+            Self::Inline { data, len } => {
+                // move old elements to heap
+                for element in data.iter_mut().take(*len) {
+                    let element = std::mem::replace(element, MaybeUninit::uninit());
+                    let (key, value) = unsafe { element.assume_init() };
 
-            // move old elements to heap
-            for element in array.iter_mut().take(*len) {
-                let element = std::mem::replace(element, MaybeUninit::uninit());
-                let (key, value) = unsafe { element.assume_init() };
+                    heap.insert(key, value);
+                }
 
-                map.insert(key, value);
+                // do not call the destructor!
+                unsafe { ptr::write(self, heap) };
             }
+            Self::Spilled(vec) => {
+                for (key, value) in vec.drain(..) {
+                    heap.insert(key, value);
+                }
 
-            // insert new element
-            map.insert(k, v);
-            let new_heap = Self::Heap(map);
-
-            // do not call the destructor!
-            unsafe { ptr::write(self, new_heap) };
-            return;
-        } else {
-            array[*len].write((k, v));
-            *len += 1;
+                *self = heap;
+            }
+            Self::Heap(_) => {}
         }
     }
 
@@ -298,6 +466,7 @@ impl<K: Eq + Hash, V, const N: usize> InlineHashMapInner<K, V, N> {
             Self::Inline { data, len } => unsafe {
                 InlineHashMapIterator::new(data, *len).any(|(key, _)| key.eq(k))
             },
+            Self::Spilled(vec) => vec.iter().any(|(key, _)| key.eq(k)),
             Self::Heap(map) => map.contains_key(k),
         }
     }
@@ -383,6 +552,34 @@ mod tests {
         assert_eq!(iter.next(), Some((&"qux".into(), &9usize)));
     }
 
+    #[test]
+    fn inlinehashmap_iter_spilled() {
+        let mut x = InlineHashMap::<usize, usize, 2>::new();
+        for i in [5, 3, 8, 1] {
+            x.insert(i, i * 2);
+        }
+
+        // small maps that do not fit inline keep their order on the heap
+        assert!(x.is_heap_allocated());
+        let keys = x.iter().map(|(&k, _)| k).collect::<Vec<_>>();
+        assert_eq!(keys, [5, 3, 8, 1]);
+
+        assert_eq!(x.remove(&3), Some(6));
+        let keys = x.iter().map(|(&k, _)| k).collect::<Vec<_>>();
+        assert_eq!(keys, [5, 8, 1]);
+
+        // larger maps are hashed
+        for i in 10..20 {
+            x.insert(i, i * 2);
+        }
+        assert_eq!(x.len(), 13);
+        assert_eq!(x.get(&8), Some(&16));
+        assert_eq!(x.get(&19), Some(&38));
+
+        x.shrink_to_fit();
+        assert_eq!(x.get(&5), Some(&10));
+    }
+
     #[test]
     fn inlinehashmap_remove() {
         let mut x = InlineHashMap::<usize, usize, 4>::new();
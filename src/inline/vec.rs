@@ -20,6 +20,12 @@ impl<T, const N: usize> InlineVec<T, N> {
         self.0.len()
     }
 
+    /// Checks whether this vector is empty
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
     /// Checks whether this vector is allocated on the heap
     #[inline]
     pub fn is_heap_allocated(&self) -> bool {
@@ -76,11 +82,26 @@ impl<T, const N: usize> InlineVec<T, N> {
         self.0.iter()
     }
 
+    /// Inserts an element at the given index, shifting all elements after it to the right
+    ///
+    /// # Panics
+    /// Just like `Vec::insert`, this method will panic if `index > len`.
+    #[inline]
+    pub fn insert(&mut self, index: usize, value: T) {
+        self.0.insert(index, value)
+    }
+
     /// Returns a slice to the contents of this vector
     #[inline]
     pub fn as_slice(&self) -> &[T] {
         self.0.as_slice()
     }
+
+    /// Returns a mutable slice to the contents of this vector
+    #[inline]
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        self.0.as_mut_slice()
+    }
 }
 
 enum InlineVecInner<T, const N: usize> {
@@ -144,6 +165,15 @@ impl<T, const N: usize> InlineVecInner<T, N> {
         }
     }
 
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        match self {
+            Self::Heap(v) => v.as_mut_slice(),
+            Self::Inline { len, data } => unsafe {
+                std::slice::from_raw_parts_mut(data.as_mut_ptr() as *mut T, *len)
+            },
+        }
+    }
+
     #[inline]
     pub fn inline_parts_mut(&mut self) -> Option<(&mut [MaybeUninit<T>; N], usize)> {
         match self {
@@ -152,6 +182,13 @@ impl<T, const N: usize> InlineVecInner<T, N> {
         }
     }
 
+    pub fn insert(&mut self, idx: usize, value: T) {
+        assert!(idx <= self.len());
+
+        self.push(value);
+        self.as_mut_slice()[idx..].rotate_right(1);
+    }
+
     pub fn to_vec(&self) -> Vec<T>
     where
         T: Clone,
@@ -161,7 +198,7 @@ impl<T, const N: usize> InlineVecInner<T, N> {
             InlineVecInner::Inline { len, data } => {
                 let mut new_data = Vec::with_capacity(*len);
 
-                let iter = data.into_iter().take(*len);
+                let iter = data.iter().take(*len);
 
                 for element in iter {
                     new_data.push(unsafe { T::clone(&*element.as_ptr()) });
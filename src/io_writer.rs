@@ -0,0 +1,65 @@
+use std::fmt;
+use std::io;
+
+/// An adapter that implements [`fmt::Write`] for an [`io::Write`], so that the `write_*` methods
+/// (such as `VDom::write_outer_html()`) can serialize into files, sockets or other byte sinks
+///
+/// Since `fmt::Error` carries no information, the first I/O error is stored in the adapter,
+/// and is returned by [`IoWriter::finish()`]. Nothing is written after an error.
+///
+/// # Example
+/// ```no_run
+/// use std::fs::File;
+/// use std::io::{BufWriter, Write};
+/// use tl::IoWriter;
+///
+/// let dom = tl::parse("<p>Hello</p>", Default::default()).unwrap();
+///
+/// let mut out = IoWriter::new(BufWriter::new(File::create("out.html")?));
+/// let result = dom.write_outer_html(&mut out);
+/// out.finish(result)?.flush()?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct IoWriter<W> {
+    inner: W,
+    /// The first error returned by `inner`
+    error: Option<io::Error>,
+}
+
+impl<W: io::Write> IoWriter<W> {
+    /// Creates an adapter that writes into the given writer
+    pub fn new(inner: W) -> Self {
+        Self { inner, error: None }
+    }
+
+    /// Returns a reference to the underlying writer
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Converts the result of writing into this adapter into an I/O result, and returns the underlying writer on success
+    ///
+    /// If the underlying writer failed, its error is returned. Otherwise, if `result` is an error
+    /// (e.g. because a `fmt::Display` implementation failed), an error of kind [`io::ErrorKind::Other`] is returned.
+    pub fn finish(self, result: fmt::Result) -> io::Result<W> {
+        match (self.error, result) {
+            (Some(error), _) => Err(error),
+            (None, Err(fmt::Error)) => Err(io::Error::other("formatter error")),
+            (None, Ok(())) => Ok(self.inner),
+        }
+    }
+}
+
+impl<W: io::Write> fmt::Write for IoWriter<W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.error.is_some() {
+            return Err(fmt::Error);
+        }
+
+        self.inner.write_all(s.as_bytes()).map_err(|error| {
+            self.error = Some(error);
+            fmt::Error
+        })
+    }
+}
@@ -1,20 +1,45 @@
 #![cfg_attr(feature = "simd", feature(portable_simd))]
 #![doc = include_str!("../README.md")]
 #![deny(missing_docs)]
+// `Option::is_none_or()` would need a newer Rust version than the rest of the crate
+#![allow(clippy::unnecessary_map_or)]
 
+mod batch;
 mod bytes;
+mod dimension;
+mod elements;
+mod entities;
 /// Errors that occur throughout the crate
 pub mod errors;
 /// Inline data structures
 pub mod inline;
+mod io_writer;
+mod link_rel;
+#[cfg(feature = "url")]
+mod link_set;
+mod meta_refresh;
+#[cfg(feature = "mmap")]
+mod mmap;
+mod mutation;
+mod outline;
 mod parser;
+/// Re-exports of the items that are needed for most uses of this library
+pub mod prelude;
 /// Query selector API
 pub mod queryselector;
 mod stream;
+mod streaming;
+mod subtree;
+/// HTML tag category classification
+pub mod tag_info;
 #[cfg(test)]
 mod tests;
+mod text;
 mod util;
+/// Document validation (linting)
+pub mod validate;
 mod vdom;
+mod walk;
 
 #[doc(hidden)]
 #[cfg(feature = "__INTERNALS_DO_NOT_USE")]
@@ -22,11 +47,34 @@ pub mod simd;
 #[cfg(not(feature = "__INTERNALS_DO_NOT_USE"))]
 mod simd;
 
+pub use batch::Batch;
 pub use bytes::Bytes;
-pub use errors::ParseError;
+pub use dimension::Dimension;
+pub use elements::{
+    Anchor, Image, ListItem, ListNode, Meta, Select, SelectOption, SrcsetCandidate,
+    SrcsetDescriptor, DEFAULT_IMAGE_SOURCE_ATTRIBUTES,
+};
+#[cfg(feature = "mmap")]
+pub use errors::FileParseError;
+pub use errors::{
+    ElementBuilderError, InsertError, MoveError, ParseError, SetBytesError, XmlExportError,
+};
+pub use io_writer::IoWriter;
+pub use link_rel::LinkRel;
+#[cfg(feature = "url")]
+pub use link_set::{LinkSet, NormalizeOptions};
+pub use meta_refresh::MetaRefresh;
+#[cfg(feature = "mmap")]
+pub use mmap::VDomFile;
+pub use mutation::{AttributesGuard, Mutation};
+pub use outline::{OutlineEntry, OutlineOptions};
 pub use parser::*;
-use queryselector::Selector;
-pub use vdom::{VDom, VDomGuard};
+pub use queryselector::{QuerySelectorIterator, QuerySelectorTags, Selector, SelectorOptions};
+pub use streaming::{stream_select, MatchEvent, SelectorSet};
+pub use subtree::SubtreeView;
+pub use text::{TextFingerprintOptions, TextJoinOptions};
+pub use vdom::{VDom, VDomGuard, VDomShared};
+pub use walk::{NodeVisitor, WalkControl};
 
 /// Parses the given input string
 ///
@@ -61,15 +109,56 @@ pub fn parse(input: &str, options: ParserOptions) -> Result<VDom<'_>, ParseError
 ///
 /// match selector {
 ///     Some(Selector::And(left, right)) => {
-///         assert!(matches!(&*left, Selector::Tag(b"div")));
-///         assert!(matches!(&*right, Selector::Id(b"test")));
+///         assert!(matches!(&*left, Selector::Tag(tag) if &**tag == b"div"));
+///         assert!(matches!(&*right, Selector::Id(id) if &**id == b"test"));
 ///     },
 ///     _ => unreachable!()
 /// }
 /// ```
 pub fn parse_query_selector(input: &str) -> Option<Selector<'_>> {
-    let selector = queryselector::Parser::new(input.as_bytes()).selector()?;
-    Some(selector)
+    parse_query_selector_checked(input).ok()
+}
+
+/// Parses a query selector, and returns an error describing what went wrong and where if it is invalid
+///
+/// # Example
+/// ```
+/// use tl::queryselector::ParseErrorKind;
+///
+/// assert!(tl::parse_query_selector_checked("div > p").is_ok());
+///
+/// let error = tl::parse_query_selector_checked("a[href").unwrap_err();
+/// assert_eq!(error.kind(), ParseErrorKind::UnterminatedAttribute);
+/// assert_eq!(error.offset(), 1);
+/// ```
+pub fn parse_query_selector_checked(
+    input: &str,
+) -> Result<Selector<'_>, queryselector::ParseError> {
+    queryselector::Parser::new(input.as_bytes()).selector()
+}
+
+/// Parses a query selector list, skipping entries that cannot be parsed instead of rejecting the whole list
+///
+/// Returns the valid entries combined with `Selector::Or` (`None` if there are none), and the text of the skipped entries
+/// without surrounding whitespace. This mirrors the forgiving selector lists of CSS, as used by `:is()`.
+///
+/// # Example
+/// ```
+/// let (selector, skipped) = tl::parse_query_selector_forgiving("div, input:focus-visible, .x");
+///
+/// assert!(selector.is_some());
+/// assert_eq!(skipped, ["input:focus-visible"]);
+/// ```
+pub fn parse_query_selector_forgiving(input: &str) -> (Option<Selector<'_>>, Vec<&str>) {
+    let (selector, skipped) = queryselector::Parser::new(input.as_bytes()).selector_forgiving();
+
+    // entries start and end at ASCII characters, so the ranges are on character boundaries
+    let skipped = skipped
+        .into_iter()
+        .map(|range| util::trim_ascii_whitespace(&input[range]))
+        .collect();
+
+    (selector, skipped)
 }
 
 /// Parses the given input string and returns an owned, RAII guarded DOM
@@ -85,3 +174,50 @@ pub fn parse_query_selector(input: &str) -> Option<Selector<'_>> {
 pub unsafe fn parse_owned(input: String, options: ParserOptions) -> Result<VDomGuard, ParseError> {
     VDomGuard::parse(input, options)
 }
+
+/// Parses the given input string and returns an owned DOM that can be shared between threads and is cheap to clone
+///
+/// Unlike `VDomGuard`, the returned [`VDomShared`] is read-only and never hands out references to the document
+/// that outlive it, which makes this function safe to call.
+///
+/// # Errors
+/// See [parse]
+pub fn parse_shared(input: String, options: ParserOptions) -> Result<VDomShared, ParseError> {
+    VDomGuard::parse(input, options).map(VDomShared::from)
+}
+
+/// Memory-maps the file at the given path and parses it, without reading it into memory first
+///
+/// This is like `parse_owned()`, but the returned [`VDomFile`] owns the mapped file instead of a string,
+/// which keeps the peak memory usage low for large files. Only available with the `mmap` feature.
+///
+/// # Errors
+/// Returns an error if the file cannot be opened or mapped, if it is not valid UTF-8,
+/// or if parsing fails (see [parse]).
+///
+/// # Safety
+/// The nodes borrow from the mapped file, so the file must not be modified or truncated while the returned `VDomFile` is alive,
+/// e.g. by another process. Otherwise the document may change or the process may crash when it is accessed.
+///
+/// # Example
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let path = std::env::temp_dir().join("tl-parse-file-doctest.html");
+/// std::fs::write(&path, "<p id=greeting>Hello</p>")?;
+///
+/// let file = unsafe { tl::parse_file(&path, Default::default())? };
+/// let dom = file.get_ref();
+/// assert!(dom.get_element_by_id("greeting").is_some());
+///
+/// drop(file);
+/// std::fs::remove_file(&path)?;
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "mmap")]
+pub unsafe fn parse_file<P: AsRef<std::path::Path>>(
+    path: P,
+    options: ParserOptions,
+) -> Result<VDomFile, FileParseError> {
+    VDomFile::parse(path.as_ref(), options)
+}
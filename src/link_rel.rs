@@ -0,0 +1,118 @@
+use crate::{util, HTMLTag};
+
+/// MIME types of `<link rel="alternate">` elements that refer to feeds
+const FEED_TYPES: &[&str] = &[
+    "application/rss+xml",
+    "application/atom+xml",
+    "application/feed+json",
+];
+
+/// Link types that declare an icon of the document
+const ICON_RELS: &[&str] = &["icon", "apple-touch-icon", "apple-touch-icon-precomposed"];
+
+/// The relation declared by a `<link rel="...">` element
+///
+/// Obtained by calling `VDom::link_relations()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkRel {
+    /// The link types of the `rel` attribute, in lowercase
+    pub rel: Vec<String>,
+    /// The normalized `href` attribute
+    pub href: Option<String>,
+    /// The `type` attribute (the MIME type of the linked resource)
+    pub mime_type: Option<String>,
+    /// The `hreflang` attribute
+    pub hreflang: Option<String>,
+    /// The `media` attribute
+    pub media: Option<String>,
+    /// The `sizes` attribute
+    pub sizes: Option<String>,
+}
+
+impl LinkRel {
+    /// Reads the link relation of a `<link>` element, or returns `None` if the element has no `rel` attribute
+    pub(crate) fn from_tag(tag: &HTMLTag<'_>) -> Option<Self> {
+        if !tag.name().as_bytes().eq_ignore_ascii_case(b"link") {
+            return None;
+        }
+
+        let attributes = tag.attributes();
+        let rel = attributes
+            .token_list("rel")?
+            .map(str::to_ascii_lowercase)
+            .collect();
+
+        let string = |name: &'static str| {
+            attributes
+                .get(name)
+                .flatten()
+                .map(|value| util::trim_ascii_whitespace(&value.as_utf8_str()).to_owned())
+        };
+
+        Some(Self {
+            rel,
+            href: attributes.get_url("href").map(Into::into),
+            mime_type: string("type"),
+            hreflang: string("hreflang"),
+            media: string("media"),
+            sizes: string("sizes"),
+        })
+    }
+
+    /// Checks whether the `rel` attribute contains the given link type (ASCII case-insensitive)
+    pub fn has_rel(&self, rel: &str) -> bool {
+        self.rel.iter().any(|x| x.eq_ignore_ascii_case(rel))
+    }
+
+    /// Checks whether this link refers to a feed, i.e. it is an alternate representation with a feed MIME type
+    pub fn is_feed(&self) -> bool {
+        self.has_rel("alternate")
+            && self.mime_type.as_deref().is_some_and(|mime_type| {
+                FEED_TYPES
+                    .iter()
+                    .any(|feed| mime_type.eq_ignore_ascii_case(feed))
+            })
+    }
+
+    /// Checks whether this link refers to an icon (`icon`, `shortcut icon` or `apple-touch-icon`)
+    pub fn is_icon(&self) -> bool {
+        ICON_RELS.iter().any(|rel| self.has_rel(rel))
+    }
+
+    /// Returns the sizes of the `sizes` attribute as `(width, height)` pairs
+    ///
+    /// Invalid sizes and the `any` keyword are skipped (see [`LinkRel::is_any_size()`]).
+    pub fn parsed_sizes(&self) -> Vec<(u32, u32)> {
+        util::split_ascii_whitespace(self.sizes.as_deref().unwrap_or_default())
+            .filter_map(|size| {
+                let (width, height) = size.split_once(['x', 'X'])?;
+                let valid = |x: &str| !x.starts_with('0') && x.bytes().all(|c| c.is_ascii_digit());
+
+                if !valid(width) || !valid(height) {
+                    return None;
+                }
+
+                Some((width.parse().ok()?, height.parse().ok()?))
+            })
+            .collect()
+    }
+
+    /// Checks whether the `sizes` attribute contains the `any` keyword, which is used for scalable icons
+    pub fn is_any_size(&self) -> bool {
+        util::split_ascii_whitespace(self.sizes.as_deref().unwrap_or_default())
+            .any(|size| size.eq_ignore_ascii_case("any"))
+    }
+
+    /// Returns a key that orders icons by their largest size
+    pub(crate) fn size_key(&self) -> u64 {
+        if self.is_any_size() {
+            return u64::MAX;
+        }
+
+        self.parsed_sizes()
+            .into_iter()
+            .map(|(width, height)| u64::from(width) * u64::from(height))
+            .max()
+            .unwrap_or(0)
+    }
+}
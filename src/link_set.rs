@@ -0,0 +1,217 @@
+use std::borrow::Cow;
+use std::collections::HashSet;
+
+use url::Url;
+
+use crate::NodeHandle;
+
+/// Options for normalizing URLs before they are compared, used by [`LinkSet`] and `VDom::links_deduped()`
+///
+/// Parsing a URL already lowercases the scheme and the host, removes default ports (e.g. `:443` for `https`)
+/// and resolves `.` and `..` path segments. On top of that, the hexadecimal digits of percent-encoded bytes
+/// are always uppercased, so that `%2f` and `%2F` compare equal. The other normalizations are configurable.
+///
+/// # Example
+/// ```
+/// use tl::NormalizeOptions;
+///
+/// let options = NormalizeOptions {
+///     sort_query: true,
+///     ..Default::default()
+/// };
+///
+/// let mut url = url::Url::parse("HTTPS://Example.com:443/a%2fb/?b=2&a=1#top").unwrap();
+/// options.normalize(&mut url);
+/// assert_eq!(url.as_str(), "https://example.com/a%2Fb?a=1&b=2");
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct NormalizeOptions {
+    /// Whether the fragment (the part after `#`) is removed. Enabled by default
+    pub strip_fragment: bool,
+    /// Whether a trailing slash is removed from the path, unless the path is just `/`. Enabled by default
+    pub strip_trailing_slash: bool,
+    /// Whether the query parameters are sorted by name. Disabled by default, since the order can be significant
+    ///
+    /// The sort is stable, so parameters with the same name keep their relative order.
+    pub sort_query: bool,
+}
+
+impl Default for NormalizeOptions {
+    fn default() -> Self {
+        Self {
+            strip_fragment: true,
+            strip_trailing_slash: true,
+            sort_query: false,
+        }
+    }
+}
+
+impl NormalizeOptions {
+    /// Normalizes the given URL in place according to these options
+    pub fn normalize(&self, url: &mut Url) {
+        uppercase_percent_encodings(url);
+
+        if self.strip_fragment {
+            url.set_fragment(None);
+        }
+
+        if self.strip_trailing_slash && !url.cannot_be_a_base() {
+            let path = url.path();
+            if path.len() > 1 && path.ends_with('/') {
+                let path = path[..path.len() - 1].to_owned();
+                url.set_path(&path);
+            }
+        }
+
+        if self.sort_query {
+            if let Some(query) = url.query() {
+                let mut pairs = query.split('&').collect::<Vec<_>>();
+                pairs.sort_by_key(|pair| pair.split('=').next());
+
+                let query = pairs.join("&");
+                url.set_query(Some(&query));
+            }
+        }
+    }
+}
+
+/// A collection of links that ignores links whose normalized URL was already inserted, obtained by calling `VDom::links_deduped()`
+///
+/// The links are kept in insertion order, together with the handle of the element that the first occurrence of each URL was found on.
+///
+/// # Example
+/// ```
+/// use tl::{LinkSet, NodeHandle, NormalizeOptions};
+/// use url::Url;
+///
+/// let mut links = LinkSet::new(NormalizeOptions::default());
+/// assert!(links.insert(NodeHandle::new(0), Url::parse("https://example.com/docs/").unwrap()));
+/// assert!(!links.insert(NodeHandle::new(1), Url::parse("https://example.com/docs#intro").unwrap()));
+///
+/// assert_eq!(links.len(), 1);
+/// assert!(links.contains(&Url::parse("https://EXAMPLE.com:443/docs").unwrap()));
+/// ```
+#[derive(Debug, Clone)]
+pub struct LinkSet {
+    options: NormalizeOptions,
+    links: Vec<(NodeHandle, Url)>,
+    seen: HashSet<Url>,
+}
+
+impl LinkSet {
+    /// Creates an empty set that compares links after normalizing them with the given options
+    pub fn new(options: NormalizeOptions) -> Self {
+        Self {
+            options,
+            links: Vec::new(),
+            seen: HashSet::new(),
+        }
+    }
+
+    /// Returns the options that links are normalized with
+    pub fn options(&self) -> NormalizeOptions {
+        self.options
+    }
+
+    /// Normalizes the URL and inserts it with the handle of the element it was found on
+    ///
+    /// Returns `false` (and keeps the link that was inserted first) if an equal URL is already in the set.
+    pub fn insert(&mut self, handle: NodeHandle, mut url: Url) -> bool {
+        self.options.normalize(&mut url);
+
+        if self.seen.contains(&url) {
+            return false;
+        }
+
+        self.seen.insert(url.clone());
+        self.links.push((handle, url));
+        true
+    }
+
+    /// Checks whether the set contains a URL that is equal to the given URL after normalization
+    pub fn contains(&self, url: &Url) -> bool {
+        let mut url = url.clone();
+        self.options.normalize(&mut url);
+        self.seen.contains(&url)
+    }
+
+    /// Returns the number of distinct links
+    pub fn len(&self) -> usize {
+        self.links.len()
+    }
+
+    /// Checks whether the set is empty
+    pub fn is_empty(&self) -> bool {
+        self.links.is_empty()
+    }
+
+    /// Returns an iterator over the normalized links and the handles of their elements, in insertion order
+    pub fn iter(&self) -> impl Iterator<Item = (NodeHandle, &Url)> + '_ {
+        self.links.iter().map(|(handle, url)| (*handle, url))
+    }
+
+    /// Returns an iterator over the normalized URLs, in insertion order
+    pub fn urls(&self) -> impl Iterator<Item = &Url> + '_ {
+        self.links.iter().map(|(_, url)| url)
+    }
+}
+
+impl IntoIterator for LinkSet {
+    type Item = (NodeHandle, Url);
+    type IntoIter = std::vec::IntoIter<(NodeHandle, Url)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.links.into_iter()
+    }
+}
+
+/// Parses the given URL attribute value, whose character references must already be decoded,
+/// and uppercases its percent-encodings
+///
+/// Leading and trailing C0 controls and spaces as well as ASCII tabs and newlines are removed by the URL parser,
+/// like in `util::normalize_url()`.
+pub(crate) fn parse(input: &str, base: Option<&Url>) -> Option<Url> {
+    let mut url = Url::options().base_url(base).parse(input).ok()?;
+    uppercase_percent_encodings(&mut url);
+    Some(url)
+}
+
+/// Uppercases the hexadecimal digits of all percent-encoded bytes in the path, query and fragment of the URL
+fn uppercase_percent_encodings(url: &mut Url) {
+    if let Cow::Owned(path) = uppercase_hex(url.path()) {
+        url.set_path(&path);
+    }
+
+    if let Some(Cow::Owned(query)) = url.query().map(uppercase_hex) {
+        url.set_query(Some(&query));
+    }
+
+    if let Some(Cow::Owned(fragment)) = url.fragment().map(uppercase_hex) {
+        url.set_fragment(Some(&fragment));
+    }
+}
+
+/// Uppercases the two hexadecimal digits after every `%`, returning a borrowed string if there are none to change
+fn uppercase_hex(input: &str) -> Cow<'_, str> {
+    let bytes = input.as_bytes();
+    let needs_change = |i: usize| {
+        bytes[i] == b'%'
+            && bytes.get(i + 1..i + 3).is_some_and(|hex| {
+                hex.iter().all(u8::is_ascii_hexdigit) && hex.iter().any(u8::is_ascii_lowercase)
+            })
+    };
+
+    if !(0..bytes.len()).any(needs_change) {
+        return Cow::Borrowed(input);
+    }
+
+    let mut output = bytes.to_vec();
+    for i in 0..bytes.len() {
+        if needs_change(i) {
+            output[i + 1..i + 3].make_ascii_uppercase();
+        }
+    }
+
+    // only ASCII letters were changed, so the output is still valid UTF-8
+    Cow::Owned(String::from_utf8(output).expect("uppercasing ASCII keeps UTF-8 valid"))
+}
@@ -0,0 +1,74 @@
+use crate::util;
+
+/// A redirect declared by a `<meta http-equiv="refresh">` element
+///
+/// Obtained by calling `VDom::meta_refresh()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetaRefresh {
+    /// The number of seconds to wait before refreshing
+    pub delay: u64,
+    /// The URL to navigate to, or `None` if the current document should be reloaded
+    pub url: Option<String>,
+}
+
+impl MetaRefresh {
+    /// Parses the `content` attribute of a `<meta http-equiv="refresh">` element
+    ///
+    /// This follows the forgiving algorithm of the HTML specification ("shared declarative refresh steps"):
+    /// the delay may be followed by a fraction that is ignored, the URL may be separated by `;` or `,`,
+    /// prefixed by `url=` (case-insensitive, with optional spaces) and quoted.
+    /// Returns `None` if the content does not start with a delay, or if the delay is followed by anything other than a separator.
+    pub fn parse(content: &str) -> Option<Self> {
+        let is_whitespace = util::is_ascii_whitespace_char;
+        let input = content.trim_start_matches(is_whitespace);
+
+        let digits = input.len() - input.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        let delay = input[..digits].bytes().fold(0u64, |delay, digit| {
+            delay
+                .saturating_mul(10)
+                .saturating_add(u64::from(digit - b'0'))
+        });
+
+        // a fraction (e.g. `1.5`) is allowed, but ignored
+        let rest = &input[digits..];
+        let rest = rest.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+        if digits == 0 && rest.len() == input.len() {
+            return None;
+        }
+
+        if !rest.is_empty() && !rest.starts_with([';', ',']) && !rest.starts_with(is_whitespace) {
+            return None;
+        }
+
+        let rest = rest.trim_start_matches(is_whitespace);
+        let rest = rest.strip_prefix([';', ',']).unwrap_or(rest);
+        let rest = rest.trim_start_matches(is_whitespace);
+
+        let rest = match rest.get(..3) {
+            Some(prefix) if prefix.eq_ignore_ascii_case("url") => {
+                let after = rest[3..].trim_start_matches(is_whitespace);
+
+                match after.strip_prefix('=') {
+                    Some(after) => after.trim_start_matches(is_whitespace),
+                    None => rest,
+                }
+            }
+            _ => rest,
+        };
+
+        let url = match rest.strip_prefix(['"', '\'']) {
+            Some(quoted) => {
+                let quote = rest.as_bytes()[0] as char;
+                quoted.split(quote).next().unwrap_or_default()
+            }
+            None => rest,
+        };
+
+        let url = util::normalize_url(url);
+
+        Some(Self {
+            delay,
+            url: (!url.is_empty()).then(|| url.into_owned()),
+        })
+    }
+}
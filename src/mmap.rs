@@ -0,0 +1,57 @@
+use std::fs::File;
+use std::path::Path;
+
+use memmap2::Mmap;
+
+use crate::errors::FileParseError;
+use crate::{Parser, ParserOptions, VDom};
+
+/// A DOM parsed from a memory-mapped file, which keeps the file mapped while it is alive
+///
+/// The file is unmapped once this struct goes out of scope. The only way to construct this is by calling `parse_file()`.
+#[derive(Debug)]
+pub struct VDomFile {
+    /// Wrapped VDom instance, which borrows from the map and is declared first so that it is dropped before it
+    dom: VDom<'static>,
+    /// The mapped file that is referenced by self.dom
+    _map: Mmap,
+}
+
+// SAFETY: the raw pointers in the DOM refer either to owned data or to the mapped file, which is owned by the guard
+// and never mutated through it. As with `VDomGuard`, only shared references are handed out.
+unsafe impl Send for VDomFile {}
+unsafe impl Sync for VDomFile {}
+
+impl VDomFile {
+    /// Maps and parses the file at the given path
+    ///
+    /// # Safety
+    /// See `parse_file()`
+    pub(crate) unsafe fn parse(
+        path: &Path,
+        options: ParserOptions,
+    ) -> Result<VDomFile, FileParseError> {
+        let file = File::open(path).map_err(FileParseError::Io)?;
+        let map = Mmap::map(&file).map_err(FileParseError::Io)?;
+
+        let input = std::str::from_utf8(&map).map_err(FileParseError::InvalidUtf8)?;
+
+        // the mapping stays at the same address when the map is moved, and it lives as long as the DOM
+        let input: &'static str = &*(input as *const str);
+
+        let mut parser = Parser::new(input, options);
+        parser.parse().map_err(FileParseError::Parse)?;
+
+        Ok(Self {
+            dom: VDom::from(parser),
+            _map: map,
+        })
+    }
+
+    /// Returns a reference to the inner DOM.
+    ///
+    /// The lifetime of the returned `VDom` is bound to self so that elements cannot outlive this `VDomFile` struct.
+    pub fn get_ref<'a>(&'a self) -> &'a VDom<'a> {
+        &self.dom
+    }
+}
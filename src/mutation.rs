@@ -0,0 +1,190 @@
+use std::{fmt, ops::Deref};
+
+use crate::{Attributes, Bytes, NodeHandle, Parser};
+
+/// A change made to a document by one of the mutation methods of `VDom`
+///
+/// Mutations are reported to the observer set with `VDom::set_mutation_observer()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Mutation {
+    /// An attribute was added, or its value was replaced
+    AttributeSet {
+        /// The element whose attribute was set
+        handle: NodeHandle,
+        /// The name of the attribute
+        name: String,
+    },
+    /// An attribute was removed
+    AttributeRemoved {
+        /// The element whose attribute was removed
+        handle: NodeHandle,
+        /// The name of the attribute
+        name: String,
+    },
+    /// A node was inserted into the children of `parent` (or the topmost nodes if `None`) at the given index
+    NodeInserted {
+        /// The inserted node
+        handle: NodeHandle,
+        /// The new parent of the node
+        parent: Option<NodeHandle>,
+        /// The index of the node among the children of its parent
+        index: usize,
+    },
+    /// A node was removed from its parent
+    ///
+    /// Nodes that are moved are reported as removed and inserted again.
+    NodeRemoved {
+        /// The removed node
+        handle: NodeHandle,
+    },
+    /// The text of a text node was changed
+    TextChanged {
+        /// The text node
+        handle: NodeHandle,
+    },
+}
+
+/// The callback set with `VDom::set_mutation_observer()`
+pub(crate) struct MutationObserver(pub(crate) Box<dyn FnMut(Mutation)>);
+
+impl MutationObserver {
+    /// Reports the mutation created by the given function
+    #[inline]
+    pub(crate) fn notify(&mut self, mutation: impl FnOnce() -> Mutation) {
+        (self.0)(mutation());
+    }
+}
+
+impl fmt::Debug for MutationObserver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MutationObserver").finish_non_exhaustive()
+    }
+}
+
+/// Mutable access to the attributes of an element that reports changes to the mutation observer
+///
+/// Obtained by calling `VDom::attributes_mut()`. The attributes can be read through `Deref`.
+/// Changes made through the guard keep the lookup tables of the document in sync: if ids, classes or the changed attribute
+/// are tracked (see `ParserOptions::track_ids()`), the element is registered under its new value and no longer found by its old value.
+/// This is not the case for changes made to the attributes of an element directly, e.g. with `HTMLTag::attributes_mut()`.
+pub struct AttributesGuard<'v, 'a> {
+    pub(crate) handle: NodeHandle,
+    /// The position of the element in the node table
+    pub(crate) position: usize,
+    pub(crate) parser: &'v mut Parser<'a>,
+    pub(crate) observer: Option<&'v mut MutationObserver>,
+}
+
+impl<'v, 'a> AttributesGuard<'v, 'a> {
+    fn notify(&mut self, mutation: impl FnOnce() -> Mutation) {
+        if let Some(observer) = &mut self.observer {
+            observer.notify(mutation);
+        }
+    }
+
+    /// Applies a change to the attribute with the given name, and updates the lookup tables if they contain it
+    fn update<R>(&mut self, name: &[u8], change: impl FnOnce(&mut Attributes<'a>) -> R) -> R {
+        let is_indexed = self.parser.is_indexed_attribute(name);
+        if is_indexed {
+            self.parser.untrack(self.position..self.position + 1);
+        }
+
+        let tag = self.parser.tags[self.position]
+            .as_tag_mut()
+            .expect("the guard refers to an element");
+        let result = change(tag.attributes_mut());
+
+        if is_indexed {
+            self.parser.track_inserted(self.handle);
+        }
+        result
+    }
+
+    /// Inserts or replaces an attribute, see `Attributes::insert()`
+    pub fn insert<K, V>(&mut self, key: K, value: Option<V>)
+    where
+        K: Into<Bytes<'a>>,
+        V: Into<Bytes<'a>>,
+    {
+        let key: Bytes = key.into();
+        let handle = self.handle;
+
+        self.notify(|| Mutation::AttributeSet {
+            handle,
+            name: key.as_utf8_str().into_owned(),
+        });
+        let name = key.clone();
+        self.update(name.as_bytes(), |attributes| attributes.insert(key, value));
+    }
+
+    /// Removes an attribute and returns its value if it existed, see `Attributes::remove()`
+    pub fn remove<B>(&mut self, key: B) -> Option<Option<Bytes<'a>>>
+    where
+        B: Into<Bytes<'a>>,
+    {
+        let key: Bytes = key.into();
+        let removed = self.update(key.as_bytes(), |attributes| attributes.remove(key.clone()))?;
+        let handle = self.handle;
+
+        self.notify(|| Mutation::AttributeRemoved {
+            handle,
+            name: key.as_utf8_str().into_owned(),
+        });
+        Some(removed)
+    }
+
+    /// Adds a class name to the `class` attribute, see `Attributes::add_class()`
+    pub fn add_class(&mut self, name: &str) -> bool {
+        let added = self.update(b"class", |attributes| attributes.add_class(name));
+        if added {
+            self.notify_class_set();
+        }
+        added
+    }
+
+    /// Removes a class name from the `class` attribute, see `Attributes::remove_class()`
+    pub fn remove_class(&mut self, name: &str) -> bool {
+        let removed = self.update(b"class", |attributes| attributes.remove_class(name));
+        if removed {
+            match self.class() {
+                Some(_) => self.notify_class_set(),
+                None => {
+                    let handle = self.handle;
+                    self.notify(|| Mutation::AttributeRemoved {
+                        handle,
+                        name: String::from("class"),
+                    });
+                }
+            }
+        }
+        removed
+    }
+
+    /// Toggles a class name in the `class` attribute, see `Attributes::toggle_class()`
+    pub fn toggle_class(&mut self, name: &str) -> bool {
+        match self.has_class(name) {
+            true => !self.remove_class(name),
+            false => self.add_class(name),
+        }
+    }
+
+    fn notify_class_set(&mut self) {
+        let handle = self.handle;
+        self.notify(|| Mutation::AttributeSet {
+            handle,
+            name: String::from("class"),
+        });
+    }
+}
+
+impl<'v, 'a> Deref for AttributesGuard<'v, 'a> {
+    type Target = Attributes<'a>;
+
+    fn deref(&self) -> &Self::Target {
+        self.parser.tags[self.position]
+            .as_tag()
+            .expect("the guard refers to an element")
+            .attributes()
+    }
+}
@@ -0,0 +1,117 @@
+use crate::{util, HTMLTag, Node, NodeHandle, Parser, VDom};
+
+/// Options for building the outline of a document with `VDom::outline_with()`
+///
+/// # Example
+/// ```
+/// let dom = tl::parse("<nav><h2>Menu</h2></nav><h1>Title</h1>", Default::default()).unwrap();
+///
+/// let options = tl::OutlineOptions { skip_nav_and_aside: true };
+/// let outline = dom.outline_with(options);
+/// assert_eq!(outline.len(), 1);
+/// assert_eq!(outline[0].text, "Title");
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct OutlineOptions {
+    /// Whether headings inside `<nav>` and `<aside>` elements (usually menus and sidebars) are left out
+    pub skip_nav_and_aside: bool,
+}
+
+/// A heading in the outline of a document, obtained by calling `VDom::outline()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutlineEntry {
+    /// The heading element
+    pub handle: NodeHandle,
+    /// The rank of the heading, from 1 for `<h1>` to 6 for `<h6>`
+    pub level: u8,
+    /// The text of the heading, with character references decoded and ASCII whitespace stripped and collapsed
+    pub text: String,
+    /// The decoded `id` attribute of the heading, which can be used as a link target
+    pub id: Option<String>,
+    /// The nodes after the heading up to the next heading in the outline of the same or a higher rank, in document order
+    ///
+    /// This includes the headings in `children` and their content. See `VDom::section_after_heading()`.
+    pub content: Vec<NodeHandle>,
+    /// The headings of a lower rank that follow this heading, up to the next heading of the same or a higher rank
+    ///
+    /// Levels may be skipped, so the children of an `<h2>` can be `<h4>` headings.
+    pub children: Vec<OutlineEntry>,
+}
+
+/// Builds the outline of the given document
+pub(crate) fn build(dom: &VDom<'_>, options: OutlineOptions) -> Vec<OutlineEntry> {
+    let parser = dom.parser();
+
+    let headings = dom
+        .nodes()
+        .iter()
+        .enumerate()
+        .filter_map(|(position, node)| {
+            let level = crate::vdom::heading_level(node)?;
+            Some((parser.handle_at(position), node.as_tag()?, level))
+        })
+        .filter(|&(handle, ..)| !options.skip_nav_and_aside || !is_in_nav_or_aside(handle, parser))
+        .collect::<Vec<_>>();
+
+    let mut roots = Vec::new();
+    let mut open = Vec::new();
+
+    for (index, &(handle, tag, level)) in headings.iter().enumerate() {
+        let end = headings[index + 1..]
+            .iter()
+            .find(|&&(.., other)| other <= level)
+            .map(|&(end, ..)| end);
+
+        let text = tag.inner_text_decoded(parser);
+
+        close_entries(&mut open, &mut roots, level);
+        open.push(OutlineEntry {
+            handle,
+            level,
+            text: util::split_ascii_whitespace(&text)
+                .collect::<Vec<_>>()
+                .join(" "),
+            id: tag
+                .attributes()
+                .id()
+                .map(|id| id.as_decoded_str().into_owned()),
+            content: dom.section_until(handle, end).collect(),
+            children: Vec::new(),
+        });
+    }
+
+    close_entries(&mut open, &mut roots, 0);
+    roots
+}
+
+/// Closes the open entries of the given or a lower rank (i.e. a level that is at least `level`),
+/// and adds them to the children of their parent entry or to the topmost entries
+fn close_entries(open: &mut Vec<OutlineEntry>, roots: &mut Vec<OutlineEntry>, level: u8) {
+    while open.last().is_some_and(|entry| entry.level >= level) {
+        let entry = open.pop().unwrap();
+
+        match open.last_mut() {
+            Some(parent) => parent.children.push(entry),
+            None => roots.push(entry),
+        }
+    }
+}
+
+/// Checks whether the given node has a `<nav>` or `<aside>` ancestor
+fn is_in_nav_or_aside(handle: NodeHandle, parser: &Parser<'_>) -> bool {
+    let mut parent = handle
+        .get(parser)
+        .and_then(Node::as_tag)
+        .and_then(HTMLTag::parent);
+
+    while let Some(tag) = parent.and_then(|x| x.get(parser)).and_then(Node::as_tag) {
+        let name = tag.name().as_bytes();
+        if name.eq_ignore_ascii_case(b"nav") || name.eq_ignore_ascii_case(b"aside") {
+            return true;
+        }
+
+        parent = tag.parent();
+    }
+
+    false
+}
@@ -1,12 +1,21 @@
 use super::{
     constants,
-    handle::NodeHandle,
-    tag::{Attributes, HTMLTag, Node},
+    diagnostics::{Construct, Diagnostic, DiagnosticKind},
+    handle::{NodeHandle, ParserId},
+    known_attr::KnownAttr,
+    options::{IndexKind, PayloadId},
+    slots::NodeSlots,
+    tag::{Attributes, EndTag, HTMLTag, Node},
 };
 use crate::InnerNodeHandle;
 use crate::{bytes::Bytes, inline::vec::InlineVec, simd, ParseError};
-use crate::{stream::Stream, ParserOptions};
-use std::collections::HashMap;
+use crate::{stream::Stream, tag_info, util, ParserOptions};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    ops::{ControlFlow, Range},
+    sync::Arc,
+};
 
 /// A list of HTML nodes
 pub type Tree<'a> = Vec<Node<'a>>;
@@ -14,6 +23,17 @@ pub type Tree<'a> = Vec<Node<'a>>;
 /// Inline class vector
 pub type ClassVec = InlineVec<NodeHandle, 2>;
 
+/// Inline vector of elements with the same value for a tracked attribute
+pub type AttributeVec = InlineVec<NodeHandle, 2>;
+
+/// Inline vector of elements with the same tag name
+pub type TagNameVec = InlineVec<NodeHandle, 2>;
+
+/// Inline id vector
+///
+/// Ids are supposed to be unique, so this will almost never need to allocate
+pub type IdVec = InlineVec<NodeHandle, 1>;
+
 /// HTML Version (<!DOCTYPE>)
 #[derive(Debug, Copy, Clone, PartialEq)]
 #[repr(C)]
@@ -35,46 +55,98 @@ pub enum HTMLVersion {
 pub struct Parser<'a> {
     /// The inner stream that is used to iterate through the HTML source
     pub(crate) stream: Stream<'a, u8>,
+    /// Identifies this parser in the handles it creates
+    pub(crate) id: ParserId,
     pub(crate) stack: Vec<NodeHandle>,
     /// Specified options for this HTML parser
     pub(crate) options: ParserOptions,
-    /// A global collection of all HTML tags that appear in the source code
+    /// A global collection of all HTML tags that appear in the source code, in document order
     ///
-    /// HTML Nodes contain indicies into this vector
+    /// HTML Nodes contain handles that resolve to indices into this vector
     pub(crate) tags: Tree<'a>,
+    /// Maps handles to indices into `tags` once nodes have been inserted or removed (identity mapping if `None`)
+    pub(crate) slots: Option<NodeSlots>,
+    /// The number of nodes at the end of `tags` that were created, but are not attached to the document
+    pub(crate) detached: usize,
     /// The topmost HTML nodes
     pub(crate) ast: Vec<NodeHandle>,
-    /// A HashMap that maps Tag ID to a Node ID
-    pub(crate) ids: HashMap<Bytes<'a>, NodeHandle>,
+    /// Maps handles to the handles of their parents (`NO_PARENT` for top-level and detached nodes),
+    /// if `ParserOptions::track_parents()` is set (empty otherwise)
+    pub(crate) parents: Vec<InnerNodeHandle>,
+    /// A HashMap that maps Tag ID to Node IDs (in document order)
+    pub(crate) ids: HashMap<Bytes<'a>, IdVec>,
     /// A HashMap that maps Tag Class to a Node ID
     pub(crate) classes: HashMap<Bytes<'a>, ClassVec>,
+    /// A HashMap that maps (lowercased, unless tag names are case-sensitive) tag names to Node IDs (in document order)
+    pub(crate) tag_names: HashMap<Arc<[u8]>, TagNameVec>,
+    /// The keys of `tag_names`, which are shared with the other documents of a `Batch`
+    interned_tag_names: HashSet<Arc<[u8]>>,
+    /// For each attribute in `ParserOptions::track_attribute()`, a HashMap that maps values to Node IDs (in document order)
+    pub(crate) attributes: Vec<HashMap<Bytes<'a>, AttributeVec>>,
     /// The current HTML version, if set
     pub(crate) version: Option<HTMLVersion>,
+    /// The doctype of the document and the number of top-level nodes before it, see `VDom::doctype()`
+    pub(crate) doctype: Option<(usize, Bytes<'a>)>,
+    /// Recoverable errors encountered while parsing, if enabled
+    pub(crate) diagnostics: Vec<Diagnostic>,
+    /// Scratch buffer for the attributes of the tag that is currently being parsed, reused across tags
+    parsed_attributes: Vec<(&'a [u8], Option<&'a [u8]>)>,
+    /// The position at which the progress callback is invoked next (`usize::MAX` if there is none)
+    next_progress: usize,
+    /// Whether parsing was cancelled by the progress callback
+    pub(crate) cancelled: bool,
+    /// The first recoverable error, which aborts parsing in strict mode
+    strict_error: Option<Diagnostic>,
+    /// The identifiers returned by the payload handlers (see `ParserOptions::tag_payload_handler()`) for each element
+    pub(crate) payloads: HashMap<NodeHandle, PayloadId>,
 }
 
 impl<'a> Parser<'a> {
-    pub(crate) fn new(input: &str, options: ParserOptions) -> Parser {
+    pub(crate) fn new(input: &str, options: ParserOptions) -> Parser<'_> {
+        Parser::from_bytes(input.as_bytes(), options)
+    }
+
+    fn from_bytes(input: &[u8], options: ParserOptions) -> Parser<'_> {
+        let next_progress = options
+            .progress
+            .as_ref()
+            .map_or(usize::MAX, |progress| progress.granularity);
+
         Parser {
+            id: ParserId::next(),
             stack: Vec::with_capacity(4),
+            attributes: vec![HashMap::new(); options.tracked_attributes.len()],
             options,
             tags: Vec::new(),
-            stream: Stream::new(input.as_bytes()),
+            slots: None,
+            detached: 0,
+            stream: Stream::new(input),
             ast: Vec::new(),
+            parents: Vec::new(),
             ids: HashMap::new(),
             classes: HashMap::new(),
+            tag_names: HashMap::new(),
+            interned_tag_names: HashSet::new(),
             version: None,
+            doctype: None,
+            diagnostics: Vec::new(),
+            parsed_attributes: Vec::new(),
+            next_progress,
+            cancelled: false,
+            strict_error: None,
+            payloads: HashMap::new(),
         }
     }
 
     #[inline(always)]
     fn register_tag(&mut self, node: Node<'a>) -> NodeHandle {
         self.tags.push(node);
-        NodeHandle::new((self.tags.len() - 1) as u32)
+        NodeHandle::with_parser((self.tags.len() - 1) as u32, self.id)
     }
 
     #[inline(always)]
     fn skip_whitespaces(&mut self) {
-        self.read_while2(b' ', b'\n');
+        self.read_while(util::is_ascii_whitespace);
     }
 
     fn read_to(&mut self, needle: u8) -> &'a [u8] {
@@ -87,29 +159,46 @@ impl<'a> Parser<'a> {
         self.stream.slice(start, start + end)
     }
 
-    fn read_to4(&mut self, needle: [u8; 4]) -> &'a [u8] {
+    /// Creates the bytes of a text node, which are truncated and whose line breaks are normalized if enabled
+    fn text_bytes(&self, text: &'a [u8], has_refs: bool) -> Bytes<'a> {
+        let bytes = stored_bytes(
+            text,
+            self.options.max_stored_text_len,
+            self.options.is_normalizing_newlines(),
+        );
+
+        // the flag is only kept for borrowed bytes
+        bytes.with_plain_text(!has_refs)
+    }
+
+    /// Reads text up to the next `<`, and returns whether it contains a character reference (`&`)
+    fn read_text(&mut self) -> (&'a [u8], bool) {
         let start = self.stream.idx;
         let bytes = &self.stream.data()[start..];
 
-        let end = simd::find4(bytes, needle).unwrap_or_else(|| self.stream.len() - start);
+        // most text has no references, in which case this finds the end of the text in a single pass
+        let (end, has_refs) = match simd::find4(bytes, [b'<', b'&', b'<', b'&']) {
+            Some(idx) if bytes[idx] == b'&' => {
+                let end = simd::find(&bytes[idx..], b'<').map_or(bytes.len(), |end| idx + end);
+                (end, true)
+            }
+            Some(idx) => (idx, false),
+            None => (bytes.len(), false),
+        };
 
         self.stream.idx += end;
-        self.stream.slice(start, start + end)
+        (self.stream.slice(start, start + end), has_refs)
     }
 
-    fn read_while2(&mut self, needle1: u8, needle2: u8) -> Option<()> {
-        loop {
-            let ch = self.stream.current_cpy()?;
-
-            let eq1 = ch == needle1;
-            let eq2 = ch == needle2;
-
-            if !eq1 & !eq2 {
-                return Some(());
-            }
+    /// Reads as long as the given condition holds
+    fn read_while(&mut self, cond: impl Fn(u8) -> bool) -> &'a [u8] {
+        let start = self.stream.idx;
 
+        while self.stream.current_cpy().is_some_and(&cond) {
             self.stream.advance();
         }
+
+        self.stream.slice(start, self.stream.idx)
     }
 
     fn read_ident(&mut self) -> Option<&'a [u8]> {
@@ -124,32 +213,136 @@ impl<'a> Parser<'a> {
         Some(self.stream.slice(start, start + end))
     }
 
+    /// Reads a comment up to and including the next `-->`, given the position of its `<` and with the stream at the `--` of `<!--`
+    ///
+    /// If the comment is not closed, it extends to the end of the input.
     fn skip_comment_with_start(&mut self, start: usize) -> &'a [u8] {
-        while !self.stream.is_eof() {
-            let idx = self.stream.idx;
+        let data = self.stream.data();
+        // the dashes of `<!--` count towards the end, so `<!-->` is a complete (empty) comment
+        let mut idx = self.stream.idx + constants::COMMENT.len();
+
+        while let Some(offset) = simd::find(&data[idx..], b'>') {
+            idx += offset + 1;
+
+            if data[idx - 3..idx - 1].eq(constants::COMMENT) {
+                self.stream.idx = idx;
+                return self.stream.slice(start, idx);
+            }
+        }
+
+        let end = self.stream.len();
+        self.unexpected_eof(Construct::Comment, start);
+        self.stream.idx = end;
+        self.stream.slice(start, end)
+    }
+
+    /// Reports that the input ended inside of the given construct, which starts at `at`
+    ///
+    /// Only the innermost construct is reported, which is the first one that reaches the end of the input.
+    #[cold]
+    #[inline(never)]
+    fn unexpected_eof(&mut self, in_construct: Construct, at: usize) {
+        let is_reported = self.diagnostics.last().is_some_and(|diagnostic| {
+            matches!(diagnostic.kind, DiagnosticKind::UnexpectedEof { .. })
+        });
 
-            if self
-                .stream
-                .slice_len(idx, constants::COMMENT.len())
-                .eq(constants::COMMENT)
-            {
-                self.stream.advance_by(constants::COMMENT.len());
+        if !is_reported {
+            self.report(DiagnosticKind::UnexpectedEof { in_construct }, at);
+        }
+    }
+
+    /// Reports a recoverable error at the given position, which is collected if diagnostics are enabled
+    /// and aborts parsing in strict mode
+    #[cold]
+    #[inline(never)]
+    fn report(&mut self, kind: DiagnosticKind, at: usize) {
+        let is_strict = self.options.is_strict() && self.strict_error.is_none();
+        let is_collecting = self.options.is_collecting_diagnostics();
 
-                let is_end_of_comment = self.stream.expect_and_skip_cond(b'>');
+        if !is_strict && !is_collecting {
+            return;
+        }
 
-                if is_end_of_comment {
-                    return self.stream.slice(start, self.stream.idx);
+        let diagnostic = Diagnostic { kind, at };
+        if is_strict {
+            self.strict_error = Some(diagnostic.clone());
+        }
+        if is_collecting {
+            self.diagnostics.push(diagnostic);
+        }
+    }
+
+    /// Skips a (possibly nested) group of braces, such as `{{#if x}}` or `{user.name}`
+    ///
+    /// The group may contain `>` (e.g. `{() => x}`), but if the braces are not balanced before the next `<`,
+    /// this only skips up to the next `>` to avoid consuming the rest of the document.
+    fn skip_braces(&mut self) {
+        let start = self.stream.idx;
+        let bytes = &self.stream.data()[start..];
+        let mut depth = 0usize;
+
+        let end = bytes
+            .iter()
+            .take_while(|&&c| c != b'<')
+            .position(|&c| {
+                match c {
+                    b'{' => depth += 1,
+                    b'}' => depth -= 1,
+                    _ => {}
                 }
+                depth == 0
+            })
+            .map(|x| x + 1)
+            .or_else(|| simd::find(bytes, b'>'))
+            .unwrap_or(bytes.len());
+
+        self.stream.idx += end;
+    }
+
+    /// Reads the rest of an attribute name that contains invalid characters (e.g. templating syntax)
+    ///
+    /// Everything up to the next whitespace, `=` or end of the tag is treated as part of the name,
+    /// and a group of braces is read as a whole, so that the following attributes can still be parsed.
+    #[cold]
+    #[inline(never)]
+    fn read_invalid_attribute_name(&mut self, start: usize) -> &'a [u8] {
+        while let Some(ch) = self.stream.current_cpy() {
+            if ch == b'{' {
+                self.skip_braces();
+                break;
+            }
+
+            let is_tag_end =
+                ch == b'/' && self.stream.data().get(self.stream.idx + 1) == Some(&b'>');
+
+            if is_tag_end || util::is_ascii_whitespace(ch) || ch == b'=' || ch == b'>' {
+                break;
             }
 
             self.stream.advance();
         }
 
-        &[]
+        self.report(DiagnosticKind::InvalidAttributeName, start);
+
+        self.stream.slice(start, self.stream.idx)
     }
 
     fn parse_attribute(&mut self) -> Option<(&'a [u8], Option<&'a [u8]>)> {
-        let name = self.read_ident()?;
+        let start = self.stream.idx;
+        let mut name = self.read_ident()?;
+
+        let is_valid = self.stream.current_cpy().map_or(true, |c| {
+            util::is_ascii_whitespace(c) || c == b'=' || simd::is_closing(c)
+        });
+
+        if !is_valid {
+            name = self.read_invalid_attribute_name(start);
+        }
+
+        if name.is_empty() {
+            return None;
+        }
+
         self.skip_whitespaces();
 
         let has_value = self.stream.expect_and_skip_cond(b'=');
@@ -159,47 +352,92 @@ impl<'a> Parser<'a> {
 
         self.skip_whitespaces();
 
-        let value = if let Some(quote) = self.stream.expect_oneof_and_skip(&[b'"', b'\'']) {
-            self.read_to(quote)
+        let value_start = self.stream.idx;
+
+        let value = if let Some(quote) = self.stream.expect_oneof_and_skip(b"\"'") {
+            let value = self.read_to(quote);
+
+            if !self.stream.expect_and_skip_cond(quote) {
+                self.unexpected_eof(Construct::AttributeValue, value_start);
+            }
+
+            value
+        } else if self.stream.current_cpy() == Some(b'{') {
+            // unquoted expressions, e.g. `value={user.name}`
+            let start = self.stream.idx;
+            self.skip_braces();
+            self.stream.slice(start, self.stream.idx)
         } else {
-            self.read_to4([b' ', b'\n', b'/', b'>'])
+            self.read_while(|c| !util::is_ascii_whitespace(c) && c != b'/' && c != b'>')
         };
 
         Some((name, Some(value)))
     }
 
-    fn parse_attributes(&mut self) -> Option<Attributes<'a>> {
-        let mut attributes = Attributes::new();
+    /// Parses the attributes of a start tag, and returns them along with whether the tag is terminated (by `>` or `/>`)
+    fn parse_attributes(&mut self) -> (Attributes<'a>, bool) {
+        // attributes are collected first, so that the map can be built with the exact capacity in one go
+        let mut parsed = std::mem::take(&mut self.parsed_attributes);
+        parsed.clear();
+
+        let max_len = self.options.max_stored_attribute_len;
+        let normalize_newlines = self.options.is_normalizing_newlines();
 
+        let is_terminated = self.read_attributes(&mut parsed);
+
+        let attributes = match max_len.is_some() || normalize_newlines {
+            true => Attributes::from_parsed_with(&parsed, |value| {
+                stored_bytes(value, max_len, normalize_newlines)
+            }),
+            false => Attributes::from_parsed(&parsed),
+        };
+
+        self.parsed_attributes = parsed;
+        (attributes, is_terminated)
+    }
+
+    /// Reads attributes up to the end of the tag, and returns `false` if the input ends before it
+    fn read_attributes(&mut self, attributes: &mut Vec<(&'a [u8], Option<&'a [u8]>)>) -> bool {
         loop {
             self.skip_whitespaces();
 
-            let cur = self.stream.current_cpy()?;
+            let Some(cur) = self.stream.current_cpy() else {
+                return false;
+            };
 
-            if simd::is_closing(cur) {
+            if cur == b'>' {
                 break;
             }
 
-            if let Some((key, value)) = self.parse_attribute() {
-                let value: Option<Bytes<'a>> = value.map(Into::into);
+            if cur == b'/' {
+                // a stray slash in the middle of a tag is ignored
+                if self.stream.data().get(self.stream.idx + 1) == Some(&b'>') {
+                    break;
+                }
 
-                match key {
-                    b"id" => attributes.id = value,
-                    b"class" => attributes.class = value,
-                    _ => attributes.raw.insert(key.into(), value),
-                };
+                self.stream.advance();
+                continue;
             }
 
-            if !simd::is_closing(self.stream.current_cpy()?) {
+            let start = self.stream.idx;
+
+            if let Some(attribute) = self.parse_attribute() {
+                attributes.push(attribute);
+            }
+
+            // make sure we always make progress, even if no attribute could be parsed
+            if self.stream.idx == start {
                 self.stream.advance();
             }
         }
 
-        Some(attributes)
+        true
     }
 
     #[inline]
     fn add_to_parent(&mut self, handle: NodeHandle) {
+        self.set_parent(handle, self.stack.last().copied());
+
         if let Some(last) = self.stack.last() {
             let last = self
                 .tags
@@ -214,24 +452,159 @@ impl<'a> Parser<'a> {
         }
     }
 
-    fn read_end(&mut self) {
-        self.stream.advance();
+    /// Reads an end tag that starts at `start`, and closes the innermost open tag if the names match
+    ///
+    /// End tags that do not match are ignored, unless they are cut off by the end of the input,
+    /// in which case they are kept as text.
+    fn read_end(&mut self, start: usize) {
+        self.stream.advance(); // skip /
 
-        let closing_tag_name = self.read_to(b'>');
+        let closing_tag_name =
+            self.read_while(|c| !util::is_ascii_whitespace(c) && c != b'/' && c != b'>');
 
-        self.stream.expect_and_skip_cond(b'>');
+        self.read_to(b'>');
+        let is_terminated = self.stream.expect_and_skip_cond(b'>');
 
-        let closing_tag_matches_parent = self
-            .stack
-            .last()
-            .and_then(|last_handle| last_handle.get(self))
-            .and_then(|last_item| last_item.as_tag())
-            .map_or(false, |last_tag| last_tag.name() == closing_tag_name);
+        if !is_terminated {
+            self.unexpected_eof(Construct::EndTag, start);
+        }
 
-        if !closing_tag_matches_parent {
-            return;
+        match self.end_tag_depth(closing_tag_name) {
+            Some(depth) => {
+                while self.stack.len() > depth + 1 {
+                    self.close_current_tag(start..start);
+                }
+
+                self.close_current_tag(start..self.stream.idx);
+            }
+            None if !is_terminated => self.push_text(self.stream.slice(start, self.stream.idx)),
+            None => {
+                let name = String::from_utf8_lossy(closing_tag_name).into_owned();
+                self.report(DiagnosticKind::StrayEndTag { name }, start);
+            }
+        }
+    }
+
+    /// Returns the depth in the stack of open elements of the element that is closed by an end tag with the given name, if any
+    ///
+    /// This is the innermost open element with that name, unless it contains open elements whose end tags cannot be omitted.
+    /// The end tags of options and list items are implied by the end of their parent, e.g. `<select><option>a</select>`.
+    pub(crate) fn end_tag_depth(&self, name: &[u8]) -> Option<usize> {
+        self.stack
+            .iter()
+            .rposition(|&handle| self.open_tag_is(handle, name))
+            .filter(|&depth| {
+                self.stack[depth + 1..]
+                    .iter()
+                    .all(|&handle| self.open_tag_has_optional_end_tag(handle))
+            })
+    }
+
+    /// Checks whether the given open tag has the given name (ASCII case-insensitive)
+    fn open_tag_is(&self, handle: NodeHandle, name: &[u8]) -> bool {
+        self.tags[handle.get_inner() as usize]
+            .as_tag()
+            .is_some_and(|tag| tag.name().as_bytes().eq_ignore_ascii_case(name))
+    }
+
+    /// Checks whether the given open tag is an `<option>`, `<optgroup>`, `<li>`, `<dt>` or `<dd>`, whose end tags are usually omitted
+    fn open_tag_has_optional_end_tag(&self, handle: NodeHandle) -> bool {
+        [&b"option"[..], b"optgroup", b"li", b"dt", b"dd"]
+            .iter()
+            .any(|name| self.open_tag_is(handle, name))
+    }
+
+    /// Closes the open elements whose end tags are implied by a start tag with the given name at `start`
+    fn close_implied_end_tags(&mut self, name: &[u8], start: usize) {
+        if let Some(depth) = self.implied_end_depth(name) {
+            while self.stack.len() > depth {
+                self.close_current_tag(start..start);
+            }
+        }
+    }
+
+    /// Returns the number of open elements that stay open if a start tag with the given name implies the end tags of the others
+    ///
+    /// An `<option>` or `<optgroup>` closes an open `<option>`, and an `<optgroup>` also closes an open `<optgroup>`
+    /// (e.g. `<option>a<option>b`). As in the HTML specification, an `<li>` closes the innermost open `<li>`, and a `<dt>` or `<dd>`
+    /// closes the innermost open `<dt>` or `<dd>`, along with the elements in it that are still open (e.g. `<li>a<li>b` or `<dt>a<dd>b`).
+    /// This stops at lists and tables, so that items of nested lists are not closed.
+    pub(crate) fn implied_end_depth(&self, name: &[u8]) -> Option<usize> {
+        let is_option = name.eq_ignore_ascii_case(b"option");
+        let is_optgroup = name.eq_ignore_ascii_case(b"optgroup");
+
+        if is_option || is_optgroup {
+            let is_innermost = |depth: usize, name: &[u8]| {
+                depth
+                    .checked_sub(1)
+                    .is_some_and(|depth| self.open_tag_is(self.stack[depth], name))
+            };
+
+            let mut depth = self.stack.len();
+            if is_innermost(depth, b"option") {
+                depth -= 1;
+            }
+            if is_optgroup && is_innermost(depth, b"optgroup") {
+                depth -= 1;
+            }
+
+            return (depth < self.stack.len()).then_some(depth);
+        }
+
+        let targets: &[&[u8]] = if name.eq_ignore_ascii_case(b"li") {
+            &[b"li"]
+        } else if name.eq_ignore_ascii_case(b"dt") || name.eq_ignore_ascii_case(b"dd") {
+            &[b"dt", b"dd"]
+        } else {
+            return None;
+        };
+
+        let is_target = |handle| targets.iter().any(|name| self.open_tag_is(handle, name));
+        let is_boundary = |handle| {
+            [
+                &b"ul"[..],
+                b"ol",
+                b"menu",
+                b"dl",
+                b"li",
+                b"dt",
+                b"dd",
+                b"table",
+                b"template",
+            ]
+            .iter()
+            .any(|name| self.open_tag_is(handle, name))
+        };
+
+        for (depth, &handle) in self.stack.iter().enumerate().rev() {
+            if is_target(handle) {
+                return Some(depth);
+            }
+
+            if is_boundary(handle) {
+                break;
+            }
         }
 
+        None
+    }
+
+    /// Passes the content of the current element to the payload handler for its name, if there is one,
+    /// and stores the returned identifier
+    fn handle_payload(&mut self, name: &[u8], payload: &[u8]) {
+        let (Some(index), Some(&handle)) = (self.options.payload_handler(name), self.stack.last())
+        else {
+            return;
+        };
+
+        let id = (self.options.payload_handlers[index].handler)(payload);
+        self.payloads.insert(handle, id);
+    }
+
+    /// Pops the innermost open tag off the stack and extends its raw span up to the end of the given end tag
+    ///
+    /// An empty range means that the end tag was omitted, in which case the tag ends where the range starts.
+    fn close_current_tag(&mut self, end_tag: Range<usize>) {
         if let Some(handle) = self.stack.pop() {
             let tag = self
                 .tags
@@ -244,40 +617,337 @@ impl<'a> Parser<'a> {
             let offset = tag._raw.as_ptr() as usize;
             let offset = offset - ptr;
 
-            tag._raw = self.stream.slice(offset, self.stream.idx).into();
-
-            let (track_classes, track_ids) = (
-                self.options.is_tracking_classes(),
-                self.options.is_tracking_ids(),
-            );
-
-            if let (true, Some(bytes)) = (track_classes, &tag._attributes.class) {
-                let s = bytes
-                    .as_bytes_borrowed()
-                    .and_then(|x| std::str::from_utf8(x).ok())
-                    .map(|x| x.split_ascii_whitespace());
-
-                if let Some(s) = s {
-                    for class in s {
-                        self.classes
-                            .entry(class.into())
-                            .or_insert_with(InlineVec::new)
-                            .push(handle);
+            tag._raw = self.stream.slice(offset, end_tag.end).into();
+
+            if !end_tag.is_empty() {
+                tag._end_tag = EndTag::Source(end_tag.len() as u32);
+            }
+        }
+    }
+
+    /// Adds a text node that does not contain markup to the innermost open tag
+    fn push_text(&mut self, text: &'a [u8]) {
+        let has_refs = simd::find(text, b'&').is_some();
+        let handle = self.register_tag(Node::Raw(self.text_bytes(text, has_refs)));
+        self.add_to_parent(handle);
+    }
+
+    /// Registers the given tag in the id, class, tag name and attribute lookup tables, if tracking is enabled
+    ///
+    /// This is called as soon as the start tag is parsed, so that elements are tracked in document order
+    /// regardless of whether (or when) they are closed.
+    fn track_tag(&mut self, position: usize, handle: NodeHandle) {
+        for kind in [IndexKind::Ids, IndexKind::Classes, IndexKind::TagNames] {
+            if self.options.is_indexing(kind) {
+                self.index_tag(kind, position, handle);
+            }
+        }
+
+        let tag = self.tags[position].as_tag().unwrap();
+
+        for (name, values) in self
+            .options
+            .tracked_attributes
+            .iter()
+            .zip(&mut self.attributes)
+        {
+            // attributes without a value are tracked with an empty value
+            if let Some(value) = tag._attributes.get_by_name(name) {
+                values
+                    .entry(value.cloned().unwrap_or_default())
+                    .or_insert_with(InlineVec::new)
+                    .push(handle);
+            }
+        }
+    }
+
+    /// Checks whether the values of the attribute with the given name are stored in one of the lookup tables
+    pub(crate) fn is_indexed_attribute(&self, name: &[u8]) -> bool {
+        match KnownAttr::from_bytes(name) {
+            Some(KnownAttr::Id) => self.options.is_indexing(IndexKind::Ids),
+            Some(KnownAttr::Class) => self.options.is_indexing(IndexKind::Classes),
+            _ => self
+                .options
+                .tracked_attributes
+                .iter()
+                .any(|tracked| tracked.eq_ignore_ascii_case(name)),
+        }
+    }
+
+    /// Returns an iterator over the handles of the elements with the given tag name at the given positions of the node table, in document order
+    ///
+    /// This uses the lookup table of tag names if it exists, otherwise it scans the nodes at the given positions.
+    pub(crate) fn elements_by_tag_name<'p>(
+        &'p self,
+        name: &'p str,
+        positions: Range<usize>,
+    ) -> Box<dyn Iterator<Item = NodeHandle> + 'p> {
+        let case_sensitive = self.options.is_case_sensitive_tag_names();
+
+        if self.options.is_tracking_tag_names() {
+            let handles = match case_sensitive {
+                true => self.tag_names.get(name.as_bytes()),
+                false => self.tag_names.get(name.to_ascii_lowercase().as_bytes()),
+            };
+            let handles = handles
+                .map(|handles| handles.as_slice())
+                .unwrap_or_default();
+
+            // the lists of elements are in document order, so the elements in range are a contiguous part of it
+            let before = |end: usize| {
+                move |&handle: &NodeHandle| {
+                    self.position_of(handle)
+                        .is_some_and(|position| position < end)
+                }
+            };
+            let start = handles.partition_point(before(positions.start));
+            let end = handles.partition_point(before(positions.end));
+
+            Box::new(handles[start..end].iter().copied())
+        } else {
+            #[cfg(test)]
+            crate::vdom::scans::record();
+
+            Box::new(positions.filter_map(move |position| {
+                let tag_name = self.tags[position].as_tag()?._name.as_bytes();
+                let matches = match case_sensitive {
+                    true => tag_name == name.as_bytes(),
+                    false => simd::eq_case_insensitive(tag_name, name.as_bytes()),
+                };
+
+                matches.then(|| self.handle_at(position))
+            }))
+        }
+    }
+
+    /// Registers all elements of the document in the lookup tables, for nodes that were added without parsing
+    pub(crate) fn track_all(&mut self) {
+        for position in 0..self.document_len() {
+            if self.tags[position].as_tag().is_some() {
+                let handle = self.handle_at(position);
+                self.track_tag(position, handle);
+                self.set_children_parents(handle);
+            }
+        }
+    }
+
+    /// Registers the element at the given position in the given lookup table
+    ///
+    /// Ids and class names are registered with their character references decoded, unless raw matching is enabled.
+    fn index_tag(&mut self, kind: IndexKind, position: usize, handle: NodeHandle) {
+        let Some(tag) = self.tags[position].as_tag() else {
+            return;
+        };
+        let decode = !self.options.is_raw_id_and_class_matching();
+
+        match kind {
+            IndexKind::Ids => {
+                let Some(bytes) = &tag._attributes.id else {
+                    return;
+                };
+
+                let key = match bytes.decoded_if(decode) {
+                    Cow::Borrowed(_) => bytes.clone(),
+                    Cow::Owned(decoded) => match owned_bytes(decoded) {
+                        Some(decoded) => decoded,
+                        None => return,
+                    },
+                };
+
+                self.ids
+                    .entry(key)
+                    .or_insert_with(InlineVec::new)
+                    .push(handle);
+            }
+            IndexKind::Classes => {
+                let Some(bytes) = &tag._attributes.class else {
+                    return;
+                };
+
+                for_each_class_key(bytes, decode, |class| {
+                    self.classes
+                        .entry(class)
+                        .or_insert_with(InlineVec::new)
+                        .push(handle);
+                });
+            }
+            IndexKind::TagNames => {
+                let name = tag._name.as_bytes();
+                let lowercase;
+                let key = match self.options.is_case_sensitive_tag_names() {
+                    true => name,
+                    false => {
+                        lowercase = name.to_ascii_lowercase();
+                        &lowercase[..]
+                    }
+                };
+
+                match self.tag_names.get_mut(key) {
+                    Some(handles) => handles.push(handle),
+                    None => {
+                        let key = match self.interned_tag_names.get(key) {
+                            Some(key) => Arc::clone(key),
+                            None => {
+                                let key = Arc::<[u8]>::from(key);
+                                self.interned_tag_names.insert(Arc::clone(&key));
+                                key
+                            }
+                        };
+
+                        let mut handles = InlineVec::new();
+                        handles.push(handle);
+                        self.tag_names.insert(key, handles);
                     }
                 }
             }
+        }
+    }
+
+    /// Registers an element that was inserted into the document after parsing in the lookup tables
+    ///
+    /// Unlike while parsing, the element is not necessarily the last one in document order,
+    /// so it is inserted at its position into the lists of elements. Detached elements are not registered.
+    pub(crate) fn track_inserted(&mut self, handle: NodeHandle) {
+        let Some(position) = self
+            .position_of(handle)
+            .filter(|&position| position < self.document_len())
+        else {
+            return;
+        };
+
+        let is_tracking = self.options.is_tracking() || !self.attributes.is_empty();
+        if !is_tracking || self.tags[position].as_tag().is_none() {
+            return;
+        }
+
+        // register the element in empty tables, which are then merged into the actual ones
+        let tables = (
+            std::mem::take(&mut self.ids),
+            std::mem::take(&mut self.classes),
+            std::mem::take(&mut self.tag_names),
+            std::mem::replace(
+                &mut self.attributes,
+                vec![HashMap::new(); self.options.tracked_attributes.len()],
+            ),
+        );
+        self.track_tag(position, handle);
+
+        let (mut ids, mut classes, mut tag_names, mut attributes) = tables;
+        let added = (
+            std::mem::take(&mut self.ids),
+            std::mem::take(&mut self.classes),
+            std::mem::take(&mut self.tag_names),
+            std::mem::take(&mut self.attributes),
+        );
+
+        let position_of = |handle| self.position_of(handle);
+        merge_in_order(&mut ids, added.0, position_of);
+        merge_in_order(&mut classes, added.1, position_of);
+        merge_in_order(&mut tag_names, added.2, position_of);
+        for (values, added) in attributes.iter_mut().zip(added.3) {
+            merge_in_order(values, added, position_of);
+        }
+
+        (self.ids, self.classes, self.tag_names, self.attributes) =
+            (ids, classes, tag_names, attributes);
+    }
+
+    /// Builds the given lookup table from the elements of the document, unless it is already maintained
+    ///
+    /// The elements are registered in document order, so the table is identical to one built while parsing.
+    pub(crate) fn build_index(&mut self, kind: IndexKind) {
+        if self.options.is_indexing(kind) {
+            return;
+        }
+
+        for position in 0..self.document_len() {
+            let handle = self.handle_at(position);
+            self.index_tag(kind, position, handle);
+        }
 
-            if let (true, Some(bytes)) = (track_ids, &tag._attributes.id) {
-                self.ids.insert(bytes.clone(), handle);
+        self.options.set_indexing(kind);
+    }
+
+    /// Reads the content of a raw text element (e.g. `<script>`) up to its end tag and closes the element
+    ///
+    /// The content is added as a single raw text node to the element, without being parsed as markup.
+    /// If there is no end tag, the rest of the input is consumed and the element is left open until the end of the input.
+    fn read_raw_text(&mut self, name: &[u8]) {
+        let start = self.stream.idx;
+        let data = self.stream.data();
+        let mut idx = start;
+
+        let end_tag = loop {
+            let Some(offset) = simd::find(&data[idx..], b'<') else {
+                break None;
+            };
+
+            idx += offset;
+
+            let is_end_tag = data.get(idx + 1) == Some(&b'/')
+                && data
+                    .get(idx + 2..idx + 2 + name.len())
+                    .is_some_and(|x| x.eq_ignore_ascii_case(name))
+                && data.get(idx + 2 + name.len()).map_or(true, |&c| {
+                    util::is_ascii_whitespace(c) || simd::is_closing(c)
+                });
+
+            if is_end_tag {
+                break Some(idx);
             }
+
+            idx += 1;
+        };
+
+        let end = end_tag.unwrap_or(data.len());
+
+        if !self.options.payload_handlers.is_empty() {
+            self.handle_payload(name, self.stream.slice(start, end));
+        }
+
+        if end > start {
+            self.push_text(self.stream.slice(start, end));
+        }
+
+        self.stream.idx = end;
+
+        match end_tag {
+            Some(end_tag) => {
+                self.read_to(b'>');
+
+                if !self.stream.expect_and_skip_cond(b'>') {
+                    self.unexpected_eof(Construct::EndTag, end_tag);
+                }
+
+                self.close_current_tag(end_tag..self.stream.idx);
+            }
+            None => self.unexpected_eof(Construct::RawText, start),
+        }
+    }
+
+    /// Reads the rest of the input as the content of a `<plaintext>` element
+    ///
+    /// Like in browsers, a `<plaintext>` element cannot be closed, so everything after its start tag
+    /// (including anything that looks like markup or its own end tag) becomes a single raw text node.
+    fn read_plaintext(&mut self) {
+        let start = self.stream.idx;
+        let end = self.stream.len();
+
+        if end > start {
+            self.push_text(self.stream.slice(start, end));
         }
+
+        self.stream.idx = end;
     }
 
+    /// Reads a comment or another markup declaration (such as a doctype) that starts at `start`,
+    /// with the stream at the `!`
+    ///
+    /// Markup declarations other than comments are read up to the next `>`. The doctype of the document
+    /// is kept out of the node tree, any other declaration is added as a comment as well.
     #[cold]
     #[inline(never)]
-    fn read_markdown(&mut self) -> Option<()> {
-        let start = self.stream.idx - 1; // position of the < which is needed when registering the comment
-
+    fn read_markdown(&mut self, start: usize) {
         self.stream.advance(); // skip !
 
         let is_comment = self
@@ -285,30 +955,63 @@ impl<'a> Parser<'a> {
             .slice_len(self.stream.idx, 2)
             .eq(constants::COMMENT);
 
-        if is_comment {
-            let comment = self.skip_comment_with_start(start);
-            let comment = self.register_tag(Node::Comment(comment.into()));
-            self.add_to_parent(comment);
+        let comment = if is_comment {
+            self.skip_comment_with_start(start)
         } else {
-            let tag = self.read_ident()?;
+            let declaration = self.read_declaration(start);
 
-            self.skip_whitespaces();
+            if self.is_at_doctype() {
+                self.doctype = Some((self.ast.len(), declaration.into()));
+                return;
+            }
 
-            if simd::matches_case_insensitive(tag, *b"doctype") {
-                let doctype = self.read_ident()?;
+            declaration
+        };
 
-                let html5 = simd::matches_case_insensitive(doctype, *b"html");
+        let comment = self.register_tag(Node::Comment(comment.into()));
+        self.add_to_parent(comment);
+    }
 
-                if html5 {
-                    self.version = Some(HTMLVersion::HTML5);
-                }
+    /// Checks whether a markup declaration that was just read is the doctype of the document,
+    /// which is the first one that is only preceded by whitespace
+    fn is_at_doctype(&self) -> bool {
+        let is_whitespace = |handle: &NodeHandle| {
+            handle.get(self).and_then(Node::as_raw).is_some_and(|text| {
+                text.as_bytes()
+                    .iter()
+                    .all(|&c| util::is_ascii_whitespace(c))
+            })
+        };
 
-                self.skip_whitespaces();
-                self.stream.advance(); // skip >
+        self.doctype.is_none()
+            && match self.ast.as_slice() {
+                [] => true,
+                [text] => is_whitespace(text),
+                _ => false,
+            }
+    }
+
+    /// Reads a markup declaration that is not a comment (e.g. `<!DOCTYPE html>`) up to and including the next `>`
+    fn read_declaration(&mut self, start: usize) -> &'a [u8] {
+        let tag = self.read_while(util::is_ident);
+
+        self.skip_whitespaces();
+
+        if simd::matches_case_insensitive(tag, *b"doctype") {
+            let doctype = self.read_while(util::is_ident);
+
+            if simd::matches_case_insensitive(doctype, *b"html") {
+                self.version = Some(HTMLVersion::HTML5);
             }
         }
 
-        Some(())
+        self.read_to(b'>');
+
+        if !self.stream.expect_and_skip_cond(b'>') {
+            self.unexpected_eof(Construct::Doctype, start);
+        }
+
+        self.stream.slice(start, self.stream.idx)
     }
 
     fn parse_tag(&mut self) -> Option<()> {
@@ -316,22 +1019,33 @@ impl<'a> Parser<'a> {
 
         self.stream.advance();
         self.skip_whitespaces();
-        let cur = self.stream.current_cpy()?;
+
+        let Some(cur) = self.stream.current_cpy() else {
+            // a `<` at the end of the input is text
+            self.unexpected_eof(Construct::StartTag, start);
+            self.push_text(self.stream.slice(start, self.stream.idx));
+            return Some(());
+        };
 
         match cur {
-            b'/' => self.read_end(),
-            b'!' => {
-                self.read_markdown();
-            }
+            b'/' => self.read_end(start),
+            b'!' => self.read_markdown(start),
             _ => {
                 let name = self.read_ident()?;
                 self.skip_whitespaces();
 
-                let attr = self.parse_attributes()?;
+                self.close_implied_end_tags(name, start);
 
-                let is_self_closing = self.stream.expect_and_skip_cond(b'/');
+                let (attr, is_terminated) = self.parse_attributes();
 
-                self.stream.expect_and_skip(b'>')?;
+                // a tag that is cut off by the end of the input has no content
+                let is_self_closing = !is_terminated || self.stream.expect_and_skip_cond(b'/');
+
+                if is_terminated {
+                    self.stream.expect_and_skip(b'>')?;
+                } else {
+                    self.unexpected_eof(Construct::StartTag, start);
+                }
 
                 let parent = if self.stack.last().is_some() {
                     self.stack.last().copied()
@@ -339,7 +1053,7 @@ impl<'a> Parser<'a> {
                     None
                 };
 
-                let this = self.register_tag(Node::Tag(HTMLTag::new(
+                let this = self.register_tag(Node::Tag(HTMLTag::from_source(
                     name.into(),
                     attr,
                     InlineVec::new(),
@@ -349,12 +1063,22 @@ impl<'a> Parser<'a> {
 
                 self.add_to_parent(this);
 
+                if self.options.is_tracking() || !self.attributes.is_empty() {
+                    self.track_tag(this.get_inner() as usize, this);
+                }
+
                 // some tags are self closing, so even though there might not be a /,
                 // we don't always want to push them to the stack
                 // e.g. <br><p>Hello</p>
                 // <p> should not be a subtag of <br>
-                if !is_self_closing && !constants::VOID_TAGS.contains(&name) {
+                if !is_self_closing && !tag_info::is_void(name) {
                     self.stack.push(this);
+
+                    if name.eq_ignore_ascii_case(b"plaintext") {
+                        self.read_plaintext();
+                    } else if self.options.is_raw_text_element(name) {
+                        self.read_raw_text(name);
+                    }
                 }
             }
         };
@@ -362,30 +1086,81 @@ impl<'a> Parser<'a> {
         Some(())
     }
 
+    /// Reads the start tag at the beginning of the given input (which starts with `<`), without its content
+    ///
+    /// The name and the attributes are parsed exactly like by `parse_tag()`, but they are copied, so that the tag
+    /// does not borrow from the input. This is used to read the start tags of a document that is not fully in memory.
+    pub(crate) fn read_start_tag(input: &[u8]) -> StartTag {
+        let mut parser = Parser::from_bytes(input, ParserOptions::default());
+
+        parser.stream.advance();
+        parser.skip_whitespaces();
+
+        let name = parser.read_ident().unwrap_or_default();
+        parser.skip_whitespaces();
+
+        let (attributes, is_terminated) = parser.parse_attributes();
+        let is_self_closing = !is_terminated || parser.stream.expect_and_skip_cond(b'/');
+        if is_terminated {
+            parser.stream.expect_and_skip(b'>');
+        }
+
+        // nothing borrows from an empty slice, so all bytes are copied
+        let tag = HTMLTag::new(
+            Bytes::from(name).rebase(&[], &[]),
+            attributes.rebase(&[], &[]),
+        );
+
+        StartTag {
+            tag,
+            len: parser.stream.idx,
+            is_terminated,
+            is_self_closing,
+        }
+    }
+
     pub(crate) fn parse_single(&mut self) -> Option<()> {
         loop {
+            if self.stream.idx >= self.next_progress && self.report_progress().is_break() {
+                self.cancelled = true;
+                return None;
+            }
+
+            if self.strict_error.is_some() {
+                return None;
+            }
+
             let cur = self.stream.current()?;
 
             if *cur == b'<' {
                 self.parse_tag();
             } else {
-                let raw = Node::Raw(self.read_to(b'<').into());
+                let (text, has_refs) = self.read_text();
+                let raw = Node::Raw(self.text_bytes(text, has_refs));
                 let handle = self.register_tag(raw);
                 self.add_to_parent(handle);
             }
         }
     }
 
+    /// Returns the number of nodes in the node table that are part of the document, which excludes created nodes that are not attached
+    #[inline]
+    pub(crate) fn document_len(&self) -> usize {
+        self.tags.len() - self.detached
+    }
+
     /// Resolves an internal Node ID obtained from a NodeHandle to a Node
     #[inline]
     pub fn resolve_node_id(&self, id: InnerNodeHandle) -> Option<&Node<'a>> {
-        self.tags.get(id as usize)
+        let position = self.position_of(NodeHandle::new(id))?;
+        self.tags.get(position)
     }
 
     /// Resolves an internal Node ID obtained from a NodeHandle to a Node
     #[inline]
     pub fn resolve_node_id_mut(&mut self, id: InnerNodeHandle) -> Option<&mut Node<'a>> {
-        self.tags.get_mut(id as usize)
+        let position = self.position_of(NodeHandle::new(id))?;
+        self.tags.get_mut(position)
     }
 
     pub(crate) fn parse(&mut self) -> Result<(), ParseError> {
@@ -393,10 +1168,218 @@ impl<'a> Parser<'a> {
             return Err(ParseError::InvalidLength);
         }
 
-        while !self.stream.is_eof() {
+        while !self.stream.is_eof() && !self.cancelled && self.strict_error.is_none() {
             self.parse_single();
         }
 
+        // elements that are still open had their end tags omitted, which is an error unless their end tags are optional
+        // or the input ended in the middle of a construct (which has been reported instead)
+        let is_eof_reported = self
+            .diagnostics
+            .iter()
+            .any(|diagnostic| matches!(diagnostic.kind, DiagnosticKind::UnexpectedEof { .. }))
+            || self.strict_error.is_some();
+
+        while let Some(&handle) = self.stack.last() {
+            if !self.cancelled && !is_eof_reported && !self.open_tag_has_optional_end_tag(handle) {
+                let tag = self.tags[handle.get_inner() as usize].as_tag().unwrap();
+                let name = tag._name.as_utf8_str().into_owned();
+                let at = tag._raw.as_ptr() as usize - self.stream.data().as_ptr() as usize;
+
+                self.report(DiagnosticKind::UnclosedElement { name }, at);
+            }
+
+            self.close_current_tag(self.stream.idx..self.stream.idx);
+        }
+
+        if let Some(diagnostic) = self.strict_error.take() {
+            return Err(ParseError::Strict(diagnostic));
+        }
+
+        if self.cancelled {
+            return match self.options.is_partial_on_cancel() {
+                true => Ok(()),
+                false => Err(ParseError::Cancelled),
+            };
+        }
+
+        if let Some(progress) = &self.options.progress {
+            let total = self.stream.len();
+            let _ = (progress.callback)(total, total);
+        }
+
         Ok(())
     }
+
+    /// Makes this parser use the allocations of the given scratch space, see [`Scratch`]
+    pub(crate) fn use_scratch(&mut self, scratch: Scratch) {
+        let Scratch {
+            mut stack,
+            parsed_attributes,
+            tag_names,
+        } = scratch;
+
+        stack.clear();
+        self.stack = stack;
+        self.parsed_attributes = recycle(parsed_attributes);
+        self.interned_tag_names = tag_names;
+    }
+
+    /// Takes the allocations that are only needed while parsing, see [`Scratch`]
+    pub(crate) fn take_scratch(&mut self) -> Scratch {
+        Scratch {
+            stack: std::mem::take(&mut self.stack),
+            parsed_attributes: recycle(std::mem::take(&mut self.parsed_attributes)),
+            tag_names: std::mem::take(&mut self.interned_tag_names),
+        }
+    }
+
+    /// Invokes the progress callback and schedules the next invocation
+    #[cold]
+    fn report_progress(&mut self) -> ControlFlow<()> {
+        let Some(progress) = &self.options.progress else {
+            return ControlFlow::Continue(());
+        };
+
+        let consumed = self.stream.idx;
+        self.next_progress = consumed.saturating_add(progress.granularity);
+        (progress.callback)(consumed, self.stream.len())
+    }
+}
+
+/// Allocations that a parser only needs while parsing, which can be reused for parsing the next document
+///
+/// The buffers are always empty. They do not borrow from any input, since only their allocations are kept.
+/// The interned tag names are kept as well, so that documents share the keys of their tag name lookup tables.
+#[derive(Debug, Default)]
+pub(crate) struct Scratch {
+    /// The stack of open elements
+    stack: Vec<NodeHandle>,
+    /// The buffer for the attributes of the tag that is currently being parsed
+    parsed_attributes: Vec<(&'static [u8], Option<&'static [u8]>)>,
+    /// The keys of the tag name lookup tables (see `ParserOptions::track_tag_names()`) seen so far
+    tag_names: HashSet<Arc<[u8]>>,
+}
+
+/// A start tag read by `Parser::read_start_tag()`
+#[derive(Debug)]
+pub(crate) struct StartTag {
+    /// The element, which owns its name and attributes and has no children
+    pub tag: HTMLTag<'static>,
+    /// The length of the start tag in bytes
+    pub len: usize,
+    /// Whether the start tag ends with `>` or `/>`, rather than being cut off by the end of the input
+    pub is_terminated: bool,
+    /// Whether the element has no content because the start tag ends with `/>` or is cut off
+    pub is_self_closing: bool,
+}
+
+/// Clears the buffer of parsed attributes, and changes its lifetime so that its allocation can usually be reused for another input
+fn recycle<'x, 'y>(
+    mut buffer: Vec<(&'x [u8], Option<&'x [u8]>)>,
+) -> Vec<(&'y [u8], Option<&'y [u8]>)> {
+    buffer.clear();
+    // collecting an empty vector into one with the same layout usually reuses its allocation (std does not guarantee it),
+    // otherwise the buffer simply grows again while parsing
+    buffer.into_iter().map(|_| unreachable!()).collect()
+}
+
+/// Creates the bytes that are stored for `text`, which is cut off after `max_len` bytes (at a char boundary)
+/// and whose line breaks are normalized if requested
+///
+/// The bytes are only owned if line breaks are normalized and `text` contains a `\r`.
+fn stored_bytes(text: &[u8], max_len: Option<usize>, normalize_newlines: bool) -> Bytes<'_> {
+    let (text, truncated) = match max_len {
+        Some(max_len) if text.len() > max_len => {
+            (&text[..util::floor_char_boundary(text, max_len)], true)
+        }
+        _ => (text, false),
+    };
+
+    let bytes = match normalize_newlines
+        .then(|| util::normalize_newlines(text))
+        .flatten()
+    {
+        Some(normalized) => {
+            let mut bytes = Bytes::new();
+            // SAFETY: normalizing never makes the text longer, and the input fits in a u32
+            unsafe { bytes.set_unchecked(normalized) };
+            bytes
+        }
+        None => Bytes::from(text),
+    };
+
+    bytes.with_truncated(truncated)
+}
+
+/// Calls `f` with each key under which an element with the given `class` attribute is stored in the lookup table of classes
+///
+/// These are its class names, with character references decoded if `decode` is `true`.
+/// Class names that are not valid UTF-8 are skipped.
+pub(crate) fn for_each_class_key<'a>(
+    class: &Bytes<'a>,
+    decode: bool,
+    mut f: impl FnMut(Bytes<'a>),
+) {
+    if let Cow::Owned(decoded) = class.decoded_if(decode) {
+        let classes = util::split_ascii_whitespace_bytes(&decoded)
+            .filter_map(|class| owned_bytes(class.to_vec()));
+
+        for class in classes {
+            f(class);
+        }
+        return;
+    }
+
+    match class.as_bytes_borrowed() {
+        Some(borrowed) => {
+            let classes = std::str::from_utf8(borrowed)
+                .into_iter()
+                .flat_map(util::split_ascii_whitespace);
+
+            for class in classes {
+                f(class.into());
+            }
+        }
+        None => {
+            // class values are only owned if their line breaks were normalized
+            let classes = class
+                .try_as_utf8_str()
+                .into_iter()
+                .flat_map(util::split_ascii_whitespace)
+                .filter_map(|class| Bytes::try_from(class.to_owned()).ok());
+
+            for class in classes {
+                f(class);
+            }
+        }
+    }
+}
+
+/// Stores the given data in owned bytes, or returns `None` if it is too long
+fn owned_bytes<'a>(data: Vec<u8>) -> Option<Bytes<'a>> {
+    let mut bytes = Bytes::new();
+    bytes.set(data).ok()?;
+    Some(bytes)
+}
+
+/// Adds the handles of `added` to the lists of elements with the same key in `table`, keeping them in document order
+fn merge_in_order<K, const N: usize>(
+    table: &mut HashMap<K, InlineVec<NodeHandle, N>>,
+    added: HashMap<K, InlineVec<NodeHandle, N>>,
+    position_of: impl Fn(NodeHandle) -> Option<usize>,
+) where
+    K: Eq + std::hash::Hash,
+{
+    for (key, handles) in added {
+        let list = table.entry(key).or_insert_with(InlineVec::new);
+
+        for &handle in handles.as_slice() {
+            let position = position_of(handle);
+            let index = list
+                .as_slice()
+                .partition_point(|&other| position_of(other) < position);
+            list.insert(index, handle);
+        }
+    }
 }
@@ -0,0 +1,205 @@
+use super::{
+    handle::NodeHandle,
+    tag::{Attributes, HTMLTag, Node},
+    Parser,
+};
+use crate::{errors::ElementBuilderError, inline::vec::InlineVec, tag_info, util, Bytes};
+
+impl<'a> Parser<'a> {
+    /// Creates a new element with the given name that is not attached to the document, and returns its handle
+    ///
+    /// Returns `None` if the name is not a valid tag name. See [`Parser::build_element()`] to also set attributes and text.
+    ///
+    /// # Example
+    /// ```
+    /// let mut dom = tl::parse("<p>Hello</p>", Default::default()).unwrap();
+    /// let parser = dom.parser_mut();
+    ///
+    /// let handle = parser.create_element("div").unwrap();
+    /// assert_eq!(handle.get(parser).unwrap().outer_html(parser), "<div></div>");
+    ///
+    /// // the element is not part of the document until it is attached
+    /// assert_eq!(dom.outer_html(), "<p>Hello</p>");
+    /// ```
+    pub fn create_element(&mut self, name: &str) -> Option<NodeHandle> {
+        self.build_element(name).finish().ok()
+    }
+
+    /// Returns a builder for a new element with the given name, which is not attached to the document
+    ///
+    /// # Example
+    /// ```
+    /// let mut dom = tl::parse("", Default::default()).unwrap();
+    /// let parser = dom.parser_mut();
+    ///
+    /// let handle = parser
+    ///     .build_element("a")
+    ///     .attr("href", "/x")
+    ///     .attr_bool("download")
+    ///     .class("btn primary")
+    ///     .text("Click")
+    ///     .finish()
+    ///     .unwrap();
+    ///
+    /// let html = handle.get(parser).unwrap().outer_html(parser);
+    /// assert_eq!(html, r#"<a href="/x" download class="btn primary">Click</a>"#);
+    /// ```
+    pub fn build_element<'p>(&'p mut self, name: &str) -> ElementBuilder<'p, 'a> {
+        let is_valid_name = !name.is_empty() && name.bytes().all(util::is_ident);
+
+        ElementBuilder {
+            parser: self,
+            name: name.to_owned(),
+            attributes: Vec::new(),
+            text: None,
+            error: (!is_valid_name)
+                .then(|| ElementBuilderError::InvalidElementName(name.to_owned())),
+        }
+    }
+
+    /// Adds the given node to this parser without attaching it to the document, and returns its handle
+    ///
+    /// This gives nodes that were constructed programmatically (e.g. with `HTMLTag::new()`) a handle,
+    /// so that they can be resolved and serialized like parsed nodes. The parent and the children of a tag are reset,
+    /// because they must refer to nodes in the document order of this parser.
+    ///
+    /// # Example
+    /// ```
+    /// let mut dom = tl::parse("<p>Hello</p>", Default::default()).unwrap();
+    /// let parser = dom.parser_mut();
+    ///
+    /// let handle = parser.add_node(tl::Node::Comment("<!-- generated -->".into()));
+    /// assert_eq!(handle.get(parser).unwrap().outer_html(parser), "<!-- generated -->");
+    ///
+    /// // the node is not part of the document until it is attached
+    /// assert_eq!(dom.outer_html(), "<p>Hello</p>");
+    /// ```
+    pub fn add_node(&mut self, mut node: Node<'a>) -> NodeHandle {
+        if let Node::Tag(tag) = &mut node {
+            tag._parent = None;
+            tag._children = InlineVec::new();
+        }
+
+        self.push_detached([node])[0]
+    }
+
+    /// Appends the given nodes to the end of the node table without attaching them to the document
+    pub(crate) fn push_detached<I>(&mut self, nodes: I) -> Vec<NodeHandle>
+    where
+        I: IntoIterator<Item = Node<'a>>,
+    {
+        let handles = self.insert_nodes(self.tags.len(), nodes);
+        self.detached += handles.len();
+        handles
+    }
+}
+
+/// A builder for an element with attributes and an optional text child, obtained by calling [`Parser::build_element()`]
+///
+/// Errors (such as invalid attribute names) are collected while building and returned by [`ElementBuilder::finish()`].
+#[derive(Debug)]
+pub struct ElementBuilder<'p, 'a> {
+    parser: &'p mut Parser<'a>,
+    name: String,
+    attributes: Vec<(String, Option<String>)>,
+    text: Option<String>,
+    /// The first error that occurred while building
+    error: Option<ElementBuilderError>,
+}
+
+impl<'p, 'a> ElementBuilder<'p, 'a> {
+    fn add_attribute(mut self, name: &str, value: Option<&str>) -> Self {
+        let is_valid_name = !name.is_empty()
+            && !name.bytes().any(|c| {
+                util::is_ascii_whitespace(c) || matches!(c, b'"' | b'\'' | b'>' | b'/' | b'=')
+            });
+
+        if !is_valid_name {
+            self.error
+                .get_or_insert_with(|| ElementBuilderError::InvalidAttributeName(name.to_owned()));
+        }
+
+        self.attributes
+            .push((name.to_owned(), value.map(str::to_owned)));
+        self
+    }
+
+    /// Adds an attribute with a value, replacing an attribute with the same name
+    pub fn attr(self, name: &str, value: &str) -> Self {
+        self.add_attribute(name, Some(value))
+    }
+
+    /// Adds an attribute without a value (e.g. `disabled`), replacing an attribute with the same name
+    pub fn attr_bool(self, name: &str) -> Self {
+        self.add_attribute(name, None)
+    }
+
+    /// Adds the whitespace-separated class names to the `class` attribute
+    pub fn class(mut self, classes: &str) -> Self {
+        let class = self
+            .attributes
+            .iter_mut()
+            .find(|(name, _)| name.eq_ignore_ascii_case("class"));
+
+        match class {
+            Some((_, Some(value))) if !value.is_empty() => {
+                value.push(' ');
+                value.push_str(classes);
+                self
+            }
+            Some((_, value)) => {
+                *value = Some(classes.to_owned());
+                self
+            }
+            None => self.attr("class", classes),
+        }
+    }
+
+    /// Sets the text content of the element, which is added as its only child
+    ///
+    /// The text is inserted as is, so characters such as `<` and `&` must already be escaped.
+    /// Void elements (e.g. `<img>`) cannot have any content, which makes `finish()` fail.
+    pub fn text(mut self, text: &str) -> Self {
+        if tag_info::is_void(self.name.as_bytes()) {
+            self.error
+                .get_or_insert_with(|| ElementBuilderError::VoidElementContent(self.name.clone()));
+        }
+
+        self.text = Some(text.to_owned());
+        self
+    }
+
+    /// Creates the element and its text child, and returns the handle of the element
+    ///
+    /// The element is not attached to the document, and its children are stored right after it.
+    ///
+    /// # Errors
+    /// Returns the first error that occurred while building, such as an invalid element or attribute name,
+    /// or text content for a void element.
+    pub fn finish(self) -> Result<NodeHandle, ElementBuilderError> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+
+        let owned = |s: String| Bytes::try_from(s).map_err(|_| ElementBuilderError::LengthOverflow);
+
+        let attributes = self
+            .attributes
+            .into_iter()
+            .map(|(name, value)| Ok((owned(name)?, value.map(owned).transpose()?)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut element = HTMLTag::from_name(owned(self.name)?, None);
+        element._attributes = Attributes::from_pairs(attributes);
+        let text = self.text.map(owned).transpose()?;
+
+        // the text child is stored right after the element, which keeps the node table in document order
+        let element = self.parser.push_detached([Node::Tag(element)])[0];
+        if let Some(text) = text {
+            let text = self.parser.push_detached([Node::Raw(text)])[0];
+            self.parser.attach(Some(element), 0, text);
+        }
+
+        Ok(element)
+    }
+}
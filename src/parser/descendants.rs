@@ -0,0 +1,403 @@
+use std::iter::FusedIterator;
+use std::ops::Range;
+
+use super::{HTMLTag, Node, NodeHandle, Parser};
+use crate::{util, Bytes};
+
+impl<'a> HTMLTag<'a> {
+    /// Returns an iterator over the handles of all nodes in the subtree of this tag (excluding the tag itself), in document order
+    ///
+    /// Unlike `Children::all()`, this yields handles rather than nodes, and it can be iterated from both ends.
+    /// Since the nodes of a subtree are stored contiguously, this does not allocate.
+    ///
+    /// # Example
+    /// ```
+    /// let dom = tl::parse("<div><p>a<b>b</b></p>c</div>", Default::default()).unwrap();
+    /// let parser = dom.parser();
+    /// let div = dom.nodes()[0].as_tag().unwrap();
+    ///
+    /// let html = div
+    ///     .descendants(parser)
+    ///     .map(|handle| handle.get(parser).unwrap().outer_html(parser))
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(html, ["<p>a<b>b</b></p>", "a", "<b>b</b>", "b", "c"]);
+    ///
+    /// let names = div
+    ///     .descendants(parser)
+    ///     .elements()
+    ///     .rev()
+    ///     .map(|handle| handle.get(parser).unwrap().as_tag().unwrap().name().as_utf8_str())
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(names, ["b", "p"]);
+    /// ```
+    pub fn descendants<'p>(&self, parser: &'p Parser<'a>) -> Descendants<'p, 'a> {
+        Descendants {
+            parser,
+            positions: self.children().positions(parser).unwrap_or(0..0),
+        }
+    }
+
+    /// Returns an iterator over the handles of the elements with the given tag name in the subtree of this tag
+    /// (excluding the tag itself), in document order
+    ///
+    /// Tag names are compared like in `VDom::get_elements_by_tag_name()`. If `ParserOptions::track_tag_names()` was set,
+    /// this uses the lookup table built while parsing, otherwise it scans the subtree.
+    ///
+    /// # Example
+    /// ```
+    /// let dom = tl::parse("<a>x</a><nav><a>a</a><p><A>b</A></p></nav>", Default::default()).unwrap();
+    /// let parser = dom.parser();
+    /// let nav = dom.get_elements_by_tag_name("nav").next().unwrap().get(parser).unwrap().as_tag().unwrap();
+    ///
+    /// let texts = nav
+    ///     .get_elements_by_tag_name(parser, "a")
+    ///     .map(|handle| handle.get(parser).unwrap().inner_text(parser))
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(texts, ["a", "b"]);
+    /// ```
+    pub fn get_elements_by_tag_name<'p>(
+        &self,
+        parser: &'p Parser<'a>,
+        name: &'p str,
+    ) -> Box<dyn Iterator<Item = NodeHandle> + 'p> {
+        let positions = self.children().positions(parser).unwrap_or(0..0);
+        parser.elements_by_tag_name(name, positions)
+    }
+
+    /// Returns an iterator over the direct children of this tag that are elements, together with their handles
+    ///
+    /// Unlike `children().top()`, this skips text nodes (including whitespace between elements) and comments.
+    ///
+    /// # Example
+    /// ```
+    /// let dom = tl::parse("<ul>\n  <li>a</li>\n  <!-- b -->\n  <li>c</li>\n</ul>", Default::default()).unwrap();
+    /// let parser = dom.parser();
+    /// let ul = dom.nodes()[0].as_tag().unwrap();
+    ///
+    /// let texts = ul
+    ///     .element_children(parser)
+    ///     .map(|(_, li)| li.inner_text(parser))
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(texts, ["a", "c"]);
+    ///
+    /// let (_, last) = ul.last_element_child(parser).unwrap();
+    /// assert_eq!(last.inner_text(parser), "c");
+    /// ```
+    pub fn element_children<'p>(&'p self, parser: &'p Parser<'a>) -> ElementChildren<'p, 'a> {
+        ElementChildren::new(parser, self._children.as_slice())
+    }
+
+    /// Returns the first direct child of this tag that is an element, see `element_children()`
+    pub fn first_element_child<'p>(
+        &'p self,
+        parser: &'p Parser<'a>,
+    ) -> Option<(NodeHandle, &'p HTMLTag<'a>)> {
+        self.element_children(parser).next()
+    }
+
+    /// Returns the last direct child of this tag that is an element, see `element_children()`
+    pub fn last_element_child<'p>(
+        &'p self,
+        parser: &'p Parser<'a>,
+    ) -> Option<(NodeHandle, &'p HTMLTag<'a>)> {
+        self.element_children(parser).next_back()
+    }
+
+    /// Returns the handle of the first node in the subtree of this tag (excluding the tag itself)
+    /// for which the predicate returns `true`, in document order
+    ///
+    /// This is like `VDom::find_node()`, but only searches this subtree.
+    ///
+    /// # Example
+    /// ```
+    /// let dom = tl::parse("<p>a</p><div><p>b</p><p>c</p></div>", Default::default()).unwrap();
+    /// let parser = dom.parser();
+    /// let div = dom.nodes()[2].as_tag().unwrap();
+    ///
+    /// let p = div
+    ///     .find_node(parser, |node, _| node.as_tag().is_some_and(|tag| tag.name() == "p"))
+    ///     .unwrap();
+    /// assert_eq!(p.get(parser).unwrap().inner_text(parser), "b");
+    /// ```
+    pub fn find_node<F>(&self, parser: &Parser<'a>, f: F) -> Option<NodeHandle>
+    where
+        F: FnMut(&Node<'a>, NodeHandle) -> bool,
+    {
+        self.find_all_nodes(parser, f).next()
+    }
+
+    /// Returns an iterator over the handles of all nodes in the subtree of this tag (excluding the tag itself)
+    /// for which the predicate returns `true`, in document order
+    ///
+    /// See `find_node()`.
+    pub fn find_all_nodes<'p, F>(
+        &self,
+        parser: &'p Parser<'a>,
+        f: F,
+    ) -> Box<dyn Iterator<Item = NodeHandle> + 'p>
+    where
+        F: FnMut(&Node<'a>, NodeHandle) -> bool + 'p,
+    {
+        parser.find_nodes(self.children().positions(parser).unwrap_or(0..0), f)
+    }
+
+    /// Returns an iterator over the text nodes in the subtree of this tag and their handles, in document order
+    ///
+    /// Unlike `HTMLTag::inner_text()`, this yields every text node separately, so that each one can be mapped back to its parent.
+    /// Comments (and other markup declarations) are skipped. Call [`TextNodes::non_whitespace()`] to also skip text nodes
+    /// that only consist of ASCII whitespace.
+    ///
+    /// # Example
+    /// ```
+    /// let dom = tl::parse("<div>\n  <p>Hello <b>world</b></p><!-- note -->\n</div>", Default::default()).unwrap();
+    /// let parser = dom.parser();
+    /// let div = dom.nodes()[0].as_tag().unwrap();
+    ///
+    /// let texts = div
+    ///     .text_nodes(parser)
+    ///     .non_whitespace()
+    ///     .map(|(_, text)| text.as_utf8_str())
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(texts, ["Hello ", "world"]);
+    ///
+    /// // including the line breaks and indentation
+    /// assert_eq!(div.text_nodes(parser).count(), 4);
+    /// ```
+    pub fn text_nodes<'p>(&self, parser: &'p Parser<'a>) -> TextNodes<'p, 'a> {
+        TextNodes::new(parser, self.children().positions(parser).unwrap_or(0..0))
+    }
+}
+
+impl<'a> Parser<'a> {
+    /// Returns an iterator over the handles of the nodes at the given positions of the node table for which the predicate returns `true`
+    pub(crate) fn find_nodes<'p, F>(
+        &'p self,
+        positions: Range<usize>,
+        mut f: F,
+    ) -> Box<dyn Iterator<Item = NodeHandle> + 'p>
+    where
+        F: FnMut(&Node<'a>, NodeHandle) -> bool + 'p,
+    {
+        Box::new(positions.filter_map(move |position| {
+            let handle = self.handle_at(position);
+            f(&self.tags[position], handle).then_some(handle)
+        }))
+    }
+}
+
+/// An iterator over the handles of the nodes in the subtree of a tag, obtained by calling [`HTMLTag::descendants()`]
+#[derive(Debug, Clone)]
+pub struct Descendants<'p, 'a> {
+    parser: &'p Parser<'a>,
+    /// The positions in the node table that have not been yielded yet
+    positions: Range<usize>,
+}
+
+impl<'p, 'a> Descendants<'p, 'a> {
+    /// Returns an iterator that only yields the handles of elements, skipping text and comment nodes
+    pub fn elements(self) -> DescendantElements<'p, 'a> {
+        DescendantElements(self)
+    }
+}
+
+impl<'p, 'a> Iterator for Descendants<'p, 'a> {
+    type Item = NodeHandle;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let position = self.positions.next()?;
+        Some(self.parser.handle_at(position))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.positions.size_hint()
+    }
+}
+
+impl<'p, 'a> DoubleEndedIterator for Descendants<'p, 'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let position = self.positions.next_back()?;
+        Some(self.parser.handle_at(position))
+    }
+}
+
+impl<'p, 'a> ExactSizeIterator for Descendants<'p, 'a> {}
+
+impl<'p, 'a> FusedIterator for Descendants<'p, 'a> {}
+
+/// An iterator over the handles of the elements in the subtree of a tag, obtained by calling [`Descendants::elements()`]
+#[derive(Debug, Clone)]
+pub struct DescendantElements<'p, 'a>(Descendants<'p, 'a>);
+
+impl<'p, 'a> DescendantElements<'p, 'a> {
+    /// Checks whether the node at the given position is an element
+    fn is_element(&self, position: usize) -> bool {
+        self.0.parser.tags[position].as_tag().is_some()
+    }
+}
+
+impl<'p, 'a> Iterator for DescendantElements<'p, 'a> {
+    type Item = NodeHandle;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(position) = self.0.positions.next() {
+            if self.is_element(position) {
+                return Some(self.0.parser.handle_at(position));
+            }
+        }
+
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.0.positions.len()))
+    }
+}
+
+impl<'p, 'a> DoubleEndedIterator for DescendantElements<'p, 'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        while let Some(position) = self.0.positions.next_back() {
+            if self.is_element(position) {
+                return Some(self.0.parser.handle_at(position));
+            }
+        }
+
+        None
+    }
+}
+
+impl<'p, 'a> FusedIterator for DescendantElements<'p, 'a> {}
+
+/// An iterator over the text nodes of a document or subtree and their handles, obtained by calling
+/// [`HTMLTag::text_nodes()`] or `VDom::text_nodes()`
+#[derive(Debug, Clone)]
+pub struct TextNodes<'p, 'a> {
+    parser: &'p Parser<'a>,
+    /// The positions in the node table that have not been yielded yet
+    positions: Range<usize>,
+    /// Whether text nodes that only consist of ASCII whitespace are skipped
+    skip_whitespace: bool,
+}
+
+impl<'p, 'a> TextNodes<'p, 'a> {
+    /// Creates an iterator over the text nodes at the given positions of the node table
+    pub(crate) fn new(parser: &'p Parser<'a>, positions: Range<usize>) -> Self {
+        Self {
+            parser,
+            positions,
+            skip_whitespace: false,
+        }
+    }
+
+    /// Returns an iterator that additionally skips text nodes that are empty or only consist of ASCII whitespace
+    pub fn non_whitespace(self) -> Self {
+        Self {
+            skip_whitespace: true,
+            ..self
+        }
+    }
+
+    /// Returns the handle and text of the node at the given position, if it is a text node that is not skipped
+    fn text_at(&self, position: usize) -> Option<(NodeHandle, &'p Bytes<'a>)> {
+        let text = self.parser.tags[position].as_raw()?;
+
+        if self.skip_whitespace
+            && text
+                .as_bytes()
+                .iter()
+                .all(|&c| util::is_ascii_whitespace(c))
+        {
+            return None;
+        }
+
+        Some((self.parser.handle_at(position), text))
+    }
+}
+
+impl<'p, 'a> Iterator for TextNodes<'p, 'a> {
+    type Item = (NodeHandle, &'p Bytes<'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(position) = self.positions.next() {
+            if let Some(item) = self.text_at(position) {
+                return Some(item);
+            }
+        }
+
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.positions.len()))
+    }
+}
+
+impl<'p, 'a> DoubleEndedIterator for TextNodes<'p, 'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        while let Some(position) = self.positions.next_back() {
+            if let Some(item) = self.text_at(position) {
+                return Some(item);
+            }
+        }
+
+        None
+    }
+}
+
+impl<'p, 'a> FusedIterator for TextNodes<'p, 'a> {}
+
+/// An iterator over the child elements of a tag or document and their handles, obtained by calling
+/// [`HTMLTag::element_children()`] or `VDom::element_children()`
+#[derive(Debug, Clone)]
+pub struct ElementChildren<'p, 'a> {
+    parser: &'p Parser<'a>,
+    /// The children that have not been yielded yet
+    children: std::slice::Iter<'p, NodeHandle>,
+}
+
+impl<'p, 'a> ElementChildren<'p, 'a> {
+    /// Creates an iterator over the elements among the given children
+    pub(crate) fn new(parser: &'p Parser<'a>, children: &'p [NodeHandle]) -> Self {
+        Self {
+            parser,
+            children: children.iter(),
+        }
+    }
+
+    /// Returns the handle and tag of the given child, if it is an element
+    fn element(&self, handle: NodeHandle) -> Option<(NodeHandle, &'p HTMLTag<'a>)> {
+        let tag = handle.get(self.parser)?.as_tag()?;
+        Some((handle, tag))
+    }
+}
+
+impl<'p, 'a> Iterator for ElementChildren<'p, 'a> {
+    type Item = (NodeHandle, &'p HTMLTag<'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(&handle) = self.children.next() {
+            if let Some(item) = self.element(handle) {
+                return Some(item);
+            }
+        }
+
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.children.len()))
+    }
+}
+
+impl<'p, 'a> DoubleEndedIterator for ElementChildren<'p, 'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        while let Some(&handle) = self.children.next_back() {
+            if let Some(item) = self.element(handle) {
+                return Some(item);
+            }
+        }
+
+        None
+    }
+}
+
+impl<'p, 'a> FusedIterator for ElementChildren<'p, 'a> {}
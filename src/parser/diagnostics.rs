@@ -0,0 +1,105 @@
+use core::fmt;
+
+/// The kind of a recoverable error that was encountered while parsing
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[non_exhaustive]
+pub enum DiagnosticKind {
+    /// A tag contained characters that do not form a valid attribute name,
+    /// such as templating syntax (`<div {{#if x}}class="a"{{/if}}>`).
+    ///
+    /// The characters are kept as an attribute, so that the following attributes can still be parsed.
+    InvalidAttributeName,
+    /// The input ended in the middle of a construct, such as an unclosed comment (`<!-- never closed`)
+    /// or an attribute value without a closing quote (`<a href="x`).
+    ///
+    /// The construct is kept as a node that covers the rest of the input, and `at` is the position at which it starts.
+    /// At most one of these is reported, for the innermost construct.
+    UnexpectedEof {
+        /// The construct that was not terminated
+        in_construct: Construct,
+    },
+    /// An end tag did not match any open element (`<p>a</span>`), or an element inside of the matching one
+    /// is still open (`<div><b>a</div>`).
+    ///
+    /// The end tag is ignored, and `at` is the position at which it starts.
+    StrayEndTag {
+        /// The tag name of the end tag, as written in the input
+        name: String,
+    },
+    /// An element was still open at the end of the input (`<div>a`).
+    ///
+    /// The element ends with the input, and `at` is the position of its start tag. Elements whose end tags may be omitted
+    /// (`<li>`, `<dt>`, `<dd>`, `<option>` and `<optgroup>`) are not reported, and neither are open elements
+    /// if the input ended in the middle of a construct (see [`DiagnosticKind::UnexpectedEof`]).
+    UnclosedElement {
+        /// The tag name of the element, as written in the input
+        name: String,
+    },
+}
+
+/// A part of the HTML syntax that the input can end in, see [`DiagnosticKind::UnexpectedEof`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum Construct {
+    /// A comment (`<!-- ... -->`)
+    Comment,
+    /// A doctype or another markup declaration (`<!DOCTYPE html>`)
+    Doctype,
+    /// A start tag (`<a href="x">`)
+    StartTag,
+    /// An end tag (`</a>`)
+    EndTag,
+    /// A quoted attribute value (`"x"`)
+    AttributeValue,
+    /// The content of a raw text element such as `<script>`, which is terminated by its end tag
+    RawText,
+}
+
+impl fmt::Display for Construct {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        f.write_str(match self {
+            Construct::Comment => "comment",
+            Construct::Doctype => "doctype",
+            Construct::StartTag => "start tag",
+            Construct::EndTag => "end tag",
+            Construct::AttributeValue => "attribute value",
+            Construct::RawText => "raw text element",
+        })
+    }
+}
+
+/// A recoverable error that was encountered while parsing
+///
+/// Diagnostics are only collected if `ParserOptions::collect_diagnostics()` was set,
+/// and can be obtained by calling `VDom::diagnostics()`. With `ParserOptions::strict()`,
+/// the first one aborts parsing with [`ParseError::Strict`](crate::ParseError::Strict).
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Diagnostic {
+    /// The kind of error
+    pub kind: DiagnosticKind,
+    /// The byte offset into the input string at which the error occurred
+    pub at: usize,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match &self.kind {
+            DiagnosticKind::InvalidAttributeName => {
+                write!(f, "Invalid attribute name at offset {}", self.at)
+            }
+            DiagnosticKind::UnexpectedEof { in_construct } => {
+                write!(
+                    f,
+                    "Unexpected end of input in {} at offset {}",
+                    in_construct, self.at
+                )
+            }
+            DiagnosticKind::StrayEndTag { name } => {
+                write!(f, "Stray end tag `</{}>` at offset {}", name, self.at)
+            }
+            DiagnosticKind::UnclosedElement { name } => {
+                write!(f, "Unclosed element `<{}>` at offset {}", name, self.at)
+            }
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use std::fmt;
+
+use super::{
+    tag::{Attributes, HTMLTag, Node},
+    Parser,
+};
+
+impl<'a, 'b> PartialEq<Attributes<'b>> for Attributes<'a> {
+    /// Compares the attributes regardless of their order
+    fn eq(&self, other: &Attributes<'b>) -> bool {
+        if self.len() != other.len() {
+            return false;
+        }
+
+        let mut this = self.iter().collect::<Vec<_>>();
+        let mut other = other.iter().collect::<Vec<_>>();
+        this.sort_unstable();
+        other.sort_unstable();
+
+        this == other
+    }
+}
+
+impl<'a> HTMLTag<'a> {
+    /// Checks if this tag is structurally equal to another tag, which may belong to a different document
+    ///
+    /// Two tags are equal if their names are equal (ignoring case), they have the same attributes in any order,
+    /// and their children are equal. Text and comments are compared by their bytes.
+    pub fn eq_in<'b>(
+        &self,
+        parser: &Parser<'a>,
+        other: &HTMLTag<'b>,
+        other_parser: &Parser<'b>,
+    ) -> bool {
+        // compare pairs of tags with an explicit stack, so that deeply nested documents cannot overflow the stack
+        let mut stack = vec![(self, other)];
+
+        while let Some((tag, other)) = stack.pop() {
+            if !tag
+                .name()
+                .as_bytes()
+                .eq_ignore_ascii_case(other.name().as_bytes())
+                || tag.attributes() != other.attributes()
+            {
+                return false;
+            }
+
+            let children = tag.children();
+            let other_children = other.children();
+            let (children, other_children) = (children.top(), other_children.top());
+
+            if children.len() != other_children.len() {
+                return false;
+            }
+
+            for (a, b) in children.iter().zip(other_children.iter()) {
+                match (a.get(parser), b.get(other_parser)) {
+                    (Some(Node::Tag(a)), Some(Node::Tag(b))) => stack.push((a, b)),
+                    (Some(a), Some(b)) if a.eq_in(parser, b, other_parser) => {}
+                    (None, None) => {}
+                    _ => return false,
+                }
+            }
+        }
+
+        true
+    }
+}
+
+impl<'a> Node<'a> {
+    /// Checks if this node is structurally equal to another node, which may belong to a different document
+    ///
+    /// See [`HTMLTag::eq_in()`] for how tags are compared, and [`NodeRef`] for a wrapper that implements `PartialEq`.
+    pub fn eq_in<'b>(
+        &self,
+        parser: &Parser<'a>,
+        other: &Node<'b>,
+        other_parser: &Parser<'b>,
+    ) -> bool {
+        match (self, other) {
+            (Node::Tag(a), Node::Tag(b)) => a.eq_in(parser, b, other_parser),
+            (Node::Raw(a), Node::Raw(b)) | (Node::Comment(a), Node::Comment(b)) => {
+                a.as_bytes() == b.as_bytes()
+            }
+            _ => false,
+        }
+    }
+}
+
+/// A node together with the parser it belongs to, which implements structural equality
+///
+/// # Example
+/// ```
+/// use tl::NodeRef;
+///
+/// let a = tl::parse(r#"<p id=x class='y'>Hello</p>"#, Default::default()).unwrap();
+/// let b = tl::parse(r#"<P class="y" id="x">Hello</P>"#, Default::default()).unwrap();
+///
+/// assert_eq!(
+///     NodeRef::new(&a.nodes()[0], a.parser()),
+///     NodeRef::new(&b.nodes()[0], b.parser())
+/// );
+/// ```
+#[derive(Clone, Copy)]
+pub struct NodeRef<'p, 'a> {
+    node: &'p Node<'a>,
+    parser: &'p Parser<'a>,
+}
+
+impl<'p, 'a> NodeRef<'p, 'a> {
+    /// Creates a new `NodeRef` from a node and the parser it belongs to
+    pub fn new(node: &'p Node<'a>, parser: &'p Parser<'a>) -> Self {
+        Self { node, parser }
+    }
+
+    /// Returns the node
+    pub fn node(&self) -> &'p Node<'a> {
+        self.node
+    }
+
+    /// Returns the parser the node belongs to
+    pub fn parser(&self) -> &'p Parser<'a> {
+        self.parser
+    }
+}
+
+impl<'p, 'q, 'a, 'b> PartialEq<NodeRef<'q, 'b>> for NodeRef<'p, 'a> {
+    fn eq(&self, other: &NodeRef<'q, 'b>) -> bool {
+        self.node.eq_in(self.parser, other.node, other.parser)
+    }
+}
+
+impl fmt::Debug for NodeRef<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("NodeRef")
+            .field(&self.node.outer_html(self.parser))
+            .finish()
+    }
+}
@@ -1,10 +1,53 @@
-use crate::Node;
+use std::{
+    cmp::Ordering,
+    fmt,
+    hash::{Hash, Hasher},
+    iter::FusedIterator,
+};
 
-use super::Parser;
+use crate::{HTMLTag, Node};
+
+use super::{NodePath, Parser};
 
 /// The inner type of a NodeHandle, used to represent an index into the tags table
 pub type InnerNodeHandle = u32;
 
+/// Identifies the parser that created a handle, so that handles can detect being used with another parser
+///
+/// This is only stored in debug builds or if the `checked-handles` feature is enabled, and is zero-sized otherwise.
+/// The id `0` is used for handles created with `NodeHandle::new()`, which are accepted by every parser.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) struct ParserId(#[cfg(any(debug_assertions, feature = "checked-handles"))] u32);
+
+impl ParserId {
+    /// Returns a new id that is different from the ids of all parsers created before
+    pub(crate) fn next() -> Self {
+        #[cfg(any(debug_assertions, feature = "checked-handles"))]
+        {
+            use std::sync::atomic::{AtomicU32, Ordering};
+
+            static NEXT: AtomicU32 = AtomicU32::new(1);
+            Self(NEXT.fetch_add(1, Ordering::Relaxed))
+        }
+
+        #[cfg(not(any(debug_assertions, feature = "checked-handles")))]
+        Self()
+    }
+
+    /// Checks whether a handle created by the parser with this id may be used with the parser with the given id
+    #[inline]
+    fn accepts(self, parser: ParserId) -> bool {
+        #[cfg(any(debug_assertions, feature = "checked-handles"))]
+        return self.0 == 0 || self.0 == parser.0;
+
+        #[cfg(not(any(debug_assertions, feature = "checked-handles")))]
+        {
+            let _ = parser;
+            true
+        }
+    }
+}
+
 /// A detached, external handle to a HTML node, originally obtained from a [Parser]
 ///
 /// It contains an identifier that uniquely identifies an HTML node.
@@ -15,38 +58,312 @@ pub type InnerNodeHandle = u32;
 /// of nodes, and store indices into the vector instead of references.
 /// In the case of tl, the "global" HTML tag vector is stored in the [`Parser`] and [`NodeHandle`] represents the index.
 /// Because [`NodeHandle`] is only an index and completely detached from anything, you need to pass a parser to `NodeHandle::get()`
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
-#[repr(transparent)]
-pub struct NodeHandle(InnerNodeHandle);
+///
+/// # Checked handles
+/// In debug builds, or if the `checked-handles` feature is enabled, handles also remember which parser created them.
+/// Using a handle with another parser then behaves as if the node did not exist: `get()` and `get_mut()` return `None`,
+/// and so do all other methods that look up nodes by handle. Otherwise, such a handle silently refers to an unrelated node.
+/// Handles created with [`NodeHandle::new()`] are not checked. Comparing and hashing handles only considers the node id.
+#[derive(Clone, Copy)]
+#[cfg_attr(
+    not(any(debug_assertions, feature = "checked-handles")),
+    repr(transparent)
+)]
+pub struct NodeHandle {
+    id: InnerNodeHandle,
+    parser: ParserId,
+}
 
 impl NodeHandle {
     /// Creates a new handle to the given node
+    ///
+    /// The handle is not associated with a parser, so it is never rejected as belonging to another parser.
     #[inline]
     pub fn new(node: InnerNodeHandle) -> Self {
-        NodeHandle(node)
+        Self::with_parser(node, ParserId::default())
+    }
+
+    /// Creates a new handle to the given node of the parser with the given id
+    #[inline]
+    pub(crate) fn with_parser(node: InnerNodeHandle, parser: ParserId) -> Self {
+        NodeHandle { id: node, parser }
+    }
+
+    /// Checks whether this handle may be used with the given parser (see "Checked handles" above)
+    #[inline]
+    pub(crate) fn belongs_to(&self, parser: &Parser) -> bool {
+        self.parser.accepts(parser.id)
     }
 
     /// Returns a reference to the node that is associated to this specific handle
     ///
-    /// It is an error to pass in the wrong parser.
+    /// It is an error to pass in the wrong parser. If the handle is checked (see the type-level documentation),
+    /// this returns `None`, otherwise it returns an unrelated node or `None`.
     /// It will either return `None` if this index points outside of the nodes table,
     /// or it will return the one it points to.
     pub fn get<'p, 'buf>(&self, parser: &'p Parser<'buf>) -> Option<&'p Node<'buf>> {
-        parser.resolve_node_id(self.0)
+        let position = parser.position_of(*self)?;
+        parser.tags.get(position)
     }
 
     /// Returns a mutable reference to the node that is associated to this specific handle
     ///
-    /// It is an error to pass in the wrong parser.
+    /// It is an error to pass in the wrong parser. If the handle is checked (see the type-level documentation),
+    /// this returns `None`, otherwise it returns an unrelated node or `None`.
     /// It will either return `None` if this index points outside of the nodes table,
     /// or it will return the one it points to.
     pub fn get_mut<'p, 'buf>(&self, parser: &'p mut Parser<'buf>) -> Option<&'p mut Node<'buf>> {
-        parser.resolve_node_id_mut(self.0)
+        let position = parser.position_of(*self)?;
+        parser.tags.get_mut(position)
+    }
+
+    /// Returns the parent element of this node, or `None` for top-level and detached nodes
+    ///
+    /// Unlike `HTMLTag::parent()`, this also works for text and comment nodes. Their parent is looked up
+    /// in a table if `ParserOptions::track_parents()` is set, and found by walking the document otherwise,
+    /// which takes time linear in the size of the document.
+    ///
+    /// # Example
+    /// ```
+    /// let dom = tl::parse("<div><p>Hello</p></div>", Default::default()).unwrap();
+    /// let parser = dom.parser();
+    ///
+    /// let p = dom.query_selector("p").unwrap().next().unwrap();
+    /// let text = p.get(parser).unwrap().children().unwrap().top()[0];
+    /// assert_eq!(text.parent(parser), Some(p));
+    ///
+    /// let div = p.parent(parser).unwrap();
+    /// assert_eq!(div.get(parser).unwrap().as_tag().unwrap().name(), "div");
+    /// assert_eq!(div.parent(parser), None);
+    /// ```
+    pub fn parent(&self, parser: &Parser) -> Option<NodeHandle> {
+        parser.parent_of(*self)
+    }
+
+    /// Returns an iterator over the ancestors of this node, from its parent up to the top-level element that contains it
+    ///
+    /// The iterator is empty for top-level and detached nodes. It yields at most as many handles as there are nodes,
+    /// so it ends even if the parents were changed into a cycle through `Parser` internals or mutable node access.
+    ///
+    /// # Example
+    /// ```
+    /// let dom = tl::parse(r#"<body><div class="content"><p>Hi</p></div></body>"#, Default::default()).unwrap();
+    /// let parser = dom.parser();
+    /// let p = dom.query_selector("p").unwrap().next().unwrap();
+    ///
+    /// let mut path = p
+    ///     .ancestor_tags(parser)
+    ///     .map(|tag| tag.name().as_utf8_str().into_owned())
+    ///     .collect::<Vec<_>>();
+    /// path.reverse();
+    /// assert_eq!(path.join(" > "), "body > div");
+    /// ```
+    pub fn ancestors<'p, 'a>(&self, parser: &'p Parser<'a>) -> Ancestors<'p, 'a> {
+        Ancestors {
+            parser,
+            current: *self,
+            remaining: parser.tags.len(),
+        }
+    }
+
+    /// Returns an iterator over the ancestors of this node as elements, see [`NodeHandle::ancestors()`]
+    pub fn ancestor_tags<'p, 'a>(
+        &self,
+        parser: &'p Parser<'a>,
+    ) -> impl Iterator<Item = &'p HTMLTag<'a>> + 'p {
+        self.ancestors(parser)
+            .filter_map(move |handle| handle.get(parser)?.as_tag())
+    }
+
+    /// Returns the node that follows this node in the children of its parent (or in the top-level nodes)
+    ///
+    /// Returns `None` for the last child and for detached nodes. Like `NodeHandle::parent()`, this is faster
+    /// for text and comment nodes if `ParserOptions::track_parents()` is set.
+    ///
+    /// # Example
+    /// ```
+    /// let dom = tl::parse("<p>a<b>b</b><!-- c --></p>", Default::default()).unwrap();
+    /// let parser = dom.parser();
+    /// let children = dom.nodes()[0].children().unwrap().top().to_vec();
+    ///
+    /// assert_eq!(children[0].next_sibling(parser), Some(children[1]));
+    /// assert_eq!(children[2].next_sibling(parser), None);
+    /// assert_eq!(children[2].prev_sibling(parser), Some(children[1]));
+    /// assert_eq!(children[0].prev_sibling(parser), None);
+    /// ```
+    pub fn next_sibling(&self, parser: &Parser) -> Option<NodeHandle> {
+        let (siblings, index) = parser.siblings_of(*self)?;
+        siblings.get(index + 1).copied()
+    }
+
+    /// Returns the node that precedes this node in the children of its parent (or in the top-level nodes)
+    ///
+    /// Returns `None` for the first child and for detached nodes. See [`NodeHandle::next_sibling()`].
+    pub fn prev_sibling(&self, parser: &Parser) -> Option<NodeHandle> {
+        let (siblings, index) = parser.siblings_of(*self)?;
+        siblings[..index].last().copied()
+    }
+
+    /// Returns the next sibling of this node that is an element, skipping text and comment nodes
+    ///
+    /// # Example
+    /// ```
+    /// let dom = tl::parse("<ul><li>a</li>\n<!-- b -->\n<li>c</li></ul>", Default::default()).unwrap();
+    /// let parser = dom.parser();
+    /// let items = dom.query_selector("li").unwrap().collect::<Vec<_>>();
+    ///
+    /// assert_eq!(items[0].next_element_sibling(parser), Some(items[1]));
+    /// assert_eq!(items[1].prev_element_sibling(parser), Some(items[0]));
+    /// assert_eq!(items[1].next_element_sibling(parser), None);
+    /// ```
+    pub fn next_element_sibling(&self, parser: &Parser) -> Option<NodeHandle> {
+        let (siblings, index) = parser.siblings_of(*self)?;
+        siblings[index + 1..]
+            .iter()
+            .copied()
+            .find(|sibling| is_element(*sibling, parser))
+    }
+
+    /// Returns the previous sibling of this node that is an element, skipping text and comment nodes
+    ///
+    /// See [`NodeHandle::next_element_sibling()`].
+    pub fn prev_element_sibling(&self, parser: &Parser) -> Option<NodeHandle> {
+        let (siblings, index) = parser.siblings_of(*self)?;
+        siblings[..index]
+            .iter()
+            .copied()
+            .rev()
+            .find(|sibling| is_element(*sibling, parser))
+    }
+
+    /// Returns the structural path of this node, i.e. the child indices that lead from the top-level nodes to this node
+    ///
+    /// The path can be resolved back to a handle using `VDom::resolve_path()`.
+    /// If this handle does not refer to a node that is reachable from the top-level nodes, the path is empty.
+    ///
+    /// # Example
+    /// ```
+    /// let dom = tl::parse("<div>a<p><b>b</b></p></div>", Default::default()).unwrap();
+    /// let b = dom.query_selector("b").unwrap().next().unwrap();
+    ///
+    /// let path = b.node_path(dom.parser());
+    /// assert_eq!(path.indices(), [0, 1, 0]);
+    /// assert_eq!(dom.resolve_path(&path), Some(b));
+    /// ```
+    pub fn node_path(&self, parser: &Parser) -> NodePath {
+        NodePath::of(*self, parser).unwrap_or_default()
+    }
+
+    /// Returns the closest element that matches the given selector, starting with this node and walking up its ancestors
+    ///
+    /// Equivalent to [Element#closest](https://developer.mozilla.org/en-US/docs/Web/API/Element/closest) in browsers.
+    /// Returns `None` if neither this node nor any of its ancestors match, if this node is not an element, or if the selector is invalid.
+    ///
+    /// # Example
+    /// ```
+    /// let dom = tl::parse(r#"<article data-id="1"><p><span>Hi</span></p></article>"#, Default::default()).unwrap();
+    /// let parser = dom.parser();
+    /// let span = dom.query_selector("span").unwrap().next().unwrap();
+    ///
+    /// let article = span.closest(parser, "article[data-id]").unwrap();
+    /// assert_eq!(article.get(parser).unwrap().as_tag().unwrap().name(), "article");
+    /// assert_eq!(span.closest(parser, "span"), Some(span));
+    /// assert_eq!(span.closest(parser, "div"), None);
+    /// ```
+    pub fn closest(&self, parser: &Parser, selector: &str) -> Option<NodeHandle> {
+        let selector = crate::parse_query_selector(selector)?;
+        let mut current = *self;
+
+        loop {
+            let node = current.get(parser)?;
+            if selector.matches(node, parser) {
+                return Some(current);
+            }
+
+            // topmost elements have no parent, which ends the walk at the document root
+            current = node.as_tag()?.parent()?;
+        }
     }
 
     /// Returns the internal unique Node ID that maps to a specific node in the node table
     #[inline]
     pub fn get_inner(&self) -> InnerNodeHandle {
-        self.0
+        self.id
+    }
+}
+
+/// An iterator over the ancestors of a node, obtained by calling [`NodeHandle::ancestors()`]
+#[derive(Debug, Clone)]
+pub struct Ancestors<'p, 'a> {
+    parser: &'p Parser<'a>,
+    /// The node whose parent is yielded next
+    current: NodeHandle,
+    /// The maximum number of handles that are still yielded, which guards against cycles
+    remaining: usize,
+}
+
+impl<'p, 'a> Iterator for Ancestors<'p, 'a> {
+    type Item = NodeHandle;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.remaining = self.remaining.checked_sub(1)?;
+
+        match self.current.parent(self.parser) {
+            Some(parent) => {
+                self.current = parent;
+                Some(parent)
+            }
+            None => {
+                self.remaining = 0;
+                None
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.remaining))
+    }
+}
+
+impl<'p, 'a> FusedIterator for Ancestors<'p, 'a> {}
+
+/// Checks whether the given handle refers to an element
+fn is_element(handle: NodeHandle, parser: &Parser) -> bool {
+    handle.get(parser).and_then(Node::as_tag).is_some()
+}
+
+impl fmt::Debug for NodeHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("NodeHandle").field(&self.id).finish()
+    }
+}
+
+impl PartialEq for NodeHandle {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for NodeHandle {}
+
+impl PartialOrd for NodeHandle {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for NodeHandle {
+    #[inline]
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.id.cmp(&other.id)
+    }
+}
+
+impl Hash for NodeHandle {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
     }
 }
@@ -0,0 +1,133 @@
+/// The longest attribute name we know about (`crossorigin`, `placeholder`)
+///
+/// Names that are longer than this are never known attributes, which lets us lowercase into a stack buffer.
+const MAX_KNOWN_NAME_LEN: usize = 11;
+
+macro_rules! known_attrs {
+    ($($(#[$meta:meta])* $variant:ident => $name:literal,)*) => {
+        /// A commonly used attribute name
+        ///
+        /// Attributes with one of these names are stored under their lowercase name, so they can be looked up with
+        /// [`Attributes::get_known()`](crate::Attributes::get_known) without lowercasing the name first.
+        ///
+        /// # Example
+        /// ```
+        /// use tl::KnownAttr;
+        ///
+        /// assert_eq!(KnownAttr::from_bytes(b"HREF"), Some(KnownAttr::Href));
+        /// assert_eq!(KnownAttr::Href.name(), "href");
+        /// assert_eq!(KnownAttr::from_bytes(b"data-id"), None);
+        /// ```
+        #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+        #[non_exhaustive]
+        #[repr(u8)]
+        pub enum KnownAttr {
+            $($(#[$meta])* $variant,)*
+        }
+
+        impl KnownAttr {
+            /// All known attributes
+            pub const ALL: &'static [Self] = &[$(Self::$variant,)*];
+
+            /// Looks up the known attribute with the given name (ASCII case-insensitive)
+            pub fn from_bytes(name: &[u8]) -> Option<Self> {
+                if name.len() > MAX_KNOWN_NAME_LEN {
+                    return None;
+                }
+
+                let mut buffer = [0u8; MAX_KNOWN_NAME_LEN];
+                let lower = &mut buffer[..name.len()];
+                lower.copy_from_slice(name);
+                lower.make_ascii_lowercase();
+
+                match &*lower {
+                    $($name => Some(Self::$variant),)*
+                    _ => None,
+                }
+            }
+
+            /// Returns the (lowercase) name of this attribute
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(Self::$variant => {
+                        // SAFETY: all names are ASCII literals
+                        unsafe { std::str::from_utf8_unchecked($name) }
+                    })*
+                }
+            }
+        }
+    };
+}
+
+known_attrs! {
+    /// `id`
+    Id => b"id",
+    /// `class`
+    Class => b"class",
+    /// `style`
+    Style => b"style",
+    /// `href`
+    Href => b"href",
+    /// `src`
+    Src => b"src",
+    /// `srcset`
+    Srcset => b"srcset",
+    /// `alt`
+    Alt => b"alt",
+    /// `title`
+    Title => b"title",
+    /// `name`
+    Name => b"name",
+    /// `type`
+    Type => b"type",
+    /// `value`
+    Value => b"value",
+    /// `rel`
+    Rel => b"rel",
+    /// `target`
+    Target => b"target",
+    /// `lang`
+    Lang => b"lang",
+    /// `dir`
+    Dir => b"dir",
+    /// `width`
+    Width => b"width",
+    /// `height`
+    Height => b"height",
+    /// `content`
+    Content => b"content",
+    /// `charset`
+    Charset => b"charset",
+    /// `http-equiv`
+    HttpEquiv => b"http-equiv",
+    /// `action`
+    Action => b"action",
+    /// `method`
+    Method => b"method",
+    /// `for`
+    For => b"for",
+    /// `placeholder`
+    Placeholder => b"placeholder",
+    /// `role`
+    Role => b"role",
+    /// `tabindex`
+    Tabindex => b"tabindex",
+    /// `hidden`
+    Hidden => b"hidden",
+    /// `disabled`
+    Disabled => b"disabled",
+    /// `checked`
+    Checked => b"checked",
+    /// `selected`
+    Selected => b"selected",
+    /// `async`
+    Async => b"async",
+    /// `defer`
+    Defer => b"defer",
+    /// `loading`
+    Loading => b"loading",
+    /// `crossorigin`
+    Crossorigin => b"crossorigin",
+    /// `integrity`
+    Integrity => b"integrity",
+}
@@ -1,10 +1,26 @@
 mod base;
+mod builder;
 pub(crate) mod constants;
+mod descendants;
+mod diagnostics;
+mod equality;
 mod handle;
+mod known_attr;
 mod options;
+mod path;
+mod slots;
+mod snapshot;
 mod tag;
+mod xml;
 
 pub use base::*;
+pub use builder::*;
+pub use descendants::*;
+pub use diagnostics::*;
+pub use equality::*;
 pub use handle::*;
+pub use known_attr::*;
 pub use options::*;
+pub use path::*;
+pub use snapshot::*;
 pub use tag::*;
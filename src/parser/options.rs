@@ -1,7 +1,41 @@
+use std::{fmt, ops::ControlFlow, sync::Arc};
+
 mod flags {
-    pub const TRACK_IDS: u8 = 1 << 0;
-    pub const TRACK_CLASSES: u8 = 1 << 1;
-    pub const HIGHEST: u8 = TRACK_CLASSES;
+    pub const TRACK_IDS: u32 = 1 << 0;
+    pub const TRACK_CLASSES: u32 = 1 << 1;
+    pub const COLLECT_DIAGNOSTICS: u32 = 1 << 2;
+    pub const PARTIAL_ON_CANCEL: u32 = 1 << 3;
+    pub const NORMALIZE_NEWLINES: u32 = 1 << 4;
+    pub const CASE_SENSITIVE_TAG_NAMES: u32 = 1 << 5;
+    pub const TRACK_TAG_NAMES: u32 = 1 << 6;
+    pub const RAW_ID_AND_CLASS_MATCHING: u32 = 1 << 7;
+    pub const TRACK_PARENTS: u32 = 1 << 8;
+    pub const STRICT: u32 = 1 << 9;
+    pub const HIGHEST: u32 = STRICT;
+
+    pub const TRACKING: u32 = TRACK_IDS | TRACK_CLASSES | TRACK_TAG_NAMES;
+}
+
+/// A lookup table of a document that can be built while parsing or afterwards with `VDom::build_index()`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum IndexKind {
+    /// Maps ids to elements, see `ParserOptions::track_ids()`
+    Ids,
+    /// Maps classes to elements, see `ParserOptions::track_classes()`
+    Classes,
+    /// Maps tag names to elements, see `ParserOptions::track_tag_names()`
+    TagNames,
+}
+
+impl IndexKind {
+    /// Returns the flag that enables tracking for this kind of lookup table
+    fn flag(self) -> u32 {
+        match self {
+            Self::Ids => flags::TRACK_IDS,
+            Self::Classes => flags::TRACK_CLASSES,
+            Self::TagNames => flags::TRACK_TAG_NAMES,
+        }
+    }
 }
 
 /// Options for the HTML Parser
@@ -10,17 +44,95 @@ mod flags {
 /// The default options (`ParserOptions::default()`) are optimized for raw parsing.
 /// If you need to do HTML tag lookups by ID or class names, you can enable tracking.
 /// This will cache HTML nodes as they appear in the source code on the fly.
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Default)]
 pub struct ParserOptions {
-    flags: u8,
+    flags: u32,
+    /// Names of raw text elements, or `None` to use [`DEFAULT_RAW_TEXT_ELEMENTS`]
+    raw_text_elements: Option<&'static [&'static str]>,
+    /// Names of the attributes whose values are stored in a lookup table
+    pub(crate) tracked_attributes: Vec<Box<[u8]>>,
+    /// The progress callback, if set
+    pub(crate) progress: Option<ProgressCallback>,
+    /// The maximum number of bytes that are stored for a text node, if limited
+    pub(crate) max_stored_text_len: Option<usize>,
+    /// The maximum number of bytes that are stored for an attribute value, if limited
+    pub(crate) max_stored_attribute_len: Option<usize>,
+    /// The handlers for the payloads of raw text elements, see [`ParserOptions::tag_payload_handler()`]
+    pub(crate) payload_handlers: Vec<PayloadHandler>,
+}
+
+/// An opaque identifier for the payload of an element, returned by a handler registered with
+/// [`ParserOptions::tag_payload_handler()`] and obtained by calling `HTMLTag::payload_id()`
+///
+/// The meaning of the value is up to the handler, e.g. an index into a list of parsed payloads.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PayloadId(u64);
+
+impl PayloadId {
+    /// Creates an identifier with the given value
+    pub fn new(value: u64) -> Self {
+        Self(value)
+    }
+
+    /// Returns the value of this identifier
+    pub fn get(&self) -> u64 {
+        self.0
+    }
+}
+
+/// A function that parses the payload of an element and returns an identifier for it
+type PayloadFn = dyn Fn(&[u8]) -> PayloadId + Send + Sync;
+
+/// A handler for the payloads of the elements with a given name, see [`ParserOptions::tag_payload_handler()`]
+#[derive(Clone)]
+pub(crate) struct PayloadHandler {
+    /// The name of the elements, which is compared case-insensitively
+    pub(crate) name: Box<[u8]>,
+    pub(crate) handler: Arc<PayloadFn>,
+}
+
+impl fmt::Debug for PayloadHandler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PayloadHandler")
+            .field("name", &String::from_utf8_lossy(&self.name))
+            .finish_non_exhaustive()
+    }
 }
 
-impl Default for ParserOptions {
-    fn default() -> Self {
-        Self { flags: 0 }
+impl PartialEq for PayloadHandler {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && Arc::ptr_eq(&self.handler, &other.handler)
     }
 }
 
+/// A callback that reports the progress of the parser, see [`ParserOptions::on_progress()`]
+#[derive(Clone)]
+pub(crate) struct ProgressCallback {
+    /// The minimum number of bytes between two calls
+    pub(crate) granularity: usize,
+    pub(crate) callback: Arc<dyn Fn(usize, usize) -> ControlFlow<()> + Send + Sync>,
+}
+
+impl fmt::Debug for ProgressCallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProgressCallback")
+            .field("granularity", &self.granularity)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for ProgressCallback {
+    fn eq(&self, other: &Self) -> bool {
+        self.granularity == other.granularity && Arc::ptr_eq(&self.callback, &other.callback)
+    }
+}
+
+/// Names of the elements that are parsed as raw text by default
+///
+/// The content of these elements is not parsed as markup, but stored as a single raw text node.
+/// This includes the legacy `<xmp>` element, which browsers parse the same way.
+pub const DEFAULT_RAW_TEXT_ELEMENTS: &[&str] = &["script", "style", "xmp"];
+
 impl ParserOptions {
     /// Creates a new [ParserOptions] with no flags set
     pub fn new() -> Self {
@@ -28,7 +140,10 @@ impl ParserOptions {
     }
 
     /// Creates a [ParserOptions] from a bitset
-    pub fn from_raw_checked(flags: u8) -> Option<Self> {
+    ///
+    /// Returns `None` if the bitset contains unknown flags.
+    /// Options that are not flags (such as tracked attributes or limits) are set to their defaults.
+    pub fn from_raw_checked(flags: u32) -> Option<Self> {
         if flags > flags::HIGHEST * 2 - 1 {
             None
         } else {
@@ -40,16 +155,18 @@ impl ParserOptions {
     }
 
     /// Returns the raw flags of this bitset
-    pub fn to_raw(&self) -> u8 {
+    ///
+    /// This only contains the options that are flags, see `from_raw_checked()`.
+    pub fn to_raw(&self) -> u32 {
         self.flags
     }
 
-    fn set_flag(&mut self, flag: u8) {
+    fn set_flag(&mut self, flag: u32) {
         self.flags |= flag;
     }
 
     #[inline]
-    fn has_flag(&self, flag: u8) -> bool {
+    fn has_flag(&self, flag: u32) -> bool {
         self.flags & flag != 0
     }
 
@@ -69,6 +186,159 @@ impl ParserOptions {
         self
     }
 
+    /// Enables tracking of HTML Tag names and stores them in a lookup table.
+    ///
+    /// This makes `VDom::get_elements_by_tag_name()` lookups ~O(1).
+    /// Names are stored lowercased, unless `case_sensitive_tag_names()` is set.
+    pub fn track_tag_names(mut self) -> Self {
+        self.set_flag(flags::TRACK_TAG_NAMES);
+        self
+    }
+
+    /// Enables tracking of the parents of all nodes, including text and comment nodes.
+    ///
+    /// Elements always know their parent (see `HTMLTag::parent()`), but text and comment nodes do not.
+    /// With this option, the parser additionally stores the parent of every node in a table,
+    /// which makes `NodeHandle::parent()` ~O(1) for text and comment nodes.
+    ///
+    /// # Example
+    /// ```
+    /// let dom = tl::parse("<p>Hello <b>world</b></p>", tl::ParserOptions::default().track_parents()).unwrap();
+    /// let parser = dom.parser();
+    ///
+    /// let b = dom.query_selector("b").unwrap().next().unwrap();
+    /// let text = b.get(parser).unwrap().children().unwrap().top()[0];
+    /// assert_eq!(text.parent(parser), Some(b));
+    /// ```
+    pub fn track_parents(mut self) -> Self {
+        self.set_flag(flags::TRACK_PARENTS);
+        self
+    }
+
+    /// Enables tracking of the values of the attribute with the given name and stores them in a lookup table.
+    ///
+    /// This makes `VDom::get_elements_by_attribute_tracked()` lookups for this attribute ~O(1).
+    /// This can be called multiple times to track several attributes. Names are matched like in `Attributes::get()`.
+    ///
+    /// # Example
+    /// ```
+    /// let options = tl::ParserOptions::default().track_attribute("data-testid").track_attribute("name");
+    /// let dom = tl::parse(r#"<input name=q><button data-testid="submit">Go</button>"#, options).unwrap();
+    ///
+    /// let button = dom.get_elements_by_attribute_tracked("data-testid", "submit").next().unwrap();
+    /// assert_eq!(button.get(dom.parser()).unwrap().inner_text(dom.parser()), "Go");
+    /// assert_eq!(dom.get_elements_by_attribute_tracked("name", "q").count(), 1);
+    /// ```
+    pub fn track_attribute(mut self, name: &str) -> Self {
+        if !self.is_tracking_attribute(name) {
+            self.tracked_attributes.push(name.as_bytes().into());
+        }
+        self
+    }
+
+    /// Enables tracking of the values of `name` attributes and stores them in a lookup table.
+    ///
+    /// This makes `VDom::get_elements_by_name()` lookups ~O(1), and is a shorthand for `track_attribute("name")`.
+    pub fn track_names(self) -> Self {
+        self.track_attribute("name")
+    }
+
+    /// Returns whether the parser is tracking the values of `name` attributes.
+    #[inline]
+    pub fn is_tracking_names(&self) -> bool {
+        self.is_tracking_attribute("name")
+    }
+
+    /// Returns whether the parser is tracking the values of the attribute with the given name.
+    pub fn is_tracking_attribute(&self, name: &str) -> bool {
+        self.tracked_attribute_index(name).is_some()
+    }
+
+    /// Returns the index of the lookup table of the attribute with the given name, if it is tracked
+    pub(crate) fn tracked_attribute_index(&self, name: &str) -> Option<usize> {
+        self.tracked_attributes
+            .iter()
+            .position(|x| x.eq_ignore_ascii_case(name.as_bytes()))
+    }
+
+    /// Sets the names of the elements whose content is parsed as raw text, replacing the default set
+    /// ([`DEFAULT_RAW_TEXT_ELEMENTS`]).
+    ///
+    /// Everything up to the matching end tag (compared case-insensitively) of such an element is stored as a single raw text node,
+    /// which is useful for elements that embed non-HTML content, such as templates.
+    /// Note that the default set is replaced, so `script`, `style` and `xmp` need to be included explicitly if they should keep being parsed as raw text.
+    /// The content of a `<plaintext>` element is always parsed as raw text, since it has no end tag.
+    ///
+    /// # Example
+    /// ```
+    /// let options = tl::ParserOptions::default().raw_text_elements(&["script", "style", "x-raw"]);
+    /// let dom = tl::parse("<x-raw><div></x-raw>", options).unwrap();
+    /// let parser = dom.parser();
+    /// let element = dom.nodes()[0].as_tag().unwrap();
+    ///
+    /// assert_eq!(element.children().top().len(), 1);
+    /// assert_eq!(element.inner_text(parser), "<div>");
+    /// ```
+    pub fn raw_text_elements(mut self, names: &'static [&'static str]) -> Self {
+        self.raw_text_elements = Some(names);
+        self
+    }
+
+    /// Registers a handler for the content of the elements with the given name, which embed a payload that is not HTML
+    ///
+    /// These elements are parsed as raw text elements, in addition to the ones set by `raw_text_elements()`.
+    /// While parsing, the handler is called with the bytes of the content of each such element, as they appear in the input
+    /// (not decoded or truncated), and the returned identifier is stored for the element. It can be obtained by calling
+    /// `HTMLTag::payload_id()`. Registering another handler for the same name (compared case-insensitively) replaces it.
+    ///
+    /// # Example
+    /// ```
+    /// use tl::PayloadId;
+    ///
+    /// let options = tl::ParserOptions::default()
+    ///     .tag_payload_handler("x-math", |payload| PayloadId::new(payload.len() as u64));
+    /// let dom = tl::parse(r"<x-math>a<b\over c</x-math>", options).unwrap();
+    /// let parser = dom.parser();
+    /// let math = dom.nodes()[0].as_tag().unwrap();
+    ///
+    /// assert_eq!(math.payload_id(parser), Some(PayloadId::new(10)));
+    /// assert_eq!(math.inner_text(parser), r"a<b\over c");
+    /// ```
+    pub fn tag_payload_handler<F>(mut self, name: &str, handler: F) -> Self
+    where
+        F: Fn(&[u8]) -> PayloadId + Send + Sync + 'static,
+    {
+        let handler = PayloadHandler {
+            name: name.as_bytes().into(),
+            handler: Arc::new(handler),
+        };
+
+        match self.payload_handler(name.as_bytes()) {
+            Some(index) => self.payload_handlers[index] = handler,
+            None => self.payload_handlers.push(handler),
+        }
+        self
+    }
+
+    /// Returns the index of the payload handler for the elements with the given name, if there is one
+    pub(crate) fn payload_handler(&self, name: &[u8]) -> Option<usize> {
+        self.payload_handlers
+            .iter()
+            .position(|handler| handler.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Returns whether the element with the given name is parsed as raw text (case-insensitive).
+    pub fn is_raw_text_element(&self, name: &[u8]) -> bool {
+        if !self.payload_handlers.is_empty() && self.payload_handler(name).is_some() {
+            return true;
+        }
+
+        self.raw_text_elements
+            .unwrap_or(DEFAULT_RAW_TEXT_ELEMENTS)
+            .iter()
+            .any(|x| x.as_bytes().eq_ignore_ascii_case(name))
+    }
+
     /// Returns whether the parser is tracking HTML Tag IDs.
     #[inline]
     pub fn is_tracking_ids(&self) -> bool {
@@ -81,12 +351,252 @@ impl ParserOptions {
         self.has_flag(flags::TRACK_CLASSES)
     }
 
-    /// Returns whether the parser is tracking HTML Tag IDs or classes (previously enabled by a call to `track_ids()` or `track_classes()`).
+    /// Returns whether the parser is tracking HTML Tag names.
+    #[inline]
+    pub fn is_tracking_tag_names(&self) -> bool {
+        self.has_flag(flags::TRACK_TAG_NAMES)
+    }
+
+    /// Returns whether the parser is tracking the parents of all nodes.
+    #[inline]
+    pub fn is_tracking_parents(&self) -> bool {
+        self.has_flag(flags::TRACK_PARENTS)
+    }
+
+    /// Returns whether the parser is tracking HTML Tag IDs, classes or names
+    /// (previously enabled by a call to `track_ids()`, `track_classes()` or `track_tag_names()`).
     #[inline]
     pub fn is_tracking(&self) -> bool {
-        // for now we can just check if any bit is set, may or may not lead to better codegen than two cmps
-        // this must be changed in some way if we ever add more flags
-        // self.is_tracking_ids() || self.is_tracking_classes()
-        self.flags != 0
+        // check all bits at once, may or may not lead to better codegen than several cmps
+        self.has_flag(flags::TRACKING)
+    }
+
+    /// Returns whether the given kind of lookup table is maintained
+    #[inline]
+    pub(crate) fn is_indexing(&self, kind: IndexKind) -> bool {
+        self.has_flag(kind.flag())
+    }
+
+    /// Marks the given kind of lookup table as maintained
+    pub(crate) fn set_indexing(&mut self, kind: IndexKind) {
+        self.set_flag(kind.flag());
+    }
+
+    /// Enables collecting diagnostics for recoverable errors that are encountered while parsing.
+    ///
+    /// The collected diagnostics can be obtained by calling `VDom::diagnostics()`.
+    pub fn collect_diagnostics(mut self) -> Self {
+        self.set_flag(flags::COLLECT_DIAGNOSTICS);
+        self
+    }
+
+    /// Returns whether the parser is collecting diagnostics.
+    #[inline]
+    pub fn is_collecting_diagnostics(&self) -> bool {
+        self.has_flag(flags::COLLECT_DIAGNOSTICS)
+    }
+
+    /// Enables strict mode, in which the first recoverable error aborts parsing instead of being recovered from.
+    ///
+    /// Parsing then fails with [`ParseError::Strict`](crate::ParseError::Strict), which contains the diagnostic
+    /// that would have been collected by `collect_diagnostics()` (see [`DiagnosticKind`](crate::DiagnosticKind)).
+    /// This includes unclosed elements, stray end tags, unterminated comments and attribute values without a closing quote.
+    /// Documents without such errors are parsed exactly like without this option.
+    ///
+    /// # Example
+    /// ```
+    /// use tl::{DiagnosticKind, ParseError};
+    ///
+    /// let options = tl::ParserOptions::default().strict();
+    /// assert!(tl::parse("<ul><li>a<li>b</ul>", options.clone()).is_ok());
+    ///
+    /// let Err(ParseError::Strict(diagnostic)) = tl::parse("<p>a</span></p>", options) else {
+    ///     panic!("expected an error");
+    /// };
+    /// assert_eq!(diagnostic.kind, DiagnosticKind::StrayEndTag { name: "span".into() });
+    /// assert_eq!(diagnostic.at, 4);
+    /// ```
+    pub fn strict(mut self) -> Self {
+        self.set_flag(flags::STRICT);
+        self
+    }
+
+    /// Returns whether strict mode is enabled.
+    #[inline]
+    pub fn is_strict(&self) -> bool {
+        self.has_flag(flags::STRICT)
+    }
+
+    /// Sets a callback that is invoked with `(bytes_consumed, total)` while parsing, at most once every `granularity` bytes
+    ///
+    /// The callback is invoked one last time with `(total, total)` once parsing has finished.
+    /// Returning `ControlFlow::Break(())` cancels parsing, which makes `tl::parse()` return [`ParseError::Cancelled`](crate::ParseError::Cancelled),
+    /// or the partially parsed document if [`ParserOptions::partial_on_cancel()`] is set.
+    ///
+    /// # Example
+    /// ```
+    /// use std::ops::ControlFlow;
+    ///
+    /// let input = "<p>Hello</p>".repeat(1000);
+    /// let options = tl::ParserOptions::default().on_progress(4096, |consumed, total| {
+    ///     assert!(consumed <= total);
+    ///     ControlFlow::Continue(())
+    /// });
+    /// let dom = tl::parse(&input, options).unwrap();
+    /// assert!(!dom.is_partial());
+    /// ```
+    pub fn on_progress<F>(mut self, granularity: usize, callback: F) -> Self
+    where
+        F: Fn(usize, usize) -> ControlFlow<()> + Send + Sync + 'static,
+    {
+        self.progress = Some(ProgressCallback {
+            granularity: granularity.max(1),
+            callback: Arc::new(callback),
+        });
+        self
+    }
+
+    /// Makes the parser return the partially parsed document instead of an error if parsing is cancelled by the progress callback.
+    ///
+    /// Whether the document is partial can be checked by calling `VDom::is_partial()`.
+    pub fn partial_on_cancel(mut self) -> Self {
+        self.set_flag(flags::PARTIAL_ON_CANCEL);
+        self
+    }
+
+    /// Returns whether the parser returns the partially parsed document when cancelled.
+    #[inline]
+    pub fn is_partial_on_cancel(&self) -> bool {
+        self.has_flag(flags::PARTIAL_ON_CANCEL)
+    }
+
+    /// Limits the number of bytes that are stored for each text node (including the content of raw text elements such as `<script>`)
+    ///
+    /// Longer text is cut off at the last char boundary before the limit, which saves memory if only the start of the text is needed.
+    /// Whether a text node was cut off can be checked with `Node::is_truncated()`.
+    ///
+    /// Note that everything operates on the truncated text, including `inner_text()`, query selectors and serialization,
+    /// so `outer_html()` does not reproduce the source of a document with truncated text nodes.
+    ///
+    /// # Example
+    /// ```
+    /// let options = tl::ParserOptions::default().max_stored_text_len(5);
+    /// let dom = tl::parse("<p>Hello world</p>", options).unwrap();
+    /// let parser = dom.parser();
+    /// let text = &dom.nodes()[1];
+    ///
+    /// assert_eq!(text.inner_text(parser), "Hello");
+    /// assert!(text.is_truncated());
+    /// ```
+    pub fn max_stored_text_len(mut self, len: usize) -> Self {
+        self.max_stored_text_len = Some(len);
+        self
+    }
+
+    /// Limits the number of bytes that are stored for each attribute value
+    ///
+    /// Longer values are cut off at the last char boundary before the limit.
+    /// Whether a value was cut off can be checked with `Attributes::is_truncated()`.
+    ///
+    /// Note that query selectors and `Attributes` operate on the truncated values. The start tag of an element
+    /// is still serialized from the source by `outer_html()` unless it is modified, and from the truncated values otherwise.
+    ///
+    /// # Example
+    /// ```
+    /// let options = tl::ParserOptions::default().max_stored_attribute_len(3);
+    /// let dom = tl::parse(r#"<div data-json='{"a":1}' id=x></div>"#, options).unwrap();
+    /// let attributes = dom.nodes()[0].as_tag().unwrap().attributes();
+    ///
+    /// assert_eq!(attributes.get("data-json"), Some(Some(&r#"{"a"#.into())));
+    /// assert!(attributes.is_truncated("data-json"));
+    /// assert!(!attributes.is_truncated("id"));
+    /// ```
+    pub fn max_stored_attribute_len(mut self, len: usize) -> Self {
+        self.max_stored_attribute_len = Some(len);
+        self
+    }
+
+    /// Normalizes line breaks (`\r\n` and lone `\r`) to `\n` in text nodes and attribute values, like browsers do.
+    ///
+    /// The input is not copied: only the text nodes and attribute values that contain a `\r` are stored as owned bytes.
+    /// Note that this means that serializing an unmodified document (e.g. with `VDom::outer_html()`) no longer
+    /// reproduces the source exactly if it contains `\r`.
+    ///
+    /// # Example
+    /// ```
+    /// let options = tl::ParserOptions::default().normalize_newlines();
+    /// let dom = tl::parse("<pre title=\"a\r\nb\">1\r\n2\r3</pre>", options).unwrap();
+    /// let parser = dom.parser();
+    /// let pre = dom.nodes()[0].as_tag().unwrap();
+    ///
+    /// assert_eq!(pre.inner_text(parser), "1\n2\n3");
+    /// assert_eq!(pre.attributes().get("title"), Some(Some(&"a\nb".into())));
+    /// ```
+    pub fn normalize_newlines(mut self) -> Self {
+        self.set_flag(flags::NORMALIZE_NEWLINES);
+        self
+    }
+
+    /// Returns whether the parser normalizes line breaks in text nodes and attribute values.
+    #[inline]
+    pub fn is_normalizing_newlines(&self) -> bool {
+        self.has_flag(flags::NORMALIZE_NEWLINES)
+    }
+
+    /// Makes type selectors in query selectors (e.g. `div`) match tag names exactly, which is useful for XML-like documents.
+    ///
+    /// By default, tag names are matched ASCII case-insensitively like in HTML, so `div` also matches `<DIV>`.
+    ///
+    /// # Example
+    /// ```
+    /// let input = "<Item>a</Item><item>b</item>";
+    ///
+    /// let dom = tl::parse(input, tl::ParserOptions::default()).unwrap();
+    /// assert_eq!(dom.query_selector_count("item"), Some(2));
+    ///
+    /// let dom = tl::parse(input, tl::ParserOptions::default().case_sensitive_tag_names()).unwrap();
+    /// assert_eq!(dom.query_selector_count("item"), Some(1));
+    /// assert_eq!(dom.query_selector_count("Item"), Some(1));
+    /// ```
+    pub fn case_sensitive_tag_names(mut self) -> Self {
+        self.set_flag(flags::CASE_SENSITIVE_TAG_NAMES);
+        self
+    }
+
+    /// Returns whether query selectors match tag names case-sensitively.
+    #[inline]
+    pub fn is_case_sensitive_tag_names(&self) -> bool {
+        self.has_flag(flags::CASE_SENSITIVE_TAG_NAMES)
+    }
+
+    /// Makes `get_element_by_id()`, `get_elements_by_class_name()` and id and class selectors compare the raw ids
+    /// and class names in the source, as in earlier versions.
+    ///
+    /// By default, character references in ids and class names are decoded before they are compared,
+    /// like in browsers: `<p id="a&amp;b">` is found by `get_element_by_id("a&b")`.
+    /// With this option, it is only found by `get_element_by_id("a&amp;b")`.
+    /// This applies to the lookup tables of `track_ids()` and `track_classes()` as well.
+    ///
+    /// # Example
+    /// ```
+    /// let input = r#"<p id="a&amp;b" class="x&#38;y">"#;
+    ///
+    /// let dom = tl::parse(input, tl::ParserOptions::default()).unwrap();
+    /// assert!(dom.get_element_by_id("a&b").is_some());
+    /// assert_eq!(dom.get_elements_by_class_name("x&y").count(), 1);
+    ///
+    /// let dom = tl::parse(input, tl::ParserOptions::default().raw_id_and_class_matching()).unwrap();
+    /// assert!(dom.get_element_by_id("a&b").is_none());
+    /// assert!(dom.get_element_by_id("a&amp;b").is_some());
+    /// ```
+    pub fn raw_id_and_class_matching(mut self) -> Self {
+        self.set_flag(flags::RAW_ID_AND_CLASS_MATCHING);
+        self
+    }
+
+    /// Returns whether ids and class names are compared without decoding character references.
+    #[inline]
+    pub fn is_raw_id_and_class_matching(&self) -> bool {
+        self.has_flag(flags::RAW_ID_AND_CLASS_MATCHING)
     }
 }
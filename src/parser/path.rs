@@ -0,0 +1,95 @@
+use super::{handle::NodeHandle, Parser};
+
+/// A structural address of a node: the sequence of child indices that lead from the top-level nodes to the node
+///
+/// For example, `[1, 0, 3]` refers to the fourth child of the first child of the second top-level node.
+/// Unlike a [`NodeHandle`], a path only depends on the structure of the document, so it can be persisted
+/// and resolved again after parsing the same document bytes (see `VDom::resolve_path()`).
+///
+/// With the `serde` feature enabled, this can be serialized as a sequence of integers.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct NodePath(Vec<u32>);
+
+impl NodePath {
+    /// Creates a new path from the given child indices
+    pub fn new(indices: Vec<u32>) -> Self {
+        Self(indices)
+    }
+
+    /// Returns the child indices of this path
+    pub fn indices(&self) -> &[u32] {
+        &self.0
+    }
+
+    /// Returns the number of child indices in this path, which is the depth of the node (starting at 1)
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Checks whether this path is empty
+    ///
+    /// An empty path never resolves to a node.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Computes the path of the given node, or `None` if it is not reachable from the top-level nodes
+    pub(crate) fn of(handle: NodeHandle, parser: &Parser) -> Option<Self> {
+        let target = parser.position_of(handle)?;
+        let mut indices = Vec::new();
+        let mut siblings = parser.ast.as_slice();
+
+        // nodes are stored in document order, so the subtree of every node is a contiguous range of handles
+        // that starts at the node itself
+        loop {
+            let (index, &node) = siblings.iter().enumerate().find(|(_, &node)| {
+                let Some(start) = parser.position_of(node) else {
+                    return false;
+                };
+
+                let end = node
+                    .get(parser)
+                    .and_then(|node| node.children())
+                    .and_then(|children| children.positions(parser))
+                    .map_or(start + 1, |range| range.end);
+
+                (start..end).contains(&target)
+            })?;
+
+            indices.push(index as u32);
+
+            if node == handle {
+                return Some(Self(indices));
+            }
+
+            siblings = node.get(parser)?.as_tag()?._children.as_slice();
+        }
+    }
+
+    /// Resolves this path to a node handle, or `None` if any index is out of range
+    pub(crate) fn resolve(&self, parser: &Parser) -> Option<NodeHandle> {
+        let (&first, rest) = self.0.split_first()?;
+        let mut handle = *parser.ast.get(first as usize)?;
+
+        for &index in rest {
+            let tag = handle.get(parser)?.as_tag()?;
+            handle = *tag._children.get(index as usize)?;
+        }
+
+        Some(handle)
+    }
+}
+
+impl From<Vec<u32>> for NodePath {
+    fn from(indices: Vec<u32>) -> Self {
+        Self(indices)
+    }
+}
+
+impl From<NodePath> for Vec<u32> {
+    fn from(path: NodePath) -> Self {
+        path.0
+    }
+}
@@ -0,0 +1,365 @@
+use super::{
+    handle::{NodeHandle, ParserId},
+    tag::{EndTag, Node},
+    NodePath, Parser,
+};
+use crate::{inline::vec::InlineVec, InnerNodeHandle};
+use std::{collections::HashMap, hash::Hash, ops::Range};
+
+/// Marks handles of removed nodes in [`NodeSlots::positions`]
+const REMOVED: u32 = u32::MAX;
+
+/// Marks nodes without a parent in [`Parser::parents`]
+const NO_PARENT: InnerNodeHandle = InnerNodeHandle::MAX;
+
+/// The positions of nodes in the node table, once nodes have been inserted into (or removed from) the document
+///
+/// The node table is always kept in document order, which means that the subtree of a node is a contiguous range
+/// that starts at the node itself. Inserting a node therefore moves all following nodes to a new position,
+/// but their handles should stay valid. This table maps handles to positions and vice versa.
+///
+/// As long as the structure of a document is never changed, handles are equal to positions and no table is needed.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct NodeSlots {
+    /// Maps handles to positions in the node table ([`REMOVED`] for removed nodes)
+    positions: Vec<u32>,
+    /// Maps positions in the node table to handles
+    handles: Vec<NodeHandle>,
+}
+
+impl NodeSlots {
+    /// Creates the table for a node table of the given length, in which handles are equal to positions
+    fn identity(len: usize, parser: ParserId) -> Self {
+        Self {
+            positions: (0..len as u32).collect(),
+            handles: (0..len as u32)
+                .map(|id| NodeHandle::with_parser(id, parser))
+                .collect(),
+        }
+    }
+
+    /// Updates the positions of the handles at the given positions and all following ones
+    fn update_positions_from(&mut self, position: usize) {
+        for (position, handle) in self.handles.iter().enumerate().skip(position) {
+            self.positions[handle.get_inner() as usize] = position as u32;
+        }
+    }
+}
+
+impl<'a> Parser<'a> {
+    /// Returns the position of the given node in the node table, or `None` if it does not exist (anymore)
+    #[inline]
+    pub(crate) fn position_of(&self, handle: NodeHandle) -> Option<usize> {
+        if !handle.belongs_to(self) {
+            return None;
+        }
+
+        let id = handle.get_inner();
+
+        let position = match &self.slots {
+            Some(slots) => *slots
+                .positions
+                .get(id as usize)
+                .filter(|&&x| x != REMOVED)?,
+            None => id,
+        } as usize;
+
+        (position < self.tags.len()).then_some(position)
+    }
+
+    /// Returns the handle of the node at the given position in the node table
+    ///
+    /// The position must be in bounds.
+    #[inline]
+    pub(crate) fn handle_at(&self, position: usize) -> NodeHandle {
+        match &self.slots {
+            Some(slots) => slots.handles[position],
+            None => NodeHandle::with_parser(position as InnerNodeHandle, self.id),
+        }
+    }
+
+    /// Inserts the given nodes into the node table at the given position, and returns their (new) handles
+    ///
+    /// This does not attach the nodes to a parent. The caller is responsible for keeping the node table in document order.
+    pub(crate) fn insert_nodes<I>(&mut self, position: usize, nodes: I) -> Vec<NodeHandle>
+    where
+        I: IntoIterator<Item = Node<'a>>,
+    {
+        let len = self.tags.len();
+        let id = self.id;
+        let slots = self
+            .slots
+            .get_or_insert_with(|| NodeSlots::identity(len, id));
+
+        let before = self.tags.len();
+        self.tags.splice(position..position, nodes);
+        let count = self.tags.len() - before;
+
+        let first_handle = slots.positions.len() as InnerNodeHandle;
+        let handles = (first_handle..first_handle + count as InnerNodeHandle)
+            .map(|handle| NodeHandle::with_parser(handle, id))
+            .collect::<Vec<_>>();
+
+        slots
+            .positions
+            .resize(slots.positions.len() + count, REMOVED);
+        slots
+            .handles
+            .splice(position..position, handles.iter().copied());
+        slots.update_positions_from(position);
+
+        handles
+    }
+
+    /// Removes the nodes at the given range of positions from the node table and from the lookup tables
+    ///
+    /// The handles of the removed nodes no longer resolve. This does not detach the nodes from their parent,
+    /// and the range must span the whole subtree of a node, so that the node table stays in document order.
+    pub(crate) fn remove_nodes(&mut self, range: Range<usize>) {
+        let len = self.tags.len();
+        let document_len = self.document_len();
+        let id = self.id;
+        let slots = self
+            .slots
+            .get_or_insert_with(|| NodeSlots::identity(len, id));
+
+        for handle in slots.handles.drain(range.clone()) {
+            slots.positions[handle.get_inner() as usize] = REMOVED;
+        }
+        slots.update_positions_from(range.start);
+
+        // created nodes that are not attached to the document are stored at the end of the node table
+        self.detached -= range.end.saturating_sub(document_len.max(range.start));
+        self.tags.drain(range);
+
+        let exists = |handle: NodeHandle| slots.positions[handle.get_inner() as usize] != REMOVED;
+        retain_handles(&mut self.ids, exists);
+        retain_handles(&mut self.classes, exists);
+        retain_handles(&mut self.tag_names, exists);
+        for values in &mut self.attributes {
+            retain_handles(values, exists);
+        }
+    }
+
+    /// Moves the nodes at the given range of positions in the node table, so that they are stored right before
+    /// the node at position `to` (counted before the move), and keeps their handles valid
+    ///
+    /// `to` must not lie within the range. Like `remove_nodes()`, this does not update the children and parents of the nodes,
+    /// the range must span the whole subtree of a node, and the lookup tables are not updated.
+    pub(crate) fn move_nodes(&mut self, range: Range<usize>, to: usize) {
+        let len = self.tags.len();
+        let id = self.id;
+        let slots = self
+            .slots
+            .get_or_insert_with(|| NodeSlots::identity(len, id));
+
+        let count = range.len();
+        if to < range.start {
+            self.tags[to..range.end].rotate_right(count);
+            slots.handles[to..range.end].rotate_right(count);
+            slots.update_positions_from(to);
+        } else if to > range.end {
+            self.tags[range.start..to].rotate_left(count);
+            slots.handles[range.start..to].rotate_left(count);
+            slots.update_positions_from(range.start);
+        }
+    }
+
+    /// Removes the nodes at the given range of positions from the lookup tables, so that they can be registered again with `track_inserted()`
+    pub(crate) fn untrack(&mut self, range: Range<usize>) {
+        let positions = self.slots.as_ref().map(|slots| &slots.positions);
+        let keep = |handle: NodeHandle| {
+            let id = handle.get_inner();
+            let position = positions.map_or(id, |positions| positions[id as usize]);
+            !range.contains(&(position as usize))
+        };
+
+        retain_handles(&mut self.ids, keep);
+        retain_handles(&mut self.classes, keep);
+        retain_handles(&mut self.tag_names, keep);
+        for values in &mut self.attributes {
+            retain_handles(values, keep);
+        }
+    }
+
+    /// Returns the parent of the given node (`None` for top-level nodes) and its index among its siblings
+    ///
+    /// Unlike `HTMLTag::parent()`, this also works for text and comment nodes.
+    pub(crate) fn locate(&self, handle: NodeHandle) -> Option<(Option<NodeHandle>, usize)> {
+        let path = NodePath::of(handle, self)?;
+        let (&index, parent) = path.indices().split_last()?;
+
+        let parent = match parent {
+            [] => None,
+            parent => Some(NodePath::new(parent.to_vec()).resolve(self)?),
+        };
+
+        Some((parent, index as usize))
+    }
+
+    /// Returns the parent of the given node, which is `None` for top-level nodes
+    ///
+    /// This uses the parent table if parents are tracked, and finds the node in the document otherwise.
+    pub(crate) fn parent_of(&self, handle: NodeHandle) -> Option<NodeHandle> {
+        if let Some(tag) = handle.get(self)?.as_tag() {
+            return tag._parent;
+        }
+
+        if !self.options.is_tracking_parents() {
+            return self.locate(handle)?.0;
+        }
+
+        match self.parents.get(handle.get_inner() as usize) {
+            Some(&parent) if parent != NO_PARENT => Some(NodeHandle::with_parser(parent, self.id)),
+            _ => None,
+        }
+    }
+
+    /// Returns the children of the parent of the given node (or the top-level nodes), and the index of the node among them
+    pub(crate) fn siblings_of(&self, handle: NodeHandle) -> Option<(&[NodeHandle], usize)> {
+        handle.get(self)?;
+
+        let siblings = match self.parent_of(handle) {
+            Some(parent) => parent.get(self)?.as_tag()?._children.as_slice(),
+            None => &self.ast,
+        };
+        let index = siblings.iter().position(|&sibling| sibling == handle)?;
+
+        Some((siblings, index))
+    }
+
+    /// Stores the parent of the given node in the parent table, if parents are tracked
+    ///
+    /// Elements store their parent themselves, but the table contains all nodes, so that it does not depend on the node type.
+    #[inline]
+    pub(crate) fn set_parent(&mut self, handle: NodeHandle, parent: Option<NodeHandle>) {
+        if !self.options.is_tracking_parents() {
+            return;
+        }
+
+        let id = handle.get_inner() as usize;
+        if id >= self.parents.len() {
+            self.parents.resize(id + 1, NO_PARENT);
+        }
+
+        self.parents[id] = parent.map_or(NO_PARENT, |parent| parent.get_inner());
+    }
+
+    /// Stores the given element as the parent of each of its children in the parent table, if parents are tracked
+    pub(crate) fn set_children_parents(&mut self, handle: NodeHandle) {
+        if !self.options.is_tracking_parents() {
+            return;
+        }
+
+        let children = handle
+            .get(self)
+            .and_then(Node::as_tag)
+            .map(|tag| tag._children.as_slice().to_vec())
+            .unwrap_or_default();
+
+        for child in children {
+            self.set_parent(child, Some(handle));
+        }
+    }
+
+    /// Inserts the given node into the children of `parent` (or the top-level nodes if `None`) at the given index
+    ///
+    /// This only updates the children and the parent of the node. The caller is responsible for keeping the node table in document order.
+    pub(crate) fn attach(&mut self, parent: Option<NodeHandle>, index: usize, handle: NodeHandle) {
+        if let Some(tag) = handle.get_mut(self).and_then(Node::as_tag_mut) {
+            tag._parent = parent;
+        }
+        self.set_parent(handle, parent);
+
+        match parent {
+            Some(parent) => {
+                let parent = parent.get_mut(self).unwrap().as_tag_mut().unwrap();
+                parent._children.insert(index, handle);
+            }
+            None => self.ast.insert(index, handle),
+        }
+
+        self.generate_end_tags(parent, index);
+        self.generate_end_tags(parent, index + 1);
+    }
+
+    /// Removes the node at the given index from the children of `parent` (or the top-level nodes if `None`)
+    ///
+    /// This only updates the children and the parent of the node. The node itself stays in the node table.
+    pub(crate) fn detach(&mut self, parent: Option<NodeHandle>, index: usize) -> NodeHandle {
+        let handle = match parent {
+            Some(parent) => {
+                let parent = parent.get_mut(self).unwrap().as_tag_mut().unwrap();
+                parent._children.remove(index)
+            }
+            None => self.ast.remove(index),
+        };
+
+        if let Some(tag) = handle.get_mut(self).and_then(Node::as_tag_mut) {
+            tag._parent = None;
+        }
+        self.set_parent(handle, None);
+        self.generate_end_tags(parent, index);
+
+        handle
+    }
+
+    /// Generates the end tags that were omitted in the source and may no longer be implied,
+    /// because the children of `parent` (or the top-level nodes if `None`) changed right before `index`
+    ///
+    /// Whether an end tag can be omitted depends on what follows the element in the source. This affects the element
+    /// and its ancestors, as well as the last descendants of the child before `index`, which are now followed by something else.
+    /// Their end tags are generated, so that `outer_html()` parses into the same structure again.
+    pub(crate) fn generate_end_tags(&mut self, parent: Option<NodeHandle>, index: usize) {
+        let siblings = match parent {
+            Some(parent) => parent
+                .get(self)
+                .and_then(Node::as_tag)
+                .map_or(&[][..], |tag| tag._children.as_slice()),
+            None => &self.ast,
+        };
+        let previous = index
+            .checked_sub(1)
+            .and_then(|index| siblings.get(index).copied());
+
+        let mut handle = previous;
+        while let Some(tag) = handle
+            .and_then(|handle| handle.get_mut(self))
+            .and_then(Node::as_tag_mut)
+        {
+            if tag._end_tag == EndTag::Omitted {
+                tag._end_tag = EndTag::Generated;
+            }
+            handle = tag._children.as_slice().last().copied();
+        }
+
+        let mut handle = parent;
+        while let Some(tag) = handle
+            .and_then(|handle| handle.get_mut(self))
+            .and_then(Node::as_tag_mut)
+        {
+            if tag._end_tag == EndTag::Omitted {
+                tag._end_tag = EndTag::Generated;
+            }
+            handle = tag._parent;
+        }
+    }
+}
+
+/// Removes the handles for which `keep` returns `false` from a lookup table, as well as entries that become empty
+fn retain_handles<K, const N: usize>(
+    table: &mut HashMap<K, InlineVec<NodeHandle, N>>,
+    keep: impl Fn(NodeHandle) -> bool,
+) where
+    K: Eq + Hash,
+{
+    table.retain(|_, handles| {
+        for index in (0..handles.len()).rev() {
+            if !keep(handles[index]) {
+                handles.remove(index);
+            }
+        }
+
+        !handles.is_empty()
+    });
+}
@@ -0,0 +1,213 @@
+use std::{convert::Infallible, ops::Range};
+
+use super::{
+    handle::{NodeHandle, ParserId},
+    tag::{HTMLTag, Node, RawChildren},
+    Parser,
+};
+use crate::{InnerNodeHandle, VDomGuard};
+
+/// An owned copy of an element that does not borrow from the document
+///
+/// Obtained by calling [`HTMLTag::to_owned_snapshot()`]. This is useful to keep extracted data around
+/// after the `VDom` (and the input it borrows) has been dropped.
+///
+/// With the `serde` feature enabled, this can be serialized.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct OwnedElement {
+    /// The tag name
+    pub name: String,
+    /// The attributes as `(name, value)` pairs, sorted by name
+    pub attributes: Vec<(String, Option<String>)>,
+    /// The inner text
+    pub text: String,
+    /// Snapshots of the child elements, up to the depth that was requested (text and comments are not included)
+    pub children: Vec<OwnedElement>,
+}
+
+impl OwnedElement {
+    /// Returns the value of the attribute with the given name
+    ///
+    /// As with `Attributes::get()`, the outer Option is `None` if the attribute does not exist,
+    /// and the inner Option is `None` if it has no value.
+    pub fn attribute(&self, name: &str) -> Option<Option<&str>> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_deref())
+    }
+}
+
+impl<'a> HTMLTag<'a> {
+    /// Creates an owned snapshot of the name, attributes and inner text of this element, without its children
+    ///
+    /// # Example
+    /// ```
+    /// let snapshot = {
+    ///     let dom = tl::parse(r#"<a href="/about">About <b>us</b></a>"#, Default::default()).unwrap();
+    ///     let tag = dom.nodes()[0].as_tag().unwrap();
+    ///     tag.to_owned_snapshot(dom.parser())
+    /// };
+    ///
+    /// assert_eq!(snapshot.name, "a");
+    /// assert_eq!(snapshot.attribute("href"), Some(Some("/about")));
+    /// assert_eq!(snapshot.text, "About us");
+    /// ```
+    pub fn to_owned_snapshot(&self, parser: &Parser<'a>) -> OwnedElement {
+        self.to_owned_snapshot_with_depth(parser, 0)
+    }
+
+    /// Creates an owned snapshot of this element, including snapshots of child elements up to the given depth
+    ///
+    /// A depth of `0` includes no children, `1` includes the direct child elements (without their children), and so on.
+    pub fn to_owned_snapshot_with_depth(&self, parser: &Parser<'a>, depth: usize) -> OwnedElement {
+        let mut attributes = self
+            .attributes()
+            .iter()
+            .map(|(key, value)| (key.into_owned(), value.map(|x| x.into_owned())))
+            .collect::<Vec<_>>();
+        attributes.sort();
+
+        let children = match depth.checked_sub(1) {
+            Some(depth) => self
+                .children()
+                .top()
+                .iter()
+                .filter_map(|handle| handle.get(parser)?.as_tag())
+                .map(|tag| tag.to_owned_snapshot_with_depth(parser, depth))
+                .collect(),
+            None => Vec::new(),
+        };
+
+        OwnedElement {
+            name: self.name().as_utf8_str().into_owned(),
+            attributes,
+            text: self.inner_text(parser).into_owned(),
+            children,
+        }
+    }
+
+    /// Deep-copies this element and its subtree into a new document that does not borrow from this one
+    ///
+    /// The copy has this element as its only top-level node and uses the same parser options, including the
+    /// lookup tables that are enabled. `outer_html()`, `inner_text()` and query selectors behave as they do on the
+    /// original subtree, except for selectors that depend on the ancestors or siblings of this element (e.g. `:first-child`).
+    /// The source of this element is copied once, and the nodes of the copy refer to it instead of the original input.
+    ///
+    /// # Example
+    /// ```
+    /// let table = {
+    ///     let input = String::from("<div><table><tr><td>1</td></tr></table></div>");
+    ///     let dom = tl::parse(&input, Default::default()).unwrap();
+    ///     let table = dom.query_selector_first("table").unwrap();
+    ///     table.get(dom.parser()).unwrap().as_tag().unwrap().to_owned_subtree(dom.parser())
+    /// };
+    ///
+    /// let dom = table.get_ref();
+    /// assert_eq!(dom.outer_html(), "<table><tr><td>1</td></tr></table>");
+    /// assert_eq!(dom.query_selector("td").unwrap().count(), 1);
+    /// ```
+    pub fn to_owned_subtree(&self, parser: &Parser<'a>) -> VDomGuard {
+        let source = self.raw().as_bytes();
+        // the source of a parsed element starts and ends at ASCII characters, so it is valid UTF-8
+        let (input, source) = match std::str::from_utf8(source) {
+            Ok(input) => (input.to_owned(), source),
+            Err(_) => (String::new(), &[][..]),
+        };
+        let subnodes = self.children().positions(parser).unwrap_or(0..0);
+
+        let mut options = parser.options.clone();
+        options.progress = None;
+
+        let Ok(guard) = VDomGuard::with_parser::<_, Infallible>(input, options, |copy, input| {
+            let copier = SubtreeCopier {
+                parser,
+                subnodes: subnodes.clone(),
+                from: source,
+                to: input.as_bytes(),
+                id: copy.id,
+            };
+
+            copy.tags.push(Node::Tag(copier.copy_tag(self, None)));
+            for node in &parser.tags[subnodes] {
+                copy.tags.push(copier.copy(node));
+            }
+
+            copy.ast.push(copier.root());
+            copy.track_all();
+            Ok(())
+        });
+
+        guard
+    }
+}
+
+/// Copies the nodes of a subtree into a new parser, see [`HTMLTag::to_owned_subtree()`]
+struct SubtreeCopier<'p, 'a, 'b> {
+    parser: &'p Parser<'a>,
+    /// The positions of the subnodes of the root in `parser`
+    subnodes: Range<usize>,
+    /// The source of the root, which the copied nodes borrow from at the same offset in `to`
+    from: &'p [u8],
+    to: &'b [u8],
+    /// The id of the new parser, whose node table starts with the root, followed by its subnodes
+    id: ParserId,
+}
+
+impl<'p, 'a, 'b> SubtreeCopier<'p, 'a, 'b> {
+    /// Returns the handle of the root in the new parser
+    fn root(&self) -> NodeHandle {
+        NodeHandle::with_parser(0, self.id)
+    }
+
+    /// Returns the handle in the new parser of the given subnode, or `None` if it is not a subnode of the root
+    fn handle(&self, handle: NodeHandle) -> Option<NodeHandle> {
+        let position = self
+            .parser
+            .position_of(handle)
+            .filter(|position| self.subnodes.contains(position))?;
+        let position = position - self.subnodes.start + 1;
+
+        Some(NodeHandle::with_parser(
+            position as InnerNodeHandle,
+            self.id,
+        ))
+    }
+
+    /// Copies the given subnode of the root
+    fn copy(&self, node: &Node<'a>) -> Node<'b> {
+        match node {
+            Node::Tag(tag) => {
+                let parent = tag
+                    ._parent
+                    .and_then(|parent| self.handle(parent))
+                    .unwrap_or_else(|| self.root());
+
+                Node::Tag(self.copy_tag(tag, Some(parent)))
+            }
+            Node::Raw(text) => Node::Raw(text.rebase(self.from, self.to)),
+            Node::Comment(text) => Node::Comment(text.rebase(self.from, self.to)),
+        }
+    }
+
+    /// Copies the given tag, which is the root or one of its subnodes
+    fn copy_tag(&self, tag: &HTMLTag<'a>, parent: Option<NodeHandle>) -> HTMLTag<'b> {
+        let mut children = RawChildren::new();
+        for &child in tag._children.iter() {
+            if let Some(child) = self.handle(child) {
+                children.push(child);
+            }
+        }
+
+        HTMLTag {
+            _name: tag._name.rebase(self.from, self.to),
+            _attributes: tag._attributes.rebase(self.from, self.to),
+            _children: children,
+            _parent: parent,
+            _raw: tag._raw.rebase(self.from, self.to),
+            _start_len: tag._start_len,
+            _end_tag: tag._end_tag,
+        }
+    }
+}
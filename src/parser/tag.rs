@@ -1,18 +1,20 @@
 use crate::{
     inline::{hashmap::InlineHashMap, vec::InlineVec},
-    queryselector::{self, QuerySelectorIterator},
-    Bytes, InnerNodeHandle,
+    queryselector::{self, QuerySelectorIterator, SelectorOptions},
+    tag_info::TagCategory,
+    util, Bytes, Dimension, InnerNodeHandle, SetBytesError, TextJoinOptions,
+};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fmt::{self, Write},
+    mem,
 };
-use std::{borrow::Cow, mem};
 
-use super::{handle::NodeHandle, Parser};
+use super::{handle::NodeHandle, known_attr::KnownAttr, options::PayloadId, Parser};
 
-const INLINED_ATTRIBUTES: usize = 2;
+const INLINED_ATTRIBUTES: usize = 1;
 const INLINED_SUBNODES: usize = 2;
-const HTML_VOID_ELEMENTS: [&str; 16] = [
-    "area", "base", "br", "col", "command", "embed", "hr", "img", "input", "keygen", "link",
-    "meta", "param", "source", "track", "wbr",
-];
 
 /// The type of map for "raw" attributes
 pub type RawAttributesMap<'a> = InlineHashMap<Bytes<'a>, Option<Bytes<'a>>, INLINED_ATTRIBUTES>;
@@ -23,24 +25,128 @@ pub type RawChildren = InlineVec<NodeHandle, INLINED_SUBNODES>;
 /// Stores all attributes of an HTML tag, as well as additional metadata such as `id` and `class`
 #[derive(Debug, Clone)]
 pub struct Attributes<'a> {
-    /// Raw attributes (maps attribute key to attribute value)
+    /// Raw attributes (maps the ASCII lowercase attribute key to attribute value)
     pub(crate) raw: RawAttributesMap<'a>,
+    /// The keys of raw attributes as written, for those that are not all lowercase (maps the lowercase key to it)
+    ///
+    /// Raw attributes are stored under their lowercase key, so that they are looked up case-insensitively with one hash probe,
+    /// but they are iterated over and serialized with their original name.
+    // boxed, because almost all attribute names are lowercase and an empty map would make every tag larger
+    #[allow(clippy::box_collection)]
+    spellings: Option<Box<HashMap<Bytes<'a>, Bytes<'a>>>>,
     /// The ID of this HTML element, if present
     pub(crate) id: Option<Bytes<'a>>,
     /// A list of class names of this HTML element, if present
     pub(crate) class: Option<Bytes<'a>>,
 }
 
+impl<'a> Default for Attributes<'a> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<'a> Attributes<'a> {
-    /// Creates a new `Attributes
-    pub(crate) fn new() -> Self {
+    /// Creates an empty set of attributes
+    pub fn new() -> Self {
         Self {
             raw: InlineHashMap::new(),
+            spellings: None,
+            id: None,
+            class: None,
+        }
+    }
+
+    /// Creates `Attributes` from all attributes of a tag at once
+    ///
+    /// The raw attribute map is allocated with the exact capacity needed, instead of growing it one attribute at a time.
+    pub(crate) fn from_parsed(attributes: &[(&'a [u8], Option<&'a [u8]>)]) -> Self {
+        Self::from_parsed_with(attributes, Bytes::from)
+    }
+
+    /// Creates `Attributes` from all attributes of a tag at once, converting each value with `value_bytes`
+    pub(crate) fn from_parsed_with(
+        attributes: &[(&'a [u8], Option<&'a [u8]>)],
+        value_bytes: impl Fn(&'a [u8]) -> Bytes<'a>,
+    ) -> Self {
+        if attributes.is_empty() {
+            return Self::new();
+        }
+
+        let mut this = Self::with_raw_capacity(attributes.iter().map(|&(key, _)| key));
+
+        for &(key, value) in attributes {
+            this.insert(key, value.map(&value_bytes));
+        }
+
+        this
+    }
+
+    /// Creates `Attributes` from owned key-value pairs at once, see `from_parsed()`
+    pub(crate) fn from_pairs(attributes: Vec<(Bytes<'a>, Option<Bytes<'a>>)>) -> Self {
+        if attributes.is_empty() {
+            return Self::new();
+        }
+
+        let mut this = Self::with_raw_capacity(attributes.iter().map(|(key, _)| key.as_bytes()));
+
+        for (key, value) in attributes {
+            this.insert(key, value);
+        }
+
+        this
+    }
+
+    /// Copies these attributes so that they do not borrow from the input, see `Bytes::rebase()`
+    pub(crate) fn rebase<'b>(&self, from: &[u8], to: &'b [u8]) -> Attributes<'b> {
+        let rebase = |bytes: &Bytes<'a>| bytes.rebase(from, to);
+
+        let mut raw = InlineHashMap::with_capacity(self.raw.len());
+        for (key, value) in self.raw.iter() {
+            raw.insert(rebase(key), value.as_ref().map(rebase));
+        }
+
+        let spellings = self.spellings.as_ref().map(|spellings| {
+            let spellings = spellings
+                .iter()
+                .map(|(key, name)| (rebase(key), rebase(name)));
+            Box::new(spellings.collect())
+        });
+
+        Attributes {
+            raw,
+            spellings,
+            id: self.id.as_ref().map(rebase),
+            class: self.class.as_ref().map(rebase),
+        }
+    }
+
+    /// Creates empty `Attributes` whose raw map has room for all of the given keys except for `id` and `class`
+    fn with_raw_capacity<'k>(keys: impl Iterator<Item = &'k [u8]>) -> Self {
+        let raw_count = keys
+            .filter(|key| !key.eq_ignore_ascii_case(b"id") && !key.eq_ignore_ascii_case(b"class"))
+            .count();
+
+        Self {
+            raw: InlineHashMap::with_capacity(raw_count),
+            spellings: None,
             id: None,
             class: None,
         }
     }
 
+    /// Reserves capacity for at least `additional` more attributes
+    ///
+    /// This is useful to avoid repeated reallocations when inserting many attributes.
+    pub fn reserve(&mut self, additional: usize) {
+        self.raw.reserve(additional);
+    }
+
+    /// Shrinks the capacity of the attribute storage as much as possible
+    pub fn shrink_to_fit(&mut self) {
+        self.raw.shrink_to_fit();
+    }
+
     /// Counts the number of attributes
     pub fn len(&self) -> usize {
         let mut raw = self.raw.len();
@@ -59,27 +165,185 @@ impl<'a> Attributes<'a> {
     }
 
     /// Checks whether a given string is in the class names list
+    ///
+    /// Character references in the `class` attribute are decoded before comparing, like `VDom::get_elements_by_class_name()`
+    /// does by default, so `class="a&amp;b"` contains `a&b`.
     pub fn is_class_member<B: AsRef<[u8]>>(&self, member: B) -> bool {
-        self.class_iter()
-            .map_or(false, |mut i| i.any(|s| s.as_bytes() == member.as_ref()))
+        self.matches_class(member.as_ref(), true)
+    }
+
+    /// Checks whether the `id` attribute is equal to the given id, decoding character references in it if `decode` is `true`
+    pub(crate) fn matches_id(&self, id: &[u8], decode: bool) -> bool {
+        self.id
+            .as_ref()
+            .is_some_and(|x| *x.decoded_if(decode) == *id)
+    }
+
+    /// Checks whether the `class` attribute contains the given class name, decoding character references in it if `decode` is `true`
+    pub(crate) fn matches_class(&self, class: &[u8], decode: bool) -> bool {
+        self.class.as_ref().is_some_and(|x| {
+            util::split_ascii_whitespace_bytes(&x.decoded_if(decode)).any(|x| x == class)
+        })
     }
 
     /// Checks whether this attributes collection contains a given key and returns its value
     ///
-    /// Attributes that exist in this tag but have no value set will have their inner Option set to None
+    /// Attributes that exist in this tag but have no value set will have their inner Option set to None.
+    /// Names are compared ASCII case-insensitively, like HTML attribute names in browsers, so `get("data-id")`
+    /// also finds `DATA-ID`. This applies to all methods of `Attributes` that take a name.
     pub fn get<B>(&self, key: B) -> Option<Option<&Bytes<'a>>>
     where
         B: Into<Bytes<'a>>,
     {
         let key: Bytes = key.into();
+        self.get_by_name(key.as_bytes())
+    }
+
+    /// Returns the first attribute of the given candidates that exists, along with the candidate that matched
+    ///
+    /// This is useful for attributes that have several names in practice, such as the URL of a lazy-loaded image.
+    /// Names are compared like in [`Attributes::get()`].
+    ///
+    /// # Example
+    /// ```
+    /// let dom = tl::parse(r#"<img data-original="a.png" src="">"#, Default::default()).unwrap();
+    /// let attributes = dom.nodes()[0].as_tag().unwrap().attributes();
+    ///
+    /// let (key, value) = attributes.get_first(&["data-src", "data-original", "src"]).unwrap();
+    /// assert_eq!(key, "data-original");
+    /// assert_eq!(value, Some(&"a.png".into()));
+    /// assert_eq!(attributes.get_first(&["data-srcset"]), None);
+    /// ```
+    pub fn get_first<'k>(&self, keys: &[&'k str]) -> Option<(&'k str, Option<&Bytes<'a>>)> {
+        keys.iter()
+            .find_map(|&key| Some((key, self.get_by_name(key.as_bytes())?)))
+    }
 
-        match key.as_bytes() {
+    /// Like [`Attributes::get()`], but the key does not need to outlive the attributes
+    pub(crate) fn get_by_name(&self, key: &[u8]) -> Option<Option<&Bytes<'a>>> {
+        match &*util::to_ascii_lowercase_bytes(key) {
             b"id" => self.id.as_ref().map(Some),
             b"class" => self.class.as_ref().map(Some),
-            _ => self.raw.get(&key).map(|x| x.as_ref()),
+            key => self.raw.get_borrowed(key).map(Option::as_ref),
         }
     }
 
+    /// Checks whether this attributes collection contains a known attribute and returns its value
+    ///
+    /// This is like [`Attributes::get()`], but faster, as known attributes are stored under their lowercase name,
+    /// so the name does not need to be lowercased before looking it up.
+    ///
+    /// # Example
+    /// ```
+    /// use tl::KnownAttr;
+    ///
+    /// let dom = tl::parse("<a HREF=\"/about\" download>About</a>", Default::default()).unwrap();
+    /// let attributes = dom.nodes()[0].as_tag().unwrap().attributes();
+    ///
+    /// assert_eq!(attributes.get_known(KnownAttr::Href), Some(Some(&"/about".into())));
+    /// assert_eq!(attributes.get_known(KnownAttr::Src), None);
+    /// ```
+    pub fn get_known(&self, attr: KnownAttr) -> Option<Option<&Bytes<'a>>> {
+        match attr {
+            KnownAttr::Id => self.id.as_ref().map(Some),
+            KnownAttr::Class => self.class.as_ref().map(Some),
+            _ => self
+                .raw
+                .get_borrowed(attr.name().as_bytes())
+                .map(Option::as_ref),
+        }
+    }
+
+    /// Returns the name of a raw attribute as written, given the lowercase key it is stored under
+    fn spelling<'s>(&'s self, key: &'s Bytes<'a>) -> &'s Bytes<'a> {
+        self.spellings
+            .as_ref()
+            .and_then(|spellings| spellings.get(key))
+            .unwrap_or(key)
+    }
+
+    /// Inserts a raw attribute under its lowercase key, which is `None` if the name is already lowercase
+    ///
+    /// If the attribute already exists with a different case, only its value is replaced, so that it is not duplicated.
+    fn insert_raw(&mut self, name: Bytes<'a>, key: Option<Vec<u8>>, value: Option<Bytes<'a>>) {
+        let lookup = key.as_deref().unwrap_or(name.as_bytes());
+        if let Some(existing) = self.raw.get_mut_borrowed(lookup) {
+            *existing = value;
+            return;
+        }
+
+        match key {
+            None => self.raw.insert(name, value),
+            Some(key) => {
+                // known attribute names are normalized instead
+                if let Some(attr) = KnownAttr::from_bytes(&key) {
+                    self.raw.insert(attr.name().into(), value);
+                    return;
+                }
+
+                let mut bytes = Bytes::new();
+                // the key is as long as the name, which already fits into `Bytes`
+                let _ = bytes.set(key);
+
+                self.spellings
+                    .get_or_insert_with(Default::default)
+                    .insert(bytes.clone(), name);
+                self.raw.insert(bytes, value);
+            }
+        }
+    }
+
+    /// Checks whether the value of the attribute with the given name was cut off by the parser
+    ///
+    /// See `ParserOptions::max_stored_attribute_len()`. Returns `false` if the attribute does not exist or has no value.
+    pub fn is_truncated<B>(&self, key: B) -> bool
+    where
+        B: Into<Bytes<'a>>,
+    {
+        self.get(key).flatten().is_some_and(Bytes::is_truncated)
+    }
+
+    /// Returns the value of a URL-valued attribute (such as `href` or `src`), normalized
+    ///
+    /// Leading and trailing C0 control characters and spaces are removed, as well as any
+    /// ASCII tab or newline within the value. The returned string is only allocated if the value needed changes.
+    ///
+    /// Returns `None` if the attribute does not exist or has no value.
+    ///
+    /// # Example
+    /// ```
+    /// let dom = tl::parse("<a href=\"  /about\n/team \">", Default::default()).unwrap();
+    /// let attributes = dom.nodes()[0].as_tag().unwrap().attributes();
+    ///
+    /// assert_eq!(attributes.get_url("href").as_deref(), Some("/about/team"));
+    /// ```
+    pub fn get_url<B>(&self, key: B) -> Option<Cow<'_, str>>
+    where
+        B: Into<Bytes<'a>>,
+    {
+        self.get(key).flatten().map(Bytes::as_url_str)
+    }
+
+    /// Returns the value of a presentational attribute that holds a length (such as `width` or `cellpadding`), parsed with its unit
+    ///
+    /// See [`Dimension::parse()`] for the accepted syntax. Returns `None` if the attribute does not exist,
+    /// has no value or does not start with a number.
+    ///
+    /// # Example
+    /// ```
+    /// let dom = tl::parse(r#"<td width=" 50% " height="20">"#, Default::default()).unwrap();
+    /// let attributes = dom.nodes()[0].as_tag().unwrap().attributes();
+    ///
+    /// assert_eq!(attributes.get_dimension("width"), Some(tl::Dimension::Percent(50.0)));
+    /// assert_eq!(attributes.get_dimension("height"), Some(tl::Dimension::Pixels(20.0)));
+    /// ```
+    pub fn get_dimension<B>(&self, key: B) -> Option<Dimension>
+    where
+        B: Into<Bytes<'a>>,
+    {
+        Dimension::parse(&self.get(key).flatten()?.as_utf8_str())
+    }
+
     /// Checks whether this attributes collection contains a given key
     pub fn contains<B>(&self, key: B) -> bool
     where
@@ -108,10 +372,16 @@ impl<'a> Attributes<'a> {
     {
         let key: Bytes = key.into();
 
-        match key.as_bytes() {
+        match &*util::to_ascii_lowercase_bytes(key.as_bytes()) {
             b"id" => self.id.take().map(Some),
             b"class" => self.class.take().map(Some),
-            _ => self.raw.remove(&key),
+            key => {
+                let value = self.raw.remove_borrowed(key)?;
+                if let Some(spellings) = &mut self.spellings {
+                    spellings.remove(key);
+                }
+                Some(value)
+            }
         }
     }
 
@@ -132,10 +402,10 @@ impl<'a> Attributes<'a> {
     {
         let key: Bytes = key.into();
 
-        match key.as_bytes() {
+        match &*util::to_ascii_lowercase_bytes(key.as_bytes()) {
             b"id" => self.id.take(),
             b"class" => self.class.take(),
-            _ => self.raw.get_mut(&key).and_then(mem::take),
+            key => self.raw.get_mut_borrowed(key).and_then(mem::take),
         }
     }
 
@@ -146,10 +416,10 @@ impl<'a> Attributes<'a> {
     {
         let key: Bytes = key.into();
 
-        match key.as_bytes() {
+        match &*util::to_ascii_lowercase_bytes(key.as_bytes()) {
             b"id" => self.id.as_mut().map(Some),
             b"class" => self.class.as_mut().map(Some),
-            _ => self.raw.get_mut(&key).map(Option::as_mut),
+            key => self.raw.get_mut_borrowed(key).map(Option::as_mut),
         }
     }
 
@@ -162,36 +432,56 @@ impl<'a> Attributes<'a> {
         let key: Bytes = key.into();
         let value = value.map(Into::into);
 
-        match key.as_bytes() {
+        let lower = match util::to_ascii_lowercase_bytes(key.as_bytes()) {
+            Cow::Borrowed(_) => None,
+            Cow::Owned(lower) => Some(lower),
+        };
+
+        match lower.as_deref().unwrap_or(key.as_bytes()) {
             b"id" => self.id = value,
             b"class" => self.class = value,
-            _ => self.raw.insert(key, value),
+            _ => self.insert_raw(key, lower, value),
         };
     }
 
     /// Returns an iterator `(attribute_key, attribute_value)` over the attributes of this `HTMLTag`
-    pub fn iter(&self) -> impl Iterator<Item = (Cow<str>, Option<Cow<str>>)> + '_ {
+    ///
+    /// The names of [`KnownAttr`]s are returned in lowercase.
+    pub fn iter(&self) -> impl Iterator<Item = (Cow<'_, str>, Option<Cow<'_, str>>)> + '_ {
         self.raw
             .iter()
             .map(|(k, v)| {
-                let k = k.as_utf8_str();
+                let k = self.spelling(k).as_utf8_str();
                 let v = v.as_ref().map(|x| x.as_utf8_str());
 
                 (Some(k), v)
             })
             .chain([
                 (
-                    self.id.is_some().then(|| Cow::Borrowed("id")),
+                    self.id.is_some().then_some(Cow::Borrowed("id")),
                     self.id.as_ref().map(|x| x.as_utf8_str()),
                 ),
                 (
-                    self.class.is_some().then(|| Cow::Borrowed("class")),
+                    self.class.is_some().then_some(Cow::Borrowed("class")),
                     self.class.as_ref().map(|x| x.as_utf8_str()),
                 ),
             ])
             .flat_map(|(k, v)| k.map(|k| (k, v)))
     }
 
+    /// Returns an iterator over the raw names and values of all attributes, in the same order as `iter()`
+    pub(crate) fn iter_bytes(&self) -> impl Iterator<Item = (&[u8], Option<&Bytes<'a>>)> + '_ {
+        self.raw
+            .iter()
+            .map(|(k, v)| (self.spelling(k).as_bytes(), v.as_ref()))
+            .chain(self.id.as_ref().map(|id| (&b"id"[..], Some(id))))
+            .chain(
+                self.class
+                    .as_ref()
+                    .map(|class| (&b"class"[..], Some(class))),
+            )
+    }
+
     /// Returns the `id` attribute of this HTML tag, if present
     pub fn id(&self) -> Option<&Bytes<'a>> {
         self.id.as_ref()
@@ -207,7 +497,132 @@ impl<'a> Attributes<'a> {
         self.class
             .as_ref()
             .and_then(Bytes::try_as_utf8_str)
-            .map(str::split_ascii_whitespace)
+            .map(util::split_ascii_whitespace)
+    }
+
+    /// Checks whether the `class` attribute contains the given class name
+    ///
+    /// This is the same as `is_class_member()`: the class names are separated by any amount of ASCII whitespace
+    /// and compared exactly after decoding character references.
+    pub fn has_class(&self, name: &str) -> bool {
+        self.matches_class(name.as_bytes(), true)
+    }
+
+    /// Adds a class name to the `class` attribute, creating the attribute if it does not exist
+    ///
+    /// Returns `false` (without changing anything) if the class is already present, or if the name is empty
+    /// or contains ASCII whitespace. Otherwise the attribute is rewritten with its class names separated
+    /// by single spaces and without duplicates, like `DOMTokenList` does in browsers.
+    ///
+    /// # Example
+    /// ```
+    /// let mut dom = tl::parse(r#"<p class="  a   b ">x</p>"#, Default::default()).unwrap();
+    /// let attributes = dom.nodes_mut()[0].as_tag_mut().unwrap().attributes_mut();
+    ///
+    /// assert!(attributes.add_class("c"));
+    /// assert!(!attributes.add_class("a"));
+    /// assert_eq!(attributes.class(), Some(&"a b c".into()));
+    /// ```
+    pub fn add_class(&mut self, name: &str) -> bool {
+        if !is_class_token(name) || self.has_class(name) {
+            return false;
+        }
+
+        let mut tokens = self.class_tokens();
+        tokens.push(name.as_bytes());
+        let value = tokens.join(&b' ');
+        self.set_class(value)
+    }
+
+    /// Removes a class name from the `class` attribute, and removes the attribute if no class names are left
+    ///
+    /// Returns `false` (without changing anything) if the class is not present.
+    /// Otherwise the remaining class names are separated by single spaces, like with `add_class()`.
+    ///
+    /// # Example
+    /// ```
+    /// let mut dom = tl::parse(r#"<p class="a b">x</p>"#, Default::default()).unwrap();
+    /// let attributes = dom.nodes_mut()[0].as_tag_mut().unwrap().attributes_mut();
+    ///
+    /// assert!(attributes.remove_class("a"));
+    /// assert_eq!(attributes.class(), Some(&"b".into()));
+    /// assert!(attributes.remove_class("b"));
+    /// assert_eq!(attributes.get("class"), None);
+    /// ```
+    pub fn remove_class(&mut self, name: &str) -> bool {
+        let mut tokens = self.class_tokens();
+        let len = tokens.len();
+        tokens.retain(|&token| *Bytes::from(token).decoded_if(true) != *name.as_bytes());
+
+        if tokens.len() == len {
+            return false;
+        }
+        if tokens.is_empty() {
+            self.class = None;
+            return true;
+        }
+
+        let value = tokens.join(&b' ');
+        self.set_class(value)
+    }
+
+    /// Removes the class name if it is present and adds it otherwise, and returns whether it is present afterwards
+    ///
+    /// See `add_class()` and `remove_class()`. Names that cannot be added are never present.
+    pub fn toggle_class(&mut self, name: &str) -> bool {
+        if self.has_class(name) {
+            !self.remove_class(name)
+        } else {
+            self.add_class(name)
+        }
+    }
+
+    /// Returns the unique class names of the `class` attribute in order
+    fn class_tokens(&self) -> Vec<&[u8]> {
+        let mut tokens: Vec<&[u8]> = Vec::new();
+
+        if let Some(class) = &self.class {
+            for token in util::split_ascii_whitespace_bytes(class.as_bytes()) {
+                if !tokens.contains(&token) {
+                    tokens.push(token);
+                }
+            }
+        }
+
+        tokens
+    }
+
+    /// Replaces the value of the `class` attribute, and returns `false` (without changing it) if it is too long
+    fn set_class(&mut self, value: Vec<u8>) -> bool {
+        let mut class = Bytes::new();
+        if class.set(value).is_err() {
+            return false;
+        }
+
+        self.class = Some(class);
+        true
+    }
+
+    /// Returns an iterator over the whitespace-separated tokens of an attribute, such as `rel` or `sizes`
+    ///
+    /// Returns `None` if the attribute does not exist, has no value or is not valid UTF-8.
+    ///
+    /// # Example
+    /// ```
+    /// let dom = tl::parse(r#"<a rel=" nofollow  noopener">"#, Default::default()).unwrap();
+    /// let attributes = dom.nodes()[0].as_tag().unwrap().attributes();
+    ///
+    /// let tokens = attributes.token_list("rel").unwrap().collect::<Vec<_>>();
+    /// assert_eq!(tokens, ["nofollow", "noopener"]);
+    /// ```
+    pub fn token_list<B>(&self, key: B) -> Option<impl Iterator<Item = &'_ str> + '_>
+    where
+        B: Into<Bytes<'a>>,
+    {
+        self.get(key)
+            .flatten()
+            .and_then(Bytes::try_as_utf8_str)
+            .map(util::split_ascii_whitespace)
     }
 
     /// Returns the underlying raw map for attributes
@@ -222,6 +637,17 @@ impl<'a> Attributes<'a> {
     }
 }
 
+/// How the end tag of an element is serialized
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum EndTag {
+    /// The end tag is generated from the name of the element
+    Generated,
+    /// The end tag is written as it appeared in the source, where it makes up the last `n` bytes of `_raw`
+    Source(u32),
+    /// The end tag was omitted in the source (e.g. `<li>One<li>Two`, or at the end of the input), so none is written
+    Omitted,
+}
+
 /// Represents a single HTML element
 #[derive(Debug, Clone)]
 pub struct HTMLTag<'a> {
@@ -230,12 +656,18 @@ pub struct HTMLTag<'a> {
     pub(crate) _children: RawChildren,
     pub(crate) _parent: Option<NodeHandle>,
     pub(crate) _raw: Bytes<'a>,
+    /// Length of the start tag in `_raw`, or `None` if the name or attributes were accessed mutably
+    ///
+    /// As long as this is set, the start tag is serialized using the original bytes
+    pub(crate) _start_len: Option<u32>,
+    /// How the end tag is serialized, which is `EndTag::Omitted` for parsed elements until their end tag is found
+    pub(crate) _end_tag: EndTag,
 }
 
 impl<'a> HTMLTag<'a> {
-    /// Creates a new HTMLTag
+    /// Creates a new HTMLTag from its source
     #[inline(always)]
-    pub(crate) fn new(
+    pub(crate) fn from_source(
         name: Bytes<'a>,
         attr: Attributes<'a>,
         children: InlineVec<NodeHandle, INLINED_SUBNODES>,
@@ -247,15 +679,73 @@ impl<'a> HTMLTag<'a> {
             _attributes: attr,
             _children: children,
             _parent: parent,
+            _start_len: Some(raw.as_bytes().len() as u32),
             _raw: raw,
+            _end_tag: EndTag::Omitted,
+        }
+    }
+
+    /// Creates a new element with the given name and attributes, which does not appear in the source and has no children
+    ///
+    /// The element is serialized from its name and attributes, and it can be added to a document with `Parser::add_node()`.
+    /// Unlike `Parser::build_element()`, this does not check that the name and the attribute names are valid.
+    ///
+    /// # Example
+    /// ```
+    /// let mut attributes = tl::Attributes::new();
+    /// attributes.insert("name", Some("generator"));
+    /// let meta = tl::HTMLTag::new("meta", attributes);
+    ///
+    /// let mut dom = tl::parse("", Default::default()).unwrap();
+    /// let parser = dom.parser_mut();
+    /// let handle = parser.add_node(tl::Node::Tag(meta));
+    /// assert_eq!(handle.get(parser).unwrap().outer_html(parser), r#"<meta name="generator">"#);
+    /// ```
+    pub fn new<N>(name: N, attributes: Attributes<'a>) -> Self
+    where
+        N: Into<Bytes<'a>>,
+    {
+        let mut tag = Self::from_name(name.into(), None);
+        tag._attributes = attributes;
+        tag
+    }
+
+    /// Creates a new HTMLTag that does not appear in the source, with no attributes and no children
+    pub(crate) fn from_name(name: Bytes<'a>, parent: Option<NodeHandle>) -> Self {
+        Self {
+            _name: name,
+            _attributes: Attributes::new(),
+            _children: InlineVec::new(),
+            _parent: parent,
+            _raw: Bytes::new(),
+            _start_len: None,
+            _end_tag: EndTag::Generated,
         }
     }
 
     /// Returns the parent of this HTML tag, if present
+    ///
+    /// Use `NodeHandle::parent()` to get the parent of text and comment nodes.
     pub fn parent(&self) -> Option<NodeHandle> {
         self._parent
     }
 
+    /// Returns the closest element that matches the given selector, starting with this tag and walking up its ancestors
+    ///
+    /// See [`NodeHandle::closest()`], which is cheaper if the handle of this tag is already known.
+    pub fn closest(&self, parser: &Parser<'a>, selector: &str) -> Option<NodeHandle> {
+        self.handle(parser)?.closest(parser, selector)
+    }
+
+    /// Looks up the handle of this tag from its address, which lies within the node at the same position in the node table
+    pub(crate) fn handle(&self, parser: &Parser<'a>) -> Option<NodeHandle> {
+        let offset = (self as *const Self as usize).checked_sub(parser.tags.as_ptr() as usize)?;
+        let position = offset / mem::size_of::<Node>();
+
+        let tag = parser.tags.get(position)?.as_tag()?;
+        std::ptr::eq(tag, self).then(|| parser.handle_at(position))
+    }
+
     /// Returns a wrapper around the children of this HTML tag
     #[inline]
     pub fn children(&self) -> Children<'a, '_> {
@@ -276,9 +766,99 @@ impl<'a> HTMLTag<'a> {
     /// Returns a mutable reference to the name of this HTML tag
     #[inline]
     pub fn name_mut(&mut self) -> &mut Bytes<'a> {
+        self._start_len = None;
+        self._end_tag = EndTag::Generated;
         &mut self._name
     }
 
+    /// Renames this HTML tag, e.g. to turn a `<b>` into a `<strong>`
+    ///
+    /// The name must be non-empty and consist of ident characters only. Renaming an element
+    /// that has children to a void element (such as `br`) is rejected, because void elements are
+    /// serialized without content. Afterwards, the start and end tag are generated from the new name.
+    ///
+    /// This does not update the lookup table of tag names (see `ParserOptions::track_tag_names()`)
+    /// or the query cache of the document.
+    ///
+    /// # Example
+    /// ```
+    /// let mut dom = tl::parse("<b class=x>Hi</b>", Default::default()).unwrap();
+    /// let parser = dom.parser_mut();
+    /// let tag = tl::NodeHandle::new(0).get_mut(parser).unwrap().as_tag_mut().unwrap();
+    ///
+    /// tag.set_name("strong").unwrap();
+    /// assert!(tag.set_name("br").is_err());
+    /// assert_eq!(dom.outer_html(), r#"<strong class="x">Hi</strong>"#);
+    /// ```
+    pub fn set_name<N>(&mut self, name: N) -> Result<(), SetBytesError>
+    where
+        N: Into<Bytes<'a>>,
+    {
+        let name: Bytes<'a> = name.into();
+        let bytes = name.as_bytes();
+
+        if bytes.is_empty() || !bytes.iter().copied().all(util::is_ident) {
+            return Err(SetBytesError::InvalidTagName(
+                name.as_utf8_str().into_owned(),
+            ));
+        }
+
+        if TagCategory::of(bytes).is_void() && !self._children.is_empty() {
+            return Err(SetBytesError::VoidElementContent(
+                name.as_utf8_str().into_owned(),
+            ));
+        }
+
+        *self.name_mut() = name;
+        Ok(())
+    }
+
+    /// Returns the categories of this HTML tag, based on its name
+    ///
+    /// See [`TagCategory::of()`] for more details.
+    #[inline]
+    pub fn category(&self) -> TagCategory {
+        TagCategory::of(self._name.as_bytes())
+    }
+
+    /// Checks whether this is a void element (an element that cannot have any content, such as `<br>`)
+    #[inline]
+    pub fn is_void(&self) -> bool {
+        self.category().is_void()
+    }
+
+    /// Checks whether this is a block-level element (such as `<div>` or `<p>`)
+    #[inline]
+    pub fn is_block_level(&self) -> bool {
+        self.category().is_block_level()
+    }
+
+    /// Checks whether this is an inline element (such as `<span>` or `<a>`)
+    ///
+    /// Unknown and custom elements are considered inline.
+    #[inline]
+    pub fn is_inline(&self) -> bool {
+        self.category().is_inline()
+    }
+
+    /// Checks whether this element is metadata content (such as `<meta>` or `<script>`)
+    #[inline]
+    pub fn is_metadata_content(&self) -> bool {
+        self.category().is_metadata_content()
+    }
+
+    /// Checks whether this is a form-associated element (such as `<input>` or `<select>`)
+    #[inline]
+    pub fn is_form_associated(&self) -> bool {
+        self.category().is_form_associated()
+    }
+
+    /// Checks whether this is a deprecated or obsolete element (such as `<font>` or `<center>`)
+    #[inline]
+    pub fn is_deprecated(&self) -> bool {
+        self.category().is_deprecated()
+    }
+
     /// Returns attributes of this HTML tag
     #[inline]
     pub fn attributes(&self) -> &Attributes<'a> {
@@ -286,60 +866,115 @@ impl<'a> HTMLTag<'a> {
     }
 
     /// Returns a mutable reference to the attributes of this HTML tag
+    ///
+    /// Afterwards, the start tag is generated from the name and attributes, and so is an end tag that was omitted in the source.
     #[inline]
     pub fn attributes_mut(&mut self) -> &mut Attributes<'a> {
+        self._start_len = None;
+        if self._end_tag == EndTag::Omitted {
+            self._end_tag = EndTag::Generated;
+        }
         &mut self._attributes
     }
 
     /// Returns the contained markup
     ///
+    /// The start tag of an element whose name and attributes were never accessed mutably
+    /// is serialized exactly as it appeared in the source.
+    /// End tags are written as they appeared in the source too, which means that end tags that were omitted
+    /// (e.g. `<li>One<li>Two`, or at the end of the input) are left out, unless the element was mutated.
+    ///
     /// ## Limitations
-    /// - The order of tag attributes is not guaranteed
-    /// - Spaces within the tag are not preserved (i.e. `<img      src="">` may become `<img src="">`)
+    /// - The order of tag attributes is not guaranteed after mutating them
+    /// - Spaces within a mutated tag are not preserved (i.e. `<img      src="">` may become `<img src="">`)
     ///
     /// Equivalent to [Element#outerHTML](https://developer.mozilla.org/en-US/docs/Web/API/Element/outerHTML) in browsers)
     pub fn outer_html<'p>(&'p self, parser: &'p Parser<'a>) -> String {
-        let tag_name = self._name.as_utf8_str();
-        let is_void_element = HTML_VOID_ELEMENTS.contains(&tag_name.as_ref());
-        let mut outer_html = format!("<{}", &tag_name);
+        let mut outer_html = String::new();
+        // writing into a string never fails
+        let _ = self.write_outer_html(parser, &mut outer_html);
+        outer_html
+    }
 
-        #[inline]
-        fn write_attribute(dest: &mut String, k: Cow<str>, v: Option<Cow<str>>) {
-            dest.push(' ');
+    /// Writes the markup of this tag and its subtree into `dest`, see `outer_html()`
+    ///
+    /// Unlike `outer_html()`, this does not build the whole markup in memory first. Errors of `dest` are returned as is.
+    /// To write into an [`io::Write`](std::io::Write) (such as a file), wrap it in an [`IoWriter`](crate::IoWriter).
+    ///
+    /// # Example
+    /// ```
+    /// let dom = tl::parse("<p>Hello <b>world</b></p>", Default::default()).unwrap();
+    /// let p = dom.nodes()[0].as_tag().unwrap();
+    ///
+    /// let mut out = String::from("> ");
+    /// p.write_outer_html(dom.parser(), &mut out).unwrap();
+    /// assert_eq!(out, "> <p>Hello <b>world</b></p>");
+    /// ```
+    pub fn write_outer_html<W: Write + ?Sized>(
+        &self,
+        parser: &Parser<'a>,
+        dest: &mut W,
+    ) -> fmt::Result {
+        write_html(dest, parser, vec![HtmlStep::Tag(self)])
+    }
 
-            dest.push_str(&k);
+    /// Writes the start tag of this tag, reusing the source if the tag was not mutated
+    fn write_start_tag<W: Write + ?Sized>(&self, dest: &mut W) -> fmt::Result {
+        match self._start_len {
+            Some(len) => dest.write_str(&String::from_utf8_lossy(
+                &self._raw.as_bytes()[..len as usize],
+            )),
+            None => self.write_generated_start_tag(dest),
+        }
+    }
 
-            if let Some(value) = v {
-                dest.push_str("=\"");
-                dest.push_str(&value);
-                dest.push('"');
+    /// Writes the end tag of this tag, which reuses the source and is left out if it was omitted in the source
+    fn write_end_tag<W: Write + ?Sized>(&self, dest: &mut W) -> fmt::Result {
+        match self._end_tag {
+            EndTag::Generated => {
+                dest.write_str("</")?;
+                dest.write_str(&self._name.as_utf8_str())?;
+                dest.write_char('>')
+            }
+            EndTag::Source(len) => {
+                let raw = self._raw.as_bytes();
+                dest.write_str(&String::from_utf8_lossy(&raw[raw.len() - len as usize..]))
             }
+            EndTag::Omitted => Ok(()),
         }
+    }
 
-        let attr = self.attributes();
+    /// Generates the start tag from the name and attributes of this tag
+    fn write_generated_start_tag<W: Write + ?Sized>(&self, dest: &mut W) -> fmt::Result {
+        dest.write_char('<')?;
+        dest.write_str(&self._name.as_utf8_str())?;
 
-        for (k, v) in attr.iter() {
-            write_attribute(&mut outer_html, k, v);
-        }
+        #[inline]
+        fn write_attribute<W: Write + ?Sized>(
+            dest: &mut W,
+            k: Cow<str>,
+            v: Option<Cow<str>>,
+        ) -> fmt::Result {
+            dest.write_char(' ')?;
 
-        outer_html.push('>');
+            dest.write_str(&k)?;
 
-        // void elements have neither content nor a closing tag.
-        if is_void_element {
-            return outer_html;
+            if let Some(value) = v {
+                dest.write_str("=\"")?;
+                dest.write_str(&value)?;
+                dest.write_char('"')?;
+            }
+
+            Ok(())
         }
 
-        // TODO(y21): More of an idea than a TODO, but a potential perf improvement
-        // could be having some kind of internal inner_html function that takes a &mut String
-        // and simply writes to it instead of returning a newly allocated string for every element
-        // and appending it
-        outer_html.push_str(&self.inner_html(parser));
+        let attr = self.attributes();
 
-        outer_html.push_str("</");
-        outer_html.push_str(&self._name.as_utf8_str());
-        outer_html.push('>');
+        for (k, v) in attr.iter() {
+            write_attribute(dest, k, v)?;
+        }
 
-        outer_html
+        dest.write_char('>')
     }
 
     /// Returns the contained markup
@@ -350,12 +985,19 @@ impl<'a> HTMLTag<'a> {
     ///
     /// Equivalent to [Element#innerHTML](https://developer.mozilla.org/en-US/docs/Web/API/Element/innerHTML) in browsers)
     pub fn inner_html<'p>(&'p self, parser: &'p Parser<'a>) -> String {
-        self.children()
-            .top()
-            .iter()
-            .map(|handle| handle.get(parser).unwrap())
-            .map(|node| node.outer_html(parser))
-            .collect::<String>()
+        let mut inner_html = String::new();
+        // writing into a string never fails
+        let _ = self.write_inner_html(parser, &mut inner_html);
+        inner_html
+    }
+
+    /// Writes the markup of the subtree of this tag into `dest`, see `inner_html()` and `write_outer_html()`
+    pub fn write_inner_html<W: Write + ?Sized>(
+        &self,
+        parser: &Parser<'a>,
+        dest: &mut W,
+    ) -> fmt::Result {
+        write_html(dest, parser, HtmlStep::children(self, parser))
     }
 
     /// Returns the raw HTML of this tag.
@@ -363,29 +1005,112 @@ impl<'a> HTMLTag<'a> {
     ///
     /// **Note:** Mutating this tag does *not* re-compute the HTML representation of this tag.
     /// This simply returns a reference to the substring.
+    /// Tags that do not appear in the source (e.g. created with `HTMLTag::new()`) have no raw HTML, so this is empty for them.
     pub fn raw(&self) -> &Bytes<'a> {
         &self._raw
     }
 
+    /// Returns the identifier that the payload handler for this element returned while parsing,
+    /// if one was registered for its name with `ParserOptions::tag_payload_handler()`
+    ///
+    /// Elements that were created after parsing have no payload identifier.
+    pub fn payload_id(&self, parser: &Parser<'a>) -> Option<PayloadId> {
+        if parser.payloads.is_empty() {
+            return None;
+        }
+
+        parser.payloads.get(&self.handle(parser)?).copied()
+    }
+
     /// Returns the boundaries/position `(start, end)` of this HTML tag in the source string.
     ///
+    /// Both positions are inclusive. Returns `None` for tags that do not appear in the source,
+    /// such as those created with `HTMLTag::new()` or `Parser::build_element()`.
+    ///
     /// # Example
     /// ```
     /// let source = "<p><span>hello</span></p>";
     /// let dom = tl::parse(source, Default::default()).unwrap();
     /// let parser = dom.parser();
     /// let span = dom.nodes().iter().filter_map(|n| n.as_tag()).find(|n| n.name() == "span").unwrap();
-    /// let (start, end) = span.boundaries(parser);
+    /// let (start, end) = span.boundaries(parser).unwrap();
     /// assert_eq!((start, end), (3, 20));
     /// assert_eq!(&source[start..=end], "<span>hello</span>");
     /// ```
-    pub fn boundaries(&self, parser: &Parser<'a>) -> (usize, usize) {
-        let raw = self._raw.as_bytes();
-        let input = parser.stream.data().as_ptr();
+    pub fn boundaries(&self, parser: &Parser<'a>) -> Option<(usize, usize)> {
+        let raw = self
+            ._raw
+            .as_bytes_borrowed()
+            .filter(|raw| !raw.is_empty())?;
+        let input = parser.stream.data().as_ptr_range();
         let start = raw.as_ptr();
-        let offset = start as usize - input as usize;
+
+        if !input.contains(&start) {
+            return None;
+        }
+
+        let offset = start as usize - input.start as usize;
         let end = offset + raw.len() - 1;
-        (offset, end)
+        Some((offset, end))
+    }
+
+    /// Returns the source of this element, see [`Node::source_bytes()`]
+    ///
+    /// The subtree is visited with an explicit stack, so that deeply nested elements cannot overflow the stack.
+    fn source_bytes(&self, parser: &Parser<'a>) -> Option<&'a [u8]> {
+        let raw = self._raw.as_bytes_borrowed()?;
+        let data = parser.stream.data();
+
+        // the children must cover the content of each element in order, apart from end tags that the parser ignored,
+        // otherwise children were removed, inserted or reordered
+        let mut position = source_offset(raw, parser)?;
+        let mut stack = vec![SourceStep::Tag(self)];
+
+        while let Some(step) = stack.pop() {
+            match step {
+                SourceStep::Tag(tag) => {
+                    let raw = tag._raw.as_bytes_borrowed()?;
+                    let start_len = tag._start_len? as usize;
+                    let end_len = match tag._end_tag {
+                        EndTag::Source(len) => len as usize,
+                        EndTag::Omitted => 0,
+                        EndTag::Generated => return None,
+                    };
+                    let start = source_offset(raw, parser)?;
+                    if start < position || !is_ignored_markup(&data[position..start]) {
+                        return None;
+                    }
+
+                    position = start + start_len;
+                    stack.push(SourceStep::Leave {
+                        content_end: start + raw.len() - end_len,
+                        end: start + raw.len(),
+                    });
+                    for child in tag._children.as_slice().iter().rev() {
+                        stack.push(match child.get(parser)? {
+                            Node::Tag(tag) => SourceStep::Tag(tag),
+                            node => SourceStep::Text(node),
+                        });
+                    }
+                }
+                SourceStep::Text(node) => {
+                    let text = node.source_bytes(parser)?;
+                    let start = source_offset(text, parser)?;
+                    if start < position || !is_ignored_markup(&data[position..start]) {
+                        return None;
+                    }
+                    position = start + text.len();
+                }
+                SourceStep::Leave { content_end, end } => {
+                    if !is_ignored_markup(data.get(position..content_end)?) {
+                        return None;
+                    }
+                    position = end;
+                }
+            }
+        }
+
+        Some(raw)
     }
 
     /// Returns the contained text of this element, excluding any markup.
@@ -393,38 +1118,146 @@ impl<'a> HTMLTag<'a> {
     /// This function may not allocate memory for a new string as it can just return the part of the tag that doesn't have markup.
     /// For tags that *do* have more than one subnode, this will allocate memory
     pub fn inner_text<'p>(&self, parser: &'p Parser<'a>) -> Cow<'p, str> {
-        let len = self._children.len();
-
-        if len == 0 {
-            // If there are no subnodes, we can just return a static, empty, string slice
-            return Cow::Borrowed("");
+        let mut texts = self.texts(parser);
+
+        match (texts.next(), texts.next()) {
+            (None, _) => Cow::Borrowed(""),
+            (Some(text), None) => text.as_utf8_str(),
+            _ => {
+                let mut inner_text = String::new();
+                // writing into a string never fails
+                let _ = self.write_inner_text(parser, &mut inner_text);
+                Cow::Owned(inner_text)
+            }
         }
+    }
 
-        let first = self._children[0].get(parser).unwrap();
+    /// Writes the contained text of this element into `dest`, see `inner_text()` and `write_outer_html()`
+    pub fn write_inner_text<W: Write + ?Sized>(
+        &self,
+        parser: &Parser<'a>,
+        dest: &mut W,
+    ) -> fmt::Result {
+        write_texts(self.texts(parser), dest)
+    }
 
-        if len == 1 {
-            match &first {
-                Node::Tag(t) => return t.inner_text(parser),
-                Node::Raw(e) => return e.as_utf8_str(),
-                Node::Comment(_) => return Cow::Borrowed(""),
+    /// Returns the contained text of this element like `inner_text()`, but with character references decoded
+    ///
+    /// Each text node is decoded separately using [`Bytes::as_decoded_str()`], which supports numeric references
+    /// and a subset of the named references of the HTML specification (such as `&amp;`, `&nbsp;` and `&eacute;`).
+    /// Unknown references are kept as they are. The content of raw text elements (e.g. `<script>` or `<style>`)
+    /// is not decoded, like in browsers.
+    ///
+    /// # Example
+    /// ```
+    /// let dom = tl::parse("<p>Tom &amp; <b>Jerry&#x27;s</b></p>", Default::default()).unwrap();
+    /// let p = dom.nodes()[0].as_tag().unwrap();
+    /// assert_eq!(p.inner_text(dom.parser()), "Tom &amp; Jerry&#x27;s");
+    /// assert_eq!(p.inner_text_decoded(dom.parser()), "Tom & Jerry's");
+    /// ```
+    pub fn inner_text_decoded<'p>(&self, parser: &'p Parser<'a>) -> Cow<'p, str> {
+        concat_texts(self.texts(parser).decoded())
+    }
+
+    /// Returns the contained text of this element like `inner_text()`, but with `sep` inserted between the texts of its children
+    ///
+    /// Every child element contributes its whole `inner_text()`, and every text node directly within this element contributes its text.
+    /// Comments and text nodes that only consist of ASCII whitespace (such as the indentation between elements) contribute nothing.
+    /// Children without text do not produce a separator, see `inner_text_with_options()` to keep them.
+    ///
+    /// # Example
+    /// ```
+    /// let dom = tl::parse("<div>\n  <p>Hello <b>world</b></p>\n  <p>Bye</p>\n</div>", Default::default()).unwrap();
+    /// let div = dom.nodes()[0].as_tag().unwrap();
+    ///
+    /// assert_eq!(div.inner_text_with(dom.parser(), "\n\n"), "Hello world\n\nBye");
+    /// ```
+    pub fn inner_text_with(&self, parser: &Parser<'a>, sep: &str) -> String {
+        self.inner_text_with_options(parser, sep, TextJoinOptions::default())
+    }
+
+    /// Returns the contained text of this element like `inner_text_with()`, using the given options
+    pub fn inner_text_with_options(
+        &self,
+        parser: &Parser<'a>,
+        sep: &str,
+        options: TextJoinOptions,
+    ) -> String {
+        let mut out = String::new();
+        let mut first = true;
+
+        for child in self.children().top().iter() {
+            let text = match child.get(parser) {
+                Some(Node::Tag(tag)) => tag.inner_text(parser),
+                Some(Node::Raw(text))
+                    if !util::trim_ascii_whitespace_bytes(text.as_bytes()).is_empty() =>
+                {
+                    text.as_utf8_str()
+                }
+                _ => continue,
+            };
+
+            if text.is_empty() && !options.keep_empty {
+                continue;
+            }
+
+            if !first {
+                out.push_str(sep);
             }
+            out.push_str(&text);
+            first = false;
         }
 
-        // If there are >1 nodes, we need to allocate a new string and push each inner_text in it
-        // TODO: check if String::with_capacity() is worth it
-        let mut s = String::from(first.inner_text(parser));
+        out
+    }
+
+    /// Checks whether the text of this element (as returned by `inner_text()`, if `text` returns the raw text of a node) contains `needle`
+    ///
+    /// Unlike searching the result of `inner_text()`, this does not concatenate the text nodes,
+    /// and stops at the first occurrence.
+    pub(crate) fn contains_text<'p>(
+        &self,
+        parser: &'p Parser<'a>,
+        needle: &[u8],
+        text: impl Fn(&'p Bytes<'a>) -> Cow<'p, str>,
+    ) -> bool {
+        let contains = |haystack: &[u8]| haystack.windows(needle.len()).any(|w| w == needle);
+
+        let Some(overlap) = needle.len().checked_sub(1) else {
+            return true;
+        };
 
-        for &id in self._children.iter().skip(1) {
-            let node = id.get(parser).unwrap();
+        // the last bytes of the previous text nodes, which may contain the start of an occurrence that spans nodes
+        let mut tail = Vec::new();
 
-            match &node {
-                Node::Tag(t) => s.push_str(&t.inner_text(parser)),
-                Node::Raw(e) => s.push_str(&e.as_utf8_str()),
-                Node::Comment(_) => { /* no op */ }
+        for node in self.texts(parser) {
+            let node = text(node);
+            let node = node.as_bytes();
+
+            if contains(node) {
+                return true;
             }
+
+            if !tail.is_empty() {
+                let end = tail.len();
+                tail.extend_from_slice(&node[..node.len().min(overlap)]);
+                if contains(&tail) {
+                    return true;
+                }
+                tail.truncate(end);
+            }
+
+            tail.extend_from_slice(&node[node.len().saturating_sub(overlap)..]);
+            tail.drain(..tail.len().saturating_sub(overlap));
         }
 
-        Cow::Owned(s)
+        false
+    }
+
+    /// Returns an iterator over the text nodes in the subtree of this tag, following its children (see [`SubtreeTexts`])
+    fn texts<'p>(&self, parser: &'p Parser<'a>) -> SubtreeTexts<'p, 'a> {
+        let is_raw_text = parser.options.is_raw_text_element(self._name.as_bytes());
+        SubtreeTexts::new(parser, self._children.as_slice(), is_raw_text)
     }
 
     /// Tries to parse the query selector and returns an iterator over elements that match the given query selector.
@@ -469,27 +1302,53 @@ impl<'a> HTMLTag<'a> {
         parser: &'b Parser<'a>,
         selector: &'b str,
     ) -> Option<QuerySelectorIterator<'a, 'b, Self>> {
-        let selector = crate::parse_query_selector(selector)?;
-        let iter = queryselector::QuerySelectorIterator::new(selector, parser, self);
-        Some(iter)
+        self.query_selector_with_options(parser, selector, SelectorOptions::default())
     }
 
-    /// Calls the given closure with each tag as parameter
+    /// Returns the first subnode in document order that matches the given query selector
     ///
-    /// The closure must return a boolean, indicating whether it should stop iterating
-    /// Returning `true` will break the loop
-    pub fn find_node<F>(&self, parser: &Parser<'a>, f: &mut F) -> Option<NodeHandle>
-    where
-        F: FnMut(&Node<'a>) -> bool,
-    {
-        for &id in self._children.iter() {
-            let node = id.get(parser).unwrap();
+    /// This stops evaluating the selector once the first match is found.
+    /// Returns `None` if the selector is invalid or if nothing matches.
+    pub fn query_selector_first(&self, parser: &Parser<'a>, selector: &str) -> Option<NodeHandle> {
+        let selector = crate::parse_query_selector(selector)?;
+        queryselector::QuerySelectorIterator::new(selector, parser, self).next()
+    }
 
-            if f(node) {
-                return Some(id);
-            }
-        }
-        None
+    /// Like `query_selector()`, but returns an error describing what went wrong and where if the selector is invalid
+    pub fn query_selector_checked<'b>(
+        &'b self,
+        parser: &'b Parser<'a>,
+        selector: &'b str,
+    ) -> Result<QuerySelectorIterator<'a, 'b, Self>, queryselector::ParseError> {
+        let selector = crate::parse_query_selector_checked(selector)?;
+        Ok(queryselector::QuerySelectorIterator::new(
+            selector, parser, self,
+        ))
+    }
+
+    /// Returns an iterator over the subnodes that match a selector compiled with `Selector::parse()`
+    pub fn query_selector_with<'b>(
+        &'b self,
+        parser: &'b Parser<'a>,
+        selector: &'b queryselector::OwnedSelector,
+    ) -> QuerySelectorIterator<'a, 'b, Self> {
+        queryselector::QuerySelectorIterator::from_selector(
+            Cow::Borrowed(selector.selector()),
+            parser,
+            self,
+        )
+    }
+
+    /// Like `query_selector()`, but matches elements using the given options
+    pub fn query_selector_with_options<'b>(
+        &'b self,
+        parser: &'b Parser<'a>,
+        selector: &'b str,
+        options: SelectorOptions,
+    ) -> Option<QuerySelectorIterator<'a, 'b, Self>> {
+        let selector = crate::parse_query_selector(selector)?;
+        let iter = queryselector::QuerySelectorIterator::new(selector, parser, self);
+        Some(iter.with_options(options))
     }
 }
 
@@ -542,6 +1401,9 @@ impl<'a, 'b> Children<'a, 'b> {
     }
 
     /// Returns the (start, end) boundaries of the children of this tag.
+    ///
+    /// These are the handles of the first child and the last subnode. Note that once nodes have been inserted
+    /// into the document, the handles in between are not necessarily subnodes of this tag. Use `all()` to get all subnodes.
     #[inline]
     pub fn boundaries(&self, parser: &Parser<'a>) -> Option<(InnerNodeHandle, InnerNodeHandle)> {
         self.start().zip(self.end(parser))
@@ -576,10 +1438,19 @@ impl<'a, 'b> Children<'a, 'b> {
     /// assert_eq!(a.children().all(dom.parser()).len(), 7);
     /// ```
     pub fn all(&self, parser: &'b Parser<'a>) -> &'b [Node<'a>] {
-        self.boundaries(parser)
-            .map(|(start, end)| &parser.tags[start as usize..=end as usize])
+        self.positions(parser)
+            .map(|range| &parser.tags[range])
             .unwrap_or(&[])
     }
+
+    /// Returns the range of positions of all subnodes in the node table of the parser
+    ///
+    /// Nodes are stored in document order, so the subnodes of a tag are always stored contiguously.
+    pub(crate) fn positions(&self, parser: &Parser<'a>) -> Option<std::ops::Range<usize>> {
+        let start = parser.position_of(*self.0._children.get(0)?)?;
+        let end = parser.position_of(find_last_node_handle(self.0, parser)?)?;
+        Some(start..end + 1)
+    }
 }
 
 /// A thin mutable wrapper around the children of [`HTMLTag`]
@@ -596,31 +1467,233 @@ impl<'a, 'b> ChildrenMut<'a, 'b> {
     }
 }
 
+/// Returns the offset of the given bytes in the input of the parser, or `None` if they are not part of it
+fn source_offset(bytes: &[u8], parser: &Parser<'_>) -> Option<usize> {
+    let input = parser.stream.data().as_ptr_range();
+    let range = bytes.as_ptr_range();
+
+    let is_contained = input.start <= range.start && range.end <= input.end;
+    is_contained.then(|| range.start as usize - input.start as usize)
+}
+
+/// A pending step of `HTMLTag::source_bytes()`
+enum SourceStep<'p, 'a> {
+    /// Checks an element, and then its children
+    Tag(&'p HTMLTag<'a>),
+    /// Checks a text or comment node
+    Text(&'p Node<'a>),
+    /// Checks the rest of the content of an element, which ends at `content_end`, and skips its end tag
+    Leave { content_end: usize, end: usize },
+}
+
+/// Checks whether the given part of the input consists of end tags only (or is empty),
+/// which the parser ignores if they do not match an open element
+fn is_ignored_markup(mut bytes: &[u8]) -> bool {
+    while !bytes.is_empty() {
+        if !bytes.starts_with(b"</") {
+            return false;
+        }
+
+        match bytes.iter().position(|&c| c == b'>') {
+            Some(end) => bytes = &bytes[end + 1..],
+            None => return false,
+        }
+    }
+
+    true
+}
+
+/// Checks whether the given string can be a class name, which must not be empty or contain ASCII whitespace
+fn is_class_token(name: &str) -> bool {
+    !name.is_empty() && !name.bytes().any(util::is_ascii_whitespace)
+}
+
 /// Attempts to find the very last node handle that is contained in the given tag
 fn find_last_node_handle<'a>(tag: &HTMLTag<'a>, parser: &Parser<'a>) -> Option<NodeHandle> {
-    let last_handle = tag._children.as_slice().last().copied()?;
+    let mut last_handle = tag._children.as_slice().last().copied()?;
 
-    let child = last_handle
+    // descend into the last child until we reach the innermost node
+    while let Some(child) = last_handle
         .get(parser)
         .expect("Failed to get child node, please open a bug report") // this shouldn't happen
-        .as_tag();
+        .as_tag()
+        .and_then(|tag| tag._children.as_slice().last().copied())
+    {
+        last_handle = child;
+    }
+
+    Some(last_handle)
+}
+
+/// An iterator over the text nodes in the subtrees of some nodes, in tree order
+///
+/// This follows the children of each element instead of using the positions in the node table,
+/// which no longer match the tree once children were rearranged through [`ChildrenMut`].
+/// It uses an explicit stack instead of recursion, so that deeply nested documents cannot overflow the stack.
+pub(crate) struct SubtreeTexts<'p, 'a> {
+    parser: &'p Parser<'a>,
+    /// The nodes that are visited next, the last one first, and whether their parent is a raw text element
+    stack: Vec<(NodeHandle, bool)>,
+}
+
+impl<'p, 'a> SubtreeTexts<'p, 'a> {
+    /// Creates an iterator over the text nodes among the given nodes and their subtrees
+    ///
+    /// `is_raw_text` tells whether the given nodes are the content of a raw text element (e.g. `<script>`).
+    pub(crate) fn new(parser: &'p Parser<'a>, nodes: &[NodeHandle], is_raw_text: bool) -> Self {
+        Self {
+            parser,
+            stack: nodes
+                .iter()
+                .rev()
+                .map(|&handle| (handle, is_raw_text))
+                .collect(),
+        }
+    }
+
+    /// Returns the next text node, and whether it is the content of a raw text element
+    fn next_text(&mut self) -> Option<(&'p Bytes<'a>, bool)> {
+        while let Some((handle, is_raw_text)) = self.stack.pop() {
+            match handle.get(self.parser) {
+                Some(Node::Raw(text)) => return Some((text, is_raw_text)),
+                Some(Node::Tag(tag)) => {
+                    let is_raw_text = self
+                        .parser
+                        .options
+                        .is_raw_text_element(tag._name.as_bytes());
+                    let children = tag._children.as_slice().iter().rev();
+                    self.stack
+                        .extend(children.map(|&child| (child, is_raw_text)));
+                }
+                Some(Node::Comment(_)) | None => {}
+            }
+        }
+
+        None
+    }
+
+    /// Returns an iterator over the text nodes with character references decoded,
+    /// except for the content of raw text elements, which browsers do not decode either
+    pub(crate) fn decoded(mut self) -> impl Iterator<Item = Cow<'p, str>> {
+        std::iter::from_fn(move || {
+            let (text, is_raw_text) = self.next_text()?;
+            Some(match is_raw_text {
+                true => text.as_utf8_str(),
+                false => text.as_decoded_str(),
+            })
+        })
+    }
+}
+
+impl<'p, 'a> Iterator for SubtreeTexts<'p, 'a> {
+    type Item = &'p Bytes<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_text().map(|(text, _)| text)
+    }
+}
+
+/// Concatenates the given texts, and only allocates if there is more than one
+pub(crate) fn concat_texts<'p>(mut texts: impl Iterator<Item = Cow<'p, str>>) -> Cow<'p, str> {
+    let Some(first) = texts.next() else {
+        return Cow::Borrowed("");
+    };
+
+    match texts.next() {
+        None => first,
+        Some(second) => {
+            let mut s = first.into_owned();
+            s.push_str(&second);
+            texts.for_each(|text| s.push_str(&text));
+            Cow::Owned(s)
+        }
+    }
+}
+
+/// Writes the given text nodes into `dest`, in the same way as [`concat_texts()`] concatenates them
+pub(crate) fn write_texts<'p, 'a: 'p, W: Write + ?Sized>(
+    texts: impl Iterator<Item = &'p Bytes<'a>>,
+    dest: &mut W,
+) -> fmt::Result {
+    for text in texts {
+        dest.write_str(&text.as_utf8_str())?;
+    }
+
+    Ok(())
+}
+
+/// A pending step of [`write_html()`]
+enum HtmlStep<'p, 'a> {
+    /// Writes an element with its start tag, content and end tag
+    Tag(&'p HTMLTag<'a>),
+    /// Writes text or a comment as is
+    Raw(&'p Bytes<'a>),
+    /// Writes the end tag of an element whose content has been written
+    EndTag(&'p HTMLTag<'a>),
+}
+
+impl<'p, 'a> HtmlStep<'p, 'a> {
+    /// Returns the steps for the children of a tag, in the reverse order so they can be popped from a stack
+    fn children(tag: &'p HTMLTag<'a>, parser: &'p Parser<'a>) -> Vec<Self> {
+        tag._children
+            .as_slice()
+            .iter()
+            .rev()
+            .map(|handle| match handle.get(parser).unwrap() {
+                Node::Tag(tag) => HtmlStep::Tag(tag),
+                Node::Raw(bytes) | Node::Comment(bytes) => HtmlStep::Raw(bytes),
+            })
+            .collect()
+    }
+}
 
-    if let Some(child) = child {
-        // Recursively call this function to get to the innermost node
-        find_last_node_handle(child, parser).or(Some(last_handle))
-    } else {
-        Some(last_handle)
+/// Serializes the nodes on the stack (the last one first) and their subtrees into `dest`
+///
+/// This uses an explicit stack instead of recursion, so that serializing deeply nested documents cannot overflow the stack.
+fn write_html<'p, 'a, W: Write + ?Sized>(
+    dest: &mut W,
+    parser: &'p Parser<'a>,
+    mut stack: Vec<HtmlStep<'p, 'a>>,
+) -> fmt::Result {
+    while let Some(step) = stack.pop() {
+        match step {
+            HtmlStep::Tag(tag) => {
+                tag.write_start_tag(dest)?;
+
+                // void elements have neither content nor a closing tag.
+                if !tag.is_void() {
+                    stack.push(HtmlStep::EndTag(tag));
+                    stack.extend(HtmlStep::children(tag, parser));
+                }
+            }
+            HtmlStep::Raw(bytes) => dest.write_str(&bytes.as_utf8_str())?,
+            HtmlStep::EndTag(tag) => tag.write_end_tag(dest)?,
+        }
     }
+
+    Ok(())
 }
 
+// every node of a document is stored in one table, so larger nodes make parsing slower (checked handles add to the size)
+#[cfg(not(any(debug_assertions, feature = "checked-handles")))]
+const _: () = assert!(mem::size_of::<Node>() <= 176, "nodes must not grow");
+
 /// An HTML Node
 #[derive(Debug, Clone)]
+// checked handles are larger, which makes tags (which store many handles) exceed the size limit of the lint
+#[cfg_attr(
+    any(debug_assertions, feature = "checked-handles"),
+    allow(clippy::large_enum_variant)
+)]
 pub enum Node<'a> {
     /// A regular HTML element/tag
     Tag(HTMLTag<'a>),
     /// Raw text (no particular HTML element)
     Raw(Bytes<'a>),
     /// Comment (<!-- -->)
+    ///
+    /// Other markup declarations (such as `<!DOCTYPE html>` after the start of the document) are stored as comments too.
+    /// The doctype of the document is not a node, see `VDom::doctype()`.
     Comment(Bytes<'a>),
 }
 
@@ -634,6 +1707,17 @@ impl<'a> Node<'a> {
         }
     }
 
+    /// Returns the inner text of this node, with character references decoded
+    ///
+    /// See [`HTMLTag::inner_text_decoded()`].
+    pub fn inner_text_decoded<'s, 'p: 's>(&'s self, parser: &'p Parser<'a>) -> Cow<'s, str> {
+        match self {
+            Node::Comment(_) => Cow::Borrowed(""),
+            Node::Raw(r) => r.as_decoded_str(),
+            Node::Tag(t) => t.inner_text_decoded(parser),
+        }
+    }
+
     /// Returns the outer HTML of this node
     pub fn outer_html<'s>(&'s self, parser: &Parser<'a>) -> Cow<'s, str> {
         match self {
@@ -652,6 +1736,83 @@ impl<'a> Node<'a> {
         }
     }
 
+    /// Writes the outer HTML of this node into `dest`, see [`HTMLTag::write_outer_html()`]
+    pub fn write_outer_html<W: Write + ?Sized>(
+        &self,
+        parser: &Parser<'a>,
+        dest: &mut W,
+    ) -> fmt::Result {
+        match self {
+            Node::Comment(bytes) | Node::Raw(bytes) => dest.write_str(&bytes.as_utf8_str()),
+            Node::Tag(t) => t.write_outer_html(parser, dest),
+        }
+    }
+
+    /// Writes the inner HTML of this node into `dest`, see [`HTMLTag::write_inner_html()`]
+    pub fn write_inner_html<W: Write + ?Sized>(
+        &self,
+        parser: &Parser<'a>,
+        dest: &mut W,
+    ) -> fmt::Result {
+        match self {
+            Node::Comment(bytes) | Node::Raw(bytes) => dest.write_str(&bytes.as_utf8_str()),
+            Node::Tag(t) => t.write_inner_html(parser, dest),
+        }
+    }
+
+    /// Writes the inner text of this node into `dest`, see [`HTMLTag::write_inner_text()`]
+    pub fn write_inner_text<W: Write + ?Sized>(
+        &self,
+        parser: &Parser<'a>,
+        dest: &mut W,
+    ) -> fmt::Result {
+        match self {
+            Node::Comment(_) => Ok(()),
+            Node::Raw(r) => dest.write_str(&r.as_utf8_str()),
+            Node::Tag(t) => t.write_inner_text(parser, dest),
+        }
+    }
+
+    /// Returns the part of the input string that this node was parsed from, or `None` if the node was created
+    /// or changed after parsing
+    ///
+    /// For text and comment nodes, this is their text. For elements, this is their outer HTML in the source
+    /// (see `HTMLTag::raw()`), which is only returned if neither the element nor anything in its subtree was changed,
+    /// so that it can be checked in time linear in the size of the subtree. Nodes whose stored text differs
+    /// from the source are not backed by it either, such as text with normalized newlines
+    /// (`ParserOptions::normalize_newlines()`) or truncated text (`ParserOptions::max_stored_text_len()`).
+    ///
+    /// # Example
+    /// ```
+    /// let mut dom = tl::parse("<p>Hello <b>world</b></p>", Default::default()).unwrap();
+    /// let b = dom.query_selector("b").unwrap().next().unwrap();
+    ///
+    /// let parser = dom.parser();
+    /// assert_eq!(dom.nodes()[0].source_bytes(parser), Some(&b"<p>Hello <b>world</b></p>"[..]));
+    /// assert_eq!(b.get(parser).unwrap().source_bytes(parser), Some(&b"<b>world</b>"[..]));
+    ///
+    /// dom.set_inner_text(b, "there").unwrap();
+    /// let parser = dom.parser();
+    /// assert_eq!(b.get(parser).unwrap().source_bytes(parser), None);
+    /// assert_eq!(dom.nodes()[0].source_bytes(parser), None);
+    /// assert!(dom.nodes()[1].is_source_backed(parser));
+    /// ```
+    pub fn source_bytes(&self, parser: &Parser<'a>) -> Option<&'a [u8]> {
+        match self {
+            Node::Raw(text) | Node::Comment(text) => {
+                let bytes = text.as_bytes_borrowed().filter(|_| !text.is_truncated())?;
+                source_offset(bytes, parser)?;
+                Some(bytes)
+            }
+            Node::Tag(tag) => tag.source_bytes(parser),
+        }
+    }
+
+    /// Checks whether this node is unchanged since it was parsed, see [`Node::source_bytes()`]
+    pub fn is_source_backed(&self, parser: &Parser<'a>) -> bool {
+        self.source_bytes(parser).is_some()
+    }
+
     /// Returns an iterator over subnodes ("children") of this HTML tag, if this is a tag
     pub fn children(&self) -> Option<Children<'a, '_>> {
         match self {
@@ -660,29 +1821,15 @@ impl<'a> Node<'a> {
         }
     }
 
-    /// Calls the given closure with each tag as parameter
+    /// Returns the handle of the first node in the subtree of this node (excluding the node itself)
+    /// for which the predicate returns `true`, see [`HTMLTag::find_node()`]
     ///
-    /// The closure must return a boolean, indicating whether it should stop iterating
-    /// Returning `true` will break the loop and return a handle to the node
-    pub fn find_node<F>(&self, parser: &Parser<'a>, f: &mut F) -> Option<NodeHandle>
+    /// Text nodes and comments have no subtree, so this always returns `None` for them.
+    pub fn find_node<F>(&self, parser: &Parser<'a>, f: F) -> Option<NodeHandle>
     where
-        F: FnMut(&Node<'a>) -> bool,
+        F: FnMut(&Node<'a>, NodeHandle) -> bool,
     {
-        if let Some(children) = self.children() {
-            for &id in children.top().iter() {
-                let node = id.get(parser).unwrap();
-
-                if f(node) {
-                    return Some(id);
-                }
-
-                let subnode = node.find_node(parser, f);
-                if subnode.is_some() {
-                    return subnode;
-                }
-            }
-        }
-        None
+        self.as_tag()?.find_node(parser, f)
     }
 
     /// Tries to coerce this node into a `HTMLTag` variant
@@ -736,4 +1883,33 @@ impl<'a> Node<'a> {
             _ => None,
         }
     }
+
+    /// Checks whether this is a text node that was cut off by the parser (see `ParserOptions::max_stored_text_len()`)
+    pub fn is_truncated(&self) -> bool {
+        self.as_raw().is_some_and(Bytes::is_truncated)
+    }
+
+    /// Copies this node so that it does not borrow from the input, keeping its handles
+    ///
+    /// The source of elements is not copied, so they are serialized from their name and attributes.
+    pub(crate) fn to_owned_node<'b>(&self) -> Node<'b> {
+        let owned = |bytes: &Bytes<'a>| bytes.rebase(&[], &[]);
+
+        match self {
+            Node::Tag(tag) => Node::Tag(HTMLTag {
+                _name: owned(&tag._name),
+                _attributes: tag._attributes.rebase(&[], &[]),
+                _children: tag._children.clone(),
+                _parent: tag._parent,
+                _raw: Bytes::new(),
+                _start_len: None,
+                _end_tag: match tag._end_tag {
+                    EndTag::Omitted => EndTag::Omitted,
+                    _ => EndTag::Generated,
+                },
+            }),
+            Node::Raw(text) => Node::Raw(owned(text)),
+            Node::Comment(text) => Node::Comment(owned(text)),
+        }
+    }
 }
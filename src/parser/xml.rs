@@ -0,0 +1,185 @@
+use super::{
+    tag::{HTMLTag, Node},
+    Parser,
+};
+use crate::{entities, errors::XmlExportError, util, Bytes};
+use std::borrow::Cow;
+
+impl<'a> HTMLTag<'a> {
+    /// Serializes this element and its subtree as well-formed XML
+    ///
+    /// Unlike `outer_html()`, the output follows the rules of XML:
+    /// - every element is closed, and void elements are self-closed (`<br/>`)
+    /// - attribute values are quoted, and boolean attributes get their name as value (`checked="checked"`)
+    /// - character references are decoded, and `&`, `<`, `>` and `"` are escaped again
+    /// - attributes are written sorted by name
+    ///
+    /// The content of raw text elements (e.g. `<script>`) is escaped, but not decoded.
+    ///
+    /// # Errors
+    /// Returns an error for constructs that XML cannot represent, such as element or attribute names
+    /// that are not valid XML names, comments containing `--` or characters that are not allowed in XML documents.
+    ///
+    /// # Example
+    /// ```
+    /// let dom = tl::parse(r#"<p class=note>Fish &amp; Chips<br><input disabled></p>"#, Default::default()).unwrap();
+    /// let parser = dom.parser();
+    /// let p = dom.nodes()[0].as_tag().unwrap();
+    ///
+    /// assert_eq!(
+    ///     p.to_xml(parser).unwrap(),
+    ///     r#"<p class="note">Fish &amp; Chips<br/><input disabled="disabled"/></p>"#
+    /// );
+    /// ```
+    pub fn to_xml(&self, parser: &Parser<'a>) -> Result<String, XmlExportError> {
+        let mut xml = String::new();
+        write_element(&mut xml, self, parser)?;
+        Ok(xml)
+    }
+}
+
+/// A pending step of [`write_element()`]
+enum XmlStep<'p, 'a> {
+    Element(&'p HTMLTag<'a>),
+    /// Text, and whether it is the content of a raw text element
+    Text(&'p Bytes<'a>, bool),
+    Comment(&'p Bytes<'a>),
+    EndTag(Cow<'p, str>),
+}
+
+/// Writes an element and its subtree, using an explicit stack so that deeply nested documents cannot overflow the stack
+fn write_element<'p, 'a>(
+    dest: &mut String,
+    tag: &'p HTMLTag<'a>,
+    parser: &'p Parser<'a>,
+) -> Result<(), XmlExportError> {
+    let mut stack = vec![XmlStep::Element(tag)];
+
+    while let Some(step) = stack.pop() {
+        let tag = match step {
+            XmlStep::Element(tag) => tag,
+            XmlStep::Text(text, true) => {
+                write_escaped(dest, &text.as_utf8_str())?;
+                continue;
+            }
+            XmlStep::Text(text, false) => {
+                write_escaped(dest, &text.as_decoded_str())?;
+                continue;
+            }
+            XmlStep::Comment(comment) => {
+                write_comment(dest, &comment.as_utf8_str())?;
+                continue;
+            }
+            XmlStep::EndTag(name) => {
+                dest.push_str("</");
+                dest.push_str(&name);
+                dest.push('>');
+                continue;
+            }
+        };
+
+        let name = tag.name().as_utf8_str();
+        if !is_name(&name) {
+            return Err(XmlExportError::InvalidElementName(name.into_owned()));
+        }
+
+        dest.push('<');
+        dest.push_str(&name);
+
+        let mut attributes = tag.attributes().iter().collect::<Vec<_>>();
+        attributes.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+
+        for (key, value) in attributes {
+            if !is_name(&key) {
+                return Err(XmlExportError::InvalidAttributeName(key.into_owned()));
+            }
+
+            dest.push(' ');
+            dest.push_str(&key);
+            dest.push_str("=\"");
+            match value {
+                Some(value) => write_escaped(dest, &entities::decode(&value))?,
+                // boolean attributes need a value in XML, which is conventionally their name
+                None => dest.push_str(&key),
+            }
+            dest.push('"');
+        }
+
+        if tag.is_void() {
+            dest.push_str("/>");
+            continue;
+        }
+
+        dest.push('>');
+
+        let is_raw_text = parser.options.is_raw_text_element(tag.name().as_bytes());
+
+        stack.push(XmlStep::EndTag(name));
+        // children are pushed in reverse, so that the first child is popped first
+        for handle in tag.children().top().as_slice().iter().rev() {
+            match handle.get(parser) {
+                Some(Node::Tag(tag)) => stack.push(XmlStep::Element(tag)),
+                Some(Node::Raw(text)) => stack.push(XmlStep::Text(text, is_raw_text)),
+                Some(Node::Comment(comment)) => stack.push(XmlStep::Comment(comment)),
+                None => {}
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes a comment, given its source including the `<!--` and `-->` delimiters
+///
+/// Other markup declarations (e.g. a misplaced `<!DOCTYPE html>`) have no equivalent in XML content and are left out.
+fn write_comment(dest: &mut String, comment: &str) -> Result<(), XmlExportError> {
+    let Some(body) = util::comment_body(comment) else {
+        return Ok(());
+    };
+
+    if body.contains("--") || body.ends_with('-') {
+        return Err(XmlExportError::InvalidComment(body.to_owned()));
+    }
+
+    if let Some(c) = body.chars().find(|&c| !is_char(c)) {
+        return Err(XmlExportError::InvalidCharacter(c));
+    }
+
+    dest.push_str("<!--");
+    dest.push_str(body);
+    dest.push_str("-->");
+    Ok(())
+}
+
+/// Writes text with `&`, `<`, `>` and `"` escaped, which makes it valid both as text content and as a quoted attribute value
+fn write_escaped(dest: &mut String, text: &str) -> Result<(), XmlExportError> {
+    for c in text.chars() {
+        match c {
+            '&' => dest.push_str("&amp;"),
+            '<' => dest.push_str("&lt;"),
+            '>' => dest.push_str("&gt;"),
+            '"' => dest.push_str("&quot;"),
+            c if is_char(c) => dest.push(c),
+            c => return Err(XmlExportError::InvalidCharacter(c)),
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks if the given character may appear in an XML document
+fn is_char(c: char) -> bool {
+    matches!(c, '\t' | '\n' | '\r') || (c >= ' ' && c != '\u{FFFE}' && c != '\u{FFFF}')
+}
+
+/// Checks if the given string is a valid XML name
+///
+/// Names must start with a letter, `_` or `:`, and may further contain digits, `-` and `.`.
+fn is_name(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    let is_name_start = |c: char| c.is_alphabetic() || c == '_' || c == ':';
+    let is_name_char = |c: char| is_name_start(c) || c.is_alphanumeric() || matches!(c, '-' | '.');
+
+    chars.next().is_some_and(is_name_start) && chars.all(is_name_char)
+}
@@ -0,0 +1,20 @@
+//! # Example
+//! ```
+//! use tl::prelude::*;
+//!
+//! let dom: VDom = parse("<p class=greeting>Hello</p>", ParserOptions::default()).unwrap();
+//! let parser: &Parser = dom.parser();
+//! let handle: NodeHandle = dom.query_selector("p.greeting").unwrap().next().unwrap();
+//! let tag: &HTMLTag = handle.get(parser).and_then(Node::as_tag).unwrap();
+//!
+//! assert_eq!(tag.inner_text(parser), "Hello");
+//! ```
+
+pub use crate::errors::ParseError;
+pub use crate::parser::{
+    Attributes, Children, HTMLTag, HTMLVersion, Node, NodeHandle, Parser, ParserOptions,
+};
+pub use crate::queryselector::{QuerySelectorIterator, Selector, SelectorOptions};
+pub use crate::{
+    parse, parse_owned, parse_query_selector, parse_shared, Bytes, VDom, VDomGuard, VDomShared,
+};
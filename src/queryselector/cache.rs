@@ -0,0 +1,59 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use super::SelectorOptions;
+use crate::NodeHandle;
+
+/// The cached results of the selectors that were evaluated with the same options
+type CachedResults = HashMap<Box<str>, Arc<[NodeHandle]>>;
+
+/// A cache of query selector results, keyed by the selector options and string
+///
+/// The cache lives in a `VDom` and must be cleared whenever the document is mutated.
+#[derive(Debug, Default)]
+pub(crate) struct QueryCache {
+    results: Mutex<HashMap<SelectorOptions, CachedResults>>,
+    /// Number of selectors that had to be evaluated because they were not cached
+    evaluations: AtomicUsize,
+}
+
+impl QueryCache {
+    /// Returns the cached results of the given selector, or evaluates and caches them using `evaluate`
+    pub fn get_or_insert_with<I>(
+        &self,
+        selector: &str,
+        options: SelectorOptions,
+        evaluate: I,
+    ) -> Arc<[NodeHandle]>
+    where
+        I: FnOnce() -> Arc<[NodeHandle]>,
+    {
+        let mut results = self.results.lock().unwrap_or_else(|e| e.into_inner());
+        let results = results.entry(options).or_default();
+
+        if let Some(handles) = results.get(selector) {
+            return handles.clone();
+        }
+
+        self.evaluations.fetch_add(1, Ordering::Relaxed);
+
+        let handles = evaluate();
+        results.insert(selector.into(), handles.clone());
+        handles
+    }
+
+    /// Removes all cached results
+    pub fn clear(&mut self) {
+        self.results
+            .get_mut()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+    }
+
+    /// Returns the number of selectors that were evaluated because they were not cached
+    #[cfg(test)]
+    pub fn evaluations(&self) -> usize {
+        self.evaluations.load(Ordering::Relaxed)
+    }
+}
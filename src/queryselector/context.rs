@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::{HTMLTag, Parser};
+
+/// A predicate that decides whether an element matches a custom pseudo-class, given its argument (if it takes one)
+type Predicate = dyn Fn(&HTMLTag<'_>, &Parser<'_>, Option<&str>) -> bool;
+
+/// A custom pseudo-class registered in a [`SelectorContext`]
+struct PseudoClass {
+    predicate: Box<Predicate>,
+    /// Whether the pseudo-class requires an argument in parentheses, e.g. `:lang-is(en)`
+    takes_argument: bool,
+}
+
+/// A registry of custom pseudo-classes, which can be used in selectors passed to `VDom::query_selector_with_context()`
+///
+/// Selectors that use custom pseudo-classes can only be parsed with a context in which they are registered,
+/// so the plain query selector functions still reject them. The predicates are called with every element
+/// that is matched against the pseudo-class, so they should be cheap.
+///
+/// # Example
+/// ```
+/// use tl::queryselector::SelectorContext;
+///
+/// let mut context = SelectorContext::new();
+/// context.register(":external", |tag, _| {
+///     tag.attributes()
+///         .get("href")
+///         .flatten()
+///         .is_some_and(|href| href.as_bytes().starts_with(b"https://"))
+/// });
+///
+/// let dom = tl::parse(r#"<a href="/home">Home</a><a href="https://example.com">Example</a>"#, Default::default()).unwrap();
+/// let parser = dom.parser();
+///
+/// let external = dom.query_selector_with_context("a:external", &context).unwrap().next().unwrap();
+/// assert_eq!(external.get(parser).unwrap().inner_text(parser), "Example");
+/// assert!(dom.query_selector("a:external").is_none());
+/// ```
+#[derive(Default)]
+pub struct SelectorContext {
+    pseudo_classes: HashMap<Box<[u8]>, PseudoClass>,
+}
+
+impl SelectorContext {
+    /// Creates an empty context
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a pseudo-class that takes no argument, such as `:external`
+    ///
+    /// The leading `:` of the name is optional. A pseudo-class that is already registered under the same name is replaced,
+    /// but built-in pseudo-classes (e.g. `:first-child`) cannot be overridden.
+    pub fn register<F>(&mut self, name: &str, predicate: F) -> &mut Self
+    where
+        F: Fn(&HTMLTag<'_>, &Parser<'_>) -> bool + 'static,
+    {
+        self.insert(
+            name,
+            PseudoClass {
+                predicate: Box::new(move |tag, parser, _| predicate(tag, parser)),
+                takes_argument: false,
+            },
+        )
+    }
+
+    /// Registers a pseudo-class that takes an argument in parentheses, such as `:lang-is(en)`
+    ///
+    /// The argument is passed to the predicate as written in the selector, with surrounding ASCII whitespace removed.
+    /// It may contain nested parentheses and quoted strings, but it is not unescaped.
+    ///
+    /// # Example
+    /// ```
+    /// use tl::queryselector::SelectorContext;
+    ///
+    /// let mut context = SelectorContext::new();
+    /// context.register_with_argument("min-words", |tag, parser, argument| {
+    ///     let min = argument.parse().unwrap_or(usize::MAX);
+    ///     tag.inner_text(parser).split_whitespace().count() >= min
+    /// });
+    ///
+    /// let dom = tl::parse("<p>One</p><p>One two three</p>", Default::default()).unwrap();
+    /// assert_eq!(dom.query_selector_with_context("p:min-words( 2 )", &context).unwrap().count(), 1);
+    /// ```
+    pub fn register_with_argument<F>(&mut self, name: &str, predicate: F) -> &mut Self
+    where
+        F: Fn(&HTMLTag<'_>, &Parser<'_>, &str) -> bool + 'static,
+    {
+        self.insert(
+            name,
+            PseudoClass {
+                predicate: Box::new(move |tag, parser, argument| {
+                    argument.is_some_and(|argument| predicate(tag, parser, argument))
+                }),
+                takes_argument: true,
+            },
+        )
+    }
+
+    /// Checks whether a pseudo-class with the given name is registered (the leading `:` is optional)
+    pub fn is_registered(&self, name: &str) -> bool {
+        self.pseudo_classes.contains_key(strip_colon(name))
+    }
+
+    fn insert(&mut self, name: &str, pseudo_class: PseudoClass) -> &mut Self {
+        self.pseudo_classes
+            .insert(strip_colon(name).into(), pseudo_class);
+        self
+    }
+
+    /// Returns whether the pseudo-class with the given name takes an argument, or `None` if it is not registered
+    pub(crate) fn takes_argument(&self, name: &[u8]) -> Option<bool> {
+        self.pseudo_classes
+            .get(name)
+            .map(|pseudo_class| pseudo_class.takes_argument)
+    }
+
+    /// Checks whether the element matches the pseudo-class with the given name, which is `false` if it is not registered
+    pub(crate) fn matches(
+        &self,
+        name: &[u8],
+        argument: Option<&str>,
+        tag: &HTMLTag<'_>,
+        parser: &Parser<'_>,
+    ) -> bool {
+        self.pseudo_classes
+            .get(name)
+            .is_some_and(|pseudo_class| (pseudo_class.predicate)(tag, parser, argument))
+    }
+}
+
+impl fmt::Debug for SelectorContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SelectorContext")
+            .field(
+                "pseudo_classes",
+                &self
+                    .pseudo_classes
+                    .keys()
+                    .map(|name| String::from_utf8_lossy(name))
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+fn strip_colon(name: &str) -> &[u8] {
+    name.strip_prefix(':').unwrap_or(name).as_bytes()
+}
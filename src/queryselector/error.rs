@@ -0,0 +1,76 @@
+use core::fmt;
+use std::error::Error;
+
+/// The kind of a [`ParseError`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ParseErrorKind {
+    /// The selector, or an entry of a selector list, is empty (e.g. the second entry of `a, `)
+    EmptySelector,
+    /// A character that cannot appear at this position (e.g. the second `>` in `div >> p`)
+    UnexpectedCharacter,
+    /// The selector ends where more input was expected (e.g. after the `>` in `div >`)
+    UnexpectedEnd,
+    /// An attribute selector is missing its closing `]` (e.g. `[href`)
+    UnterminatedAttribute,
+    /// A quoted attribute value is missing its closing quote (e.g. `[href="/a]`)
+    UnterminatedString,
+    /// A pseudo-class that is not supported, or whose argument is malformed (e.g. `:hover` or `:nth-child(x)`)
+    UnsupportedPseudoClass,
+    /// The selector depends on the siblings or the content of an element (e.g. `li + li` or `p:empty`),
+    /// so it cannot be matched while streaming a document, see [`SelectorSet`](crate::SelectorSet)
+    NotStreamable,
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        let message = match self {
+            ParseErrorKind::EmptySelector => "empty selector",
+            ParseErrorKind::UnexpectedCharacter => "unexpected character",
+            ParseErrorKind::UnexpectedEnd => "unexpected end of selector",
+            ParseErrorKind::UnterminatedAttribute => "unterminated attribute selector",
+            ParseErrorKind::UnterminatedString => "unterminated string",
+            ParseErrorKind::UnsupportedPseudoClass => "unsupported pseudo-class",
+            ParseErrorKind::NotStreamable => "selector cannot be matched while streaming",
+        };
+
+        f.write_str(message)
+    }
+}
+
+/// An error that occurred while parsing a query selector
+///
+/// # Example
+/// ```
+/// use tl::queryselector::ParseErrorKind;
+///
+/// let error = tl::parse_query_selector_checked("div >> p").unwrap_err();
+/// assert_eq!(error.kind(), ParseErrorKind::UnexpectedCharacter);
+/// assert_eq!(error.offset(), 5);
+/// assert_eq!(error.to_string(), "unexpected character at offset 5");
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ParseError {
+    pub(crate) kind: ParseErrorKind,
+    pub(crate) offset: usize,
+}
+
+impl ParseError {
+    /// Returns what went wrong
+    pub fn kind(&self) -> ParseErrorKind {
+        self.kind
+    }
+
+    /// Returns the byte offset into the selector string at which the error occurred
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "{} at offset {}", self.kind, self.offset)
+    }
+}
+
+impl Error for ParseError {}
@@ -1,16 +1,29 @@
+use std::borrow::Cow;
+use std::fmt;
 use std::marker::PhantomData;
+use std::ops::Range;
+use std::sync::Arc;
+use std::vec;
 
-use crate::{NodeHandle, Parser};
+use crate::{HTMLTag, Node, NodeHandle, Parser};
 
-use super::{iterable::QueryIterable, Selector};
+use super::{iterable::QueryIterable, Selector, SelectorContext, SelectorOptions};
 
 /// A query selector iterator that yields matching HTML nodes
 pub struct QuerySelectorIterator<'a, 'b, Q: QueryIterable<'a>> {
-    selector: Selector<'b>,
+    /// The selector, which is borrowed if it was compiled ahead of time (see `Selector::parse()`)
+    selector: Cow<'b, Selector<'b>>,
+    options: SelectorOptions,
+    /// The context that custom pseudo-classes are looked up in, if any
+    context: Option<&'b SelectorContext>,
     collection: &'b Q,
     parser: &'b Parser<'a>,
     index: usize,
     len: usize,
+    /// The ranges of indices that are visited after the current one
+    ranges: vec::IntoIter<Range<usize>>,
+    /// Precomputed results from a query cache, if any
+    cached: Option<Arc<[NodeHandle]>>,
     _a: PhantomData<&'a ()>,
 }
 
@@ -18,24 +31,135 @@ impl<'a, 'b, Q: QueryIterable<'a>> Clone for QuerySelectorIterator<'a, 'b, Q> {
     fn clone(&self) -> Self {
         Self {
             selector: self.selector.clone(),
+            options: self.options,
+            context: self.context,
             collection: self.collection,
             parser: self.parser,
             index: self.index,
             len: self.len,
+            ranges: self.ranges.clone(),
+            cached: self.cached.clone(),
             _a: PhantomData,
         }
     }
 }
 
+impl<'a, 'b, Q: QueryIterable<'a>> fmt::Debug for QuerySelectorIterator<'a, 'b, Q> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QuerySelectorIterator")
+            .field("selector", &self.selector)
+            .field("options", &self.options)
+            .field("index", &self.index)
+            .field("len", &self.len)
+            .finish_non_exhaustive()
+    }
+}
+
 impl<'a, 'b, Q: QueryIterable<'a>> QuerySelectorIterator<'a, 'b, Q> {
     /// Creates a new query selector iterator
     pub fn new(selector: Selector<'b>, parser: &'b Parser<'a>, collection: &'b Q) -> Self {
+        Self::from_selector(Cow::Owned(selector), parser, collection)
+    }
+
+    /// Creates a new query selector iterator from an owned or borrowed selector
+    pub(crate) fn from_selector(
+        selector: Cow<'b, Selector<'b>>,
+        parser: &'b Parser<'a>,
+        collection: &'b Q,
+    ) -> Self {
         Self {
             selector,
+            options: SelectorOptions::default(),
+            context: None,
             collection,
             index: 0,
             len: collection.len(parser),
+            ranges: Vec::new().into_iter(),
+            parser,
+            cached: None,
+            _a: PhantomData,
+        }
+    }
+
+    /// Creates a new query selector iterator that only visits the nodes at the given indices of the collection
+    ///
+    /// The ranges must be sorted and must not overlap, so that nodes are visited in order and at most once.
+    pub(crate) fn with_ranges(
+        selector: Cow<'b, Selector<'b>>,
+        parser: &'b Parser<'a>,
+        collection: &'b Q,
+        ranges: Vec<Range<usize>>,
+    ) -> Self {
+        let len = collection.len(parser);
+        let ranges = ranges
+            .into_iter()
+            .map(|range| range.start..range.end.min(len))
+            .collect::<Vec<_>>();
+
+        Self {
+            selector,
+            options: SelectorOptions::default(),
+            context: None,
+            collection,
+            index: 0,
+            len: 0,
+            ranges: ranges.into_iter(),
+            parser,
+            cached: None,
+            _a: PhantomData,
+        }
+    }
+
+    /// Returns an iterator that yields the matching elements themselves instead of their handles
+    ///
+    /// Matching nodes that are not elements are skipped. Use the handles if the elements need to be mutated.
+    ///
+    /// # Example
+    /// ```
+    /// let dom = tl::parse(r#"<a href="/a">A</a><a href="/b">B</a>"#, Default::default()).unwrap();
+    ///
+    /// let hrefs = dom
+    ///     .query_selector("a")
+    ///     .unwrap()
+    ///     .tags()
+    ///     .filter_map(|tag| tag.attributes().get("href").flatten())
+    ///     .map(|href| href.as_utf8_str())
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(hrefs, ["/a", "/b"]);
+    /// ```
+    pub fn tags(self) -> QuerySelectorTags<'a, 'b, Q> {
+        QuerySelectorTags { inner: self }
+    }
+
+    /// Sets the options used to match nodes against the selector
+    pub(crate) fn with_options(mut self, options: SelectorOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Sets the context that custom pseudo-classes are looked up in
+    pub(crate) fn with_context(mut self, context: &'b SelectorContext) -> Self {
+        self.context = Some(context);
+        self
+    }
+
+    /// Creates a query selector iterator that yields precomputed results
+    pub(crate) fn from_cached(
+        selector: Cow<'b, Selector<'b>>,
+        parser: &'b Parser<'a>,
+        collection: &'b Q,
+        handles: Arc<[NodeHandle]>,
+    ) -> Self {
+        Self {
+            selector,
+            options: SelectorOptions::default(),
+            context: None,
+            collection,
+            index: 0,
+            len: handles.len(),
+            ranges: Vec::new().into_iter(),
             parser,
+            cached: Some(handles),
             _a: PhantomData,
         }
     }
@@ -45,18 +169,97 @@ impl<'a, 'b, Q: QueryIterable<'a>> Iterator for QuerySelectorIterator<'a, 'b, Q>
     type Item = NodeHandle;
 
     fn next(&mut self) -> Option<Self::Item> {
-        while self.index < self.len {
-            let node = self.collection.get(self.parser, self.index);
+        if let Some(cached) = &self.cached {
+            let handle = cached.get(self.index).copied()?;
             self.index += 1;
-            if let Some((node, id)) = node {
-                let matches = self.selector.matches(node, self.parser);
+            return Some(handle);
+        }
+
+        loop {
+            while self.index < self.len {
+                let node = self.collection.get(self.parser, self.index);
+                self.index += 1;
+
+                #[cfg(test)]
+                visits::record();
 
-                if matches {
-                    return Some(id);
+                if let Some((node, id)) = node {
+                    let matches = match self.context {
+                        Some(context) => self.selector.matches_with_context(
+                            node,
+                            self.parser,
+                            self.options,
+                            context,
+                        ),
+                        None => self.selector.matches_with(node, self.parser, self.options),
+                    };
+
+                    if matches {
+                        return Some(id);
+                    }
                 }
             }
+
+            let range = self.ranges.next()?;
+            self.index = range.start;
+            self.len = range.end;
         }
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        if self.cached.is_some() {
+            // cached results can be skipped without looking at them
+            self.index = self.index.saturating_add(n);
+        } else {
+            for _ in 0..n {
+                self.next()?;
+            }
+        }
+
+        self.next()
+    }
+
+    fn count(self) -> usize {
+        match &self.cached {
+            Some(cached) => cached.len().saturating_sub(self.index),
+            None => self.fold(0, |count, _| count + 1),
+        }
+    }
+}
+
+/// A query selector iterator that yields matching HTML elements, obtained by calling [`QuerySelectorIterator::tags()`]
+#[derive(Debug, Clone)]
+pub struct QuerySelectorTags<'a, 'b, Q: QueryIterable<'a>> {
+    inner: QuerySelectorIterator<'a, 'b, Q>,
+}
+
+impl<'a, 'b, Q: QueryIterable<'a>> Iterator for QuerySelectorTags<'a, 'b, Q> {
+    type Item = &'b HTMLTag<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let parser = self.inner.parser;
+
+        self.inner
+            .by_ref()
+            .find_map(|handle| handle.get(parser).and_then(Node::as_tag))
+    }
+}
+
+/// Instrumentation for counting the nodes visited by query selector iterators on the current thread
+#[cfg(test)]
+pub(crate) mod visits {
+    use std::cell::Cell;
+
+    thread_local! {
+        static VISITS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    pub fn record() {
+        VISITS.with(|v| v.set(v.get() + 1));
+    }
 
-        None
+    /// Returns the number of visited nodes since the last call and resets the counter
+    pub fn take() -> usize {
+        VISITS.with(|v| v.replace(0))
     }
 }
@@ -26,17 +26,16 @@ impl<'a> QueryIterable<'a> for VDom<'a> {
         parser: &'b Parser<'a>,
         index: usize,
     ) -> Option<(&'b Node<'a>, NodeHandle)> {
-        // In a VDom, the index is equal to the node's id
-        // and as such, we can recreate a `NodeHandle` from that ID
+        // In a VDom, the index is the position of the node in the node table
         parser
             .tags
             .get(index)
-            .map(|node| (node, NodeHandle::new(index as u32)))
+            .map(|node| (node, parser.handle_at(index)))
     }
 
     #[inline]
     fn len(&self, _parser: &Parser) -> usize {
-        self.parser().tags.len()
+        self.parser().document_len()
     }
 
     #[inline]
@@ -54,20 +53,17 @@ impl<'a> QueryIterable<'a> for HTMLTag<'a> {
         parser: &'b Parser<'a>,
         index: usize,
     ) -> Option<(&'b Node<'a>, NodeHandle)> {
-        // Add `index` to the starting ID to get the ID of the node we need
-        let index = self.start().map(|h| h as usize + index)?;
-        let handle = NodeHandle::new(index as u32);
+        // Add `index` to the position of the first child to get the position of the node we need
+        let index = parser.position_of(*self._children.get(0)?)? + index;
         let node = parser.tags.get(index)?;
-        Some((node, handle))
+        Some((node, parser.handle_at(index)))
     }
 
     #[inline]
     fn len(&self, parser: &Parser) -> usize {
-        if let Some((start, end)) = self.children().boundaries(parser) {
-            ((end - start) + 1) as usize
-        } else {
-            0
-        }
+        self.children()
+            .positions(parser)
+            .map_or(0, |range| range.len())
     }
 
     #[inline]
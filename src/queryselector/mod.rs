@@ -1,3 +1,6 @@
+mod cache;
+mod context;
+mod error;
 /// Query selector iterator
 pub mod iter;
 /// Query selector iterable
@@ -7,6 +10,9 @@ pub mod parser;
 /// Query selector
 pub mod selector;
 
+pub(crate) use cache::QueryCache;
+pub use context::SelectorContext;
+pub use error::*;
 pub use iter::*;
 pub use parser::*;
 pub use selector::*;
@@ -1,10 +1,14 @@
+use std::{borrow::Cow, ops::Range};
+
 use crate::{stream::Stream, util};
 
-use super::Selector;
+use super::{CaseSensitivity, ParseError, ParseErrorKind, Selector, SelectorContext};
 
 /// A query selector parser
 pub struct Parser<'a> {
     stream: Stream<'a, u8>,
+    /// The custom pseudo-classes that are accepted in addition to the built-in ones
+    context: Option<&'a SelectorContext>,
 }
 
 impl<'a> Parser<'a> {
@@ -12,29 +16,113 @@ impl<'a> Parser<'a> {
     pub fn new(input: &'a [u8]) -> Self {
         Self {
             stream: Stream::new(input),
+            context: None,
         }
     }
 
+    /// Accepts the custom pseudo-classes registered in the given context
+    ///
+    /// # Example
+    /// ```
+    /// use tl::queryselector::{ParseErrorKind, Parser, SelectorContext};
+    ///
+    /// let mut context = SelectorContext::new();
+    /// context.register("visible", |tag, _| tag.attributes().get("hidden").is_none());
+    ///
+    /// assert!(Parser::new(b"p:visible").with_context(&context).selector().is_ok());
+    ///
+    /// let error = Parser::new(b"p:visible").selector().unwrap_err();
+    /// assert_eq!(error.kind(), ParseErrorKind::UnsupportedPseudoClass);
+    /// ```
+    pub fn with_context(mut self, context: &'a SelectorContext) -> Self {
+        self.context = Some(context);
+        self
+    }
+
+    /// Creates an error of the given kind at the current position
+    fn error(&self, kind: ParseErrorKind) -> ParseError {
+        self.error_at(kind, self.stream.idx)
+    }
+
+    /// Creates an error of the given kind at the given position
+    fn error_at(&self, kind: ParseErrorKind, offset: usize) -> ParseError {
+        ParseError { kind, offset }
+    }
+
+    /// Creates an error for the current character, or for the end of the input if there is none
+    fn unexpected(&self) -> ParseError {
+        match self.stream.is_eof() {
+            true => self.error(ParseErrorKind::UnexpectedEnd),
+            false => self.error(ParseErrorKind::UnexpectedCharacter),
+        }
+    }
+
+    /// Skips the given character, or returns an error if it is not the current one
+    fn expect(&mut self, c: u8) -> Result<(), ParseError> {
+        match self.stream.expect_and_skip(c) {
+            Some(_) => Ok(()),
+            None => Err(self.unexpected()),
+        }
+    }
+
+    /// Skips ASCII whitespace (space, tab, LF, FF and CR), and returns whether there was any
     fn skip_whitespaces(&mut self) -> bool {
-        let has_whitespace = self.stream.expect_and_skip_cond(b' ');
-        while !self.stream.is_eof() {
-            if self.stream.expect_and_skip(b' ').is_none() {
+        !self.read_while(util::is_ascii_whitespace).is_empty()
+    }
+
+    /// Reads a name (e.g. of a tag, class or attribute), which ends at a `:` or `+` since those start a pseudo-class or a combinator
+    ///
+    /// Names may contain escapes (e.g. `md\:flex` or `\31 0`), in which case the name is unescaped into an owned buffer.
+    fn read_identifier(&mut self) -> Result<Cow<'a, [u8]>, ParseError> {
+        self.read_escaped_while(is_name)
+            .ok_or_else(|| self.unexpected())
+    }
+
+    /// Reads the name of an attribute, which unlike other names may contain a `:` (e.g. `xlink:href`)
+    fn read_attribute_name(&mut self) -> Result<Cow<'a, [u8]>, ParseError> {
+        self.read_escaped_while(util::is_ident)
+            .ok_or_else(|| self.unexpected())
+    }
+
+    /// Reads as long as the given condition holds or there is an escape, and returns the unescaped value
+    ///
+    /// Returns `None` if the input ends right after a `\`.
+    fn read_escaped_while(&mut self, cond: impl Fn(u8) -> bool) -> Option<Cow<'a, [u8]>> {
+        let start = self.stream.idx;
+        // only allocated once the first escape is found
+        let mut unescaped: Option<Vec<u8>> = None;
+
+        while let Some(c) = self.stream.current_cpy() {
+            if c == b'\\' {
+                let unescaped = unescaped
+                    .get_or_insert_with(|| self.stream.slice(start, self.stream.idx).to_vec());
+                self.stream.advance();
+                self.read_escape(unescaped)?;
+                continue;
+            }
+
+            if !cond(c) {
                 break;
             }
+
+            if let Some(unescaped) = &mut unescaped {
+                unescaped.push(c);
+            }
+            self.stream.advance();
         }
-        has_whitespace
+
+        Some(match unescaped {
+            Some(unescaped) => Cow::Owned(unescaped),
+            None => Cow::Borrowed(self.stream.slice(start, self.stream.idx)),
+        })
     }
 
-    fn read_identifier(&mut self) -> &'a [u8] {
+    /// Reads as long as the given condition holds
+    fn read_while(&mut self, cond: impl Fn(u8) -> bool) -> &'a [u8] {
         let start = self.stream.idx;
 
-        while !self.stream.is_eof() {
-            let is_ident = self.stream.current().copied().map_or(false, util::is_ident);
-            if !is_ident {
-                break;
-            } else {
-                self.stream.advance();
-            }
+        while self.stream.current_cpy().is_some_and(&cond) {
+            self.stream.advance();
         }
 
         self.stream.slice(start, self.stream.idx)
@@ -44,133 +132,470 @@ impl<'a> Parser<'a> {
     ///
     /// A query selector list is a list of complex selectors separated by
     /// commas. This is the entire query selector string.
-    pub fn selector(&mut self) -> Option<Selector<'a>> {
-        let mut left = self.parse_complex_selector(false)?;
+    ///
+    /// Returns an error if the input is not a valid selector list, which includes
+    /// empty list entries such as a trailing comma (`a, b,`).
+    pub fn selector(&mut self) -> Result<Selector<'a>, ParseError> {
+        let selector = self.parse_selector_list()?;
+
+        if !self.stream.is_eof() {
+            return Err(self.unexpected());
+        }
+
+        Ok(selector)
+    }
+
+    /// Parses a query selector list, skipping entries that are not valid complex selectors
+    ///
+    /// This is the forgiving selector list of the specification, as used by `:is()`.
+    /// Returns the valid entries combined with `Selector::Or` (`None` if there are none),
+    /// and the positions of the skipped entries in the input. Empty entries are skipped without being reported.
+    pub fn selector_forgiving(&mut self) -> (Option<Selector<'a>>, Vec<Range<usize>>) {
+        let mut selector = None;
+        let mut skipped = Vec::new();
+
+        loop {
+            let start = self.stream.idx;
+            let entry = self
+                .parse_complex_selector()
+                .ok()
+                .filter(|_| matches!(self.stream.current_cpy(), None | Some(b',')));
+
+            match entry {
+                Some(right) => {
+                    selector = Some(match selector {
+                        Some(left) => Selector::Or(Box::new(left), Box::new(right)),
+                        None => right,
+                    });
+                }
+                None => {
+                    self.stream.idx = start;
+                    self.skip_list_entry();
+
+                    let entry = self.stream.slice(start, self.stream.idx);
+                    if !entry.iter().all(|&c| util::is_ascii_whitespace(c)) {
+                        skipped.push(start..self.stream.idx);
+                    }
+                }
+            }
+
+            if !self.stream.expect_and_skip_cond(b',') {
+                return (selector, skipped);
+            }
+        }
+    }
+
+    /// Skips to the comma that ends the current entry of a selector list, or to the end of the input
+    ///
+    /// Commas inside of parentheses, brackets and quoted strings do not end the entry.
+    fn skip_list_entry(&mut self) {
+        let mut depth = 0usize;
+        let mut quote = None;
+
+        while let Some(c) = self.stream.current_cpy() {
+            match (c, quote) {
+                // skip the escaped character as well
+                (b'\\', _) => self.stream.advance(),
+                (c, Some(q)) if c == q => quote = None,
+                (_, Some(_)) => {}
+                (b'"' | b'\'', None) => quote = Some(c),
+                (b'(' | b'[', None) => depth += 1,
+                (b')' | b']', None) => depth = depth.saturating_sub(1),
+                (b',', None) if depth == 0 => return,
+                _ => {}
+            }
 
-        while let Some(right) = self.parse_complex_selector(false) {
+            self.stream.advance();
+        }
+
+        // an escape at the very end may have skipped past it
+        self.stream.idx = self.stream.idx.min(self.stream.len());
+    }
+
+    /// Parses a list of complex selectors separated by commas, up to the first token that cannot continue the list
+    ///
+    /// Commas are only handled at this level, so every entry of the list is a complete complex selector:
+    /// `a b, c` is `(a b), c` and never `a (b, c)`.
+    fn parse_selector_list(&mut self) -> Result<Selector<'a>, ParseError> {
+        let mut left = self.parse_list_entry()?;
+
+        while self.stream.expect_and_skip_cond(b',') {
+            let right = self.parse_list_entry()?;
             left = Selector::Or(Box::new(left), Box::new(right));
         }
 
-        Some(left)
+        Ok(left)
+    }
+
+    /// Parses an entry of a selector list, which must not be empty
+    fn parse_list_entry(&mut self) -> Result<Selector<'a>, ParseError> {
+        self.skip_whitespaces();
+
+        match self.stream.current_cpy() {
+            None | Some(b',') => Err(self.error(ParseErrorKind::EmptySelector)),
+            Some(_) => self.parse_complex_selector(),
+        }
     }
 
     /// Parses a complex query selector
     ///
     /// A complex selector is series of compound selectors separated by combinators.
-    fn parse_complex_selector(&mut self, nested: bool) -> Option<Selector<'a>> {
+    /// It ends at a comma, the end of the input or any other token that cannot continue it, none of which is consumed.
+    fn parse_complex_selector(&mut self) -> Result<Selector<'a>, ParseError> {
         let mut left = self.parse_compound_selector()?;
-        let has_whitespaces = self.skip_whitespaces();
 
-        if nested {
-            return Some(left);
-        }
+        loop {
+            let has_whitespaces = self.skip_whitespaces();
 
-        while let Some(tok) = self.stream.current_cpy() {
-            match tok {
-                b',' => {
-                    self.stream.advance();
-                    return Some(left);
-                }
-                b'>' => {
+            match self.stream.current_cpy() {
+                None | Some(b',') => return Ok(left),
+                Some(combinator @ (b'>' | b'+' | b'~')) => {
                     self.stream.advance();
-                    let right = self.parse_complex_selector(true)?;
-                    left = Selector::Parent(Box::new(left), Box::new(right));
+                    let right = Box::new(self.parse_compound_selector()?);
+                    let left_box = Box::new(left);
+                    left = match combinator {
+                        b'>' => Selector::Parent(left_box, right),
+                        b'+' => Selector::NextSibling(left_box, right),
+                        _ => Selector::SubsequentSibling(left_box, right),
+                    };
                 }
-                _ if has_whitespaces => {
-                    let right = self.parse_complex_selector(true)?;
+                Some(_) if has_whitespaces => {
+                    let right = self.parse_compound_selector()?;
                     left = Selector::Descendant(Box::new(left), Box::new(right));
                 }
-                _ if !has_whitespaces => {
-                    let right = self.parse_complex_selector(true)?;
-                    left = Selector::And(Box::new(left), Box::new(right));
-                }
-                _ => unreachable!(),
+                // anything else ends the selector, and is rejected by `selector()`
+                Some(_) => return Ok(left),
             }
         }
-
-        Some(left)
     }
 
     /// Parses a compound query selector
     ///
     /// A compound selector is a series of simple selectors.
-    fn parse_compound_selector(&mut self) -> Option<Selector<'a>> {
+    fn parse_compound_selector(&mut self) -> Result<Selector<'a>, ParseError> {
         let mut result = None;
 
         self.skip_whitespaces();
-        while let Some(right) = match self.stream.current_cpy() {
+        while let Some(right) = self.parse_simple_selector()? {
+            if let Some(left) = result {
+                result = Some(Selector::And(Box::new(left), Box::new(right)));
+            } else {
+                result = Some(right);
+            }
+        }
+
+        result.ok_or_else(|| self.unexpected())
+    }
+
+    /// Parses a simple selector, such as a tag name, class or attribute selector
+    ///
+    /// Returns `None` if there is no simple selector at the current position.
+    fn parse_simple_selector(&mut self) -> Result<Option<Selector<'a>>, ParseError> {
+        let selector = match self.stream.current_cpy() {
             Some(b'#') => {
                 self.stream.advance();
-                let id = self.read_identifier();
-                Some(Selector::Id(id))
+                Selector::Id(self.read_identifier()?)
             }
             Some(b'.') => {
                 self.stream.advance();
-                let class = self.read_identifier();
-                Some(Selector::Class(class))
+                Selector::Class(self.read_identifier()?)
             }
             Some(b'*') => {
                 self.stream.advance();
-                Some(Selector::All)
+                Selector::All
             }
-            Some(b'[') => {
-                self.stream.advance();
-                self.parse_attribute()
-            }
-            Some(tok) if util::is_ident(tok) => {
-                let tag = self.read_identifier();
-                Some(Selector::Tag(tag))
+            Some(b'[') => self.parse_attribute()?,
+            Some(b':') => self.parse_pseudo_class()?,
+            Some(tok) if is_name(tok) || tok == b'\\' => Selector::Tag(self.read_identifier()?),
+            _ => return Ok(None),
+        };
+
+        Ok(Some(selector))
+    }
+
+    /// Parses a pseudo-class, such as `:first-child`
+    ///
+    /// Unsupported or malformed pseudo-classes are reported at the position of the `:`.
+    /// Names that are not built in are looked up in the context, if any.
+    fn parse_pseudo_class(&mut self) -> Result<Selector<'a>, ParseError> {
+        let start = self.stream.idx;
+        self.stream.advance();
+
+        let name = self.read_while(is_name);
+        let selector = match name {
+            b"first-child" => Some(Selector::FirstChild),
+            b"last-child" => Some(Selector::LastChild),
+            b"only-child" => Some(Selector::OnlyChild),
+            b"empty" => Some(Selector::Empty),
+            b"root" => Some(Selector::Root),
+            b"nth-child" => self.parse_nth_child(),
+            b"contains" => Some(self.parse_contains()?),
+            _ => self.parse_custom_pseudo_class(name),
+        };
+
+        selector.ok_or_else(|| self.error_at(ParseErrorKind::UnsupportedPseudoClass, start))
+    }
+
+    /// Parses the parenthesized argument of `:nth-child()`
+    fn parse_nth_child(&mut self) -> Option<Selector<'a>> {
+        self.stream.expect_and_skip(b'(')?;
+        self.skip_whitespaces();
+        let (a, b) = self.parse_nth()?;
+        self.skip_whitespaces();
+        self.stream.expect_and_skip(b')')?;
+
+        Some(Selector::NthChild { a, b })
+    }
+
+    /// Parses the parenthesized argument of `:contains()`, which is a quoted string or an identifier like the value of an attribute selector
+    fn parse_contains(&mut self) -> Result<Selector<'a>, ParseError> {
+        self.expect(b'(')?;
+        self.skip_whitespaces();
+        if self.stream.current_cpy() == Some(b')') {
+            return Err(self.unexpected());
+        }
+        let text = self.read_attribute_value()?;
+        self.skip_whitespaces();
+        self.expect(b')')?;
+
+        Ok(Selector::Contains(text))
+    }
+
+    /// Parses a pseudo-class registered in the context, whose name has already been read
+    fn parse_custom_pseudo_class(&mut self, name: &'a [u8]) -> Option<Selector<'a>> {
+        let argument = match self.context?.takes_argument(name)? {
+            true => Some(Cow::Borrowed(self.read_raw_argument()?)),
+            false => None,
+        };
+
+        Some(Selector::Custom {
+            name: Cow::Borrowed(name),
+            argument,
+        })
+    }
+
+    /// Reads a parenthesized argument up to the matching `)`, and returns it without the parentheses and surrounding whitespace
+    ///
+    /// Parentheses inside of the argument must be balanced, except within quoted strings.
+    fn read_raw_argument(&mut self) -> Option<&'a [u8]> {
+        self.stream.expect_and_skip(b'(')?;
+        let start = self.stream.idx;
+        let mut depth = 0usize;
+        let mut quote = None;
+
+        loop {
+            let c = self.stream.current_cpy()?;
+
+            match (c, quote) {
+                // skip the escaped character as well
+                (b'\\', _) => self.stream.advance(),
+                (c, Some(q)) if c == q => quote = None,
+                (_, Some(_)) => {}
+                (b'"' | b'\'', None) => quote = Some(c),
+                (b'(', None) => depth += 1,
+                (b')', None) if depth == 0 => break,
+                (b')', None) => depth -= 1,
+                _ => {}
             }
-            _ => None,
-        } {
-            if let Some(left) = result {
-                result = Some(Selector::And(Box::new(left), Box::new(right)));
-            } else {
-                result = Some(right);
+
+            self.stream.advance();
+        }
+
+        let argument = self.stream.slice(start, self.stream.idx);
+        self.stream.advance();
+
+        Some(util::trim_ascii_whitespace_bytes(argument))
+    }
+
+    /// Parses the `an+b` micro-syntax (e.g. `2n+1`, `-n + 3` or `4`), or one of the keywords `odd` and `even`
+    fn parse_nth(&mut self) -> Option<(i32, i32)> {
+        let start = self.stream.idx;
+        let keyword = self.read_while(is_name);
+
+        if keyword.eq_ignore_ascii_case(b"odd") {
+            return Some((2, 1));
+        } else if keyword.eq_ignore_ascii_case(b"even") {
+            return Some((2, 0));
+        }
+
+        self.stream.idx = start;
+
+        let sign = self.read_sign().unwrap_or(1);
+        let digits = self.read_integer();
+
+        if self.stream.expect_oneof_and_skip(b"nN").is_none() {
+            return Some((0, sign * digits?));
+        }
+
+        let a = sign * digits.unwrap_or(1);
+
+        self.skip_whitespaces();
+        let Some(sign) = self.read_sign() else {
+            return Some((a, 0));
+        };
+        self.skip_whitespaces();
+
+        Some((a, sign * self.read_integer()?))
+    }
+
+    /// Reads a `+` or `-` sign, and returns it as `1` or `-1`
+    fn read_sign(&mut self) -> Option<i32> {
+        match self.stream.expect_oneof_and_skip(b"+-")? {
+            b'-' => Some(-1),
+            _ => Some(1),
+        }
+    }
+
+    /// Reads an unsigned decimal integer, returning `None` if there are no digits or the value overflows
+    fn read_integer(&mut self) -> Option<i32> {
+        let start = self.stream.idx;
+
+        while self
+            .stream
+            .current_cpy()
+            .is_some_and(|c| c.is_ascii_digit())
+        {
+            self.stream.advance();
+        }
+
+        std::str::from_utf8(self.stream.slice(start, self.stream.idx))
+            .ok()?
+            .parse()
+            .ok()
+    }
+
+    /// Reads the value of an attribute selector, which is either an identifier or a quoted string
+    ///
+    /// Quoted strings may contain any character except for the quote itself, and identifiers may contain
+    /// non-ASCII characters. Both may contain escapes (e.g. `\"` or `\26 `), in which case the value is unescaped into an owned buffer.
+    fn read_attribute_value(&mut self) -> Result<Cow<'a, [u8]>, ParseError> {
+        let quote_start = self.stream.idx;
+        let quote = self.stream.expect_oneof_and_skip(b"\"'");
+        let unterminated = |this: &Self| match quote {
+            Some(_) => this.error_at(ParseErrorKind::UnterminatedString, quote_start),
+            None => this.unexpected(),
+        };
+        let value = self
+            .read_escaped_while(|c| match quote {
+                Some(quote) => c != quote,
+                None => is_unquoted_value(c),
+            })
+            .ok_or_else(|| unterminated(self))?;
+
+        if let Some(quote) = quote {
+            self.stream
+                .expect_and_skip(quote)
+                .ok_or_else(|| unterminated(self))?;
+        }
+
+        Ok(value)
+    }
+
+    /// Reads an escape after a `\` and appends the escaped character to `dest`
+    ///
+    /// An escape is either up to six hex digits followed by an optional whitespace (e.g. `\26 ` for `&`),
+    /// or any other character, which is taken literally (e.g. `\"`). Escaped line breaks are removed.
+    fn read_escape(&mut self, dest: &mut Vec<u8>) -> Option<()> {
+        let start = self.stream.idx;
+
+        while self.stream.idx - start < 6
+            && self
+                .stream
+                .current_cpy()
+                .is_some_and(|c| c.is_ascii_hexdigit())
+        {
+            self.stream.advance();
+        }
+
+        let hex = self.stream.slice(start, self.stream.idx);
+        if hex.is_empty() {
+            let c = self.stream.current_cpy()?;
+            if c != b'\n' {
+                dest.push(c);
             }
+            self.stream.advance();
+            return Some(());
+        }
+
+        if self
+            .stream
+            .current_cpy()
+            .is_some_and(util::is_ascii_whitespace)
+        {
+            self.stream.advance();
         }
 
-        result
+        let c = std::str::from_utf8(hex)
+            .ok()
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+            .and_then(char::from_u32)
+            .filter(|&c| c != '\0')
+            .unwrap_or(char::REPLACEMENT_CHARACTER);
+
+        dest.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+        Some(())
+    }
+
+    /// Reads the optional `i` or `s` flag after the value of an attribute selector
+    fn read_attribute_flag(&mut self) -> CaseSensitivity {
+        self.skip_whitespaces();
+        let case = match self.stream.expect_oneof_and_skip(b"iIsS") {
+            Some(b'i' | b'I') => CaseSensitivity::Insensitive,
+            _ => CaseSensitivity::Sensitive,
+        };
+        self.skip_whitespaces();
+
+        case
     }
 
-    fn parse_attribute(&mut self) -> Option<Selector<'a>> {
-        let attribute = self.read_identifier();
+    /// Parses an attribute selector, such as `[href]` or `[href^="https:" i]`
+    fn parse_attribute(&mut self) -> Result<Selector<'a>, ParseError> {
+        let start = self.stream.idx;
+        self.stream.advance();
+
+        let attribute = self.read_attribute_name()?;
         let ty = match self.stream.current_cpy() {
-            Some(b']') => {
-                self.stream.advance();
-                Selector::Attribute(attribute)
-            }
+            Some(b']') => Selector::Attribute(attribute),
             Some(b'=') => {
                 self.stream.advance();
-                let quote = self.stream.expect_oneof_and_skip(&[b'"', b'\'']);
-                let value = self.read_identifier();
-                if let Some(quote) = quote {
-                    // Only require the given quote if the value starts with a quote
-                    self.stream.expect_and_skip(quote)?;
-                }
-                self.stream.expect_and_skip(b']')?;
-                Selector::AttributeValue(attribute, value)
+                let value = self.read_attribute_value()?;
+                let case = self.read_attribute_flag();
+                Selector::AttributeValue(attribute, value, case)
             }
-            Some(c @ b'~' | c @ b'^' | c @ b'$' | c @ b'*') => {
+            Some(c @ b'~' | c @ b'^' | c @ b'$' | c @ b'*' | c @ b'|') => {
                 self.stream.advance();
-                self.stream.expect_and_skip(b'=')?;
-                let quote = self.stream.expect_oneof_and_skip(&[b'"', b'\'']);
-                let value = self.read_identifier();
-                if let Some(quote) = quote {
-                    // Only require the given quote if the value starts with a quote
-                    self.stream.expect_and_skip(quote)?;
-                }
-                self.stream.expect_and_skip(b']')?;
+                self.expect(b'=')?;
+                let value = self.read_attribute_value()?;
+                let case = self.read_attribute_flag();
                 match c {
-                    b'~' => Selector::AttributeValueWhitespacedContains(attribute, value),
-                    b'^' => Selector::AttributeValueStartsWith(attribute, value),
-                    b'$' => Selector::AttributeValueEndsWith(attribute, value),
-                    b'*' => Selector::AttributeValueSubstring(attribute, value),
+                    b'~' => Selector::AttributeValueWhitespacedContains(attribute, value, case),
+                    b'^' => Selector::AttributeValueStartsWith(attribute, value, case),
+                    b'$' => Selector::AttributeValueEndsWith(attribute, value, case),
+                    b'*' => Selector::AttributeValueSubstring(attribute, value, case),
+                    b'|' => Selector::AttributeValueHyphenPrefix(attribute, value, case),
                     _ => unreachable!(),
                 }
             }
-            _ => return None,
+            None => return Err(self.error_at(ParseErrorKind::UnterminatedAttribute, start)),
+            Some(_) => return Err(self.unexpected()),
         };
-        Some(ty)
+
+        match self.stream.expect_and_skip(b']') {
+            Some(_) => Ok(ty),
+            None if self.stream.is_eof() => {
+                Err(self.error_at(ParseErrorKind::UnterminatedAttribute, start))
+            }
+            None => Err(self.unexpected()),
+        }
     }
 }
+
+/// Checks if the given byte can be part of an unquoted attribute value, which includes all bytes of non-ASCII characters
+fn is_unquoted_value(c: u8) -> bool {
+    util::is_ident(c) || !c.is_ascii()
+}
+
+/// Checks if the given byte can be part of a name in a selector
+fn is_name(c: u8) -> bool {
+    c != b':' && c != b'+' && util::is_ident(c)
+}
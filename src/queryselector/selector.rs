@@ -1,14 +1,112 @@
-use crate::{Node, Parser};
+use std::borrow::Cow;
+
+use super::{ParseError, SelectorContext};
+use crate::{simd, util, Bytes, Node, NodeHandle, Parser};
+
+/// Options that control how selectors are matched against nodes
+///
+/// # Example
+/// ```
+/// use tl::queryselector::SelectorOptions;
+///
+/// let dom = tl::parse(r#"<a title="Tom &amp; Jerry">"#, Default::default()).unwrap();
+/// let options = SelectorOptions { compare_decoded: true };
+///
+/// assert_eq!(dom.query_selector(r#"[title="Tom & Jerry"]"#).unwrap().count(), 0);
+/// assert_eq!(dom.query_selector_with_options(r#"[title="Tom & Jerry"]"#, options).unwrap().count(), 1);
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct SelectorOptions {
+    /// Whether attribute selectors and `:contains()` compare against the decoded value of an attribute or text (e.g. `a&b` for `a&amp;b`)
+    /// instead of the raw bytes in the source, which is the default.
+    pub compare_decoded: bool,
+}
+
+impl SelectorOptions {
+    /// Returns the value of an attribute or text node to compare against, according to these options
+    fn compared_value<'v>(&self, value: &'v Bytes<'_>) -> Cow<'v, str> {
+        match self.compare_decoded {
+            true => value.as_decoded_str(),
+            false => value.as_utf8_str(),
+        }
+    }
+}
+
+/// How an attribute selector compares attribute values, set by the `i` and `s` flags (e.g. `[type="text" i]`)
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum CaseSensitivity {
+    /// Values must match exactly, which is the default and can be requested explicitly with the `s` flag
+    #[default]
+    Sensitive,
+    /// ASCII letters match regardless of their case: the `i` flag
+    Insensitive,
+}
+
+impl CaseSensitivity {
+    fn equals(self, attr: &str, value: &str) -> bool {
+        match self {
+            Self::Sensitive => attr == value,
+            Self::Insensitive => simd::eq_case_insensitive(attr.as_bytes(), value.as_bytes()),
+        }
+    }
+
+    fn starts_with(self, attr: &str, value: &str) -> bool {
+        match self {
+            Self::Sensitive => attr.starts_with(value),
+            Self::Insensitive => attr
+                .as_bytes()
+                .get(..value.len())
+                .is_some_and(|prefix| simd::eq_case_insensitive(prefix, value.as_bytes())),
+        }
+    }
+
+    fn ends_with(self, attr: &str, value: &str) -> bool {
+        match self {
+            Self::Sensitive => attr.ends_with(value),
+            Self::Insensitive => attr.len().checked_sub(value.len()).is_some_and(|start| {
+                simd::eq_case_insensitive(&attr.as_bytes()[start..], value.as_bytes())
+            }),
+        }
+    }
+
+    fn contains(self, attr: &str, value: &str) -> bool {
+        match self {
+            Self::Sensitive => attr.contains(value),
+            Self::Insensitive => {
+                value.is_empty()
+                    || attr
+                        .as_bytes()
+                        .windows(value.len())
+                        .any(|window| simd::eq_case_insensitive(window, value.as_bytes()))
+            }
+        }
+    }
+}
+
+/// An ID or class that can be looked up in the tables built by `ParserOptions::track_ids()` and `ParserOptions::track_classes()`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum LookupKey<'s> {
+    /// An ID: #foo
+    Id(&'s [u8]),
+    /// A class: .foo
+    Class(&'s [u8]),
+}
 
 /// A single query selector node
 #[derive(Debug, Clone)]
 pub enum Selector<'a> {
     /// Tag selector: foo
-    Tag(&'a [u8]),
+    ///
+    /// Tag names are compared ASCII case-insensitively, unless `ParserOptions::case_sensitive_tag_names()` is set.
+    Tag(Cow<'a, [u8]>),
     /// ID selector: #foo
-    Id(&'a [u8]),
+    ///
+    /// Character references in ids are decoded before they are compared, unless `ParserOptions::raw_id_and_class_matching()` is set.
+    Id(Cow<'a, [u8]>),
     /// Class selector: .foo
-    Class(&'a [u8]),
+    ///
+    /// Like with ids, character references in class names are decoded before they are compared.
+    Class(Cow<'a, [u8]>),
     /// All selector: *
     All,
     /// And combinator: .foo.bar
@@ -19,70 +117,304 @@ pub enum Selector<'a> {
     Descendant(Box<Selector<'a>>, Box<Selector<'a>>),
     /// Parent combinator: .foo > .bar
     Parent(Box<Selector<'a>>, Box<Selector<'a>>),
+    /// Next sibling combinator: .foo + .bar
+    NextSibling(Box<Selector<'a>>, Box<Selector<'a>>),
+    /// Subsequent sibling combinator: .foo ~ .bar
+    SubsequentSibling(Box<Selector<'a>>, Box<Selector<'a>>),
     /// Attribute: [foo]
-    Attribute(&'a [u8]),
+    Attribute(Cow<'a, [u8]>),
     /// Attribute with value: [foo=bar]
-    AttributeValue(&'a [u8], &'a [u8]),
+    AttributeValue(Cow<'a, [u8]>, Cow<'a, [u8]>, CaseSensitivity),
     /// Attribute with whitespace-separated list of values that contains a value: [foo~=bar]
-    AttributeValueWhitespacedContains(&'a [u8], &'a [u8]),
+    AttributeValueWhitespacedContains(Cow<'a, [u8]>, Cow<'a, [u8]>, CaseSensitivity),
     /// Attribute with value that starts with: [foo^=bar]
-    AttributeValueStartsWith(&'a [u8], &'a [u8]),
+    AttributeValueStartsWith(Cow<'a, [u8]>, Cow<'a, [u8]>, CaseSensitivity),
     /// Attribute with value that ends with: [foo$=bar]
-    AttributeValueEndsWith(&'a [u8], &'a [u8]),
+    AttributeValueEndsWith(Cow<'a, [u8]>, Cow<'a, [u8]>, CaseSensitivity),
     /// Attribute with value that contains: [foo*=bar]
-    AttributeValueSubstring(&'a [u8], &'a [u8]),
+    AttributeValueSubstring(Cow<'a, [u8]>, Cow<'a, [u8]>, CaseSensitivity),
+    /// Attribute with value that is equal to or starts with a value followed by a hyphen: [foo|=bar]
+    AttributeValueHyphenPrefix(Cow<'a, [u8]>, Cow<'a, [u8]>, CaseSensitivity),
+    /// First element among its siblings: :first-child
+    FirstChild,
+    /// Last element among its siblings: :last-child
+    LastChild,
+    /// Element without element siblings: :only-child
+    OnlyChild,
+    /// Element whose 1-based index among its siblings is `a*n + b` for some `n >= 0`: :nth-child(an+b)
+    NthChild {
+        /// The step size
+        a: i32,
+        /// The offset
+        b: i32,
+    },
+    /// Element without element children and without text other than whitespace: :empty
+    ///
+    /// Comments and text that only consists of ASCII whitespace are ignored, so `<td> </td>` is empty too.
+    Empty,
+    /// The topmost `<html>` element of the document: :root
+    Root,
+    /// Element whose text (see `HTMLTag::inner_text()`) contains a string: :contains("foo")
+    ///
+    /// This is not part of the CSS standard, but is supported by jQuery and many scraping tools.
+    /// Matching has to look at the text of every descendant, so a selector that starts with `:contains()`
+    /// is expensive on large documents; combining it with a more specific selector (e.g. `button:contains("Add")`) avoids most of that work.
+    Contains(Cow<'a, [u8]>),
+    /// A custom pseudo-class registered in a [`SelectorContext`]: :external or :lang-is(en)
+    ///
+    /// This can only be parsed with a context in which the pseudo-class is registered, and never matches without it.
+    Custom {
+        /// The name of the pseudo-class, without the leading `:`
+        name: Cow<'a, [u8]>,
+        /// The argument in parentheses as written in the selector, if the pseudo-class takes one
+        argument: Option<Cow<'a, [u8]>>,
+    },
+}
+
+/// A compiled query selector that owns its data, obtained by calling [`Selector::parse()`]
+///
+/// Unlike [`Selector`], it does not borrow from the selector string, so it can be parsed once,
+/// stored in a `static` and used with any number of documents on any thread.
+#[derive(Debug, Clone)]
+pub struct OwnedSelector(Selector<'static>);
+
+impl OwnedSelector {
+    /// Returns the parsed selector
+    pub fn selector(&self) -> &Selector<'static> {
+        &self.0
+    }
+}
+
+impl From<Selector<'_>> for OwnedSelector {
+    fn from(selector: Selector<'_>) -> Self {
+        Self(selector.into_owned())
+    }
+}
+
+impl Selector<'static> {
+    /// Parses a query selector into an [`OwnedSelector`], which can be reused without parsing it again
+    ///
+    /// # Example
+    /// ```
+    /// use std::sync::OnceLock;
+    /// use tl::queryselector::{OwnedSelector, Selector};
+    ///
+    /// static PRICE: OnceLock<OwnedSelector> = OnceLock::new();
+    /// let price = PRICE.get_or_init(|| Selector::parse("div.product > span.price").unwrap());
+    ///
+    /// for input in ["<div class=product><span class=price>1</span></div>", "<span class=price>2</span>"] {
+    ///     let dom = tl::parse(input, Default::default()).unwrap();
+    ///     let count = dom.query_selector_with(price).count();
+    ///     assert_eq!(count, if input.starts_with("<div") { 1 } else { 0 });
+    /// }
+    /// ```
+    pub fn parse(selector: &str) -> Result<OwnedSelector, ParseError> {
+        crate::parse_query_selector_checked(selector).map(OwnedSelector::from)
+    }
 }
 
 impl<'a> Selector<'a> {
-    /// Checks if the given node matches this selector
+    /// Converts this selector into one that owns all of its data
+    pub fn into_owned(self) -> Selector<'static> {
+        fn owned(bytes: Cow<'_, [u8]>) -> Cow<'static, [u8]> {
+            Cow::Owned(bytes.into_owned())
+        }
+
+        let boxed = |selector: Box<Selector<'a>>| Box::new(selector.into_owned());
+
+        match self {
+            Self::Tag(tag) => Selector::Tag(owned(tag)),
+            Self::Id(id) => Selector::Id(owned(id)),
+            Self::Class(class) => Selector::Class(owned(class)),
+            Self::All => Selector::All,
+            Self::And(a, b) => Selector::And(boxed(a), boxed(b)),
+            Self::Or(a, b) => Selector::Or(boxed(a), boxed(b)),
+            Self::Descendant(a, b) => Selector::Descendant(boxed(a), boxed(b)),
+            Self::Parent(a, b) => Selector::Parent(boxed(a), boxed(b)),
+            Self::NextSibling(a, b) => Selector::NextSibling(boxed(a), boxed(b)),
+            Self::SubsequentSibling(a, b) => Selector::SubsequentSibling(boxed(a), boxed(b)),
+            Self::Attribute(attribute) => Selector::Attribute(owned(attribute)),
+            Self::AttributeValue(attribute, value, case) => {
+                Selector::AttributeValue(owned(attribute), owned(value), case)
+            }
+            Self::AttributeValueWhitespacedContains(attribute, value, case) => {
+                Selector::AttributeValueWhitespacedContains(owned(attribute), owned(value), case)
+            }
+            Self::AttributeValueStartsWith(attribute, value, case) => {
+                Selector::AttributeValueStartsWith(owned(attribute), owned(value), case)
+            }
+            Self::AttributeValueEndsWith(attribute, value, case) => {
+                Selector::AttributeValueEndsWith(owned(attribute), owned(value), case)
+            }
+            Self::AttributeValueSubstring(attribute, value, case) => {
+                Selector::AttributeValueSubstring(owned(attribute), owned(value), case)
+            }
+            Self::AttributeValueHyphenPrefix(attribute, value, case) => {
+                Selector::AttributeValueHyphenPrefix(owned(attribute), owned(value), case)
+            }
+            Self::FirstChild => Selector::FirstChild,
+            Self::LastChild => Selector::LastChild,
+            Self::OnlyChild => Selector::OnlyChild,
+            Self::NthChild { a, b } => Selector::NthChild { a, b },
+            Self::Empty => Selector::Empty,
+            Self::Root => Selector::Root,
+            Self::Contains(text) => Selector::Contains(owned(text)),
+            Self::Custom { name, argument } => Selector::Custom {
+                name: owned(name),
+                argument: argument.map(owned),
+            },
+        }
+    }
+
+    /// Returns the ID or class that every match of this selector must either have, or have an ancestor with
+    ///
+    /// For example, this is `#main` for `#main`, `div#main` and `#main > p .item`, and `.list` for `.list li`.
+    pub(crate) fn root_key(&self) -> Option<LookupKey<'_>> {
+        match self {
+            Self::Descendant(a, _) | Self::Parent(a, _) => a.root_key(),
+            _ => self.compound_key(|s| s.root_key()),
+        }
+    }
+
+    /// Returns the ID or class that every match of this selector must have
+    ///
+    /// For example, this is `.item` for `.item`, `li.item` and `#main > p .item`.
+    pub(crate) fn subject_key(&self) -> Option<LookupKey<'_>> {
+        match self {
+            Self::Descendant(_, b)
+            | Self::Parent(_, b)
+            | Self::NextSibling(_, b)
+            | Self::SubsequentSibling(_, b) => b.subject_key(),
+            _ => self.compound_key(|s| s.subject_key()),
+        }
+    }
+
+    /// Returns the ID or class of a compound selector, preferring IDs because they are usually more selective
+    fn compound_key<'s, F>(&'s self, key: F) -> Option<LookupKey<'s>>
+    where
+        F: Fn(&'s Self) -> Option<LookupKey<'s>>,
+    {
+        match self {
+            Self::Id(id) => Some(LookupKey::Id(id)),
+            Self::Class(class) => Some(LookupKey::Class(class)),
+            Self::And(a, b) => match (key(a), key(b)) {
+                (Some(LookupKey::Class(_)), Some(id @ LookupKey::Id(_))) => Some(id),
+                (a, b) => a.or(b),
+            },
+            _ => None,
+        }
+    }
+
+    /// Checks if the given node matches this selector, comparing raw attribute values
     pub fn matches<'b>(&self, node: &Node<'b>, parser: &Parser<'b>) -> bool {
+        self.matches_with(node, parser, SelectorOptions::default())
+    }
+
+    /// Checks if the given node matches this selector using the given options
+    pub fn matches_with<'b>(
+        &self,
+        node: &Node<'b>,
+        parser: &Parser<'b>,
+        options: SelectorOptions,
+    ) -> bool {
+        self.matches_in(node, parser, options, None)
+    }
+
+    /// Checks if the given node matches this selector using the given options, looking up custom pseudo-classes in the given context
+    pub fn matches_with_context<'b>(
+        &self,
+        node: &Node<'b>,
+        parser: &Parser<'b>,
+        options: SelectorOptions,
+        context: &SelectorContext,
+    ) -> bool {
+        self.matches_in(node, parser, options, Some(context))
+    }
+
+    fn matches_in<'b>(
+        &self,
+        node: &Node<'b>,
+        parser: &Parser<'b>,
+        options: SelectorOptions,
+        context: Option<&SelectorContext>,
+    ) -> bool {
         match self {
-            Self::Tag(tag) => node.as_tag().map_or(false, |t| t._name.as_bytes().eq(*tag)),
-            Self::Id(id) => node
-                .as_tag()
-                .map_or(false, |t| t._attributes.id == Some((*id).into())),
-            Self::Class(class) => node
-                .as_tag()
-                .map_or(false, |t| t._attributes.is_class_member(*class)),
-            Self::And(a, b) => a.matches(node, parser) && b.matches(node, parser),
-            Self::Or(a, b) => a.matches(node, parser) || b.matches(node, parser),
+            Self::Tag(tag) => node.as_tag().is_some_and(|t| {
+                let name = t._name.as_bytes();
+                match parser.options.is_case_sensitive_tag_names() {
+                    true => name == &**tag,
+                    false => simd::eq_case_insensitive(name, tag),
+                }
+            }),
+            Self::Id(id) => node.as_tag().is_some_and(|t| {
+                t._attributes
+                    .matches_id(id, !parser.options.is_raw_id_and_class_matching())
+            }),
+            Self::Class(class) => node.as_tag().is_some_and(|t| {
+                t._attributes
+                    .matches_class(class, !parser.options.is_raw_id_and_class_matching())
+            }),
+            Self::And(a, b) => {
+                a.matches_in(node, parser, options, context)
+                    && b.matches_in(node, parser, options, context)
+            }
+            Self::Or(a, b) => {
+                a.matches_in(node, parser, options, context)
+                    || b.matches_in(node, parser, options, context)
+            }
             Self::All => true,
             Self::Attribute(attribute) => node
                 .as_tag()
-                .map_or(false, |t| t._attributes.get(*attribute).is_some()),
-            Self::AttributeValue(attribute, value) => {
-                check_attribute(node, attribute, value, |attr, value| attr == value)
+                .is_some_and(|t| t._attributes.get(&**attribute).is_some()),
+            Self::AttributeValue(attribute, value, case) => {
+                check_attribute(node, attribute, value, options, |attr, value| {
+                    case.equals(attr, value)
+                })
+            }
+            Self::AttributeValueEndsWith(attribute, value, case) => {
+                check_attribute(node, attribute, value, options, |attr, value| {
+                    case.ends_with(attr, value)
+                })
             }
-            Self::AttributeValueEndsWith(attribute, value) => {
-                check_attribute(node, attribute, value, |attr, value| attr.ends_with(value))
+            Self::AttributeValueStartsWith(attribute, value, case) => {
+                check_attribute(node, attribute, value, options, |attr, value| {
+                    case.starts_with(attr, value)
+                })
             }
-            Self::AttributeValueStartsWith(attribute, value) => {
-                check_attribute(node, attribute, value, |attr, value| {
-                    attr.starts_with(value)
+            Self::AttributeValueSubstring(attribute, value, case) => {
+                check_attribute(node, attribute, value, options, |attr, value| {
+                    case.contains(attr, value)
                 })
             }
-            Self::AttributeValueSubstring(attribute, value) => {
-                check_attribute(node, attribute, value, |attr, value| attr.contains(value))
+            Self::AttributeValueHyphenPrefix(attribute, value, case) => {
+                check_attribute(node, attribute, value, options, |attr, value| {
+                    case.equals(attr, value)
+                        || (case.starts_with(attr, value)
+                            && attr.as_bytes().get(value.len()) == Some(&b'-'))
+                })
             }
-            Self::AttributeValueWhitespacedContains(attribute, value) => {
-                check_attribute(node, attribute, value, |attr, value| {
-                    attr.split_whitespace().any(|x| x == value)
+            Self::AttributeValueWhitespacedContains(attribute, value, case) => {
+                check_attribute(node, attribute, value, options, |attr, value| {
+                    util::split_ascii_whitespace(attr).any(|x| case.equals(x, value))
                 })
             }
             Self::Parent(a, b) => node
                 .as_tag()
                 .and_then(|t| {
-                    Some(a.matches(t._parent?.get(parser)?, parser) && b.matches(node, parser))
+                    Some(
+                        a.matches_in(t._parent?.get(parser)?, parser, options, context)
+                            && b.matches_in(node, parser, options, context),
+                    )
                 })
                 .unwrap_or(false),
             Self::Descendant(a, b) => {
-                if !b.matches(node, parser) {
+                if !b.matches_in(node, parser, options, context) {
                     return false;
                 }
 
                 let mut curr = node;
                 while let Some(ancestor) = curr.as_tag().and_then(|t| t._parent?.get(parser)) {
-                    if a.matches(ancestor, parser) {
+                    if a.matches_in(ancestor, parser, options, context) {
                         return true;
                     }
 
@@ -91,20 +423,129 @@ impl<'a> Selector<'a> {
 
                 false
             }
+            Self::NextSibling(a, b) => {
+                b.matches_in(node, parser, options, context)
+                    && previous_element_siblings(node, parser)
+                        .and_then(|mut previous| previous.next())
+                        .is_some_and(|previous| a.matches_in(previous, parser, options, context))
+            }
+            Self::SubsequentSibling(a, b) => {
+                b.matches_in(node, parser, options, context)
+                    && previous_element_siblings(node, parser).is_some_and(|mut previous| {
+                        previous.any(|previous| a.matches_in(previous, parser, options, context))
+                    })
+            }
+            Self::FirstChild => element_siblings(node, parser)
+                .and_then(|mut siblings| siblings.next())
+                .is_some_and(|first| std::ptr::eq(first, node)),
+            Self::LastChild => element_siblings(node, parser)
+                .and_then(|mut siblings| siblings.next_back())
+                .is_some_and(|last| std::ptr::eq(last, node)),
+            Self::OnlyChild => element_siblings(node, parser).is_some_and(|mut siblings| {
+                siblings
+                    .next()
+                    .is_some_and(|first| std::ptr::eq(first, node))
+                    && siblings.next().is_none()
+            }),
+            Self::NthChild { a, b } => element_siblings(node, parser)
+                .and_then(|mut siblings| siblings.position(|sibling| std::ptr::eq(sibling, node)))
+                .is_some_and(|index| is_nth(*a, *b, index + 1)),
+            Self::Empty => node.as_tag().is_some_and(|tag| {
+                tag._children
+                    .iter()
+                    .filter_map(|handle| handle.get(parser))
+                    .all(|child| match child {
+                        Node::Tag(_) => false,
+                        Node::Raw(text) => text
+                            .as_bytes()
+                            .iter()
+                            .all(|&c| util::is_ascii_whitespace(c)),
+                        Node::Comment(_) => true,
+                    })
+            }),
+            Self::Root => node.as_tag().is_some_and(|tag| {
+                tag._parent.is_none()
+                    && tag._name.as_bytes().eq_ignore_ascii_case(b"html")
+                    // detached elements have no parent either
+                    && parser
+                        .ast
+                        .iter()
+                        .any(|handle| handle.get(parser).is_some_and(|top| std::ptr::eq(top, node)))
+            }),
+            Self::Contains(text) => node.as_tag().is_some_and(|tag| {
+                tag.contains_text(parser, text, |text| options.compared_value(text))
+            }),
+            Self::Custom { name, argument } => node.as_tag().is_some_and(|tag| {
+                let argument = argument.as_deref().map(String::from_utf8_lossy);
+                context
+                    .is_some_and(|context| context.matches(name, argument.as_deref(), tag, parser))
+            }),
         }
     }
 }
 
-fn check_attribute<F>(node: &Node, attribute: &[u8], value: &[u8], callback: F) -> bool
+/// Returns an iterator over the element siblings of a tag (including itself)
+///
+/// The siblings of a tag without a parent are the topmost nodes of the document, and text and comment nodes are not included.
+/// Nodes borrowed from `parser` can be found among their siblings by comparing addresses.
+fn element_siblings<'p, 'b>(
+    node: &Node<'b>,
+    parser: &'p Parser<'b>,
+) -> Option<impl DoubleEndedIterator<Item = &'p Node<'b>> + 'p> {
+    let tag = node.as_tag()?;
+
+    let siblings: &[NodeHandle] = match tag._parent {
+        Some(parent) => parent.get(parser)?.as_tag()?._children.as_slice(),
+        None => &parser.ast,
+    };
+
+    Some(
+        siblings
+            .iter()
+            .filter_map(|handle| handle.get(parser))
+            .filter(|sibling| sibling.as_tag().is_some()),
+    )
+}
+
+/// Returns an iterator over the element siblings that precede a tag, starting with the closest one
+fn previous_element_siblings<'p, 'b>(
+    node: &Node<'b>,
+    parser: &'p Parser<'b>,
+) -> Option<impl Iterator<Item = &'p Node<'b>> + 'p> {
+    let mut siblings = element_siblings(node, parser)?.rev();
+    siblings.find(|sibling| std::ptr::eq(*sibling, node))?;
+    Some(siblings)
+}
+
+/// Checks if the 1-based `index` is `a*n + b` for some integer `n >= 0`
+fn is_nth(a: i32, b: i32, index: usize) -> bool {
+    let (a, b) = (i64::from(a), i64::from(b));
+    let Ok(index) = i64::try_from(index) else {
+        return false;
+    };
+
+    match a {
+        0 => index == b,
+        a => (index - b) % a == 0 && (index - b) / a >= 0,
+    }
+}
+
+fn check_attribute<F>(
+    node: &Node,
+    attribute: &[u8],
+    value: &[u8],
+    options: SelectorOptions,
+    callback: F,
+) -> bool
 where
     F: Fn(&str, &str) -> bool,
 {
-    node.as_tag().map_or(false, |t| {
-        t._attributes
-            .get(attribute)
-            .flatten()
-            .map_or(false, |attr| {
-                callback(&attr.as_utf8_str(), &String::from_utf8_lossy(value))
-            })
+    node.as_tag().is_some_and(|t| {
+        t._attributes.get(attribute).flatten().is_some_and(|attr| {
+            callback(
+                &options.compared_value(attr),
+                &String::from_utf8_lossy(value),
+            )
+        })
     })
 }
@@ -69,3 +69,18 @@ pub fn matches_case_insensitive<const N: usize>(haystack: &[u8], needle: [u8; N]
     }
     mask
 }
+
+/// Checks if the ASCII characters in `a` and `b` are equal, ignoring case
+///
+/// Unlike `matches_case_insensitive()`, both sides are lowercased and the length is only known at runtime.
+pub fn eq_case_insensitive(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    let mut mask = true;
+    for (&a, &b) in a.iter().zip(b) {
+        mask &= util::to_lower(a) == util::to_lower(b);
+    }
+    mask
+}
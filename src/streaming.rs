@@ -0,0 +1,635 @@
+use std::io::{self, Read};
+
+use crate::parser::StartTag;
+use crate::queryselector::{ParseError, ParseErrorKind, Selector};
+use crate::{
+    simd, tag_info, util, Attributes, Bytes, HTMLTag, Node, OwnedElement, Parser, ParserOptions,
+};
+
+/// The number of bytes that are read from the input at once
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// A list of selectors that are matched against a document while it is read, see [`stream_select()`]
+///
+/// Only selectors that can be decided when the start tag of an element is read are supported: tag names, ids, classes,
+/// attribute selectors and `:root`, combined with the descendant (` `) and child (`>`) combinators and in selector lists.
+/// Selectors that depend on the siblings of an element (such as `h1 + p` or `:first-child`) or on its content
+/// (such as `:empty` or `:contains()`) are rejected.
+///
+/// # Example
+/// ```
+/// use tl::queryselector::ParseErrorKind;
+///
+/// let mut selectors = tl::SelectorSet::new();
+/// assert_eq!(selectors.add("ul > li").unwrap(), 0);
+/// assert_eq!(selectors.add("a[href^=https]").unwrap(), 1);
+///
+/// let error = selectors.add("li:last-child").unwrap_err();
+/// assert_eq!(error.kind(), ParseErrorKind::NotStreamable);
+/// assert_eq!(selectors.len(), 2);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SelectorSet {
+    selectors: Vec<Selector<'static>>,
+}
+
+impl SelectorSet {
+    /// Creates an empty set
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses a selector and adds it to this set, and returns its index, which identifies it in [`MatchEvent::selector()`]
+    ///
+    /// # Errors
+    /// Returns an error if the selector cannot be parsed, or if it cannot be matched while streaming,
+    /// in which case the kind is `ParseErrorKind::NotStreamable` and the offset is 0.
+    pub fn add(&mut self, selector: &str) -> Result<usize, ParseError> {
+        let selector = crate::parse_query_selector_checked(selector)?.into_owned();
+
+        if !is_streamable(&selector) {
+            return Err(ParseError {
+                kind: ParseErrorKind::NotStreamable,
+                offset: 0,
+            });
+        }
+
+        self.selectors.push(selector);
+        Ok(self.selectors.len() - 1)
+    }
+
+    /// Returns the number of selectors in this set
+    pub fn len(&self) -> usize {
+        self.selectors.len()
+    }
+
+    /// Checks whether this set contains no selectors
+    pub fn is_empty(&self) -> bool {
+        self.selectors.is_empty()
+    }
+}
+
+/// Checks whether the given selector only depends on an element and its ancestors
+fn is_streamable(selector: &Selector<'_>) -> bool {
+    match selector {
+        Selector::And(a, b)
+        | Selector::Or(a, b)
+        | Selector::Descendant(a, b)
+        | Selector::Parent(a, b) => is_streamable(a) && is_streamable(b),
+        Selector::Tag(_)
+        | Selector::Id(_)
+        | Selector::Class(_)
+        | Selector::All
+        | Selector::Attribute(_)
+        | Selector::AttributeValue(..)
+        | Selector::AttributeValueWhitespacedContains(..)
+        | Selector::AttributeValueStartsWith(..)
+        | Selector::AttributeValueEndsWith(..)
+        | Selector::AttributeValueSubstring(..)
+        | Selector::AttributeValueHyphenPrefix(..)
+        | Selector::Root => true,
+        _ => false,
+    }
+}
+
+/// An element that matched a selector of a [`SelectorSet`], which is passed to the callback of [`stream_select()`] when the element ends
+///
+/// The event borrows from the state of the stream, so it only lives until the callback returns.
+/// Use `to_owned_snapshot()` to keep a copy of the element.
+#[derive(Debug, Copy, Clone)]
+pub struct MatchEvent<'e> {
+    selector: usize,
+    index: usize,
+    /// The open elements, of which the matched element is the one at `depth`
+    open: &'e Parser<'static>,
+    depth: usize,
+    text: &'e str,
+}
+
+impl<'e> MatchEvent<'e> {
+    /// Returns the index of the selector that matched, as returned by `SelectorSet::add()`
+    ///
+    /// If an element matches several selectors, there is an event for each of them, in the order of their indices.
+    pub fn selector(&self) -> usize {
+        self.selector
+    }
+
+    /// Returns the index of the element among all elements of the document, in the order of their start tags
+    ///
+    /// Events are emitted when elements end, so an element that contains another matched element is reported after it.
+    /// Sorting the events by this index restores document order.
+    pub fn element_index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the name of the element
+    pub fn name(&self) -> &'e Bytes<'static> {
+        self.tag().name()
+    }
+
+    /// Returns the attributes of the element
+    pub fn attributes(&self) -> &'e Attributes<'static> {
+        self.tag().attributes()
+    }
+
+    /// Returns the names of the elements that contain the element, from its parent up to the outermost element
+    pub fn ancestor_names(&self) -> impl Iterator<Item = &'e Bytes<'static>> + 'e {
+        self.open.tags[..self.depth]
+            .iter()
+            .rev()
+            .filter_map(|node| Some(node.as_tag()?.name()))
+    }
+
+    /// Returns the text of the element, which is the same as the one returned by `HTMLTag::inner_text()`
+    /// if the document was parsed into a DOM
+    pub fn inner_text(&self) -> &'e str {
+        self.text
+    }
+
+    /// Creates an owned snapshot of the name, attributes and text of the element, see `HTMLTag::to_owned_snapshot()`
+    pub fn to_owned_snapshot(&self) -> OwnedElement {
+        let mut attributes = self
+            .attributes()
+            .iter()
+            .map(|(key, value)| (key.into_owned(), value.map(|x| x.into_owned())))
+            .collect::<Vec<_>>();
+        attributes.sort();
+
+        OwnedElement {
+            name: self.name().as_utf8_str().into_owned(),
+            attributes,
+            text: self.text.to_owned(),
+            children: Vec::new(),
+        }
+    }
+
+    fn tag(&self) -> &'e HTMLTag<'static> {
+        self.open.tags[self.depth].as_tag().unwrap()
+    }
+}
+
+/// Matches the selectors of a [`SelectorSet`] against the HTML document read from `reader`, without building a DOM
+///
+/// `on_match` is called for every element that matches a selector when the element ends, with its name, attributes,
+/// the names of its ancestors and its text. Only the open elements and the text of the open elements that matched are kept,
+/// along with a buffer for the input that holds at most a chunk of 64 KiB and the tag or end tag that is currently being read.
+/// The memory used is therefore bounded by the nesting depth and the size of the matched elements rather than by the size of the document.
+///
+/// The document is parsed like by `tl::parse()` with the default options, so the same elements are matched and their text
+/// is the same as their `inner_text()`. Text that is not valid UTF-8 is converted lossily.
+///
+/// # Errors
+/// Returns the errors of `reader`, except for `io::ErrorKind::Interrupted`, after which reading is retried.
+///
+/// # Example
+/// ```
+/// let mut selectors = tl::SelectorSet::new();
+/// selectors.add("ul.links > li a").unwrap();
+///
+/// let input = r#"<ul class=links><li><a href="/a">A</a><li><a href="/b">B <b>!</b></a></ul><a href="/c">C</a>"#;
+///
+/// let mut links = Vec::new();
+/// tl::stream_select(input.as_bytes(), &selectors, |event| {
+///     let href = event.attributes().get("href").flatten().unwrap();
+///     let ancestors = event.ancestor_names().map(|name| name.as_utf8_str().into_owned()).collect::<Vec<_>>();
+///     links.push((href.as_utf8_str().into_owned(), event.inner_text().to_owned(), ancestors));
+/// })
+/// .unwrap();
+///
+/// assert_eq!(links[0], ("/a".to_owned(), "A".to_owned(), vec!["li".to_owned(), "ul".to_owned()]));
+/// assert_eq!(links[1].1, "B !");
+/// assert_eq!(links.len(), 2);
+/// ```
+pub fn stream_select<R, F>(reader: R, selectors: &SelectorSet, on_match: F) -> io::Result<()>
+where
+    R: Read,
+    F: FnMut(MatchEvent<'_>),
+{
+    SelectStream {
+        reader,
+        selectors,
+        on_match,
+        buffer: Vec::new(),
+        start: 0,
+        end: 0,
+        eof: false,
+        content: Content::Markup,
+        open: Parser::new("", ParserOptions::default()),
+        pending: Vec::new(),
+        elements: 0,
+    }
+    .run()
+}
+
+/// How the input at the current position is read
+#[derive(Debug)]
+enum Content {
+    /// Text and markup
+    Markup,
+    /// The text of the raw text element with the given name up to its end tag, see `ParserOptions::raw_text_elements()`
+    RawText(Box<[u8]>),
+    /// The rest of a comment, of which the last two bytes that were read are not consumed yet
+    Comment,
+    /// The text of a `<plaintext>` element, which extends to the end of the input
+    Plaintext,
+}
+
+/// An open element that matched at least one selector, and the text that has been read in it so far
+#[derive(Debug)]
+struct PendingMatch {
+    depth: usize,
+    index: usize,
+    selectors: Vec<usize>,
+    text: Vec<u8>,
+}
+
+/// The state of `stream_select()`
+struct SelectStream<'s, R, F> {
+    reader: R,
+    selectors: &'s SelectorSet,
+    on_match: F,
+    /// The input that has been read, of which `buffer[start..end]` has not been consumed yet
+    ///
+    /// The rest of the buffer is only kept so that it does not need to be initialized again for the next read.
+    buffer: Vec<u8>,
+    start: usize,
+    end: usize,
+    /// Whether the reader has reached the end of the input
+    eof: bool,
+    content: Content,
+    /// The open elements, stored in a parser so that selectors can be matched against them
+    ///
+    /// The element at depth `n` is at position `n` of the node table, and its handle is at index `n` of the stack.
+    open: Parser<'static>,
+    /// The open elements that matched, innermost last
+    pending: Vec<PendingMatch>,
+    /// The number of elements whose start tags have been read
+    elements: usize,
+}
+
+impl<R, F> SelectStream<'_, R, F>
+where
+    R: Read,
+    F: FnMut(MatchEvent<'_>),
+{
+    fn run(mut self) -> io::Result<()> {
+        loop {
+            let buffer = std::mem::take(&mut self.buffer);
+            let consumed = self.step(&buffer[self.start..self.end]);
+            self.buffer = buffer;
+
+            match consumed {
+                Some(len) => self.start += len,
+                None if self.eof => break,
+                None => self.fill()?,
+            }
+        }
+
+        while !self.open.stack.is_empty() {
+            self.close_element();
+        }
+
+        Ok(())
+    }
+
+    /// Discards the consumed input and appends the next chunk of the input to the buffer
+    fn fill(&mut self) -> io::Result<()> {
+        self.buffer.copy_within(self.start..self.end, 0);
+        self.end -= self.start;
+        self.start = 0;
+
+        if self.buffer.len() < self.end + CHUNK_SIZE {
+            self.buffer.resize(self.end + CHUNK_SIZE, 0);
+        }
+
+        let read = loop {
+            match self
+                .reader
+                .read(&mut self.buffer[self.end..self.end + CHUNK_SIZE])
+            {
+                Ok(read) => break read,
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+                Err(error) => return Err(error),
+            }
+        };
+
+        self.end += read;
+        self.eof = read == 0;
+
+        #[cfg(test)]
+        buffered::record(self.buffer.len());
+
+        Ok(())
+    }
+
+    /// Reads the next token (or part of a text) at the start of the given unconsumed input, and returns its length
+    ///
+    /// Returns `None` if more input is needed, which never happens at the end of the input unless `data` is empty.
+    fn step(&mut self, data: &[u8]) -> Option<usize> {
+        if data.is_empty() {
+            return None;
+        }
+
+        match std::mem::replace(&mut self.content, Content::Markup) {
+            Content::Markup => self.step_markup(data),
+            Content::RawText(name) => match raw_text_end(data, &name, self.eof) {
+                RawTextEnd::EndTag(start, end) => {
+                    self.push_text(&data[..start]);
+                    self.close_element();
+                    Some(end)
+                }
+                RawTextEnd::NotBefore(end) => {
+                    self.push_text(&data[..end]);
+                    self.content = Content::RawText(name);
+                    (end > 0).then_some(end)
+                }
+            },
+            Content::Comment => match comment_end(data) {
+                Some(end) => Some(end),
+                None if self.eof => Some(data.len()),
+                None => {
+                    self.content = Content::Comment;
+                    (data.len() > 2).then(|| data.len() - 2)
+                }
+            },
+            Content::Plaintext => {
+                self.push_text(data);
+                self.content = Content::Plaintext;
+                Some(data.len())
+            }
+        }
+    }
+
+    /// Reads the text or the tag at the start of the given input
+    fn step_markup(&mut self, data: &[u8]) -> Option<usize> {
+        if data[0] != b'<' {
+            let end = simd::find(data, b'<').unwrap_or(data.len());
+            self.push_text(&data[..end]);
+            return Some(end);
+        }
+
+        let after_whitespace = data[1..]
+            .iter()
+            .position(|&c| !util::is_ascii_whitespace(c))
+            .map(|offset| offset + 1);
+
+        match after_whitespace {
+            // a `<` at the end of the input is text
+            None if self.eof => {
+                self.push_text(data);
+                Some(data.len())
+            }
+            None => None,
+            Some(slash) if data[slash] == b'/' => self.end_tag(data, slash),
+            Some(bang) if data[bang] == b'!' => self.markup_declaration(data, bang),
+            Some(_) => self.start_tag(data),
+        }
+    }
+
+    /// Reads the start tag at the start of the given input, and opens its element
+    fn start_tag(&mut self, data: &[u8]) -> Option<usize> {
+        let StartTag {
+            tag,
+            len,
+            is_terminated,
+            is_self_closing,
+        } = Parser::read_start_tag(data);
+
+        if !is_terminated && !self.eof {
+            return None;
+        }
+
+        let name = tag.name().as_bytes();
+        let has_content = !is_self_closing && !tag_info::is_void(name);
+        let content = if !has_content {
+            Content::Markup
+        } else if name.eq_ignore_ascii_case(b"plaintext") {
+            Content::Plaintext
+        } else if self.open.options.is_raw_text_element(name) {
+            Content::RawText(name.into())
+        } else {
+            Content::Markup
+        };
+
+        if let Some(depth) = self.open.implied_end_depth(name) {
+            while self.open.stack.len() > depth {
+                self.close_element();
+            }
+        }
+
+        self.open_element(tag);
+        if !has_content {
+            self.close_element();
+        }
+
+        self.content = content;
+        Some(len)
+    }
+
+    /// Reads the end tag at the start of the given input, whose `/` is at `slash`, and closes the element it ends
+    fn end_tag(&mut self, data: &[u8], slash: usize) -> Option<usize> {
+        let name_start = slash + 1;
+        let terminator = simd::find(&data[name_start..], b'>');
+
+        let end = match terminator {
+            Some(offset) => name_start + offset + 1,
+            None if self.eof => data.len(),
+            None => return None,
+        };
+
+        let name_len = data[name_start..end]
+            .iter()
+            .position(|&c| util::is_ascii_whitespace(c) || c == b'/' || c == b'>')
+            .unwrap_or(end - name_start);
+        let name = &data[name_start..name_start + name_len];
+
+        match self.open.end_tag_depth(name) {
+            Some(depth) => {
+                while self.open.stack.len() > depth {
+                    self.close_element();
+                }
+            }
+            // end tags that are cut off by the end of the input are text, and stray end tags are ignored
+            None if terminator.is_none() => self.push_text(&data[..end]),
+            None => {}
+        }
+
+        Some(end)
+    }
+
+    /// Skips the comment or other markup declaration (such as a doctype) at the start of the given input, whose `!` is at `bang`
+    fn markup_declaration(&mut self, data: &[u8], bang: usize) -> Option<usize> {
+        let rest = &data[bang + 1..];
+
+        if rest.len() < 2 && !self.eof {
+            return None;
+        }
+
+        if rest.starts_with(b"--") {
+            return match comment_end(rest) {
+                Some(end) => Some(bang + 1 + end),
+                None if self.eof => Some(data.len()),
+                None => {
+                    self.content = Content::Comment;
+                    Some(data.len() - 2)
+                }
+            };
+        }
+
+        match simd::find(rest, b'>') {
+            Some(end) => Some(bang + 1 + end + 1),
+            None if self.eof => Some(data.len()),
+            None => None,
+        }
+    }
+
+    /// Adds the text to the text of all open elements that matched
+    fn push_text(&mut self, text: &[u8]) {
+        for pending in &mut self.pending {
+            pending.text.extend_from_slice(text);
+        }
+    }
+
+    /// Pushes the given element onto the stack of open elements, and checks which selectors it matches
+    fn open_element(&mut self, mut tag: HTMLTag<'static>) {
+        let depth = self.open.stack.len();
+        tag._parent = self.open.stack.last().copied();
+
+        self.open.tags.push(Node::Tag(tag));
+        let handle = self.open.handle_at(depth);
+        self.open.stack.push(handle);
+
+        // `:root` matches the outermost element
+        if depth == 0 {
+            self.open.ast.clear();
+            self.open.ast.push(handle);
+        }
+
+        let node = &self.open.tags[depth];
+        let selectors = self
+            .selectors
+            .selectors
+            .iter()
+            .enumerate()
+            .filter(|(_, selector)| selector.matches(node, &self.open))
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+
+        if !selectors.is_empty() {
+            self.pending.push(PendingMatch {
+                depth,
+                index: self.elements,
+                selectors,
+                text: Vec::new(),
+            });
+        }
+
+        self.elements += 1;
+    }
+
+    /// Pops the innermost open element off the stack, and emits its events if it matched
+    fn close_element(&mut self) {
+        let depth = self.open.stack.len() - 1;
+
+        if self
+            .pending
+            .last()
+            .is_some_and(|pending| pending.depth == depth)
+        {
+            let pending = self.pending.pop().unwrap();
+            let text = String::from_utf8_lossy(&pending.text);
+
+            for &selector in &pending.selectors {
+                (self.on_match)(MatchEvent {
+                    selector,
+                    index: pending.index,
+                    open: &self.open,
+                    depth,
+                    text: &text,
+                });
+            }
+        }
+
+        self.open.stack.pop();
+        self.open.tags.pop();
+    }
+}
+
+/// Where the content of a raw text element ends in the input that has been read
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum RawTextEnd {
+    /// The end tag spans the given range
+    EndTag(usize, usize),
+    /// The end tag does not start before the given offset, but more input is needed to tell whether it starts there
+    NotBefore(usize),
+}
+
+/// Finds the end tag of the raw text element with the given name in the given input, like `Parser::read_raw_text()`
+fn raw_text_end(data: &[u8], name: &[u8], eof: bool) -> RawTextEnd {
+    let mut idx = 0;
+
+    while let Some(offset) = simd::find(&data[idx..], b'<') {
+        idx += offset;
+
+        if !eof && data.len() - idx < name.len() + 3 {
+            return RawTextEnd::NotBefore(idx);
+        }
+
+        let is_end_tag = data.get(idx + 1) == Some(&b'/')
+            && data
+                .get(idx + 2..idx + 2 + name.len())
+                .is_some_and(|x| x.eq_ignore_ascii_case(name))
+            && data.get(idx + 2 + name.len()).map_or(true, |&c| {
+                util::is_ascii_whitespace(c) || simd::is_closing(c)
+            });
+
+        if is_end_tag {
+            return match simd::find(&data[idx..], b'>') {
+                Some(end) => RawTextEnd::EndTag(idx, idx + end + 1),
+                None if eof => RawTextEnd::EndTag(idx, data.len()),
+                None => RawTextEnd::NotBefore(idx),
+            };
+        }
+
+        idx += 1;
+    }
+
+    RawTextEnd::NotBefore(data.len())
+}
+
+/// Returns the length of a comment up to and including its `-->`, given the input after the `<!` of the comment,
+/// or the input from the last two bytes of the comment that have been read
+///
+/// As in `Parser::skip_comment_with_start()`, the dashes of `<!--` count towards the end, so `<!-->` is a complete comment.
+fn comment_end(data: &[u8]) -> Option<usize> {
+    let mut idx = 2;
+
+    while let Some(offset) = simd::find(data.get(idx..)?, b'>') {
+        idx += offset + 1;
+
+        if &data[idx - 3..idx - 1] == b"--" {
+            return Some(idx);
+        }
+    }
+
+    None
+}
+
+/// Instrumentation for the size of the input buffer of `stream_select()` on the current thread
+#[cfg(test)]
+pub(crate) mod buffered {
+    use std::cell::Cell;
+
+    thread_local! {
+        static PEAK: Cell<usize> = const { Cell::new(0) };
+    }
+
+    pub fn record(len: usize) {
+        PEAK.with(|v| v.set(v.get().max(len)));
+    }
+
+    /// Returns the largest number of buffered bytes since the last call and resets it
+    pub fn take() -> usize {
+        PEAK.with(|v| v.replace(0))
+    }
+}
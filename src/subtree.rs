@@ -0,0 +1,150 @@
+use std::borrow::Cow;
+use std::ops::Range;
+
+use crate::queryselector::{LookupKey, QuerySelectorIterator, SelectorOptions};
+use crate::{Bytes, HTMLTag, Node, NodeHandle, Parser, VDom};
+
+/// A read-only view of the subtree of a node, which can be queried like a document of its own
+///
+/// Obtained by calling `VDom::subtree_view()`. The node is the only top-level node of the view,
+/// and lookups only return elements within its subtree (including the node itself).
+/// Creating a view is cheap, since the subtree is stored contiguously in the node table.
+#[derive(Debug, Clone)]
+pub struct SubtreeView<'v, 'a> {
+    dom: &'v VDom<'a>,
+    root: NodeHandle,
+    /// The positions of the root and all of its subnodes
+    range: Range<usize>,
+}
+
+impl<'v, 'a> SubtreeView<'v, 'a> {
+    pub(crate) fn new(dom: &'v VDom<'a>, root: NodeHandle, range: Range<usize>) -> Self {
+        Self { dom, root, range }
+    }
+
+    /// Returns the node whose subtree this view covers
+    #[inline]
+    pub fn root(&self) -> NodeHandle {
+        self.root
+    }
+
+    /// Returns a reference to the underlying parser
+    #[inline]
+    pub fn parser(&self) -> &'v Parser<'a> {
+        self.dom.parser()
+    }
+
+    /// Returns the topmost nodes of this view, which is only the root
+    pub fn children(&self) -> &[NodeHandle] {
+        std::slice::from_ref(&self.root)
+    }
+
+    /// Returns a slice of all nodes in this view, which are the root and its subnodes in document order
+    pub fn nodes(&self) -> &'v [Node<'a>] {
+        &self.parser().tags[self.range.clone()]
+    }
+
+    /// Checks whether the given node is part of this view
+    pub fn contains(&self, handle: NodeHandle) -> bool {
+        self.parser()
+            .position_of(handle)
+            .is_some_and(|position| self.range.contains(&position))
+    }
+
+    /// Finds the first element in this view by its `id` attribute
+    ///
+    /// Like `VDom::get_element_by_id()`, this uses the lookup table if ids are tracked.
+    pub fn get_element_by_id<'b, S>(&'b self, id: S) -> Option<NodeHandle>
+    where
+        S: Into<Bytes<'a>>,
+    {
+        let bytes: Bytes = id.into();
+
+        match self.dom.lookup(LookupKey::Id(bytes.as_bytes())) {
+            Some(handles) => handles
+                .iter()
+                .copied()
+                .find(|&handle| self.contains(handle)),
+            None => {
+                let decode = !self.parser().options.is_raw_id_and_class_matching();
+                self.elements_where(move |tag| tag._attributes.matches_id(bytes.as_bytes(), decode))
+                    .next()
+            }
+        }
+    }
+
+    /// Returns the elements in this view that have the given class, in document order
+    ///
+    /// Like `VDom::get_elements_by_class_name()`, this uses the lookup table if classes are tracked.
+    pub fn get_elements_by_class_name<'b>(
+        &'b self,
+        class: &'b str,
+    ) -> Box<dyn Iterator<Item = NodeHandle> + 'b> {
+        match self.dom.lookup(LookupKey::Class(class.as_bytes())) {
+            Some(handles) => Box::new(
+                handles
+                    .iter()
+                    .copied()
+                    .filter(move |&handle| self.contains(handle)),
+            ),
+            None => {
+                let decode = !self.parser().options.is_raw_id_and_class_matching();
+                self.elements_where(move |tag| {
+                    tag._attributes.matches_class(class.as_bytes(), decode)
+                })
+            }
+        }
+    }
+
+    /// Returns the elements in this view for which the predicate returns `true`, in document order
+    fn elements_where<'b, F>(&'b self, f: F) -> Box<dyn Iterator<Item = NodeHandle> + 'b>
+    where
+        F: Fn(&HTMLTag<'a>) -> bool + 'b,
+    {
+        let parser = self.parser();
+
+        let iter = self
+            .range
+            .clone()
+            .filter(move |&position| parser.tags[position].as_tag().is_some_and(&f))
+            .map(move |position| parser.handle_at(position));
+
+        Box::new(iter)
+    }
+
+    /// Tries to parse the query selector and returns an iterator over the elements in this view that match it
+    ///
+    /// Only the nodes of this view are visited, but as with `HTMLTag::query_selector()`, combinators
+    /// (e.g. `div > p`) are resolved against the actual ancestors and siblings of the nodes.
+    /// The query cache of the document is not used.
+    pub fn query_selector<'b>(
+        &'b self,
+        selector: &'b str,
+    ) -> Option<QuerySelectorIterator<'a, 'b, VDom<'a>>> {
+        let selector = crate::parse_query_selector(selector)?;
+
+        Some(self.dom.evaluate_query_in(
+            Cow::Owned(selector),
+            SelectorOptions::default(),
+            self.range.clone(),
+        ))
+    }
+
+    /// Returns the markup of the root and its subtree
+    pub fn outer_html(&self) -> String {
+        self.root
+            .get(self.parser())
+            .map(|node| node.outer_html(self.parser()).into_owned())
+            .unwrap_or_default()
+    }
+
+    /// Returns the text of the root and its subtree, excluding any markup
+    pub fn inner_text(&self) -> Cow<'v, str> {
+        crate::parser::concat_texts(
+            self.nodes()
+                .iter()
+                .filter_map(Node::as_raw)
+                .map(Bytes::as_utf8_str),
+        )
+    }
+}
@@ -0,0 +1,183 @@
+use core::ops::{BitAnd, BitOr};
+
+/// The longest standard tag name we know about (`blockquote`, `figcaption`)
+///
+/// Names that are longer than this are never standard tags, which lets us lowercase into a stack buffer.
+const MAX_KNOWN_NAME_LEN: usize = 10;
+
+/// A set of categories an HTML element belongs to
+///
+/// Elements can belong to several categories at once (e.g. `<input>` is void, inline and form-associated).
+/// Use [`TagCategory::of()`] to look up the categories of an element by its tag name.
+///
+/// # Example
+/// ```
+/// use tl::tag_info::TagCategory;
+///
+/// let category = TagCategory::of(b"INPUT");
+/// assert!(category.contains(TagCategory::VOID | TagCategory::FORM_ASSOCIATED));
+/// assert!(!category.is_block_level());
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct TagCategory(u8);
+
+impl TagCategory {
+    /// No categories
+    pub const NONE: Self = Self(0);
+    /// Void elements, which have no content and no end tag (e.g. `<br>`)
+    pub const VOID: Self = Self(1 << 0);
+    /// Block-level elements (e.g. `<div>`, `<p>`)
+    pub const BLOCK_LEVEL: Self = Self(1 << 1);
+    /// Inline (phrasing) elements (e.g. `<span>`, `<a>`)
+    pub const INLINE: Self = Self(1 << 2);
+    /// Metadata content, which sets up the presentation or behavior of the document (e.g. `<meta>`, `<script>`)
+    pub const METADATA_CONTENT: Self = Self(1 << 3);
+    /// Form-associated elements (e.g. `<input>`, `<select>`)
+    pub const FORM_ASSOCIATED: Self = Self(1 << 4);
+    /// Deprecated or obsolete elements (e.g. `<font>`, `<center>`)
+    pub const DEPRECATED: Self = Self(1 << 5);
+
+    /// Returns the categories of the element with the given tag name (ASCII case-insensitive)
+    ///
+    /// Unknown or custom elements are treated as inline, non-void elements.
+    pub fn of(name: &[u8]) -> Self {
+        if name.len() > MAX_KNOWN_NAME_LEN {
+            return Self::INLINE;
+        }
+
+        let mut buffer = [0u8; MAX_KNOWN_NAME_LEN];
+        let lower = &mut buffer[..name.len()];
+        lower.copy_from_slice(name);
+        lower.make_ascii_lowercase();
+
+        const V: TagCategory = TagCategory::VOID;
+        const B: TagCategory = TagCategory::BLOCK_LEVEL;
+        const I: TagCategory = TagCategory::INLINE;
+        const M: TagCategory = TagCategory::METADATA_CONTENT;
+        const F: TagCategory = TagCategory::FORM_ASSOCIATED;
+        const D: TagCategory = TagCategory::DEPRECATED;
+        const N: TagCategory = TagCategory::NONE;
+
+        match &*lower {
+            // void elements
+            b"area" | b"col" | b"embed" | b"param" | b"source" | b"track" => V,
+            b"base" | b"meta" => V | M,
+            b"link" => V | M | I,
+            b"br" | b"wbr" => V | I,
+            b"hr" => V | B,
+            b"img" => V | I | F,
+            b"input" => V | I | F,
+            b"keygen" => V | I | F | D,
+            b"basefont" => V | I | D,
+            b"command" => V | M | D,
+            b"bgsound" | b"frame" => V | D,
+
+            // block-level elements
+            b"address" | b"article" | b"aside" | b"blockquote" | b"details" | b"dialog" | b"dd"
+            | b"div" | b"dl" | b"dt" | b"figcaption" | b"figure" | b"footer" | b"form" | b"h1"
+            | b"h2" | b"h3" | b"h4" | b"h5" | b"h6" | b"header" | b"hgroup" | b"li" | b"main"
+            | b"nav" | b"ol" | b"p" | b"pre" | b"section" | b"table" | b"ul" | b"menu"
+            | b"search" => B,
+            b"fieldset" => B | F,
+            b"center" | b"dir" | b"listing" | b"plaintext" | b"xmp" => B | D,
+
+            // metadata content
+            b"script" | b"noscript" | b"template" => M | I,
+            b"style" | b"title" => M,
+
+            // inline elements
+            b"a" | b"abbr" | b"audio" | b"b" | b"bdi" | b"bdo" | b"canvas" | b"cite" | b"code"
+            | b"data" | b"datalist" | b"del" | b"dfn" | b"em" | b"i" | b"iframe" | b"ins"
+            | b"kbd" | b"map" | b"mark" | b"math" | b"meter" | b"picture" | b"progress" | b"q"
+            | b"ruby" | b"s" | b"samp" | b"slot" | b"small" | b"span" | b"strong" | b"sub"
+            | b"sup" | b"svg" | b"time" | b"u" | b"var" | b"video" | b"label" => I,
+            b"button" | b"object" | b"output" | b"select" | b"textarea" => I | F,
+            b"acronym" | b"applet" | b"big" | b"blink" | b"font" | b"marquee" | b"nobr"
+            | b"spacer" | b"strike" | b"tt" => I | D,
+
+            // elements that are neither (document structure, tables, lists, ...)
+            b"html" | b"head" | b"body" | b"caption" | b"colgroup" | b"tbody" | b"thead"
+            | b"tfoot" | b"tr" | b"td" | b"th" | b"optgroup" | b"option" | b"legend"
+            | b"summary" | b"rp" | b"rt" => N,
+            b"frameset" | b"noframes" | b"noembed" | b"isindex" | b"menuitem" | b"rb" | b"rtc" => D,
+
+            _ => I,
+        }
+    }
+
+    /// Checks whether all categories in `other` are also in `self`
+    #[inline]
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Checks whether this is a void element
+    #[inline]
+    pub fn is_void(self) -> bool {
+        self.contains(Self::VOID)
+    }
+
+    /// Checks whether this is a block-level element
+    #[inline]
+    pub fn is_block_level(self) -> bool {
+        self.contains(Self::BLOCK_LEVEL)
+    }
+
+    /// Checks whether this is an inline element
+    #[inline]
+    pub fn is_inline(self) -> bool {
+        self.contains(Self::INLINE)
+    }
+
+    /// Checks whether this element is metadata content
+    #[inline]
+    pub fn is_metadata_content(self) -> bool {
+        self.contains(Self::METADATA_CONTENT)
+    }
+
+    /// Checks whether this is a form-associated element
+    #[inline]
+    pub fn is_form_associated(self) -> bool {
+        self.contains(Self::FORM_ASSOCIATED)
+    }
+
+    /// Checks whether this is a deprecated or obsolete element
+    #[inline]
+    pub fn is_deprecated(self) -> bool {
+        self.contains(Self::DEPRECATED)
+    }
+}
+
+impl BitOr for TagCategory {
+    type Output = Self;
+
+    #[inline]
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitAnd for TagCategory {
+    type Output = Self;
+
+    #[inline]
+    fn bitand(self, rhs: Self) -> Self {
+        Self(self.0 & rhs.0)
+    }
+}
+
+/// Returns the rank of the heading element with the given tag name (1 for `<h1>` to 6 for `<h6>`, ASCII case-insensitive)
+///
+/// Returns `None` if the element is not a heading.
+pub(crate) fn heading_level(name: &[u8]) -> Option<u8> {
+    match name {
+        [b'h' | b'H', level @ b'1'..=b'6'] => Some(level - b'0'),
+        _ => None,
+    }
+}
+
+/// Checks whether the element with the given tag name is a void element (ASCII case-insensitive)
+#[inline]
+pub fn is_void(name: &[u8]) -> bool {
+    TagCategory::of(name).is_void()
+}
@@ -1,5 +1,9 @@
-use crate::{parse, parse_owned, Bytes};
-use crate::{parser::*, HTMLTag, Node};
+use crate::prelude::*;
+use crate::{
+    Construct, Diagnostic, DiagnosticKind, Dimension, ElementBuilderError, IndexKind, InsertError,
+    KnownAttr, LinkRel, MetaRefresh, MoveError, NodePath, NodeRef, OutlineEntry, OutlineOptions,
+    SetBytesError, TextFingerprintOptions, TextJoinOptions, TextNodes,
+};
 
 fn force_as_tag<'a, 'b>(actual: &'a Node<'b>) -> &'a HTMLTag<'b> {
     match actual {
@@ -123,7 +127,33 @@ fn html5() {
     let dom = parse("<!DOCTYPE html> hello", ParserOptions::default()).unwrap();
 
     assert_eq!(dom.version(), Some(HTMLVersion::HTML5));
-    assert_eq!(dom.children().len(), 1)
+    assert_eq!(dom.children().len(), 1);
+    // the doctype is not a node, but it is serialized again
+    assert_eq!(dom.doctype(), Some(&"<!DOCTYPE html>".into()));
+    assert_eq!(dom.outer_html(), "<!DOCTYPE html> hello");
+}
+
+#[test]
+fn doctype_position() {
+    // leading whitespace is kept before the doctype
+    let input = "\n <!DOCTYPE html>\n<p>a</p>";
+    let mut dom = parse(input, ParserOptions::default()).unwrap();
+    assert_eq!(dom.children().len(), 3);
+    assert_eq!(dom.outer_html(), input);
+
+    // the doctype stays in place when top-level nodes are removed
+    for child in dom.children().to_vec() {
+        dom.remove(child).unwrap();
+    }
+    assert_eq!(dom.outer_html(), "<!DOCTYPE html>");
+
+    // markup declarations elsewhere are comments
+    let dom = parse("<p>a</p><!DOCTYPE html>", ParserOptions::default()).unwrap();
+    assert_eq!(dom.doctype(), None);
+    assert_eq!(
+        dom.children()[1].get(dom.parser()).unwrap().as_comment(),
+        Some(&"<!DOCTYPE html>".into())
+    );
 }
 
 #[test]
@@ -147,6 +177,7 @@ fn ignore_void_closing_tags() {
 }
 
 #[test]
+#[allow(clippy::clone_on_copy)]
 pub fn children_mut() {
     let input = "<head><p>Replace me</p> World</head>";
 
@@ -183,6 +214,7 @@ fn nested_inner_text() {
 }
 
 #[test]
+#[allow(clippy::let_and_return)]
 fn owned_dom() {
     let owned_dom = {
         let input = String::from("<p id=\"test\">hello</p>");
@@ -270,6 +302,44 @@ fn fuzz() {
     parse(&"<p>".repeat(count), ParserOptions::default()).unwrap();
 }
 
+#[test]
+fn deeply_nested() {
+    // Traversals must not recurse per nesting level, so this runs on a thread with a small stack
+    let count = if cfg!(miri) { 100usize } else { 100_000usize };
+
+    let thread = std::thread::Builder::new().stack_size(256 * 1024);
+    let handle = thread.spawn(move || {
+        let input = "<div>".repeat(count) + "x";
+        let dom = parse(&input, ParserOptions::default()).unwrap();
+        let parser = dom.parser();
+
+        let outermost = dom.nodes()[0].as_tag().unwrap();
+        let deepest = dom.nodes()[count - 1].as_tag().unwrap();
+
+        assert_eq!(outermost.inner_text(parser), "x");
+        assert_eq!(outermost.inner_text_decoded(parser), "x");
+        assert_eq!(deepest.inner_text(parser), "x");
+        assert_eq!(deepest.outer_html(parser), "<div>x");
+
+        let html = dom.outer_html();
+        assert_eq!(html, input);
+        assert_eq!(
+            outermost.inner_html(parser).len(),
+            html.len() - "<div>".len()
+        );
+        assert!(outermost.to_xml(parser).is_ok());
+        assert_eq!(dom.visible_text(Default::default()), "x");
+
+        let other = parse(&input, ParserOptions::default()).unwrap();
+        assert_eq!(
+            NodeRef::new(&dom.nodes()[0], parser),
+            NodeRef::new(&other.nodes()[0], other.parser())
+        );
+    });
+
+    handle.unwrap().join().unwrap();
+}
+
 #[test]
 fn mutate_dom() {
     let input = r#"<img src="test.png" />"#;
@@ -512,6 +582,7 @@ fn unquoted() {
 
 mod query_selector {
     use super::*;
+    use crate::queryselector::Selector;
     #[test]
     fn query_selector_simple() {
         let input = "<div><p class=\"hi\">hello</p></div>";
@@ -584,6 +655,7 @@ mod query_selector {
     }
 
     #[test]
+    #[allow(clippy::manual_map)]
     fn query_selector_with_quote() {
         let input = r#"<div><meta property="og:title" content="hello" /></div>"#;
         let dom = parse(input, ParserOptions::default()).unwrap();
@@ -648,6 +720,835 @@ mod query_selector {
 
         assert_eq!(texts, vec![String::from("cond1"), String::from("cond2")]);
     }
+
+    #[test]
+    fn query_selector_tags_complex_list() {
+        fn matching<'d>(dom: &'d VDom<'d>, selector: &'d str) -> Vec<&'d HTMLTag<'d>> {
+            dom.query_selector(selector).unwrap().tags().collect()
+        }
+
+        let input = r#"<main><div><p class="hello world" id="id"><span>cond1</span><section>cond2</section></p></div></main>"#;
+        let dom = parse(input, ParserOptions::default()).unwrap();
+        let parser = dom.parser();
+
+        let tags = matching(&dom, "div > .hello.world#id > span, main section");
+        let texts = tags
+            .iter()
+            .map(|tag| tag.inner_text(parser))
+            .collect::<Vec<_>>();
+        assert_eq!(texts, ["cond1", "cond2"]);
+
+        // the tags outlive the iterator and borrow from the input
+        let names: Vec<&str> = tags
+            .iter()
+            .filter_map(|tag| tag.name().try_as_utf8_str())
+            .collect();
+        assert_eq!(names, ["span", "section"]);
+
+        // handles are still available for mutation
+        let handles = dom
+            .query_selector("main section")
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert!(std::ptr::eq(
+            handles[0].get(parser).unwrap().as_tag().unwrap(),
+            tags[1]
+        ));
+    }
+
+    #[test]
+    fn query_selector_list_precedence() {
+        let input = r#"<x><y>1</y></x><z>2</z><a><b>3</b><c><d>4</d></c></a><b>5</b><c>6</c>"#;
+        let dom = parse(input, ParserOptions::default()).unwrap();
+        let parser = dom.parser();
+        let texts = |selector: &str| {
+            dom.query_selector(selector)
+                .unwrap()
+                .map(|x| x.get(parser).unwrap().inner_text(parser).into_owned())
+                .collect::<Vec<_>>()
+        };
+
+        // `x y, z` is `(x y), z`, not `x (y, z)`
+        assert_eq!(texts("x y, z"), ["1", "2"]);
+        assert_eq!(texts("z, x y"), ["1", "2"]);
+        // both branches are independent complex selectors
+        assert_eq!(texts("a > b, c d"), ["3", "4"]);
+        assert_eq!(texts("a>b , c d"), ["3", "4"]);
+        // combinators after a child combinator without whitespace
+        assert_eq!(texts("a>c d"), ["4"]);
+        assert_eq!(texts("c d, a > b, x > y"), ["1", "3", "4"]);
+    }
+
+    #[test]
+    fn query_selector_decoded_attributes() {
+        use crate::queryselector::SelectorOptions;
+
+        let input = r#"<div><p title="a&amp;b">1</p><p title="a&b">2</p></div>"#;
+        let mut dom = parse(input, ParserOptions::default()).unwrap();
+        let raw = SelectorOptions::default();
+        let decoded = SelectorOptions {
+            compare_decoded: true,
+        };
+
+        let texts = |dom: &crate::VDom, selector: &str, options| {
+            dom.query_selector_with_options(selector, options)
+                .unwrap()
+                .map(|x| {
+                    x.get(dom.parser())
+                        .unwrap()
+                        .inner_text(dom.parser())
+                        .into_owned()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        for _ in 0..2 {
+            assert_eq!(texts(&dom, r#"[title="a&b"]"#, raw), ["2"]);
+            assert_eq!(texts(&dom, r#"[title="a&b"]"#, decoded), ["1", "2"]);
+            assert_eq!(texts(&dom, r#"[title="a&amp;b"]"#, raw), ["1"]);
+            assert_eq!(
+                texts(&dom, r#"[title="a&amp;b"]"#, decoded),
+                [] as [&str; 0]
+            );
+            assert_eq!(texts(&dom, r#"[title$="&b"]"#, decoded), ["1", "2"]);
+
+            // results are cached separately for each set of options
+            dom.enable_query_cache();
+        }
+
+        let div = dom.nodes()[0].as_tag().unwrap();
+        let parser = dom.parser();
+        let count = |options| {
+            div.query_selector_with_options(parser, r#"p[title="a&b"]"#, options)
+                .unwrap()
+                .count()
+        };
+        assert_eq!((count(raw), count(decoded)), (1, 2));
+    }
+
+    #[test]
+    fn query_selector_invalid_lists() {
+        // empty list entries are parse errors
+        assert!(crate::parse_query_selector("a, b,").is_none());
+        assert!(crate::parse_query_selector(", a").is_none());
+        assert!(crate::parse_query_selector("a,, b").is_none());
+        assert!(crate::parse_query_selector("a >").is_none());
+        assert!(crate::parse_query_selector("a > , b").is_none());
+        assert!(crate::parse_query_selector("a b) c").is_none());
+
+        assert!(matches!(
+            crate::parse_query_selector("a b, c"),
+            Some(Selector::Or(left, right))
+                if matches!(*left, Selector::Descendant(..))
+                    && matches!(&*right, Selector::Tag(tag) if &**tag == b"c")
+        ));
+    }
+
+    #[test]
+    fn query_selector_child_pseudo_classes() {
+        let dom = parse(
+            "<h1>a</h1> text <!-- c --><article> <p>1</p><p>2</p><div><p>3</p></div> </article><footer><p>4</p></footer> end",
+            ParserOptions::default(),
+        )
+        .unwrap();
+        let parser = dom.parser();
+        let texts = |selector| {
+            dom.query_selector(selector)
+                .unwrap()
+                .map(|handle| handle.get(parser).unwrap().inner_text(parser).into_owned())
+                .collect::<Vec<_>>()
+        };
+
+        // text and comments are not counted
+        assert_eq!(texts("article > p:first-child"), ["1"]);
+        assert_eq!(texts("p:first-child"), ["1", "3", "4"]);
+        assert_eq!(texts("p:last-child"), ["3", "4"]);
+        assert_eq!(texts("p:only-child"), ["3", "4"]);
+        assert_eq!(texts("article :last-child"), ["3", "3"]);
+        assert_eq!(texts("footer:only-child"), [] as [&str; 0]);
+
+        // tags without a parent are compared with the other topmost tags
+        assert_eq!(texts("h1:first-child"), ["a"]);
+        assert_eq!(texts(":first-child:last-child"), ["3", "4"]);
+        assert_eq!(dom.query_selector_count("footer:last-child"), Some(1));
+        assert_eq!(dom.query_selector_count("article:first-child"), Some(0));
+
+        assert!(crate::parse_query_selector("p:hover").is_none());
+        assert!(crate::parse_query_selector("p:").is_none());
+        assert!(crate::parse_query_selector("p :first-child").is_some());
+    }
+
+    #[test]
+    fn query_selector_empty_and_root() {
+        let dom = parse(
+            "<!DOCTYPE html><html><body><table><tr><td></td><td> \n </td><td><!-- c --></td><td>x</td><td><b></b></td></tr></table>\
+             <div id=a></div><div id=b>&nbsp;</div></body></html>",
+            ParserOptions::default(),
+        )
+        .unwrap();
+        let count = |selector| dom.query_selector_count(selector).unwrap();
+
+        // whitespace-only text and comments do not count as content
+        assert_eq!(count("td:empty"), 3);
+        assert_eq!(count("b:empty"), 1);
+        assert_eq!(count("tr:empty"), 0);
+        assert_eq!(count("div:empty"), 1);
+        assert_eq!(count("div:empty#a"), 1);
+
+        assert_eq!(count(":root"), 1);
+        assert_eq!(count("html:root"), 1);
+        assert_eq!(count(":root > body"), 1);
+        assert_eq!(count(":root td:empty"), 3);
+        assert_eq!(count("body:root"), 0);
+
+        // only the topmost <html> element is the root
+        let dom = parse("<div><html></html></div>", ParserOptions::default()).unwrap();
+        assert_eq!(dom.query_selector_count(":root"), Some(0));
+        assert!(crate::parse_query_selector(":empty()").is_none());
+    }
+
+    #[test]
+    fn query_selector_tag_case() {
+        let input =
+            "<DIV><Span>a</Span><span>b</span></DIV><svg><foreignObject>c</foreignObject></svg>";
+        let dom = parse(input, ParserOptions::default()).unwrap();
+        let count = |selector| dom.query_selector_count(selector).unwrap();
+
+        assert_eq!(count("div"), 1);
+        assert_eq!(count("DIV"), 1);
+        assert_eq!(count("span"), 2);
+        assert_eq!(count("SPAN"), 2);
+        assert_eq!(count("dIv > sPaN:first-child"), 1);
+        assert_eq!(count("foreignobject"), 1);
+        assert_eq!(count("div, FOREIGNOBJECT"), 2);
+        assert_eq!(count("spa"), 0);
+
+        // tag names of XML-like documents can be compared exactly
+        let options = ParserOptions::default().case_sensitive_tag_names();
+        let dom = parse(input, options).unwrap();
+        let count = |selector| dom.query_selector_count(selector).unwrap();
+
+        assert_eq!(count("div"), 0);
+        assert_eq!(count("DIV"), 1);
+        assert_eq!(count("span"), 1);
+        assert_eq!(count("Span"), 1);
+        assert_eq!(count("DIV > Span"), 1);
+        assert_eq!(count("foreignObject"), 1);
+        assert_eq!(count("foreignobject"), 0);
+    }
+
+    #[test]
+    fn query_selector_contains() {
+        let dom = parse(
+            r#"<div><button>Add <b>to</b> cart</button><button>Remove</button><p>say "hi" &amp; <!-- x -->leave</p></div>"#,
+            ParserOptions::default(),
+        )
+        .unwrap();
+        let parser = dom.parser();
+        let texts = |selector: &str| {
+            dom.query_selector(selector)
+                .unwrap_or_else(|| panic!("{selector} should be valid"))
+                .map(|handle| handle.get(parser).unwrap().inner_text(parser).into_owned())
+                .collect::<Vec<_>>()
+        };
+
+        // text that spans multiple nodes is found, and ancestors match too
+        assert_eq!(texts(r#"button:contains("Add to cart")"#), ["Add to cart"]);
+        assert_eq!(texts(r#"button:contains( 'o c' )"#), ["Add to cart"]);
+        assert_eq!(texts("button:contains(Remove)"), ["Remove"]);
+        assert_eq!(dom.query_selector_count(r#":contains("to")"#), Some(3));
+        assert_eq!(dom.query_selector_count(r#"button:contains("")"#), Some(2));
+        assert_eq!(
+            dom.query_selector_count(r#"button:contains("add")"#),
+            Some(0)
+        );
+
+        // quotes can be escaped, comments are skipped, and text is compared raw unless decoding is requested
+        assert_eq!(
+            dom.query_selector_count(r#"p:contains("say \"hi\"")"#),
+            Some(1)
+        );
+        assert_eq!(
+            dom.query_selector_count(r#"p:contains("&amp; leave")"#),
+            Some(1)
+        );
+        assert_eq!(
+            dom.query_selector_count(r#"p:contains("& leave")"#),
+            Some(0)
+        );
+        let options = SelectorOptions {
+            compare_decoded: true,
+        };
+        let decoded = dom
+            .query_selector_with_options(r#"p:contains("& leave")"#, options)
+            .unwrap();
+        assert_eq!(decoded.count(), 1);
+
+        // the result is the same as searching the inner text, including occurrences at node boundaries
+        let dom = parse(
+            "<p>ab<i>c</i><b></b>d<i>e<u>f</u></i>g</p>",
+            ParserOptions::default(),
+        )
+        .unwrap();
+        let text = "abcdefg";
+        for start in 0..text.len() {
+            for end in start..=text.len() {
+                let selector = format!(r#"p:contains("{}")"#, &text[start..end]);
+                assert_eq!(dom.query_selector_count(&selector), Some(1), "{selector}");
+            }
+        }
+        assert_eq!(dom.query_selector_count(r#"p:contains("ac")"#), Some(0));
+        assert_eq!(dom.query_selector_count(r#"i:contains("ef")"#), Some(1));
+
+        for malformed in [
+            ":contains",
+            ":contains()",
+            ":contains(\"x\"",
+            ":contains(\"x)",
+            ":contains(x y)",
+        ] {
+            assert!(
+                crate::parse_query_selector(malformed).is_none(),
+                "{malformed}"
+            );
+        }
+    }
+
+    #[test]
+    fn query_selector_nth_child() {
+        let dom = parse(
+            "<table><tr>1</tr> <tr>2</tr><!-- c --><tr>3</tr><tr>4</tr><tr>5</tr><tr>6</tr><tr>7</tr></table>",
+            ParserOptions::default(),
+        )
+        .unwrap();
+        let parser = dom.parser();
+        let rows = |selector: &str| {
+            dom.query_selector(selector)
+                .unwrap_or_else(|| panic!("{selector} should be valid"))
+                .map(|handle| handle.get(parser).unwrap().inner_text(parser).into_owned())
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+
+        assert_eq!(rows("tr:nth-child(2n+1)"), "1,3,5,7");
+        assert_eq!(rows("tr:nth-child(odd)"), "1,3,5,7");
+        assert_eq!(rows("tr:nth-child(EVEN)"), "2,4,6");
+        assert_eq!(rows("tr:nth-child(3)"), "3");
+        assert_eq!(rows("tr:nth-child(+3)"), "3");
+        assert_eq!(rows("tr:nth-child(0)"), "");
+        assert_eq!(rows("tr:nth-child(n)"), "1,2,3,4,5,6,7");
+        assert_eq!(rows("tr:nth-child(3n)"), "3,6");
+        assert_eq!(rows("tr:nth-child(-n+3)"), "1,2,3");
+        assert_eq!(rows("tr:nth-child(3n-1)"), "2,5");
+        assert_eq!(rows("tr:nth-child(-2n+7)"), "1,3,5,7");
+        assert_eq!(rows("tr:nth-child( 2n + 1 )"), "1,3,5,7");
+        assert_eq!(rows("tr:nth-child( -n - 1 )"), "");
+        assert_eq!(rows("table:nth-child(1)"), "1 234567");
+
+        for malformed in [
+            "tr:nth-child",
+            "tr:nth-child()",
+            "tr:nth-child(",
+            "tr:nth-child(3",
+            "tr:nth-child(2n+)",
+            "tr:nth-child(2 n)",
+            "tr:nth-child(n n)",
+            "tr:nth-child(--n)",
+            "tr:nth-child(2n+1x)",
+            "tr:nth-child(odd 1)",
+            "tr:nth-child(abc)",
+            "tr:nth-child(99999999999)",
+        ] {
+            assert!(
+                crate::parse_query_selector(malformed).is_none(),
+                "{malformed}"
+            );
+        }
+    }
+
+    #[test]
+    fn query_selector_sibling_combinators() {
+        let dom = parse(
+            "<h2>a</h2><p>1</p><div><h2>b</h2> text <!-- c --> <p>2</p><p>3</p><span></span><p>4</p></div><p>5</p>",
+            ParserOptions::default(),
+        )
+        .unwrap();
+        let parser = dom.parser();
+        let texts = |selector: &str| {
+            dom.query_selector(selector)
+                .unwrap_or_else(|| panic!("{selector} should be valid"))
+                .map(|handle| handle.get(parser).unwrap().inner_text(parser).into_owned())
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+
+        // text and comments between elements are skipped
+        assert_eq!(texts("h2 + p"), "1,2");
+        assert_eq!(texts("h2+p"), "1,2");
+        assert_eq!(texts("h2 ~ p"), "1,2,3,4,5");
+        assert_eq!(texts("h2~p"), "1,2,3,4,5");
+        assert_eq!(texts("span + p"), "4");
+        assert_eq!(texts("p + p"), "3");
+        assert_eq!(texts("span ~ span"), "");
+
+        // combinators compose
+        assert_eq!(texts("div > h2 + p"), "2");
+        assert_eq!(texts("div h2 ~ p"), "2,3,4");
+        assert_eq!(texts("h2 + p + p"), "3");
+        assert_eq!(texts("h2 ~ div > p:last-child"), "4");
+        assert_eq!(texts("h2 + p ~ p:nth-child(3)"), "3");
+        assert_eq!(texts("h2 + p, span + p"), "1,2,4");
+
+        assert!(crate::parse_query_selector("h2 +").is_none());
+        assert!(crate::parse_query_selector("+ p").is_none());
+        assert!(crate::parse_query_selector("h2 ~ ~ p").is_none());
+
+        // unquoted attribute values may still contain `+` and `:`
+        let dom = parse(r#"<a data-x="a+b:c"></a>"#, ParserOptions::default()).unwrap();
+        assert_eq!(dom.query_selector_count("[data-x=a+b:c]"), Some(1));
+    }
+
+    #[test]
+    fn query_selector_ascii_whitespace() {
+        let dom = parse(
+            "<div class=\"a\tb\nc\x0Cd\re f\"><p id=x>1</p><span><p>2</p></span></div>",
+            ParserOptions::default().track_classes(),
+        )
+        .unwrap();
+        let count = |selector: &str| {
+            dom.query_selector_count(selector)
+                .unwrap_or_else(|| panic!("{selector:?} should be valid"))
+        };
+
+        for class in ["a", "b", "c", "d", "e", "f"] {
+            assert_eq!(count(&format!(".{class}")), 1);
+            assert_eq!(count(&format!("[class~={class}]")), 1);
+            assert_eq!(dom.get_elements_by_class_name(class).count(), 1);
+        }
+
+        assert_eq!(count("div\tp"), 2);
+        assert_eq!(count("div\n  p"), 2);
+        assert_eq!(count("div\t>\tp"), 1);
+        assert_eq!(count("div\r\n>\r\np"), 1);
+        assert_eq!(count("\n\tdiv > span\x0Cp,\n#x\n"), 2);
+        assert_eq!(count("[id=x\t]"), 1);
+    }
+
+    #[test]
+    fn query_selector_escaped_identifiers() {
+        let dom = parse(
+            r#"<div class="md:flex w-1/2" id="item.3"><p class="10" data-a:b="x">a</p><span class="md">b</span></div>"#,
+            ParserOptions::default(),
+        )
+        .unwrap();
+        let count = |selector| dom.query_selector_count(selector).unwrap();
+
+        assert_eq!(count(r".md\:flex"), 1);
+        assert_eq!(count(r".md\:flex.w-1\/2"), 1);
+        assert_eq!(count(r"#item\.3"), 1);
+        assert_eq!(count(r"#item\2e 3 > p"), 1);
+        assert_eq!(count(r".\31 0"), 1);
+        assert_eq!(count(r".\31 0:first-child"), 1);
+        assert_eq!(count(r"[data-a\:b=x]"), 1);
+        assert_eq!(count(r"\70"), 1);
+        assert_eq!(count(r"d\iv"), 1);
+
+        // without escapes, `:` starts a pseudo-class and `.` another class
+        assert_eq!(count(".md"), 1);
+        assert!(dom.query_selector(".md:flex").is_none());
+        assert_eq!(count("#item.3"), 0);
+
+        let error = crate::parse_query_selector_checked(r".md\").unwrap_err();
+        assert_eq!(
+            error.kind(),
+            crate::queryselector::ParseErrorKind::UnexpectedEnd
+        );
+    }
+
+    #[test]
+    fn query_selector_quoted_attribute_values() {
+        let dom = parse(
+            r#"<a href="https://example.com/index.html?a=1&amp;b=2#top">1</a>
+            <meta property="og:title" content="Hello, World!">
+            <p title='Say "hi"'>2</p><p title="It's">3</p><p data-x="a\b">4</p><p data-x="café">5</p>"#,
+            ParserOptions::default(),
+        )
+        .unwrap();
+        let count = |selector: &str| {
+            dom.query_selector_count(selector)
+                .unwrap_or_else(|| panic!("{selector} should be valid"))
+        };
+
+        // URLs and other values with characters that are not part of identifiers
+        assert_eq!(
+            count(r#"a[href="https://example.com/index.html?a=1&amp;b=2#top"]"#),
+            1
+        );
+        assert_eq!(count(r#"a[href^="https://example.com/"]"#), 1);
+        assert_eq!(count("a[href$='#top']"), 1);
+        assert_eq!(count(r#"meta[property="og:title"]"#), 1);
+
+        // values containing spaces and commas
+        assert_eq!(count(r#"[content="Hello, World!"]"#), 1);
+        assert_eq!(count(r#"[content*=", W"], p"#), 5);
+
+        // the other quote character, and escaped quotes and backslashes
+        assert_eq!(count(r#"[title='Say "hi"']"#), 1);
+        assert_eq!(count(r#"[title="Say \"hi\""]"#), 1);
+        assert_eq!(count(r#"[title="It's"]"#), 1);
+        assert_eq!(count(r#"[title='It\'s']"#), 1);
+        assert_eq!(count(r#"[data-x="a\\b"]"#), 1);
+
+        // hex escapes, which may be followed by a whitespace
+        assert_eq!(count(r#"[title="It\27s"]"#), 1);
+        assert_eq!(count(r#"[data-x="caf\e9"]"#), 1);
+        assert_eq!(count(r#"[data-x="caf\0000e9 "]"#), 1);
+
+        // unquoted values may contain non-ASCII characters and escapes
+        assert_eq!(count("[data-x=café]"), 1);
+        assert_eq!(count("[data-x=caf\\e9 ]"), 1);
+        assert_eq!(count("[data-x=a\\\\b]"), 1);
+
+        assert!(crate::parse_query_selector(r#"[title="unterminated]"#).is_none());
+        assert!(crate::parse_query_selector(r#"[title="a\"]"#).is_none());
+        assert!(crate::parse_query_selector("[title=a\\").is_none());
+    }
+
+    #[test]
+    fn query_selector_compiled() {
+        use crate::queryselector::OwnedSelector;
+        use std::sync::OnceLock;
+
+        fn assert_send_sync<T: Send + Sync + 'static>() {}
+        assert_send_sync::<OwnedSelector>();
+
+        static PRICE: OnceLock<OwnedSelector> = OnceLock::new();
+        let price = || {
+            PRICE.get_or_init(|| {
+                // the selector string does not need to outlive the compiled selector
+                let input = String::from(r#"div.product > span.price, [data-price="a\"b"]"#);
+                Selector::parse(&input).unwrap()
+            })
+        };
+
+        let inputs = [
+            r#"<div class="product"><span class="price">1</span></div>"#,
+            r#"<span class="price">2</span><i data-price='a"b'>3</i>"#,
+            r#"<div class="product"><b><span class="price">4</span></b><span class="price">5</span></div>"#,
+        ];
+
+        let results = std::thread::scope(|scope| {
+            let workers = inputs
+                .iter()
+                .map(|input| {
+                    scope.spawn(move || {
+                        let dom = parse(input, ParserOptions::default()).unwrap();
+                        let parser = dom.parser();
+                        let texts = dom
+                            .query_selector_with(price())
+                            .map(|handle| handle.get(parser).unwrap().inner_text(parser))
+                            .collect::<Vec<_>>()
+                            .join(",");
+
+                        // the same as parsing the selector on every call
+                        assert_eq!(
+                            dom.query_selector_with(price()).count(),
+                            dom.query_selector(r#"div.product > span.price, [data-price="a\"b"]"#)
+                                .unwrap()
+                                .count()
+                        );
+                        texts
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            workers
+                .into_iter()
+                .map(|worker| worker.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+        assert_eq!(results, ["1", "3", "5"]);
+
+        let dom = parse(inputs[2], ParserOptions::default()).unwrap();
+        let div = dom.nodes()[0].as_tag().unwrap();
+        assert_eq!(div.query_selector_with(dom.parser(), price()).count(), 1);
+
+        let error = Selector::parse("div >").unwrap_err();
+        assert_eq!(
+            error.kind(),
+            crate::queryselector::ParseErrorKind::UnexpectedEnd
+        );
+    }
+
+    #[test]
+    fn query_selector_parse_errors() {
+        use crate::queryselector::ParseErrorKind::*;
+
+        let error = |selector| {
+            let error = crate::parse_query_selector_checked(selector).unwrap_err();
+            (error.kind(), error.offset())
+        };
+
+        assert_eq!(error(""), (EmptySelector, 0));
+        assert_eq!(error("a, "), (EmptySelector, 3));
+        assert_eq!(error("a,,b"), (EmptySelector, 2));
+        assert_eq!(error("div >> p"), (UnexpectedCharacter, 5));
+        assert_eq!(error("div]"), (UnexpectedCharacter, 3));
+        assert_eq!(error("div >"), (UnexpectedEnd, 5));
+        assert_eq!(error("p ~ !"), (UnexpectedCharacter, 4));
+        assert_eq!(error("a[href"), (UnterminatedAttribute, 1));
+        assert_eq!(error("a[href=x"), (UnterminatedAttribute, 1));
+        assert_eq!(error("a[href=x y]"), (UnexpectedCharacter, 9));
+        assert_eq!(error("a[href!=x]"), (UnexpectedCharacter, 6));
+        assert_eq!(error("a[href~x]"), (UnexpectedCharacter, 7));
+        assert_eq!(error(r#"a[href="/x]"#), (UnterminatedString, 7));
+        assert_eq!(error(r#"a[href="\"#), (UnterminatedString, 7));
+        assert_eq!(error("input:focus-visible"), (UnsupportedPseudoClass, 5));
+        assert_eq!(error("li:nth-child(x)"), (UnsupportedPseudoClass, 2));
+
+        let error = crate::parse_query_selector_checked("div >> p").unwrap_err();
+        assert_eq!(error.to_string(), "unexpected character at offset 5");
+
+        // errors can be bubbled up as `Box<dyn Error>`
+        let count = || -> Result<usize, Box<dyn std::error::Error>> {
+            let dom = parse("<div><p></p></div>", ParserOptions::default())?;
+            let count = dom.query_selector_checked("div > p")?.count();
+            let tag = dom.nodes()[0].as_tag().unwrap();
+            Ok(count + tag.query_selector_checked(dom.parser(), "p ~")?.count())
+        };
+        assert_eq!(
+            count().unwrap_err().to_string(),
+            "unexpected end of selector at offset 3"
+        );
+    }
+
+    #[test]
+    fn query_selector_forgiving() {
+        let dom = parse(
+            r#"<div>1</div><input value="2"><p class="x">3</p><a title="a, b">4</a>"#,
+            ParserOptions::default(),
+        )
+        .unwrap();
+        let parser = dom.parser();
+        let forgiving = |selector| {
+            let (matches, skipped) = dom.query_selector_forgiving(selector);
+            let texts = matches
+                .map(|handle| {
+                    let tag = handle.get(parser).unwrap().as_tag().unwrap();
+                    match tag.attributes().get("value").flatten() {
+                        Some(value) => value.as_utf8_str().into_owned(),
+                        None => tag.inner_text(parser).into_owned(),
+                    }
+                })
+                .collect::<Vec<_>>();
+            (texts.join(","), skipped)
+        };
+
+        let list = "div, input:focus-visible, .x";
+        assert!(dom.query_selector(list).is_none());
+        assert_eq!(forgiving(list), ("1,3".into(), vec!["input:focus-visible"]));
+
+        // valid lists behave like the strict mode
+        assert_eq!(forgiving("input, .x"), ("2,3".into(), vec![]));
+
+        // commas in quotes and parentheses do not end an entry, and empty entries are ignored
+        assert_eq!(
+            forgiving(r#"a[title="a, b"] ,:is(p, div),, [title='x, y'"#),
+            ("4".into(), vec![":is(p, div)", "[title='x, y'"])
+        );
+        assert_eq!(
+            forgiving("div > , ::before, input"),
+            ("2".into(), vec!["div >", "::before"])
+        );
+        assert_eq!(
+            forgiving("!!, div:hover"),
+            ("".into(), vec!["!!", "div:hover"])
+        );
+        assert_eq!(forgiving(r#"[a="\"#), ("".into(), vec![r#"[a="\"#]));
+        assert_eq!(forgiving(""), ("".into(), vec![]));
+    }
+
+    #[test]
+    fn query_selector_hyphen_prefix() {
+        let dom = parse(
+            r#"<p lang="en">1</p><p lang="en-US">2</p><p lang="ens">3</p><p lang="EN-gb">4</p><p lang="">5</p><p lang="-x">6</p><p lang="en-US-x">7</p><p>8</p>"#,
+            ParserOptions::default(),
+        )
+        .unwrap();
+        let parser = dom.parser();
+        let texts = |selector: &str| {
+            dom.query_selector(selector)
+                .unwrap_or_else(|| panic!("{selector} should be valid"))
+                .map(|handle| handle.get(parser).unwrap().inner_text(parser).into_owned())
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+
+        assert_eq!(texts(r#"[lang|="en"]"#), "1,2,7");
+        assert_eq!(texts("[lang|='en']"), "1,2,7");
+        assert_eq!(texts("[lang|=en]"), "1,2,7");
+        assert_eq!(texts("[lang|=en i]"), "1,2,4,7");
+
+        // hyphenated values match themselves and longer subtags
+        assert_eq!(texts("[lang|=en-US]"), "2,7");
+        assert_eq!(texts("[lang|=en-U]"), "");
+
+        // an empty value matches empty values and values starting with a hyphen
+        assert_eq!(texts(r#"[lang|=""]"#), "5,6");
+
+        assert!(crate::parse_query_selector("[lang|en]").is_none());
+    }
+
+    #[test]
+    fn query_selector_case_insensitive_attributes() {
+        let dom = parse(
+            r#"<input type="TEXT" class="Big Red"><input type="text" data-x="Prefix-Middle-Suffix">"#,
+            ParserOptions::default(),
+        )
+        .unwrap();
+        let count = |selector: &str| {
+            dom.query_selector_count(selector)
+                .unwrap_or_else(|| panic!("{selector} should be valid"))
+        };
+
+        // without a flag, values are compared case-sensitively
+        assert_eq!(count(r#"[type="text"]"#), 1);
+        assert_eq!(count(r#"[type="text" s]"#), 1);
+        assert_eq!(count("[type=text S]"), 1);
+
+        assert_eq!(count(r#"[type="text" i]"#), 2);
+        assert_eq!(count(r#"[type="tExT"I]"#), 2);
+        assert_eq!(count("[type=TeXt i]"), 2);
+        assert_eq!(count("[class~=red i]"), 1);
+        assert_eq!(count("[class~=red]"), 0);
+        assert_eq!(count("[data-x^=prefix i]"), 1);
+        assert_eq!(count("[data-x$=SUFFIX i]"), 1);
+        assert_eq!(count("[data-x*=-middle- i]"), 1);
+        assert_eq!(count("[data-x*=-middle-]"), 0);
+        assert_eq!(count(r#"[data-x$="" i]"#), 1);
+        assert_eq!(count("[data-x^=prefix-middle-suffix-and-more i]"), 0);
+
+        assert!(crate::parse_query_selector("[type=text x]").is_none());
+        assert!(crate::parse_query_selector("[type=text i i]").is_none());
+    }
+
+    #[test]
+    fn query_selector_nth_and_count() {
+        use crate::queryselector::visits;
+
+        let input = "<div><p>0</p><span></span><p>1</p></div>".repeat(100);
+        let mut dom = parse(&input, ParserOptions::default()).unwrap();
+
+        for cached in [false, true] {
+            let parser = dom.parser();
+            let all = dom.query_selector("div > p").unwrap().collect::<Vec<_>>();
+
+            assert_eq!(dom.query_selector_count("div > p"), Some(all.len()));
+            assert_eq!(dom.query_selector_count("p, span"), Some(300));
+            assert_eq!(dom.query_selector_count("table"), Some(0));
+            assert_eq!(dom.query_selector_count("p,"), None);
+
+            assert_eq!(dom.query_selector_nth("div > p", 0), all.first().copied());
+            assert_eq!(dom.query_selector_nth("div > p", 5), Some(all[5]));
+            assert_eq!(dom.query_selector_nth("div > p", 199), all.last().copied());
+            assert_eq!(dom.query_selector_nth("div > p", 200), None);
+            assert_eq!(dom.query_selector_nth("div > p", usize::MAX), None);
+            assert_eq!(dom.query_selector_nth("p,", 0), None);
+
+            let third = dom.query_selector_nth("p", 2).unwrap();
+            assert_eq!(third.get(parser).unwrap().inner_text(parser), "0");
+
+            if !cached {
+                // evaluation stops at the match
+                visits::take();
+                dom.query_selector_nth("p", 1);
+                assert_eq!(visits::take(), 5);
+            }
+
+            dom.enable_query_cache();
+        }
+    }
+
+    #[test]
+    fn custom_pseudo_classes() {
+        use crate::queryselector::{ParseErrorKind, Parser, SelectorContext};
+
+        let input = r#"<nav><a href="/">Home</a><a href="https://example.com/a">A</a></nav>
+<main><a href="https://example.com/b" lang="de">B</a><p lang="de-AT">Text</p><p>More</p></main>"#;
+        let dom = parse(input, ParserOptions::default()).unwrap();
+        let parser = dom.parser();
+
+        let mut context = SelectorContext::new();
+        context
+            .register(":external", |tag, _| {
+                tag.attributes()
+                    .get("href")
+                    .flatten()
+                    .is_some_and(|href| href.as_bytes().starts_with(b"https:"))
+            })
+            .register_with_argument("lang-is", |tag, _, argument| {
+                tag.attributes()
+                    .get("lang")
+                    .flatten()
+                    .is_some_and(|lang| lang.as_utf8_str().split('-').next() == Some(argument))
+            });
+        assert!(context.is_registered("external"));
+        assert!(context.is_registered(":lang-is"));
+        assert!(!context.is_registered("hover"));
+
+        let texts = |selector: &str| {
+            dom.query_selector_with_context(selector, &context)
+                .unwrap()
+                .map(|handle| handle.get(parser).unwrap().inner_text(parser).into_owned())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(texts("a:external"), ["A", "B"]);
+        assert_eq!(texts("nav > a:external:last-child"), ["A"]);
+        assert_eq!(texts("main :lang-is( de )"), ["B", "Text"]);
+        assert_eq!(
+            texts("p:lang-is(de), a:external:first-child"),
+            ["B", "Text"]
+        );
+        assert_eq!(
+            texts("p:first-child, main > :lang-is(en)"),
+            Vec::<String>::new()
+        );
+
+        // unregistered pseudo-classes, and registered ones used without a context or with a wrong argument, are rejected
+        assert!(dom
+            .query_selector_with_context("a:hover", &context)
+            .is_none());
+        assert!(dom
+            .query_selector_with_context("a:external(x)", &context)
+            .is_none());
+        assert!(dom
+            .query_selector_with_context("p:lang-is", &context)
+            .is_none());
+        assert!(dom
+            .query_selector_with_context("p:lang-is(de", &context)
+            .is_none());
+        assert!(dom.query_selector("a:external").is_none());
+
+        let error = Parser::new(b"a:external, a:visited")
+            .with_context(&context)
+            .selector()
+            .unwrap_err();
+        assert_eq!(error.kind(), ParseErrorKind::UnsupportedPseudoClass);
+        assert_eq!(error.offset(), 13);
+
+        // selectors with custom pseudo-classes never match without the context
+        let selector = Parser::new(b"a:external")
+            .with_context(&context)
+            .selector()
+            .unwrap();
+        let link = dom
+            .query_selector_first("a[href^=https]")
+            .unwrap()
+            .get(parser)
+            .unwrap();
+        assert!(selector.matches_with_context(link, parser, Default::default(), &context));
+        assert!(!selector.matches(link, parser));
+    }
 }
 
 #[test]
@@ -758,7 +1659,7 @@ fn boundaries() {
     let dom = parse("<div><p>haha</p></div>", Default::default()).unwrap();
     let span = dom.nodes()[1].as_tag().unwrap();
     let boundary = span.boundaries(dom.parser());
-    assert_eq!(boundary, (5, 15));
+    assert_eq!(boundary, Some((5, 15)));
 }
 
 #[test]
@@ -815,3 +1716,5038 @@ fn tag_raw_abrupt_stop() {
     let from_raw = first_tag.raw().try_as_utf8_str().unwrap();
     assert_eq!(from_raw, "<p>abcd</p");
 }
+
+#[test]
+fn attribute_get_url() {
+    use std::borrow::Cow;
+
+    let dom = parse(
+        "<a href=\"  /leading\"></a>\
+        <a href=\"/spam\n/ex\tample\r\n.html \"></a>\
+        <a href=\"https://example.com/clean\"></a>\
+        <a href></a>",
+        ParserOptions::default(),
+    )
+    .unwrap();
+
+    let urls = dom
+        .nodes()
+        .iter()
+        .map(|node| node.as_tag().unwrap().attributes().get_url("href"))
+        .collect::<Vec<_>>();
+
+    assert_eq!(urls[0].as_deref(), Some("/leading"));
+    assert_eq!(urls[1].as_deref(), Some("/spam/example.html"));
+    assert!(matches!(
+        urls[2],
+        Some(Cow::Borrowed("https://example.com/clean"))
+    ));
+    assert_eq!(urls[3], None);
+
+    let links = dom.links().map(|(_, url)| url).collect::<Vec<_>>();
+    assert_eq!(
+        links,
+        [
+            "/leading",
+            "/spam/example.html",
+            "https://example.com/clean"
+        ]
+    );
+}
+
+#[test]
+fn elements_with_id_and_class() {
+    let input = r#"
+        <div id="a" class="x y">
+            <p id="b" class="y">one</p>
+            <img id="a" class="z" src="x.png">
+            <span class="x  z">two</span>
+        </div>
+        <p id="c">three</p>
+    "#;
+
+    fn collect<'a>(
+        iter: impl Iterator<Item = (NodeHandle, &'a Bytes<'a>)>,
+    ) -> Vec<(NodeHandle, String)> {
+        iter.map(|(handle, value)| (handle, value.as_utf8_str().into_owned()))
+            .collect()
+    }
+
+    let untracked = parse(input, ParserOptions::default()).unwrap();
+    let tracked = parse(input, ParserOptions::default().track_ids().track_classes()).unwrap();
+
+    let ids = collect(untracked.elements_with_id());
+    assert_eq!(ids, collect(tracked.elements_with_id()));
+    assert_eq!(
+        ids.iter().map(|(_, id)| id.as_str()).collect::<Vec<_>>(),
+        ["a", "b", "a", "c"]
+    );
+
+    let classes = collect(untracked.elements_with_class());
+    assert_eq!(classes, collect(tracked.elements_with_class()));
+    assert_eq!(
+        classes
+            .iter()
+            .map(|(_, class)| class.as_str())
+            .collect::<Vec<_>>(),
+        ["x y", "y", "z", "x  z"]
+    );
+
+    // duplicate ids resolve to the first element in document order in both modes
+    assert_eq!(untracked.get_element_by_id("a"), Some(ids[0].0));
+    assert_eq!(tracked.get_element_by_id("a"), Some(ids[0].0));
+}
+
+#[test]
+fn raw_text_elements() {
+    let input = r#"<x-raw>{{#each items}}<div></notit></X-RAWR>{{/each}}</X-RAW><p>after</p>"#;
+    let options = ParserOptions::default().raw_text_elements(&["script", "style", "x-raw"]);
+
+    let dom = parse(input, options).unwrap();
+    let parser = dom.parser();
+    let element = dom.children()[0].get(parser).unwrap().as_tag().unwrap();
+
+    assert_eq!(element.children().top().len(), 1);
+    assert_eq!(
+        element.inner_text(parser),
+        "{{#each items}}<div></notit></X-RAWR>{{/each}}"
+    );
+    assert_eq!(dom.children().len(), 2);
+    assert_eq!(dom.outer_html(), input);
+
+    // not a raw text element by default
+    let dom = parse(input, ParserOptions::default()).unwrap();
+    assert!(dom.nodes()[0].as_tag().unwrap().children().top().len() > 1);
+}
+
+#[test]
+fn script_is_raw_text() {
+    let input = r#"<script>if (a < b && "</p>") { x = "<div>"; }</script><div></div>"#;
+    let dom = parse(input, ParserOptions::default()).unwrap();
+    let parser = dom.parser();
+    let script = dom.children()[0].get(parser).unwrap().as_tag().unwrap();
+
+    assert_eq!(script.children().top().len(), 1);
+    assert_eq!(dom.children().len(), 2);
+    assert_eq!(dom.outer_html(), input);
+
+    // unterminated raw text consumes the rest of the input
+    let dom = parse("<style>a > b { }", ParserOptions::default()).unwrap();
+    assert_eq!(dom.nodes().len(), 2);
+    assert_eq!(dom.nodes()[1].as_raw().unwrap().as_bytes(), b"a > b { }");
+}
+
+#[test]
+fn tag_categories() {
+    use crate::tag_info::TagCategory;
+
+    let dom = parse(
+        "<BR><div></div><span></span><meta><select></select><font></font><my-element></my-element><tr></tr>",
+        ParserOptions::default(),
+    )
+    .unwrap();
+    let tags = dom
+        .children()
+        .iter()
+        .map(|h| h.get(dom.parser()).unwrap().as_tag().unwrap())
+        .collect::<Vec<_>>();
+
+    // uppercase void elements do not swallow their siblings
+    assert_eq!(tags.len(), 8);
+
+    let [br, div, span, meta, select, font, custom, tr] = tags[..] else {
+        unreachable!()
+    };
+
+    assert!(br.is_void() && br.is_inline());
+    assert!(div.is_block_level() && !div.is_void() && !div.is_inline());
+    assert!(span.is_inline() && !span.is_block_level());
+    assert!(meta.is_metadata_content() && meta.is_void());
+    assert!(select.is_form_associated() && select.is_inline());
+    assert!(font.is_deprecated());
+    assert!(custom.is_inline() && !custom.is_void());
+    assert_eq!(custom.category(), TagCategory::INLINE);
+    assert_eq!(tr.category(), TagCategory::NONE);
+
+    assert!(TagCategory::of(b"BlockQuote").is_block_level());
+    assert_eq!(TagCategory::of(b"label"), TagCategory::INLINE);
+
+    // legacy void elements
+    for name in ["basefont", "bgsound", "command", "frame", "keygen"] {
+        assert!(TagCategory::of(name.as_bytes()).is_void(), "{name}");
+    }
+    let mut dom = parse("<command label=x>a<frame>b", ParserOptions::default()).unwrap();
+    assert_eq!(dom.children().len(), 4);
+    assert_eq!(dom.outer_html(), "<command label=x>a<frame>b");
+
+    // a generated start tag is not followed by an end tag either
+    let command = dom.nodes_mut()[0].as_tag_mut().unwrap();
+    command.attributes_mut().remove("label");
+    assert_eq!(dom.outer_html(), "<command>a<frame>b");
+    assert_eq!(
+        TagCategory::of(b"a-very-long-custom-element"),
+        TagCategory::INLINE
+    );
+}
+
+#[test]
+fn templating_syntax_in_attributes() {
+    let inputs = [
+        r#"<div {{#if x}}class="a"{{/if}} data-y="1">text</div>"#,
+        r#"<input value={user.name} onChange={() => set(1)} data-y="1"/>"#,
+    ];
+
+    for input in inputs {
+        let dom = parse(input, ParserOptions::default().collect_diagnostics()).unwrap();
+        let tag = dom.children()[0]
+            .get(dom.parser())
+            .unwrap()
+            .as_tag()
+            .unwrap();
+
+        assert_eq!(
+            tag.attributes().get("data-y"),
+            Some(Some(&"1".into())),
+            "{input}"
+        );
+        assert_eq!(dom.outer_html(), input);
+    }
+
+    let dom = parse(inputs[0], ParserOptions::default().collect_diagnostics()).unwrap();
+    let tag = dom.nodes()[0].as_tag().unwrap();
+    assert_eq!(tag.attributes().class(), Some(&"a".into()));
+    assert_eq!(tag.children().top().len(), 1);
+    assert_eq!(
+        dom.diagnostics(),
+        [
+            Diagnostic {
+                kind: DiagnosticKind::InvalidAttributeName,
+                at: 5
+            },
+            Diagnostic {
+                kind: DiagnosticKind::InvalidAttributeName,
+                at: 23
+            }
+        ]
+    );
+
+    // diagnostics are opt-in
+    let dom = parse(inputs[0], ParserOptions::default()).unwrap();
+    assert!(dom.diagnostics().is_empty());
+}
+
+#[test]
+fn mutated_start_tag_is_regenerated() {
+    let mut dom = parse(r#"<p   id="a"  hidden>x</p>"#, ParserOptions::default()).unwrap();
+    assert_eq!(dom.outer_html(), r#"<p   id="a"  hidden>x</p>"#);
+
+    let tag = dom.nodes_mut()[0].as_tag_mut().unwrap();
+    tag.attributes_mut().remove("hidden");
+    assert_eq!(dom.outer_html(), r#"<p id="a">x</p>"#);
+}
+
+#[test]
+fn query_cache() {
+    let mut dom = parse(
+        r#"<div class="a"></div><p class="a"></p><div></div><span class="a"></span>"#,
+        ParserOptions::default(),
+    )
+    .unwrap();
+    dom.enable_query_cache();
+
+    let fresh = dom.query_selector(".a").unwrap().collect::<Vec<_>>();
+    let cached = dom.query_selector(".a").unwrap().collect::<Vec<_>>();
+    assert_eq!(fresh.len(), 3);
+    assert_eq!(fresh, cached);
+    assert_eq!(dom.query_cache().unwrap().evaluations(), 1);
+
+    // invalid selectors are never cached
+    assert!(dom.query_selector("<").is_none());
+    assert_eq!(dom.query_cache().unwrap().evaluations(), 1);
+
+    // mutating the document invalidates the cache
+    let handle = fresh[0];
+    handle
+        .get_mut(dom.parser_mut())
+        .unwrap()
+        .as_tag_mut()
+        .unwrap()
+        .attributes_mut()
+        .remove("class");
+
+    let after = dom.query_selector(".a").unwrap().collect::<Vec<_>>();
+    assert_eq!(after, fresh[1..]);
+    assert_eq!(dom.query_cache().unwrap().evaluations(), 2);
+}
+
+#[test]
+fn meta_refresh() {
+    fn refresh(content: &str) -> Option<MetaRefresh> {
+        let input = format!(r#"<head><META HTTP-EQUIV="refresh" content="{content}"></head>"#);
+        let dom = parse(&input, ParserOptions::default()).unwrap();
+        dom.meta_refresh()
+    }
+
+    let expected = |delay, url: Option<&str>| {
+        Some(MetaRefresh {
+            delay,
+            url: url.map(String::from),
+        })
+    };
+
+    assert_eq!(refresh("0; url=/next"), expected(0, Some("/next")));
+    assert_eq!(refresh("5"), expected(5, None));
+    assert_eq!(refresh("0;URL='x'"), expected(0, Some("x")));
+    assert_eq!(refresh("1.5 , Url = 'a b' c"), expected(1, Some("a b")));
+    assert_eq!(refresh("3;/plain"), expected(3, Some("/plain")));
+    assert_eq!(refresh("garbage"), None);
+    assert_eq!(refresh("5garbage"), None);
+    assert_eq!(refresh(""), None);
+
+    let dom = parse(r#"<meta content="0; url=/x">"#, ParserOptions::default()).unwrap();
+    assert_eq!(dom.meta_refresh(), None);
+}
+
+#[test]
+fn id_rooted_query_visits_only_subtree() {
+    use crate::queryselector::visits;
+
+    let mut input = String::new();
+    for i in 0..1000 {
+        input.push_str(&format!(r#"<div class="item"><span>{i}</span></div>"#));
+    }
+    input
+        .push_str(r#"<ul id="list"><li class="item">a</li><li>b</li><li class="item">c</li></ul>"#);
+
+    let tracked = parse(&input, ParserOptions::default().track_ids()).unwrap();
+    let untracked = parse(&input, ParserOptions::default()).unwrap();
+
+    for selector in [
+        "#list",
+        "ul#list",
+        "#list .item",
+        "#list > li",
+        "#missing .item",
+    ] {
+        visits::take();
+        let fast = tracked
+            .query_selector(selector)
+            .unwrap()
+            .collect::<Vec<_>>();
+        let fast_visits = visits::take();
+
+        let slow = untracked
+            .query_selector(selector)
+            .unwrap()
+            .collect::<Vec<_>>();
+        let slow_visits = visits::take();
+
+        // same results, but only the subtree of #list (9 nodes) is visited
+        assert_eq!(fast, slow, "{selector}");
+        assert!(fast_visits <= 9, "{selector}: {fast_visits}");
+        assert_eq!(slow_visits, untracked.nodes().len(), "{selector}");
+    }
+
+    // selectors that are not rooted at an id still visit the whole document
+    visits::take();
+    assert_eq!(tracked.query_selector(".item").unwrap().count(), 1002);
+    assert_eq!(visits::take(), tracked.nodes().len());
+}
+
+#[test]
+fn content_after_document_element() {
+    let input = "<!DOCTYPE html><html><head></head><body><p>a</p></body>\n</html><div>b</div>c";
+
+    let mut dom = parse(input, ParserOptions::default()).unwrap();
+    let html = dom.document_element().unwrap();
+
+    // by default, trailing content stays at the top level
+    assert_eq!(dom.children().len(), 3);
+    assert_eq!(dom.children()[0], html);
+    assert_eq!(dom.outer_html(), input);
+
+    dom.normalize_document();
+
+    assert_eq!(dom.children(), [html]);
+    assert_eq!(dom.document_element(), Some(html));
+    assert_eq!(
+        dom.outer_html(),
+        "<!DOCTYPE html><html><head></head><body><p>a</p>\n<div>b</div>c</body></html>"
+    );
+
+    let parser = dom.parser();
+    let body = dom.query_selector("body").unwrap().next().unwrap();
+    let div = dom.query_selector("div").unwrap().next().unwrap();
+    let body_tag = body.get(parser).unwrap().as_tag().unwrap();
+    assert_eq!(
+        div.get(parser).unwrap().as_tag().unwrap().parent(),
+        Some(body)
+    );
+    assert_eq!(body_tag.children().top().len(), 4);
+    // the subtree of the body is still contiguous
+    assert_eq!(body_tag.children().all(parser).len(), 6);
+    assert_eq!(body_tag.inner_text(parser), "a\nbc");
+
+    // only the content of a second <html> element (e.g. from concatenated templates) is moved into the body
+    let mut dom = parse(
+        "<html><body>a</body></html><html>b</html>",
+        Default::default(),
+    )
+    .unwrap();
+    dom.normalize_document();
+    assert_eq!(dom.outer_html(), "<html><body>ab</body></html>");
+
+    let input = "<html><head></head><body><p>a</p></body></html>\n\
+                 <html><head><title>2</title></head><body><b>2</b></body></html><body><i>3</i></body>";
+    let mut dom = parse(input, Default::default()).unwrap();
+    let second = dom.children()[2];
+    dom.normalize_document();
+    assert_eq!(
+        dom.outer_html(),
+        "<html><head></head><body><p>a</p>\n<title>2</title><b>2</b><i>3</i></body></html>"
+    );
+    assert!(second.get(dom.parser()).is_none());
+    assert_eq!(dom.query_selector("html").unwrap().count(), 1);
+    assert_eq!(dom.query_selector("body").unwrap().count(), 1);
+
+    let parser = dom.parser();
+    let body = dom.query_selector("body").unwrap().next().unwrap();
+    let b = dom.query_selector("b").unwrap().next().unwrap();
+    assert_eq!(
+        b.get(parser).unwrap().as_tag().unwrap().parent(),
+        Some(body)
+    );
+    let body_tag = body.get(parser).unwrap().as_tag().unwrap();
+    assert_eq!(body_tag.children().top().len(), 5);
+    assert_eq!(body_tag.children().all(parser).len(), 9);
+}
+
+#[test]
+fn node_paths() {
+    let input = r#"
+        <!-- header -->
+        <div id="a">
+            text
+            <ul><li>1</li><li>2<b>!</b></li></ul>
+            <br>
+            <p>last</p>
+        </div>
+        <span>tail</span>
+    "#;
+    let mut dom = parse(input, ParserOptions::default()).unwrap();
+
+    // every node round-trips
+    let paths = (0..dom.nodes().len())
+        .map(|id| NodeHandle::new(id as u32))
+        .map(|handle| (handle, handle.node_path(dom.parser())))
+        .collect::<Vec<_>>();
+
+    for (handle, path) in &paths {
+        assert!(!path.is_empty());
+        assert_eq!(dom.resolve_path(path), Some(*handle));
+    }
+
+    let b = dom.query_selector("b").unwrap().next().unwrap();
+    assert_eq!(b.node_path(dom.parser()).indices(), [3, 1, 1, 1]);
+
+    assert_eq!(dom.resolve_path(&NodePath::default()), None);
+    assert_eq!(dom.resolve_path(&vec![3, 1, 1, 9].into()), None);
+    assert_eq!(dom.resolve_path(&vec![100].into()), None);
+    // text nodes have no children
+    assert_eq!(dom.resolve_path(&vec![3, 0, 0].into()), None);
+
+    // after removing nodes, stale paths fail cleanly
+    let ul = dom.query_selector("ul").unwrap().next().unwrap();
+    let ul = ul.get_mut(dom.parser_mut()).unwrap().as_tag_mut().unwrap();
+    ul.children_mut().top_mut().remove(1);
+
+    let stale = b.node_path(dom.parser());
+    assert!(stale.is_empty());
+    assert_eq!(dom.resolve_path(&paths[b.get_inner() as usize].1), None);
+}
+
+#[test]
+fn wrap_text_range() {
+    let input = "<div><p>Hello wörld, hello!</p><span>x</span></div>";
+
+    for (term, expected) in [
+        ("wörld", "<p>Hello <mark>wörld</mark>, hello!</p>"),
+        ("Hello", "<p><mark>Hello</mark> wörld, hello!</p>"),
+        ("hello!", "<p>Hello wörld, <mark>hello!</mark></p>"),
+        (
+            "Hello wörld, hello!",
+            "<p><mark>Hello wörld, hello!</mark></p>",
+        ),
+    ] {
+        let mut dom = parse(input, ParserOptions::default()).unwrap();
+        let p = dom.query_selector("p").unwrap().next().unwrap();
+        let span = dom.query_selector("span").unwrap().next().unwrap();
+        let text = p
+            .get(dom.parser())
+            .unwrap()
+            .as_tag()
+            .unwrap()
+            .children()
+            .top()[0];
+
+        let start = "Hello wörld, hello!".find(term).unwrap();
+        let mark = dom
+            .wrap_text_range(text, start..start + term.len(), "mark")
+            .unwrap();
+
+        let parser = dom.parser();
+        let p_tag = p.get(parser).unwrap().as_tag().unwrap();
+        assert_eq!(p_tag.outer_html(parser), expected);
+        assert_eq!(p_tag.inner_text(parser), "Hello wörld, hello!");
+        assert_eq!(mark.get(parser).unwrap().inner_text(parser), term);
+        assert_eq!(
+            mark.get(parser).unwrap().as_tag().unwrap().parent(),
+            Some(p)
+        );
+
+        // handles of other nodes stay valid, and the document can still be queried
+        assert_eq!(span.get(parser).unwrap().inner_text(parser), "x");
+        assert_eq!(
+            dom.query_selector("mark").unwrap().collect::<Vec<_>>(),
+            [mark]
+        );
+        assert_eq!(dom.query_selector("div *").unwrap().count(), 3);
+        assert_eq!(dom.resolve_path(&mark.node_path(parser)), Some(mark));
+    }
+
+    let mut dom = parse(input, ParserOptions::default()).unwrap();
+    let text = NodeHandle::new(2);
+    // not a char boundary (inside of ö)
+    assert_eq!(dom.split_text(text, 8), None);
+    assert_eq!(dom.wrap_text_range(text, 6..8, "mark"), None);
+    assert_eq!(dom.wrap_text_range(text, 3..3, "mark"), None);
+    assert_eq!(dom.wrap_text_range(text, 3..100, "mark"), None);
+    assert_eq!(dom.wrap_text_range(text, 0..3, "<b>"), None);
+    // not a text node
+    assert_eq!(dom.split_text(NodeHandle::new(1), 1), None);
+    assert_eq!(dom.split_text(text, 0), None);
+    assert_eq!(dom.outer_html(), input);
+}
+
+#[test]
+fn sections_between_headings() {
+    let input = r#"<body>
+        <h2 id="intro">Intro</h2><p>Welcome</p>
+        <h2 id="usage">Usage</h2><p>Install <b>it</b></p><h3>Details</h3><ul><li>One</li></ul>
+        <h2 id="faq">FAQ</h2><p>None yet</p>
+    </body>"#;
+    let dom = parse(input, ParserOptions::default().track_ids()).unwrap();
+    let parser = dom.parser();
+    let usage = dom.get_element_by_id("usage").unwrap();
+    let faq = dom.get_element_by_id("faq").unwrap();
+
+    let names = |iter: &mut dyn Iterator<Item = NodeHandle>| {
+        iter.filter_map(|handle| handle.get(parser).unwrap().as_tag())
+            .map(|tag| tag.name().as_utf8_str().into_owned())
+            .collect::<Vec<_>>()
+    };
+    let text = |iter: &mut dyn Iterator<Item = NodeHandle>| {
+        iter.filter_map(|handle| handle.get(parser).unwrap().as_raw())
+            .map(|raw| raw.as_utf8_str().trim().to_string())
+            .filter(|text| !text.is_empty())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        names(&mut dom.nodes_between(usage, faq)),
+        ["p", "b", "h3", "ul", "li"]
+    );
+    assert_eq!(
+        text(&mut dom.nodes_between(usage, faq)),
+        ["Install", "it", "Details", "One"]
+    );
+
+    // h3 does not end the section of a h2
+    assert_eq!(
+        names(&mut dom.section_after_heading(usage).unwrap()),
+        ["p", "b", "h3", "ul", "li"]
+    );
+    // the last section runs until the end of the document
+    assert_eq!(
+        text(&mut dom.section_after_heading(faq).unwrap()),
+        ["None yet"]
+    );
+
+    // end before start, and end inside start
+    assert_eq!(dom.nodes_between(faq, usage).count(), 0);
+    let body = dom.query_selector("body").unwrap().next().unwrap();
+    assert_eq!(dom.nodes_between(body, faq).count(), 0);
+    // ancestors of end are skipped
+    assert_eq!(
+        names(&mut dom.nodes_between(NodeHandle::new(0), usage)),
+        [] as [String; 0]
+    );
+    assert!(dom.section_after_heading(body).is_none());
+}
+
+#[test]
+fn known_attributes() {
+    let dom = parse(
+        r#"<img SRC="a.png" Alt=logo data-src="b.png" loading ID=main class=x hidden>"#,
+        ParserOptions::default().track_ids(),
+    )
+    .unwrap();
+    let tag = dom.nodes()[0].as_tag().unwrap();
+    let attributes = tag.attributes();
+
+    // recognition is case-insensitive
+    assert_eq!(KnownAttr::from_bytes(b"sRc"), Some(KnownAttr::Src));
+    assert_eq!(
+        KnownAttr::from_bytes(b"http-equiv"),
+        Some(KnownAttr::HttpEquiv)
+    );
+    assert_eq!(KnownAttr::from_bytes(b"data-src"), None);
+    assert_eq!(KnownAttr::from_bytes(b"placeholders"), None);
+    for &attr in KnownAttr::ALL {
+        assert_eq!(KnownAttr::from_bytes(attr.name().as_bytes()), Some(attr));
+    }
+
+    assert_eq!(
+        attributes.get_known(KnownAttr::Src),
+        Some(Some(&"a.png".into()))
+    );
+    assert_eq!(
+        attributes.get_known(KnownAttr::Alt),
+        Some(Some(&"logo".into()))
+    );
+    assert_eq!(attributes.get_known(KnownAttr::Loading), Some(None));
+    assert_eq!(
+        attributes.get_known(KnownAttr::Id),
+        Some(Some(&"main".into()))
+    );
+    assert_eq!(
+        attributes.get_known(KnownAttr::Class),
+        Some(Some(&"x".into()))
+    );
+    assert_eq!(attributes.get_known(KnownAttr::Href), None);
+    assert_eq!(dom.get_element_by_id("main"), Some(NodeHandle::new(0)));
+
+    // the generic path still works, for known and unknown attributes
+    assert_eq!(attributes.get("src"), Some(Some(&"a.png".into())));
+    assert_eq!(attributes.get("data-src"), Some(Some(&"b.png".into())));
+    assert_eq!(attributes.get("DATA-SRC"), Some(Some(&"b.png".into())));
+    assert_eq!(attributes.len(), 7);
+
+    // known names are normalized
+    let mut names = attributes
+        .iter()
+        .map(|(k, _)| k.into_owned())
+        .collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(
+        names,
+        ["alt", "class", "data-src", "hidden", "id", "loading", "src"]
+    );
+
+    let mut tag = tag.clone();
+    let attributes = tag.attributes_mut();
+    attributes.insert("src", Some("c.png"));
+    assert_eq!(
+        attributes.get_known(KnownAttr::Src),
+        Some(Some(&"c.png".into()))
+    );
+    assert_eq!(attributes.remove("ALT"), Some(Some("logo".into())));
+    assert_eq!(attributes.get_known(KnownAttr::Alt), None);
+    assert_eq!(attributes.len(), 6);
+}
+
+#[test]
+fn link_relations() {
+    let input = r#"<!DOCTYPE html>
+    <html>
+        <head>
+            <meta charset="utf-8">
+            <link rel="canonical" href=" https://example.com/blog/ ">
+            <link rel="shortcut icon" href="/favicon.ico">
+            <link rel="icon" type="image/png" sizes="16x16 32x32" href="/icon-32.png">
+            <link rel="apple-touch-icon" sizes="180x180" href="/apple.png">
+            <link rel="icon" type="image/svg+xml" sizes="any" href="/icon.svg">
+            <link rel="alternate" type="application/rss+xml" title="RSS" href="/feed.xml">
+            <link rel="alternate" type="application/atom+xml" href="/atom.xml">
+            <link rel="alternate" hreflang="de" href="https://example.com/de/blog/">
+            <link rel="preload stylesheet" href="/main.css" media="(min-width: 600px)">
+        </head>
+        <body><a rel="nofollow" href="/x">x</a></body>
+    </html>"#;
+    let dom = parse(input, ParserOptions::default()).unwrap();
+
+    assert_eq!(dom.link_relations().count(), 9);
+    assert_eq!(
+        dom.canonical_url().as_deref(),
+        Some("https://example.com/blog/")
+    );
+
+    let feeds = dom.feeds().filter_map(|x| x.href).collect::<Vec<_>>();
+    assert_eq!(feeds, ["/feed.xml", "/atom.xml"]);
+
+    let icons = dom.icons();
+    let hrefs = icons
+        .iter()
+        .filter_map(|x| x.href.as_deref())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        hrefs,
+        ["/icon.svg", "/apple.png", "/icon-32.png", "/favicon.ico"]
+    );
+    assert_eq!(icons[2].parsed_sizes(), [(16, 16), (32, 32)]);
+    assert!(icons[0].is_any_size());
+
+    let alternate = dom.link_relations().find(|x| x.hreflang.is_some()).unwrap();
+    assert_eq!(
+        alternate,
+        LinkRel {
+            rel: vec!["alternate".into()],
+            href: Some("https://example.com/de/blog/".into()),
+            mime_type: None,
+            hreflang: Some("de".into()),
+            media: None,
+            sizes: None,
+        }
+    );
+    assert!(!alternate.is_feed());
+
+    let preload = dom.link_relations().last().unwrap();
+    assert_eq!(preload.rel, ["preload", "stylesheet"]);
+    assert_eq!(preload.media.as_deref(), Some("(min-width: 600px)"));
+}
+
+#[test]
+fn decoded_inner_text() {
+    let input = r#"<div>
+        <p id="plain">Just some text</p>
+        <p id="refs">Fish &amp; Chips &lt;3 &#8364;5 &#x1F600; &copy 2024 &unknown; & &#0; &#150;</p>
+        <p id="mixed">A&nbsp;<b>B &quot;C&quot;</b> <!-- &amp; --> D</p>
+        <script>if (a &amp;&amp; b) {}</script>
+    </div>"#;
+    let dom = parse(input, ParserOptions::default().track_ids()).unwrap();
+    let parser = dom.parser();
+
+    // the parser only flags text nodes with an `&`
+    let plain = dom.get_element_by_id("plain").unwrap();
+    let refs = dom.get_element_by_id("refs").unwrap();
+    let text_of = |handle: NodeHandle| {
+        let tag = handle.get(parser).unwrap().as_tag().unwrap();
+        tag.children().top()[0]
+            .get(parser)
+            .unwrap()
+            .as_raw()
+            .unwrap()
+    };
+    assert!(!text_of(plain).may_contain_char_refs());
+    assert!(text_of(refs).may_contain_char_refs());
+
+    let decode_all = || {
+        dom.nodes()
+            .iter()
+            .map(|node| node.inner_text_decoded(parser).into_owned())
+            .collect::<Vec<_>>()
+    };
+
+    let with_fast_path = decode_all();
+    crate::bytes::char_ref_fast_path::set_enabled(false);
+    assert!(text_of(plain).may_contain_char_refs());
+    let without_fast_path = decode_all();
+    crate::bytes::char_ref_fast_path::set_enabled(true);
+    assert_eq!(with_fast_path, without_fast_path);
+
+    let decoded = |id: &str| {
+        let handle = dom.get_element_by_id(id).unwrap();
+        let tag = handle.get(parser).unwrap().as_tag().unwrap();
+        tag.inner_text_decoded(parser).into_owned()
+    };
+    assert_eq!(decoded("plain"), "Just some text");
+    assert_eq!(
+        decoded("refs"),
+        "Fish & Chips <3 €5 😀 © 2024 &unknown; & \u{fffd} –"
+    );
+    assert_eq!(decoded("mixed"), "A\u{a0}B \"C\"  D");
+}
+
+#[test]
+fn validation() {
+    use crate::validate::{Rule, RuleGroup, ValidationRules};
+
+    let input = r#"<body>
+        <h1>Title</h1>
+        <h3 id="x">Skipped</h3>
+        <ul><li>ok</li></ul>
+        <div><li>stray</li></div>
+        <table><tr><td>ok</td></tr></table>
+        <div><td>stray</td></div>
+        <img src="a.png" alt=""><IMG src="b.png">
+        <a href="/a"><span><a href="/b">nested</a></span></a>
+        <p id="x" style="color: red">Duplicate</p>
+    </body>"#;
+    let dom = parse(input, ParserOptions::default()).unwrap();
+    let parser = dom.parser();
+
+    let summary = |rules: &ValidationRules| {
+        dom.validate(rules)
+            .into_iter()
+            .map(|issue| {
+                let tag = issue.handle.get(parser).unwrap().as_tag().unwrap();
+                (issue.rule, tag.inner_text(parser).into_owned())
+            })
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        summary(&ValidationRules::new()),
+        [
+            (Rule::SkippedHeadingLevel, "Skipped".into()),
+            (Rule::ListItemOutsideList, "stray".into()),
+            (Rule::TableCellOutsideRow, "stray".into()),
+            (Rule::ImageWithoutAlt, String::new()),
+            (Rule::NestedLink, "nested".into()),
+            (Rule::DuplicateId, "Duplicate".into()),
+        ]
+    );
+
+    let issues = dom.validate(&ValidationRules::new());
+    assert_eq!(
+        issues[0].message,
+        "<h3> follows <h1>, skipping a heading level"
+    );
+    assert_eq!(issues[0].rule.id(), "skipped-heading-level");
+
+    let rules = ValidationRules::new()
+        .disable(RuleGroup::ALL)
+        .enable(RuleGroup::IDS)
+        .custom("no-inline-style", |tag, _| {
+            tag.attributes()
+                .contains("style")
+                .then(|| format!("<{}> has an inline style", tag.name().as_utf8_str()))
+        });
+    let issues = dom.validate(&rules);
+    assert_eq!(issues.len(), 2);
+    assert_eq!(issues[0].rule, Rule::DuplicateId);
+    assert_eq!(issues[1].rule, Rule::Custom("no-inline-style"));
+    assert_eq!(issues[1].message, "<p> has an inline style");
+    assert_eq!(issues[0].handle, issues[1].handle);
+
+    assert!(dom
+        .validate(&ValidationRules::new().disable(RuleGroup::ALL))
+        .is_empty());
+}
+
+#[test]
+fn owned_snapshots() {
+    let (shallow, deep) = {
+        let input = String::from(
+            r#"<ul class="menu" data-x><li><a href="/a">A</a></li><li>B <!-- c --></li></ul>"#,
+        );
+        let dom = parse(&input, ParserOptions::default()).unwrap();
+        let ul = dom.nodes()[0].as_tag().unwrap();
+
+        (
+            ul.to_owned_snapshot(dom.parser()),
+            ul.to_owned_snapshot_with_depth(dom.parser(), 1),
+        )
+    };
+
+    assert_eq!(shallow.name, "ul");
+    assert_eq!(
+        shallow.attributes,
+        [
+            ("class".into(), Some("menu".into())),
+            ("data-x".into(), None)
+        ]
+    );
+    assert_eq!(shallow.attribute("data-x"), Some(None));
+    assert_eq!(shallow.attribute("id"), None);
+    assert_eq!(shallow.text, "AB ");
+    assert!(shallow.children.is_empty());
+
+    // the children of `<li>` are beyond the configured depth
+    assert_eq!(deep.children.len(), 2);
+    assert_eq!(deep.children[0].name, "li");
+    assert_eq!(deep.children[0].text, "A");
+    assert!(deep.children[0].children.is_empty());
+    assert_eq!(deep.clone(), deep);
+    assert_ne!(deep, shallow);
+}
+
+#[test]
+fn parse_progress() {
+    use std::ops::ControlFlow;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    };
+
+    let input = "<div><p>Hello</p></div>".repeat(1000);
+    let total = input.len();
+
+    let reports = Arc::new(Mutex::new(Vec::new()));
+    let options = ParserOptions::default().on_progress(1000, {
+        let reports = Arc::clone(&reports);
+        move |consumed, total| {
+            reports.lock().unwrap().push((consumed, total));
+            ControlFlow::Continue(())
+        }
+    });
+    parse(&input, options).unwrap();
+
+    let reports = reports.lock().unwrap();
+    assert!(reports.len() >= total / 1000);
+    assert!(reports.len() <= total / 1000 + 1);
+    // all but the final report are at least `granularity` bytes apart
+    let (_, intermediate) = reports.split_last().unwrap();
+    assert!(intermediate.windows(2).all(|x| x[1].0 - x[0].0 >= 1000));
+    assert!(reports.iter().all(|&(_, t)| t == total));
+    assert_eq!(reports.last(), Some(&(total, total)));
+
+    // cancel halfway
+    let calls = Arc::new(AtomicUsize::new(0));
+    let cancel_halfway = |calls: Arc<AtomicUsize>| {
+        move |consumed, total| {
+            calls.fetch_add(1, Ordering::Relaxed);
+            match consumed >= total / 2 {
+                true => ControlFlow::Break(()),
+                false => ControlFlow::Continue(()),
+            }
+        }
+    };
+
+    let options = ParserOptions::default().on_progress(100, cancel_halfway(Arc::clone(&calls)));
+    assert_eq!(parse(&input, options).unwrap_err(), ParseError::Cancelled);
+    assert!(calls.load(Ordering::Relaxed) > 1);
+
+    let options = ParserOptions::default()
+        .on_progress(100, cancel_halfway(Arc::clone(&calls)))
+        .partial_on_cancel();
+    let dom = parse(&input, options).unwrap();
+    assert!(dom.is_partial());
+    let divs = dom.query_selector("div").unwrap().count();
+    assert!((500..510).contains(&divs), "{divs}");
+
+    assert!(!parse(&input, ParserOptions::default().partial_on_cancel())
+        .unwrap()
+        .is_partial());
+}
+
+#[test]
+fn attributes_separated_by_ascii_whitespace() {
+    let dom = parse(
+        "<input\ttype=text\rname=q\x0Cvalue=x\tdisabled>",
+        ParserOptions::default(),
+    )
+    .unwrap();
+    let attributes = dom.nodes()[0].as_tag().unwrap().attributes();
+
+    assert_eq!(
+        attributes.get("type").flatten().unwrap().as_utf8_str(),
+        "text"
+    );
+    assert_eq!(attributes.get("name").flatten().unwrap().as_utf8_str(), "q");
+    assert_eq!(
+        attributes.get("value").flatten().unwrap().as_utf8_str(),
+        "x"
+    );
+    assert_eq!(attributes.get("disabled"), Some(None));
+}
+
+#[test]
+fn normalize_newlines() {
+    let input = "<p class=\"a\r\nb\" title=\"x\ry\">one\r\ntwo\rthree\n</p><pre>\r\n1\r\r\n2</pre><script>a\r\nb</script><span>plain</span>";
+
+    let dom = parse(input, ParserOptions::default().normalize_newlines()).unwrap();
+    let parser = dom.parser();
+    let text = |selector: &str| {
+        let node = dom.query_selector(selector).unwrap().next().unwrap();
+        node.get(parser).unwrap().inner_text(parser).into_owned()
+    };
+
+    assert_eq!(text("p"), "one\ntwo\nthree\n");
+    assert_eq!(text("pre"), "\n1\n\n2");
+    assert_eq!(text("script"), "a\nb");
+
+    let p = dom.query_selector("p").unwrap().next().unwrap();
+    let attributes = p.get(parser).unwrap().as_tag().unwrap().attributes();
+    assert_eq!(attributes.class().map(|x| x.as_bytes()), Some(&b"a\nb"[..]));
+    assert_eq!(
+        attributes.get("title").flatten().map(|x| x.as_bytes()),
+        Some(&b"x\ny"[..])
+    );
+
+    // text without a `\r` is still borrowed from the input
+    let span = dom.query_selector("span").unwrap().next().unwrap();
+    let span = span.get(parser).unwrap().as_tag().unwrap();
+    let text = span.children().top()[0]
+        .get(parser)
+        .unwrap()
+        .as_raw()
+        .unwrap();
+    assert!(input.as_bytes().as_ptr_range().contains(&text.as_ptr()));
+
+    // without the option, the source is kept as is
+    let dom = parse(input, ParserOptions::default()).unwrap();
+    assert_eq!(dom.outer_html(), input);
+}
+
+/// A minimal XML well-formedness checker: elements must be nested and closed properly, attributes must be quoted
+/// and unique, and `&` must only start one of the predefined entities or a character reference
+fn assert_well_formed_xml(xml: &str) {
+    fn check_refs(text: &str) {
+        assert!(!text.contains('<'), "unescaped `<` in {text:?}");
+        for (idx, _) in text.match_indices('&') {
+            let reference =
+                &text[idx + 1..text[idx..].find(';').map_or(text.len(), |end| idx + end)];
+            let is_valid = matches!(reference, "amp" | "lt" | "gt" | "quot" | "apos")
+                || reference
+                    .strip_prefix("#x")
+                    .is_some_and(|x| !x.is_empty() && x.chars().all(|c| c.is_ascii_hexdigit()))
+                || reference
+                    .strip_prefix('#')
+                    .is_some_and(|x| !x.is_empty() && x.chars().all(|c| c.is_ascii_digit()));
+            assert!(is_valid, "invalid reference `&{reference};` in {text:?}");
+        }
+    }
+
+    let is_name_char = |c: char| c.is_alphanumeric() || matches!(c, '_' | ':' | '-' | '.');
+    let mut stack = Vec::new();
+    let mut roots = 0;
+    let mut rest = xml;
+
+    while !rest.is_empty() {
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            let end = comment.find("-->").expect("unterminated comment");
+            assert!(!comment[..end].contains("--"));
+            rest = &comment[end + 3..];
+        } else if let Some(end_tag) = rest.strip_prefix("</") {
+            let end = end_tag.find('>').expect("unterminated end tag");
+            assert_eq!(stack.pop(), Some(&end_tag[..end]), "mismatched end tag");
+            rest = &end_tag[end + 1..];
+        } else if let Some(start_tag) = rest.strip_prefix('<') {
+            let end = start_tag.find(|c| !is_name_char(c)).unwrap();
+            let name = &start_tag[..end];
+            assert!(!name.is_empty());
+            if stack.is_empty() {
+                roots += 1;
+            }
+
+            let mut attributes = Vec::new();
+            rest = &start_tag[end..];
+            loop {
+                let trimmed = rest.trim_start();
+                if let Some(after) = trimmed.strip_prefix("/>") {
+                    rest = after;
+                    break;
+                } else if let Some(after) = trimmed.strip_prefix('>') {
+                    stack.push(name);
+                    rest = after;
+                    break;
+                }
+
+                assert!(
+                    trimmed.len() < rest.len(),
+                    "attributes must be separated by whitespace"
+                );
+                let (key, value) = trimmed.split_once('=').expect("attribute without value");
+                assert!(
+                    !key.is_empty() && key.chars().all(is_name_char),
+                    "invalid attribute name {key:?}"
+                );
+                assert!(!attributes.contains(&key), "duplicate attribute {key:?}");
+                attributes.push(key);
+
+                let value = value.strip_prefix('"').expect("unquoted attribute value");
+                let end = value.find('"').expect("unterminated attribute value");
+                check_refs(&value[..end]);
+                rest = &value[end + 1..];
+            }
+        } else {
+            let end = rest.find('<').unwrap_or(rest.len());
+            assert!(
+                !stack.is_empty() || rest[..end].trim().is_empty(),
+                "text outside of the root"
+            );
+            check_refs(&rest[..end]);
+            rest = &rest[end..];
+        }
+    }
+
+    assert!(stack.is_empty(), "unclosed elements: {stack:?}");
+    assert_eq!(roots, 1);
+}
+
+#[test]
+fn xml_export() {
+    use crate::errors::XmlExportError;
+
+    let input = concat!(
+        r#"<div id=main class='a "b"'><p>Fish &amp; Chips &nbsp;&lt;5&gt; AT&T<br>"#,
+        r#"<input type=checkbox checked disabled><img src="x.png" alt="&quot;x&quot;"></p>"#,
+        r#"<!-- note --><script>if (a < b && c) {}</script><hr></div>"#
+    );
+    let dom = parse(input, ParserOptions::default()).unwrap();
+    let parser = dom.parser();
+    let div = dom.nodes()[0].as_tag().unwrap();
+
+    let xml = div.to_xml(parser).unwrap();
+    assert_eq!(
+        xml,
+        concat!(
+            r#"<div class="a &quot;b&quot;" id="main"><p>Fish &amp; Chips "#,
+            "\u{a0}&lt;5&gt; AT&amp;T<br/>",
+            r#"<input checked="checked" disabled="disabled" type="checkbox"/><img alt="&quot;x&quot;" src="x.png"/></p>"#,
+            r#"<!-- note --><script>if (a &lt; b &amp;&amp; c) {}</script><hr/></div>"#
+        )
+    );
+    assert_well_formed_xml(&xml);
+
+    let to_xml = |input| {
+        let dom = parse(input, ParserOptions::default()).unwrap();
+        let result = dom.nodes()[0].as_tag().unwrap().to_xml(dom.parser());
+        result
+    };
+
+    assert_eq!(
+        to_xml("<p><!-- a -- b --></p>"),
+        Err(XmlExportError::InvalidComment(" a -- b ".into()))
+    );
+    assert_eq!(
+        to_xml("<p 1a=x></p>"),
+        Err(XmlExportError::InvalidAttributeName("1a".into()))
+    );
+    assert_eq!(
+        to_xml("<p>a\u{1}b</p>"),
+        Err(XmlExportError::InvalidCharacter('\u{1}'))
+    );
+}
+
+#[test]
+fn structural_equality() {
+    let options = || ParserOptions::default().normalize_newlines();
+    let a = parse(
+        "<div id=main class='a b'><p>one\r\ntwo</p><!-- c --><br><input checked></div>",
+        options(),
+    )
+    .unwrap();
+    let b = parse(
+        "<DIV class=\"a b\" id=\"main\"><p>one\ntwo</p><!-- c --><br ><input checked></DIV>",
+        options(),
+    )
+    .unwrap();
+
+    fn node<'p, 'a>(dom: &'p VDom<'a>, index: usize) -> NodeRef<'p, 'a> {
+        NodeRef::new(&dom.nodes()[index], dom.parser())
+    }
+
+    assert_eq!(node(&a, 0), node(&b, 0));
+    assert!(a.nodes()[0].eq_in(a.parser(), &b.nodes()[0], b.parser()));
+    assert_eq!(
+        a.nodes()[0].as_tag().unwrap().attributes(),
+        b.nodes()[0].as_tag().unwrap().attributes()
+    );
+
+    for other in [
+        "<div id=main class='a b'><p>one\ntwo!</p><!-- c --><br><input checked></div>",
+        "<div id=main class='a b' title><p>one\ntwo</p><!-- c --><br><input checked></div>",
+        "<div id=main class='b a'><p>one\ntwo</p><!-- c --><br><input checked></div>",
+        "<div id=main class='a b'><p>one\ntwo</p><!-- d --><br><input checked></div>",
+        "<div id=main class='a b'><p>one\ntwo</p><br><input checked></div>",
+        "<div id=main class='a b'><p>one\ntwo</p><!-- c --><br><input checked=checked></div>",
+        "<section id=main class='a b'><p>one\ntwo</p><!-- c --><br><input checked></section>",
+    ] {
+        let other = parse(other, options()).unwrap();
+        assert_ne!(node(&a, 0), node(&other, 0));
+    }
+
+    // text is compared by bytes, so line breaks only match if they are normalized
+    let raw = parse("<p>one\r\ntwo</p>", ParserOptions::default()).unwrap();
+    let normalized = parse("<p>one\r\ntwo</p>", options()).unwrap();
+    assert_ne!(node(&raw, 0), node(&normalized, 0));
+    assert_eq!(node(&raw, 0), node(&raw, 0));
+}
+
+#[test]
+fn truncated_storage() {
+    let long_text = "a".repeat(1000);
+    // 'é' is two bytes long and starts at index 9, so a limit of 10 would cut it in half
+    let input = format!(
+        r#"<div data-json='{long_text}' title="123456789é" id=short><p>{long_text}</p><p>123456789é!</p><p>short</p><script>{long_text}</script></div>"#
+    );
+    let options = ParserOptions::default()
+        .max_stored_text_len(10)
+        .max_stored_attribute_len(10);
+    let dom = parse(&input, options).unwrap();
+
+    let div = dom.nodes()[0].as_tag().unwrap();
+    let attributes = div.attributes();
+    assert_eq!(
+        attributes.get("data-json").flatten().unwrap().as_bytes(),
+        b"aaaaaaaaaa"
+    );
+    assert_eq!(
+        attributes.get("title").flatten().unwrap().as_bytes(),
+        b"123456789"
+    );
+    assert_eq!(attributes.id().unwrap().as_bytes(), b"short");
+    assert!(attributes.is_truncated("data-json"));
+    assert!(attributes.is_truncated("title"));
+    assert!(!attributes.is_truncated("id"));
+    assert!(!attributes.is_truncated("missing"));
+
+    let texts = dom
+        .nodes()
+        .iter()
+        .filter_map(|node| Some((node.as_raw()?.as_utf8_str(), node.is_truncated())))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        texts,
+        [
+            ("aaaaaaaaaa".into(), true),
+            ("123456789".into(), true),
+            ("short".into(), false),
+            ("aaaaaaaaaa".into(), true),
+        ]
+    );
+
+    // selectors see the truncated values
+    assert_eq!(dom.query_selector_count(r#"[title="123456789"]"#), Some(1));
+
+    // a limit at the boundary of a multi-byte character keeps it
+    let dom = parse(
+        &input,
+        ParserOptions::default()
+            .max_stored_text_len(11)
+            .max_stored_attribute_len(11),
+    )
+    .unwrap();
+    let div = dom.nodes()[0].as_tag().unwrap();
+    assert_eq!(
+        div.attributes().get("title"),
+        Some(Some(&"123456789é".into()))
+    );
+    assert!(!div.attributes().is_truncated("title"));
+
+    // truncated text can still be normalized
+    let dom = parse(
+        "<p>a\r\nb\r\nc\r\nd</p>",
+        ParserOptions::default()
+            .max_stored_text_len(6)
+            .normalize_newlines(),
+    )
+    .unwrap();
+    assert_eq!(dom.nodes()[1].as_raw().unwrap().as_bytes(), b"a\nb\n");
+    assert!(dom.nodes()[1].is_truncated());
+
+    // without limits, nothing is truncated
+    let dom = parse(&input, ParserOptions::default()).unwrap();
+    assert!(dom.nodes().iter().all(|node| !node.is_truncated()));
+}
+
+#[test]
+fn mutation_observer() {
+    use crate::Mutation;
+    use std::{cell::RefCell, rc::Rc};
+
+    let mut dom = parse(
+        r#"<html><body><a id=x href="/a">Hello world</a></body></html><p>after</p>"#,
+        ParserOptions::default(),
+    )
+    .unwrap();
+    let link = dom.query_selector("a").unwrap().next().unwrap();
+    let body = dom.query_selector("body").unwrap().next().unwrap();
+    let text = link.get(dom.parser()).unwrap().children().unwrap().top()[0];
+    let after = dom.children()[1];
+
+    // nothing is recorded without an observer
+    dom.attributes_mut(link).unwrap().insert("title", Some("t"));
+
+    let mutations = Rc::new(RefCell::new(Vec::new()));
+    dom.set_mutation_observer(Box::new({
+        let mutations = Rc::clone(&mutations);
+        move |mutation| mutations.borrow_mut().push(mutation)
+    }));
+
+    let mut attributes = dom.attributes_mut(link).unwrap();
+    attributes.insert("href", Some("/b"));
+    assert_eq!(attributes.get("href"), Some(Some(&"/b".into())));
+    assert_eq!(attributes.remove("id"), Some(Some("x".into())));
+    assert_eq!(attributes.remove("missing"), None);
+
+    assert!(dom.attributes_mut(text).is_none());
+    dom.set_text(text, "Hi there").unwrap();
+    let (_, second) = dom.split_text(text, 2).unwrap();
+    let mark = dom.wrap_text_range(second, 1..6, "mark").unwrap();
+    dom.normalize_document();
+
+    let attribute_set = |name: &str| Mutation::AttributeSet {
+        handle: link,
+        name: name.into(),
+    };
+    let marked = mark.get(dom.parser()).unwrap().children().unwrap().top()[0];
+    let expected = [
+        attribute_set("href"),
+        Mutation::AttributeRemoved {
+            handle: link,
+            name: "id".into(),
+        },
+        Mutation::TextChanged { handle: text },
+        Mutation::TextChanged { handle: text },
+        Mutation::NodeInserted {
+            handle: second,
+            parent: Some(link),
+            index: 1,
+        },
+        Mutation::TextChanged { handle: second },
+        Mutation::NodeInserted {
+            handle: marked,
+            parent: Some(link),
+            index: 2,
+        },
+        Mutation::NodeRemoved { handle: marked },
+        Mutation::NodeInserted {
+            handle: mark,
+            parent: Some(link),
+            index: 2,
+        },
+        Mutation::NodeInserted {
+            handle: marked,
+            parent: Some(mark),
+            index: 0,
+        },
+        Mutation::NodeRemoved { handle: after },
+        Mutation::NodeInserted {
+            handle: after,
+            parent: Some(body),
+            index: 1,
+        },
+    ];
+    assert_eq!(*mutations.borrow(), expected);
+    assert_eq!(
+        dom.outer_html(),
+        r#"<html><body><a href="/b" title="t">Hi <mark>there</mark></a><p>after</p></body></html>"#
+    );
+
+    assert!(dom.remove_mutation_observer().is_some());
+    dom.set_text(text, "x").unwrap();
+    assert_eq!(mutations.borrow().len(), expected.len());
+}
+
+#[test]
+fn element_builder() {
+    let mut dom = parse("<p>Hello</p>", ParserOptions::default()).unwrap();
+    let parser = dom.parser_mut();
+
+    let a = parser
+        .build_element("a")
+        .attr("href", "/x")
+        .attr_bool("download")
+        .class("btn")
+        .class("primary")
+        .text("Click &amp; go")
+        .finish()
+        .unwrap();
+    let img = parser
+        .build_element("img")
+        .attr("src", "cat.png")
+        .attr("alt", "A cat")
+        .finish()
+        .unwrap();
+
+    let tag = a.get(parser).unwrap().as_tag().unwrap();
+    assert_eq!(
+        tag.outer_html(parser),
+        r#"<a href="/x" download class="btn primary">Click &amp; go</a>"#
+    );
+    assert_eq!(tag.inner_text(parser), "Click &amp; go");
+    assert!(tag.attributes().is_class_member("primary"));
+    assert_eq!(tag.children().all(parser).len(), 1);
+    assert_eq!(
+        img.get(parser).unwrap().outer_html(parser),
+        r#"<img src="cat.png" alt="A cat">"#
+    );
+
+    // void elements cannot have content
+    assert_eq!(
+        parser.build_element("img").text("x").finish(),
+        Err(ElementBuilderError::VoidElementContent("img".into()))
+    );
+    assert_eq!(
+        parser.build_element("a b").finish(),
+        Err(ElementBuilderError::InvalidElementName("a b".into()))
+    );
+    assert_eq!(
+        parser.build_element("a").attr("x=y", "z").finish(),
+        Err(ElementBuilderError::InvalidAttributeName("x=y".into()))
+    );
+
+    // created elements are not part of the document until they are attached
+    assert_eq!(dom.outer_html(), "<p>Hello</p>");
+    assert_eq!(dom.nodes().len(), 2);
+    assert_eq!(dom.query_selector_count("a, img"), Some(0));
+}
+
+#[test]
+fn text_fingerprint() {
+    let options = TextFingerprintOptions {
+        fold_case: true,
+        collapse_whitespace: true,
+    };
+
+    let a = parse(
+        r#"<html><head><title>A</title><style>p { color: red }</style></head>
+        <body><h1>Hello  World</h1><p>Tom &amp; <b>Jerry</b><!-- note --></p>
+        <script>alert(1)</script><div hidden>secret</div></body></html>"#,
+        ParserOptions::default(),
+    )
+    .unwrap();
+    let b = parse(
+        "<div><span>hello</span> WORLD</div>\n<div>  Tom &#38; Jerry</div>",
+        ParserOptions::default(),
+    )
+    .unwrap();
+    let c = parse(
+        "<div>hello world</div><div>Tom &amp; Tuffy</div>",
+        ParserOptions::default(),
+    )
+    .unwrap();
+
+    assert_eq!(a.visible_text(options), "hello world\ntom & jerry");
+    assert_eq!(a.visible_text(options), b.visible_text(options));
+    assert_eq!(a.text_fingerprint(options), b.text_fingerprint(options));
+    assert_ne!(a.text_fingerprint(options), c.text_fingerprint(options));
+
+    // without normalization, differences in case and whitespace change the fingerprint
+    let raw = TextFingerprintOptions::default();
+    assert_eq!(b.visible_text(raw), "hello WORLD\n\n  Tom & Jerry");
+    assert_ne!(a.text_fingerprint(raw), b.text_fingerprint(raw));
+
+    // table cells are separated, and rows and options are on their own lines
+    let table = parse(
+        "<table><tr><th>Name</th><th>Age</th></tr>\n<tr><td>Tom</td><td></td><td>3</td></tr></table>\
+         <select><option>one<option>two</select><ul><li>a</li><li>b</li></ul>",
+        ParserOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(
+        table.visible_text(options),
+        "name\tage\ntom\t3\none\ntwo\na\nb"
+    );
+    assert_eq!(
+        table.visible_text(raw),
+        "Name\tAge\n\nTom\t3\none\ntwo\na\nb"
+    );
+
+    let cells = parse(
+        "<table><tr><td>Name</td><td>Age</td></tr></table>",
+        ParserOptions::default(),
+    )
+    .unwrap();
+    let glued = parse(
+        "<table><tr><td>NameAge</td></tr></table>",
+        ParserOptions::default(),
+    )
+    .unwrap();
+    for options in [options, raw] {
+        assert_ne!(
+            cells.text_fingerprint(options),
+            glued.text_fingerprint(options)
+        );
+    }
+
+    // line endings do not matter
+    let lf = "<pre>a\nb\n\nc</pre><p>d\ne</p>";
+    let lf = parse(lf, ParserOptions::default()).unwrap();
+    let crlf = "<pre>a\r\nb\r\n\rc</pre><p>d\r\ne</p>";
+    let crlf = parse(crlf, ParserOptions::default()).unwrap();
+    assert_eq!(crlf.visible_text(raw), "a\nb\n\nc\nd\ne");
+    for options in [options, raw] {
+        assert_eq!(crlf.visible_text(options), lf.visible_text(options));
+        assert_eq!(crlf.text_fingerprint(options), lf.text_fingerprint(options));
+    }
+}
+
+#[test]
+fn tracked_attributes() {
+    let input = r#"
+        <form>
+            <input name="q" data-testid="search">
+            <input name="page" type="hidden">
+            <button data-testid="submit">Go</button>
+            <div><span data-testid="search">nested</span></div>
+            <p data-testid>empty</p>
+            <p>none</p>
+        </form>"#;
+
+    let tracked = parse(
+        input,
+        ParserOptions::default()
+            .track_attribute("data-testid")
+            .track_attribute("name")
+            .track_attribute("name"),
+    )
+    .unwrap();
+    let untracked = parse(input, ParserOptions::default()).unwrap();
+
+    assert!(tracked.parser().options.is_tracking_attribute("name"));
+    assert!(!untracked.parser().options.is_tracking_attribute("name"));
+
+    let lookups = [
+        ("data-testid", "search"),
+        ("data-testid", "submit"),
+        ("data-testid", ""),
+        ("data-testid", "missing"),
+        ("name", "q"),
+        ("name", "page"),
+        ("type", "hidden"),
+        ("id", "x"),
+    ];
+    for (name, value) in lookups {
+        let a = tracked
+            .get_elements_by_attribute_tracked(name, value)
+            .collect::<Vec<_>>();
+        let b = untracked
+            .get_elements_by_attribute_tracked(name, value)
+            .collect::<Vec<_>>();
+        assert_eq!(a, b, "{name}={value}");
+    }
+
+    let parser = tracked.parser();
+    let names = tracked
+        .get_elements_by_attribute_tracked("data-testid", "search")
+        .map(|handle| {
+            handle
+                .get(parser)
+                .unwrap()
+                .as_tag()
+                .unwrap()
+                .name()
+                .as_utf8_str()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["input", "span"]);
+
+    let empty = tracked
+        .get_elements_by_attribute_tracked("data-testid", "")
+        .next()
+        .unwrap();
+    assert_eq!(empty.get(parser).unwrap().inner_text(parser), "empty");
+    assert_eq!(
+        tracked
+            .get_elements_by_attribute_tracked("name", "q")
+            .count(),
+        1
+    );
+    assert_eq!(
+        tracked
+            .get_elements_by_attribute_tracked("name", "none")
+            .count(),
+        0
+    );
+}
+
+#[test]
+fn closest() {
+    let mut dom = parse(
+        r#"<article data-id="1"><section class="a"><p><span>Hi</span></p></section></article><span id="top">x</span>"#,
+        ParserOptions::default(),
+    )
+    .unwrap();
+    let parser = dom.parser();
+
+    let span = dom.query_selector("p > span").unwrap().next().unwrap();
+    let section = dom.query_selector("section").unwrap().next().unwrap();
+    let article = dom.query_selector("article").unwrap().next().unwrap();
+    let tag = span.get(parser).unwrap().as_tag().unwrap();
+
+    assert_eq!(span.closest(parser, "article[data-id]"), Some(article));
+    assert_eq!(tag.closest(parser, "article[data-id]"), Some(article));
+    assert_eq!(span.closest(parser, ".a, article"), Some(section));
+    assert_eq!(tag.closest(parser, "span"), Some(span));
+    assert_eq!(span.closest(parser, "article > section span"), Some(span));
+
+    // the walk ends at the topmost element
+    assert_eq!(span.closest(parser, "div"), None);
+    assert_eq!(span.closest(parser, "[invalid"), None);
+
+    // topmost elements have no parent
+    let top = dom.get_element_by_id("top").unwrap();
+    let top_tag = top.get(parser).unwrap().as_tag().unwrap();
+    assert_eq!(top_tag.closest(parser, "#top"), Some(top));
+    assert_eq!(top.closest(parser, "article"), None);
+
+    // text nodes are not elements
+    let text = top_tag.children().top()[0];
+    assert_eq!(text.closest(parser, "span"), None);
+
+    // detached elements are found as well
+    let parser = dom.parser_mut();
+    let div = parser.build_element("div").text("y").finish().unwrap();
+    let div_tag = div.get(parser).unwrap().as_tag().unwrap();
+    assert_eq!(div_tag.closest(parser, "div"), Some(div));
+}
+
+#[test]
+fn filter_handles() {
+    let dom = parse(
+        r#"<div class="a"><p class="x">1</p><span class="x">2</span></div>
+        <div><p class="x">3</p><p>4</p></div><p class="x">5</p>"#,
+        ParserOptions::default(),
+    )
+    .unwrap();
+    let query = |selector| dom.query_selector(selector).unwrap().collect::<Vec<_>>();
+
+    let candidates = query(".x");
+    let chained = dom
+        .filter_handles(candidates.clone(), "div.a > *")
+        .unwrap()
+        .collect::<Vec<_>>();
+    assert_eq!(chained, query("div.a > .x"));
+    assert_eq!(chained.len(), 2);
+
+    let chained = dom
+        .filter_handles(query("div p"), ".x")
+        .unwrap()
+        .collect::<Vec<_>>();
+    assert_eq!(chained, query("div p.x"));
+
+    // the order of the candidates is preserved
+    let reversed = dom
+        .filter_handles(candidates.iter().rev().copied(), "p")
+        .unwrap()
+        .collect::<Vec<_>>();
+    let mut expected = query("p.x");
+    expected.reverse();
+    assert_eq!(reversed, expected);
+
+    assert!(dom.filter_handles(candidates, "[").is_none());
+}
+
+#[test]
+fn query_selector_first() {
+    let dom = parse(
+        r#"<section><span>1</span><p class="b">2</p><p class="a">3</p></section><p class="a">4</p>"#,
+        ParserOptions::default(),
+    )
+    .unwrap();
+    let parser = dom.parser();
+    let text =
+        |handle: Option<NodeHandle>| handle.map(|h| h.get(parser).unwrap().inner_text(parser));
+
+    // the first branch matches a later node than the second one
+    assert_eq!(text(dom.query_selector_first(".a, span")), Some("1".into()));
+    assert_eq!(text(dom.query_selector_first("p.a")), Some("3".into()));
+    assert_eq!(
+        dom.query_selector_first(".a, .b"),
+        dom.query_selector(".a, .b").unwrap().next()
+    );
+    assert_eq!(dom.query_selector_first("div"), None);
+    assert_eq!(dom.query_selector_first("["), None);
+
+    let section = dom.nodes()[0].as_tag().unwrap();
+    let first = section.query_selector_first(parser, "p.a, p.b");
+    assert_eq!(text(first), Some("2".into()));
+    assert_eq!(section.query_selector_first(parser, "section"), None);
+}
+
+#[test]
+#[cfg(any(debug_assertions, feature = "checked-handles"))]
+fn checked_handles() {
+    let mut a = parse("<p>a</p><div>b</div>", ParserOptions::default()).unwrap();
+    let mut b = parse("<div>1</div><p>2</p>", ParserOptions::default()).unwrap();
+
+    let a_div = a.query_selector_first("div").unwrap();
+    let b_div = b.query_selector_first("div").unwrap();
+    assert!(a_div.get(a.parser()).is_some());
+
+    // handles of one document are rejected by another one instead of resolving to an unrelated node
+    assert!(a_div.get(b.parser()).is_none());
+    assert!(b_div.get_mut(a.parser_mut()).is_none());
+    assert!(b.set_text(a_div, "x").is_none());
+    assert!(b.attributes_mut(a_div).is_none());
+    assert_eq!(a_div.closest(b.parser(), "*"), None);
+
+    // handles created from raw ids are not associated with a parser
+    let raw = NodeHandle::new(b_div.get_inner());
+    assert_eq!(raw, b_div);
+    assert_eq!(raw.get(a.parser()).unwrap().inner_text(a.parser()), "a");
+    assert_eq!(raw.get(b.parser()).unwrap().inner_text(b.parser()), "1");
+
+    // handles stay checked after the document is restructured
+    let parser = a.parser_mut();
+    let span = parser.create_element("span").unwrap();
+    assert!(span.get(parser).is_some());
+    assert!(span.get(b.parser()).is_none());
+    assert_eq!(a.outer_html(), "<p>a</p><div>b</div>");
+}
+
+#[test]
+fn attributes_matching() {
+    let nested = "<div>".repeat(50)
+        + r#"<iframe src="http://deep.example/frame" title="http:// is not secure"></iframe>"#;
+    let input = format!(
+        r#"<html><head><link rel="stylesheet" href="http://cdn.example/a.css"><script src="https://ok.example/a.js"></script></head>
+        <body><img src="/local.png" srcset="http://img.example/a.png 1x, /b.png 2x" alt="">
+        <a href="HTTP://upper.example" data-x>Link</a><input disabled>{nested}</body></html>"#
+    );
+    let dom = parse(&input, ParserOptions::default()).unwrap();
+    let parser = dom.parser();
+
+    let contains = |value: &[u8]| value.windows(7).any(|window| window == b"http://");
+    let found = dom
+        .attributes_matching(|_, value| contains(value))
+        .map(|(handle, name, value)| {
+            let tag = handle.get(parser).unwrap().as_tag().unwrap();
+            // the yielded pair is the attribute of the yielded element
+            assert_eq!(
+                tag.attributes().get(std::str::from_utf8(name).unwrap()),
+                Some(Some(value))
+            );
+            (
+                tag.name().as_utf8_str().into_owned(),
+                String::from_utf8(name.to_vec()).unwrap(),
+                value.as_utf8_str().into_owned(),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        found,
+        [
+            ("link", "href", "http://cdn.example/a.css"),
+            ("img", "srcset", "http://img.example/a.png 1x, /b.png 2x"),
+            ("iframe", "src", "http://deep.example/frame"),
+            ("iframe", "title", "http:// is not secure"),
+        ]
+        .map(|(a, b, c)| (a.to_owned(), b.to_owned(), c.to_owned()))
+    );
+
+    // the predicate also receives the name, and attributes without a value are skipped
+    let sources = dom.attributes_matching(|name, _| name == b"src").count();
+    assert_eq!(sources, 3);
+    assert_eq!(
+        dom.attributes_matching(|_, value| value.is_empty()).count(),
+        1
+    );
+    assert_eq!(
+        dom.attributes_matching(|name, _| name == b"disabled")
+            .count(),
+        0
+    );
+}
+
+#[test]
+fn vdom_inner_text_and_html() {
+    let input = "<!DOCTYPE html><html><head><title>T</title></head><body><p>Hello</p> <b>world</b></body></html>";
+    let dom = parse(input, ParserOptions::default()).unwrap();
+    assert_eq!(dom.inner_text(), "THello world");
+    assert_eq!(
+        dom.inner_html(),
+        "<head><title>T</title></head><body><p>Hello</p> <b>world</b></body>"
+    );
+
+    // fragments have no enclosing element, so the surrounding text is part of the inner HTML
+    let dom = parse("Hello <b>big</b> world", ParserOptions::default()).unwrap();
+    assert_eq!(dom.inner_text(), "Hello big world");
+    assert_eq!(dom.inner_html(), "Hello <b>big</b> world");
+    assert_eq!(dom.inner_html(), dom.outer_html());
+
+    // comments before the document element are skipped
+    let dom = parse(
+        "<!-- a --><!-- b --><html><body><!-- c -->Text</body></html>",
+        ParserOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(dom.inner_text(), "Text");
+    assert_eq!(dom.inner_html(), "<body><!-- c -->Text</body>");
+}
+
+#[test]
+fn tracked_query_selectors_match_full_scan() {
+    use crate::queryselector::visits;
+
+    let mut input = String::from(r#"<div id="main" class="box"><div class="box inner">"#);
+    for i in 0..200 {
+        input.push_str(&format!(
+            r#"<p class="item{}" id="p{i}"><span class="label">{i}</span></p>"#,
+            if i % 50 == 0 { " first" } else { "" }
+        ));
+    }
+    input.push_str("</div><p class=\"item\r\nlast\">end</p></div>");
+    input.push_str(r#"<aside class="box"><p class="item">x</p><b class="label">y</b></aside>"#);
+
+    let options = ParserOptions::default().normalize_newlines();
+    let tracked = parse(&input, options.clone().track_ids().track_classes()).unwrap();
+    let untracked = parse(&input, options).unwrap();
+
+    for selector in [
+        ".item",
+        ".first",
+        ".last",
+        "p.item.first",
+        "#p42",
+        "p#p42.item",
+        ".box .item",
+        ".box > .item",
+        ".inner .label",
+        "#main .label",
+        "#main > p",
+        ".first + p",
+        ".first ~ .item .label",
+        "aside .label",
+        ".item, .label",
+        ".missing",
+        "#missing .item",
+        ".box #p7 span",
+    ] {
+        visits::take();
+        let fast = tracked
+            .query_selector(selector)
+            .unwrap()
+            .collect::<Vec<_>>();
+        let fast_visits = visits::take();
+        let slow = untracked
+            .query_selector(selector)
+            .unwrap()
+            .collect::<Vec<_>>();
+
+        assert_eq!(fast, slow, "{selector}");
+        assert!(fast_visits <= untracked.nodes().len(), "{selector}");
+    }
+
+    // class values with normalized line breaks are tracked too
+    assert_eq!(tracked.query_selector(".last").unwrap().count(), 1);
+
+    // only the elements with the class (or the subtree of the id) are visited
+    visits::take();
+    assert_eq!(tracked.query_selector(".first").unwrap().count(), 4);
+    assert_eq!(visits::take(), 4);
+
+    visits::take();
+    assert_eq!(tracked.query_selector("#p7 span").unwrap().count(), 1);
+    assert_eq!(visits::take(), 3);
+
+    // selector lists are not seeded from the lookup tables
+    visits::take();
+    tracked.query_selector(".item, .label").unwrap().count();
+    assert_eq!(visits::take(), tracked.nodes().len());
+}
+
+#[test]
+fn anchor_view() {
+    let dom = parse(
+        r#"<a href=" /a
+/b " rel=" NoFollow  noopener" target=" _blank ">Go <b>there</b></a><AREA href="/map"><a>plain</a><p>x</p>"#,
+        ParserOptions::default(),
+    )
+    .unwrap();
+    let parser = dom.parser();
+    let tag = |i: usize| dom.nodes()[i].as_tag();
+    let anchors = dom
+        .nodes()
+        .iter()
+        .filter_map(Node::as_tag)
+        .filter_map(HTMLTag::as_anchor)
+        .collect::<Vec<_>>();
+    assert_eq!(anchors.len(), 3);
+
+    let link = anchors[0];
+    assert_eq!(link.href().as_deref(), Some("/a/b"));
+    assert_eq!(
+        link.rel_tokens().collect::<Vec<_>>(),
+        ["NoFollow", "noopener"]
+    );
+    assert!(link.has_rel("nofollow") && !link.has_rel("noreferrer"));
+    assert_eq!(link.target().as_deref(), Some("_blank"));
+    assert_eq!(link.text(parser), "Go there");
+    assert!(std::ptr::eq(link.tag(), tag(0).unwrap()));
+
+    // <area> elements are anchors too, and missing attributes are absent rather than empty
+    assert_eq!(anchors[1].href().as_deref(), Some("/map"));
+    assert_eq!(anchors[1].text(parser), "");
+    let plain = anchors[2];
+    assert_eq!(plain.href(), None);
+    assert_eq!(plain.target(), None);
+    assert_eq!(plain.rel_tokens().count(), 0);
+    assert_eq!(plain.text(parser), "plain");
+
+    let dom = parse("<a href rel>x</a><abbr>y</abbr>", ParserOptions::default()).unwrap();
+    let link = dom.nodes()[0].as_tag().unwrap().as_anchor().unwrap();
+    assert_eq!(link.href(), None);
+    assert_eq!(link.rel_tokens().count(), 0);
+    assert!(dom.nodes()[2].as_tag().unwrap().as_anchor().is_none());
+}
+
+#[test]
+fn image_view() {
+    use crate::{SrcsetCandidate, SrcsetDescriptor};
+
+    let dom = parse(
+        r#"<img src=" a.png " alt="A &amp; B" width=" 120.9px" height="0" ismap srcset=" a.png 1.5x, b.png, c.png 640w 480h , d.png?x=(1,2) 2x">"#,
+        ParserOptions::default(),
+    )
+    .unwrap();
+    let image = dom.nodes()[0].as_tag().unwrap().as_image().unwrap();
+
+    assert_eq!(image.src().as_deref(), Some("a.png"));
+    assert_eq!(image.alt().as_deref(), Some("A &amp; B"));
+    assert_eq!(image.width(), Some(120));
+    assert_eq!(image.height(), Some(0));
+    assert!(image.is_map());
+
+    let candidate = |url: &str, descriptor| SrcsetCandidate {
+        url: url.to_owned(),
+        descriptor,
+    };
+    assert_eq!(
+        image.srcset_candidates(),
+        [
+            candidate("a.png", SrcsetDescriptor::Density(1.5)),
+            candidate("b.png", SrcsetDescriptor::Density(1.0)),
+            candidate("c.png", SrcsetDescriptor::Width(640)),
+            candidate("d.png?x=(1,2)", SrcsetDescriptor::Density(2.0)),
+        ]
+    );
+
+    // like in browsers, commas within a URL are part of it, while commas at its end separate candidates
+    let dom = parse(
+        r#"<img srcset="a.png,b.png 2x,,c.png,">"#,
+        ParserOptions::default(),
+    )
+    .unwrap();
+    let image = dom.nodes()[0].as_tag().unwrap().as_image().unwrap();
+    assert_eq!(
+        image.srcset_candidates(),
+        [
+            candidate("a.png,b.png", SrcsetDescriptor::Density(2.0)),
+            candidate("c.png", SrcsetDescriptor::Density(1.0)),
+        ]
+    );
+
+    // malformed values
+    let dom = parse(
+        r#"<img alt width="px" height="99999999999" srcset="a.png 0w, b.png 2x 2x, c.png 1x 100w, d.png 100h, e.png -1x, f.png inf, g.png 3ẋ, h.png 2x">"#,
+        ParserOptions::default(),
+    )
+    .unwrap();
+    let image = dom.nodes()[0].as_tag().unwrap().as_image().unwrap();
+
+    assert_eq!(image.src(), None);
+    assert_eq!(image.alt().as_deref(), Some(""));
+    assert_eq!(image.width(), None);
+    assert_eq!(image.height(), None);
+    assert!(!image.is_map());
+    assert_eq!(
+        image.srcset_candidates(),
+        [candidate("h.png", SrcsetDescriptor::Density(2.0))]
+    );
+
+    let dom = parse("<img><picture></picture>", ParserOptions::default()).unwrap();
+    let image = dom.nodes()[0].as_tag().unwrap().as_image().unwrap();
+    assert_eq!(image.alt(), None);
+    assert!(image.srcset_candidates().is_empty());
+    assert!(dom.nodes()[1].as_tag().unwrap().as_image().is_none());
+}
+
+#[test]
+fn meta_view() {
+    let dom = parse(
+        r#"<meta name=" description " content=" A page "><meta property="og:title" content="Title"><meta name="" property="og:type"><meta charset="utf-8"><link name="x">"#,
+        ParserOptions::default(),
+    )
+    .unwrap();
+    let metas = dom
+        .nodes()
+        .iter()
+        .filter_map(Node::as_tag)
+        .filter_map(HTMLTag::as_meta)
+        .map(|meta| (meta.name_or_property(), meta.content()))
+        .collect::<Vec<_>>();
+
+    let expected = [
+        (Some("description"), Some(" A page ")),
+        (Some("og:title"), Some("Title")),
+        // an empty name falls back to the property
+        (Some("og:type"), None),
+        (None, None),
+    ];
+    assert_eq!(metas.len(), expected.len());
+    for ((name, content), (expected_name, expected_content)) in metas.iter().zip(expected) {
+        assert_eq!(name.as_deref(), expected_name);
+        assert_eq!(content.as_deref(), expected_content);
+    }
+
+    let dom = parse("<meta name content>", ParserOptions::default()).unwrap();
+    let meta = dom.nodes()[0].as_tag().unwrap().as_meta().unwrap();
+    assert_eq!(meta.name_or_property(), None);
+    assert_eq!(meta.content(), None);
+}
+
+#[test]
+fn truncated_documents_round_trip() {
+    let input = r#"<!DOCTYPE html>
+<html lang="en">
+<head><meta charset="utf-8"><title>A &amp; B</title>
+<style>p > a { color: red }</style>
+<script>if (a < b) { x = "</p>"; }</script>
+</head>
+<body class='main' data-x=1>
+<!-- a comment -- with dashes -->
+<p id="p1">Héllo <b>wörld</b>&nbsp;!<br>
+<a href="/x?a=1&b=2" title='q"uote'>link</a></p >
+<img src=a.png alt="" />
+<div><span>«nested»</span></div>
+</body>
+</html>
+"#;
+    let options = ParserOptions::default().collect_diagnostics();
+
+    for end in (0..=input.len()).filter(|&end| input.is_char_boundary(end)) {
+        let truncated = &input[..end];
+        let dom = parse(truncated, options.clone()).unwrap();
+
+        assert_eq!(dom.outer_html(), truncated, "cut at {end}");
+
+        let eof_diagnostics = dom
+            .diagnostics()
+            .iter()
+            .filter(|d| matches!(d.kind, DiagnosticKind::UnexpectedEof { .. }))
+            .count();
+        assert!(eof_diagnostics <= 1, "cut at {end}");
+    }
+
+    let dom = parse(input, options).unwrap();
+    assert!(dom.diagnostics().is_empty());
+    assert_eq!(dom.version(), Some(HTMLVersion::HTML5));
+}
+
+#[test]
+fn unexpected_eof_diagnostics() {
+    let cases = [
+        ("<p><!-- never closed", Construct::Comment, 3),
+        ("<!DOCTYPE html", Construct::Doctype, 0),
+        ("<p>a <a href=\"x", Construct::AttributeValue, 13),
+        ("<p>a <a href=x", Construct::StartTag, 5),
+        ("<p>a <", Construct::StartTag, 5),
+        ("<p>a</p", Construct::EndTag, 4),
+        ("<p>a</sp", Construct::EndTag, 4),
+        ("<script>if (a < b)", Construct::RawText, 8),
+        ("<script>x</script", Construct::EndTag, 9),
+    ];
+
+    for (input, in_construct, at) in cases {
+        let dom = parse(input, ParserOptions::default().collect_diagnostics()).unwrap();
+
+        assert_eq!(dom.outer_html(), input);
+        assert_eq!(
+            dom.diagnostics(),
+            [Diagnostic {
+                kind: DiagnosticKind::UnexpectedEof { in_construct },
+                at
+            }],
+            "{input}"
+        );
+    }
+
+    // an unterminated doctype is still the doctype of the document
+    let dom = parse("<!DOCTYPE html", ParserOptions::default()).unwrap();
+    assert_eq!(dom.version(), Some(HTMLVersion::HTML5));
+    assert_eq!(dom.doctype(), Some(&"<!DOCTYPE html".into()));
+    assert!(dom.children().is_empty());
+    assert!(dom.diagnostics().is_empty());
+}
+
+#[test]
+fn subtree_view_lookups() {
+    let input = r#"<p id="dup" class="c">outside</p><section><div id="dup" class="c">inside <b class="c">b</b></div><p id="only">x</p></section><b id="only"></b>"#;
+
+    for options in [
+        ParserOptions::default(),
+        ParserOptions::default().track_ids().track_classes(),
+    ] {
+        let dom = parse(input, options).unwrap();
+        let parser = dom.parser();
+        let section = dom.query_selector_first("section").unwrap();
+        let view = dom.subtree_view(section).unwrap();
+
+        let inside = view.get_element_by_id("dup").unwrap();
+        assert_eq!(inside.get(parser).unwrap().inner_text(parser), "inside b");
+        assert_ne!(Some(inside), dom.get_element_by_id("dup"));
+        assert!(view.contains(inside));
+        assert!(!view.contains(dom.get_element_by_id("dup").unwrap()));
+
+        let only = view.get_element_by_id("only").unwrap();
+        assert_eq!(only.get(parser).unwrap().as_tag().unwrap().name(), "p");
+        assert_eq!(view.get_element_by_id("missing"), None);
+
+        assert_eq!(view.get_elements_by_class_name("c").count(), 2);
+        assert_eq!(
+            view.query_selector("#dup").unwrap().collect::<Vec<_>>(),
+            [inside]
+        );
+        assert_eq!(view.query_selector(".c").unwrap().count(), 2);
+        assert_eq!(view.query_selector("#only").unwrap().count(), 1);
+        assert_eq!(view.query_selector("section *").unwrap().count(), 3);
+
+        assert_eq!(view.children(), [section]);
+        assert_eq!(view.nodes().len(), 7);
+        assert_eq!(view.inner_text(), "inside bx");
+        assert_eq!(
+            view.outer_html(),
+            r#"<section><div id="dup" class="c">inside <b class="c">b</b></div><p id="only">x</p></section>"#
+        );
+    }
+}
+
+#[test]
+fn remove_node() {
+    use crate::Mutation;
+    use std::{cell::RefCell, rc::Rc};
+
+    let input = r#"<ul><li id="a" class="item">a</li><li class="item">b</li></ul>"#;
+    let mut dom = parse(input, ParserOptions::default().track_ids().track_classes()).unwrap();
+    let mutations = Rc::new(RefCell::new(Vec::new()));
+    dom.set_mutation_observer(Box::new({
+        let mutations = Rc::clone(&mutations);
+        move |mutation| mutations.borrow_mut().push(mutation)
+    }));
+
+    let first = dom.query_selector_first("li").unwrap();
+    let text = first.get(dom.parser()).unwrap().children().unwrap().top()[0];
+    let second = dom.query_selector_nth("li", 1).unwrap();
+
+    dom.remove(first).unwrap();
+
+    assert_eq!(dom.outer_html(), r#"<ul><li class="item">b</li></ul>"#);
+    assert_eq!(dom.inner_text(), "b");
+    assert_eq!(dom.nodes().len(), 3);
+    assert_eq!(
+        *mutations.borrow(),
+        [Mutation::NodeRemoved { handle: first }]
+    );
+
+    // handles into the removed subtree are dangling
+    assert!(first.get(dom.parser()).is_none());
+    assert!(text.get(dom.parser()).is_none());
+    assert_eq!(dom.remove(first), None);
+
+    // other handles are still valid
+    assert_eq!(
+        second.get(dom.parser()).unwrap().inner_text(dom.parser()),
+        "b"
+    );
+    assert_eq!(
+        dom.query_selector("li").unwrap().collect::<Vec<_>>(),
+        [second]
+    );
+    assert_eq!(dom.get_element_by_id("a"), None);
+    assert_eq!(
+        dom.get_elements_by_class_name("item").collect::<Vec<_>>(),
+        [second]
+    );
+
+    // removing a top-level node
+    let ul = dom.children()[0];
+    dom.remove(ul).unwrap();
+    assert!(dom.children().is_empty());
+    assert!(dom.nodes().is_empty());
+    assert_eq!(dom.outer_html(), "");
+}
+
+#[test]
+fn select_view() {
+    let input = r#"<select name="size">
+  <option value="s">Small
+  <option selected>  Medium &amp;
+     large </option>
+  <optgroup label="Big"><option value="xl" label="Extra large">XL<option disabled>XXL</optgroup>
+  <option selected value="">None
+</select><p>after</p>"#;
+    let dom = parse(input, ParserOptions::default()).unwrap();
+    let parser = dom.parser();
+
+    // unclosed options are closed by the next option, the optgroup or the end of the select
+    assert_eq!(dom.outer_html(), input);
+    assert_eq!(dom.children().len(), 2);
+
+    let select = dom.nodes()[0].as_tag().unwrap().as_select(parser).unwrap();
+    let options = select.options();
+    assert_eq!(options.len(), 5);
+
+    let values = options.iter().map(|o| o.value()).collect::<Vec<_>>();
+    assert_eq!(values, ["s", "Medium & large", "xl", "XXL", ""]);
+    assert_eq!(options[1].text(), "Medium & large");
+    assert_eq!(options[2].label(), "Extra large");
+    assert_eq!(options[3].label(), "XXL");
+    assert!(options[3].is_disabled());
+
+    let groups = select
+        .groups()
+        .map(|(label, options)| (label, options.len()))
+        .collect::<Vec<_>>();
+    assert_eq!(groups, [(None, 2), (Some("Big".into()), 2), (None, 1)]);
+
+    // the last selected option wins in a single select
+    assert!(!select.is_multiple());
+    assert_eq!(select.selected_value().as_deref(), Some(""));
+    assert_eq!(select.selected_values(), [""]);
+
+    let dom = parse(
+        "<select multiple><option selected>a<option>b<option selected value=c>C</select>",
+        ParserOptions::default(),
+    )
+    .unwrap();
+    let select = dom.nodes()[0]
+        .as_tag()
+        .unwrap()
+        .as_select(dom.parser())
+        .unwrap();
+    assert!(select.is_multiple());
+    assert_eq!(select.selected_values(), ["a", "c"]);
+    assert_eq!(select.selected_value().as_deref(), Some("a"));
+
+    // without a selected option, the first one that is not disabled is selected
+    let dom = parse(
+        "<select><optgroup disabled><option>a</optgroup><option>b</select>",
+        ParserOptions::default(),
+    )
+    .unwrap();
+    let select = dom.nodes()[0]
+        .as_tag()
+        .unwrap()
+        .as_select(dom.parser())
+        .unwrap();
+    assert_eq!(select.selected_value().as_deref(), Some("b"));
+    assert!(dom.nodes()[1]
+        .as_tag()
+        .unwrap()
+        .as_select(dom.parser())
+        .is_none());
+}
+
+#[test]
+fn build_index_after_parsing() {
+    use crate::vdom::scans;
+
+    let html =
+        r#"<div id="a" class="x y"><P class="y">1</P><p id="b">2</p></div><span class="x"></span>"#;
+    let tracked = parse(
+        html,
+        ParserOptions::default()
+            .track_ids()
+            .track_classes()
+            .track_tag_names(),
+    )
+    .unwrap();
+    let mut dom = parse(html, ParserOptions::default()).unwrap();
+
+    let lookups = |dom: &VDom| {
+        (
+            ["a", "b", "c"].map(|id| dom.get_element_by_id(id)),
+            ["x", "y", "z"].map(|class| dom.get_elements_by_class_name(class).collect::<Vec<_>>()),
+            ["p", "DIV", "a"].map(|name| dom.get_elements_by_tag_name(name).collect::<Vec<_>>()),
+        )
+    };
+
+    scans::take();
+    let expected = lookups(&tracked);
+    assert_eq!(scans::take(), 0);
+    assert_eq!(lookups(&dom), expected);
+    assert_eq!(scans::take(), 9);
+
+    dom.build_index(IndexKind::Ids);
+    assert_eq!(lookups(&dom), expected);
+    assert_eq!(scans::take(), 6);
+
+    // building an index twice does not register elements twice
+    for _ in 0..2 {
+        dom.build_index(IndexKind::Classes);
+        dom.build_index(IndexKind::TagNames);
+    }
+    assert_eq!(lookups(&dom), expected);
+    assert_eq!(scans::take(), 0);
+    assert_eq!(dom.get_elements_by_tag_name("p").count(), 2);
+}
+
+#[test]
+fn synthetic_tags() {
+    let mut dom = parse("<head><title>x</title></head>", ParserOptions::default()).unwrap();
+    let parser = dom.parser_mut();
+
+    let mut attributes = Attributes::new();
+    attributes.insert("name", Some("generator"));
+    attributes.insert("content", Some("tl"));
+    let meta = parser.add_node(Node::Tag(HTMLTag::new("meta", attributes)));
+    let div = parser.add_node(Node::Tag(HTMLTag::new("div", Attributes::new())));
+    assert_ne!(meta, div);
+
+    let meta = meta.get(parser).unwrap().as_tag().unwrap();
+    assert_eq!(
+        meta.outer_html(parser),
+        r#"<meta name="generator" content="tl">"#
+    );
+    assert_eq!(meta.raw(), "");
+    assert_eq!(meta.boundaries(parser), None);
+    assert_eq!(meta.parent(), None);
+
+    let div = div.get(parser).unwrap().as_tag().unwrap();
+    assert_eq!(div.outer_html(parser), "<div></div>");
+    assert_eq!(div.boundaries(parser), None);
+
+    // parsed tags keep their boundaries, and added nodes are not part of the document
+    let title = dom.nodes()[1].as_tag().unwrap();
+    assert_eq!(title.boundaries(dom.parser()), Some((6, 21)));
+    assert_eq!(dom.nodes().len(), 3);
+    assert_eq!(dom.outer_html(), "<head><title>x</title></head>");
+}
+
+#[test]
+fn image_source_lazy_loading() {
+    const PIXEL: &str =
+        "data:image/gif;base64,R0lGODlhAQABAIAAAAAAAP///yH5BAEAAAAALAAAAAABAAEAAAIBRAA7";
+
+    let cases = [
+        // lazysizes
+        (format!(r#"<img src="{PIXEL}" data-src="/a.jpg" class="lazyload">"#), Some("/a.jpg")),
+        // the real URL is preferred over a low quality placeholder
+        (r#"<img src="/a-small.jpg" data-lazy-src=" /a.jpg ">"#.into(), Some("/a.jpg")),
+        // jQuery Lazy Load
+        (r#"<img data-original="/a.jpg" src="">"#.into(), Some("/a.jpg")),
+        (format!(r#"<img src="{PIXEL}" data-srcset="/a-300.jpg 300w, /a-1024.jpg 1024w, /a-768.jpg 768w">"#), Some("/a-1024.jpg")),
+        (r#"<img src="/spacer.gif" width="1" height="1" srcset="/a.jpg, /a@3x.jpg 3x, /a@2x.jpg 2x">"#.into(), Some("/a@3x.jpg")),
+        (r#"<img src="/a.jpg" width="1" height="100">"#.into(), Some("/a.jpg")),
+        (format!(r#"<picture><source media="(min-width: 800px)" data-srcset="/b.webp 800w, /b-big.webp 1600w"><img src="{PIXEL}"></picture>"#), Some("/b-big.webp")),
+        (format!(r#"<img src="{pixel}" data-src="{pixel}">"#, pixel = PIXEL.to_uppercase()), None),
+        (r#"<img alt="">"#.into(), None),
+    ];
+
+    for (html, expected) in cases {
+        let dom = parse(&html, ParserOptions::default()).unwrap();
+        let parser = dom.parser();
+        let image = dom.query_selector_first("img").unwrap();
+        let image = image.get(parser).unwrap().as_tag().unwrap();
+
+        assert_eq!(image.image_source(parser).as_deref(), expected, "{html}");
+    }
+
+    let dom = parse(
+        r#"<img data-src="/a.jpg" data-zoom="/a-zoom.jpg" src="/a-small.jpg">"#,
+        ParserOptions::default(),
+    )
+    .unwrap();
+    let image = dom.nodes()[0].as_tag().unwrap();
+    let candidates = ["data-zoom", "data-src"];
+    assert_eq!(
+        image.attributes().get_first(&candidates),
+        Some(("data-zoom", Some(&"/a-zoom.jpg".into())))
+    );
+    assert_eq!(
+        image
+            .image_source_with(dom.parser(), &candidates)
+            .as_deref(),
+        Some("/a-zoom.jpg")
+    );
+    assert_eq!(
+        image.attributes().get_first(&["srcset", "data-lazy-src"]),
+        None
+    );
+}
+
+#[test]
+fn append_and_prepend_child() {
+    let mut dom = parse(
+        r#"<div id="a"></div><p id="b">end</p>"#,
+        ParserOptions::default().track_ids(),
+    )
+    .unwrap();
+    let div = dom.get_element_by_id("a").unwrap();
+
+    // the div has no children yet
+    let text = dom.append_child(div, Node::Raw("Hello ".into())).unwrap();
+    let mut attributes = Attributes::new();
+    attributes.insert("id", Some("c"));
+    let span = dom
+        .append_child(div, Node::Tag(HTMLTag::new("span", attributes)))
+        .unwrap();
+    let bold = dom
+        .append_child(span, Node::Tag(HTMLTag::new("b", Attributes::new())))
+        .unwrap();
+    dom.append_child(bold, Node::Raw("world".into())).unwrap();
+    dom.prepend_child(div, Node::Comment("<!-- greeting -->".into()))
+        .unwrap();
+
+    assert_eq!(
+        dom.outer_html(),
+        r#"<div id="a"><!-- greeting -->Hello <span id="c"><b>world</b></span></div><p id="b">end</p>"#
+    );
+
+    let parser = dom.parser();
+    let tag = div.get(parser).unwrap().as_tag().unwrap();
+    assert_eq!(tag.children().top().len(), 3);
+    assert_eq!(tag.children().all(parser).len(), 5);
+    assert_eq!(tag.inner_text(parser), "Hello world");
+    assert_eq!(
+        tag.inner_html(parser),
+        r#"<!-- greeting -->Hello <span id="c"><b>world</b></span>"#
+    );
+    assert_eq!(text.get(parser).unwrap().inner_text(parser), "Hello ");
+    assert_eq!(
+        span.get(parser).unwrap().as_tag().unwrap().parent(),
+        Some(div)
+    );
+
+    // inserted elements are found by lookups and query selectors in document order
+    assert_eq!(dom.get_element_by_id("c"), Some(span));
+    let ids = dom
+        .elements_with_id()
+        .map(|(_, id)| id.as_utf8_str().into_owned())
+        .collect::<Vec<_>>();
+    assert_eq!(ids, ["a", "c", "b"]);
+    assert_eq!(
+        dom.query_selector("div *").unwrap().collect::<Vec<_>>(),
+        [span, bold]
+    );
+
+    // void elements cannot have children
+    let mut dom = parse("<img>", ParserOptions::default()).unwrap();
+    let img = dom.children()[0];
+    assert_eq!(dom.append_child(img, Node::Raw("x".into())), None);
+    assert_eq!(dom.outer_html(), "<img>");
+
+    // children of detached elements are not part of the document
+    let detached = dom.parser_mut().create_element("ul").unwrap();
+    dom.append_child(detached, Node::Tag(HTMLTag::new("li", Attributes::new())))
+        .unwrap();
+    assert_eq!(dom.nodes().len(), 1);
+    let parser = dom.parser();
+    assert_eq!(
+        detached.get(parser).unwrap().outer_html(parser),
+        "<ul><li></li></ul>"
+    );
+}
+
+#[test]
+fn shared_dom_across_threads() {
+    fn assert_send_sync<T: Send + Sync + Clone + 'static>() {}
+    assert_send_sync::<VDomShared>();
+
+    let html = (0..50)
+        .map(|i| {
+            format!(
+                r#"<li id="item{i}" class="{}">{i}</li>"#,
+                if i % 2 == 0 { "even" } else { "odd" }
+            )
+        })
+        .collect::<String>();
+    let dom = parse_shared(
+        format!("<ul>{html}</ul>"),
+        ParserOptions::default().track_ids(),
+    )
+    .unwrap();
+
+    let workers = (0..8)
+        .map(|worker| {
+            let dom = dom.clone();
+            std::thread::spawn(move || {
+                let even = dom.query_selector("li.even").unwrap().count();
+                let odd = dom.get_elements_by_class_name("odd").count();
+                let id = format!("item{worker}");
+                let item = dom.get_element_by_id(&id).unwrap();
+                (even, odd, dom.inner_text_of(item).unwrap())
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for (worker, handle) in workers.into_iter().enumerate() {
+        assert_eq!(handle.join().unwrap(), (25, 25, worker.to_string()));
+    }
+
+    // the document outlives the clones that were moved to other threads
+    assert_eq!(dom.children().len(), 1);
+    assert_eq!(dom.nodes().len(), 101);
+    let first = dom.query_selector_first("li").unwrap();
+    assert_eq!(
+        dom.outer_html_of(first).unwrap(),
+        r#"<li id="item0" class="even">0</li>"#
+    );
+    assert!(dom.outer_html().starts_with("<ul><li"));
+    assert!(dom.inner_text().starts_with("0123"));
+}
+
+#[test]
+fn insert_before_and_after() {
+    let mut dom = parse(
+        r#"<figure><img src="a.png"><p>Source</p></figure><img id="other">"#,
+        ParserOptions::default(),
+    )
+    .unwrap();
+    let figure = dom.query_selector_first("figure").unwrap();
+    let image = dom.query_selector_first("img").unwrap();
+    let source = dom.query_selector_first("p").unwrap();
+    let other = dom.query_selector_first("#other").unwrap();
+
+    let caption = dom
+        .insert_after(
+            figure,
+            image,
+            Node::Tag(HTMLTag::new("figcaption", Attributes::new())),
+        )
+        .unwrap();
+    dom.append_child(caption, Node::Raw("A caption".into()))
+        .unwrap();
+    let comment = dom
+        .insert_before(figure, image, Node::Comment("<!-- photo -->".into()))
+        .unwrap();
+    let end = dom
+        .insert_after(figure, source, Node::Raw("!".into()))
+        .unwrap();
+
+    let parser = dom.parser();
+    let tag = figure.get(parser).unwrap().as_tag().unwrap();
+    assert_eq!(
+        tag.children().top().as_slice(),
+        [comment, image, caption, source, end]
+    );
+    assert_eq!(
+        tag.inner_html(parser),
+        r#"<!-- photo --><img src="a.png"><figcaption>A caption</figcaption><p>Source</p>!"#
+    );
+    assert_eq!(
+        dom.query_selector("figure > *")
+            .unwrap()
+            .collect::<Vec<_>>(),
+        [image, caption, source]
+    );
+
+    // the reference node must be a child of the parent
+    let node = || Node::Raw("x".into());
+    assert_eq!(
+        dom.insert_before(figure, other, node()),
+        Err(InsertError::NotAChild)
+    );
+    assert_eq!(
+        dom.insert_after(caption, image, node()),
+        Err(InsertError::NotAChild)
+    );
+    assert_eq!(
+        dom.insert_after(end, image, node()),
+        Err(InsertError::InvalidParent)
+    );
+    assert_eq!(dom.nodes().len(), 9);
+}
+
+#[test]
+fn set_inner_html() {
+    let fragment = String::from(
+        r#"<!-- new --><p id="new">Hello <b>world</b><br>!</p><img src="a.png"><ul><li>1</li><li>2</li></ul>"#,
+    );
+    let script = String::from("if (a < b) { run(); }");
+
+    let mut dom = parse(
+        r#"<div id="target"><p id="old">Old <i>text</i></p></div><p id="after">after</p>"#,
+        ParserOptions::default().track_ids(),
+    )
+    .unwrap();
+    let target = dom.get_element_by_id("target").unwrap();
+    let old = dom.get_element_by_id("old").unwrap();
+
+    dom.set_inner_html(target, &fragment).unwrap();
+
+    let parser = dom.parser();
+    let tag = target.get(parser).unwrap().as_tag().unwrap();
+    assert_eq!(tag.inner_html(parser), fragment);
+    assert_eq!(tag.children().top().len(), 4);
+    assert_eq!(tag.inner_text(parser), "Hello world!12");
+    assert!(old.get(parser).is_none());
+
+    // the new elements are found by lookups and query selectors in document order
+    assert_eq!(dom.get_element_by_id("old"), None);
+    let new = dom.get_element_by_id("new").unwrap();
+    assert_eq!(
+        new.get(parser).unwrap().as_tag().unwrap().parent(),
+        Some(target)
+    );
+    assert_eq!(dom.query_selector_count("#target li"), Some(2));
+    assert_eq!(
+        dom.query_selector("p")
+            .unwrap()
+            .map(|p| p.get(parser).unwrap().inner_text(parser).into_owned())
+            .collect::<Vec<_>>(),
+        ["Hello world!", "after"]
+    );
+
+    // the content of raw text elements is not parsed
+    let mut dom = parse("<script>old()</script><img>", ParserOptions::default()).unwrap();
+    let (element, img) = (dom.children()[0], dom.children()[1]);
+    dom.set_inner_html(element, &script).unwrap();
+    assert_eq!(
+        dom.outer_html(),
+        "<script>if (a < b) { run(); }</script><img>"
+    );
+    assert_eq!(dom.nodes().len(), 3);
+
+    assert_eq!(
+        dom.set_inner_html(img, &script),
+        Err(ParseError::VoidElement)
+    );
+    assert_eq!(
+        dom.set_inner_html(NodeHandle::new(100), "<b></b>"),
+        Err(ParseError::InvalidParent)
+    );
+
+    // the fragments do not need to outlive the document
+    let mut dom = parse("<ul></ul>", ParserOptions::default().track_ids()).unwrap();
+    let list = dom.children()[0];
+    for i in 0..3 {
+        let fragment = format!(r#"<li id="item-{i}" class=x>{i} &amp; <b>more</b><!-- c --></li>"#);
+        dom.set_inner_html(list, &fragment).unwrap();
+    }
+    assert_eq!(
+        dom.outer_html(),
+        r#"<ul><li id="item-2" class="x">2 &amp; <b>more</b><!-- c --></li></ul>"#
+    );
+    assert!(dom.get_element_by_id("item-2").is_some());
+    assert_eq!(dom.get_element_by_id("item-1"), None);
+}
+
+#[test]
+fn document_outline() {
+    let html = r#"<nav><h2>Menu</h2></nav>
+<h1 id="top">Title &amp; more</h1>
+<section><h2 id="a">  Part
+ A </h2><p>intro</p><h4>Detail</h4><p>deep</p></section>
+<section><h2>Part B</h2><aside><h3>Note</h3></aside><h3>B.1</h3></section>
+<h1>Appendix</h1>"#;
+    let dom = parse(html, ParserOptions::default()).unwrap();
+
+    // the level, text and number of children of each entry
+    fn shape(entries: &[OutlineEntry]) -> Vec<(u8, &str, usize)> {
+        entries
+            .iter()
+            .map(|entry| (entry.level, entry.text.as_str(), entry.children.len()))
+            .collect()
+    }
+
+    let outline = dom.outline();
+    assert_eq!(
+        shape(&outline),
+        [(2, "Menu", 0), (1, "Title & more", 2), (1, "Appendix", 0)]
+    );
+
+    let title = &outline[1];
+    assert_eq!(title.id.as_deref(), Some("top"));
+    let part_a = &title.children[0];
+    assert_eq!(part_a.id.as_deref(), Some("a"));
+    assert_eq!(shape(&part_a.children), [(4, "Detail", 0)]);
+    let part_b = &title.children[1];
+    assert_eq!(shape(&part_b.children), [(3, "Note", 0), (3, "B.1", 0)]);
+
+    // the content ends at the next heading of the same or a higher rank
+    let texts = |handles: &[NodeHandle]| {
+        handles
+            .iter()
+            .filter_map(|handle| handle.get(dom.parser())?.as_raw())
+            .map(|text| text.as_utf8_str().into_owned())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(texts(&part_a.content), ["intro", "Detail", "deep", "\n"]);
+    assert_eq!(texts(&part_a.children[0].content), ["deep", "\n"]);
+    assert!(outline[2].content.is_empty());
+
+    let outline = dom.outline_with(OutlineOptions {
+        skip_nav_and_aside: true,
+    });
+    assert_eq!(outline.len(), 2);
+    assert_eq!(shape(&outline[0].children[1].children), [(3, "B.1", 0)]);
+}
+
+#[test]
+fn set_tag_name() {
+    let mut dom = parse(
+        "<h1 id=t>Title</h1><li>One<img src=x>",
+        ParserOptions::default(),
+    )
+    .unwrap();
+    let parser = dom.parser_mut();
+
+    let heading = NodeHandle::new(0)
+        .get_mut(parser)
+        .unwrap()
+        .as_tag_mut()
+        .unwrap();
+    heading.set_name("h2").unwrap();
+    assert_eq!(heading.name(), "h2");
+    assert_eq!(
+        heading.set_name(""),
+        Err(SetBytesError::InvalidTagName(String::new()))
+    );
+    assert_eq!(
+        heading.set_name("a b"),
+        Err(SetBytesError::InvalidTagName("a b".into()))
+    );
+    assert_eq!(
+        heading.set_name("br"),
+        Err(SetBytesError::VoidElementContent("br".into()))
+    );
+
+    // the end tag of the list item was omitted in the source, but is generated after renaming
+    let item = NodeHandle::new(2)
+        .get_mut(parser)
+        .unwrap()
+        .as_tag_mut()
+        .unwrap();
+    item.set_name("p").unwrap();
+
+    // elements without children can become void elements
+    let image = NodeHandle::new(4)
+        .get_mut(parser)
+        .unwrap()
+        .as_tag_mut()
+        .unwrap();
+    image.set_name("hr").unwrap();
+
+    assert_eq!(
+        dom.outer_html(),
+        r#"<h2 id="t">Title</h2><p>One<hr src="x"></p>"#
+    );
+}
+
+#[test]
+fn bytes_eq_trimmed() {
+    let bytes = Bytes::from("\t\n value \r\n");
+    assert!(bytes.eq_trimmed(b"value"));
+    assert!(!bytes.eq_trimmed(b"Value"));
+    assert!(!bytes.eq_trimmed(b" value"));
+
+    assert!(Bytes::from("").eq_trimmed(b""));
+    assert!(Bytes::from(" \t\n\x0C\r").eq_trimmed(b""));
+    assert!(!Bytes::from(" \t\n").eq_trimmed(b" "));
+
+    // non-breaking spaces are not ASCII whitespace
+    assert!(Bytes::from("\u{a0}é\n").eq_trimmed("\u{a0}é".as_bytes()));
+}
+
+#[test]
+fn bytes_eq_trimmed_ignore_case() {
+    assert!(Bytes::from("\n\tSubmit  ").eq_trimmed_ignore_case(b"sUBMIT"));
+    assert!(!Bytes::from("Submit").eq_trimmed_ignore_case(b"submits"));
+
+    assert!(Bytes::from("").eq_trimmed_ignore_case(b""));
+    assert!(Bytes::from("\t\n ").eq_trimmed_ignore_case(b""));
+    assert!(!Bytes::from("\t\n ").eq_trimmed_ignore_case(b"x"));
+
+    // only ASCII letters are folded
+    assert!(Bytes::from(" CAFÉ ").eq_trimmed_ignore_case("cafÉ".as_bytes()));
+    assert!(!Bytes::from("CAFÉ").eq_trimmed_ignore_case("café".as_bytes()));
+    assert!(!Bytes::from("ǅ").eq_trimmed_ignore_case("ǆ".as_bytes()));
+}
+
+#[test]
+fn bytes_contains_token_ignore_case() {
+    let bytes = Bytes::from("\tnoopener\n\nNoFollow  ");
+    assert!(bytes.contains_token_ignore_case(b"nofollow"));
+    assert!(bytes.contains_token_ignore_case(b"NOOPENER"));
+    assert!(!bytes.contains_token_ignore_case(b"follow"));
+    assert!(!bytes.contains_token_ignore_case(b"noopener nofollow"));
+    assert!(!bytes.contains_token_ignore_case(b""));
+
+    assert!(!Bytes::from("").contains_token_ignore_case(b"a"));
+    assert!(!Bytes::from(" \t\r\n").contains_token_ignore_case(b""));
+
+    // only ASCII letters are folded
+    let bytes = Bytes::from("Größe ÜBER");
+    assert!(bytes.contains_token_ignore_case("GRößE".as_bytes()));
+    assert!(!bytes.contains_token_ignore_case("über".as_bytes()));
+
+    let dom = parse(
+        r#"<a rel=" NoOpener  external">x</a>"#,
+        ParserOptions::default(),
+    )
+    .unwrap();
+    let link = dom.nodes()[0].as_tag().unwrap();
+    assert!(link.as_anchor().unwrap().has_rel("noopener"));
+}
+
+#[test]
+fn to_owned_subtree() {
+    let (copy, outer_html, inner_text) = {
+        let input = String::from(
+            r#"<div><table id=t class="data"><tr><td>A &amp; B<td><!-- x --><b id=bold>C</b></tr></table></div>"#,
+        );
+        let mut dom = parse(&input, ParserOptions::default().track_ids()).unwrap();
+        let table = dom.get_element_by_id("t").unwrap();
+        let bold = dom.get_element_by_id("bold").unwrap();
+        dom.attributes_mut(bold)
+            .unwrap()
+            .insert("title", Some("owned"));
+        dom.append_child(bold, Node::Raw("!".into())).unwrap();
+
+        let tag = table.get(dom.parser()).unwrap().as_tag().unwrap();
+        (
+            tag.to_owned_subtree(dom.parser()),
+            tag.outer_html(dom.parser()),
+            tag.inner_text(dom.parser()).into_owned(),
+        )
+    };
+
+    let dom = copy.get_ref();
+    assert_eq!(dom.children().len(), 1);
+    assert_eq!(dom.outer_html(), outer_html);
+    assert_eq!(dom.inner_text(), inner_text);
+    assert_eq!(dom.inner_text(), "A &amp; BC!");
+
+    // the lookup tables and selectors work on the copy
+    let bold = dom.get_element_by_id("bold").unwrap();
+    let tag = bold.get(dom.parser()).unwrap().as_tag().unwrap();
+    assert_eq!(tag.attributes().get("title"), Some(Some(&"owned".into())));
+    assert_eq!(tag.raw(), r#"<b id=bold>C</b>"#);
+    assert_eq!(
+        dom.query_selector("table.data td > b").unwrap().next(),
+        Some(bold)
+    );
+    assert_eq!(dom.query_selector("td").unwrap().count(), 2);
+    assert_eq!(dom.nodes()[0].as_tag().unwrap().parent(), None);
+}
+
+#[test]
+fn dimension_attributes() {
+    assert_eq!(Dimension::parse("300"), Some(Dimension::Pixels(300.0)));
+    assert_eq!(Dimension::parse(" 50% "), Some(Dimension::Percent(50.0)));
+    assert_eq!(Dimension::parse("2.5*"), Some(Dimension::Relative(2.5)));
+    assert_eq!(
+        Dimension::parse("\n\t1.25em"),
+        Some(Dimension::Pixels(1.25))
+    );
+    // the fraction needs a digit, and the unit must follow the number directly
+    assert_eq!(Dimension::parse("3.%"), Some(Dimension::Pixels(3.0)));
+    assert_eq!(Dimension::parse("3 %"), Some(Dimension::Pixels(3.0)));
+    assert_eq!(Dimension::parse("0.5.5%"), Some(Dimension::Pixels(0.5)));
+    assert_eq!(Dimension::parse("abc"), None);
+    assert_eq!(Dimension::parse(".5"), None);
+    assert_eq!(Dimension::parse("+1"), None);
+    assert_eq!(Dimension::parse(""), None);
+    assert_eq!(Dimension::parse("  "), None);
+
+    let dom = parse(
+        r#"<table width="50%" border=1 cellpadding="" cellspacing><tr><td><img width="80%" height="2.9"></table>"#,
+        ParserOptions::default(),
+    )
+    .unwrap();
+    let table = dom.nodes()[0].as_tag().unwrap().attributes();
+    assert_eq!(table.get_dimension("width"), Some(Dimension::Percent(50.0)));
+    assert_eq!(table.get_dimension("border"), Some(Dimension::Pixels(1.0)));
+    assert_eq!(table.get_dimension("cellpadding"), None);
+    assert_eq!(table.get_dimension("cellspacing"), None);
+    assert_eq!(table.get_dimension("height"), None);
+
+    // images only report lengths in pixels
+    let image = dom.query_selector_first("img").unwrap();
+    let image = image.get(dom.parser()).unwrap().as_tag().unwrap();
+    assert_eq!(image.as_image().unwrap().width(), None);
+    assert_eq!(image.as_image().unwrap().height(), Some(2));
+}
+
+#[test]
+fn move_node_between_parents() {
+    let options = ParserOptions::default().track_ids().track_classes();
+    let mut dom = parse(
+        r#"<ul id=a><li class=x>1</li><li class=x><b>2</b></li></ul><p></p><ul id=b><li class=x>3</li></ul>"#,
+        options,
+    )
+    .unwrap();
+    let a = dom.get_element_by_id("a").unwrap();
+    let b = dom.get_element_by_id("b").unwrap();
+    let items = dom.query_selector("li").unwrap().collect::<Vec<_>>();
+    let bold = dom.query_selector_first("b").unwrap();
+
+    // forward, into the middle of the other list
+    dom.move_node(items[1], b, 0).unwrap();
+    // backward, to the end of the first list
+    dom.move_node(items[2], a, 1).unwrap();
+    assert_eq!(
+        dom.outer_html(),
+        r#"<ul id=a><li class=x>1</li><li class=x>3</li></ul><p></p><ul id=b><li class=x><b>2</b></li></ul>"#
+    );
+
+    // within the same parent, the index refers to the children without the node
+    dom.move_node(items[0], a, 1).unwrap();
+    assert_eq!(
+        a.get(dom.parser()).unwrap().inner_html(dom.parser()),
+        r#"<li class=x>3</li><li class=x>1</li>"#
+    );
+
+    // handles stay valid, and the node table, lookups and selectors follow the new document order
+    let texts = |handles: Vec<NodeHandle>| {
+        handles
+            .into_iter()
+            .map(|handle| handle.get(dom.parser()).unwrap().inner_text(dom.parser()))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        texts(dom.get_elements_by_class_name("x").collect()),
+        ["3", "1", "2"]
+    );
+    assert_eq!(texts(dom.query_selector("#b li").unwrap().collect()), ["2"]);
+    assert_eq!(
+        texts(dom.query_selector("p + ul > li").unwrap().collect()),
+        ["2"]
+    );
+    assert_eq!(
+        bold.get(dom.parser()).unwrap().inner_text(dom.parser()),
+        "2"
+    );
+    let b_tag = b.get(dom.parser()).unwrap().as_tag().unwrap();
+    assert_eq!(b_tag.children().all(dom.parser()).len(), 3);
+    assert_eq!(
+        dom.nodes()
+            .iter()
+            .filter_map(|node| Some(node.as_tag()?.name().as_utf8_str()))
+            .collect::<Vec<_>>(),
+        ["ul", "li", "li", "p", "ul", "li", "b"]
+    );
+
+    assert_eq!(dom.move_node(a, items[0], 0), Err(MoveError::Cycle));
+    assert_eq!(dom.move_node(a, a, 0), Err(MoveError::Cycle));
+    assert_eq!(dom.move_node(items[0], b, 2), Err(MoveError::InvalidIndex));
+    assert_eq!(
+        dom.move_node(items[0], NodeHandle::new(100), 0),
+        Err(MoveError::InvalidParent)
+    );
+    assert_eq!(
+        dom.move_node(NodeHandle::new(100), a, 0),
+        Err(MoveError::InvalidNode)
+    );
+}
+
+#[test]
+fn batch_parsing() {
+    let mut batch = crate::Batch::new(ParserOptions::default().track_classes());
+    let inputs = [
+        String::from(r#"<ul class=list><li>1</li><li>2</li></ul>"#),
+        String::from(r#"<p class=list>Hello <b>world</b></p>"#),
+        String::from(r#"<div><div><img alt=x></div></div><!-- end -->"#),
+    ];
+
+    let mut doms = inputs
+        .iter()
+        .map(|input| batch.parse(input).unwrap())
+        .collect::<Vec<_>>();
+
+    // the documents are independent of each other
+    for (dom, input) in doms.iter().zip(&inputs) {
+        assert_eq!(dom.outer_html(), *input);
+    }
+    assert_eq!(doms[0].get_elements_by_class_name("list").count(), 1);
+    assert_eq!(doms[1].query_selector("b").unwrap().count(), 1);
+    assert_eq!(
+        doms[2].query_selector("div > div > img").unwrap().count(),
+        1
+    );
+
+    // compiled selectors are shared by all documents
+    let selector = batch.selector(".list").unwrap() as *const _;
+    assert_eq!(batch.selector(".list").unwrap() as *const _, selector);
+    let counts = doms
+        .iter()
+        .map(|dom| {
+            dom.query_selector_with(batch.selector(".list").unwrap())
+                .count()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(counts, [1, 1, 0]);
+    assert!(batch.selector("div >").is_err());
+
+    assert_eq!(batch.documents(), 3);
+    assert_eq!(batch.total_nodes(), 5 + 4 + 4);
+    assert_eq!(
+        batch.total_bytes(),
+        inputs.iter().map(String::len).sum::<usize>()
+    );
+
+    // documents can be dropped in any order, and failed documents are not counted
+    let middle = doms.remove(1);
+    drop(doms);
+    assert_eq!(middle.inner_text(), "Hello world");
+
+    // the tag names are interned, so the lookup tables of all documents share their keys
+    let mut batch = crate::Batch::new(ParserOptions::default().track_tag_names());
+    let first = batch.parse("<div><P>a</P></div>").unwrap();
+    let second = batch.parse("<p>b</p><span></span>").unwrap();
+    let key = |dom: &VDom, name: &[u8]| {
+        let (key, _) = dom.parser().tag_names.get_key_value(name).unwrap();
+        std::sync::Arc::clone(key)
+    };
+    assert!(std::sync::Arc::ptr_eq(
+        &key(&first, b"p"),
+        &key(&second, b"p")
+    ));
+    assert_eq!(second.get_elements_by_tag_name("P").count(), 1);
+    drop(first);
+    assert_eq!(&*key(&second, b"span"), b"span");
+
+    let options = ParserOptions::default().on_progress(1, |_, _| std::ops::ControlFlow::Break(()));
+    let mut batch = crate::Batch::new(options);
+    assert_eq!(batch.parse("<p></p>").err(), Some(ParseError::Cancelled));
+    assert_eq!(batch.documents(), 0);
+}
+
+#[test]
+fn legacy_raw_text_elements() {
+    let input = r#"<xmp><div class="a">&amp; <b></XMP ><p>after</p>"#;
+    let dom = parse(input, ParserOptions::default()).unwrap();
+    let parser = dom.parser();
+    let xmp = dom.children()[0].get(parser).unwrap().as_tag().unwrap();
+
+    assert_eq!(xmp.children().top().len(), 1);
+    assert_eq!(xmp.inner_text(parser), r#"<div class="a">&amp; <b>"#);
+    assert_eq!(dom.children().len(), 2);
+    assert_eq!(dom.query_selector("div, b").unwrap().count(), 0);
+    assert_eq!(dom.outer_html(), input);
+
+    // plaintext cannot be closed, so the rest of the input is its only child
+    let input = "<p>Hi</p><PLAINTEXT>a < b\n</plaintext><div>x</div><!-- y -->";
+    let dom = parse(input, ParserOptions::default()).unwrap();
+    let parser = dom.parser();
+    let plaintext = dom.children()[1].get(parser).unwrap().as_tag().unwrap();
+
+    assert_eq!(dom.children().len(), 2);
+    assert_eq!(plaintext.children().top().len(), 1);
+    assert_eq!(
+        plaintext.inner_text(parser),
+        "a < b\n</plaintext><div>x</div><!-- y -->"
+    );
+    assert_eq!(dom.nodes().len(), 4);
+    assert_eq!(dom.outer_html(), input);
+
+    let dom = parse("<plaintext>", ParserOptions::default()).unwrap();
+    assert_eq!(dom.nodes().len(), 1);
+    assert_eq!(dom.outer_html(), "<plaintext>");
+}
+
+#[test]
+fn set_inner_text() {
+    let mut dom = parse(
+        "<div><p id=a>Old <b>text</b></p><br><span>!</span></div>",
+        ParserOptions::default().track_ids(),
+    )
+    .unwrap();
+    let p = dom.get_element_by_id("a").unwrap();
+    let bold = dom.query_selector_first("b").unwrap();
+
+    for text in [
+        "<b>not a tag</b>",
+        "5 > 3 & true",
+        "already &amp; escaped &lt;",
+    ] {
+        dom.set_inner_text(p, text).unwrap();
+
+        let html = dom.outer_html();
+        let reparsed = parse(&html, ParserOptions::default()).unwrap();
+        let tag = p.get(dom.parser()).unwrap().as_tag().unwrap();
+        assert_eq!(tag.children().top().len(), 1);
+        assert_eq!(tag.inner_text_decoded(dom.parser()), text);
+        assert_eq!(reparsed.query_selector("p b").unwrap().count(), 0);
+    }
+
+    assert_eq!(
+        dom.outer_html(),
+        r#"<div><p id=a>already &amp;amp; escaped &amp;lt;</p><br><span>!</span></div>"#
+    );
+    assert!(bold.get(dom.parser()).is_none());
+    assert_eq!(dom.query_selector_count("b"), Some(0));
+
+    dom.set_inner_text(p, "").unwrap();
+    assert_eq!(
+        dom.outer_html(),
+        "<div><p id=a></p><br><span>!</span></div>"
+    );
+    assert!(dom.get_element_by_id("a").is_some());
+
+    let br = dom.query_selector_first("br").unwrap();
+    assert_eq!(dom.set_inner_text(br, "x"), Err(InsertError::VoidElement));
+    assert_eq!(
+        dom.set_inner_text(NodeHandle::new(100), "x"),
+        Err(InsertError::InvalidParent)
+    );
+}
+
+#[test]
+fn remove_all_matching() {
+    let input = r#"<!DOCTYPE html>
+<html>
+<head><title>Page</title><script src="/app.js"></script><style>body { margin: 0 }</style></head>
+<body>
+<div class="content"><p>Hello <b onclick="track()">world</b></p><script>document.write("<p>x</p>")</script></div>
+<noscript><script>nested()</script></noscript>
+<iframe src="/ad"><script>inside()</script></iframe>
+</body>
+</html>"#;
+    let mut dom = parse(input, ParserOptions::default().track_classes()).unwrap();
+    let content = dom.get_elements_by_class_name("content").next().unwrap();
+
+    assert_eq!(dom.remove_all("script"), Ok(4));
+    assert_eq!(dom.remove_all("script"), Ok(0));
+    assert_eq!(
+        dom.outer_html(),
+        r#"<!DOCTYPE html>
+<html>
+<head><title>Page</title><style>body { margin: 0 }</style></head>
+<body>
+<div class="content"><p>Hello <b onclick="track()">world</b></p></div>
+<noscript></noscript>
+<iframe src="/ad"></iframe>
+</body>
+</html>"#
+    );
+
+    // nested matches are removed with their ancestor, but still counted
+    assert_eq!(dom.remove_all("iframe, style, div *, [onclick]"), Ok(4));
+    assert_eq!(
+        dom.outer_html(),
+        r#"<!DOCTYPE html>
+<html>
+<head><title>Page</title></head>
+<body>
+<div class="content"></div>
+<noscript></noscript>
+
+</body>
+</html>"#
+    );
+    assert!(content.get(dom.parser()).is_some());
+    assert_eq!(dom.query_selector_count("div, p, b"), Some(1));
+
+    assert!(dom.remove_all("div >> p").is_err());
+    assert_eq!(dom.query_selector_count("div, p, b"), Some(1));
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn parse_memory_mapped_file() {
+    use crate::{parse_file, FileParseError};
+
+    let dir = std::env::temp_dir();
+    let path = dir.join(format!("tl-parse-file-{}.html", std::process::id()));
+    let invalid = dir.join(format!("tl-parse-file-invalid-{}.html", std::process::id()));
+    std::fs::write(
+        &path,
+        "<ul id=list><li class=item>a</li><li class=item>b</li></ul>".repeat(1000),
+    )
+    .unwrap();
+    std::fs::write(&invalid, b"<p>\xff</p>").unwrap();
+
+    let file = unsafe { parse_file(&path, ParserOptions::default().track_ids()).unwrap() };
+
+    fn move_me<T>(p: T) -> T {
+        p
+    }
+
+    let file = std::thread::spawn(|| file).join().unwrap();
+    let file = move_me(file);
+
+    let dom = file.get_ref();
+    let parser = dom.parser();
+    assert_eq!(dom.query_selector_count("ul > li.item"), Some(2000));
+    let list = dom.get_element_by_id("list").unwrap();
+    let last = dom.query_selector("li").unwrap().last().unwrap();
+    assert_eq!(list.get(parser).unwrap().inner_text(parser), "ab");
+    assert_eq!(last.get(parser).unwrap().inner_text(parser), "b");
+
+    assert!(matches!(
+        unsafe { parse_file(&invalid, ParserOptions::default()) },
+        Err(FileParseError::InvalidUtf8(_))
+    ));
+    assert!(matches!(
+        unsafe { parse_file(dir.join("tl-does-not-exist.html"), ParserOptions::default()) },
+        Err(FileParseError::Io(_))
+    ));
+
+    drop(file);
+    std::fs::remove_file(&path).unwrap();
+    std::fs::remove_file(&invalid).unwrap();
+}
+
+#[test]
+fn class_list() {
+    use crate::Mutation;
+    use std::{cell::RefCell, rc::Rc};
+
+    let mut dom = parse(
+        "<p class=\"  a\t\tb  a \n\">x</p><span>y</span>",
+        ParserOptions::default(),
+    )
+    .unwrap();
+    let attributes = dom.nodes_mut()[0].as_tag_mut().unwrap().attributes_mut();
+
+    assert!(attributes.has_class("a"));
+    assert!(attributes.has_class("b"));
+    assert!(!attributes.has_class("a b"));
+    assert!(!attributes.has_class(""));
+
+    assert!(!attributes.add_class("b"));
+    assert_eq!(attributes.class(), Some(&"  a\t\tb  a \n".into()));
+    assert!(!attributes.add_class(""));
+    assert!(!attributes.add_class("c d"));
+    assert!(attributes.add_class("c"));
+    assert_eq!(attributes.class(), Some(&"a b c".into()));
+
+    assert!(attributes.remove_class("b"));
+    assert!(!attributes.remove_class("b"));
+    assert!(!attributes.toggle_class("a"));
+    assert!(attributes.toggle_class("d"));
+    assert_eq!(attributes.class(), Some(&"c d".into()));
+    assert!(!attributes.toggle_class("a b"));
+
+    assert!(attributes.remove_class("c"));
+    assert!(attributes.remove_class("d"));
+    assert_eq!(attributes.get("class"), None);
+    assert_eq!(dom.outer_html(), "<p>x</p><span>y</span>");
+
+    // the attribute is created if it does not exist
+    let span = dom.query_selector_first("span").unwrap();
+    let mutations = Rc::new(RefCell::new(Vec::new()));
+    dom.set_mutation_observer(Box::new({
+        let mutations = Rc::clone(&mutations);
+        move |mutation| mutations.borrow_mut().push(mutation)
+    }));
+
+    let mut attributes = dom.attributes_mut(span).unwrap();
+    assert!(attributes.add_class("new"));
+    assert!(!attributes.add_class("new"));
+    assert!(attributes.toggle_class("other"));
+    assert_eq!(
+        dom.outer_html(),
+        r#"<p>x</p><span class="new other">y</span>"#
+    );
+    assert_eq!(dom.query_selector_count(".other.new"), Some(1));
+
+    let mut attributes = dom.attributes_mut(span).unwrap();
+    assert!(!attributes.toggle_class("new"));
+    assert!(attributes.remove_class("other"));
+    assert_eq!(dom.outer_html(), "<p>x</p><span>y</span>");
+
+    let class_set = || Mutation::AttributeSet {
+        handle: span,
+        name: "class".into(),
+    };
+    assert_eq!(
+        *mutations.borrow(),
+        [
+            class_set(),
+            class_set(),
+            class_set(),
+            Mutation::AttributeRemoved {
+                handle: span,
+                name: "class".into(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn decoded_ids_and_classes() {
+    let input = r#"<div id="a&amp;b" class="x&#38;y  tom&amp;jerry"><p id="caf&#xE9;" class="plain">1</p></div><p id=a&b>2</p>"#;
+
+    for options in [
+        ParserOptions::default(),
+        ParserOptions::default().track_ids().track_classes(),
+    ] {
+        let dom = parse(input, options).unwrap();
+        let div = dom.query_selector_first("div").unwrap();
+        let p = dom.query_selector_first("p").unwrap();
+
+        // the first element with the decoded id is found
+        assert_eq!(dom.get_element_by_id("a&b"), Some(div));
+        assert_eq!(dom.get_element_by_id("café"), Some(p));
+        assert_eq!(dom.get_element_by_id("a&amp;b"), None);
+        assert_eq!(
+            dom.get_elements_by_class_name("x&y").collect::<Vec<_>>(),
+            [div]
+        );
+        assert_eq!(dom.get_elements_by_class_name("tom&jerry").count(), 1);
+        assert_eq!(dom.get_elements_by_class_name("x&#38;y").count(), 0);
+        assert_eq!(dom.get_elements_by_class_name("plain").count(), 1);
+
+        assert_eq!(dom.query_selector_count(r"#a\26 b"), Some(2));
+        assert_eq!(dom.query_selector_count(r"#caf\E9"), Some(1));
+        assert_eq!(dom.query_selector_count(r".x\&y > #caf\E9.plain"), Some(1));
+        assert_eq!(dom.query_selector_count(r".tom\&jerry"), Some(1));
+
+        let view = dom.subtree_view(div).unwrap();
+        assert_eq!(view.get_element_by_id("café"), Some(p));
+        assert_eq!(view.get_elements_by_class_name("x&y").count(), 1);
+    }
+
+    // raw matching keeps comparing the ids and class names as written in the source
+    for options in [
+        ParserOptions::default().raw_id_and_class_matching(),
+        ParserOptions::default()
+            .raw_id_and_class_matching()
+            .track_ids()
+            .track_classes(),
+    ] {
+        let dom = parse(input, options).unwrap();
+        let div = dom.query_selector_first("div").unwrap();
+        let last = dom.query_selector("p").unwrap().last().unwrap();
+
+        assert_eq!(dom.get_element_by_id("a&amp;b"), Some(div));
+        assert_eq!(dom.get_element_by_id("a&b"), Some(last));
+        assert_eq!(dom.get_element_by_id("café"), None);
+        assert_eq!(dom.get_elements_by_class_name("x&#38;y").count(), 1);
+        assert_eq!(dom.get_elements_by_class_name("x&y").count(), 0);
+        assert_eq!(dom.query_selector_count(r"#a\26 b"), Some(1));
+        assert_eq!(dom.query_selector_count(r".x\&y"), Some(0));
+    }
+
+    // the flags are preserved by raw bitsets, and unknown bits are rejected
+    let options = ParserOptions::default()
+        .raw_id_and_class_matching()
+        .track_parents()
+        .strict();
+    let raw = ParserOptions::from_raw_checked(options.to_raw()).unwrap();
+    assert!(raw.is_raw_id_and_class_matching());
+    assert!(raw.is_tracking_parents());
+    assert!(raw.is_strict());
+    assert!(ParserOptions::from_raw_checked(u32::MAX).is_none());
+}
+
+#[test]
+fn attribute_changes_update_lookup_tables() {
+    let options = ParserOptions::default()
+        .track_ids()
+        .track_classes()
+        .track_attribute("name");
+    let mut dom = parse(
+        r#"<p id=first class=a>1</p><p id=second name=x>2</p><p>3</p>"#,
+        options,
+    )
+    .unwrap();
+    let handles = dom.query_selector("p").unwrap().collect::<Vec<_>>();
+    let [first, second, third] = handles[..] else {
+        unreachable!()
+    };
+
+    // changing an id
+    dom.attributes_mut(first)
+        .unwrap()
+        .insert("id", Some("renamed"));
+    assert_eq!(dom.get_element_by_id("first"), None);
+    assert_eq!(dom.get_element_by_id("renamed"), Some(first));
+    assert_eq!(dom.query_selector_first("#renamed"), Some(first));
+
+    // removing an id
+    assert!(dom.attributes_mut(second).unwrap().remove("ID").is_some());
+    assert_eq!(dom.get_element_by_id("second"), None);
+
+    // adding an id, which is found after elements earlier in the document with the same id
+    dom.attributes_mut(third)
+        .unwrap()
+        .insert("id", Some("renamed"));
+    dom.attributes_mut(second)
+        .unwrap()
+        .insert("id", Some("renamed"));
+    assert_eq!(dom.get_element_by_id("renamed"), Some(first));
+    dom.attributes_mut(first).unwrap().remove("id");
+    assert_eq!(dom.get_element_by_id("renamed"), Some(second));
+
+    // classes and tracked attributes
+    let mut attributes = dom.attributes_mut(third).unwrap();
+    attributes.add_class("a");
+    attributes.insert("name", Some("x"));
+    assert!(dom.attributes_mut(first).unwrap().remove_class("a"));
+    assert_eq!(
+        dom.get_elements_by_class_name("a").collect::<Vec<_>>(),
+        [third]
+    );
+    assert_eq!(
+        dom.get_elements_by_attribute_tracked("name", "x")
+            .collect::<Vec<_>>(),
+        [second, third]
+    );
+}
+
+#[test]
+fn definition_list_with_multiple_descriptions() {
+    let input = r#"
+        <dl>
+            <dt>Color<dd>Red<dd>Blue &amp; green
+            <dt>Size</dt><dt>Dimensions</dt><dd>Large</dd>
+            <div><dt>Weight<dd>  2
+            kg</div>
+        </dl>
+    "#;
+    let dom = parse(input, ParserOptions::default()).unwrap();
+    let parser = dom.parser();
+    let dl = dom.query_selector("dl").unwrap().next().unwrap();
+
+    // the end tags of the terms and descriptions are implied
+    assert_eq!(dom.query_selector("dl > dt").unwrap().count(), 3);
+    assert_eq!(dom.query_selector("dd dd").unwrap().count(), 0);
+
+    let entries = dl
+        .get(parser)
+        .unwrap()
+        .as_tag()
+        .unwrap()
+        .as_definition_list(parser)
+        .unwrap();
+
+    let expected = [
+        ("Color", &["Red", "Blue & green"][..]),
+        ("Size", &["Large"]),
+        ("Dimensions", &["Large"]),
+        ("Weight", &["2 kg"]),
+    ];
+    assert_eq!(entries.len(), expected.len());
+    for ((term, descriptions), (expected_term, expected_descriptions)) in
+        entries.iter().zip(expected)
+    {
+        assert_eq!(term, expected_term);
+        assert_eq!(descriptions, expected_descriptions);
+    }
+
+    let ul = parse("<ul><li>a</ul>", ParserOptions::default()).unwrap();
+    assert!(ul.nodes()[0]
+        .as_tag()
+        .unwrap()
+        .as_definition_list(ul.parser())
+        .is_none());
+}
+
+#[test]
+fn nested_list_extraction() {
+    use crate::{ListItem, ListNode};
+
+    let input = r#"
+        <ul>
+            <li>Fruit
+                <ol>
+                    <li><b>Apple</b>
+                        <ul><li>Gala<li>Fuji</ul>
+                    <li>Pear
+                </ol>
+            <li>Bread</li>
+            <li>Cheese
+        </ul>
+        <p>After</p>
+    "#;
+    let dom = parse(input, ParserOptions::default()).unwrap();
+    let parser = dom.parser();
+
+    // the unclosed items do not swallow the paragraph after the list
+    let p = dom.query_selector("p").unwrap().next().unwrap();
+    assert!(p.get(parser).unwrap().as_tag().unwrap().parent().is_none());
+
+    let ul = dom.query_selector("ul").unwrap().next().unwrap();
+    let list = ul
+        .get(parser)
+        .unwrap()
+        .as_tag()
+        .unwrap()
+        .as_list(parser)
+        .unwrap();
+
+    let item = |text: &str, sublists: Vec<ListNode>| ListItem {
+        text: text.into(),
+        sublists,
+    };
+
+    assert_eq!(
+        list,
+        ListNode {
+            ordered: false,
+            items: vec![
+                item(
+                    "Fruit",
+                    vec![ListNode {
+                        ordered: true,
+                        items: vec![
+                            item(
+                                "Apple",
+                                vec![ListNode {
+                                    ordered: false,
+                                    items: vec![item("Gala", vec![]), item("Fuji", vec![])],
+                                }]
+                            ),
+                            item("Pear", vec![]),
+                        ],
+                    }]
+                ),
+                item("Bread", vec![]),
+                item("Cheese", vec![]),
+            ],
+        }
+    );
+}
+
+#[test]
+fn track_parents() {
+    let input = "Top<!-- c --><div><p>Hello <b><i>deep</i></b></p></div>";
+
+    let untracked = parse(input, ParserOptions::default()).unwrap();
+    assert!(untracked.parser().parents.is_empty());
+
+    let mut dom = parse(input, ParserOptions::default().track_parents()).unwrap();
+    let parser = dom.parser();
+
+    // top-level nodes have no parent
+    for &node in dom.children() {
+        assert_eq!(node.parent(parser), None);
+    }
+
+    let p = dom.query_selector("p").unwrap().next().unwrap();
+    let b = dom.query_selector("b").unwrap().next().unwrap();
+    let i = dom.query_selector("i").unwrap().next().unwrap();
+    let hello = p.get(parser).unwrap().children().unwrap().top()[0];
+    let deep = i.get(parser).unwrap().children().unwrap().top()[0];
+
+    assert_eq!(hello.parent(parser), Some(p));
+    assert_eq!(deep.parent(parser), Some(i));
+    assert_eq!(i.parent(parser), Some(b));
+
+    // the walk up from a deeply nested text node ends at the top-level element
+    let mut ancestors = Vec::new();
+    let mut current = deep;
+    while let Some(parent) = current.parent(parser) {
+        ancestors.push(
+            parent
+                .get(parser)
+                .unwrap()
+                .as_tag()
+                .unwrap()
+                .name()
+                .as_utf8_str()
+                .into_owned(),
+        );
+        current = parent;
+    }
+    assert_eq!(ancestors, ["i", "b", "p", "div"]);
+
+    // the table is the same as the (slower) lookup without it
+    let untracked_parser = untracked.parser();
+    for (position, node) in parser.tags.iter().enumerate() {
+        let handle = parser.handle_at(position);
+        if node.as_tag().is_none() {
+            assert_eq!(
+                handle.parent(parser),
+                NodeHandle::new(handle.get_inner()).parent(untracked_parser)
+            );
+        }
+    }
+
+    // the table is kept up to date when nodes are moved and inserted
+    let div = dom.query_selector("div").unwrap().next().unwrap();
+    dom.move_node(deep, div, 0).unwrap();
+    assert_eq!(deep.parent(dom.parser()), Some(div));
+    dom.set_inner_html(b, "new <u>text</u>").unwrap();
+    let inserted = b.get(dom.parser()).unwrap().children().unwrap().top()[0];
+    assert_eq!(inserted.parent(dom.parser()), Some(b));
+    let u = dom.query_selector("u").unwrap().next().unwrap();
+    let text = u.get(dom.parser()).unwrap().children().unwrap().top()[0];
+    assert_eq!(text.parent(dom.parser()), Some(u));
+}
+
+#[test]
+fn strict_mode() {
+    let clean = r#"<!DOCTYPE html><html><body><p class="a">Hello <b>world</b><br></p><ul><li>a<li>b</ul><!-- c --></body></html>"#;
+    let strict = ParserOptions::default().strict();
+
+    let lenient = parse(clean, ParserOptions::default()).unwrap();
+    let dom = parse(clean, strict.clone()).unwrap();
+    assert_eq!(dom.outer_html(), clean);
+    assert_eq!(dom.parser().tags.len(), lenient.parser().tags.len());
+    for (node, lenient_node) in dom.nodes().iter().zip(lenient.nodes()) {
+        assert!(node.eq_in(dom.parser(), lenient_node, lenient.parser()));
+    }
+
+    let cases = [
+        (
+            "<div><p>text</div>",
+            DiagnosticKind::StrayEndTag { name: "div".into() },
+            12,
+        ),
+        (
+            "<p>a</span></p>",
+            DiagnosticKind::StrayEndTag {
+                name: "span".into(),
+            },
+            4,
+        ),
+        (
+            "<main><section>a</section>",
+            DiagnosticKind::UnclosedElement {
+                name: "main".into(),
+            },
+            0,
+        ),
+        (
+            "<p>a <!-- never closed",
+            DiagnosticKind::UnexpectedEof {
+                in_construct: Construct::Comment,
+            },
+            5,
+        ),
+        (
+            r#"<a href="x>link</a>"#,
+            DiagnosticKind::UnexpectedEof {
+                in_construct: Construct::AttributeValue,
+            },
+            8,
+        ),
+        ("<div {{x}}></div>", DiagnosticKind::InvalidAttributeName, 5),
+    ];
+
+    for (input, kind, at) in cases {
+        let expected = Diagnostic { kind, at };
+
+        assert_eq!(
+            parse(input, strict.clone()).err(),
+            Some(ParseError::Strict(expected.clone())),
+            "{input}"
+        );
+
+        // the same error is collected as a diagnostic without strict mode
+        let dom = parse(input, ParserOptions::default().collect_diagnostics()).unwrap();
+        assert_eq!(dom.diagnostics().first(), Some(&expected), "{input}");
+    }
+}
+
+#[test]
+fn sibling_navigation() {
+    let input = "start<!-- c --><div><b>first</b> middle <i>last</i></div><p>end</p>";
+
+    for options in [
+        ParserOptions::default(),
+        ParserOptions::default().track_parents(),
+    ] {
+        let dom = parse(input, options).unwrap();
+        let parser = dom.parser();
+
+        // top-level nodes
+        let top = dom.children();
+        assert_eq!(top.len(), 4);
+        assert_eq!(top[0].prev_sibling(parser), None);
+        assert_eq!(top[0].next_sibling(parser), Some(top[1]));
+        assert_eq!(top[2].prev_sibling(parser), Some(top[1]));
+        assert_eq!(top[3].next_sibling(parser), None);
+        assert_eq!(top[0].next_element_sibling(parser), Some(top[2]));
+        assert_eq!(top[2].prev_element_sibling(parser), None);
+        assert_eq!(top[3].prev_element_sibling(parser), Some(top[2]));
+
+        // inside of a tag, where the first and last children are elements
+        let children = top[2]
+            .get(parser)
+            .unwrap()
+            .children()
+            .unwrap()
+            .top()
+            .to_vec();
+        let (b, text, i) = (children[0], children[1], children[2]);
+        assert_eq!(b.prev_sibling(parser), None);
+        assert_eq!(b.next_sibling(parser), Some(text));
+        assert_eq!(text.prev_sibling(parser), Some(b));
+        assert_eq!(text.next_sibling(parser), Some(i));
+        assert_eq!(i.next_sibling(parser), None);
+        assert_eq!(b.next_element_sibling(parser), Some(i));
+        assert_eq!(i.prev_element_sibling(parser), Some(b));
+        assert_eq!(b.prev_element_sibling(parser), None);
+        assert_eq!(i.next_element_sibling(parser), None);
+        assert_eq!(text.next_element_sibling(parser), Some(i));
+        assert_eq!(text.prev_element_sibling(parser), Some(b));
+
+        // only children have no siblings
+        let first = b.get(parser).unwrap().children().unwrap().top()[0];
+        assert_eq!(first.next_sibling(parser), None);
+        assert_eq!(first.prev_sibling(parser), None);
+    }
+
+    // detached nodes have no siblings
+    let mut dom = parse(input, ParserOptions::default()).unwrap();
+    let detached = dom
+        .parser_mut()
+        .add_node(Node::Comment("<!-- x -->".into()));
+    assert_eq!(detached.next_sibling(dom.parser()), None);
+    assert_eq!(detached.prev_sibling(dom.parser()), None);
+}
+
+#[test]
+fn source_bytes() {
+    let input = r#"<!DOCTYPE html><div id="a">Text <!-- note --><b>bold</b></span> tail<br><ul><li>one<li>two</ul></div><p>unclosed"#;
+    let mut dom = parse(input, ParserOptions::default()).unwrap();
+    let parser = dom.parser();
+
+    // every parsed node is backed by the source, including elements with ignored or omitted end tags
+    for node in dom.nodes() {
+        let source = std::str::from_utf8(node.source_bytes(parser).unwrap()).unwrap();
+        assert!(input.contains(source));
+    }
+
+    let div = dom.get_element_by_id("a").unwrap();
+    let b = dom.query_selector("b").unwrap().next().unwrap();
+    let br = dom.query_selector("br").unwrap().next().unwrap();
+    let source = div.get(parser).unwrap().source_bytes(parser).unwrap();
+    assert!(source.ends_with(b"</span> tail<br><ul><li>one<li>two</ul></div>"));
+
+    let children = div.get(parser).unwrap().children().unwrap().top().to_vec();
+    let (text, comment) = (children[0], children[1]);
+
+    // changing a text node only affects it and its ancestors
+    dom.set_inner_text(b, "strong").unwrap();
+    let parser = dom.parser();
+    assert!(!b.get(parser).unwrap().is_source_backed(parser));
+    assert!(!div.get(parser).unwrap().is_source_backed(parser));
+    assert!(text.get(parser).unwrap().is_source_backed(parser));
+    assert!(comment.get(parser).unwrap().is_source_backed(parser));
+    assert!(br.get(parser).unwrap().is_source_backed(parser));
+
+    // text, comments and attributes that are changed in place
+    let parser = dom.parser_mut();
+    let raw = text.get_mut(parser).unwrap().as_raw_mut().unwrap();
+    raw.set("Changed ").unwrap();
+    let raw = comment.get_mut(parser).unwrap().as_comment_mut().unwrap();
+    raw.set("<!-- changed -->").unwrap();
+    dom.attributes_mut(br).unwrap().insert("class", Some("x"));
+    let parser = dom.parser();
+    for handle in [text, comment, br] {
+        assert_eq!(handle.get(parser).unwrap().source_bytes(parser), None);
+    }
+
+    // removing a child is detected, even though the remaining children are unchanged
+    let mut dom = parse(input, ParserOptions::default()).unwrap();
+    let ul = dom.query_selector("ul").unwrap().next().unwrap();
+    let li = dom.query_selector("li").unwrap().next().unwrap();
+    dom.remove(li).unwrap();
+    let parser = dom.parser();
+    assert!(!ul.get(parser).unwrap().is_source_backed(parser));
+    assert!(dom
+        .query_selector("li")
+        .unwrap()
+        .next()
+        .unwrap()
+        .get(parser)
+        .unwrap()
+        .is_source_backed(parser));
+
+    // created nodes and text that differs from the source
+    let mut dom = parse(
+        "<p>a\r\nb</p>",
+        ParserOptions::default().normalize_newlines(),
+    )
+    .unwrap();
+    let created = dom.parser_mut().add_node(Node::Raw("new".into()));
+    let parser = dom.parser();
+    assert!(!created.get(parser).unwrap().is_source_backed(parser));
+    assert!(!dom.nodes()[1].is_source_backed(parser));
+}
+
+#[test]
+fn descendants_iterator() {
+    let input = r#"<main><p>test</p><div><span>test2</span></div><!-- c --></main>"#;
+    let dom = parse(input, Default::default()).unwrap();
+    let parser = dom.parser();
+    let main = dom.nodes()[0].as_tag().unwrap();
+
+    // the same order as `nodes_order`, followed by the comment
+    let descendants = main.descendants(parser);
+    assert_eq!(descendants.len(), 6);
+    let nodes = descendants
+        .clone()
+        .map(|handle| handle.get(parser).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(&nodes[0].as_tag().unwrap()._name, "p");
+    assert_eq!(nodes[1].as_raw().unwrap().as_bytes(), b"test");
+    assert_eq!(&nodes[2].as_tag().unwrap()._name, "div");
+    assert_eq!(&nodes[3].as_tag().unwrap()._name, "span");
+    assert_eq!(nodes[4].as_raw().unwrap().as_bytes(), b"test2");
+    assert!(nodes[5].as_comment().is_some());
+
+    let forward = descendants.clone().collect::<Vec<_>>();
+    let mut backward = descendants.clone().rev().collect::<Vec<_>>();
+    backward.reverse();
+    assert_eq!(forward, backward);
+
+    // elements only, from both ends at once
+    let mut elements = descendants.elements();
+    let names = |handle: NodeHandle| {
+        handle
+            .get(parser)
+            .unwrap()
+            .as_tag()
+            .unwrap()
+            .name()
+            .as_utf8_str()
+            .into_owned()
+    };
+    assert_eq!(elements.next().map(names).as_deref(), Some("p"));
+    assert_eq!(elements.next_back().map(names).as_deref(), Some("span"));
+    assert_eq!(elements.next().map(names).as_deref(), Some("div"));
+    assert_eq!(elements.next(), None);
+    assert_eq!(elements.next_back(), None);
+
+    // handles stay in document order after nodes are inserted
+    let mut dom = parse(input, Default::default()).unwrap();
+    let div = dom.query_selector("div").unwrap().next().unwrap();
+    let span = dom.query_selector("span").unwrap().next().unwrap();
+    dom.insert_before(div, span, Node::Raw("new".into()))
+        .unwrap();
+    let parser = dom.parser();
+    let main = dom.nodes()[0].as_tag().unwrap();
+    let texts = main
+        .descendants(parser)
+        .filter_map(|handle| handle.get(parser).unwrap().as_raw())
+        .map(|text| text.as_utf8_str().into_owned())
+        .collect::<Vec<_>>();
+    assert_eq!(texts, ["test", "new", "test2"]);
+
+    // tags without children
+    let empty = parse("<br>", Default::default()).unwrap();
+    let br = empty.nodes()[0].as_tag().unwrap();
+    assert_eq!(br.descendants(empty.parser()).next(), None);
+}
+
+#[test]
+fn tag_payload_handlers() {
+    use crate::PayloadId;
+
+    let options = ParserOptions::default()
+        .track_ids()
+        .tag_payload_handler("x-chart", |payload| {
+            PayloadId::new(payload.split(|&c| c == b'\n').count() as u64)
+        })
+        .tag_payload_handler("X-MATH", |payload| PayloadId::new(payload.len() as u64));
+
+    let input = "<x-chart id=a>year,sales\n2023,<10>\n2024,12</x-chart>\
+        <p>text</p>\
+        <x-chart id=b>\n</X-Chart>\
+        <x-math id=c>\\frac{1}{2}</x-math>\
+        <x-chart id=d></x-chart>";
+    let dom = parse(input, options).unwrap();
+    let parser = dom.parser();
+    let payload_id = |id: &str| {
+        let tag = dom.get_element_by_id(id).unwrap().get(parser).unwrap();
+        tag.as_tag().unwrap().payload_id(parser)
+    };
+
+    assert_eq!(payload_id("a"), Some(PayloadId::new(3)));
+    assert_eq!(payload_id("b"), Some(PayloadId::new(2)));
+    assert_eq!(payload_id("c").map(|id| id.get()), Some(11));
+    // the handler is called for empty payloads too
+    assert_eq!(payload_id("d"), Some(PayloadId::new(1)));
+
+    // the content is kept as raw text
+    let chart = dom.get_element_by_id("a").unwrap().get(parser).unwrap();
+    assert_eq!(chart.inner_text(parser), "year,sales\n2023,<10>\n2024,12");
+    assert_eq!(dom.query_selector("p").unwrap().count(), 1);
+    let p = dom.query_selector("p").unwrap().next().unwrap();
+    assert_eq!(
+        p.get(parser).unwrap().as_tag().unwrap().payload_id(parser),
+        None
+    );
+
+    // handlers are replaced by name
+    let options = ParserOptions::default()
+        .tag_payload_handler("x-chart", |_| PayloadId::new(1))
+        .tag_payload_handler("x-chart", |_| PayloadId::new(2));
+    let dom = parse("<x-chart>x</x-chart>", options).unwrap();
+    let tag = dom.nodes()[0].as_tag().unwrap();
+    assert_eq!(tag.payload_id(dom.parser()), Some(PayloadId::new(2)));
+
+    // a copy of a tag does not belong to the parser
+    assert_eq!(tag.clone().payload_id(dom.parser()), None);
+
+    // looking up the identifiers of many siblings is not quadratic, and still works once the node table changed
+    let options = ParserOptions::default()
+        .tag_payload_handler("x-item", |payload| PayloadId::new(payload.len() as u64));
+    let input = (0..20_000)
+        .map(|i| format!("<x-item>{i}</x-item>"))
+        .collect::<String>();
+    let mut dom = parse(&input, options).unwrap();
+    dom.remove(dom.children()[0]).unwrap();
+
+    let parser = dom.parser();
+    let ids = dom
+        .children()
+        .iter()
+        .map(|handle| {
+            let tag = handle.get(parser).unwrap().as_tag().unwrap();
+            tag.payload_id(parser).unwrap().get()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(ids.len(), 19_999);
+    assert_eq!(&ids[..3], [1, 1, 1]);
+    assert_eq!(ids[8], 1);
+    assert_eq!(ids[9], 2);
+    assert_eq!(ids[19_998], 5);
+}
+
+#[test]
+fn ancestors_iterator() {
+    let input = r#"<html><body><div class="content"><p>Hello <b>world</b></p></div></body></html><!-- c -->"#;
+
+    for options in [
+        ParserOptions::default(),
+        ParserOptions::default().track_parents(),
+    ] {
+        let mut dom = parse(input, options).unwrap();
+        let parser = dom.parser();
+
+        let b = dom.query_selector("b").unwrap().next().unwrap();
+        let text = b.get(parser).unwrap().children().unwrap().top()[0];
+        let path = text
+            .ancestor_tags(parser)
+            .map(|tag| tag.name().as_utf8_str().into_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(path, ["b", "p", "div", "body", "html"]);
+        assert_eq!(text.ancestors(parser).next(), Some(b));
+
+        // top-level nodes have no ancestors
+        for &node in dom.children() {
+            assert_eq!(node.ancestors(parser).count(), 0);
+        }
+
+        // a cycle in the parents still ends
+        let html = dom.children()[0];
+        dom.parser_mut()
+            .resolve_node_id_mut(html.get_inner())
+            .unwrap()
+            .as_tag_mut()
+            .unwrap()
+            ._parent = Some(b);
+        let parser = dom.parser();
+        assert!(b.ancestors(parser).count() <= parser.tags.len());
+    }
+}
+
+#[cfg(feature = "url")]
+#[test]
+fn links_deduped() {
+    use crate::NormalizeOptions;
+    use url::Url;
+
+    let dom = parse(
+        r#"
+        <a href="/a%2fb">1</a>
+        <a href="/a%2Fb">2</a>
+        <a href="https://Example.com:443/a%2fb#top">3</a>
+        <a href="/docs/">4</a>
+        <a href="/docs">5</a>
+        <a href="/search?q=1&amp;lang=en">6</a>
+        <a href="/search?lang=en&amp;q=1">7</a>
+        <a href="http://example.com/a%2Fb">8</a>
+        <a href="http://[">9</a>
+        "#,
+        ParserOptions::default(),
+    )
+    .unwrap();
+    let parser = dom.parser();
+    let base = Url::parse("https://example.com/").unwrap();
+
+    let links = dom.links_deduped(Some(&base), NormalizeOptions::default());
+    assert_eq!(links.len(), 5);
+    let (first, url) = links.iter().next().unwrap();
+    assert_eq!(first.get(parser).unwrap().inner_text(parser), "1");
+    assert_eq!(url.as_str(), "https://example.com/a%2Fb");
+
+    let sorted = NormalizeOptions {
+        sort_query: true,
+        ..Default::default()
+    };
+    assert_eq!(dom.links_deduped(Some(&base), sorted).len(), 4);
+
+    let exact = NormalizeOptions {
+        strip_fragment: false,
+        strip_trailing_slash: false,
+        sort_query: false,
+    };
+    assert_eq!(dom.links_deduped(Some(&base), exact).len(), 7);
+
+    // relative links cannot be resolved without a base
+    let links = dom.links_deduped(None, NormalizeOptions::default());
+    let urls = links.urls().map(Url::as_str).collect::<Vec<_>>();
+    assert_eq!(
+        urls,
+        ["https://example.com/a%2Fb", "http://example.com/a%2Fb"]
+    );
+}
+
+#[test]
+fn tag_get_elements_by_tag_name() {
+    let html = "<a>0</a><div id=nav><a>1</a><p><A>2</A><b>x</b></p><a>3</a></div><a>4</a>";
+
+    for options in [
+        ParserOptions::default(),
+        ParserOptions::default().track_tag_names(),
+    ] {
+        let dom = parse(html, options).unwrap();
+        let parser = dom.parser();
+        let texts = |handles: Box<dyn Iterator<Item = NodeHandle> + '_>| {
+            handles
+                .map(|handle| handle.get(parser).unwrap().inner_text(parser))
+                .collect::<Vec<_>>()
+        };
+
+        let nav = dom
+            .get_elements_by_tag_name("DIV")
+            .next()
+            .unwrap()
+            .get(parser)
+            .unwrap()
+            .as_tag()
+            .unwrap();
+        assert_eq!(
+            texts(nav.get_elements_by_tag_name(parser, "a")),
+            ["1", "2", "3"]
+        );
+        assert_eq!(texts(nav.get_elements_by_tag_name(parser, "B")), ["x"]);
+        assert_eq!(nav.get_elements_by_tag_name(parser, "div").count(), 0);
+
+        let p = nav.get_elements_by_tag_name(parser, "p").next().unwrap();
+        let p = p.get(parser).unwrap().as_tag().unwrap();
+        assert_eq!(texts(p.get_elements_by_tag_name(parser, "a")), ["2"]);
+
+        assert_eq!(
+            texts(dom.get_elements_by_tag_name("a")),
+            ["0", "1", "2", "3", "4"]
+        );
+    }
+}
+
+/// Generates a document with `items` list items that exercise implied end tags, raw text, comments and multibyte text
+fn streamed_document_item(i: usize) -> String {
+    let end = if i % 2 == 1 { "</li>" } else { "" };
+    let br = if i.is_multiple_of(3) { "<br>" } else { "" };
+
+    format!(
+        "<li class=\"item item-{i}\" data-i={i}>Item &amp; {i} é<b>日本</b><!-- c > -->{br}\
+         <a href=\"/i/{i}\">link</a><script>if (a </b> \"</scrip\") {{}}</script> <img alt=x{i}/>\
+         <dl><dt>t<dd>d{i}<dt>u</dl><select><option>o<option selected>p{i}</select></span>{end}\n"
+    )
+}
+
+fn streamed_document(items: usize) -> String {
+    let mut html = String::from("<!doctype html><html><body><ul class=items>");
+    for i in 0..items {
+        html.push_str(&streamed_document_item(i));
+    }
+    html.push_str("</ul><p id=target>end</p></body></html>");
+    html
+}
+
+/// A reader that returns at most `step` bytes per read
+struct Trickle<'a> {
+    data: &'a [u8],
+    step: usize,
+}
+
+impl std::io::Read for Trickle<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = self.step.min(buf.len()).min(self.data.len());
+        buf[..len].copy_from_slice(&self.data[..len]);
+        self.data = &self.data[len..];
+        Ok(len)
+    }
+}
+
+#[test]
+fn stream_select_matches_dom() {
+    use crate::SelectorSet;
+
+    let selectors = [
+        "li",
+        "ul.items > li > b",
+        "li a[href$=\"5\"]",
+        "script",
+        "dd",
+        "option[selected]",
+        "#target",
+        ":root",
+        "li b, dt",
+        "img[alt]",
+    ];
+    let mut set = SelectorSet::new();
+    for selector in selectors {
+        set.add(selector).unwrap();
+    }
+
+    let document = streamed_document(40);
+    let mut samples = vec![document.as_str()];
+    for cut in [
+        document.len() / 3,
+        document.len() / 2 + 17,
+        document.len() - 9,
+    ] {
+        let cut = (0..=cut)
+            .rev()
+            .find(|&i| document.is_char_boundary(i))
+            .unwrap();
+        samples.push(&document[..cut]);
+    }
+
+    for sample in samples {
+        let dom = parse(sample, ParserOptions::default()).unwrap();
+        let parser = dom.parser();
+
+        let expected = selectors
+            .iter()
+            .map(|selector| {
+                dom.query_selector(selector)
+                    .unwrap()
+                    .map(|handle| {
+                        let ancestors = handle
+                            .ancestor_tags(parser)
+                            .map(|tag| tag.name().as_utf8_str().into_owned())
+                            .collect::<Vec<_>>();
+                        let text = handle.get(parser).unwrap().inner_text(parser).into_owned();
+                        (text, ancestors)
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        assert!(
+            expected.iter().all(|matches| !matches.is_empty()) || sample.len() < document.len()
+        );
+
+        for step in [1, 7, 4096, usize::MAX] {
+            let mut events = Vec::new();
+            let reader = Trickle {
+                data: sample.as_bytes(),
+                step,
+            };
+
+            crate::stream_select(reader, &set, |event| {
+                let ancestors = event
+                    .ancestor_names()
+                    .map(|name| name.as_utf8_str().into_owned())
+                    .collect::<Vec<_>>();
+                let text = event.inner_text().to_owned();
+                events.push((event.selector(), event.element_index(), text, ancestors));
+            })
+            .unwrap();
+
+            events.sort_by_key(|&(selector, index, ..)| (selector, index));
+            let actual = (0..selectors.len())
+                .map(|selector| {
+                    events
+                        .iter()
+                        .filter(|event| event.0 == selector)
+                        .map(|(_, _, text, ancestors)| (text.clone(), ancestors.clone()))
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>();
+
+            assert_eq!(actual, expected, "step {step}, {} bytes", sample.len());
+        }
+    }
+}
+
+#[test]
+fn stream_select_bounded_memory() {
+    use crate::queryselector::ParseErrorKind;
+    use crate::streaming::buffered;
+    use crate::SelectorSet;
+
+    /// Generates the items of a document on demand
+    struct Generated {
+        next: usize,
+        items: usize,
+        chunk: Vec<u8>,
+    }
+
+    impl std::io::Read for Generated {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            while self.chunk.is_empty() && self.next <= self.items {
+                self.chunk = match self.next {
+                    0 => "<html><body><ul class=items>".into(),
+                    i if i == self.items => "</ul></body></html>".into(),
+                    i => streamed_document_item(i).into(),
+                };
+                self.next += 1;
+            }
+
+            let len = buf.len().min(self.chunk.len());
+            buf[..len].copy_from_slice(&self.chunk[..len]);
+            self.chunk.drain(..len);
+            Ok(len)
+        }
+    }
+
+    let mut set = SelectorSet::new();
+    set.add("li > a").unwrap();
+    set.add("ul.items").unwrap();
+    assert_eq!(
+        set.add("li + li").unwrap_err().kind(),
+        ParseErrorKind::NotStreamable
+    );
+
+    let items = 5_000;
+    let reader = Generated {
+        next: 0,
+        items,
+        chunk: Vec::new(),
+    };
+
+    buffered::take();
+    let (mut links, mut lists) = (0, 0);
+    crate::stream_select(reader, &set, |event| match event.selector() {
+        0 => links += 1,
+        _ => lists += event.ancestor_names().count() + 1,
+    })
+    .unwrap();
+
+    assert_eq!((links, lists), (items - 1, 3));
+    // the document is more than a megabyte long, but only a chunk and the rest of the previous one are buffered
+    assert!(buffered::take() < 2 * 64 * 1024);
+}
+
+#[test]
+fn get_elements_by_name() {
+    let input = r#"
+        <form>
+            <input type="radio" name="color" value="red">
+            <input type="radio" name="color" value="green" checked>
+            <input name="email"><input name><textarea name=""></textarea>
+            <input type="radio" name="Color" value="blue">
+        </form>
+        <input type="radio" name="color" value="other">
+    "#;
+
+    for tracked in [false, true] {
+        let options = match tracked {
+            true => ParserOptions::default().track_names(),
+            false => ParserOptions::default(),
+        };
+        assert_eq!(options.is_tracking_names(), tracked);
+        let dom = parse(input, options).unwrap();
+        let parser = dom.parser();
+
+        let values = |name| {
+            dom.get_elements_by_name(name)
+                .map(|handle| {
+                    let tag = handle.get(parser).unwrap().as_tag().unwrap();
+                    let value = tag.attributes().get("value").flatten();
+                    value.map_or_else(|| tag.name().as_utf8_str(), |value| value.as_utf8_str())
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // radio buttons share a name, which is compared case-sensitively
+        assert_eq!(values("color"), ["red", "green", "other"]);
+        assert_eq!(values("Color"), ["blue"]);
+        assert_eq!(values("email"), ["input"]);
+        // a valueless name attribute has an empty value
+        assert_eq!(values(""), ["input", "textarea"]);
+        assert!(values("missing").is_empty());
+    }
+}
+
+#[test]
+fn text_nodes() {
+    let input = r#"
+    <p>test</p><div><span>test2</span></div>
+    "#
+    .trim();
+    let dom = parse(input, Default::default()).unwrap();
+
+    // the text nodes of `nodes_order`, at the same positions
+    let handles = dom
+        .text_nodes()
+        .map(|(handle, _)| handle)
+        .collect::<Vec<_>>();
+    assert_eq!(handles, [NodeHandle::new(1), NodeHandle::new(4)]);
+    let texts = dom
+        .text_nodes()
+        .map(|(_, text)| text.as_bytes())
+        .collect::<Vec<_>>();
+    assert_eq!(texts, [&b"test"[..], b"test2"]);
+
+    let input = "<main>\n  <p>a<!-- b --></p>\n  <div> <span>c</span>d</div>\n</main><p>e</p>";
+    let dom = parse(input, Default::default()).unwrap();
+    let parser = dom.parser();
+    let main = dom.nodes()[0].as_tag().unwrap();
+
+    let texts = |nodes: TextNodes<'_, '_>| {
+        nodes
+            .map(|(handle, text)| {
+                assert_eq!(handle.get(parser).unwrap().as_raw(), Some(text));
+                text.as_utf8_str().into_owned()
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        texts(main.text_nodes(parser)),
+        ["\n  ", "a", "\n  ", " ", "c", "d", "\n"]
+    );
+    assert_eq!(
+        texts(main.text_nodes(parser).non_whitespace()),
+        ["a", "c", "d"]
+    );
+    assert_eq!(
+        texts(dom.text_nodes().non_whitespace()),
+        ["a", "c", "d", "e"]
+    );
+
+    let mut backward = main
+        .text_nodes(parser)
+        .non_whitespace()
+        .rev()
+        .collect::<Vec<_>>();
+    backward.reverse();
+    assert_eq!(
+        main.text_nodes(parser).non_whitespace().collect::<Vec<_>>(),
+        backward
+    );
+
+    // every text node can be mapped back to its parent element
+    let parents = main
+        .text_nodes(parser)
+        .non_whitespace()
+        .map(|(handle, _)| {
+            let parent = handle.parent(parser).unwrap().get(parser).unwrap();
+            parent.as_tag().unwrap().name().as_utf8_str()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(parents, ["p", "span", "div"]);
+
+    let empty = dom.nodes()[2].as_tag().unwrap();
+    assert_eq!(empty.text_nodes(parser).count(), 1);
+}
+
+#[test]
+fn find_and_remove_comments_and_text() {
+    let input = concat!(
+        "<!DOCTYPE html><body>",
+        "<!-- BEGIN AD --><div class=ad>Buy</div><!-- END AD -->",
+        "<p>[[draft]]Intro<!-- BEGIN AD inline --></p>",
+        "<ul><li>[[draft]]One</li><li>Two<!-- comment --></li></ul>",
+        "</body><!--BEGIN AD-->"
+    );
+    let mut dom = parse(input, Default::default()).unwrap();
+
+    let ads = dom
+        .find_comments(|comment| comment.contains("BEGIN AD"))
+        .collect::<Vec<_>>();
+    assert_eq!(ads.len(), 3);
+    let parser = dom.parser();
+    assert!(ads
+        .iter()
+        .all(|handle| handle.get(parser).unwrap().as_comment().is_some()));
+    // the doctype is not a comment
+    assert_eq!(dom.find_comments(|_| true).count(), 5);
+    assert_eq!(
+        dom.find_comments(|comment| comment == "BEGIN AD").count(),
+        1
+    );
+
+    let drafts = dom
+        .find_raw(|text| text.starts_with("[[draft]]"))
+        .collect::<Vec<_>>();
+    assert_eq!(drafts.len(), 2);
+    // comments are not text nodes, even if their content matches
+    assert_eq!(dom.find_raw(|text| text.contains("AD")).count(), 0);
+
+    for handle in drafts {
+        let text = handle.get(dom.parser()).unwrap().inner_text(dom.parser());
+        let text = text.trim_start_matches("[[draft]]").to_owned();
+        dom.set_text(handle, &text).unwrap();
+    }
+
+    assert_eq!(
+        dom.remove_matching_comments(|comment| comment.contains("AD")),
+        4
+    );
+    assert_eq!(
+        dom.remove_matching_comments(|comment| comment.contains("AD")),
+        0
+    );
+    assert_eq!(
+        dom.outer_html(),
+        concat!(
+            "<!DOCTYPE html><body>",
+            "<div class=ad>Buy</div>",
+            "<p>Intro</p>",
+            "<ul><li>One</li><li>Two<!-- comment --></li></ul>",
+            "</body>"
+        )
+    );
+    assert_eq!(dom.find_comments(|_| true).count(), 1);
+    assert_eq!(dom.remove_matching_comments(|_| true), 1);
+    assert!(dom.outer_html().starts_with("<!DOCTYPE html>"));
+}
+
+#[test]
+fn inner_text_with_separator() {
+    let dom = parse(
+        "<table><tr>\n  <td>a</td>\n  <td>b <i>c</i></td>\n  <td>d</td>\n</tr></table>",
+        Default::default(),
+    )
+    .unwrap();
+    let parser = dom.parser();
+    let tr = dom.get_elements_by_tag_name("tr").next().unwrap();
+    let tr = tr.get(parser).unwrap().as_tag().unwrap();
+    assert_eq!(tr.inner_text_with(parser, "\t"), "a\tb c\td");
+
+    let dom = parse(
+        "<div><p>First <b>para</b>graph</p><!-- note --><p>Second</p></div>",
+        Default::default(),
+    )
+    .unwrap();
+    let parser = dom.parser();
+    let div = dom.nodes()[0].as_tag().unwrap();
+    assert_eq!(
+        div.inner_text_with(parser, "\n\n"),
+        "First paragraph\n\nSecond"
+    );
+    // text nodes are not split, only the contributions of distinct children are separated
+    let p = div.children().top()[0]
+        .get(parser)
+        .unwrap()
+        .as_tag()
+        .unwrap();
+    assert_eq!(p.inner_text_with(parser, "|"), "First |para|graph");
+
+    let dom = parse(
+        "<tr><td>a</td><td></td><td><img></td><td>d</td></tr><tr><td></td><td>b</td><td></td></tr>",
+        Default::default(),
+    )
+    .unwrap();
+    let parser = dom.parser();
+    let rows = dom
+        .nodes()
+        .iter()
+        .filter_map(Node::as_tag)
+        .filter(|tag| tag.name() == "tr")
+        .collect::<Vec<_>>();
+
+    let keep = TextJoinOptions { keep_empty: true };
+    assert_eq!(rows[0].inner_text_with(parser, "\t"), "a\td");
+    assert_eq!(
+        rows[0].inner_text_with_options(parser, "\t", keep),
+        "a\t\t\td"
+    );
+    assert_eq!(rows[1].inner_text_with(parser, ","), "b");
+    assert_eq!(rows[1].inner_text_with_options(parser, ",", keep), ",b,");
+}
+
+#[test]
+fn walk_enter_leave_pairing() {
+    use crate::{NodeVisitor, WalkControl};
+
+    #[derive(Default)]
+    struct Recorder {
+        events: Vec<String>,
+        open: Vec<NodeHandle>,
+        skip: &'static str,
+        stop: &'static str,
+    }
+
+    impl NodeVisitor for Recorder {
+        fn enter_tag(&mut self, tag: &HTMLTag<'_>, handle: NodeHandle) -> WalkControl {
+            let name = tag.name().as_utf8_str();
+            self.events.push(format!("<{name}>"));
+            self.open.push(handle);
+
+            match &*name {
+                name if name == self.skip => WalkControl::SkipChildren,
+                name if name == self.stop => WalkControl::Break,
+                _ => WalkControl::Continue,
+            }
+        }
+
+        fn leave_tag(&mut self, tag: &HTMLTag<'_>, handle: NodeHandle) -> WalkControl {
+            // every element is left exactly once, after all elements that were entered within it
+            assert_eq!(self.open.pop(), Some(handle));
+            self.events.push(format!("</{}>", tag.name().as_utf8_str()));
+            WalkControl::Continue
+        }
+
+        fn visit_raw(&mut self, text: &Bytes<'_>, _: NodeHandle) -> WalkControl {
+            self.events.push(text.as_utf8_str().into_owned());
+            WalkControl::Continue
+        }
+
+        fn visit_comment(&mut self, _: &Bytes<'_>, _: NodeHandle) -> WalkControl {
+            self.events.push("#comment".into());
+            WalkControl::Continue
+        }
+    }
+
+    let dom = parse(
+        "<div><p>a<b>b</b><br>c</p><!-- x --><ul><li>1<li>2</ul></div>d",
+        Default::default(),
+    )
+    .unwrap();
+    let parser = dom.parser();
+
+    let mut recorder = Recorder::default();
+    dom.walk(&mut recorder);
+    assert!(recorder.open.is_empty());
+    assert_eq!(
+        recorder.events.join(""),
+        "<div><p>a<b>b</b><br></br>c</p>#comment<ul><li>1</li><li>2</li></ul></div>d"
+    );
+
+    let mut recorder = Recorder {
+        skip: "ul",
+        ..Default::default()
+    };
+    dom.walk(&mut recorder);
+    assert_eq!(
+        recorder.events.join(""),
+        "<div><p>a<b>b</b><br></br>c</p>#comment<ul></ul></div>d"
+    );
+
+    let mut recorder = Recorder {
+        stop: "b",
+        ..Default::default()
+    };
+    dom.walk(&mut recorder);
+    assert_eq!(recorder.events.join(""), "<div><p>a<b>");
+
+    let ul = dom.get_elements_by_tag_name("ul").next().unwrap();
+    let mut recorder = Recorder::default();
+    ul.get(parser)
+        .unwrap()
+        .as_tag()
+        .unwrap()
+        .walk(parser, &mut recorder);
+    assert_eq!(recorder.events.join(""), "<ul><li>1</li><li>2</li></ul>");
+
+    // deeply nested documents do not overflow the stack
+    let input = "<div>".repeat(100_000) + &"<p>".repeat(10_000);
+    let dom = parse(&input, Default::default()).unwrap();
+    let mut recorder = Recorder::default();
+    dom.walk(&mut recorder);
+    assert!(recorder.open.is_empty());
+    assert_eq!(recorder.events.len(), 2 * (100_000 + 10_000));
+}
+
+#[test]
+fn find_node_by_predicate() {
+    let input =
+        r#"<p>hello <span>whats up</span></p><div><img src="a.png"><p><img src="b.png"></p></div>"#;
+    let mut dom = parse(input, ParserOptions::default()).unwrap();
+    let is_img = |node: &Node, _| node.as_tag().is_some_and(|tag| tag.name() == "img");
+
+    // the same search as in `with`, but returning a handle
+    let span = dom
+        .find_node(|node, _| node.as_tag().is_some_and(|tag| tag.name() == "span"))
+        .unwrap();
+    assert_eq!(
+        span.get(dom.parser()).unwrap().inner_text(dom.parser()),
+        "whats up"
+    );
+
+    let images = dom.find_all_nodes(is_img).collect::<Vec<_>>();
+    assert_eq!(images.len(), 2);
+    // the predicate receives the handle of each node
+    assert_eq!(
+        dom.find_node(|_, handle| handle == images[1]),
+        Some(images[1])
+    );
+
+    let div = dom.get_elements_by_tag_name("div").next().unwrap();
+    let inner_p = {
+        let parser = dom.parser();
+        let div = div.get(parser).unwrap();
+        assert_eq!(div.find_node(parser, is_img), Some(images[0]));
+        let p = div
+            .find_node(parser, |node, _| {
+                node.as_tag().is_some_and(|tag| tag.name() == "p")
+            })
+            .unwrap();
+
+        let p_tag = p.get(parser).unwrap().as_tag().unwrap();
+        assert_eq!(
+            p_tag.find_all_nodes(parser, is_img).collect::<Vec<_>>(),
+            [images[1]]
+        );
+        // only the subtree is searched, excluding the element itself
+        assert_eq!(p_tag.find_node(parser, |_, handle| handle == p), None);
+        assert_eq!(
+            p_tag.find_node(parser, |_, handle| handle == images[0]),
+            None
+        );
+        p
+    };
+    assert!(dom.nodes()[0]
+        .find_node(dom.parser(), |_, handle| handle == inner_p)
+        .is_none());
+
+    // the returned handles can be resolved mutably, like in `mutate_dom`
+    let parser = dom.parser_mut();
+    let tag = images[1].get_mut(parser).unwrap().as_tag_mut().unwrap();
+    let bytes = tag.attributes_mut().get_mut("src").flatten().unwrap();
+    bytes.set("c.png").unwrap();
+
+    assert_eq!(
+        dom.outer_html(),
+        r#"<p>hello <span>whats up</span></p><div><img src="a.png"><p><img src="c.png"></p></div>"#
+    );
+}
+
+#[test]
+fn element_children() {
+    // the document of `ignore_void_closing_tags`, without indexing past the whitespace
+    let input = r#"
+        <head>
+            <base href='single_quoted_item'></base>
+            <!-- styles -->
+            <link rel="stylesheet" type="text/css" href="non-exising"/>
+        </head>
+    "#;
+
+    let dom = parse(input, ParserOptions::default()).unwrap();
+    let parser = dom.parser();
+
+    assert_eq!(dom.element_children().count(), 1);
+    let (head, head_tag) = dom.first_element_child().unwrap();
+    assert_eq!(dom.children()[1], head);
+    assert_eq!(dom.last_element_child().unwrap().0, head);
+    assert_eq!(head_tag.name(), "head");
+
+    let names = head_tag
+        .element_children(parser)
+        .map(|(_, tag)| tag.name().as_utf8_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["base", "link"]);
+
+    let (base, base_tag) = head_tag.first_element_child(parser).unwrap();
+    let (link, link_tag) = head_tag.last_element_child(parser).unwrap();
+    assert_eq!(base_tag.name(), "base");
+    assert_eq!(link_tag.name(), "link");
+    assert_eq!(head_tag.children().top()[1], base);
+    assert_eq!(head_tag.children().top()[5], link);
+
+    let mut children = head_tag.element_children(parser);
+    assert_eq!(children.size_hint(), (0, Some(7)));
+    assert_eq!(children.next_back().map(|(handle, _)| handle), Some(link));
+    assert_eq!(children.next().map(|(handle, _)| handle), Some(base));
+    assert!(children.next().is_none());
+
+    // elements without child elements
+    assert!(base_tag.first_element_child(parser).is_none());
+    let dom = parse("text<!-- comment -->", ParserOptions::default()).unwrap();
+    assert!(dom.first_element_child().is_none());
+    assert!(dom.last_element_child().is_none());
+}
+
+#[test]
+fn write_html_and_text() {
+    use std::fmt::{self, Write};
+
+    let input = r#"<!DOCTYPE html><html><body><p id=a>Hello <b>world</b><br></p><!-- x --><ul><li>1<li>2</ul></body></html>"#;
+    let mut dom = parse(input, Default::default()).unwrap();
+    let a = dom.get_element_by_id("a").unwrap();
+    a.get_mut(dom.parser_mut())
+        .unwrap()
+        .as_tag_mut()
+        .unwrap()
+        .attributes_mut()
+        .insert("class", Some("x"));
+
+    let parser = dom.parser();
+    let written = |f: &dyn Fn(&mut String) -> fmt::Result| {
+        let mut out = String::new();
+        f(&mut out).unwrap();
+        out
+    };
+
+    // the string-returning methods and the writer versions agree
+    assert_eq!(written(&|out| dom.write_outer_html(out)), dom.outer_html());
+    assert_eq!(written(&|out| dom.write_inner_html(out)), dom.inner_html());
+    assert_eq!(written(&|out| dom.write_inner_text(out)), dom.inner_text());
+    for node in dom.nodes() {
+        assert_eq!(
+            written(&|out| node.write_outer_html(parser, out)),
+            node.outer_html(parser)
+        );
+        assert_eq!(
+            written(&|out| node.write_inner_html(parser, out)),
+            node.inner_html(parser)
+        );
+        assert_eq!(
+            written(&|out| node.write_inner_text(parser, out)),
+            node.inner_text(parser)
+        );
+    }
+    assert_eq!(
+        written(&|out| dom.write_outer_html(out)),
+        r#"<!DOCTYPE html><html><body><p id="a" class="x">Hello <b>world</b><br></p><!-- x --><ul><li>1<li>2</ul></body></html>"#
+    );
+
+    // errors of the destination stop the serialization
+    struct Limited(usize);
+    impl Write for Limited {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            self.0 = self.0.checked_sub(s.len()).ok_or(fmt::Error)?;
+            Ok(())
+        }
+    }
+    let len = dom.outer_html().len();
+    assert!(dom.write_outer_html(&mut Limited(len)).is_ok());
+    assert!(dom.write_outer_html(&mut Limited(len - 1)).is_err());
+    assert!(dom.write_outer_html(&mut Limited(20)).is_err());
+
+    // deeply nested documents do not overflow the stack
+    let input = "<div>".repeat(100_000) + "x";
+    let dom = parse(&input, Default::default()).unwrap();
+    let mut out = crate::IoWriter::new(Vec::new());
+    let result = dom.write_outer_html(&mut out);
+    assert_eq!(out.finish(result).unwrap(), input.as_bytes());
+}
+
+#[test]
+fn io_writer_errors() {
+    use std::fmt::Write;
+    use std::io;
+
+    #[derive(Debug)]
+    struct Failing;
+    impl io::Write for Failing {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::BrokenPipe, "closed"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let dom = parse("<p>Hello</p>", Default::default()).unwrap();
+
+    let mut out = crate::IoWriter::new(Failing);
+    let result = dom.write_outer_html(&mut out);
+    assert!(result.is_err());
+    assert_eq!(
+        out.finish(result).unwrap_err().kind(),
+        io::ErrorKind::BrokenPipe
+    );
+
+    // formatting errors that do not come from the writer are reported as well
+    let out = crate::IoWriter::new(Vec::new());
+    assert_eq!(
+        out.finish(Err(std::fmt::Error)).unwrap_err().kind(),
+        io::ErrorKind::Other
+    );
+
+    let mut out = crate::IoWriter::new(Vec::new());
+    let result = dom
+        .write_inner_text(&mut out)
+        .and_then(|_| out.write_char('!'));
+    assert_eq!(out.get_ref(), b"Hello!");
+    assert_eq!(out.finish(result).unwrap(), b"Hello!");
+}
+
+#[test]
+fn inner_text_after_reparenting_children() {
+    let mut dom = parse("<div><p>a</p><span>b</span></div>", Default::default()).unwrap();
+    let div = dom.nodes()[0].as_tag().unwrap();
+    let (p, span) = (div.children().top()[0], div.children().top()[1]);
+
+    // move the paragraph into the span, after its text, without updating the node table
+    let div = dom.nodes_mut()[0].as_tag_mut().unwrap();
+    div.children_mut().top_mut().remove(0);
+    let span_tag = span
+        .get_mut(dom.parser_mut())
+        .unwrap()
+        .as_tag_mut()
+        .unwrap();
+    span_tag.children_mut().top_mut().push(p);
+
+    let parser = dom.parser();
+    let span_tag = span.get(parser).unwrap().as_tag().unwrap();
+    assert_eq!(span_tag.inner_text(parser), "ba");
+    assert_eq!(span_tag.inner_text_decoded(parser), "ba");
+    assert!(span_tag.contains_text(parser, b"ba", Bytes::as_utf8_str));
+    let div = dom.nodes()[0].as_tag().unwrap();
+    assert_eq!(div.inner_text(parser), "ba");
+}
+
+#[test]
+fn write_inner_text_after_reparenting_children() {
+    let mut dom = parse("<div><p>a</p><span>b</span></div>", Default::default()).unwrap();
+    let div = dom.nodes()[0].as_tag().unwrap();
+    let (p, span) = (div.children().top()[0], div.children().top()[1]);
+
+    let div = dom.nodes_mut()[0].as_tag_mut().unwrap();
+    div.children_mut().top_mut().remove(0);
+    let span_tag = span
+        .get_mut(dom.parser_mut())
+        .unwrap()
+        .as_tag_mut()
+        .unwrap();
+    span_tag.children_mut().top_mut().push(p);
+
+    let parser = dom.parser();
+    let mut out = String::new();
+    span.get(parser)
+        .unwrap()
+        .write_inner_text(parser, &mut out)
+        .unwrap();
+    assert_eq!(out, "ba");
+
+    let mut out = String::new();
+    dom.write_inner_text(&mut out).unwrap();
+    assert_eq!(out, "ba");
+    assert_eq!(dom.inner_text(), "ba");
+}
+
+#[test]
+fn omitted_end_tags_after_mutation() {
+    let mut dom = parse("<div><p>a", ParserOptions::default()).unwrap();
+    let div = dom.children()[0];
+
+    dom.append_child(div, Node::Tag(HTMLTag::new("span", Default::default())))
+        .unwrap();
+
+    let html = dom.outer_html();
+    assert_eq!(html, "<div><p>a</p><span></span></div>");
+    let reparsed = parse(&html, ParserOptions::default()).unwrap();
+    let span = reparsed.query_selector_first("span").unwrap();
+    assert_eq!(
+        span.get(reparsed.parser())
+            .unwrap()
+            .as_tag()
+            .unwrap()
+            .parent(),
+        Some(reparsed.children()[0])
+    );
+
+    // moved elements are followed by something else, so their end tags are generated as well
+    let mut dom = parse("<ul><li>a<li>b</ul><ol></ol>", ParserOptions::default()).unwrap();
+    let first = dom.query_selector_first("li").unwrap();
+    let list = dom.query_selector_first("ol").unwrap();
+
+    dom.move_node(first, list, 0).unwrap();
+    assert_eq!(dom.outer_html(), "<ul><li>b</ul><ol><li>a</li></ol>");
+
+    // untouched elements keep their omitted end tags
+    let mut dom = parse("<li>a<li>b<li>c", ParserOptions::default()).unwrap();
+    let second = dom.children()[1];
+    dom.set_inner_html(second, "d").unwrap();
+    assert_eq!(dom.outer_html(), "<li>a<li>d</li><li>c");
+}
+
+#[test]
+fn source_bytes_deeply_nested() {
+    // deeply nested documents do not overflow the stack
+    let input = "<div>".repeat(100_000) + "x";
+    let mut dom = parse(&input, Default::default()).unwrap();
+    let parser = dom.parser();
+    assert!(dom.nodes()[0].is_source_backed(parser));
+    assert_eq!(dom.nodes()[0].source_bytes(parser), Some(input.as_bytes()));
+
+    let innermost = dom.parser().handle_at(100_000);
+    dom.set_text(innermost, "y").unwrap();
+    assert!(!dom.nodes()[0].is_source_backed(dom.parser()));
+}
+
+#[test]
+fn query_selector_namespaced_attributes() {
+    let input = r#"<svg><use xlink:href="icons.svg"></use></svg><div><p xml:lang=en>a</p><p xml:lang=de>b</p></div>"#;
+    let dom = parse(input, ParserOptions::default()).unwrap();
+
+    let used = dom.query_selector_first("[xlink:href]").unwrap();
+    assert_eq!(
+        used.get(dom.parser()).unwrap().as_tag().unwrap().name(),
+        "use"
+    );
+
+    let english = dom
+        .query_selector("[xml:lang=en]")
+        .unwrap()
+        .collect::<Vec<_>>();
+    assert_eq!(english.len(), 1);
+    assert_eq!(
+        english[0]
+            .get(dom.parser())
+            .unwrap()
+            .inner_text(dom.parser()),
+        "a"
+    );
+
+    // a `:` after the attribute selector still starts a pseudo-class
+    assert_eq!(dom.query_selector_count("p[xml:lang]:last-child"), Some(1));
+}
+
+#[test]
+fn set_inner_text_raw_text_elements() {
+    let mut dom = parse(
+        "<script>old()</script><style></style><xmp></xmp>",
+        ParserOptions::default(),
+    )
+    .unwrap();
+    let script = dom.children()[0];
+    let style = dom.children()[1];
+    let xmp = dom.children()[2];
+
+    // the content of raw text elements is not escaped
+    dom.set_inner_text(script, "if (a < b && c > d) { x = '&amp;'; }")
+        .unwrap();
+    dom.set_inner_text(style, "a > b { content: '<p>'; }")
+        .unwrap();
+    dom.set_inner_text(xmp, "<b>&lt;</b>").unwrap();
+    let html = dom.outer_html();
+    assert_eq!(
+        html,
+        "<script>if (a < b && c > d) { x = '&amp;'; }</script><style>a > b { content: '<p>'; }</style><xmp><b>&lt;</b></xmp>"
+    );
+
+    let reparsed = parse(&html, ParserOptions::default()).unwrap();
+    assert_eq!(reparsed.children().len(), 3);
+    assert_eq!(reparsed.nodes().len(), 6);
+
+    // text that would end the element early is rejected, without changing anything
+    for text in ["</script>", "x = '</SCRIPT'", "</script\n>"] {
+        assert_eq!(
+            dom.set_inner_text(script, text),
+            Err(InsertError::RawTextEndTag)
+        );
+    }
+    assert_eq!(dom.outer_html(), html);
+
+    // other end tags are fine
+    dom.set_inner_text(script, "document.write('</p></scrip>')")
+        .unwrap();
+    assert_eq!(
+        script.get(dom.parser()).unwrap().inner_text(dom.parser()),
+        "document.write('</p></scrip>')"
+    );
+}
+
+#[test]
+fn class_membership_decodes_char_refs() {
+    let mut dom = parse(r#"<p class="a&amp;b c">x</p>"#, ParserOptions::default()).unwrap();
+    let attributes = dom.nodes_mut()[0].as_tag_mut().unwrap().attributes_mut();
+
+    assert!(attributes.is_class_member("a&b"));
+    assert!(attributes.has_class("a&b"));
+    assert!(!attributes.is_class_member("a&amp;b"));
+    assert!(!attributes.has_class("a&amp;b"));
+    assert!(!attributes.add_class("a&b"));
+
+    assert!(!attributes.remove_class("a&amp;b"));
+    assert!(attributes.remove_class("a&b"));
+    assert_eq!(attributes.class(), Some(&"c".into()));
+
+    let dom = parse(r#"<p class="a&amp;b c">x</p>"#, ParserOptions::default()).unwrap();
+    assert_eq!(dom.get_elements_by_class_name("a&b").count(), 1);
+    assert_eq!(dom.query_selector_count(r".a\&b"), Some(1));
+}
+
+#[test]
+fn elements_with_empty_class() {
+    let input = r#"<p class="">a</p><p class="  ">b</p><p class=" x ">c</p><p class>d</p><p class="&#32;">e</p>"#;
+
+    for options in [
+        ParserOptions::default(),
+        ParserOptions::default().track_classes(),
+    ] {
+        let dom = parse(input, options).unwrap();
+        let with_class = dom
+            .elements_with_class()
+            .map(|(_, class)| class.as_utf8_str().into_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(with_class, [" x "]);
+    }
+}
+
+#[test]
+fn links_uppercase_tag_names() {
+    let dom = parse(
+        r#"<A HREF="/a">A</A><Area href="/b"><abbr href="/c">C</abbr>"#,
+        ParserOptions::default(),
+    )
+    .unwrap();
+    let links = dom.links().map(|(_, href)| href).collect::<Vec<_>>();
+    assert_eq!(links, ["/a", "/b"]);
+}
+
+#[test]
+fn attribute_names_are_case_insensitive() {
+    let mut dom = parse(
+        r#"<div data-X="1" Custom=a ID=main></div>"#,
+        ParserOptions::default().track_attribute("DATA-x"),
+    )
+    .unwrap();
+    assert_eq!(
+        dom.get_elements_by_attribute_tracked("data-x", "1").count(),
+        1
+    );
+
+    let attributes = dom.nodes_mut()[0].as_tag_mut().unwrap().attributes_mut();
+
+    // unknown attributes are compared like known ones
+    assert_eq!(attributes.get("data-x"), Some(Some(&"1".into())));
+    assert_eq!(attributes.get("DATA-X"), Some(Some(&"1".into())));
+    assert_eq!(attributes.get("custom"), Some(Some(&"a".into())));
+    assert_eq!(attributes.get("id"), Some(Some(&"main".into())));
+    assert!(attributes.contains("CUSTOM"));
+
+    // inserting an existing attribute with a different case replaces its value and keeps its name
+    attributes.insert("data-x", Some("2"));
+    assert_eq!(attributes.len(), 3);
+    assert_eq!(attributes.get("data-X"), Some(Some(&"2".into())));
+    assert_eq!(
+        attributes.get_mut("DATA-x").unwrap().unwrap().as_utf8_str(),
+        "2"
+    );
+
+    assert_eq!(attributes.remove_value("CUSTOM"), Some("a".into()));
+    assert_eq!(attributes.remove("custom"), Some(None));
+    assert_eq!(attributes.remove("custom"), None);
+    assert_eq!(attributes.len(), 2);
+    assert_eq!(dom.outer_html(), r#"<div data-X="2" id="main"></div>"#);
+}
+
+#[test]
+fn attribute_names_keep_their_spelling() {
+    let mut dom = parse(r#"<div Data-A="1" b="2"></div>"#, ParserOptions::default()).unwrap();
+    let attributes = dom.nodes_mut()[0].as_tag_mut().unwrap().attributes_mut();
+
+    // names are stored in lowercase, but iterated over as written
+    assert!(attributes.unstable_raw().contains_key(&"data-a".into()));
+    let mut names = attributes.iter().map(|(k, _)| k).collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(names, ["Data-A", "b"]);
+
+    // once removed, an attribute can be inserted again with another spelling
+    assert_eq!(attributes.remove("DATA-a"), Some(Some("1".into())));
+    attributes.insert("DATA-A", Some("3"));
+    attributes.insert("B", Some("4"));
+    assert_eq!(attributes.len(), 2);
+
+    let mut pairs = attributes
+        .iter()
+        .map(|(k, v)| (k.into_owned(), v.unwrap().into_owned()))
+        .collect::<Vec<_>>();
+    pairs.sort();
+    assert_eq!(
+        pairs,
+        [("DATA-A".into(), "3".into()), ("b".into(), "4".into())]
+    );
+}
+
+#[test]
+fn inner_text_decoded_skips_raw_text() {
+    let dom = parse(
+        "<div>a &amp; b<script>if (x &amp;&amp; y) {}</script><style>p::after { content: '&lt;' }</style></div>",
+        ParserOptions::default(),
+    )
+    .unwrap();
+    let parser = dom.parser();
+    let div = dom.nodes()[0].as_tag().unwrap();
+    let script = dom.query_selector_first("script").unwrap();
+    let script = script.get(parser).unwrap().as_tag().unwrap();
+
+    assert_eq!(
+        div.inner_text_decoded(parser),
+        "a & bif (x &amp;&amp; y) {}p::after { content: '&lt;' }"
+    );
+    assert_eq!(script.inner_text_decoded(parser), "if (x &amp;&amp; y) {}");
+    assert_eq!(script.inner_text_decoded(parser), script.inner_text(parser));
+
+    // unknown references are kept
+    let dom = parse("<p>&amp; &bogus; &#x41;</p>", ParserOptions::default()).unwrap();
+    let p = dom.nodes()[0].as_tag().unwrap();
+    assert_eq!(p.inner_text_decoded(dom.parser()), "& &bogus; A");
+}
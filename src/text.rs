@@ -0,0 +1,278 @@
+use std::fmt::{self, Write};
+
+use crate::{util, HTMLTag, Node, NodeHandle, Parser};
+
+/// Options for extracting the visible text of a document with `VDom::visible_text()` and `VDom::text_fingerprint()`
+///
+/// # Example
+/// ```
+/// use tl::TextFingerprintOptions;
+///
+/// let options = TextFingerprintOptions {
+///     fold_case: true,
+///     ..Default::default()
+/// };
+/// assert!(options.fold_case && !options.collapse_whitespace);
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct TextFingerprintOptions {
+    /// Whether the text should be lowercased
+    pub fold_case: bool,
+    /// Whether runs of whitespace should be collapsed into a single space (or a single newline between blocks
+    /// and a tab between table cells), and leading and trailing whitespace removed
+    pub collapse_whitespace: bool,
+}
+
+/// Options for joining the texts of the children of an element with `HTMLTag::inner_text_with_options()`
+///
+/// # Example
+/// ```
+/// use tl::TextJoinOptions;
+///
+/// let dom = tl::parse("<tr><td>a</td><td></td><td>c</td></tr>", Default::default()).unwrap();
+/// let tr = dom.nodes()[0].as_tag().unwrap();
+///
+/// let options = TextJoinOptions { keep_empty: true };
+/// assert_eq!(tr.inner_text_with_options(dom.parser(), ",", options), "a,,c");
+/// assert_eq!(tr.inner_text_with(dom.parser(), ","), "a,c");
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct TextJoinOptions {
+    /// Whether children without text still produce a separator, which keeps positions aligned (e.g. of table cells)
+    pub keep_empty: bool,
+}
+
+/// A 64-bit FNV-1a hasher
+///
+/// Unlike `DefaultHasher`, the result is stable across processes and versions of Rust,
+/// so fingerprints can be stored and compared later.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+}
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(Self::OFFSET_BASIS)
+    }
+}
+
+impl Write for Fnv1a {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for &byte in s.as_bytes() {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(Self::PRIME);
+        }
+        Ok(())
+    }
+}
+
+/// A separator that is written lazily before the next text, which allows trimming trailing whitespace
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Separator {
+    None,
+    Space,
+    Tab,
+    Newline,
+}
+
+/// Streams the visible text of a document into a writer, applying the normalizations of [`TextFingerprintOptions`]
+struct TextWriter<'w, W> {
+    out: &'w mut W,
+    options: TextFingerprintOptions,
+    pending: Separator,
+    /// Whether any text has been written yet
+    started: bool,
+    /// Whether nothing has been written yet, or the last written character is a newline
+    /// (only used without whitespace collapsing)
+    at_line_start: bool,
+}
+
+impl<'w, W: Write> TextWriter<'w, W> {
+    fn new(out: &'w mut W, options: TextFingerprintOptions) -> Self {
+        Self {
+            out,
+            options,
+            pending: Separator::None,
+            started: false,
+            at_line_start: true,
+        }
+    }
+
+    fn write_raw(&mut self, s: &str) -> fmt::Result {
+        if self.options.fold_case {
+            for c in s.chars().flat_map(char::to_lowercase) {
+                self.out.write_char(c)?;
+            }
+        } else {
+            self.out.write_str(s)?;
+        }
+
+        if let Some(last) = s.chars().last() {
+            self.started = true;
+            self.at_line_start = last == '\n';
+        }
+        Ok(())
+    }
+
+    fn write_word(&mut self, word: &str) -> fmt::Result {
+        match std::mem::replace(&mut self.pending, Separator::None) {
+            Separator::Space if self.started => self.out.write_char(' ')?,
+            Separator::Tab if self.started => self.out.write_char('\t')?,
+            Separator::Newline if self.started => self.out.write_char('\n')?,
+            _ => {}
+        }
+        self.write_raw(word)
+    }
+
+    fn text(&mut self, text: &str) -> fmt::Result {
+        if !self.options.collapse_whitespace {
+            if text.is_empty() {
+                return Ok(());
+            }
+
+            // line breaks are normalized even if the parser kept them (see `ParserOptions::normalize_newlines()`),
+            // so that the text does not depend on the line endings of the source
+            return match util::normalize_newlines(text.as_bytes()) {
+                Some(normalized) => self.write_word(&String::from_utf8_lossy(&normalized)),
+                None => self.write_word(text),
+            };
+        }
+
+        let bytes = text.as_bytes();
+        if bytes.first().is_some_and(|&c| util::is_ascii_whitespace(c)) {
+            self.pending = self.pending.max(Separator::Space);
+        }
+
+        let mut words = util::split_ascii_whitespace(text).peekable();
+        while let Some(word) = words.next() {
+            self.write_word(word)?;
+
+            if words.peek().is_some() {
+                self.pending = Separator::Space;
+            }
+        }
+
+        if bytes.last().is_some_and(|&c| util::is_ascii_whitespace(c)) {
+            self.pending = self.pending.max(Separator::Space);
+        }
+        Ok(())
+    }
+
+    fn line_break(&mut self) {
+        if self.options.collapse_whitespace || !self.at_line_start {
+            self.pending = Separator::Newline;
+        }
+    }
+
+    /// Separates the text of a table cell from the previous cell of the row (but not from the previous row)
+    fn cell_break(&mut self) {
+        if self.options.collapse_whitespace || !self.at_line_start {
+            self.pending = self.pending.max(Separator::Tab);
+        }
+    }
+
+    /// Writes the text of the given nodes and their subtrees, using an explicit stack so that
+    /// deeply nested documents cannot overflow the stack
+    fn nodes(&mut self, parser: &Parser<'_>, roots: &[NodeHandle]) -> fmt::Result {
+        let mut stack = roots
+            .iter()
+            .rev()
+            .map(|&h| Step::Node(h))
+            .collect::<Vec<_>>();
+
+        while let Some(step) = stack.pop() {
+            let tag = match step {
+                Step::LineBreak => {
+                    self.line_break();
+                    continue;
+                }
+                Step::Node(handle) => match handle.get(parser) {
+                    Some(Node::Tag(tag)) => tag,
+                    Some(Node::Raw(text)) => {
+                        self.text(&text.as_decoded_str())?;
+                        continue;
+                    }
+                    Some(Node::Comment(_)) | None => continue,
+                },
+            };
+
+            if !is_rendered(tag) {
+                continue;
+            }
+
+            let name = tag.name().as_bytes();
+            let is_line = tag.is_block_level() || is_line_element(name);
+            if is_line {
+                self.line_break();
+            } else if is_table_cell(name) {
+                self.cell_break();
+            }
+
+            if is_line || name.eq_ignore_ascii_case(b"br") {
+                stack.push(Step::LineBreak);
+            }
+
+            let children = tag.children();
+            stack.extend(
+                children
+                    .top()
+                    .as_slice()
+                    .iter()
+                    .rev()
+                    .map(|&h| Step::Node(h)),
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// A pending step of [`TextWriter::nodes()`]
+enum Step {
+    Node(NodeHandle),
+    LineBreak,
+}
+
+/// Checks whether the contents of an element are rendered, which excludes metadata (`<script>`, `<title>`, ...),
+/// the `<head>` and elements with a `hidden` attribute
+fn is_rendered(tag: &HTMLTag<'_>) -> bool {
+    !tag.is_metadata_content()
+        && !tag.name().as_bytes().eq_ignore_ascii_case(b"head")
+        && !tag.attributes().contains("hidden")
+}
+
+/// Checks whether the text of an element that is not block-level is still on its own line, like table rows and options
+fn is_line_element(name: &[u8]) -> bool {
+    [&b"tr"[..], b"caption", b"option", b"optgroup"]
+        .iter()
+        .any(|line| name.eq_ignore_ascii_case(line))
+}
+
+/// Checks whether an element is a table cell, whose text is separated from the previous cell by a tab
+fn is_table_cell(name: &[u8]) -> bool {
+    name.eq_ignore_ascii_case(b"td") || name.eq_ignore_ascii_case(b"th")
+}
+
+/// Writes the visible text of the given top-level nodes into `out`
+pub(crate) fn write_visible_text<W: Write>(
+    parser: &Parser<'_>,
+    roots: &[NodeHandle],
+    options: TextFingerprintOptions,
+    out: &mut W,
+) -> fmt::Result {
+    TextWriter::new(out, options).nodes(parser, roots)
+}
+
+/// Hashes the visible text of the given top-level nodes without building the full string
+pub(crate) fn fingerprint(
+    parser: &Parser<'_>,
+    roots: &[NodeHandle],
+    options: TextFingerprintOptions,
+) -> u64 {
+    let mut hasher = Fnv1a::default();
+    // writing into the hasher never fails
+    let _ = write_visible_text(parser, roots, options, &mut hasher);
+    hasher.0
+}
@@ -1,8 +1,10 @@
+use std::borrow::Cow;
+
 #[inline(never)]
 pub fn is_ident(c: u8) -> bool {
-    (b'0'..=b'9').contains(&c)
-        || (b'A'..=b'Z').contains(&c)
-        || (b'a'..=b'z').contains(&c)
+    c.is_ascii_digit()
+        || c.is_ascii_uppercase()
+        || c.is_ascii_lowercase()
         || c == b'-'
         || c == b'_'
         || c == b':'
@@ -10,9 +12,122 @@ pub fn is_ident(c: u8) -> bool {
         || c == b'/'
 }
 
+/// Checks if the given byte is ASCII whitespace as defined by HTML and CSS: space, tab, LF, FF or CR
+///
+/// Unlike `char::is_whitespace()`, this does not depend on Unicode, and it is used wherever the crate splits or skips whitespace.
+#[inline(always)]
+pub fn is_ascii_whitespace(c: u8) -> bool {
+    matches!(c, b' ' | b'\t' | b'\n' | b'\x0C' | b'\r')
+}
+
+/// Checks if the given character is ASCII whitespace, see `is_ascii_whitespace()`
+#[inline(always)]
+pub fn is_ascii_whitespace_char(c: char) -> bool {
+    u8::try_from(c).is_ok_and(is_ascii_whitespace)
+}
+
+/// Removes leading and trailing ASCII whitespace
+pub fn trim_ascii_whitespace(s: &str) -> &str {
+    s.trim_matches(is_ascii_whitespace_char)
+}
+
+/// Returns an iterator over the non-empty substrings of `s` separated by ASCII whitespace
+pub fn split_ascii_whitespace(s: &str) -> impl Iterator<Item = &str> {
+    s.split(is_ascii_whitespace_char)
+        .filter(|token| !token.is_empty())
+}
+
+/// Removes leading and trailing ASCII whitespace, see `trim_ascii_whitespace()`
+pub fn trim_ascii_whitespace_bytes(s: &[u8]) -> &[u8] {
+    let start = s
+        .iter()
+        .position(|&c| !is_ascii_whitespace(c))
+        .unwrap_or(s.len());
+    let end = s
+        .iter()
+        .rposition(|&c| !is_ascii_whitespace(c))
+        .map_or(start, |idx| idx + 1);
+
+    &s[start..end]
+}
+
+/// Returns an iterator over the non-empty subslices of `s` separated by ASCII whitespace, see `split_ascii_whitespace()`
+pub fn split_ascii_whitespace_bytes(s: &[u8]) -> impl Iterator<Item = &[u8]> {
+    s.split(|&c| is_ascii_whitespace(c))
+        .filter(|token| !token.is_empty())
+}
+
 #[inline(always)]
 pub fn to_lower(byte: u8) -> u8 {
-    let is_upper = (byte >= b'A' && byte <= b'Z') as u8;
+    let is_upper = byte.is_ascii_uppercase() as u8;
     let lower = is_upper * 0x20;
     byte + lower
 }
+
+/// Converts `s` to ASCII lowercase, only allocating if it contains uppercase letters
+pub fn to_ascii_lowercase_bytes(s: &[u8]) -> Cow<'_, [u8]> {
+    match s.iter().any(u8::is_ascii_uppercase) {
+        true => Cow::Owned(s.to_ascii_lowercase()),
+        false => Cow::Borrowed(s),
+    }
+}
+
+/// Returns the largest index that is not greater than `index` and lies on a char boundary of the UTF-8 encoded `text`
+pub fn floor_char_boundary(text: &[u8], index: usize) -> usize {
+    if index >= text.len() {
+        return text.len();
+    }
+
+    // continuation bytes of multi-byte characters have the form 0b10xxxxxx
+    (0..=index)
+        .rev()
+        .find(|&idx| text[idx] & 0b1100_0000 != 0b1000_0000)
+        .unwrap_or(0)
+}
+
+/// Returns the text of a comment between `<!--` and `-->`, given its source
+///
+/// Returns `None` for other markup declarations (e.g. a misplaced doctype), which are stored as comments too.
+/// The `-->` is missing if the input ended inside the comment.
+pub fn comment_body(comment: &str) -> Option<&str> {
+    let body = comment.strip_prefix("<!--")?;
+    Some(body.strip_suffix("-->").unwrap_or(body))
+}
+
+/// Replaces every `\r\n` and lone `\r` in `text` with `\n`
+///
+/// Returns `None` if the input contains no `\r`, in which case it is already normalized.
+pub fn normalize_newlines(text: &[u8]) -> Option<Vec<u8>> {
+    let first = crate::simd::find(text, b'\r')?;
+    let mut normalized = Vec::with_capacity(text.len());
+    normalized.extend_from_slice(&text[..first]);
+
+    let mut bytes = text[first..].iter().copied().peekable();
+    while let Some(byte) = bytes.next() {
+        if byte == b'\r' {
+            bytes.next_if_eq(&b'\n');
+            normalized.push(b'\n');
+        } else {
+            normalized.push(byte);
+        }
+    }
+
+    Some(normalized)
+}
+
+/// Applies the URL attribute value normalization rules:
+/// leading and trailing C0 controls and spaces are stripped, and any ASCII tab or newline is removed.
+///
+/// Returns a borrowed string if the input is already normalized.
+pub fn normalize_url(url: &str) -> Cow<'_, str> {
+    let is_c0_or_space = |c: char| c <= ' ';
+    let is_tab_or_newline = |c: char| matches!(c, '\t' | '\n' | '\r');
+
+    let trimmed = url.trim_matches(is_c0_or_space);
+
+    if trimmed.contains(is_tab_or_newline) {
+        Cow::Owned(trimmed.replace(is_tab_or_newline, ""))
+    } else {
+        Cow::Borrowed(trimmed)
+    }
+}
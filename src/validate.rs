@@ -0,0 +1,325 @@
+use core::ops::BitOr;
+use std::{collections::HashMap, fmt};
+
+use crate::{tag_info, HTMLTag, KnownAttr, Node, NodeHandle, Parser};
+
+/// A custom validation rule, which returns a message if the given element is invalid
+type CustomRule = Box<dyn Fn(&HTMLTag<'_>, &Parser<'_>) -> Option<String>>;
+
+/// A set of groups of built-in validation rules
+///
+/// # Example
+/// ```
+/// use tl::validate::{RuleGroup, ValidationRules};
+///
+/// let rules = ValidationRules::new().disable(RuleGroup::HEADINGS | RuleGroup::ATTRIBUTES);
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct RuleGroup(u8);
+
+impl RuleGroup {
+    /// No rules
+    pub const NONE: Self = Self(0);
+    /// Ids must be unique within the document
+    pub const IDS: Self = Self(1 << 0);
+    /// Elements must be nested legally (e.g. `<li>` in a list, `<td>` in a `<tr>`, no `<a>` inside `<a>`)
+    pub const NESTING: Self = Self(1 << 1);
+    /// Elements must have their required attributes (e.g. `alt` for `<img>`)
+    pub const ATTRIBUTES: Self = Self(1 << 2);
+    /// Headings must not skip levels (e.g. an `<h4>` after an `<h2>`)
+    pub const HEADINGS: Self = Self(1 << 3);
+    /// All built-in rules
+    pub const ALL: Self =
+        Self(Self::IDS.0 | Self::NESTING.0 | Self::ATTRIBUTES.0 | Self::HEADINGS.0);
+
+    /// Checks whether all groups in `other` are also in `self`
+    #[inline]
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for RuleGroup {
+    type Output = Self;
+
+    #[inline]
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// The rule that was violated by a [`ValidationIssue`]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Rule {
+    /// An id is used by more than one element
+    DuplicateId,
+    /// A `<li>` element is not a child of `<ul>`, `<ol>` or `<menu>`
+    ListItemOutsideList,
+    /// A `<td>` or `<th>` element is not a child of `<tr>`
+    TableCellOutsideRow,
+    /// An `<img>` element has no `alt` attribute
+    ImageWithoutAlt,
+    /// An `<a>` element is nested inside another `<a>` element
+    NestedLink,
+    /// A heading is more than one level deeper than the previous heading
+    SkippedHeadingLevel,
+    /// A custom rule added with [`ValidationRules::custom()`]
+    Custom(&'static str),
+}
+
+impl Rule {
+    /// Returns the id of this rule (e.g. `duplicate-id`), or the name of a custom rule
+    pub fn id(&self) -> &'static str {
+        match self {
+            Self::DuplicateId => "duplicate-id",
+            Self::ListItemOutsideList => "list-item-outside-list",
+            Self::TableCellOutsideRow => "table-cell-outside-row",
+            Self::ImageWithoutAlt => "image-without-alt",
+            Self::NestedLink => "nested-link",
+            Self::SkippedHeadingLevel => "skipped-heading-level",
+            Self::Custom(name) => name,
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.id())
+    }
+}
+
+/// A problem found by [`VDom::validate()`](crate::VDom::validate)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationIssue {
+    /// The element that violates the rule
+    pub handle: NodeHandle,
+    /// The rule that was violated
+    pub rule: Rule,
+    /// A human readable description of the problem
+    pub message: String,
+}
+
+/// The rules to check when validating a document
+///
+/// All built-in rules are enabled by default.
+///
+/// # Example
+/// ```
+/// use tl::validate::{Rule, RuleGroup, ValidationRules};
+///
+/// let dom = tl::parse(r#"<img src="a.png"><button>OK</button>"#, Default::default()).unwrap();
+/// let rules = ValidationRules::new()
+///     .disable(RuleGroup::ATTRIBUTES)
+///     .custom("button-type", |tag, _| {
+///         (tag.name() == "button" && !tag.attributes().contains("type"))
+///             .then(|| "buttons should have an explicit type".to_string())
+///     });
+///
+/// let issues = dom.validate(&rules);
+/// assert_eq!(issues.len(), 1);
+/// assert_eq!(issues[0].rule, Rule::Custom("button-type"));
+/// ```
+pub struct ValidationRules {
+    groups: RuleGroup,
+    custom: Vec<(&'static str, CustomRule)>,
+}
+
+impl Default for ValidationRules {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for ValidationRules {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let custom = self.custom.iter().map(|(name, _)| name).collect::<Vec<_>>();
+
+        f.debug_struct("ValidationRules")
+            .field("groups", &self.groups)
+            .field("custom", &custom)
+            .finish()
+    }
+}
+
+impl ValidationRules {
+    /// Creates a new set of rules with all built-in rules enabled
+    pub fn new() -> Self {
+        Self {
+            groups: RuleGroup::ALL,
+            custom: Vec::new(),
+        }
+    }
+
+    /// Enables the given groups of built-in rules
+    pub fn enable(mut self, groups: RuleGroup) -> Self {
+        self.groups = self.groups | groups;
+        self
+    }
+
+    /// Disables the given groups of built-in rules
+    pub fn disable(mut self, groups: RuleGroup) -> Self {
+        self.groups = RuleGroup(self.groups.0 & !groups.0);
+        self
+    }
+
+    /// Adds a custom rule, which is called for every element and returns a message if the element is invalid
+    pub fn custom<F>(mut self, name: &'static str, rule: F) -> Self
+    where
+        F: Fn(&HTMLTag<'_>, &Parser<'_>) -> Option<String> + 'static,
+    {
+        self.custom.push((name, Box::new(rule)));
+        self
+    }
+
+    /// Checks whether all groups of built-in rules in `groups` are enabled
+    #[inline]
+    pub fn is_enabled(&self, groups: RuleGroup) -> bool {
+        self.groups.contains(groups)
+    }
+}
+
+/// The state of a validation run over a document, in document order
+struct Validator<'r, 'p, 'a> {
+    rules: &'r ValidationRules,
+    parser: &'p Parser<'a>,
+    ids: HashMap<&'p [u8], NodeHandle>,
+    heading_level: Option<u8>,
+    issues: Vec<ValidationIssue>,
+}
+
+impl<'r, 'p, 'a> Validator<'r, 'p, 'a> {
+    fn report(&mut self, handle: NodeHandle, rule: Rule, message: String) {
+        self.issues.push(ValidationIssue {
+            handle,
+            rule,
+            message,
+        });
+    }
+
+    fn check(&mut self, handle: NodeHandle, tag: &'p HTMLTag<'a>) {
+        let name = tag.name().as_utf8_str().to_ascii_lowercase();
+        let parent = self.parent_name(tag);
+
+        if self.rules.is_enabled(RuleGroup::IDS) {
+            if let Some(id) = tag.attributes().id() {
+                match self.ids.get(id.as_bytes()) {
+                    Some(first) => self.report(
+                        handle,
+                        Rule::DuplicateId,
+                        format!(
+                            "id `{}` is already used by node {}",
+                            id.as_utf8_str(),
+                            first.get_inner()
+                        ),
+                    ),
+                    None => {
+                        self.ids.insert(id.as_bytes(), handle);
+                    }
+                }
+            }
+        }
+
+        if self.rules.is_enabled(RuleGroup::NESTING) {
+            if name == "li" && !matches!(parent.as_deref(), Some("ul" | "ol" | "menu")) {
+                self.report(
+                    handle,
+                    Rule::ListItemOutsideList,
+                    "<li> must be a child of <ul>, <ol> or <menu>".into(),
+                );
+            }
+
+            if matches!(&*name, "td" | "th") && parent.as_deref() != Some("tr") {
+                self.report(
+                    handle,
+                    Rule::TableCellOutsideRow,
+                    format!("<{name}> must be a child of <tr>"),
+                );
+            }
+
+            if name == "a" && self.has_ancestor(tag, "a") {
+                self.report(
+                    handle,
+                    Rule::NestedLink,
+                    "<a> must not be nested inside another <a>".into(),
+                );
+            }
+        }
+
+        if self.rules.is_enabled(RuleGroup::ATTRIBUTES)
+            && name == "img"
+            && tag.attributes().get_known(KnownAttr::Alt).is_none()
+        {
+            self.report(
+                handle,
+                Rule::ImageWithoutAlt,
+                "<img> must have an alt attribute".into(),
+            );
+        }
+
+        if let Some(level) = tag_info::heading_level(name.as_bytes()) {
+            if self.rules.is_enabled(RuleGroup::HEADINGS) {
+                if let Some(previous) = self.heading_level.filter(|&x| level > x + 1) {
+                    self.report(
+                        handle,
+                        Rule::SkippedHeadingLevel,
+                        format!("<h{level}> follows <h{previous}>, skipping a heading level"),
+                    );
+                }
+            }
+
+            self.heading_level = Some(level);
+        }
+
+        let rules = self.rules;
+        for (name, rule) in &rules.custom {
+            if let Some(message) = rule(tag, self.parser) {
+                self.report(handle, Rule::Custom(name), message);
+            }
+        }
+    }
+
+    /// Returns the lowercase name of the parent element
+    fn parent_name(&self, tag: &HTMLTag<'a>) -> Option<String> {
+        let parent = tag.parent()?.get(self.parser)?.as_tag()?;
+        Some(parent.name().as_utf8_str().to_ascii_lowercase())
+    }
+
+    /// Checks whether any ancestor of the given element has the given (lowercase) name
+    fn has_ancestor(&self, tag: &HTMLTag<'a>, name: &str) -> bool {
+        let mut current = tag.parent();
+
+        while let Some(ancestor) = current.and_then(|x| x.get(self.parser)?.as_tag()) {
+            if ancestor
+                .name()
+                .as_bytes()
+                .eq_ignore_ascii_case(name.as_bytes())
+            {
+                return true;
+            }
+
+            current = ancestor.parent();
+        }
+
+        false
+    }
+}
+
+/// Checks all elements of the given parser against the given rules, see `VDom::validate()`
+pub(crate) fn validate(parser: &Parser<'_>, rules: &ValidationRules) -> Vec<ValidationIssue> {
+    let mut validator = Validator {
+        rules,
+        parser,
+        ids: HashMap::new(),
+        heading_level: None,
+        issues: Vec::new(),
+    };
+
+    for (position, node) in parser.tags[..parser.document_len()].iter().enumerate() {
+        if let Node::Tag(tag) = node {
+            validator.check(parser.handle_at(position), tag);
+        }
+    }
+
+    validator.issues
+}
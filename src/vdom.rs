@@ -1,13 +1,34 @@
-use crate::errors::ParseError;
+use crate::entities;
+use crate::errors::{InsertError, MoveError, ParseError};
+use crate::inline::vec::InlineVec;
+use crate::mutation::MutationObserver;
+use crate::outline::{self, OutlineEntry, OutlineOptions};
+use crate::parser::for_each_class_key;
+use crate::parser::ElementChildren;
 use crate::parser::HTMLVersion;
+use crate::parser::IndexKind;
 use crate::parser::NodeHandle;
+use crate::parser::SubtreeTexts;
+use crate::parser::TextNodes;
 use crate::queryselector;
-use crate::queryselector::QuerySelectorIterator;
+use crate::queryselector::{LookupKey, QueryCache, QuerySelectorIterator, SelectorOptions};
+use crate::tag_info;
+use crate::text::{self, TextFingerprintOptions};
+use crate::util;
+use crate::validate::{self, ValidationIssue, ValidationRules};
 use crate::Bytes;
-use crate::InnerNodeHandle;
+use crate::LinkRel;
+use crate::MetaRefresh;
+use crate::NodePath;
 use crate::ParserOptions;
-use crate::{Node, Parser};
+use crate::SubtreeView;
+use crate::{AttributesGuard, Mutation};
+use crate::{HTMLTag, Node, Parser};
+use std::borrow::Cow;
+use std::fmt;
 use std::marker::PhantomData;
+use std::ops::Range;
+use std::sync::Arc;
 
 /// VDom represents a [Document Object Model](https://developer.mozilla.org/en/docs/Web/API/Document_Object_Model)
 ///
@@ -18,167 +39,2323 @@ use std::marker::PhantomData;
 pub struct VDom<'a> {
     /// Internal parser
     parser: Parser<'a>,
+    /// Cached query selector results, if enabled
+    query_cache: Option<QueryCache>,
+    /// The callback that is notified of mutations, if set
+    observer: Option<MutationObserver>,
 }
 
 impl<'a> From<Parser<'a>> for VDom<'a> {
     fn from(parser: Parser<'a>) -> Self {
-        Self { parser }
+        Self {
+            parser,
+            query_cache: None,
+            observer: None,
+        }
+    }
+}
+
+impl<'a> VDom<'a> {
+    /// Returns a reference to the underlying parser
+    #[inline]
+    pub fn parser(&self) -> &Parser<'a> {
+        &self.parser
+    }
+
+    /// Returns a mutable reference to the underlying parser
+    ///
+    /// This clears the query cache, if enabled.
+    #[inline]
+    pub fn parser_mut(&mut self) -> &mut Parser<'a> {
+        self.invalidate_query_cache();
+        &mut self.parser
+    }
+
+    /// Enables memoization of `query_selector()` results, keyed by the selector string
+    ///
+    /// Running the same selector again returns the cached handles (in the same order) instead of evaluating it again.
+    /// The cache is cleared whenever the document is accessed mutably, i.e. through `parser_mut()`, `nodes_mut()` or `children_mut()`.
+    ///
+    /// # Example
+    /// ```
+    /// let mut dom = tl::parse("<p class=\"a\"></p><p></p>", Default::default()).unwrap();
+    /// dom.enable_query_cache();
+    ///
+    /// let first = dom.query_selector("p.a").unwrap().collect::<Vec<_>>();
+    /// let second = dom.query_selector("p.a").unwrap().collect::<Vec<_>>();
+    /// assert_eq!(first, second);
+    /// ```
+    pub fn enable_query_cache(&mut self) {
+        if self.query_cache.is_none() {
+            self.query_cache = Some(QueryCache::default());
+        }
+    }
+
+    /// Disables memoization of `query_selector()` results and drops the cache
+    pub fn disable_query_cache(&mut self) {
+        self.query_cache = None;
+    }
+
+    /// Clears all cached query selector results
+    #[inline]
+    fn invalidate_query_cache(&mut self) {
+        if let Some(cache) = &mut self.query_cache {
+            cache.clear();
+        }
+    }
+
+    /// Sets a callback that is notified of every change made by the mutation methods of this document
+    ///
+    /// These are `attributes_mut()`, `set_text()`, `split_text()`, `wrap_text_range()`, `append_child()`, `prepend_child()`, `insert_before()`, `insert_after()`, `set_inner_html()`, `set_inner_text()`, `move_node()`, `remove()`, `remove_all()` and `normalize_document()`.
+    /// Changes made through `parser_mut()`, `nodes_mut()` and `children_mut()` are not reported.
+    ///
+    /// # Example
+    /// ```
+    /// use std::sync::{Arc, Mutex};
+    /// use tl::{Mutation, NodeHandle};
+    ///
+    /// let mut dom = tl::parse("<a>link</a>", Default::default()).unwrap();
+    /// let mutations = Arc::new(Mutex::new(Vec::new()));
+    /// dom.set_mutation_observer(Box::new({
+    ///     let mutations = Arc::clone(&mutations);
+    ///     move |mutation| mutations.lock().unwrap().push(mutation)
+    /// }));
+    ///
+    /// let link = NodeHandle::new(0);
+    /// dom.attributes_mut(link).unwrap().insert("href", Some("/"));
+    ///
+    /// let name = String::from("href");
+    /// assert_eq!(*mutations.lock().unwrap(), [Mutation::AttributeSet { handle: link, name }]);
+    /// ```
+    pub fn set_mutation_observer(&mut self, observer: Box<dyn FnMut(Mutation)>) {
+        self.observer = Some(MutationObserver(observer));
+    }
+
+    /// Removes the mutation observer and returns it, if set
+    pub fn remove_mutation_observer(&mut self) -> Option<Box<dyn FnMut(Mutation)>> {
+        self.observer.take().map(|observer| observer.0)
+    }
+
+    /// Reports the mutation created by the given function to the observer, if set
+    #[inline]
+    fn notify(&mut self, mutation: impl FnOnce() -> Mutation) {
+        if let Some(observer) = &mut self.observer {
+            observer.notify(mutation);
+        }
+    }
+
+    /// Returns mutable access to the attributes of an element, which reports changes to the mutation observer
+    ///
+    /// Changes to ids, classes and tracked attributes are applied to the lookup tables as well, see [`AttributesGuard`].
+    /// Returns `None` if `handle` does not refer to an element. This clears the query cache, if enabled.
+    pub fn attributes_mut(&mut self, handle: NodeHandle) -> Option<AttributesGuard<'_, 'a>> {
+        self.invalidate_query_cache();
+
+        handle.get(&self.parser)?.as_tag()?;
+        let position = self.parser.position_of(handle)?;
+
+        Some(AttributesGuard {
+            handle,
+            position,
+            parser: &mut self.parser,
+            observer: self.observer.as_mut(),
+        })
+    }
+
+    /// Replaces the text of a text node
+    ///
+    /// Returns `None` (without changing anything) if `handle` does not refer to a text node,
+    /// or if the text is too long to be stored in `Bytes`. This clears the query cache, if enabled.
+    ///
+    /// # Example
+    /// ```
+    /// let mut dom = tl::parse("<p>Hello</p>", Default::default()).unwrap();
+    /// dom.set_text(tl::NodeHandle::new(1), "Bye").unwrap();
+    /// assert_eq!(dom.outer_html(), "<p>Bye</p>");
+    /// ```
+    pub fn set_text(&mut self, handle: NodeHandle, text: &str) -> Option<()> {
+        self.invalidate_query_cache();
+
+        handle
+            .get_mut(&mut self.parser)?
+            .as_raw_mut()?
+            .set(text)
+            .ok()?;
+
+        self.notify(|| Mutation::TextChanged { handle });
+        Some(())
+    }
+
+    /// Returns the query cache, if enabled
+    #[cfg(test)]
+    pub(crate) fn query_cache(&self) -> Option<&QueryCache> {
+        self.query_cache.as_ref()
+    }
+
+    /// Finds an element by its `id` attribute.
+    ///
+    /// Character references in ids are decoded before they are compared, unless `ParserOptions::raw_id_and_class_matching()` is set.
+    pub fn get_element_by_id<'b, S>(&'b self, id: S) -> Option<NodeHandle>
+    where
+        S: Into<Bytes<'a>>,
+    {
+        let bytes: Bytes = id.into();
+        let parser = self.parser();
+
+        if parser.options.is_tracking_ids() {
+            parser.ids.get(&bytes).and_then(|x| x.get(0)).copied()
+        } else {
+            #[cfg(test)]
+            scans::record();
+
+            let decode = !parser.options.is_raw_id_and_class_matching();
+
+            self.nodes()
+                .iter()
+                .enumerate()
+                .find(|(_, node)| {
+                    node.as_tag()
+                        .is_some_and(|tag| tag._attributes.matches_id(bytes.as_bytes(), decode))
+                })
+                .map(|(position, _)| parser.handle_at(position))
+        }
+    }
+
+    /// Returns a list of elements that match a given class name.
+    ///
+    /// Character references in class names are decoded before they are compared, unless `ParserOptions::raw_id_and_class_matching()` is set.
+    pub fn get_elements_by_class_name<'b>(
+        &'b self,
+        id: &'b str,
+    ) -> Box<dyn Iterator<Item = NodeHandle> + 'b> {
+        let parser = self.parser();
+
+        if parser.options.is_tracking_classes() {
+            parser
+                .classes
+                .get(&Bytes::from(id.as_bytes()))
+                .map(|x| Box::new(x.iter().cloned()) as Box<dyn Iterator<Item = NodeHandle>>)
+                .unwrap_or_else(|| Box::new(std::iter::empty()))
+        } else {
+            #[cfg(test)]
+            scans::record();
+
+            let member = id;
+            let decode = !parser.options.is_raw_id_and_class_matching();
+
+            let iter = self
+                .nodes()
+                .iter()
+                .enumerate()
+                .filter_map(move |(id, node)| {
+                    node.as_tag().and_then(|tag| {
+                        tag._attributes
+                            .matches_class(member.as_bytes(), decode)
+                            .then(|| parser.handle_at(id))
+                    })
+                });
+
+            Box::new(iter)
+        }
+    }
+
+    /// Returns an iterator over the elements with the given tag name, in document order
+    ///
+    /// Tag names are compared ASCII case-insensitively, unless `ParserOptions::case_sensitive_tag_names()` is set.
+    /// If `ParserOptions::track_tag_names()` was set, this uses the lookup table built while parsing,
+    /// otherwise it scans all nodes.
+    ///
+    /// # Example
+    /// ```
+    /// let dom = tl::parse("<p>a</p><div><P>b</P></div>", tl::ParserOptions::default().track_tag_names()).unwrap();
+    /// let parser = dom.parser();
+    ///
+    /// let texts = dom
+    ///     .get_elements_by_tag_name("p")
+    ///     .map(|handle| handle.get(parser).unwrap().inner_text(parser))
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(texts, ["a", "b"]);
+    /// ```
+    pub fn get_elements_by_tag_name<'b>(
+        &'b self,
+        name: &'b str,
+    ) -> Box<dyn Iterator<Item = NodeHandle> + 'b> {
+        self.parser
+            .elements_by_tag_name(name, 0..self.parser.document_len())
+    }
+
+    /// Builds the given lookup table from the nodes of this document, without reparsing it
+    ///
+    /// Afterwards, lookups behave exactly as if the corresponding `ParserOptions` flag (`track_ids()`,
+    /// `track_classes()` or `track_tag_names()`) had been set while parsing. This scans all nodes once,
+    /// and does nothing if the lookup table already exists.
+    ///
+    /// # Example
+    /// ```
+    /// use tl::IndexKind;
+    ///
+    /// let mut dom = tl::parse(r#"<div class="a b"></div><p class="b"></p>"#, Default::default()).unwrap();
+    /// dom.build_index(IndexKind::Classes);
+    ///
+    /// assert_eq!(dom.get_elements_by_class_name("b").count(), 2);
+    /// ```
+    pub fn build_index(&mut self, kind: IndexKind) {
+        self.parser.build_index(kind);
+    }
+
+    /// Returns an iterator over the elements whose attribute `name` has the given value, in document order
+    ///
+    /// If the attribute was tracked with `ParserOptions::track_attribute()`, this uses the lookup table built while parsing,
+    /// otherwise it scans all nodes. Values are compared by their raw bytes, and attributes without a value
+    /// are treated as having an empty value.
+    pub fn get_elements_by_attribute_tracked<'b>(
+        &'b self,
+        name: &'b str,
+        value: &'b str,
+    ) -> Box<dyn Iterator<Item = NodeHandle> + 'b> {
+        let parser = self.parser();
+
+        match parser.options.tracked_attribute_index(name) {
+            Some(index) => parser.attributes[index]
+                .get(&Bytes::from(value.as_bytes()))
+                .map(|x| Box::new(x.iter().cloned()) as Box<dyn Iterator<Item = NodeHandle>>)
+                .unwrap_or_else(|| Box::new(std::iter::empty())),
+            None => {
+                let iter = self
+                    .nodes()
+                    .iter()
+                    .enumerate()
+                    .filter_map(move |(position, node)| {
+                        let attribute = node.as_tag()?._attributes.get(name)?;
+                        let attribute = attribute.map_or(&[][..], Bytes::as_bytes);
+
+                        (attribute == value.as_bytes()).then(|| parser.handle_at(position))
+                    });
+
+                Box::new(iter)
+            }
+        }
+    }
+
+    /// Returns an iterator over the elements whose `name` attribute has the given value, in document order
+    ///
+    /// This mirrors `document.getElementsByName()`. If `ParserOptions::track_names()` was set, this uses the lookup table
+    /// built while parsing, otherwise it scans all nodes. Values are compared like in `get_elements_by_attribute_tracked()`,
+    /// so elements with a valueless `name` attribute are found by looking up an empty name.
+    ///
+    /// # Example
+    /// ```
+    /// let input = r#"<form><input type=radio name=size value=s><input type=radio name=size value=m><input name=email></form>"#;
+    /// let dom = tl::parse(input, tl::ParserOptions::default().track_names()).unwrap();
+    /// let parser = dom.parser();
+    ///
+    /// let values = dom
+    ///     .get_elements_by_name("size")
+    ///     .map(|handle| handle.get(parser).unwrap().as_tag().unwrap().attributes().get("value").flatten().unwrap().as_utf8_str())
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(values, ["s", "m"]);
+    /// ```
+    pub fn get_elements_by_name<'b>(
+        &'b self,
+        name: &'b str,
+    ) -> Box<dyn Iterator<Item = NodeHandle> + 'b> {
+        self.get_elements_by_attribute_tracked("name", name)
+    }
+
+    /// Returns an iterator over all elements that have an `id` attribute, along with the raw value of the attribute
+    ///
+    /// If `ParserOptions::track_ids()` was set, this is computed from the lookup table built while parsing,
+    /// otherwise it scans all nodes. Either way, elements are yielded in document order.
+    pub fn elements_with_id(&self) -> impl Iterator<Item = (NodeHandle, &Bytes<'a>)> + '_ {
+        let tracked = self
+            .parser
+            .options
+            .is_tracking_ids()
+            .then(|| self.parser.ids.values().flat_map(|x| x.iter().copied()));
+
+        self.elements_with_attribute(tracked, |tag| tag._attributes.id.as_ref())
+    }
+
+    /// Returns an iterator over all elements that have a `class` attribute, along with the raw value of the attribute
+    ///
+    /// If `ParserOptions::track_classes()` was set, this is computed from the lookup table built while parsing,
+    /// otherwise it scans all nodes. Either way, elements are yielded in document order, and only once
+    /// even if they have multiple classes. Elements whose `class` attribute contains no class names
+    /// (e.g. `class=""`) are skipped.
+    pub fn elements_with_class(&self) -> impl Iterator<Item = (NodeHandle, &Bytes<'a>)> + '_ {
+        let tracked = self
+            .parser
+            .options
+            .is_tracking_classes()
+            .then(|| self.parser.classes.values().flat_map(|x| x.iter().copied()));
+        let decode = !self.parser.options.is_raw_id_and_class_matching();
+
+        self.elements_with_attribute(tracked, move |tag| {
+            // the class names are split like for the lookup table, so that both ways yield the same elements
+            let class = tag._attributes.class.as_ref()?;
+            let mut has_class = false;
+            for_each_class_key(class, decode, |_| has_class = true);
+            has_class.then_some(class)
+        })
+    }
+
+    /// Returns an iterator over all attributes in this document for which the predicate returns `true`,
+    /// given the raw name and value of the attribute
+    ///
+    /// The attributes are yielded in document order as `(element, name, value)`, borrowing from the document.
+    /// Attributes without a value (e.g. `disabled`) are skipped.
+    ///
+    /// # Example
+    /// ```
+    /// let html = r#"<img src="http://a.com/x.png"><a href="https://b.com" data-src="http://c.com">Link</a>"#;
+    /// let dom = tl::parse(html, Default::default()).unwrap();
+    ///
+    /// let insecure = dom
+    ///     .attributes_matching(|_, value| value.starts_with(b"http://"))
+    ///     .map(|(_, name, value)| (name, value.as_utf8_str()))
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(insecure, [(&b"src"[..], "http://a.com/x.png".into()), (&b"data-src"[..], "http://c.com".into())]);
+    /// ```
+    pub fn attributes_matching<'b, F>(
+        &'b self,
+        f: F,
+    ) -> impl Iterator<Item = (NodeHandle, &'b [u8], &'b Bytes<'a>)> + 'b
+    where
+        F: Fn(&[u8], &[u8]) -> bool + 'b,
+    {
+        self.nodes()
+            .iter()
+            .enumerate()
+            .filter_map(|(position, node)| Some((position, node.as_tag()?)))
+            .flat_map(move |(position, tag)| {
+                let handle = self.parser.handle_at(position);
+                tag._attributes
+                    .iter_bytes()
+                    .filter_map(move |(name, value)| Some((handle, name, value?)))
+            })
+            .filter(move |(_, name, value)| f(name, value.as_bytes()))
+    }
+
+    /// Shared implementation of `elements_with_id()` and `elements_with_class()`
+    ///
+    /// `tracked` contains the (unordered, possibly duplicated) handles from a lookup table, if one exists
+    fn elements_with_attribute<'b, I, F>(
+        &'b self,
+        tracked: Option<I>,
+        attribute: F,
+    ) -> impl Iterator<Item = (NodeHandle, &'b Bytes<'a>)> + 'b
+    where
+        I: Iterator<Item = NodeHandle>,
+        F: Fn(&'b HTMLTag<'a>) -> Option<&'b Bytes<'a>> + 'b,
+    {
+        let handles: Box<dyn Iterator<Item = NodeHandle>> = match tracked {
+            Some(tracked) => {
+                let mut handles = tracked.collect::<Vec<_>>();
+                handles.sort_unstable_by_key(|&handle| self.parser.position_of(handle));
+                handles.dedup();
+                Box::new(handles.into_iter())
+            }
+            None => {
+                Box::new((0..self.nodes().len()).map(|position| self.parser.handle_at(position)))
+            }
+        };
+
+        handles.filter_map(move |handle| {
+            let tag = handle.get(&self.parser)?.as_tag()?;
+            attribute(tag).map(|value| (handle, value))
+        })
+    }
+
+    /// Returns an iterator over all links (`<a>` and `<area>` elements with an `href` attribute) in this document
+    ///
+    /// The link targets are normalized using [`Attributes::get_url()`](crate::Attributes::get_url),
+    /// and yielded in document order together with the handle of their element.
+    ///
+    /// # Example
+    /// ```
+    /// let dom = tl::parse(r#"<a href=" /a">A</a><a>B</a><area href="/c">"#, Default::default()).unwrap();
+    /// let links = dom.links().map(|(_, href)| href).collect::<Vec<_>>();
+    /// assert_eq!(links, ["/a", "/c"]);
+    /// ```
+    pub fn links(&self) -> impl Iterator<Item = (NodeHandle, Cow<'_, str>)> + '_ {
+        self.nodes()
+            .iter()
+            .enumerate()
+            .filter_map(|(position, node)| {
+                let tag = node.as_tag()?;
+                let name = tag.name().as_bytes();
+
+                if !name.eq_ignore_ascii_case(b"a") && !name.eq_ignore_ascii_case(b"area") {
+                    return None;
+                }
+
+                let href = tag._attributes.get_url("href")?;
+                Some((self.parser.handle_at(position), href))
+            })
+    }
+
+    /// Collects the links of this document (see `VDom::links()`) into a [`LinkSet`](crate::LinkSet),
+    /// which only keeps the first link for every distinct URL after normalization
+    ///
+    /// Character references in the link targets are decoded. Relative links are resolved against the given base URL, and links that cannot be parsed as URLs
+    /// (such as relative links without a base URL) are skipped. Only available with the `url` feature.
+    ///
+    /// # Example
+    /// ```
+    /// use tl::NormalizeOptions;
+    ///
+    /// let dom = tl::parse(
+    ///     r#"<a href="/docs/">Docs</a><a href="/docs#intro">Intro</a><a href="https://other.com/">Other</a>"#,
+    ///     Default::default(),
+    /// )
+    /// .unwrap();
+    ///
+    /// let base = url::Url::parse("https://example.com/").unwrap();
+    /// let links = dom.links_deduped(Some(&base), NormalizeOptions::default());
+    /// let urls = links.urls().map(|url| url.as_str()).collect::<Vec<_>>();
+    /// assert_eq!(urls, ["https://example.com/docs", "https://other.com/"]);
+    /// ```
+    #[cfg(feature = "url")]
+    pub fn links_deduped(
+        &self,
+        base: Option<&url::Url>,
+        options: crate::NormalizeOptions,
+    ) -> crate::LinkSet {
+        let mut links = crate::LinkSet::new(options);
+
+        for (handle, href) in self.links() {
+            if let Some(url) = crate::link_set::parse(&entities::decode(&href), base) {
+                links.insert(handle, url);
+            }
+        }
+
+        links
+    }
+
+    /// Returns an iterator over the relations declared by all `<link rel="...">` elements in this document
+    ///
+    /// # Example
+    /// ```
+    /// let dom = tl::parse(r#"<link rel="Preload stylesheet" href="/a.css" media="print">"#, Default::default()).unwrap();
+    /// let link = dom.link_relations().next().unwrap();
+    /// assert_eq!(link.rel, ["preload", "stylesheet"]);
+    /// assert_eq!(link.href.as_deref(), Some("/a.css"));
+    /// assert_eq!(link.media.as_deref(), Some("print"));
+    /// ```
+    pub fn link_relations(&self) -> impl Iterator<Item = LinkRel> + '_ {
+        self.nodes()
+            .iter()
+            .filter_map(|node| LinkRel::from_tag(node.as_tag()?))
+    }
+
+    /// Returns the URL of the first `<link rel="canonical">` element in this document, if any
+    pub fn canonical_url(&self) -> Option<String> {
+        self.link_relations()
+            .filter(|link| link.has_rel("canonical"))
+            .find_map(|link| link.href)
+    }
+
+    /// Returns an iterator over all feeds (e.g. `<link rel="alternate" type="application/rss+xml">`) in this document
+    ///
+    /// See [`LinkRel::is_feed()`].
+    pub fn feeds(&self) -> impl Iterator<Item = LinkRel> + '_ {
+        self.link_relations().filter(LinkRel::is_feed)
+    }
+
+    /// Returns all icons (e.g. `<link rel="icon">`) in this document, largest first
+    ///
+    /// Icons are ordered by the largest size in their `sizes` attribute. Scalable icons (`sizes="any"`) come first,
+    /// icons without sizes last, and icons of the same size are kept in document order.
+    ///
+    /// # Example
+    /// ```
+    /// let dom = tl::parse(r#"<link rel=icon href="/a.png" sizes=16x16><link rel=icon href="/b.png" sizes=32x32>"#, Default::default()).unwrap();
+    /// let icons = dom.icons().into_iter().filter_map(|icon| icon.href).collect::<Vec<_>>();
+    /// assert_eq!(icons, ["/b.png", "/a.png"]);
+    /// ```
+    pub fn icons(&self) -> Vec<LinkRel> {
+        let mut icons = self
+            .link_relations()
+            .filter(LinkRel::is_icon)
+            .collect::<Vec<_>>();
+
+        icons.sort_by_key(|icon| std::cmp::Reverse(icon.size_key()));
+        icons
+    }
+
+    /// Checks this document against the given validation rules, and returns all issues in document order
+    ///
+    /// # Example
+    /// ```
+    /// use tl::validate::{Rule, ValidationRules};
+    ///
+    /// let dom = tl::parse(r#"<p id="a"></p><p id="a"></p>"#, Default::default()).unwrap();
+    /// let issues = dom.validate(&ValidationRules::new());
+    ///
+    /// assert_eq!(issues.len(), 1);
+    /// assert_eq!(issues[0].rule, Rule::DuplicateId);
+    /// ```
+    pub fn validate(&self, rules: &ValidationRules) -> Vec<ValidationIssue> {
+        validate::validate(&self.parser, rules)
+    }
+
+    /// Returns the redirect declared by the first `<meta http-equiv="refresh">` element in this document, if any
+    ///
+    /// The attribute name and value are compared case-insensitively, and the `content` attribute
+    /// is parsed using [`MetaRefresh::parse()`].
+    ///
+    /// # Example
+    /// ```
+    /// let dom = tl::parse(r#"<meta http-equiv="Refresh" content="3; url=/next">"#, Default::default()).unwrap();
+    /// let refresh = dom.meta_refresh().unwrap();
+    /// assert_eq!(refresh.delay, 3);
+    /// assert_eq!(refresh.url.as_deref(), Some("/next"));
+    /// ```
+    pub fn meta_refresh(&self) -> Option<MetaRefresh> {
+        let content = self.nodes().iter().find_map(|node| {
+            let tag = node.as_tag()?;
+
+            if !tag.name().as_bytes().eq_ignore_ascii_case(b"meta") {
+                return None;
+            }
+
+            let mut http_equiv = None;
+            let mut content = None;
+
+            for (key, value) in tag.attributes().iter() {
+                if key.eq_ignore_ascii_case("http-equiv") {
+                    http_equiv = value;
+                } else if key.eq_ignore_ascii_case("content") {
+                    content = value;
+                }
+            }
+
+            http_equiv
+                .is_some_and(|x| util::trim_ascii_whitespace(&x).eq_ignore_ascii_case("refresh"))
+                .then_some(content)
+                .flatten()
+        })?;
+
+        MetaRefresh::parse(&content)
+    }
+
+    /// Returns a slice of *all* the elements in the HTML document
+    ///
+    /// The difference between `children()` and `nodes()` is that children only returns the immediate children of the root node,
+    /// while `nodes()` returns all nodes, including nested tags.
+    ///
+    /// # Order
+    /// The order of the returned nodes is the same as the order of the nodes in the HTML document.
+    pub fn nodes(&self) -> &[Node<'a>] {
+        &self.parser.tags[..self.parser.document_len()]
+    }
+
+    /// Returns a mutable slice of *all* the elements in the HTML document
+    ///
+    /// The difference between `children()` and `nodes()` is that children only returns the immediate children of the root node,
+    /// while `nodes()` returns all nodes, including nested tags.
+    pub fn nodes_mut(&mut self) -> &mut [Node<'a>] {
+        self.invalidate_query_cache();
+        let len = self.parser.document_len();
+        &mut self.parser.tags[..len]
+    }
+
+    /// Returns the topmost subnodes ("children") of this DOM
+    pub fn children(&self) -> &[NodeHandle] {
+        &self.parser.ast
+    }
+
+    /// Returns a mutable reference to the topmost subnodes ("children") of this DOM
+    pub fn children_mut(&mut self) -> &mut [NodeHandle] {
+        self.invalidate_query_cache();
+        &mut self.parser.ast
+    }
+
+    /// Returns an iterator over the topmost subnodes of this DOM that are elements, together with their handles
+    ///
+    /// Like `HTMLTag::element_children()`, this skips text nodes and comments.
+    ///
+    /// # Example
+    /// ```
+    /// let dom = tl::parse("<!DOCTYPE html>\n<head></head>\n<body></body>\n", Default::default()).unwrap();
+    ///
+    /// let names = dom.element_children().map(|(_, tag)| tag.name().as_utf8_str()).collect::<Vec<_>>();
+    /// assert_eq!(names, ["head", "body"]);
+    /// assert_eq!(dom.last_element_child().unwrap().1.name(), "body");
+    /// ```
+    pub fn element_children(&self) -> ElementChildren<'_, 'a> {
+        ElementChildren::new(&self.parser, &self.parser.ast)
+    }
+
+    /// Returns the first topmost subnode of this DOM that is an element, see `element_children()`
+    pub fn first_element_child(&self) -> Option<(NodeHandle, &HTMLTag<'a>)> {
+        self.element_children().next()
+    }
+
+    /// Returns the last topmost subnode of this DOM that is an element, see `element_children()`
+    pub fn last_element_child(&self) -> Option<(NodeHandle, &HTMLTag<'a>)> {
+        self.element_children().next_back()
+    }
+
+    /// Returns the first top-level `<html>` element of this document, if any
+    ///
+    /// # Example
+    /// ```
+    /// let dom = tl::parse("<!DOCTYPE html><html><body></body></html>", Default::default()).unwrap();
+    /// let html = dom.document_element().unwrap();
+    /// assert_eq!(html.get(dom.parser()).unwrap().as_tag().unwrap().name(), "html");
+    /// ```
+    pub fn document_element(&self) -> Option<NodeHandle> {
+        self.children()
+            .iter()
+            .copied()
+            .find(|handle| is_tag_named(handle.get(&self.parser), b"html"))
+    }
+
+    /// Moves all content that follows the `<body>` element into it, like browsers do
+    ///
+    /// By default, content after `</html>` (e.g. a stray `<div>`, or a second `<html>` element from concatenated templates)
+    /// is kept as additional top-level nodes. This appends all nodes that follow the body element inside of the document element,
+    /// and all top-level nodes that follow the document element, to the body, in document order.
+    /// If the document element has no `<body>` child, the nodes are appended to the document element instead.
+    /// Moved `<html>`, `<head>` and `<body>` elements are replaced with their children and removed, so that a second document
+    /// only adds its content to the body (handles to the removed elements become dangling).
+    ///
+    /// This does nothing if there is no document element (see `document_element()`).
+    ///
+    /// # Example
+    /// ```
+    /// let mut dom = tl::parse("<html><body><p>a</p></body></html><div>b</div>", Default::default()).unwrap();
+    /// assert_eq!(dom.children().len(), 2);
+    ///
+    /// dom.normalize_document();
+    /// assert_eq!(dom.children().len(), 1);
+    /// assert_eq!(dom.outer_html(), "<html><body><p>a</p><div>b</div></body></html>");
+    /// ```
+    pub fn normalize_document(&mut self) {
+        let Some(html) = self.document_element() else {
+            return;
+        };
+
+        self.invalidate_query_cache();
+
+        let position = self.parser.ast.iter().position(|&x| x == html).unwrap();
+        let trailing = self.parser.ast.split_off(position + 1);
+
+        let html_tag = html.get(&self.parser).unwrap().as_tag().unwrap();
+        let body_position = html_tag
+            ._children
+            .iter()
+            .position(|handle| is_tag_named(handle.get(&self.parser), b"body"));
+
+        // nodes following the body inside of the document element are moved too,
+        // so that the subtree of the body stays contiguous
+        let mut moved = Vec::new();
+        let target = match body_position {
+            Some(index) => {
+                let html_tag = html
+                    .get_mut(&mut self.parser)
+                    .unwrap()
+                    .as_tag_mut()
+                    .unwrap();
+
+                while html_tag._children.len() > index + 1 {
+                    moved.push(html_tag._children.remove(index + 1));
+                }
+
+                *html_tag._children.get(index).unwrap()
+            }
+            None => html,
+        };
+        moved.extend(trailing);
+
+        // a second <html> element (or a stray <head> or <body>) only contributes its content, like the repeated start tags
+        // that browsers ignore, so its children are spliced in instead and the wrapper element is removed
+        let mut content = Vec::new();
+        let mut wrappers = Vec::new();
+        moved.reverse();
+        while let Some(handle) = moved.pop() {
+            let node = handle.get(&self.parser);
+            if is_tag_named(node, b"html")
+                || is_tag_named(node, b"head")
+                || is_tag_named(node, b"body")
+            {
+                let children = node.and_then(Node::as_tag).unwrap()._children.as_slice();
+                moved.extend(children.iter().rev());
+                wrappers.push(handle);
+            } else {
+                content.push(handle);
+            }
+        }
+
+        for handle in wrappers {
+            let position = self.parser.position_of(handle).unwrap();
+            self.parser.remove_nodes(position..position + 1);
+            self.notify(|| Mutation::NodeRemoved { handle });
+        }
+
+        for handle in content {
+            if let Some(tag) = handle.get_mut(&mut self.parser).and_then(Node::as_tag_mut) {
+                tag._parent = Some(target);
+            }
+            self.parser.set_parent(handle, Some(target));
+
+            let target_tag = target
+                .get_mut(&mut self.parser)
+                .unwrap()
+                .as_tag_mut()
+                .unwrap();
+            target_tag._children.push(handle);
+            let index = target_tag._children.len() - 1;
+            self.parser.generate_end_tags(Some(target), index);
+
+            self.notify(|| Mutation::NodeRemoved { handle });
+            self.notify(|| Mutation::NodeInserted {
+                handle,
+                parent: Some(target),
+                index,
+            });
+        }
+    }
+
+    /// Resolves a structural path (obtained from `NodeHandle::node_path()`) to a node in this document
+    ///
+    /// Returns `None` if the path is empty, or if any of its indices is out of range
+    /// (for example, because the document changed since the path was computed).
+    pub fn resolve_path(&self, path: &NodePath) -> Option<NodeHandle> {
+        path.resolve(&self.parser)
+    }
+
+    /// Returns an iterator over all nodes that come after the subtree of `start` and before `end`, in document order
+    ///
+    /// Nested nodes are yielded as well, except for the ancestors of `end` (which start before `end`, but also contain it).
+    /// The iterator is empty if `end` does not come after the subtree of `start`.
+    ///
+    /// # Example
+    /// ```
+    /// let dom = tl::parse("<h2 id=a>A</h2><p>1</p><div><p>2</p><h2 id=b>B</h2></div>", Default::default()).unwrap();
+    /// let (a, b) = (dom.get_element_by_id("a").unwrap(), dom.get_element_by_id("b").unwrap());
+    ///
+    /// let text = dom
+    ///     .nodes_between(a, b)
+    ///     .filter_map(|handle| handle.get(dom.parser()).unwrap().as_raw())
+    ///     .map(|text| text.as_utf8_str())
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(text, ["1", "2"]);
+    /// ```
+    pub fn nodes_between(
+        &self,
+        start: NodeHandle,
+        end: NodeHandle,
+    ) -> impl Iterator<Item = NodeHandle> + '_ {
+        let range = self
+            .subtree_end(start)
+            .zip(self.parser.position_of(end))
+            .map_or(0..0, |(start, end)| start..end.max(start));
+
+        // ancestors of `end` are the only nodes in the range that are not completely before `end`
+        let ancestors = if range.is_empty() {
+            Vec::new()
+        } else {
+            self.ancestor_positions(end)
+        };
+
+        range
+            .filter(move |position| !ancestors.contains(position))
+            .map(|position| self.parser.handle_at(position))
+    }
+
+    /// Returns an iterator over the section that starts with the given heading (`<h1>` to `<h6>`), in document order
+    ///
+    /// The section consists of all nodes after the heading up to the next heading of the same or a higher rank
+    /// (e.g. the next `<h1>` or `<h2>` for an `<h2>`), or the end of the document. See `nodes_between()` for details.
+    ///
+    /// Returns `None` if `handle` does not refer to a heading.
+    ///
+    /// # Example
+    /// ```
+    /// let dom = tl::parse("<h2 id=a>A</h2><p>1</p><h3>B</h3><h2>C</h2><p>2</p>", Default::default()).unwrap();
+    /// let a = dom.get_element_by_id("a").unwrap();
+    ///
+    /// let section = dom.section_after_heading(a).unwrap();
+    /// assert_eq!(section.filter(|h| h.get(dom.parser()).unwrap().as_tag().is_some()).count(), 2);
+    /// ```
+    pub fn section_after_heading(
+        &self,
+        handle: NodeHandle,
+    ) -> Option<impl Iterator<Item = NodeHandle> + '_> {
+        let level = heading_level(handle.get(&self.parser)?)?;
+        let start = self.subtree_end(handle)?;
+
+        let end = self.nodes()[start..]
+            .iter()
+            .position(|node| heading_level(node).is_some_and(|x| x <= level))
+            .map(|offset| self.parser.handle_at(start + offset));
+
+        Some(self.section_until(handle, end))
+    }
+
+    /// Returns an iterator over the nodes after the given heading up to `end`, or the end of the document if `None`
+    pub(crate) fn section_until(
+        &self,
+        handle: NodeHandle,
+        end: Option<NodeHandle>,
+    ) -> Box<dyn Iterator<Item = NodeHandle> + '_> {
+        match end {
+            Some(end) => Box::new(self.nodes_between(handle, end)),
+            None => {
+                let start = self.subtree_end(handle).unwrap_or(self.nodes().len());
+                Box::new((start..self.nodes().len()).map(|x| self.parser.handle_at(x)))
+            }
+        }
+    }
+
+    /// Returns the headings (`<h1>` to `<h6>`) of this document as a tree, which can be used to build a table of contents
+    ///
+    /// This is `outline_with()` using the default options, which include all headings.
+    ///
+    /// # Example
+    /// ```
+    /// let dom = tl::parse("<h1>Guide</h1><h2 id=install>Install</h2><p>Run it</p><h2>Usage</h2>", Default::default()).unwrap();
+    /// let outline = dom.outline();
+    ///
+    /// assert_eq!(outline.len(), 1);
+    /// let sections = outline[0].children.iter().map(|x| (x.text.as_str(), x.id.as_deref())).collect::<Vec<_>>();
+    /// assert_eq!(sections, [("Install", Some("install")), ("Usage", None)]);
+    /// assert_eq!(outline[0].children[0].content.len(), 2);
+    /// ```
+    pub fn outline(&self) -> Vec<OutlineEntry> {
+        self.outline_with(OutlineOptions::default())
+    }
+
+    /// Returns the headings (`<h1>` to `<h6>`) of this document as a tree, using the given options
+    ///
+    /// Headings are visited in document order, and each one becomes a child of the closest preceding heading of a higher rank.
+    /// Levels may be skipped, so an `<h4>` that follows an `<h2>` is a child of the `<h2>`.
+    pub fn outline_with(&self, options: OutlineOptions) -> Vec<OutlineEntry> {
+        outline::build(self, options)
+    }
+
+    /// Returns a read-only view of the subtree of the given node, which can be queried like a document of its own
+    ///
+    /// Lookups such as `get_element_by_id()` and `query_selector()` only return elements within the subtree.
+    /// Returns `None` if the handle does not refer to a node of this document.
+    ///
+    /// # Example
+    /// ```
+    /// let html = r#"<p id="x">outside</p><section><p id="x">inside</p></section>"#;
+    /// let dom = tl::parse(html, tl::ParserOptions::default().track_ids()).unwrap();
+    /// let section = dom.query_selector_first("section").unwrap();
+    ///
+    /// let view = dom.subtree_view(section).unwrap();
+    /// let p = view.get_element_by_id("x").unwrap();
+    /// assert_eq!(p.get(dom.parser()).unwrap().inner_text(dom.parser()), "inside");
+    /// assert_eq!(view.outer_html(), r#"<section><p id="x">inside</p></section>"#);
+    /// ```
+    pub fn subtree_view(&self, handle: NodeHandle) -> Option<SubtreeView<'_, 'a>> {
+        let start = self
+            .parser
+            .position_of(handle)
+            .filter(|&position| position < self.parser.document_len())?;
+        let end = self.subtree_end(handle)?;
+
+        Some(SubtreeView::new(self, handle, start..end))
+    }
+
+    /// Returns the position right after the subtree of the given node
+    fn subtree_end(&self, handle: NodeHandle) -> Option<usize> {
+        let start = self.parser.position_of(handle)?;
+
+        let end = handle
+            .get(&self.parser)?
+            .children()
+            .and_then(|children| children.positions(&self.parser))
+            .map_or(start + 1, |range| range.end);
+
+        Some(end)
+    }
+
+    /// Returns the positions of all ancestors of the given node
+    fn ancestor_positions(&self, handle: NodeHandle) -> Vec<usize> {
+        let Some(path) = NodePath::of(handle, &self.parser) else {
+            return Vec::new();
+        };
+
+        let indices = path.indices();
+
+        (1..indices.len())
+            .filter_map(|depth| NodePath::new(indices[..depth].to_vec()).resolve(&self.parser))
+            .filter_map(|ancestor| self.parser.position_of(ancestor))
+            .collect()
+    }
+
+    /// Splits a text node into two at the given byte offset
+    ///
+    /// The text node referred to by `handle` keeps the text before `at`, and a new text node containing the rest
+    /// is inserted right after it. Returns the handles of both nodes.
+    ///
+    /// Returns `None` (without changing anything) if `handle` does not refer to a text node, or if `at` is not
+    /// a UTF-8 character boundary strictly inside of the text.
+    ///
+    /// # Example
+    /// ```
+    /// let mut dom = tl::parse("<p>Hello world</p>", Default::default()).unwrap();
+    /// let text = tl::NodeHandle::new(1);
+    ///
+    /// let (first, second) = dom.split_text(text, 5).unwrap();
+    /// assert_eq!(first.get(dom.parser()).unwrap().as_raw().unwrap(), "Hello");
+    /// assert_eq!(second.get(dom.parser()).unwrap().as_raw().unwrap(), " world");
+    /// assert_eq!(dom.outer_html(), "<p>Hello world</p>");
+    /// ```
+    pub fn split_text(
+        &mut self,
+        handle: NodeHandle,
+        at: usize,
+    ) -> Option<(NodeHandle, NodeHandle)> {
+        let text = handle.get(&self.parser)?.as_raw()?;
+        let len = text.as_bytes().len();
+
+        if at == 0 || at >= len || !is_char_boundary(text.as_bytes(), at) {
+            return None;
+        }
+
+        let (first, second) = (text.slice(0..at), text.slice(at..len));
+        let (parent, index) = self.parser.locate(handle)?;
+
+        self.invalidate_query_cache();
+
+        *handle.get_mut(&mut self.parser)?.as_raw_mut()? = first;
+
+        let position = self.parser.position_of(handle)? + 1;
+        let second = self.parser.insert_nodes(position, [Node::Raw(second)])[0];
+        self.parser.attach(parent, index + 1, second);
+
+        self.notify(|| Mutation::TextChanged { handle });
+        self.notify(|| Mutation::NodeInserted {
+            handle: second,
+            parent,
+            index: index + 1,
+        });
+
+        Some((handle, second))
+    }
+
+    /// Wraps the given byte range of a text node in a new element with the given tag name, and returns the handle of the element
+    ///
+    /// The text node is split (see `split_text()`) as needed, so that the wrapped text becomes the only child of the new element.
+    /// This is useful for highlighting search terms, for example.
+    ///
+    /// Returns `None` (without changing anything) if `handle` does not refer to a text node, if the range is empty or out of bounds,
+    /// if it does not start and end at UTF-8 character boundaries, or if `tag_name` is not a valid tag name.
+    ///
+    /// # Example
+    /// ```
+    /// let mut dom = tl::parse("<p>Hello world</p>", Default::default()).unwrap();
+    /// let text = tl::NodeHandle::new(1);
+    ///
+    /// dom.wrap_text_range(text, 6..11, "mark").unwrap();
+    /// assert_eq!(dom.outer_html(), "<p>Hello <mark>world</mark></p>");
+    /// ```
+    pub fn wrap_text_range(
+        &mut self,
+        handle: NodeHandle,
+        range: Range<usize>,
+        tag_name: &str,
+    ) -> Option<NodeHandle> {
+        let text = handle.get(&self.parser)?.as_raw()?.as_bytes();
+
+        let is_valid_range = range.start < range.end
+            && is_char_boundary(text, range.start)
+            && is_char_boundary(text, range.end);
+        let is_valid_name = !tag_name.is_empty() && tag_name.bytes().all(crate::util::is_ident);
+
+        if !is_valid_range || !is_valid_name {
+            return None;
+        }
+
+        let len = text.len();
+        let name = Bytes::try_from(tag_name.to_owned()).ok()?;
+
+        let mut middle = handle;
+        if range.end < len {
+            middle = self.split_text(middle, range.end)?.0;
+        }
+        if range.start > 0 {
+            middle = self.split_text(middle, range.start)?.1;
+        }
+
+        self.invalidate_query_cache();
+
+        let (parent, index) = self.parser.locate(middle)?;
+        let position = self.parser.position_of(middle)?;
+
+        // the element comes right before its only child in document order
+        let mut element = HTMLTag::from_name(name, parent);
+        element._children.push(middle);
+        let element = self.parser.insert_nodes(position, [Node::Tag(element)])[0];
+        self.parser.set_parent(middle, Some(element));
+
+        self.parser.detach(parent, index);
+        self.parser.attach(parent, index, element);
+
+        self.notify(|| Mutation::NodeRemoved { handle: middle });
+        self.notify(|| Mutation::NodeInserted {
+            handle: element,
+            parent,
+            index,
+        });
+        self.notify(|| Mutation::NodeInserted {
+            handle: middle,
+            parent: Some(element),
+            index: 0,
+        });
+
+        Some(element)
+    }
+
+    /// Adds a node as the last child of the given element, and returns the handle of the new node
+    ///
+    /// The node is stored right after the last node in the subtree of the element, so `children().all()`,
+    /// `nodes()` and query selectors see it in document order. The parent and the children of a tag are reset
+    /// (see `Parser::add_node()`), so nested elements are built by appending to the returned handle.
+    /// Inserted elements are added to the lookup tables of tracked ids, classes, tag names and attributes.
+    ///
+    /// Returns `None` (without changing anything) if `parent` does not refer to an element of this document,
+    /// or if it is a void element (e.g. `<img>`), which cannot have children. This clears the query cache, if enabled.
+    ///
+    /// # Example
+    /// ```
+    /// let mut dom = tl::parse("<ul><li>a</li></ul>", Default::default()).unwrap();
+    /// let list = dom.query_selector_first("ul").unwrap();
+    ///
+    /// let item = dom.append_child(list, tl::Node::Tag(tl::HTMLTag::new("li", Default::default()))).unwrap();
+    /// dom.append_child(item, tl::Node::Raw("b".into())).unwrap();
+    /// assert_eq!(dom.outer_html(), "<ul><li>a</li><li>b</li></ul>");
+    /// ```
+    pub fn append_child(&mut self, parent: NodeHandle, node: Node<'a>) -> Option<NodeHandle> {
+        let index = parent.get(&self.parser)?.as_tag()?._children.len();
+        self.insert_child(parent, index, node).ok()
+    }
+
+    /// Adds a node as the first child of the given element, and returns the handle of the new node
+    ///
+    /// This works like `append_child()`, except that the node is stored right after the element itself.
+    ///
+    /// # Example
+    /// ```
+    /// let mut dom = tl::parse("<p><b>world</b></p>", Default::default()).unwrap();
+    /// let paragraph = dom.query_selector_first("p").unwrap();
+    ///
+    /// dom.prepend_child(paragraph, tl::Node::Raw("Hello ".into())).unwrap();
+    /// assert_eq!(dom.outer_html(), "<p>Hello <b>world</b></p>");
+    /// ```
+    pub fn prepend_child(&mut self, parent: NodeHandle, node: Node<'a>) -> Option<NodeHandle> {
+        self.insert_child(parent, 0, node).ok()
+    }
+
+    /// Inserts a node into the children of the given element right before `reference`, and returns the handle of the new node
+    ///
+    /// This works like `append_child()`, except for the position of the node.
+    ///
+    /// # Errors
+    /// Returns an error (without changing anything) if `parent` does not refer to an element of this document,
+    /// if it is a void element, or if `reference` is not one of its children.
+    ///
+    /// # Example
+    /// ```
+    /// let mut dom = tl::parse("<ol><li>a</li><li>c</li></ol>", Default::default()).unwrap();
+    /// let list = dom.query_selector_first("ol").unwrap();
+    /// let c = dom.query_selector("li").unwrap().nth(1).unwrap();
+    ///
+    /// let b = dom.insert_before(list, c, tl::Node::Tag(tl::HTMLTag::new("li", Default::default()))).unwrap();
+    /// dom.append_child(b, tl::Node::Raw("b".into()));
+    /// assert_eq!(dom.outer_html(), "<ol><li>a</li><li>b</li><li>c</li></ol>");
+    ///
+    /// assert_eq!(dom.insert_before(b, c, tl::Node::Raw("x".into())), Err(tl::InsertError::NotAChild));
+    /// ```
+    pub fn insert_before(
+        &mut self,
+        parent: NodeHandle,
+        reference: NodeHandle,
+        node: Node<'a>,
+    ) -> Result<NodeHandle, InsertError> {
+        let index = self.child_index(parent, reference)?;
+        self.insert_child(parent, index, node)
+    }
+
+    /// Inserts a node into the children of the given element right after `reference`, and returns the handle of the new node
+    ///
+    /// This works like `insert_before()`, except that the node is inserted after the reference node (and its subtree).
+    ///
+    /// # Errors
+    /// See `insert_before()`.
+    ///
+    /// # Example
+    /// ```
+    /// let mut dom = tl::parse(r#"<figure><img src="a.png"></figure>"#, Default::default()).unwrap();
+    /// let figure = dom.query_selector_first("figure").unwrap();
+    /// let image = dom.query_selector_first("img").unwrap();
+    ///
+    /// let caption = tl::Node::Tag(tl::HTMLTag::new("figcaption", Default::default()));
+    /// dom.insert_after(figure, image, caption).unwrap();
+    /// assert_eq!(dom.outer_html(), r#"<figure><img src="a.png"><figcaption></figcaption></figure>"#);
+    /// ```
+    pub fn insert_after(
+        &mut self,
+        parent: NodeHandle,
+        reference: NodeHandle,
+        node: Node<'a>,
+    ) -> Result<NodeHandle, InsertError> {
+        let index = self.child_index(parent, reference)?;
+        self.insert_child(parent, index + 1, node)
+    }
+
+    /// Returns the index of `child` among the children of the element `parent`
+    fn child_index(&self, parent: NodeHandle, child: NodeHandle) -> Result<usize, InsertError> {
+        let tag = parent
+            .get(&self.parser)
+            .and_then(Node::as_tag)
+            .ok_or(InsertError::InvalidParent)?;
+
+        tag._children
+            .as_slice()
+            .iter()
+            .position(|&handle| handle == child)
+            .ok_or(InsertError::NotAChild)
+    }
+
+    /// Inserts a node into the children of the given element at the given index, which must be at most the number of children
+    fn insert_child(
+        &mut self,
+        parent: NodeHandle,
+        index: usize,
+        mut node: Node<'a>,
+    ) -> Result<NodeHandle, InsertError> {
+        let tag = parent
+            .get(&self.parser)
+            .and_then(Node::as_tag)
+            .ok_or(InsertError::InvalidParent)?;
+        if tag.is_void() {
+            return Err(InsertError::VoidElement);
+        }
+
+        // the node is stored before the sibling it is inserted before, or after the subtree of the parent
+        let position = match tag._children.get(index) {
+            Some(&sibling) => self.parser.position_of(sibling),
+            None => self.subtree_end(parent),
+        };
+        let parent_position = self.parser.position_of(parent);
+        let (Some(position), Some(parent_position)) = (position, parent_position) else {
+            return Err(InsertError::InvalidParent);
+        };
+        let is_detached = parent_position >= self.parser.document_len();
+
+        if let Node::Tag(tag) = &mut node {
+            tag._children = InlineVec::new();
+        }
+
+        self.invalidate_query_cache();
+
+        let handle = self.parser.insert_nodes(position, [node])[0];
+        if is_detached {
+            self.parser.detached += 1;
+        }
+
+        self.parser.attach(Some(parent), index, handle);
+        self.parser.track_inserted(handle);
+
+        self.notify(|| Mutation::NodeInserted {
+            handle,
+            parent: Some(parent),
+            index,
+        });
+
+        Ok(handle)
+    }
+
+    /// Replaces the children of the given element with the nodes parsed from an HTML fragment
+    ///
+    /// The fragment is parsed with the options of this document (except for the progress callback). The new nodes
+    /// are copied, so the fragment does not need to outlive the document, and the new elements are serialized from their
+    /// name and attributes. The content of raw text elements (e.g. `<script>`) is not parsed, but inserted as a single text node.
+    /// The old children and their subtrees are removed like with `remove()`, so their handles no longer resolve.
+    /// New elements are added to the lookup tables.
+    ///
+    /// This clears the query cache, if enabled.
+    ///
+    /// # Errors
+    /// Returns an error (without changing anything) if `handle` does not refer to an element of this document,
+    /// if it is a void element, or if the fragment cannot be parsed.
+    ///
+    /// # Example
+    /// ```
+    /// let mut dom = tl::parse("<ul><li>a</li></ul>", Default::default()).unwrap();
+    /// let list = dom.query_selector_first("ul").unwrap();
+    ///
+    /// let fragment = String::from("<li>b</li><li>c</li>");
+    /// dom.set_inner_html(list, &fragment).unwrap();
+    /// drop(fragment);
+    ///
+    /// assert_eq!(dom.outer_html(), "<ul><li>b</li><li>c</li></ul>");
+    /// assert_eq!(dom.query_selector_count("li"), Some(2));
+    /// ```
+    pub fn set_inner_html(&mut self, handle: NodeHandle, html: &str) -> Result<(), ParseError> {
+        let tag = handle
+            .get(&self.parser)
+            .and_then(Node::as_tag)
+            .ok_or(ParseError::InvalidParent)?;
+        if tag.is_void() {
+            return Err(ParseError::VoidElement);
+        }
+
+        if self
+            .parser
+            .options
+            .is_raw_text_element(tag.name().as_bytes())
+        {
+            let mut bytes = Bytes::new();
+            bytes.set(html).map_err(|_| ParseError::InvalidLength)?;
+            self.replace_children(handle, vec![Node::Raw(bytes)], &[0]);
+            return Ok(());
+        }
+
+        let mut options = self.parser.options.clone();
+        options.progress = None;
+
+        let mut fragment = Parser::new(html, options);
+        fragment.parse()?;
+
+        let nodes = fragment.tags.iter().map(Node::to_owned_node).collect();
+        let top = fragment
+            .ast
+            .iter()
+            .map(|handle| handle.get_inner() as usize)
+            .collect::<Vec<_>>();
+        self.replace_children(handle, nodes, &top);
+
+        Ok(())
+    }
+
+    /// Replaces the children of the given element with a single text node
+    ///
+    /// Unlike with `set_inner_html()`, the text is never parsed: `&`, `<` and `>` are escaped, so that the markup
+    /// of the document stays valid and `inner_text_decoded()` of the element returns exactly the given text.
+    /// The content of raw text elements (e.g. `<script>`) is not decoded by browsers, so their text is inserted as it is,
+    /// but it must not contain the end tag of the element. If the text is empty, all children are removed. The old children and their subtrees are removed
+    /// like with `remove()`, so their handles no longer resolve.
+    ///
+    /// This clears the query cache, if enabled.
+    ///
+    /// # Errors
+    /// Returns an error (without changing anything) if `handle` does not refer to an element of this document,
+    /// if it is a void element, if it is a raw text element and the text contains `</` followed by its name
+    /// (compared ASCII case-insensitively), or if the escaped text is too long to be stored in `Bytes`.
+    ///
+    /// # Example
+    /// ```
+    /// let mut dom = tl::parse("<p>Hello <b>World</b></p>", Default::default()).unwrap();
+    /// let p = dom.query_selector_first("p").unwrap();
+    ///
+    /// dom.set_inner_text(p, "5 > 3 & true").unwrap();
+    /// assert_eq!(dom.outer_html(), "<p>5 &gt; 3 &amp; true</p>");
+    ///
+    /// let tag = p.get(dom.parser()).unwrap().as_tag().unwrap();
+    /// assert_eq!(tag.inner_text_decoded(dom.parser()), "5 > 3 & true");
+    ///
+    /// let mut dom = tl::parse("<script></script>", Default::default()).unwrap();
+    /// let script = dom.children()[0];
+    /// dom.set_inner_text(script, "if (a < b && c) {}").unwrap();
+    /// assert_eq!(dom.outer_html(), "<script>if (a < b && c) {}</script>");
+    /// assert_eq!(dom.set_inner_text(script, "x = '</script>'"), Err(tl::InsertError::RawTextEndTag));
+    /// ```
+    pub fn set_inner_text(&mut self, handle: NodeHandle, text: &str) -> Result<(), InsertError> {
+        let tag = handle
+            .get(&self.parser)
+            .and_then(Node::as_tag)
+            .ok_or(InsertError::InvalidParent)?;
+        if tag.is_void() {
+            return Err(InsertError::VoidElement);
+        }
+
+        let name = tag.name().as_bytes();
+        let is_raw_text = self.parser.options.is_raw_text_element(name);
+        if is_raw_text && contains_end_tag(text.as_bytes(), name) {
+            return Err(InsertError::RawTextEndTag);
+        }
+
+        if text.is_empty() {
+            self.replace_children(handle, Vec::new(), &[]);
+            return Ok(());
+        }
+
+        let text = match is_raw_text {
+            true => Cow::Borrowed(text),
+            false => entities::escape(text),
+        };
+        let mut bytes = Bytes::new();
+        bytes
+            .set(text.into_owned())
+            .map_err(|_| InsertError::LengthOverflow)?;
+        self.replace_children(handle, vec![Node::Raw(bytes)], &[0]);
+
+        Ok(())
+    }
+
+    /// Replaces the children of the given element with the given nodes
+    ///
+    /// `nodes` are in document order and refer to each other by handles that are equal to their index,
+    /// like the nodes of a parser that has not been mutated. `top` contains the indices of the new children.
+    /// The element must exist.
+    fn replace_children(&mut self, handle: NodeHandle, nodes: Vec<Node<'a>>, top: &[usize]) {
+        let (Some(start), Some(end)) = (self.parser.position_of(handle), self.subtree_end(handle))
+        else {
+            return;
+        };
+
+        self.invalidate_query_cache();
+
+        let children = handle
+            .get(&self.parser)
+            .and_then(Node::as_tag)
+            .map_or(0, |tag| tag._children.len());
+        let mut removed = (0..children)
+            .rev()
+            .map(|index| self.parser.detach(Some(handle), index))
+            .collect::<Vec<_>>();
+        removed.reverse();
+        self.parser.remove_nodes(start + 1..end);
+
+        let is_detached = start >= self.parser.document_len();
+        let count = nodes.len();
+        let handles = self.parser.insert_nodes(start + 1, nodes);
+        if is_detached {
+            self.parser.detached += count;
+        }
+
+        // the nodes referred to each other by their index, which is mapped to their new handle
+        let remap = |handle: NodeHandle| handles[handle.get_inner() as usize];
+        for &new in &handles {
+            if let Some(tag) = new.get_mut(&mut self.parser).and_then(Node::as_tag_mut) {
+                tag._parent = tag._parent.map(remap);
+                for child in tag._children.as_mut_slice() {
+                    *child = remap(*child);
+                }
+            }
+        }
+
+        for &new in &handles {
+            self.parser.set_children_parents(new);
+        }
+
+        for (index, &node) in top.iter().enumerate() {
+            self.parser.attach(Some(handle), index, handles[node]);
+        }
+
+        for &new in &handles {
+            self.parser.track_inserted(new);
+        }
+
+        for child in removed {
+            self.notify(|| Mutation::NodeRemoved { handle: child });
+        }
+        for (index, &node) in top.iter().enumerate() {
+            self.notify(|| Mutation::NodeInserted {
+                handle: handles[node],
+                parent: Some(handle),
+                index,
+            });
+        }
+    }
+
+    /// Removes a node and its subtree from the document
+    ///
+    /// The removed nodes no longer appear in `children()`, `nodes()`, `outer_html()`, lookups or query selector results.
+    /// Handles to the node or any node in its subtree become dangling, so that resolving them returns `None`.
+    ///
+    /// Returns `None` (without changing anything) if `handle` does not refer to a node of this document.
+    /// This clears the query cache, if enabled.
+    ///
+    /// # Example
+    /// ```
+    /// let mut dom = tl::parse("<ul><li>a</li><li>b</li></ul>", Default::default()).unwrap();
+    /// let first = dom.query_selector_first("li").unwrap();
+    ///
+    /// dom.remove(first).unwrap();
+    /// assert_eq!(dom.outer_html(), "<ul><li>b</li></ul>");
+    /// assert!(first.get(dom.parser()).is_none());
+    /// ```
+    pub fn remove(&mut self, handle: NodeHandle) -> Option<()> {
+        let (parent, index) = self.parser.locate(handle)?;
+        let start = self.parser.position_of(handle)?;
+        let end = self.subtree_end(handle)?;
+
+        self.invalidate_query_cache();
+
+        self.parser.detach(parent, index);
+        self.parser.remove_nodes(start..end);
+
+        self.notify(|| Mutation::NodeRemoved { handle });
+        Some(())
+    }
+
+    /// Removes all elements that match the query selector together with their subtrees, and returns how many matched
+    ///
+    /// Matching elements within the subtree of another matching element are removed with it and are counted as well.
+    /// The selector is evaluated once before anything is removed, so the result does not depend on the order of removal
+    /// (e.g. `p + p` removes every second and later paragraph). Like with `remove()`, handles to the removed nodes
+    /// become dangling. This clears the query cache, if enabled.
+    ///
+    /// # Errors
+    /// Returns an error (without changing anything) if the selector is invalid.
+    ///
+    /// # Example
+    /// ```
+    /// let mut dom = tl::parse(
+    ///     r#"<div onclick="x()"><script>a()</script>Hi</div><p>Text<style>p{}</style></p>"#,
+    ///     Default::default()
+    /// ).unwrap();
+    ///
+    /// assert_eq!(dom.remove_all("script, style, [onclick]"), Ok(3));
+    /// assert_eq!(dom.outer_html(), "<p>Text</p>");
+    /// ```
+    pub fn remove_all(&mut self, selector: &str) -> Result<usize, queryselector::ParseError> {
+        let matches = self.query_selector_checked(selector)?.collect::<Vec<_>>();
+
+        // nested matches that were already removed with an ancestor no longer resolve, so this does nothing for them
+        for &handle in &matches {
+            self.remove(handle);
+        }
+
+        Ok(matches.len())
+    }
+
+    /// Removes all comments for which the predicate returns `true`, and returns how many were removed
+    ///
+    /// The predicate receives the same text as with `find_comments()`. Like with `remove()`, handles to the removed nodes
+    /// become dangling. This clears the query cache, if enabled.
+    ///
+    /// # Example
+    /// ```
+    /// let mut dom = tl::parse("<p>a<!-- BEGIN AD --></p><!-- keep -->", Default::default()).unwrap();
+    ///
+    /// assert_eq!(dom.remove_matching_comments(|comment| comment.contains("BEGIN AD")), 1);
+    /// assert_eq!(dom.outer_html(), "<p>a</p><!-- keep -->");
+    /// ```
+    pub fn remove_matching_comments<F>(&mut self, pred: F) -> usize
+    where
+        F: Fn(&str) -> bool,
+    {
+        let matches = self.find_comments(pred).collect::<Vec<_>>();
+
+        for &handle in &matches {
+            self.remove(handle);
+        }
+
+        matches.len()
+    }
+
+    /// Moves a node and its subtree into the children of another element, at the given index
+    ///
+    /// The index refers to the children of `new_parent` without the moved node, so moving a node within its parent works
+    /// as if it was removed first. Unlike with `remove()` and `append_child()`, the handles of the moved nodes stay valid.
+    /// The nodes are moved within the node table as well, so `children().all()`, `nodes()`, lookups and query selectors
+    /// see them in their new place in document order. This clears the query cache, if enabled.
+    ///
+    /// # Errors
+    /// Returns an error (without changing anything) if `node` does not refer to a node of this document,
+    /// if `new_parent` is not an element or is a void element, if `new_parent` is `node` itself or lies within its subtree,
+    /// or if the index is greater than the number of children.
+    ///
+    /// # Example
+    /// ```
+    /// let mut dom = tl::parse("<ul id=a><li>1</li><li>2</li></ul><ul id=b><li>3</li></ul>", Default::default()).unwrap();
+    /// let a = dom.get_element_by_id("a").unwrap();
+    /// let b = dom.get_element_by_id("b").unwrap();
+    ///
+    /// let items = dom.query_selector("#a > li").unwrap().collect::<Vec<_>>();
+    /// for (index, item) in items.into_iter().enumerate() {
+    ///     dom.move_node(item, b, index).unwrap();
+    /// }
+    ///
+    /// assert_eq!(dom.outer_html(), r#"<ul id=a></ul><ul id=b><li>1</li><li>2</li><li>3</li></ul>"#);
+    /// assert_eq!(dom.move_node(b, b, 0), Err(tl::MoveError::Cycle));
+    /// ```
+    pub fn move_node(
+        &mut self,
+        node: NodeHandle,
+        new_parent: NodeHandle,
+        index: usize,
+    ) -> Result<(), MoveError> {
+        let (old_parent, old_index) = self.parser.locate(node).ok_or(MoveError::InvalidNode)?;
+        let (Some(start), Some(end)) = (self.parser.position_of(node), self.subtree_end(node))
+        else {
+            return Err(MoveError::InvalidNode);
+        };
+
+        let tag = new_parent
+            .get(&self.parser)
+            .and_then(Node::as_tag)
+            .ok_or(MoveError::InvalidParent)?;
+        if tag.is_void() {
+            return Err(MoveError::VoidElement);
+        }
+
+        let parent_position = self
+            .parser
+            .position_of(new_parent)
+            .ok_or(MoveError::InvalidParent)?;
+        if (start..end).contains(&parent_position) {
+            return Err(MoveError::Cycle);
+        }
+
+        // the node is stored before the sibling it is moved before, or after the subtree of the new parent
+        let siblings = tag
+            ._children
+            .as_slice()
+            .iter()
+            .copied()
+            .filter(|&child| child != node)
+            .collect::<Vec<_>>();
+        let to = match siblings.get(index) {
+            Some(&sibling) => self.parser.position_of(sibling),
+            None if index == siblings.len() => self.subtree_end(new_parent),
+            None => return Err(MoveError::InvalidIndex),
+        }
+        .ok_or(MoveError::InvalidParent)?;
+
+        self.invalidate_query_cache();
+
+        let document_len = self.parser.document_len();
+        let was_detached = start >= document_len;
+        let is_detached = parent_position >= document_len;
+
+        self.parser.untrack(start..end);
+        self.parser.detach(old_parent, old_index);
+        self.parser.move_nodes(start..end, to);
+        match (was_detached, is_detached) {
+            (false, true) => self.parser.detached += end - start,
+            (true, false) => self.parser.detached -= end - start,
+            _ => {}
+        }
+        self.parser.attach(Some(new_parent), index, node);
+
+        let new_start = self.parser.position_of(node).unwrap_or(start);
+        for position in new_start..new_start + (end - start) {
+            let handle = self.parser.handle_at(position);
+            self.parser.track_inserted(handle);
+        }
+
+        self.notify(|| Mutation::NodeRemoved { handle: node });
+        self.notify(|| Mutation::NodeInserted {
+            handle: node,
+            parent: Some(new_parent),
+            index,
+        });
+
+        Ok(())
+    }
+
+    /// Returns the HTML version.
+    /// This is determined by the `<!DOCTYPE>` tag
+    pub fn version(&self) -> Option<HTMLVersion> {
+        self.parser.version
+    }
+
+    /// Returns the doctype of this document (e.g. `<!DOCTYPE html>`) as it appears in the source, if there is one
+    ///
+    /// The doctype is the first markup declaration of the document, if only whitespace precedes it.
+    /// It is not a node, so it is not part of `children()` or `nodes()`, but `outer_html()` writes it.
+    /// Markup declarations elsewhere in the document are stored as comments.
+    ///
+    /// # Example
+    /// ```
+    /// let dom = tl::parse("<!DOCTYPE html><p>Hi</p>", Default::default()).unwrap();
+    /// assert_eq!(dom.doctype(), Some(&"<!DOCTYPE html>".into()));
+    /// assert_eq!(dom.children().len(), 1);
+    /// assert_eq!(dom.outer_html(), "<!DOCTYPE html><p>Hi</p>");
+    /// ```
+    pub fn doctype(&self) -> Option<&Bytes<'a>> {
+        self.parser.doctype.as_ref().map(|(_, doctype)| doctype)
+    }
+
+    /// Returns the recoverable errors that were encountered while parsing
+    ///
+    /// This is always empty unless `ParserOptions::collect_diagnostics()` was set.
+    ///
+    /// # Example
+    /// ```
+    /// let options = tl::ParserOptions::default().collect_diagnostics();
+    /// let dom = tl::parse("<div {{#if x}}hidden{{/if}}></div>", options).unwrap();
+    /// assert_eq!(dom.diagnostics().len(), 2);
+    /// assert_eq!(dom.diagnostics()[0].at, 5);
+    /// ```
+    pub fn diagnostics(&self) -> &[crate::Diagnostic] {
+        &self.parser.diagnostics
+    }
+
+    /// Returns whether parsing was cancelled by the progress callback, in which case this document is incomplete
+    ///
+    /// This can only be `true` if `ParserOptions::partial_on_cancel()` was set.
+    pub fn is_partial(&self) -> bool {
+        self.parser.cancelled
+    }
+
+    /// Returns the contained markup of all of the elements in this DOM.
+    ///
+    /// Equivalent to [Element#outerHTML](https://developer.mozilla.org/en-US/docs/Web/API/Element/outerHTML) in browsers)
+    ///
+    /// # Example
+    /// ```
+    /// let html = r#"<div><p href="/about" id="find-me">Hello world</p></div>"#;
+    /// let mut dom = tl::parse(html, Default::default()).unwrap();
+    ///
+    /// let element = dom.get_element_by_id("find-me")
+    ///     .unwrap()
+    ///     .get_mut(dom.parser_mut())
+    ///     .unwrap()
+    ///     .as_tag_mut()
+    ///     .unwrap();
+    ///
+    /// element.attributes_mut().get_mut("href").flatten().unwrap().set("/");
+    ///
+    /// assert_eq!(dom.outer_html(), r#"<div><p href="/" id="find-me">Hello world</p></div>"#);
+    /// ```
+    pub fn outer_html(&self) -> String {
+        let mut outer_html = String::with_capacity(self.parser.stream.len());
+        // writing into a string never fails
+        let _ = self.write_outer_html(&mut outer_html);
+        outer_html
+    }
+
+    /// Writes the markup of all topmost nodes and their subtrees into `dest`, see `outer_html()`
+    ///
+    /// Unlike `outer_html()`, this does not build the whole document in memory first, and it does not recurse,
+    /// so deeply nested documents cannot overflow the stack. Errors of `dest` are returned as is.
+    /// To write into an [`io::Write`](std::io::Write), such as a `BufWriter<File>`, wrap it in an [`IoWriter`](crate::IoWriter).
+    ///
+    /// # Example
+    /// ```
+    /// use tl::IoWriter;
+    ///
+    /// let dom = tl::parse("<ul><li>a</li><li>b</li></ul>", Default::default()).unwrap();
+    ///
+    /// let mut out = IoWriter::new(Vec::new());
+    /// let result = dom.write_outer_html(&mut out);
+    /// let bytes = out.finish(result).unwrap();
+    /// assert_eq!(bytes, b"<ul><li>a</li><li>b</li></ul>");
+    /// ```
+    pub fn write_outer_html<W: fmt::Write + ?Sized>(&self, dest: &mut W) -> fmt::Result {
+        let children = self.children();
+        // the doctype is written where it appeared in the source, or at the end if there are fewer nodes now
+        let doctype = self
+            .parser
+            .doctype
+            .as_ref()
+            .map(|(position, doctype)| ((*position).min(children.len()), doctype));
+
+        for (index, node) in children.iter().enumerate() {
+            if let Some((_, doctype)) = doctype.filter(|&(position, _)| position == index) {
+                dest.write_str(&doctype.as_utf8_str())?;
+            }
+
+            let node = node.get(&self.parser).unwrap();
+            node.write_outer_html(&self.parser, dest)?;
+        }
+
+        if let Some((_, doctype)) = doctype.filter(|&(position, _)| position == children.len()) {
+            dest.write_str(&doctype.as_utf8_str())?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the markup inside the document element (`<html>`), or the markup of all topmost nodes if there is none
+    ///
+    /// For a full document, this is the content of the `<html>` element, like `HTMLTag::inner_html()`.
+    /// A fragment (such as `Hello <b>world</b>`) has no enclosing element, so this is the same as `outer_html()`.
+    ///
+    /// # Example
+    /// ```
+    /// let dom = tl::parse("<!DOCTYPE html><html><body>Hi</body></html>", Default::default()).unwrap();
+    /// assert_eq!(dom.inner_html(), "<body>Hi</body>");
+    ///
+    /// let fragment = tl::parse("Hello <b>world</b>", Default::default()).unwrap();
+    /// assert_eq!(fragment.inner_html(), "Hello <b>world</b>");
+    /// ```
+    pub fn inner_html(&self) -> String {
+        let mut inner_html = String::new();
+        // writing into a string never fails
+        let _ = self.write_inner_html(&mut inner_html);
+        inner_html
+    }
+
+    /// Writes the markup inside the document element into `dest`, see `inner_html()` and `write_outer_html()`
+    pub fn write_inner_html<W: fmt::Write + ?Sized>(&self, dest: &mut W) -> fmt::Result {
+        let html = self
+            .document_element()
+            .and_then(|handle| handle.get(&self.parser)?.as_tag());
+
+        match html {
+            Some(html) => html.write_inner_html(&self.parser, dest),
+            None => self.write_outer_html(dest),
+        }
+    }
+
+    /// Returns the text of all topmost nodes and their subtrees, excluding any markup
+    ///
+    /// This works the same for full documents and fragments, and uses the same rules as `HTMLTag::inner_text()`:
+    /// text nodes are concatenated as they are (including the content of elements such as `<title>` or `<script>`),
+    /// while comments and the doctype are skipped. See [`VDom::visible_text()`] for the text that is actually rendered.
+    ///
+    /// # Example
+    /// ```
+    /// let dom = tl::parse("<!-- note --><p>Hello</p> <b>world</b>", Default::default()).unwrap();
+    /// assert_eq!(dom.inner_text(), "Hello world");
+    /// ```
+    pub fn inner_text(&self) -> Cow<'_, str> {
+        let mut texts = SubtreeTexts::new(&self.parser, self.children(), false);
+
+        match (texts.next(), texts.next()) {
+            (None, _) => Cow::Borrowed(""),
+            (Some(text), None) => text.as_utf8_str(),
+            _ => {
+                let mut inner_text = String::new();
+                // writing into a string never fails
+                let _ = self.write_inner_text(&mut inner_text);
+                Cow::Owned(inner_text)
+            }
+        }
+    }
+
+    /// Writes the text of all topmost nodes and their subtrees into `dest`, see `inner_text()` and `write_outer_html()`
+    pub fn write_inner_text<W: fmt::Write + ?Sized>(&self, dest: &mut W) -> fmt::Result {
+        crate::parser::write_texts(
+            SubtreeTexts::new(&self.parser, self.children(), false),
+            dest,
+        )
+    }
+
+    /// Returns an iterator over the text nodes of this document and their handles, in document order
+    ///
+    /// This yields the same text nodes as `HTMLTag::text_nodes()` does for a subtree, skipping comments and the doctype.
+    ///
+    /// # Example
+    /// ```
+    /// let dom = tl::parse("<!DOCTYPE html><p>a</p> <p>b<!-- c --></p>", Default::default()).unwrap();
+    /// let parser = dom.parser();
+    ///
+    /// let texts = dom.text_nodes().map(|(_, text)| text.as_utf8_str()).collect::<Vec<_>>();
+    /// assert_eq!(texts, ["a", " ", "b"]);
+    ///
+    /// let (handle, _) = dom.text_nodes().non_whitespace().last().unwrap();
+    /// let parent = handle.parent(parser).unwrap();
+    /// assert_eq!(parent.get(parser).unwrap().inner_text(parser), "b");
+    /// ```
+    pub fn text_nodes(&self) -> TextNodes<'_, 'a> {
+        TextNodes::new(&self.parser, 0..self.parser.document_len())
+    }
+
+    /// Returns the handle of the first node (in document order) for which the predicate returns `true`
+    ///
+    /// Unlike searching `nodes()`, this returns a handle, which can be used to change the node afterwards
+    /// (e.g. with `NodeHandle::get_mut()`). See `HTMLTag::find_node()` to only search the subtree of an element.
+    ///
+    /// # Example
+    /// ```
+    /// let mut dom = tl::parse(r#"<p>hello <span class="x">whats up</span></p>"#, Default::default()).unwrap();
+    ///
+    /// let handle = dom
+    ///     .find_node(|node, _| node.as_tag().is_some_and(|tag| tag.name() == "span"))
+    ///     .unwrap();
+    ///
+    /// let tag = handle.get_mut(dom.parser_mut()).unwrap().as_tag_mut().unwrap();
+    /// tag.attributes_mut().insert("class", Some("y"));
+    /// assert_eq!(dom.outer_html(), r#"<p>hello <span class="y">whats up</span></p>"#);
+    /// ```
+    pub fn find_node<F>(&self, f: F) -> Option<NodeHandle>
+    where
+        F: FnMut(&Node<'a>, NodeHandle) -> bool,
+    {
+        self.find_all_nodes(f).next()
+    }
+
+    /// Returns an iterator over the handles of all nodes for which the predicate returns `true`, in document order
+    ///
+    /// See `find_node()`.
+    pub fn find_all_nodes<'b, F>(&'b self, f: F) -> Box<dyn Iterator<Item = NodeHandle> + 'b>
+    where
+        F: FnMut(&Node<'a>, NodeHandle) -> bool + 'b,
+    {
+        self.parser.find_nodes(0..self.parser.document_len(), f)
+    }
+
+    /// Returns an iterator over the handles of the comments for which the predicate returns `true`, in document order
+    ///
+    /// The predicate receives the text of the comment without `<!--` and `-->`, converted to UTF-8 lossily.
+    /// Other markup declarations that are stored as comments (such as a misplaced doctype) are skipped.
+    /// Selectors can only match elements, so this (together with `find_raw()`) covers the other kinds of nodes.
+    ///
+    /// # Example
+    /// ```
+    /// let dom = tl::parse("<!DOCTYPE html><!-- BEGIN AD --><p>a</p><!-- END AD --><!-- note -->", Default::default()).unwrap();
+    /// assert_eq!(dom.find_comments(|comment| comment.ends_with(" AD ")).count(), 2);
+    /// assert_eq!(dom.find_comments(|_| true).count(), 3);
+    /// ```
+    pub fn find_comments<'b, F>(&'b self, pred: F) -> impl Iterator<Item = NodeHandle> + 'b
+    where
+        F: Fn(&str) -> bool + 'b,
+    {
+        let parser = self.parser();
+
+        self.nodes()
+            .iter()
+            .enumerate()
+            .filter_map(move |(position, node)| {
+                let comment = node.as_comment()?.as_utf8_str();
+                let body = util::comment_body(&comment)?;
+                pred(body).then(|| parser.handle_at(position))
+            })
+    }
+
+    /// Returns an iterator over the handles of the text nodes for which the predicate returns `true`, in document order
+    ///
+    /// The predicate receives the raw text, converted to UTF-8 lossily, with character references left as they are.
+    ///
+    /// # Example
+    /// ```
+    /// let mut dom = tl::parse("<p>[[draft]] Hello</p><p>World</p>", Default::default()).unwrap();
+    ///
+    /// let marked = dom.find_raw(|text| text.starts_with("[[draft]]")).collect::<Vec<_>>();
+    /// for handle in marked {
+    ///     let text = handle.get(dom.parser()).unwrap().inner_text(dom.parser()).replace("[[draft]] ", "");
+    ///     dom.set_text(handle, &text);
+    /// }
+    /// assert_eq!(dom.outer_html(), "<p>Hello</p><p>World</p>");
+    /// ```
+    pub fn find_raw<'b, F>(&'b self, pred: F) -> impl Iterator<Item = NodeHandle> + 'b
+    where
+        F: Fn(&str) -> bool + 'b,
+    {
+        self.text_nodes()
+            .filter_map(move |(handle, text)| pred(&text.as_utf8_str()).then_some(handle))
+    }
+
+    /// Returns the text a user would see when viewing this document
+    ///
+    /// The contents of metadata elements (such as `<script>` and `<title>`), the `<head>`, comments and elements
+    /// with a `hidden` attribute are skipped. Character references are decoded, block-level elements, table rows, options and `<br>`
+    /// start a new line, and table cells are separated by a tab. This is the text that [`VDom::text_fingerprint()`] hashes with the same options.
+    ///
+    /// # Example
+    /// ```
+    /// use tl::TextFingerprintOptions;
+    ///
+    /// let html = "<title>Page</title><h1>Tom &amp; Jerry</h1><p>A  <b>cat</b><span hidden>!</span></p>";
+    /// let dom = tl::parse(html, Default::default()).unwrap();
+    /// let options = TextFingerprintOptions {
+    ///     collapse_whitespace: true,
+    ///     ..Default::default()
+    /// };
+    /// assert_eq!(dom.visible_text(options), "Tom & Jerry\nA cat");
+    /// ```
+    pub fn visible_text(&self, options: TextFingerprintOptions) -> String {
+        let mut out = String::new();
+        // writing into a string never fails
+        let _ = text::write_visible_text(&self.parser, self.children(), options, &mut out);
+        out
+    }
+
+    /// Returns a hash of the visible text of this document, which can be used to detect changes to the text
+    /// while ignoring changes to the markup
+    ///
+    /// The text is the same as the one returned by [`VDom::visible_text()`], but it is hashed as it is extracted
+    /// instead of being collected into a string. The hash (64-bit FNV-1a) is stable across runs, but it is not
+    /// cryptographically secure.
+    ///
+    /// # Example
+    /// ```
+    /// use tl::TextFingerprintOptions;
+    ///
+    /// let a = tl::parse("<p>Hello <b>World</b></p>", Default::default()).unwrap();
+    /// let b = tl::parse("<div>hello\n  world</div>", Default::default()).unwrap();
+    /// let options = TextFingerprintOptions {
+    ///     fold_case: true,
+    ///     collapse_whitespace: true,
+    /// };
+    /// assert_eq!(a.text_fingerprint(options), b.text_fingerprint(options));
+    /// ```
+    pub fn text_fingerprint(&self, options: TextFingerprintOptions) -> u64 {
+        text::fingerprint(&self.parser, self.children(), options)
+    }
+
+    /// Tries to parse the query selector and returns an iterator over elements that match the given query selector.
+    ///
+    /// If the query cache is enabled (see `enable_query_cache()`), the matching elements are computed eagerly
+    /// and memoized for subsequent calls with the same selector.
+    ///
+    /// # Example
+    /// ```
+    /// let dom = tl::parse("<div><p class=\"foo\">bar</div>", tl::ParserOptions::default()).unwrap();
+    /// let handle = dom.query_selector("p.foo").and_then(|mut iter| iter.next()).unwrap();
+    /// let node = handle.get(dom.parser()).unwrap();
+    /// assert_eq!(node.inner_text(dom.parser()), "bar");
+    /// ```
+    pub fn query_selector<'b>(
+        &'b self,
+        selector: &'b str,
+    ) -> Option<QuerySelectorIterator<'a, 'b, Self>> {
+        self.query_selector_with_options(selector, SelectorOptions::default())
     }
-}
 
-impl<'a> VDom<'a> {
-    /// Returns a reference to the underlying parser
-    #[inline]
-    pub fn parser(&self) -> &Parser<'a> {
-        &self.parser
+    /// Like `query_selector()`, but returns an error describing what went wrong and where if the selector is invalid
+    ///
+    /// # Example
+    /// ```
+    /// use tl::queryselector::ParseErrorKind;
+    ///
+    /// let dom = tl::parse("<div><p>Hello</p></div>", Default::default()).unwrap();
+    /// assert_eq!(dom.query_selector_checked("div > p").unwrap().count(), 1);
+    ///
+    /// let error = dom.query_selector_checked("div >> p").unwrap_err();
+    /// assert_eq!(error.kind(), ParseErrorKind::UnexpectedCharacter);
+    /// assert_eq!(error.offset(), 5);
+    /// ```
+    pub fn query_selector_checked<'b>(
+        &'b self,
+        selector: &'b str,
+    ) -> Result<QuerySelectorIterator<'a, 'b, Self>, queryselector::ParseError> {
+        let parsed = crate::parse_query_selector_checked(selector)?;
+        Ok(self.query_parsed(selector, parsed, SelectorOptions::default()))
     }
 
-    /// Returns a mutable reference to the underlying parser
-    #[inline]
-    pub fn parser_mut(&mut self) -> &mut Parser<'a> {
-        &mut self.parser
+    /// Like `query_selector()`, but matches elements using the given options
+    ///
+    /// See [`SelectorOptions`] for an example.
+    pub fn query_selector_with_options<'b>(
+        &'b self,
+        selector: &'b str,
+        options: SelectorOptions,
+    ) -> Option<QuerySelectorIterator<'a, 'b, Self>> {
+        let parsed = crate::parse_query_selector(selector)?;
+        Some(self.query_parsed(selector, parsed, options))
     }
 
-    /// Finds an element by its `id` attribute.
-    pub fn get_element_by_id<'b, S>(&'b self, id: S) -> Option<NodeHandle>
-    where
-        S: Into<Bytes<'a>>,
-    {
-        let bytes: Bytes = id.into();
-        let parser = self.parser();
+    /// Like `query_selector()`, but entries of a selector list that cannot be parsed are skipped instead of rejecting the whole list
+    ///
+    /// Returns an iterator over the elements that match any of the valid entries (which is empty if there are none),
+    /// and the text of the skipped entries. See [`crate::parse_query_selector_forgiving()`].
+    ///
+    /// # Example
+    /// ```
+    /// let dom = tl::parse("<div></div><input><p class=x></p>", Default::default()).unwrap();
+    ///
+    /// assert!(dom.query_selector("div, input:focus-visible, .x").is_none());
+    ///
+    /// let (matches, skipped) = dom.query_selector_forgiving("div, input:focus-visible, .x");
+    /// assert_eq!(matches.count(), 2);
+    /// assert_eq!(skipped, ["input:focus-visible"]);
+    /// ```
+    pub fn query_selector_forgiving<'b>(
+        &'b self,
+        selector: &'b str,
+    ) -> (QuerySelectorIterator<'a, 'b, Self>, Vec<&'b str>) {
+        let (parsed, skipped) = crate::parse_query_selector_forgiving(selector);
 
-        if parser.options.is_tracking_ids() {
-            parser.ids.get(&bytes).copied()
-        } else {
-            self.nodes()
-                .iter()
-                .enumerate()
-                .find(|(_, node)| {
-                    node.as_tag().map_or(false, |tag| {
-                        tag._attributes.id.as_ref().map_or(false, |x| x.eq(&bytes))
-                    })
-                })
-                .map(|(id, _)| NodeHandle::new(id as InnerNodeHandle))
-        }
+        let iter = match parsed {
+            Some(parsed) => self.query_parsed(selector, parsed, SelectorOptions::default()),
+            None => QuerySelectorIterator::with_ranges(
+                Cow::Owned(queryselector::Selector::All),
+                self.parser(),
+                self,
+                Vec::new(),
+            ),
+        };
+
+        (iter, skipped)
     }
 
-    /// Returns a list of elements that match a given class name.
-    pub fn get_elements_by_class_name<'b>(
+    /// Evaluates a parsed selector, using the query cache if it is enabled
+    fn query_parsed<'b>(
         &'b self,
-        id: &'b str,
-    ) -> Box<dyn Iterator<Item = NodeHandle> + 'b> {
-        let parser = self.parser();
-
-        if parser.options.is_tracking_classes() {
-            parser
-                .classes
-                .get(&Bytes::from(id.as_bytes()))
-                .map(|x| Box::new(x.iter().cloned()) as Box<dyn Iterator<Item = NodeHandle>>)
-                .unwrap_or_else(|| Box::new(std::iter::empty()))
-        } else {
-            let member = id;
-
-            let iter = self
-                .nodes()
-                .iter()
-                .enumerate()
-                .filter_map(move |(id, node)| {
-                    node.as_tag().and_then(|tag| {
-                        tag._attributes
-                            .is_class_member(member)
-                            .then(|| NodeHandle::new(id as InnerNodeHandle))
-                    })
-                });
+        selector: &'b str,
+        parsed: queryselector::Selector<'b>,
+        options: SelectorOptions,
+    ) -> QuerySelectorIterator<'a, 'b, Self> {
+        if let Some(cache) = &self.query_cache {
+            let handles = cache.get_or_insert_with(selector, options, || {
+                self.evaluate_query(Cow::Borrowed(&parsed), options)
+                    .collect()
+            });
 
-            Box::new(iter)
+            return QuerySelectorIterator::from_cached(
+                Cow::Owned(parsed),
+                self.parser(),
+                self,
+                handles,
+            );
         }
-    }
 
-    /// Returns a slice of *all* the elements in the HTML document
-    ///
-    /// The difference between `children()` and `nodes()` is that children only returns the immediate children of the root node,
-    /// while `nodes()` returns all nodes, including nested tags.
-    ///
-    /// # Order
-    /// The order of the returned nodes is the same as the order of the nodes in the HTML document.
-    pub fn nodes(&self) -> &[Node<'a>] {
-        &self.parser.tags
+        self.evaluate_query(Cow::Owned(parsed), options)
     }
 
-    /// Returns a mutable slice of *all* the elements in the HTML document
+    /// Like `query_selector()`, but also accepts the custom pseudo-classes registered in the given context
     ///
-    /// The difference between `children()` and `nodes()` is that children only returns the immediate children of the root node,
-    /// while `nodes()` returns all nodes, including nested tags.
-    pub fn nodes_mut(&mut self) -> &mut [Node<'a>] {
-        &mut self.parser.tags
-    }
-
-    /// Returns the topmost subnodes ("children") of this DOM
-    pub fn children(&self) -> &[NodeHandle] {
-        &self.parser.ast
-    }
+    /// Returns `None` if the selector is invalid, which includes pseudo-classes that are neither built in nor registered.
+    /// The query cache is not used, since the results depend on the predicates of the context.
+    /// See [`SelectorContext`](queryselector::SelectorContext) for an example.
+    pub fn query_selector_with_context<'b>(
+        &'b self,
+        selector: &'b str,
+        context: &'b queryselector::SelectorContext,
+    ) -> Option<QuerySelectorIterator<'a, 'b, Self>> {
+        let parsed = queryselector::Parser::new(selector.as_bytes())
+            .with_context(context)
+            .selector()
+            .ok()?;
 
-    /// Returns a mutable reference to the topmost subnodes ("children") of this DOM
-    pub fn children_mut(&mut self) -> &mut [NodeHandle] {
-        &mut self.parser.ast
+        Some(
+            self.evaluate_query(Cow::Owned(parsed), SelectorOptions::default())
+                .with_context(context),
+        )
     }
 
-    /// Returns the HTML version.
-    /// This is determined by the `<!DOCTYPE>` tag
-    pub fn version(&self) -> Option<HTMLVersion> {
-        self.parser.version
+    /// Returns an iterator over the elements that match a selector compiled with `Selector::parse()`
+    ///
+    /// Unlike `query_selector()`, the selector is not parsed again, and the query cache is not used.
+    ///
+    /// # Example
+    /// ```
+    /// use tl::queryselector::Selector;
+    ///
+    /// let selector = Selector::parse("p.foo").unwrap();
+    /// let dom = tl::parse(r#"<p class="foo">a</p><p>b</p>"#, Default::default()).unwrap();
+    ///
+    /// assert_eq!(dom.query_selector_with(&selector).count(), 1);
+    /// ```
+    pub fn query_selector_with<'b>(
+        &'b self,
+        selector: &'b queryselector::OwnedSelector,
+    ) -> QuerySelectorIterator<'a, 'b, Self> {
+        self.evaluate_query(
+            Cow::Borrowed(selector.selector()),
+            SelectorOptions::default(),
+        )
     }
 
-    /// Returns the contained markup of all of the elements in this DOM.
+    /// Returns the `n`th (zero-based) element in document order that matches the given query selector
     ///
-    /// Equivalent to [Element#outerHTML](https://developer.mozilla.org/en-US/docs/Web/API/Element/outerHTML) in browsers)
+    /// This stops evaluating the selector once the element is found.
+    /// Returns `None` if the selector is invalid or if there are not more than `n` matches.
     ///
     /// # Example
     /// ```
-    /// let html = r#"<div><p href="/about" id="find-me">Hello world</p></div>"#;
-    /// let mut dom = tl::parse(html, Default::default()).unwrap();
+    /// let dom = tl::parse("<p>a</p><p>b</p><p>c</p>", Default::default()).unwrap();
+    /// let parser = dom.parser();
     ///
-    /// let element = dom.get_element_by_id("find-me")
-    ///     .unwrap()
-    ///     .get_mut(dom.parser_mut())
-    ///     .unwrap()
-    ///     .as_tag_mut()
-    ///     .unwrap();
+    /// let third = dom.query_selector_nth("p", 2).unwrap();
+    /// assert_eq!(third.get(parser).unwrap().inner_text(parser), "c");
+    /// assert_eq!(dom.query_selector_nth("p", 3), None);
+    /// ```
+    pub fn query_selector_nth(&self, selector: &str, n: usize) -> Option<NodeHandle> {
+        self.query_selector(selector)?.nth(n)
+    }
+
+    /// Returns the first element in document order that matches the given query selector
     ///
-    /// element.attributes_mut().get_mut("href").flatten().unwrap().set("/");
+    /// This stops evaluating the selector once the first match is found, and bypasses the query cache.
+    /// Returns `None` if the selector is invalid or if nothing matches.
     ///
-    /// assert_eq!(dom.outer_html(), r#"<div><p href="/" id="find-me">Hello world</p></div>"#);
+    /// # Example
     /// ```
-    pub fn outer_html(&self) -> String {
-        let mut inner_html = String::with_capacity(self.parser.stream.len());
-
-        for node in self.children() {
-            let node = node.get(&self.parser).unwrap();
-            inner_html.push_str(&node.outer_html(&self.parser));
-        }
+    /// let dom = tl::parse("<p>a</p><div>b</div>", Default::default()).unwrap();
+    /// let parser = dom.parser();
+    ///
+    /// let first = dom.query_selector_first("div, p").unwrap();
+    /// assert_eq!(first.get(parser).unwrap().inner_text(parser), "a");
+    /// assert_eq!(dom.query_selector_first("span"), None);
+    /// ```
+    pub fn query_selector_first(&self, selector: &str) -> Option<NodeHandle> {
+        let selector = crate::parse_query_selector(selector)?;
+        self.evaluate_query(Cow::Owned(selector), SelectorOptions::default())
+            .next()
+    }
 
-        inner_html
+    /// Returns the number of elements that match the given query selector, or `None` if the selector is invalid
+    ///
+    /// # Example
+    /// ```
+    /// let dom = tl::parse("<p>a</p><div><p>b</p></div>", Default::default()).unwrap();
+    /// assert_eq!(dom.query_selector_count("p"), Some(2));
+    /// assert_eq!(dom.query_selector_count("div > p"), Some(1));
+    /// ```
+    pub fn query_selector_count(&self, selector: &str) -> Option<usize> {
+        self.query_selector(selector).map(Iterator::count)
     }
 
-    /// Tries to parse the query selector and returns an iterator over elements that match the given query selector.
+    /// Returns an iterator over the given handles that refer to elements matching the query selector, in the order they were given
+    ///
+    /// Only the given nodes are tested, but combinators (e.g. `div > p`) are still resolved against their actual ancestors and siblings.
+    /// This allows refining the results of a previous query, or of any other pre-filtering, without evaluating the selector on the whole document.
+    /// Returns `None` if the selector is invalid.
     ///
     /// # Example
     /// ```
-    /// let dom = tl::parse("<div><p class=\"foo\">bar</div>", tl::ParserOptions::default()).unwrap();
-    /// let handle = dom.query_selector("p.foo").and_then(|mut iter| iter.next()).unwrap();
-    /// let node = handle.get(dom.parser()).unwrap();
-    /// assert_eq!(node.inner_text(dom.parser()), "bar");
+    /// let dom = tl::parse(r#"<ul class="menu"><li>a</li></ul><ol><li>b</li></ol>"#, Default::default()).unwrap();
+    /// let items = dom.query_selector("li").unwrap().collect::<Vec<_>>();
+    ///
+    /// let in_menu = dom.filter_handles(items, "ul.menu > li").unwrap().collect::<Vec<_>>();
+    /// assert_eq!(in_menu, dom.query_selector("ul.menu > li").unwrap().collect::<Vec<_>>());
     /// ```
-    pub fn query_selector<'b>(
+    pub fn filter_handles<'b, I>(
         &'b self,
+        handles: I,
         selector: &'b str,
-    ) -> Option<QuerySelectorIterator<'a, 'b, Self>> {
+    ) -> Option<impl Iterator<Item = NodeHandle> + 'b>
+    where
+        I: IntoIterator<Item = NodeHandle>,
+        I::IntoIter: 'b,
+    {
         let selector = crate::parse_query_selector(selector)?;
-        let iter = queryselector::QuerySelectorIterator::new(selector, self.parser(), self);
-        Some(iter)
+        let parser = self.parser();
+
+        Some(handles.into_iter().filter(move |handle| {
+            handle
+                .get(parser)
+                .is_some_and(|node| selector.matches(node, parser))
+        }))
+    }
+
+    /// Creates an iterator over the elements that match the given selector
+    ///
+    /// If ids or classes are tracked, the nodes to visit are seeded from the lookup tables: when every match
+    /// must have a specific id or class, only the elements with it are visited, and when every match must be
+    /// (inside) an element with a specific id or class, only the subtrees of those elements are visited.
+    /// If both apply, the one that visits fewer nodes is used.
+    fn evaluate_query<'b>(
+        &'b self,
+        selector: Cow<'b, queryselector::Selector<'b>>,
+        options: SelectorOptions,
+    ) -> QuerySelectorIterator<'a, 'b, Self> {
+        self.evaluate_query_in(selector, options, 0..self.parser.document_len())
+    }
+
+    /// Like `evaluate_query()`, but only visits the nodes at the given range of positions
+    pub(crate) fn evaluate_query_in<'b>(
+        &'b self,
+        selector: Cow<'b, queryselector::Selector<'b>>,
+        options: SelectorOptions,
+        bounds: Range<usize>,
+    ) -> QuerySelectorIterator<'a, 'b, Self> {
+        let parser = self.parser();
+
+        let subjects = selector
+            .subject_key()
+            .and_then(|key| self.lookup(key))
+            .map(|handles| self.candidate_ranges(handles, |position, _| position + 1));
+
+        let subtrees = selector
+            .root_key()
+            .and_then(|key| self.lookup(key))
+            .map(|handles| {
+                self.candidate_ranges(handles, |position, handle| {
+                    self.subtree_end(handle).unwrap_or(position + 1)
+                })
+            });
+
+        let ranges = subjects
+            .into_iter()
+            .chain(subtrees)
+            .map(|ranges| {
+                ranges
+                    .into_iter()
+                    .map(|range| range.start.max(bounds.start)..range.end.min(bounds.end))
+                    .filter(|range| !range.is_empty())
+                    .collect::<Vec<_>>()
+            })
+            .min_by_key(|ranges| ranges.iter().map(ExactSizeIterator::len).sum::<usize>())
+            .unwrap_or_else(|| vec![bounds]);
+
+        QuerySelectorIterator::with_ranges(selector, parser, self, ranges).with_options(options)
+    }
+
+    /// Returns the elements with the given id or class from the lookup tables, or `None` if it is not tracked
+    pub(crate) fn lookup<'s>(&'s self, key: LookupKey<'s>) -> Option<&'s [NodeHandle]> {
+        let parser = self.parser();
+
+        let handles = match key {
+            LookupKey::Id(id) if parser.options.is_tracking_ids() => {
+                parser.ids.get(&Bytes::from(id)).map(|x| x.as_slice())
+            }
+            LookupKey::Class(class) if parser.options.is_tracking_classes() => parser
+                .classes
+                .get(&Bytes::from(class))
+                .map(|x| x.as_slice()),
+            _ => return None,
+        };
+
+        Some(handles.unwrap_or_default())
+    }
+
+    /// Returns the sorted, non-overlapping ranges of positions that start at the given elements and end at `end`,
+    /// which is given the position of an element and its handle
+    ///
+    /// Elements that are not attached to the document are skipped.
+    fn candidate_ranges<F>(&self, handles: &[NodeHandle], end: F) -> Vec<Range<usize>>
+    where
+        F: Fn(usize, NodeHandle) -> usize,
+    {
+        let mut ranges = handles
+            .iter()
+            .filter_map(|&handle| {
+                let start = self
+                    .parser
+                    .position_of(handle)
+                    .filter(|&position| position < self.parser.document_len())?;
+
+                Some(start..end(start, handle))
+            })
+            .collect::<Vec<_>>();
+
+        ranges.sort_unstable_by_key(|range| range.start);
+
+        // subtrees of nested elements overlap, so they are merged
+        let mut merged: Vec<Range<usize>> = Vec::with_capacity(ranges.len());
+        for range in ranges {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+        merged
     }
 }
 
+/// Checks whether the given offset is a UTF-8 character boundary of `text`, or its end
+fn is_char_boundary(text: &[u8], at: usize) -> bool {
+    match text.get(at) {
+        // continuation bytes have the form 0b10xxxxxx
+        Some(&byte) => (byte as i8) >= -0x40,
+        None => at == text.len(),
+    }
+}
+
+/// Returns the rank of the given heading element (1 for `<h1>` to 6 for `<h6>`), or `None` if it is not a heading
+pub(crate) fn heading_level(node: &Node) -> Option<u8> {
+    tag_info::heading_level(node.as_tag()?.name().as_bytes())
+}
+
+/// Checks whether the given node is a tag with the given (lowercase) name, compared case-insensitively
+fn is_tag_named(node: Option<&Node>, name: &[u8]) -> bool {
+    node.and_then(Node::as_tag)
+        .is_some_and(|tag| tag.name().as_bytes().eq_ignore_ascii_case(name))
+}
+
+/// Checks whether the given text contains `</` followed by the given name, compared ASCII case-insensitively
+fn contains_end_tag(text: &[u8], name: &[u8]) -> bool {
+    text.windows(name.len() + 2)
+        .any(|window| window.starts_with(b"</") && window[2..].eq_ignore_ascii_case(name))
+}
+
 /// A RAII guarded version of VDom
 ///
 /// The input string is freed once this struct goes out of scope.
@@ -193,25 +2370,42 @@ pub struct VDomGuard {
     _phantom: PhantomData<&'static str>,
 }
 
+// SAFETY: the raw pointers in the DOM refer either to owned data or to the input string, which is owned by the guard
+// and never mutated. The query cache is synchronized with a mutex, and the guard only hands out shared references,
+// so no (possibly non-`Send`) mutation observer can be set.
 unsafe impl Send for VDomGuard {}
 unsafe impl Sync for VDomGuard {}
 
 impl VDomGuard {
     /// Parses the input string
     pub(crate) fn parse(input: String, options: ParserOptions) -> Result<VDomGuard, ParseError> {
+        Self::with_parser(input, options, |parser, _| parser.parse())
+    }
+
+    /// Creates a parser for the input string and lets `build` fill it, e.g. by parsing the input
+    ///
+    /// `build` receives the input string, which the nodes may borrow from.
+    pub(crate) fn with_parser<F, E>(
+        input: String,
+        options: ParserOptions,
+        build: F,
+    ) -> Result<VDomGuard, E>
+    where
+        F: FnOnce(&mut Parser<'static>, &'static str) -> Result<(), E>,
+    {
         let input = RawString::new(input);
 
         let ptr = input.as_ptr();
 
         let input_ref: &'static str = unsafe { &*ptr };
 
-        // Parsing will either:
+        // Building will either:
         // a) succeed, and we return a VDom instance
         //    that, when dropped, will free the input string
-        // b) fail, and we return a ParseError
+        // b) fail, and we return the error
         //    and `RawString`s destructor will run and deallocate the string properly
         let mut parser = Parser::new(input_ref, options);
-        parser.parse()?;
+        build(&mut parser, input_ref)?;
 
         Ok(Self {
             _s: input,
@@ -237,6 +2431,115 @@ impl VDomGuard {
     }
 }
 
+/// A read-only, owned DOM that can be shared between threads and is cheap to clone
+///
+/// Clones refer to the same document and input string, which are freed once the last clone goes out of scope.
+/// This is obtained by calling `parse_shared()` or by converting a [`VDomGuard`].
+/// The methods of [`VDom`] that do not mutate the document are available through `get_ref()`,
+/// and the common ones are provided directly, so that they can be called without a parser.
+///
+/// # Example
+/// ```
+/// let dom = tl::parse_shared("<p id=greeting>Hello</p>".into(), Default::default()).unwrap();
+///
+/// let worker = {
+///     let dom = dom.clone();
+///     std::thread::spawn(move || dom.query_selector_first("p").and_then(|p| dom.inner_text_of(p)))
+/// };
+///
+/// assert_eq!(worker.join().unwrap().as_deref(), Some("Hello"));
+/// assert!(dom.get_element_by_id("greeting").is_some());
+/// ```
+#[derive(Debug, Clone)]
+pub struct VDomShared(Arc<VDomGuard>);
+
+impl VDomShared {
+    /// Returns a reference to the inner DOM.
+    ///
+    /// The lifetime of the returned `VDom` is bound to self so that elements cannot outlive this `VDomShared` struct.
+    pub fn get_ref(&self) -> &VDom<'_> {
+        self.0.get_ref()
+    }
+
+    /// Returns a reference to the underlying parser
+    pub fn parser(&self) -> &Parser<'_> {
+        self.get_ref().parser()
+    }
+
+    /// Returns a slice of *all* the elements in the HTML document, see `VDom::nodes()`
+    pub fn nodes(&self) -> &[Node<'_>] {
+        self.get_ref().nodes()
+    }
+
+    /// Returns the topmost subnodes ("children") of this DOM, see `VDom::children()`
+    pub fn children(&self) -> &[NodeHandle] {
+        self.get_ref().children()
+    }
+
+    /// Finds an element by its `id` attribute, see `VDom::get_element_by_id()`
+    pub fn get_element_by_id(&self, id: &str) -> Option<NodeHandle> {
+        self.get_ref().get_element_by_id(id)
+    }
+
+    /// Returns a list of elements that match a given class name, see `VDom::get_elements_by_class_name()`
+    pub fn get_elements_by_class_name<'b>(
+        &'b self,
+        class: &'b str,
+    ) -> Box<dyn Iterator<Item = NodeHandle> + 'b> {
+        self.get_ref().get_elements_by_class_name(class)
+    }
+
+    /// Returns an iterator over the elements whose `name` attribute has the given value, see `VDom::get_elements_by_name()`
+    pub fn get_elements_by_name<'b>(
+        &'b self,
+        name: &'b str,
+    ) -> Box<dyn Iterator<Item = NodeHandle> + 'b> {
+        self.get_ref().get_elements_by_name(name)
+    }
+
+    /// Tries to parse the query selector and returns an iterator over elements that match the given query selector,
+    /// see `VDom::query_selector()`
+    pub fn query_selector<'b>(
+        &'b self,
+        selector: &'b str,
+    ) -> Option<QuerySelectorIterator<'b, 'b, VDom<'b>>> {
+        self.get_ref().query_selector(selector)
+    }
+
+    /// Returns the first element that matches the given query selector, see `VDom::query_selector_first()`
+    pub fn query_selector_first(&self, selector: &str) -> Option<NodeHandle> {
+        self.get_ref().query_selector_first(selector)
+    }
+
+    /// Returns the contained markup of all of the elements in this DOM, see `VDom::outer_html()`
+    pub fn outer_html(&self) -> String {
+        self.get_ref().outer_html()
+    }
+
+    /// Returns the text of the document, excluding any markup, see `VDom::inner_text()`
+    pub fn inner_text(&self) -> Cow<'_, str> {
+        self.get_ref().inner_text()
+    }
+
+    /// Returns the markup of the given node and its subtree, or `None` if the handle does not resolve
+    pub fn outer_html_of(&self, handle: NodeHandle) -> Option<String> {
+        let parser = self.parser();
+        Some(handle.get(parser)?.outer_html(parser).into_owned())
+    }
+
+    /// Returns the text of the given node and its subtree, or `None` if the handle does not resolve
+    pub fn inner_text_of(&self, handle: NodeHandle) -> Option<String> {
+        let parser = self.parser();
+        Some(handle.get(parser)?.inner_text(parser).into_owned())
+    }
+}
+
+impl From<VDomGuard> for VDomShared {
+    fn from(guard: VDomGuard) -> Self {
+        Self(Arc::new(guard))
+    }
+}
+
 #[derive(Debug)]
 struct RawString(*mut str);
 
@@ -258,3 +2561,22 @@ impl Drop for RawString {
         };
     }
 }
+
+/// Instrumentation for counting the lookups that scan all nodes instead of using a lookup table on the current thread
+#[cfg(test)]
+pub(crate) mod scans {
+    use std::cell::Cell;
+
+    thread_local! {
+        static SCANS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    pub fn record() {
+        SCANS.with(|v| v.set(v.get() + 1));
+    }
+
+    /// Returns the number of scanning lookups since the last call and resets the counter
+    pub fn take() -> usize {
+        SCANS.with(|v| v.replace(0))
+    }
+}
@@ -0,0 +1,151 @@
+use crate::{Bytes, HTMLTag, Node, NodeHandle, Parser, VDom};
+
+/// Tells a walk started by `VDom::walk()` or `HTMLTag::walk()` how to continue after a callback of a [`NodeVisitor`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum WalkControl {
+    /// Continues with the next node
+    #[default]
+    Continue,
+    /// Skips the subtree of the element that was just entered, and continues with `leave_tag()` for it
+    ///
+    /// Returned from any other callback, this is the same as `Continue`.
+    SkipChildren,
+    /// Stops the walk immediately, without leaving the elements that are still open
+    Break,
+}
+
+/// Callbacks for the nodes of a document, which are invoked in document order by `VDom::walk()` and `HTMLTag::walk()`
+///
+/// Every element that is entered is left again after its subtree was visited, unless the walk is stopped by returning
+/// [`WalkControl::Break`]. Void elements are left right after they are entered. All callbacks do nothing by default.
+///
+/// # Example
+/// ```
+/// use tl::{HTMLTag, NodeHandle, NodeVisitor, WalkControl};
+///
+/// /// Converts emphasis to Markdown and drops all other markup
+/// struct Markdown(String);
+///
+/// impl NodeVisitor for Markdown {
+///     fn enter_tag(&mut self, tag: &HTMLTag<'_>, _: NodeHandle) -> WalkControl {
+///         match tag.name().as_bytes() {
+///             b"em" => self.0.push('_'),
+///             b"script" => return WalkControl::SkipChildren,
+///             _ => {}
+///         }
+///         WalkControl::Continue
+///     }
+///
+///     fn leave_tag(&mut self, tag: &HTMLTag<'_>, _: NodeHandle) -> WalkControl {
+///         if tag.name() == "em" {
+///             self.0.push('_');
+///         }
+///         WalkControl::Continue
+///     }
+///
+///     fn visit_raw(&mut self, text: &tl::Bytes<'_>, _: NodeHandle) -> WalkControl {
+///         self.0.push_str(&text.as_utf8_str());
+///         WalkControl::Continue
+///     }
+/// }
+///
+/// let dom = tl::parse("<p>A <em>very</em> nice<script>x()</script> day</p>", Default::default()).unwrap();
+/// let mut markdown = Markdown(String::new());
+/// dom.walk(&mut markdown);
+/// assert_eq!(markdown.0, "A _very_ nice day");
+/// ```
+pub trait NodeVisitor {
+    /// Called when an element is entered, before its subtree is visited
+    fn enter_tag(&mut self, tag: &HTMLTag<'_>, handle: NodeHandle) -> WalkControl {
+        let _ = (tag, handle);
+        WalkControl::Continue
+    }
+
+    /// Called when an element is left, after its subtree was visited (or skipped)
+    fn leave_tag(&mut self, tag: &HTMLTag<'_>, handle: NodeHandle) -> WalkControl {
+        let _ = (tag, handle);
+        WalkControl::Continue
+    }
+
+    /// Called for a text node
+    fn visit_raw(&mut self, text: &Bytes<'_>, handle: NodeHandle) -> WalkControl {
+        let _ = (text, handle);
+        WalkControl::Continue
+    }
+
+    /// Called for a comment, or for another markup declaration such as a misplaced doctype
+    fn visit_comment(&mut self, comment: &Bytes<'_>, handle: NodeHandle) -> WalkControl {
+        let _ = (comment, handle);
+        WalkControl::Continue
+    }
+}
+
+/// A pending step of [`walk()`]
+enum WalkStep<'p, 'a> {
+    /// Visits a node, and enters it if it is an element
+    Visit(NodeHandle),
+    /// Leaves an element that was entered before
+    Leave(&'p HTMLTag<'a>, NodeHandle),
+}
+
+/// Visits the given nodes and their subtrees in document order
+///
+/// This uses an explicit stack instead of recursion, so that walking deeply nested documents cannot overflow the stack.
+pub(crate) fn walk<V: NodeVisitor + ?Sized>(
+    parser: &Parser<'_>,
+    nodes: &[NodeHandle],
+    visitor: &mut V,
+) {
+    let mut stack = nodes
+        .iter()
+        .rev()
+        .map(|&handle| WalkStep::Visit(handle))
+        .collect::<Vec<_>>();
+
+    while let Some(step) = stack.pop() {
+        let control = match step {
+            WalkStep::Visit(handle) => match handle.get(parser) {
+                Some(Node::Tag(tag)) => {
+                    let control = visitor.enter_tag(tag, handle);
+                    stack.push(WalkStep::Leave(tag, handle));
+
+                    if control == WalkControl::Continue {
+                        let children = tag._children.as_slice().iter().rev();
+                        stack.extend(children.map(|&child| WalkStep::Visit(child)));
+                    }
+                    control
+                }
+                Some(Node::Raw(text)) => visitor.visit_raw(text, handle),
+                Some(Node::Comment(comment)) => visitor.visit_comment(comment, handle),
+                None => WalkControl::Continue,
+            },
+            WalkStep::Leave(tag, handle) => visitor.leave_tag(tag, handle),
+        };
+
+        if control == WalkControl::Break {
+            return;
+        }
+    }
+}
+
+impl<'a> VDom<'a> {
+    /// Walks over all nodes of this document in document order, invoking the callbacks of the visitor
+    ///
+    /// See [`NodeVisitor`] for the order of the callbacks. Unlike recursing over `children()`,
+    /// this does not overflow the stack for deeply nested documents.
+    pub fn walk<V: NodeVisitor + ?Sized>(&self, visitor: &mut V) {
+        walk(self.parser(), self.children(), visitor);
+    }
+}
+
+impl<'a> HTMLTag<'a> {
+    /// Walks over this element and its subtree in document order, invoking the callbacks of the visitor
+    ///
+    /// This is like `VDom::walk()`, except that only this element is entered at the top level.
+    /// Nothing is visited if this tag does not belong to the given parser.
+    pub fn walk<V: NodeVisitor + ?Sized>(&self, parser: &Parser<'a>, visitor: &mut V) {
+        if let Some(handle) = self.handle(parser) {
+            walk(parser, &[handle], visitor);
+        }
+    }
+}
@@ -0,0 +1,103 @@
+//! Tests that count allocations, which need a global allocator and therefore a test binary of their own
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use tl::{Attributes, Batch, ParserOptions};
+
+thread_local! {
+    static COUNT: Cell<usize> = const { Cell::new(0) };
+}
+
+/// A global allocator that counts the allocations made on the current thread
+struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = COUNT.try_with(|c| c.set(c.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let _ = COUNT.try_with(|c| c.set(c.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Calls `f` and returns its result along with the number of allocations it made
+fn count<R>(f: impl FnOnce() -> R) -> (R, usize) {
+    let before = COUNT.with(Cell::get);
+    let result = f();
+    (result, COUNT.with(Cell::get) - before)
+}
+
+#[test]
+fn attributes_bulk_construction() {
+    let keys = (0..30).map(|i| format!("data-{i}")).collect::<Vec<_>>();
+    let tag = |keys: &[String]| {
+        let attributes = keys
+            .iter()
+            .map(|key| format!(r#"{key}="1""#))
+            .collect::<Vec<_>>();
+        format!("<path {}/>", attributes.join(" "))
+    };
+    let one = tag(&keys[..1]);
+    let all = tag(&keys);
+
+    // building all attributes of a tag at once allocates exactly once, so once the scratch buffers
+    // of the parser have grown, parsing 30 attributes allocates only once more than parsing one
+    // (reusing the attribute buffer relies on an in-place collect, which std does in practice but does not guarantee)
+    let mut batch = Batch::new(ParserOptions::default());
+    batch.parse(&all).unwrap();
+    let (_, one_count) = count(|| batch.parse(&one).unwrap());
+    let (dom, all_count) = count(|| batch.parse(&all).unwrap());
+    assert_eq!(all_count, one_count + 1);
+
+    let attributes = dom.nodes()[0].as_tag().unwrap().attributes();
+    assert_eq!(attributes.len(), 30);
+    assert_eq!(attributes.get("data-29"), Some(Some(&"1".into())));
+    assert!(attributes.unstable_raw().capacity() >= 30);
+
+    // whereas inserting them one by one has to grow the map repeatedly
+    let (_, count) = count(|| {
+        let mut attributes = Attributes::new();
+        for key in &keys {
+            attributes.insert(key.as_str(), Some("value"));
+        }
+        attributes
+    });
+    assert!(count > 1);
+}
+
+#[test]
+fn attributes_reserve_and_shrink() {
+    let keys = (0..10).map(|i| format!("b{i}")).collect::<Vec<_>>();
+    let mut dom = tl::parse(r#"<div id="x" a="1"></div>"#, ParserOptions::default()).unwrap();
+    let tag = dom.nodes_mut()[0].as_tag_mut().unwrap();
+    let attributes = tag.attributes_mut();
+
+    attributes.reserve(10);
+    assert!(attributes.unstable_raw().is_heap_allocated());
+    assert!(attributes.unstable_raw().capacity() >= 11);
+
+    let (_, count) = count(|| {
+        for key in &keys {
+            attributes.insert(key.as_str(), None::<&str>);
+        }
+    });
+    assert_eq!(count, 0);
+
+    for key in &keys {
+        attributes.remove(key.as_str());
+    }
+    attributes.shrink_to_fit();
+    assert!(!attributes.unstable_raw().is_heap_allocated());
+    assert_eq!(attributes.get("a"), Some(Some(&"1".into())));
+    assert_eq!(attributes.len(), 2);
+}
@@ -0,0 +1,52 @@
+use tl::prelude::*;
+
+#[test]
+fn parse_select_mutate_serialize() {
+    let mut dom: VDom = parse(
+        r#"<ul id="list"><li class="item">a</li><li>b</li><li class="item">c</li></ul>"#,
+        ParserOptions::default().track_ids(),
+    )
+    .unwrap();
+
+    let selector: Selector = parse_query_selector("li.item").unwrap();
+    let items: Vec<NodeHandle> = dom
+        .query_selector_with_options("#list > li.item", SelectorOptions::default())
+        .unwrap()
+        .collect();
+    assert_eq!(items.len(), 2);
+
+    let parser: &Parser = dom.parser();
+    assert!(items
+        .iter()
+        .all(|item| selector.matches(item.get(parser).unwrap(), parser)));
+
+    let list: &HTMLTag = dom
+        .get_element_by_id("list")
+        .and_then(|handle| handle.get(parser))
+        .and_then(Node::as_tag)
+        .unwrap();
+    let children: Children = list.children();
+    assert_eq!(children.top().len(), 3);
+    assert_eq!(dom.version(), None::<HTMLVersion>);
+
+    let parser = dom.parser_mut();
+    for item in &items {
+        let tag = item.get_mut(parser).and_then(Node::as_tag_mut).unwrap();
+        let attributes: &mut Attributes = tag.attributes_mut();
+        attributes.insert("data-selected", Some("true"));
+
+        let text = tag.children_mut().top_mut()[0];
+        let text: &mut Bytes = text.get_mut(parser).and_then(Node::as_raw_mut).unwrap();
+        text.set("selected").unwrap();
+    }
+
+    let items = dom.query_selector("[data-selected=true]").unwrap().count();
+    assert_eq!(items, 2);
+
+    let html = dom.outer_html();
+    assert_eq!(html.matches("selected</li>").count(), 2);
+    assert!(html.contains("<li>b</li>"));
+
+    let error: ParseError = ParseError::InvalidLength;
+    assert!(!error.to_string().is_empty());
+}